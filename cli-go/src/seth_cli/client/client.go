@@ -171,7 +171,7 @@ func (c *Client) CreateExternalAccount(
 	encoder := NewEncoder(sender, TransactionParams{
 		FamilyName:    FAMILY_NAME,
 		FamilyVersion: FAMILY_VERSION,
-		Inputs:        addresses,
+		Inputs:        append(addresses, SETTINGS_PREFIX),
 		Outputs:       addresses,
 	})
 
@@ -229,7 +229,7 @@ func (c *Client) CreateContractAccount(
 	encoder := NewEncoder(priv, TransactionParams{
 		FamilyName:    FAMILY_NAME,
 		FamilyVersion: FAMILY_VERSION,
-		Inputs:        addresses,
+		Inputs:        append(addresses, SETTINGS_PREFIX),
 		Outputs:       addresses,
 	})
 
@@ -303,7 +303,7 @@ func (c *Client) MessageCall(
 	encoder := NewEncoder(priv, TransactionParams{
 		FamilyName:    FAMILY_NAME,
 		FamilyVersion: FAMILY_VERSION,
-		Inputs:        addresses,
+		Inputs:        append(addresses, SETTINGS_PREFIX),
 		Outputs:       addresses,
 	})
 
@@ -370,7 +370,7 @@ func (c *Client) SetPermissions(priv, to []byte, permissions *EvmPermissions, no
 	encoder := NewEncoder(priv, TransactionParams{
 		FamilyName:    FAMILY_NAME,
 		FamilyVersion: FAMILY_VERSION,
-		Inputs:        addresses,
+		Inputs:        append(addresses, SETTINGS_PREFIX),
 		Outputs:       addresses,
 	})
 
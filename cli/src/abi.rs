@@ -0,0 +1,463 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Encoding of contract calls and constructor arguments given on the command line,
+//! and decoding of what calls return and of the events contracts log, from a
+//! contract's JSON ABI
+
+use failure::{err_msg, Error};
+use serde_json::{self, Map, Value};
+use std::fs::File;
+use tiny_keccak::keccak256;
+use types::abi::{self, canonical_list, decode_params, ParamType};
+pub use types::abi::{from_hex, to_hex};
+
+/// Parses an argument given on the command line. Arrays and tuples are given as JSON,
+/// such as `[1,2]` or `["0xabc..",true]`, and everything else as is.
+fn parse_arg(kind: &ParamType, arg: &str) -> Result<Value, Error> {
+    match *kind {
+        ParamType::Array(_) | ParamType::FixedArray(..) | ParamType::Tuple(_) => {
+            serde_json::from_str(arg)
+                .map_err(|error| format_err!("Invalid value for `{}`: {}", kind.canonical(), error))
+        }
+        ParamType::Bool => match arg {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            _ => Err(format_err!("Invalid value for `bool`: {}", arg)),
+        },
+        _ => Ok(Value::from(arg)),
+    }
+}
+
+/// A function of a contract
+#[derive(Clone, Debug)]
+pub struct Function {
+    pub name: String,
+    pub inputs: Vec<ParamType>,
+    pub outputs: Vec<ParamType>,
+}
+
+impl Function {
+    pub fn signature(&self) -> String {
+        format!("{}({})", self.name, canonical_list(&self.inputs))
+    }
+
+    /// Returns the input data of a call to the function with arguments given on the
+    /// command line
+    pub fn encode_call(&self, args: &[&str]) -> Result<Vec<u8>, Error> {
+        let hash = keccak256(self.signature().as_bytes());
+        let mut data = hash[..4].to_vec();
+        data.extend(encode_args(&self.inputs, args)?);
+        Ok(data)
+    }
+
+    /// Decodes the return value of a call to the function into a list of its outputs
+    pub fn decode_result(&self, data: &[u8]) -> Result<Value, Error> {
+        decode_params(&self.outputs, data)
+            .map(Value::Array)
+            .map_err(err_msg)
+    }
+}
+
+/// An event of a contract
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub name: String,
+    /// The name and type of each parameter, and whether it is indexed
+    pub inputs: Vec<(String, ParamType, bool)>,
+    /// Whether the event is logged without its signature as the first topic
+    pub anonymous: bool,
+}
+
+impl Event {
+    pub fn signature(&self) -> String {
+        let types: Vec<ParamType> = self
+            .inputs
+            .iter()
+            .map(|(_, kind, _)| kind.clone())
+            .collect();
+        format!("{}({})", self.name, canonical_list(&types))
+    }
+
+    /// The first topic of the logs of the event, unless it is anonymous
+    pub fn topic(&self) -> String {
+        to_hex(&keccak256(self.signature().as_bytes()))
+    }
+
+    /// Decodes the parameters of a log of the event into an object keyed by their
+    /// names, or by their positions for parameters without names. Indexed strings,
+    /// bytes, arrays and tuples are only logged as their hash, which is given as is.
+    pub fn decode_log(&self, topics: &[String], data: &[u8]) -> Result<Value, Error> {
+        let mut topics = topics.iter().skip(if self.anonymous { 0 } else { 1 });
+        let unindexed: Vec<ParamType> = self
+            .inputs
+            .iter()
+            .filter(|(_, _, indexed)| !indexed)
+            .map(|(_, kind, _)| kind.clone())
+            .collect();
+        let mut unindexed = decode_params(&unindexed, data)
+            .map_err(err_msg)?
+            .into_iter();
+
+        let mut args = Map::new();
+        for (position, (name, kind, indexed)) in self.inputs.iter().enumerate() {
+            let value = if *indexed {
+                let topic = topics
+                    .next()
+                    .ok_or_else(|| format_err!("The log has too few topics for {}", self.name))?;
+                if kind.is_dynamic() || kind.head_size() != 32 {
+                    Value::String(topic.clone())
+                } else {
+                    let word =
+                        from_hex(topic).ok_or_else(|| format_err!("Got a topic that isn't hex"))?;
+                    abi::decode(kind, &word).map_err(err_msg)?
+                }
+            } else {
+                unindexed.next().ok_or_else(|| err_msg(abi::too_short()))?
+            };
+            let name = if name.is_empty() {
+                position.to_string()
+            } else {
+                name.clone()
+            };
+            args.insert(name, value);
+        }
+        Ok(Value::Object(args))
+    }
+}
+
+/// The functions, constructor and events of a contract, from its JSON ABI
+#[derive(Clone, Debug)]
+pub struct Abi {
+    pub functions: Vec<Function>,
+    pub constructor: Vec<ParamType>,
+    pub events: Vec<Event>,
+}
+
+impl Abi {
+    /// Reads an ABI from a file, which is either the ABI itself or a build artifact
+    /// with the ABI in its `abi` field, as Truffle and Hardhat write them
+    pub fn load(path: &str) -> Result<Abi, Error> {
+        let file = File::open(path).map_err(|error| format_err!("{}: {}", path, error))?;
+        let json: Value =
+            serde_json::from_reader(file).map_err(|error| format_err!("{}: {}", path, error))?;
+        Abi::from_json(json.get("abi").unwrap_or(&json))
+    }
+
+    pub fn from_json(abi: &Value) -> Result<Abi, Error> {
+        let params = |entry: &Value, key| -> Result<Vec<ParamType>, Error> {
+            match entry.get(key) {
+                Some(Value::Array(params)) => params
+                    .iter()
+                    .map(|param| ParamType::from_abi(param).map_err(err_msg))
+                    .collect(),
+                _ => Ok(Vec::new()),
+            }
+        };
+
+        let mut functions = Vec::new();
+        let mut constructor = Vec::new();
+        let mut events = Vec::new();
+        for entry in abi
+            .as_array()
+            .ok_or_else(|| format_err!("The ABI must be an array"))?
+        {
+            match entry
+                .get("type")
+                .and_then(Value::as_str)
+                .unwrap_or("function")
+            {
+                "function" => functions.push(Function {
+                    name: entry
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .map(String::from)
+                        .ok_or_else(|| format_err!("Functions must have a name"))?,
+                    inputs: params(entry, "inputs")?,
+                    outputs: params(entry, "outputs")?,
+                }),
+                "constructor" => constructor = params(entry, "inputs")?,
+                "event" => events.push(Event {
+                    name: entry
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .map(String::from)
+                        .ok_or_else(|| format_err!("Events must have a name"))?,
+                    inputs: match entry.get("inputs") {
+                        Some(Value::Array(inputs)) => inputs
+                            .iter()
+                            .map(|input| {
+                                Ok((
+                                    input
+                                        .get("name")
+                                        .and_then(Value::as_str)
+                                        .unwrap_or_default()
+                                        .to_string(),
+                                    ParamType::from_abi(input).map_err(err_msg)?,
+                                    input
+                                        .get("indexed")
+                                        .and_then(Value::as_bool)
+                                        .unwrap_or(false),
+                                ))
+                            })
+                            .collect::<Result<_, Error>>()?,
+                        _ => Vec::new(),
+                    },
+                    anonymous: entry
+                        .get("anonymous")
+                        .and_then(Value::as_bool)
+                        .unwrap_or(false),
+                }),
+                _ => {}
+            }
+        }
+        Ok(Abi {
+            functions,
+            constructor,
+            events,
+        })
+    }
+
+    /// Returns the function called `name` that takes `args` arguments. Overloaded
+    /// functions that take as many can be told apart by their signature, such as
+    /// `transfer(address,uint256)`.
+    pub fn function(&self, name: &str, args: usize) -> Result<&Function, Error> {
+        if name.contains('(') {
+            return self
+                .functions
+                .iter()
+                .find(|function| function.signature() == name)
+                .ok_or_else(|| format_err!("The ABI has no function `{}`", name));
+        }
+
+        let named: Vec<&Function> = self
+            .functions
+            .iter()
+            .filter(|function| function.name == name)
+            .collect();
+        if named.is_empty() {
+            return Err(format_err!("The ABI has no function `{}`", name));
+        }
+        let mut matching = named
+            .iter()
+            .filter(|function| function.inputs.len() == args);
+        match (matching.next(), matching.next()) {
+            (Some(function), None) => Ok(function),
+            (Some(_), Some(_)) => Err(format_err!(
+                "`{}` is overloaded, so give its signature, one of {}",
+                name,
+                signatures(
+                    named
+                        .iter()
+                        .filter(|function| function.inputs.len() == args)
+                )
+            )),
+            (None, _) => Err(format_err!(
+                "`{}` takes different arguments: {}",
+                name,
+                signatures(named.iter())
+            )),
+        }
+    }
+
+    /// Returns the event a log is of, by its first topic, or the only anonymous event
+    /// that takes as many topics if it isn't of one with a signature
+    pub fn event(&self, topics: &[String]) -> Option<&Event> {
+        let named = topics.first().and_then(|topic| {
+            self.events
+                .iter()
+                .find(|event| !event.anonymous && event.topic() == topic.to_lowercase())
+        });
+        named.or_else(|| {
+            let mut anonymous = self.events.iter().filter(|event| {
+                event.anonymous
+                    && event
+                        .inputs
+                        .iter()
+                        .filter(|(_, _, indexed)| *indexed)
+                        .count()
+                        == topics.len()
+            });
+            match (anonymous.next(), anonymous.next()) {
+                (Some(event), None) => Some(event),
+                _ => None,
+            }
+        })
+    }
+
+    /// Returns the constructor arguments to append to the init code of a deploy
+    pub fn encode_constructor(&self, args: &[&str]) -> Result<Vec<u8>, Error> {
+        encode_args(&self.constructor, args)
+    }
+}
+
+fn signatures<'a, I: Iterator<Item = &'a &'a Function>>(functions: I) -> String {
+    functions
+        .map(|function| function.signature())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn encode_args(types: &[ParamType], args: &[&str]) -> Result<Vec<u8>, Error> {
+    if types.len() != args.len() {
+        return Err(format_err!(
+            "Expected {} arguments, got {}",
+            types.len(),
+            args.len()
+        ));
+    }
+    let values = types
+        .iter()
+        .zip(args)
+        .map(|(kind, arg)| parse_arg(kind, arg))
+        .collect::<Result<Vec<_>, _>>()?;
+    abi::encode_params(types, &values).map_err(err_msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    const TOKEN_ABI: &str = r#"[
+        {"type": "constructor", "inputs": [{"name": "supply", "type": "uint256"}]},
+        {"type": "function", "name": "transfer", "inputs": [
+            {"name": "to", "type": "address"},
+            {"name": "value", "type": "uint256"}
+        ], "outputs": [{"name": "", "type": "bool"}]},
+        {"name": "mint", "inputs": [{"name": "value", "type": "uint256"}]},
+        {"type": "function", "name": "mint", "inputs": [{"name": "to", "type": "address"}]},
+        {"type": "event", "name": "Transfer", "inputs": [
+            {"name": "from", "type": "address", "indexed": true},
+            {"name": "to", "type": "address", "indexed": true},
+            {"name": "value", "type": "uint256", "indexed": false}
+        ]},
+        {"type": "event", "name": "Noted", "anonymous": true, "inputs": [
+            {"name": "note", "type": "string", "indexed": true},
+            {"name": "", "type": "uint8", "indexed": false}
+        ]}
+    ]"#;
+
+    const ADDRESS: &str = "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed";
+
+    fn token() -> Abi {
+        Abi::from_json(&serde_json::from_str(TOKEN_ABI).unwrap()).unwrap()
+    }
+
+    /// An address or a number as the 32 byte word it is encoded as
+    fn word(hex: &str) -> String {
+        format!("{:0>64}", hex.trim_start_matches("0x"))
+    }
+
+    #[test]
+    fn encodes_calls_of_functions_found_by_name_or_signature() {
+        let abi = token();
+        let transfer = abi.function("transfer", 2).unwrap();
+        assert_eq!(transfer.signature(), "transfer(address,uint256)");
+        assert_eq!(
+            to_hex(&transfer.encode_call(&[ADDRESS, "1000"]).unwrap()),
+            format!("0xa9059cbb{}{}", word(ADDRESS), word("3e8"))
+        );
+        assert_eq!(
+            transfer
+                .decode_result(&from_hex(&word("1")).unwrap())
+                .unwrap(),
+            Value::Array(vec![Value::Bool(true)])
+        );
+        assert!(transfer.encode_call(&[ADDRESS]).is_err());
+        assert!(abi.function("transfer", 1).is_err());
+        assert!(abi.function("burn", 1).is_err());
+
+        // `mint` is overloaded with one argument either way
+        assert!(abi.function("mint", 1).is_err());
+        assert_eq!(
+            abi.function("mint(address)", 1).unwrap().inputs,
+            vec![ParamType::Address]
+        );
+        assert_eq!(
+            to_hex(&abi.encode_constructor(&["1"]).unwrap()),
+            format!("0x{}", word("1"))
+        );
+    }
+
+    #[test]
+    fn parses_arguments_given_on_the_command_line() {
+        let parse = |kind: &str, arg| {
+            let kind = serde_json::from_str(&format!(r#"{{"type": "{}"}}"#, kind)).unwrap();
+            parse_arg(&ParamType::from_abi(&kind).unwrap(), arg)
+        };
+        assert_eq!(parse("bool", "true").unwrap(), Value::Bool(true));
+        assert!(parse("bool", "yes").is_err());
+        assert_eq!(
+            parse("uint8[]", "[1,2]").unwrap(),
+            serde_json::from_str::<Value>("[1,2]").unwrap()
+        );
+        assert!(parse("uint8[2]", "1,2").is_err());
+        assert_eq!(parse("string", "[1,2]").unwrap(), Value::from("[1,2]"));
+    }
+
+    #[test]
+    fn decodes_the_logs_of_events() {
+        let abi = token();
+        let transfer_topic = to_hex(&keccak256(b"Transfer(address,address,uint256)"));
+        let topics = vec![transfer_topic, word(ADDRESS), word("1")];
+        let transfer = abi.event(&topics).unwrap();
+        assert_eq!(transfer.name, "Transfer");
+        let decoded = transfer
+            .decode_log(&topics, &from_hex(&word("5")).unwrap())
+            .unwrap();
+        assert_eq!(decoded["from"], Value::from(ADDRESS));
+        assert_eq!(
+            decoded["to"],
+            Value::from("0x0000000000000000000000000000000000000001")
+        );
+        assert_eq!(decoded["value"], Value::from("5"));
+        assert!(transfer.decode_log(&topics[..2], &[]).is_err());
+
+        // The anonymous event has one topic, the hash of its indexed string
+        let topics = vec![to_hex(&keccak256(b"a note"))];
+        let noted = abi.event(&topics).unwrap();
+        assert_eq!(noted.name, "Noted");
+        let decoded = noted
+            .decode_log(&topics, &from_hex(&word("7")).unwrap())
+            .unwrap();
+        assert_eq!(decoded["note"], Value::from(topics[0].clone()));
+        assert_eq!(decoded["1"], Value::from("7"));
+        assert!(abi.event(&[]).is_none());
+    }
+
+    #[test]
+    fn loads_abis_from_build_artifacts() {
+        let path = env::temp_dir().join(format!("seth-abi-{}.json", process::id()));
+        fs::write(
+            &path,
+            format!(r#"{{"contractName": "Token", "abi": {}}}"#, TOKEN_ABI),
+        )
+        .unwrap();
+        let loaded = Abi::load(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.functions.len(), 3);
+        assert_eq!(loaded.constructor, vec![ParamType::Uint(256)]);
+        assert_eq!(loaded.events.len(), 2);
+        assert!(Abi::from_json(&Value::Object(Map::new())).is_err());
+        assert!(Abi::load("/nonexistent/abi.json").is_err());
+    }
+}
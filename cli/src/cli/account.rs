@@ -20,7 +20,8 @@
 use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use client::Client;
 use failure::Error;
-use serde_json::to_string_pretty;
+use output;
+use serde_json::{from_slice, Value};
 use std::fs::File;
 use std::io::Read;
 use std::str::from_utf8;
@@ -50,6 +51,17 @@ pub fn get_cli<'a, 'b>() -> App<'a, 'b> {
                         .help(
                             "Permissions for new account; see 'seth permissions -h' for more info",
                         ),
+                    Arg::with_name("mnemonic")
+                        .long("mnemonic")
+                        .conflicts_with_all(&["moderator", "permissions"])
+                        .help("Derive the account from a new BIP-39 mnemonic, which is printed with it"),
+                    Arg::with_name("words")
+                        .long("words")
+                        .takes_value(true)
+                        .possible_values(&["12", "15", "18", "21", "24"])
+                        .requires("mnemonic")
+                        .help("Number of words of the mnemonic, 12 by default"),
+                    get_derive_arg(),
                 ]),
             SubCommand::with_name("unlock")
                 .about("Unlocks a seth account")
@@ -77,16 +89,63 @@ pub fn get_cli<'a, 'b>() -> App<'a, 'b> {
                         .long("pass-file")
                         .takes_value(true)
                         .help("Path to file containing password to encrypt key with"),
+                    Arg::with_name("keystore")
+                        .long("keystore")
+                        .requires("pass-file")
+                        .help("Import a JSON keystore from geth or another wallet, decrypted with the password"),
+                    Arg::with_name("mnemonic")
+                        .long("mnemonic")
+                        .conflicts_with("keystore")
+                        .help("Import the accounts of a BIP-39 mnemonic from MetaMask or another wallet"),
+                    get_derive_arg(),
+                ]),
+            SubCommand::with_name("export")
+                .about("Exports a seth account as a JSON keystore")
+                .args(&[
+                    Arg::with_name("address")
+                        .required(true)
+                        .help("The address of the account to export"),
+                    Arg::with_name("pass-file")
+                        .long("pass-file")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Path to file containing password of the account, which encrypts the keystore"),
                 ]),
             SubCommand::with_name("list").about("Lists seth accounts"),
+            SubCommand::with_name("nonce")
+                .about("Shows how seth-rpc counts the nonce of an account")
+                .args(&[
+                    Arg::with_name("address")
+                        .required(true)
+                        .help("The address of the account"),
+                    Arg::with_name("reset")
+                        .long("reset")
+                        .help("Forget the transactions seth-rpc counts that haven't been committed"),
+                ]),
         ])
 }
 
+/// Number of accounts to derive from a mnemonic, along the path MetaMask does
+fn get_derive_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("derive")
+        .long("derive")
+        .takes_value(true)
+        .requires("mnemonic")
+        .help("Number of accounts to derive from the mnemonic, 1 by default")
+}
+
 /// Handles parsing Clap CLI matches
 pub fn parse_cli<'a>(matches: (&'a str, Option<&'a ArgMatches>)) -> Result<(), Error> {
     let client = &::client::Client::new()?;
 
     match matches {
+        ("create", Some(m)) if m.is_present("mnemonic") => {
+            let pass_file = m.value_of("pass-file");
+            let words = m.value_of("words").map(str::parse::<usize>).transpose()?;
+            let derive = m.value_of("derive").map(str::parse::<u32>).transpose()?;
+
+            do_create_from_mnemonic(client, pass_file, words, derive)?;
+        }
         ("create", Some(m)) => {
             let pass_file = m.value_of("pass-file");
             let moderator = m.value_of("moderator");
@@ -104,15 +163,33 @@ pub fn parse_cli<'a>(matches: (&'a str, Option<&'a ArgMatches>)) -> Result<(), E
 
             do_unlock(client, address, pass_file, duration)?;
         }
+        ("import", Some(m)) if m.is_present("mnemonic") => {
+            let key_file = m.value_of("key-file").expect("Key file path is required!");
+            let pass_file = m.value_of("pass-file");
+            let derive = m.value_of("derive").map(str::parse::<u32>).transpose()?;
+
+            do_import_mnemonic(client, key_file, pass_file, derive)?;
+        }
         ("import", Some(m)) => {
             let key_file = m.value_of("key-file").expect("Key file path is required!");
             let pass_file = m.value_of("pass-file");
 
-            do_import(client, key_file, pass_file)?;
+            do_import(client, key_file, pass_file, m.is_present("keystore"))?;
+        }
+        ("export", Some(m)) => {
+            let address = m.value_of("address").expect("Address is required!");
+            let pass_file = m.value_of("pass-file").expect("Pass file is required!");
+
+            do_export(client, address, pass_file)?;
         }
         ("list", Some(_)) => {
             do_list(&client)?;
         }
+        ("nonce", Some(m)) => {
+            let address = m.value_of("address").expect("Address is required!");
+
+            do_nonce(client, address, m.is_present("reset"))?;
+        }
         _ => unreachable!(),
     }
 
@@ -133,22 +210,38 @@ pub fn do_create(
     moderator: Option<&str>,
     permissions: Option<&str>,
 ) -> Result<(), Error> {
-    let password = match pass_file {
-        Some(pf) => {
-            let mut file = File::open(&pf)?;
-            let mut password = String::new();
-            file.read_to_string(&mut password)?;
-            Some(password.trim().to_string())
-        }
-        None => None,
-    };
+    let password = read_password(pass_file)?;
 
     let account_id: String = client.send_rpc_transaction(
         "personal_newAccount",
         &json!((password, moderator, permissions)),
     )?;
 
-    println!("\"{}\"", account_id);
+    output::print_value(&json!(account_id))?;
+
+    Ok(())
+}
+
+/// Creates accounts from a new BIP-39 mnemonic
+///
+/// seth-rpc makes the mnemonic and imports the first `derive` accounts it derives,
+/// which are the ones MetaMask derives from it. The mnemonic isn't kept, so it is
+/// printed with the accounts' addresses for it to be written down.
+pub fn do_create_from_mnemonic(
+    client: &Client,
+    pass_file: Option<&str>,
+    words: Option<usize>,
+    derive: Option<u32>,
+) -> Result<(), Error> {
+    let password = read_password(pass_file)?;
+
+    let mnemonic: String = client.send_rpc_transaction("personal_newMnemonic", &json!([words]))?;
+    let accounts: Vec<String> = client.send_rpc_transaction(
+        "personal_importMnemonic",
+        &json!((&mnemonic, password, derive)),
+    )?;
+
+    output::print_value(&json!({ "Mnemonic": mnemonic, "Accounts": accounts }))?;
 
     Ok(())
 }
@@ -165,50 +258,83 @@ pub fn do_unlock(
     pass_file: Option<&str>,
     duration: Option<u64>,
 ) -> Result<(), Error> {
-    let password = match pass_file {
-        Some(pf) => {
-            let mut file = File::open(&pf)?;
-            let mut password = String::new();
-            file.read_to_string(&mut password)?;
-            Some(password.trim().to_string())
-        }
-        None => None,
-    };
+    let password = read_password(pass_file)?;
 
     let result: bool = client.send_rpc_transaction(
         "personal_unlockAccount",
         &json!((address, password, duration)),
     )?;
 
-    println!("{}", result);
+    output::print_value(&json!(result))?;
 
     Ok(())
 }
 
 /// Imports a key into the RPC service's account store
 ///
-/// Optionally encrypts imported key
-pub fn do_import(client: &Client, key_file: &str, pass_file: Option<&str>) -> Result<(), Error> {
+/// Optionally encrypts imported key. Keystores are decrypted with the same password.
+pub fn do_import(
+    client: &Client,
+    key_file: &str,
+    pass_file: Option<&str>,
+    keystore: bool,
+) -> Result<(), Error> {
     let mut file = File::open(&key_file)?;
     let mut key = vec![];
     file.read_to_end(&mut key)?;
 
-    let password = match pass_file {
-        Some(pf) => {
-            let mut file = File::open(&pf)?;
-            let mut password = String::new();
-            file.read_to_string(&mut password)?;
-            Some(password.trim().to_string())
-        }
-        None => None,
+    let password = read_password(pass_file)?;
+
+    let key: Value = if keystore {
+        from_slice(&key)?
+    } else {
+        Value::from(from_utf8(&key)?)
     };
 
-    let account_id: String = client.send_rpc_transaction(
-        "personal_importRawKey",
-        &json!([from_utf8(&key)?, password]),
+    let account_id: String =
+        client.send_rpc_transaction("personal_importRawKey", &json!([key, password]))?;
+
+    output::print_value(&json!(account_id))?;
+
+    Ok(())
+}
+
+/// Imports the first `derive` accounts of the BIP-39 mnemonic in a file
+///
+/// The keys are encrypted by the password like imported keys, and can be exported
+/// as keystores the same way.
+pub fn do_import_mnemonic(
+    client: &Client,
+    mnemonic_file: &str,
+    pass_file: Option<&str>,
+    derive: Option<u32>,
+) -> Result<(), Error> {
+    let mut mnemonic = String::new();
+    File::open(mnemonic_file)?.read_to_string(&mut mnemonic)?;
+    let password = read_password(pass_file)?;
+
+    let accounts: Vec<String> = client.send_rpc_transaction(
+        "personal_importMnemonic",
+        &json!((mnemonic.trim(), password, derive)),
     )?;
 
-    println!("\"{}\"", account_id);
+    output::print_value(&json!(accounts))?;
+
+    Ok(())
+}
+
+/// Exports an account as a JSON keystore, which geth and other wallets can import
+pub fn do_export(client: &Client, address: &str, pass_file: &str) -> Result<(), Error> {
+    let mut file = File::open(pass_file)?;
+    let mut password = String::new();
+    file.read_to_string(&mut password)?;
+
+    let keystore: Value = client.send_rpc_transaction(
+        "personal_exportKeystore",
+        &json!([address, password.trim()]),
+    )?;
+
+    output::print_value(&keystore)?;
 
     Ok(())
 }
@@ -217,7 +343,38 @@ pub fn do_import(client: &Client, key_file: &str, pass_file: Option<&str>) -> Re
 pub fn do_list(client: &Client) -> Result<(), Error> {
     let result: Vec<String> = client.send_rpc_transaction("personal_listAccounts", &json!([]))?;
 
-    println!("{}", to_string_pretty(&json!(result))?);
+    output::print_value(&json!(result))?;
+
+    Ok(())
+}
+
+/// Shows an account's nonce in the latest block and the one seth-rpc gives its next
+/// transaction
+///
+/// seth-rpc counts the transactions it sent from the account that haven't been
+/// committed. If a validator rollback undid them, `reset` has it forget them first,
+/// so that the next transaction takes the nonce in the latest block again.
+pub fn do_nonce(client: &Client, address: &str, reset: bool) -> Result<(), Error> {
+    if reset {
+        let _: String = client.send_rpc_transaction("seth_resetNonces", &json!([address]))?;
+    }
+
+    let status: Value = client.send_rpc_transaction("seth_getNonceStatus", &json!([address]))?;
+
+    output::print_value(&status)?;
 
     Ok(())
 }
+
+/// Reads the password in a file, if one is given
+fn read_password(pass_file: Option<&str>) -> Result<Option<String>, Error> {
+    match pass_file {
+        Some(pf) => {
+            let mut file = File::open(pf)?;
+            let mut password = String::new();
+            file.read_to_string(&mut password)?;
+            Ok(Some(password.trim().to_string()))
+        }
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,201 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Export and import the batches of committed blocks
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use client::Client;
+use failure::Error;
+use output;
+use std::fs::{self, File};
+use std::io::Write;
+use types::ExportedBatches;
+
+/// How many batches are imported with each request
+const IMPORT_CHUNK_SIZE: usize = 64;
+
+/// The tag of the `batches` field of a serialized BatchList
+const BATCHES_TAG: u8 = 0x0a;
+
+/// Returns Clap configuration
+pub fn get_cli<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("batch")
+        .about("Exports and imports the batches of committed blocks")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommands(vec![
+            SubCommand::with_name("export")
+                .about("Saves the batches of a range of blocks to a file")
+                .long_about(
+                    "Saves the batches of a range of blocks to a file, as a serialized \
+                     BatchList that `seth batch import` and `sawtooth batch submit` read.",
+                )
+                .args(&[
+                    Arg::with_name("from")
+                        .long("from")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Number of the first block to export"),
+                    Arg::with_name("to")
+                        .long("to")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Number of the last block to export"),
+                    Arg::with_name("output")
+                        .required(true)
+                        .help("File to save the batches to"),
+                ]),
+            SubCommand::with_name("import")
+                .about("Submits batches saved by `seth batch export`")
+                .long_about(
+                    "Submits batches saved by `seth batch export`, in the order they were \
+                     committed, waiting for each to be validated before the next. \
+                     The batches keep their signatures, so a network rebuilt from \
+                     another's history should import it from the first block on.",
+                )
+                .args(&[Arg::with_name("input")
+                    .required(true)
+                    .help("File to read the batches from")]),
+        ])
+}
+
+/// Handles parsing Clap CLI matches
+pub fn parse_cli<'a>(matches: (&'a str, Option<&'a ArgMatches>)) -> Result<(), Error> {
+    let client = &::client::Client::new()?;
+
+    match matches {
+        ("export", Some(m)) => {
+            let from = value_t!(m, "from", u64)?;
+            let to = value_t!(m, "to", u64)?;
+            let output = m.value_of("output").expect("Output is required!");
+
+            do_export(client, from, to, output)?;
+        }
+        ("import", Some(m)) => {
+            let input = m.value_of("input").expect("Input is required!");
+
+            do_import(client, input)?;
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+/// Saves the batches of the blocks from `from` to `to` to a file
+pub fn do_export(client: &Client, from: u64, to: u64, output: &str) -> Result<(), Error> {
+    if from > to {
+        return Err(format_err!("--from {} is after --to {}", from, to));
+    }
+
+    let mut file = File::create(output)?;
+    let mut next = from;
+    while next <= to {
+        let exported: ExportedBatches = client.send_rpc_transaction(
+            "seth_exportBatches",
+            &vec![format!("{:#x}", next), format!("{:#x}", to - next + 1)],
+        )?;
+        if exported.blocks.0 == 0 {
+            return Err(format_err!("Block {} hasn't been committed", next));
+        }
+
+        // Serialized lists append to one list holding the batches of both
+        file.write_all(&hex_to_bytes(&exported.batches)?)?;
+        next += exported.blocks.0;
+    }
+
+    output::print(
+        &format!("Exported the batches of blocks {} to {}", from, to),
+        &json!({ "From": from, "To": to, "Output": output }),
+    )?;
+
+    Ok(())
+}
+
+/// Submits the batches saved in a file a few at a time
+pub fn do_import(client: &Client, input: &str) -> Result<(), Error> {
+    let batch_list = fs::read(input)?;
+    let batches = split_batch_list(&batch_list)?;
+
+    for (index, chunk) in batches.chunks(IMPORT_CHUNK_SIZE).enumerate() {
+        let data = chunk
+            .iter()
+            .flat_map(|batch| batch.iter())
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        let _: Vec<String> = client
+            .send_rpc_transaction("seth_importBatches", &vec![format!("0x{}", data)])
+            .map_err(|err| {
+                format_err!(
+                    "Failed to import batches after {}: {}",
+                    index * IMPORT_CHUNK_SIZE,
+                    err
+                )
+            })?;
+    }
+
+    output::print(
+        &format!("Imported {} batches", batches.len()),
+        &json!({ "Imported": batches.len() }),
+    )?;
+
+    Ok(())
+}
+
+// Utility functions
+
+/// Splits a serialized BatchList into one serialized BatchList per batch, which can
+/// be joined back together into a list of any of them
+fn split_batch_list(bytes: &[u8]) -> Result<Vec<&[u8]>, Error> {
+    let mut batches = Vec::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        if rest[0] != BATCHES_TAG {
+            return Err(format_err!("File doesn't hold a serialized BatchList"));
+        }
+        let (len, len_size) = read_varint(&rest[1..])?;
+        let end = len
+            .checked_add(1 + len_size)
+            .filter(|&end| end <= rest.len())
+            .ok_or_else(|| format_err!("File ends in the middle of a batch"))?;
+        batches.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    Ok(batches)
+}
+
+/// Reads a protobuf varint, returning its value and how many bytes it took
+fn read_varint(bytes: &[u8]) -> Result<(usize, usize), Error> {
+    let mut value: u64 = 0;
+    for (i, byte) in bytes.iter().take(10).enumerate() {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value as usize, i + 1));
+        }
+    }
+    Err(format_err!("File doesn't hold a serialized BatchList"))
+}
+
+fn hex_to_bytes(hex: &str) -> Result<Vec<u8>, Error> {
+    let hex = hex.trim_start_matches("0x");
+    if !hex.len().is_multiple_of(2) {
+        return Err(format_err!("Got an odd number of hex digits"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(Error::from))
+        .collect()
+}
@@ -68,6 +68,7 @@ pub fn do_init(url: Option<&str>) -> Result<(), Error> {
         dirs::home_dir().ok_or_else(|| format_err!("Couldn't find home directory!"))?;
 
     settings_file.push(".sawtooth");
+    fs::create_dir_all(&settings_file)?;
     settings_file.push("seth-config.toml");
 
     fs::write(settings_file, toml)?;
@@ -17,12 +17,22 @@
 
 //! Manage contract accounts
 
+use abi::{self, Abi, Function};
 use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
-use client::Client;
+use client::{Client, TransactionFailed};
 use failure::Error;
-use serde_json::to_string_pretty;
+use output;
+use serde_json::{to_string, Value};
+use std::thread::sleep;
+use std::time::Duration;
 use tiny_keccak::keccak256;
-use types::TransactionReceipt;
+use types::{ErrorCode, LogPage, TransactionLog, TransactionReceipt};
+
+/// How many logs are asked for at once when printing events
+const EVENTS_PAGE_SIZE: u64 = 1000;
+
+/// How often new blocks are looked for when following events
+const EVENTS_POLL_INTERVAL: Duration = Duration::from_secs(2);
 
 /// Returns Clap configuration
 pub fn get_cli<'a, 'b>() -> App<'a, 'b> {
@@ -43,7 +53,8 @@ pub fn get_cli<'a, 'b>() -> App<'a, 'b> {
                         .short("d")
                         .long("data")
                         .takes_value(true)
-                        .required(true)
+                        .required_unless("abi")
+                        .conflicts_with("abi")
                         .help(
                             "Input data to pass to contract when called; must conform to \
                              contract ABI",
@@ -58,10 +69,21 @@ pub fn get_cli<'a, 'b>() -> App<'a, 'b> {
                         .takes_value(true)
                         .default_value(::DEFAULT_GAS)
                         .help("Gas limit for calling the contract (default: 90,000)"),
+                    get_abi_arg(),
                     ::get_wait_arg(),
                     Arg::with_name("address")
                         .required(true)
                         .help("Address of contract to call"),
+                    Arg::with_name("function")
+                        .requires("abi")
+                        .help("Name or signature of the function to call, with --abi"),
+                    Arg::with_name("args")
+                        .multiple(true)
+                        .requires("function")
+                        .help(
+                            "Arguments of the function; arrays and tuples are given as JSON, \
+                             such as [1,2]",
+                        ),
                 ]),
             SubCommand::with_name("create")
                 .about("Creates a seth contract")
@@ -83,7 +105,33 @@ pub fn get_cli<'a, 'b>() -> App<'a, 'b> {
                         .long("permissions")
                         .takes_value(true)
                         .help("Permissions for the new contract"),
+                    Arg::with_name("salt")
+                        .short("s")
+                        .long("salt")
+                        .takes_value(true)
+                        .help("32 byte salt to create the contract at its CREATE2 address"),
+                    get_abi_arg(),
                     ::get_wait_arg(),
+                    Arg::with_name("args")
+                        .multiple(true)
+                        .requires("abi")
+                        .help("Arguments of the constructor, with --abi"),
+                ]),
+            SubCommand::with_name("events")
+                .about("Prints the events a seth contract logged, one JSON object a line")
+                .args(&[
+                    Arg::with_name("address")
+                        .required(true)
+                        .help("Address of the contract to print events of"),
+                    get_abi_arg(),
+                    Arg::with_name("from-block")
+                        .long("from-block")
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Number of the first block to print events of"),
+                    Arg::with_name("follow")
+                        .long("follow")
+                        .help("Keep printing events as blocks are committed"),
                 ]),
             SubCommand::with_name("list")
                 .about("Lists seth contracts")
@@ -101,7 +149,25 @@ pub fn parse_cli<'a>(matches: (&'a str, Option<&'a ArgMatches>)) -> Result<(), E
         ("call", Some(m)) => {
             let from = m.value_of("from").expect("From address is required!");
             let address = m.value_of("address").expect("Address is required!");
-            let data = m.value_of("data").expect("Data is required!");
+            let args: Vec<&str> = m
+                .values_of("args")
+                .map(Iterator::collect)
+                .unwrap_or_default();
+            let (data, function) = match m.value_of("abi") {
+                Some(path) => {
+                    let abi = Abi::load(path)?;
+                    let name = m
+                        .value_of("function")
+                        .ok_or_else(|| format_err!("Give the function to call"))?;
+                    let function = abi.function(name, args.len())?.clone();
+                    let data = abi::to_hex(&function.encode_call(&args)?);
+                    (String::from(&data[2..]), Some(function))
+                }
+                None => (
+                    String::from(m.value_of("data").expect("Data is required!")),
+                    None,
+                ),
+            };
             let chaining = m.is_present("chaining");
             let gas = m
                 .value_of("gas")
@@ -109,19 +175,56 @@ pub fn parse_cli<'a>(matches: (&'a str, Option<&'a ArgMatches>)) -> Result<(), E
                 .parse::<u64>()?;
             let wait = ::parse_wait_flag(m)?;
 
-            do_call(client, from, address, data, chaining, gas, wait)?;
+            do_call(
+                client,
+                from,
+                address,
+                &data,
+                function.as_ref(),
+                chaining,
+                gas,
+                wait,
+            )?;
         }
         ("create", Some(m)) => {
             let from = m.value_of("from").expect("From address is required!");
-            let init = m.value_of("init").expect("Contract value is required!");
+            let mut init = String::from(m.value_of("init").expect("Contract value is required!"));
+            if let Some(path) = m.value_of("abi") {
+                let args: Vec<&str> = m
+                    .values_of("args")
+                    .map(Iterator::collect)
+                    .unwrap_or_default();
+                let encoded = Abi::load(path)?.encode_constructor(&args)?;
+                init.push_str(&abi::to_hex(&encoded)[2..]);
+            }
             let gas = m
                 .value_of("gas")
                 .expect("Default gas must exist!")
                 .parse::<u64>()?;
             let permissions = m.value_of("permissions");
+            let salt = m.value_of("salt");
             let wait = ::parse_wait_flag(m)?;
 
-            do_create(client, from, init, gas, permissions, wait)?;
+            do_create(client, from, &init, gas, permissions, salt, wait)?;
+        }
+        ("events", Some(m)) => {
+            let address = m.value_of("address").expect("Address is required!");
+            let abi = match m.value_of("abi") {
+                Some(path) => Some(Abi::load(path)?),
+                None => None,
+            };
+            let from_block = m
+                .value_of("from-block")
+                .expect("Default from block must exist!")
+                .parse::<u64>()?;
+
+            do_events(
+                client,
+                address,
+                abi.as_ref(),
+                from_block,
+                m.is_present("follow"),
+            )?;
         }
         ("list", Some(m)) => {
             let address = m.value_of("address").expect("Address is required!");
@@ -134,12 +237,14 @@ pub fn parse_cli<'a>(matches: (&'a str, Option<&'a ArgMatches>)) -> Result<(), E
     Ok(())
 }
 
-/// Calls a contract
+/// Calls a contract, decoding what it returns if the function called is given
+#[allow(clippy::too_many_arguments)]
 pub fn do_call(
     client: &Client,
     from: &str,
     address: &str,
     data: &str,
+    function: Option<&Function>,
     chaining: bool,
     gas: u64,
     wait: Option<u64>,
@@ -162,23 +267,26 @@ pub fn do_call(
                 &vec![txn_id.clone()],
                 w,
             )?;
+            check_reverted(&receipt)?;
 
-            (
-                u64::from_str_radix(&receipt.gas_used[2..], 16)?,
-                receipt.return_value[2..].to_string(),
-            )
+            let retval = match function {
+                Some(function) => {
+                    let data = abi::from_hex(&receipt.return_value)
+                        .ok_or_else(|| format_err!("Got a return value that isn't hex"))?;
+                    function.decode_result(&data)?
+                }
+                None => json!(receipt.return_value[2..]),
+            };
+            (receipt.gas_used.0, retval)
         }
-        None => (0u64, "<Not retrieved>".into()),
+        None => (0u64, output::not_retrieved()),
     };
 
-    println!(
-        "{}",
-        to_string_pretty(&json!({
-            "TransactionID": txn_id,
-            "GasUsed": gas_used,
-            "ReturnValue": retval,
-        }))?
-    );
+    output::print_value(&json!({
+        "TransactionID": txn_id,
+        "GasUsed": gas_used,
+        "ReturnValue": retval,
+    }))?;
 
     Ok(())
 }
@@ -190,17 +298,19 @@ pub fn do_create(
     init: &str,
     gas: u64,
     permissions: Option<&str>,
+    salt: Option<&str>,
     wait: Option<u64>,
 ) -> Result<(), Error> {
-    let txn_id: String = client.send_rpc_transaction(
-        "eth_sendTransaction",
-        &vec![json!({
-            "from": format!("0x{}", from),
-            "data": format!("0x{}", init),
-            "gas": format!("0x{:x}", gas),
-            "permissions": permissions,
-        })],
-    )?;
+    let mut txn = json!({
+        "from": format!("0x{}", from),
+        "data": format!("0x{}", init),
+        "gas": format!("0x{:x}", gas),
+        "permissions": permissions,
+    });
+    if let Some(salt) = salt {
+        txn["salt"] = json!(format!("0x{}", salt.trim_start_matches("0x")));
+    }
+    let txn_id: String = client.send_rpc_transaction("eth_sendTransaction", &vec![txn])?;
 
     let (gas_used, retval, address) = match wait {
         Some(w) => {
@@ -209,28 +319,73 @@ pub fn do_create(
                 &vec![txn_id.clone()],
                 w,
             )?;
+            check_reverted(&receipt)?;
             (
-                u64::from_str_radix(&receipt.gas_used[2..], 16)?,
-                receipt.return_value[2..].to_string(),
-                receipt.contract_address.to_string(),
+                receipt.gas_used.0,
+                json!(receipt.return_value[2..]),
+                json!(receipt.contract_address.unwrap_or_default()),
             )
         }
-        None => (0u64, "<Not retrieved>".into(), "<Not retrieved>".into()),
+        None => (0u64, output::not_retrieved(), output::not_retrieved()),
     };
 
-    println!(
-        "{}",
-        to_string_pretty(&json!({
-            "TransactionID": txn_id,
-            "Address": address,
-            "GasUsed": gas_used,
-            "ReturnValue": retval,
-        }))?
-    );
+    output::print_value(&json!({
+        "TransactionID": txn_id,
+        "Address": address,
+        "GasUsed": gas_used,
+        "ReturnValue": retval,
+    }))?;
 
     Ok(())
 }
 
+/// Prints the events a contract logged since a block, decoded if its ABI is given,
+/// and if `follow` keeps printing them as blocks are committed
+pub fn do_events(
+    client: &Client,
+    address: &str,
+    abi: Option<&Abi>,
+    from_block: u64,
+    follow: bool,
+) -> Result<(), Error> {
+    let address = format!("0x{}", address.trim_start_matches("0x"));
+    let mut from = from_block;
+    loop {
+        let head: String = client.send_rpc_transaction("eth_blockNumber", &Vec::<String>::new())?;
+        let head = u64::from_str_radix(head.trim_start_matches("0x"), 16)?;
+        if head >= from {
+            let mut cursor: Option<String> = None;
+            loop {
+                let page: LogPage = client.send_rpc_transaction(
+                    "seth_getLogsPage",
+                    &json!([
+                        {
+                            "address": address,
+                            "fromBlock": format!("0x{:x}", from),
+                            "toBlock": format!("0x{:x}", head),
+                        },
+                        format!("0x{:x}", EVENTS_PAGE_SIZE),
+                        cursor,
+                    ]),
+                )?;
+                for log in &page.logs {
+                    println!("{}", to_string(&event_line(log, abi))?);
+                }
+                match page.next_cursor {
+                    Some(next) => cursor = Some(next),
+                    None => break,
+                }
+            }
+            from = head + 1;
+        }
+
+        if !follow {
+            return Ok(());
+        }
+        sleep(EVENTS_POLL_INTERVAL);
+    }
+}
+
 /// Lists contracts for a given alias
 ///
 /// Stops listing contracts if it encounters an error while talking to the JSON-RPC API
@@ -254,13 +409,67 @@ pub fn do_list(client: &Client, address: &str) -> Result<(), Error> {
         })
         .collect::<Vec<_>>();
 
-    println!("{}", to_string_pretty(&json!(contracts))?);
+    output::print_value(&json!(contracts))?;
 
     Ok(())
 }
 
 // Utility functions
 
+/// Returns what is printed for a log, its event and parameters if the ABI has the
+/// event, or else its raw topics and data
+fn event_line(log: &TransactionLog, abi: Option<&Abi>) -> Value {
+    let mut line = json!({
+        "blockNumber": log.block_number,
+        "transactionHash": log.transaction_hash,
+        "logIndex": log.log_index,
+    });
+    let decoded = abi
+        .and_then(|abi| abi.event(&log.topics))
+        .and_then(|event| {
+            let data = abi::from_hex(&log.data)?;
+            event
+                .decode_log(&log.topics, &data)
+                .ok()
+                .map(|args| (event, args))
+        });
+    match decoded {
+        Some((event, args)) => {
+            line["event"] = json!(event.name);
+            line["args"] = args;
+        }
+        None => {
+            line["topics"] = json!(log.topics);
+            line["data"] = json!(log.data);
+        }
+    }
+    line
+}
+
+fn get_abi_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("abi")
+        .short("a")
+        .long("abi")
+        .takes_value(true)
+        .help("JSON ABI of the contract, or a build artifact with one, to encode arguments with")
+}
+
+/// Fails if the transaction of a receipt reverted, with its reason if it had one
+fn check_reverted(receipt: &TransactionReceipt) -> Result<(), Error> {
+    if receipt.status.0 != 0 {
+        return Ok(());
+    }
+    Err(TransactionFailed {
+        transaction_id: receipt.transaction_hash.clone(),
+        code: ErrorCode::Reverted,
+        message: match receipt.revert_reason {
+            Some(ref reason) => format!("Transaction reverted: {}", reason),
+            None => format!("Transaction reverted with {}", receipt.return_value),
+        },
+    }
+    .into())
+}
+
 /// Derive a contract address from the main Account address
 pub fn derive(address: String, nonce: u64) -> String {
     match nonce {
@@ -20,7 +20,7 @@
 use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use client::Client;
 use failure::Error;
-use serde_json::to_string_pretty;
+use output;
 use types::TransactionReceipt;
 
 /// Returns Clap configuration
@@ -56,7 +56,7 @@ pub fn do_list(client: &Client, txn_id: &str) -> Result<(), Error> {
     let receipt: TransactionReceipt =
         client.send_rpc_transaction("eth_getTransactionReceipt", &vec![format!("0x{}", txn_id)])?;
 
-    println!("{}", to_string_pretty(&json!(receipt.logs))?);
+    output::print_value(&json!(receipt.logs))?;
 
     Ok(())
 }
@@ -18,8 +18,10 @@
 //! Functions that implement CLI functionality
 
 pub mod account;
+pub mod batch;
 pub mod config;
 pub mod contract;
 pub mod event;
 pub mod permissions;
 pub mod receipt;
+pub mod state;
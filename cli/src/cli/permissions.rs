@@ -20,6 +20,7 @@
 use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use client::Client;
 use failure::Error;
+use output;
 
 /// Returns Clap configuration
 pub fn get_cli<'a, 'b>() -> App<'a, 'b> {
@@ -89,7 +90,7 @@ pub fn do_set(client: &Client, address: &str, permissions: &str) -> Result<(), E
     let result: bool =
         client.send_rpc_transaction("seth_setPermissions", &json!(vec![address, permissions]))?;
 
-    println!("{}", result);
+    output::print_value(&json!(result))?;
 
     Ok(())
 }
@@ -106,7 +107,7 @@ pub fn do_get(client: &Client, address: &str) -> Result<(), Error> {
     let result: String =
         client.send_rpc_transaction("seth_getPermissions", &json!(vec![address]))?;
 
-    println!("\"{}\"", result);
+    output::print_value(&json!(result))?;
 
     Ok(())
 }
@@ -20,19 +20,46 @@
 use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use client::Client;
 use failure::Error;
-use serde_json::to_string_pretty;
+use output;
+use serde_json::from_reader;
+use std::collections::BTreeMap;
+use std::fs::File;
 use types::TransactionReceipt;
 
+/// The number of times each opcode was executed and the gas it used, by opcode
+type GasProfile = BTreeMap<String, (u64, u64)>;
+
 /// Returns Clap configuration
 pub fn get_cli<'a, 'b>() -> App<'a, 'b> {
     SubCommand::with_name("receipt")
         .about("Manages seth receipts")
         .setting(AppSettings::SubcommandRequiredElseHelp)
-        .subcommands(vec![SubCommand::with_name("show")
-            .about("Manages seth receipts")
-            .args(&[Arg::with_name("txn-id")
-                .required(true)
-                .help("Transaction ID of receipt to show")])])
+        .subcommands(vec![
+            SubCommand::with_name("show")
+                .about("Manages seth receipts")
+                .args(&[Arg::with_name("txn-id")
+                    .required(true)
+                    .help("Transaction ID of receipt to show")]),
+            SubCommand::with_name("gas-profile")
+                .about("Shows the gas used by each opcode in a transaction")
+                .long_about(
+                    "Shows the gas used by each opcode in a transaction. The transaction \
+                     must have been executed by a processor running with --gas-audit.",
+                )
+                .args(&[Arg::with_name("txn-id")
+                    .required(true)
+                    .help("Transaction ID of receipt to show")]),
+            SubCommand::with_name("gas-diff")
+                .about("Compares two gas profiles saved from `seth receipt gas-profile`")
+                .args(&[
+                    Arg::with_name("old")
+                        .required(true)
+                        .help("File containing the first gas profile"),
+                    Arg::with_name("new")
+                        .required(true)
+                        .help("File containing the second gas profile"),
+                ]),
+        ])
 }
 
 /// Handles parsing Clap CLI matches
@@ -45,6 +72,17 @@ pub fn parse_cli<'a>(matches: (&'a str, Option<&'a ArgMatches>)) -> Result<(), E
 
             do_show(client, txn_id)?;
         }
+        ("gas-profile", Some(m)) => {
+            let txn_id = m.value_of("txn-id").expect("Transaction ID is required!");
+
+            do_gas_profile(client, txn_id)?;
+        }
+        ("gas-diff", Some(m)) => {
+            let old = m.value_of("old").expect("Old profile is required!");
+            let new = m.value_of("new").expect("New profile is required!");
+
+            do_gas_diff(old, new)?;
+        }
         _ => unreachable!(),
     }
 
@@ -56,14 +94,78 @@ pub fn do_show(client: &Client, txn_id: &str) -> Result<(), Error> {
     let receipt: TransactionReceipt =
         client.send_rpc_transaction("eth_getTransactionReceipt", &vec![format!("0x{}", txn_id)])?;
 
-    println!(
-        "{}",
-        to_string_pretty(&json!({
-            "GasUsed": u64::from_str_radix(&receipt.gas_used[2..], 16)?,
-            "Address": receipt.contract_address,
-            "ReturnValue": receipt.return_value,
-        }))?
-    );
+    output::print_value(&json!({
+        "GasUsed": receipt.gas_used.0,
+        "Address": receipt.contract_address,
+        "ReturnValue": receipt.return_value,
+        "Reverted": receipt.status.0 == 0,
+        "RevertReason": receipt.revert_reason,
+    }))?;
+
+    Ok(())
+}
+
+/// Shows the gas profile recorded in the receipt for the given transaction
+pub fn do_gas_profile(client: &Client, txn_id: &str) -> Result<(), Error> {
+    let receipt: TransactionReceipt =
+        client.send_rpc_transaction("eth_getTransactionReceipt", &vec![format!("0x{}", txn_id)])?;
+
+    if receipt.gas_profile.is_empty() {
+        return Err(format_err!(
+            "Receipt has no gas profile, was the processor running with --gas-audit?"
+        ));
+    }
+
+    let mut profile = GasProfile::new();
+    for entry in receipt.gas_profile {
+        profile.insert(entry.opcode, (entry.count.0, entry.gas.0));
+    }
+
+    output::print_value(&json!(profile))?;
+
+    Ok(())
+}
+
+/// Prints each opcode whose execution count or gas differs between two profiles, and
+/// fails if there are any
+pub fn do_gas_diff(old: &str, new: &str) -> Result<(), Error> {
+    let old_profile: GasProfile = from_reader(File::open(old)?)?;
+    let new_profile: GasProfile = from_reader(File::open(new)?)?;
+
+    let mut opcodes: Vec<&String> = old_profile.keys().chain(new_profile.keys()).collect();
+    opcodes.sort();
+    opcodes.dedup();
+
+    let mut differences = Vec::new();
+    for opcode in opcodes {
+        let old_entry = old_profile.get(opcode).cloned().unwrap_or((0, 0));
+        let new_entry = new_profile.get(opcode).cloned().unwrap_or((0, 0));
+        if old_entry != new_entry {
+            if !output::is_json() {
+                println!(
+                    "{:<14} count {} -> {}, gas {} -> {}",
+                    opcode, old_entry.0, new_entry.0, old_entry.1, new_entry.1
+                );
+            }
+            differences.push(json!({
+                "Opcode": opcode,
+                "Count": [old_entry.0, new_entry.0],
+                "Gas": [old_entry.1, new_entry.1],
+            }));
+        }
+    }
+
+    if !differences.is_empty() {
+        if output::is_json() {
+            output::print_value(&json!({ "Differences": differences }))?;
+        }
+        return Err(format_err!("{} opcodes differ", differences.len()));
+    }
+
+    output::print(
+        "Gas profiles are identical",
+        &json!({ "Differences": differences }),
+    )?;
 
     Ok(())
 }
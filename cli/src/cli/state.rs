@@ -0,0 +1,153 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Export the accounts of a block's state and seed another network with them
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use client::Client;
+use failure::Error;
+use output;
+use serde_json::{self, Value};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use types::{GenesisAccount, StateSnapshot};
+
+/// How many accounts are imported with each request
+const IMPORT_CHUNK_SIZE: usize = 64;
+
+/// Returns Clap configuration
+pub fn get_cli<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("state")
+        .about("Exports the accounts of a block's state and imports them into a network")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommands(vec![
+            SubCommand::with_name("export")
+                .about("Saves every account of a block's state to a snapshot")
+                .long_about(
+                    "Saves every account of a block's state, with its balance, nonce, \
+                     code, storage and permissions, to a JSON snapshot. Its `alloc` is \
+                     the `alloc` of a geth genesis file.",
+                )
+                .args(&[
+                    Arg::with_name("block")
+                        .long("block")
+                        .takes_value(true)
+                        .help("Number of the block to export the state of, by default the latest"),
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .takes_value(true)
+                        .help("File to save the snapshot to, instead of printing it"),
+                ]),
+            SubCommand::with_name("import")
+                .about("Creates the accounts of a snapshot saved by `seth state export`")
+                .long_about(
+                    "Creates the accounts of a snapshot saved by `seth state export` with \
+                     transactions sent from the account seth-rpc has unlocked, which must \
+                     have the root permission. None of the accounts may exist yet, so a \
+                     new network should be seeded right after its first account is \
+                     created.",
+                )
+                .args(&[Arg::with_name("input")
+                    .required(true)
+                    .help("File to read the snapshot from")]),
+        ])
+}
+
+/// Handles parsing Clap CLI matches
+pub fn parse_cli<'a>(matches: (&'a str, Option<&'a ArgMatches>)) -> Result<(), Error> {
+    let client = &::client::Client::new()?;
+
+    match matches {
+        ("export", Some(m)) => {
+            let block = match m.value_of("block") {
+                Some(block) => Some(block.parse::<u64>()?),
+                None => None,
+            };
+
+            do_export(client, block, m.value_of("output"))?;
+        }
+        ("import", Some(m)) => {
+            let input = m.value_of("input").expect("Input is required!");
+
+            do_import(client, input)?;
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+/// Saves the accounts of a block's state, or of the latest block, to a file, or
+/// prints them
+pub fn do_export(client: &Client, block: Option<u64>, output: Option<&str>) -> Result<(), Error> {
+    let block = block.map_or_else(|| String::from("latest"), |block| format!("{:#x}", block));
+    let snapshot: StateSnapshot = client.send_rpc_transaction("seth_exportState", &vec![block])?;
+
+    let output = match output {
+        Some(output) => output,
+        None => return output::print_value(&serde_json::to_value(&snapshot)?),
+    };
+    serde_json::to_writer_pretty(File::create(output)?, &snapshot)?;
+
+    output::print(
+        &format!(
+            "Exported {} accounts of block {}",
+            snapshot.alloc.len(),
+            snapshot.block_number.0
+        ),
+        &json!({
+            "Accounts": snapshot.alloc.len(),
+            "Block": snapshot.block_number.0,
+            "Output": output,
+        }),
+    )?;
+
+    Ok(())
+}
+
+/// Creates the accounts of a snapshot a few at a time
+pub fn do_import(client: &Client, input: &str) -> Result<(), Error> {
+    let mut file: Value = serde_json::from_slice(&fs::read(input)?)?;
+    let alloc: BTreeMap<String, GenesisAccount> =
+        serde_json::from_value(file.get_mut("alloc").map(Value::take).unwrap_or(file))
+            .map_err(|err| format_err!("File doesn't hold a state snapshot: {}", err))?;
+    let accounts: Vec<(String, GenesisAccount)> = alloc.into_iter().collect();
+
+    for (index, chunk) in accounts.chunks(IMPORT_CHUNK_SIZE).enumerate() {
+        let chunk: BTreeMap<&String, &GenesisAccount> = chunk
+            .iter()
+            .map(|(address, account)| (address, account))
+            .collect();
+        let _: Vec<String> = client
+            .send_rpc_transaction("seth_importState", &json!([chunk]))
+            .map_err(|err| {
+                format_err!(
+                    "Failed to import accounts after {}: {}",
+                    index * IMPORT_CHUNK_SIZE,
+                    err
+                )
+            })?;
+    }
+
+    output::print(
+        &format!("Imported {} accounts", accounts.len()),
+        &json!({ "Imported": accounts.len() }),
+    )?;
+
+    Ok(())
+}
@@ -21,14 +21,16 @@
 
 use config::{Config, Environment, File};
 use dirs;
-use failure::Error;
+use failure::{Error, Fail};
 use jsonrpc_core::Value;
 use reqwest;
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
+use std::fmt;
 use std::thread::sleep;
 use std::time::Duration;
 use time;
+use types::ErrorCode;
 
 /// Client settings
 #[derive(Debug, Serialize, Deserialize)]
@@ -39,11 +41,48 @@ pub struct Settings {
 /// An error returned by the JSON-RPC API
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct JsonRpcError {
+pub struct JsonRpcError {
     code: i64,
     message: String,
+    data: Option<Value>,
 }
 
+impl JsonRpcError {
+    /// Returns the code of the error in the seth error catalogue, if the server gave
+    /// one
+    pub fn error_code(&self) -> Option<ErrorCode> {
+        self.data
+            .as_ref()
+            .and_then(|data| data.get("code"))
+            .and_then(Value::as_u64)
+            .and_then(ErrorCode::from_code)
+    }
+}
+
+impl fmt::Display for JsonRpcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+impl Fail for JsonRpcError {}
+
+/// A transaction that was committed but failed, which the CLI exits with the code of
+#[derive(Debug)]
+pub struct TransactionFailed {
+    pub transaction_id: String,
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl fmt::Display for TransactionFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Fail for TransactionFailed {}
+
 /// A response from the JSON-RPC API
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -130,7 +169,7 @@ impl Client {
         match (body.result, body.error) {
             (Some(_), Some(_)) => Err(format_err!("Got both a result and an error!"))?,
             (Some(res), None) => Ok(res),
-            (None, Some(err)) => Err(format_err!("{:?}", err)),
+            (None, Some(err)) => Err(err)?,
             (None, None) => Err(format_err!("Got an empty response!"))?,
         }
     }
@@ -35,6 +35,7 @@ extern crate dirs;
 extern crate failure;
 extern crate jsonrpc_core;
 extern crate reqwest;
+extern crate sawtooth_seth_types as types;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
@@ -44,9 +45,10 @@ extern crate time;
 extern crate tiny_keccak;
 extern crate toml;
 
+pub mod abi;
 pub mod cli;
 pub mod client;
-pub mod types;
+pub mod output;
 
 use clap::{App, AppSettings, Arg, ArgMatches};
 use failure::Error;
@@ -96,39 +98,83 @@ fn run() -> Result<(), Error> {
         .version(crate_version!())
         .about(crate_description!())
         .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(output::FORMATS)
+                .default_value("text")
+                .global(true)
+                .help("Print results and errors as text, or as JSON for scripts"),
+        )
         .subcommands(vec![
             cli::account::get_cli(),
+            cli::batch::get_cli(),
             cli::config::get_cli(),
             cli::contract::get_cli(),
             cli::event::get_cli(),
             cli::permissions::get_cli(),
             cli::receipt::get_cli(),
+            cli::state::get_cli(),
         ])
         .get_matches();
 
+    output::set_format(format_of(&matches));
+
     match matches.subcommand() {
         ("account", Some(am)) => cli::account::parse_cli(am.subcommand())?,
+        ("batch", Some(bm)) => cli::batch::parse_cli(bm.subcommand())?,
         ("config", Some(sm)) => cli::config::parse_cli(sm.subcommand())?,
         ("contract", Some(cm)) => cli::contract::parse_cli(cm.subcommand())?,
         ("event", Some(em)) => cli::event::parse_cli(em.subcommand())?,
         ("permissions", Some(pm)) => cli::permissions::parse_cli(pm.subcommand())?,
         ("receipt", Some(rm)) => cli::receipt::parse_cli(rm.subcommand())?,
+        ("state", Some(sm)) => cli::state::parse_cli(sm.subcommand())?,
         _ => unreachable!(),
     }
 
     Ok(())
 }
 
+/// Returns the `--format` given, which can follow any of the subcommands
+fn format_of<'a>(matches: &'a ArgMatches) -> &'a str {
+    match matches.subcommand() {
+        (_, Some(subcommand)) => format_of(subcommand),
+        _ => matches.value_of("format").unwrap_or("text"),
+    }
+}
+
 fn main() {
     // Attempt to run command, and print out any errors encountered
     if let Err(e) = run() {
-        eprint!("Error: {}", e);
-        let mut e = e.as_fail();
-        while let Some(cause) = e.cause() {
-            eprint!(", {}", cause);
-            e = cause;
+        let mut message = e.to_string();
+        let mut fail = e.as_fail();
+        while let Some(cause) = fail.cause() {
+            message.push_str(&format!(", {}", cause));
+            fail = cause;
+        }
+
+        // Errors from the catalogue, and transactions that failed, exit with their
+        // code, so scripts can tell them apart
+        let failed = e.downcast_ref::<client::TransactionFailed>();
+        let code = e
+            .downcast_ref::<client::JsonRpcError>()
+            .and_then(client::JsonRpcError::error_code)
+            .or_else(|| failed.map(|failed| failed.code));
+
+        if output::is_json() {
+            eprintln!(
+                "{}",
+                json!({
+                    "Error": message,
+                    "Code": code.map(types::ErrorCode::code),
+                    "Name": code.map(types::ErrorCode::name),
+                    "TransactionID": failed.map(|failed| &failed.transaction_id),
+                })
+            );
+        } else {
+            eprintln!("Error: {}", message);
         }
-        eprintln!();
-        std::process::exit(1);
+        std::process::exit(code.map_or(1, |code| i32::from(code.code())));
     }
 }
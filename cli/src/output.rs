@@ -0,0 +1,66 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! How commands print what they did. They print text for people by default, and
+//! with `--format json` each prints one JSON value on one line, and errors are
+//! printed as JSON too, so that scripts can parse them.
+
+use failure::Error;
+use serde_json::{to_string, to_string_pretty, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// The formats of `--format`
+pub const FORMATS: &[&str] = &["text", "json"];
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+pub fn set_format(format: &str) {
+    JSON.store(format == "json", Ordering::Relaxed);
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Prints a value, indented unless the output is JSON
+pub fn print_value(value: &Value) -> Result<(), Error> {
+    if is_json() {
+        println!("{}", to_string(value)?);
+    } else {
+        println!("{}", to_string_pretty(value)?);
+    }
+    Ok(())
+}
+
+/// Prints a message, or the value if the output is JSON
+pub fn print(text: &str, value: &Value) -> Result<(), Error> {
+    if is_json() {
+        println!("{}", to_string(value)?);
+    } else {
+        println!("{}", text);
+    }
+    Ok(())
+}
+
+/// What is shown for a value that wasn't waited for
+pub fn not_retrieved() -> Value {
+    if is_json() {
+        Value::Null
+    } else {
+        Value::from("<Not retrieved>")
+    }
+}
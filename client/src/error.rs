@@ -0,0 +1,76 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+use reqwest;
+use serde_json;
+use std::error::Error as StdError;
+use std::fmt;
+use types::ErrorCode;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The server couldn't be reached or didn't respond with JSON
+    Http(reqwest::Error),
+    /// The server returned an error code and message, and the code of the error in
+    /// the seth error catalogue if it has one
+    Rpc(i64, String, Option<ErrorCode>),
+    /// The result didn't have the type of the binding
+    Parse(serde_json::Error),
+}
+
+impl Error {
+    /// Returns the code of the error in the seth error catalogue, for errors the
+    /// server gave one
+    pub fn code(&self) -> Option<ErrorCode> {
+        match *self {
+            Error::Rpc(_, _, code) => code,
+            _ => None,
+        }
+    }
+}
+
+impl StdError for Error {
+    fn cause(&self) -> Option<&dyn StdError> {
+        match *self {
+            Error::Http(ref err) => Some(err),
+            Error::Rpc(..) => None,
+            Error::Parse(ref err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Http(ref err) => write!(f, "HttpError: {}", err),
+            Error::Rpc(code, ref msg, _) => write!(f, "RpcError: {} ({})", msg, code),
+            Error::Parse(ref err) => write!(f, "ParseError: {}", err),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Http(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Parse(err)
+    }
+}
@@ -0,0 +1,114 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Typed bindings for the JSON-RPC API served by seth-rpc
+//!
+//! ```no_run
+//! # use sawtooth_seth_client::{Error, SethClient};
+//! # fn main() -> Result<(), Error> {
+//! let client = SethClient::new("http://seth-rpc:3030/");
+//! let block = client.block_number()?;
+//! let receipt = client.wait_for_transaction_receipt("0x1234", Some(30))?;
+//! # Ok(())
+//! # }
+//! ```
+
+extern crate reqwest;
+pub extern crate sawtooth_seth_types as types;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+mod error;
+mod methods;
+
+pub use error::Error;
+pub use methods::METHODS;
+
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use types::ErrorCode;
+
+/// A response from the JSON-RPC API
+#[derive(Debug, Deserialize)]
+struct JsonRpcResponse {
+    result: Option<Value>,
+    error: Option<JsonRpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonRpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+/// A client for a seth-rpc server. Every method of the server has a binding on this
+/// type, named after the method without its namespace.
+pub struct SethClient {
+    url: String,
+    client: reqwest::Client,
+    next_id: AtomicUsize,
+}
+
+impl SethClient {
+    pub fn new(url: &str) -> Self {
+        SethClient {
+            url: url.into(),
+            client: reqwest::Client::new(),
+            next_id: AtomicUsize::new(1),
+        }
+    }
+
+    /// Calls a method of the server, for methods that don't have a binding yet
+    pub fn request<R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Vec<Value>,
+    ) -> Result<R, Error> {
+        let mut request = Map::new();
+        request.insert(String::from("jsonrpc"), Value::String(String::from("2.0")));
+        request.insert(
+            String::from("id"),
+            Value::from(self.next_id.fetch_add(1, Ordering::SeqCst)),
+        );
+        request.insert(String::from("method"), Value::String(method.into()));
+        request.insert(String::from("params"), Value::Array(params));
+
+        let response: JsonRpcResponse = self
+            .client
+            .post(&self.url)
+            .json(&Value::Object(request))
+            .send()?
+            .json()?;
+
+        match (response.result, response.error) {
+            (_, Some(error)) => {
+                let code = error
+                    .data
+                    .as_ref()
+                    .and_then(|data| data.get("code"))
+                    .and_then(Value::as_u64)
+                    .and_then(ErrorCode::from_code);
+                Err(Error::Rpc(error.code, error.message, code))
+            }
+            (result, None) => Ok(serde_json::from_value(result.unwrap_or(Value::Null))?),
+        }
+    }
+}
@@ -0,0 +1,282 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+use error::Error;
+use serde_json;
+use std::collections::BTreeMap;
+use types::*;
+use SethClient;
+
+/// Generates a binding on `SethClient` for each method in the table, which sends its
+/// arguments as the positional params of the method and parses its result
+macro_rules! rpc_methods {
+    ($(
+        $(#[$attr:meta])*
+        fn $name:ident($($arg:ident: $ty:ty),*) -> $ret:ty = $method:expr;
+    )*) => {
+        /// The methods that have bindings, in the order of the table
+        pub const METHODS: &[&str] = &[$($method),*];
+
+        impl SethClient {
+            $(
+                $(#[$attr])*
+                pub fn $name(&self, $($arg: $ty),*) -> Result<$ret, Error> {
+                    self.request($method, vec![$(serde_json::to_value($arg)?),*])
+                }
+            )*
+        }
+    };
+}
+
+rpc_methods! {
+    // -- account --
+    fn get_balance(address: &str, block: BlockId) -> Wei = "eth_getBalance";
+    fn get_storage_at(address: &str, position: &str, block: BlockId) -> Option<String> = "eth_getStorageAt";
+    fn get_code(address: &str, block: BlockId) -> String = "eth_getCode";
+    fn get_code_by_hash(code_hash: &str) -> Option<String> = "eth_getCodeByHash";
+    fn accounts() -> Vec<String> = "eth_accounts";
+    fn get_transaction_count(address: &str, block: BlockId) -> Quantity = "eth_getTransactionCount";
+    /// The format is "sawtooth" unless "mpt" is given, for storage proofs of the trie
+    /// Ethereum would keep the account's storage in
+    fn get_proof(address: &str, storage_keys: &[&str], block: BlockId, format: Option<&str>) -> AccountProof =
+        "eth_getProof";
+    /// Returns the 65 byte signature of the EIP-712 hash of the typed data
+    fn sign_typed_data_v4(address: &str, typed_data: &serde_json::Value) -> String =
+        "eth_signTypedData_v4";
+
+    // -- block --
+    fn block_number() -> Quantity = "eth_blockNumber";
+    fn get_block_by_hash(hash: &str, full: bool) -> Option<Block> = "eth_getBlockByHash";
+    fn get_block_by_number(block: BlockId, full: bool) -> Option<Block> = "eth_getBlockByNumber";
+    fn get_block_transaction_count_by_hash(hash: &str) -> Option<Quantity> =
+        "eth_getBlockTransactionCountByHash";
+    fn get_block_transaction_count_by_number(block: BlockId) -> Option<Quantity> =
+        "eth_getBlockTransactionCountByNumber";
+
+    // -- logs --
+    fn new_filter(filter: &LogFilter) -> Quantity = "eth_newFilter";
+    fn new_block_filter() -> Quantity = "eth_newBlockFilter";
+    fn new_pending_transaction_filter() -> Quantity = "eth_newPendingTransactionFilter";
+    fn uninstall_filter(filter_id: Quantity) -> bool = "eth_uninstallFilter";
+    fn get_filter_changes(filter_id: Quantity) -> FilterChanges = "eth_getFilterChanges";
+    fn get_filter_logs(filter_id: Quantity) -> Vec<TransactionLog> = "eth_getFilterLogs";
+    fn get_logs(filter: &LogFilter) -> Vec<TransactionLog> = "eth_getLogs";
+
+    // -- network --
+    fn chain_id() -> Quantity = "eth_chainId";
+    fn net_version() -> String = "net_version";
+    fn net_peer_count() -> Quantity = "net_peerCount";
+    fn net_listening() -> bool = "net_listening";
+
+    // -- transaction --
+    /// Returns the return value of the call
+    fn call(txn: &TransactionRequest, block: BlockId) -> String = "eth_call";
+    fn estimate_gas(txn: &TransactionRequest) -> Quantity = "eth_estimateGas";
+    /// Returns the base fee of the latest block
+    fn gas_price() -> Wei = "eth_gasPrice";
+    fn get_transaction_by_block_hash_and_index(hash: &str, index: Quantity) -> Option<Transaction> =
+        "eth_getTransactionByBlockHashAndIndex";
+    fn get_transaction_by_block_number_and_index(block: BlockId, index: Quantity) -> Option<Transaction> =
+        "eth_getTransactionByBlockNumberAndIndex";
+    fn get_transaction_by_hash(hash: &str) -> Option<Transaction> = "eth_getTransactionByHash";
+    fn get_transaction_receipt(hash: &str) -> Option<TransactionReceipt> = "eth_getTransactionReceipt";
+    /// Returns 0, since block producers aren't paid to include transactions
+    fn max_priority_fee_per_gas() -> Wei = "eth_maxPriorityFeePerGas";
+    fn fee_history(block_count: Quantity, newest_block: BlockId, reward_percentiles: &[f64]) -> FeeHistory =
+        "eth_feeHistory";
+    /// Returns the hash of the transaction
+    fn send_raw_transaction(data: &str) -> String = "eth_sendRawTransaction";
+    /// Returns the hash of the transaction
+    fn send_transaction(txn: &TransactionRequest) -> String = "eth_sendTransaction";
+    fn sign(address: &str, data: &str) -> String = "eth_sign";
+    fn syncing() -> SyncStatus = "eth_syncing";
+
+    // -- txpool --
+    fn txpool_status() -> TxPoolStatus = "txpool_status";
+    /// Returns the transactions this server sent that haven't been committed yet
+    fn txpool_content() -> TxPoolContent<Transaction> = "txpool_content";
+    /// Returns a summary of each transaction this server sent that hasn't been
+    /// committed yet
+    fn txpool_inspect() -> TxPoolContent<String> = "txpool_inspect";
+
+    // -- personal --
+    fn list_accounts() -> Vec<String> = "personal_listAccounts";
+    /// Returns the address of the new account
+    fn new_account(password: Option<&str>, moderator: Option<&str>, permissions: Option<&str>) -> String =
+        "personal_newAccount";
+    fn unlock_account(address: &str, password: Option<&str>, duration: Option<u64>) -> bool =
+        "personal_unlockAccount";
+    fn lock_account(address: &str) -> bool = "personal_lockAccount";
+    /// Returns the hash of the transaction
+    fn send_transaction_with_password(txn: &TransactionRequest, password: &str) -> String =
+        "personal_sendTransaction";
+    /// Returns the address of the imported account
+    fn import_raw_key(key: &str, password: Option<&str>) -> String = "personal_importRawKey";
+    /// Returns the key of the account as a JSON keystore
+    fn export_keystore(address: &str, password: &str) -> serde_json::Value =
+        "personal_exportKeystore";
+    /// Returns a new BIP-39 mnemonic of 12 words by default, which seth-rpc doesn't keep
+    fn new_mnemonic(words: Option<usize>) -> String = "personal_newMnemonic";
+    /// Returns the addresses of the first `count` accounts of the mnemonic, once imported
+    fn import_mnemonic(mnemonic: &str, password: Option<&str>, count: Option<u32>) -> Vec<String> =
+        "personal_importMnemonic";
+
+    // -- seth --
+    /// Returns the hash of the transaction, whose receipt holds the archive
+    fn archive_contract(address: &str) -> String = "seth_archiveContract";
+    /// Returns the `output`, `gasUsed` and `error` of the call, and the `witness` of the
+    /// state it read
+    fn call_with_witness(txn: &TransactionRequest, block: BlockId) -> serde_json::Value =
+        "seth_callWithWitness";
+    /// Returns the outputs of the function, integers as decimal strings, decoded with
+    /// the ABI the contract had at `block`, or has now
+    fn decode_result(address: &str, function: &str, data: &str, block: Option<BlockId>) -> Vec<serde_json::Value> =
+        "seth_decodeResult";
+    /// Returns the input data of a call to the function
+    fn encode_call(address: &str, function: &str, args: &[serde_json::Value]) -> String =
+        "seth_encodeCall";
+    /// Returns the batches of up to `count` blocks from `from`, which may be fewer to
+    /// keep the response small
+    fn export_batches(from: Quantity, count: Quantity) -> ExportedBatches =
+        "seth_exportBatches";
+    /// Returns every account in the state of the block, by address
+    fn export_state(block: BlockId) -> StateSnapshot = "seth_exportState";
+    /// Returns the number of results removed from the cache
+    fn flush_call_cache() -> Quantity = "seth_flushCallCache";
+    /// Returns the JSON text of a report of what the watched addresses did between two
+    /// times in seconds, signed by `signer`. Needs seth-rpc to keep a log index.
+    fn get_activity_report(addresses: &[&str], from_time: Quantity, to_time: Quantity, signer: &str) -> SignedReport =
+        "seth_getActivityReport";
+    /// Returns the receipts, logs bloom and state diff of the block, and the traces of
+    /// its transactions if `options` has `"traces": true`, or null if there's no such
+    /// block
+    fn get_block_artifacts(block: BlockId, options: Option<&serde_json::Value>) -> serde_json::Value =
+        "seth_getBlockArtifacts";
+    /// Returns the signed headers of up to `count` blocks from `from`, each as a 4 byte
+    /// big-endian length, the serialized header and its 64 byte signature
+    fn get_block_headers(from: Quantity, count: Quantity) -> String = "seth_getBlockHeaders";
+    /// Returns the `implementation` behind the proxies an address delegates through,
+    /// and the `proxies`
+    fn get_clone_implementations(address: &str, block: Option<BlockId>) -> serde_json::Value =
+        "seth_getCloneImplementations";
+    fn get_consensus_info(block: BlockId) -> ConsensusInfo = "seth_getConsensusInfo";
+    /// Fails while seth-rpc can't reach the validator
+    fn get_health() -> Health = "seth_getHealth";
+    /// Returns up to `limit` logs, ordered like eth_getLogs, starting after the log
+    /// the cursor of the previous page points at
+    fn get_logs_page(filter: &LogFilter, limit: Quantity, cursor: Option<&str>) -> LogPage =
+        "seth_getLogsPage";
+    /// Returns the account's nonce and the one seth-rpc gives its next transaction
+    fn get_nonce_status(address: &str) -> NonceStatus = "seth_getNonceStatus";
+    /// Returns the permissions of one account if an address is given, which may be
+    /// `global`, or else every account whose permissions are set
+    fn get_permissions(address: Option<&str>, block: BlockId) -> PermissionsResult =
+        "seth_getPermissions";
+    /// Needs seth-rpc to be started with --shadow-fork
+    fn get_shadow_report() -> ShadowReport = "seth_getShadowReport";
+    /// Returns the stats of one contract if an address is given, or else of every
+    /// contract
+    fn get_storage_stats(address: Option<&str>, block: BlockId) -> StorageStatsResult =
+        "seth_getStorageStats";
+    /// Returns how many times each client called each method seth-rpc doesn't serve
+    fn get_unsupported_methods() -> Vec<UnsupportedMethodCalls> = "seth_getUnsupportedMethods";
+    /// Needs seth-rpc to keep a log index
+    fn get_watched_address_report(address: &str, from: Option<Quantity>, to: Option<Quantity>) -> WatchedAddressReport =
+        "seth_getWatchedAddressReport";
+    fn get_watchlists() -> serde_json::Value = "seth_getWatchlists";
+    /// Returns the ids of the batches, once each has been validated
+    fn import_batches(batches: &str) -> Vec<String> = "seth_importBatches";
+    /// Returns the hashes of the transactions creating the accounts, in order
+    fn import_state(alloc: &BTreeMap<String, GenesisAccount>) -> Vec<String> =
+        "seth_importState";
+    /// Returns the name of an address, if it has one that resolves back to it
+    fn lookup_address(address: &str) -> Option<String> = "seth_lookupAddress";
+    /// Returns the latest of `versions` that seth-rpc serves the seth_* methods of
+    fn negotiate_version(versions: &[&str]) -> VersionNegotiation = "seth_negotiateVersion";
+    fn new_permission_filter() -> Quantity = "seth_newPermissionFilter";
+    /// Returns the signer of `message`, which is hex data signed with `eth_sign` or
+    /// EIP-712 typed data, given a signature that ends with its recovery id
+    fn recover_address(message: &serde_json::Value, signature: &str) -> Option<String> =
+        "seth_recoverAddress";
+    /// Registers the ABI the contract has from `from_block` on, or from the first block
+    fn register_contract(address: &str, abi: &serde_json::Value, from_block: Option<Quantity>) -> bool =
+        "seth_registerContract";
+    /// Returns the `output`, `gasUsed` and `error` of the call, run against a witness
+    fn replay_call(txn: &TransactionRequest, witness: &serde_json::Value) -> serde_json::Value =
+        "seth_replayCall";
+    /// Returns how many of the account's transactions seth-rpc forgot
+    fn reset_nonces(address: &str) -> Quantity = "seth_resetNonces";
+    fn resolve_name(name: &str) -> Option<String> = "seth_resolveName";
+    /// Returns the hash of the transaction
+    fn resurrect_contract(archive: &str) -> String = "seth_resurrectContract";
+    fn set_permissions(address: &str, permissions: &str) -> bool = "seth_setPermissions";
+    /// Replaces the addresses on a watchlist, or removes it if none are given
+    fn set_watchlist(name: &str, addresses: &[&str]) -> bool = "seth_setWatchlist";
+    /// Returns the hashes of the transactions, which are run in the given order
+    fn submit_ordered(txns: &[TransactionRequest]) -> Vec<String> = "seth_submitOrdered";
+    fn verify_signature(address: &str, message: &serde_json::Value, signature: &str) -> bool =
+        "seth_verifySignature";
+    /// Waits up to `timeout` seconds for the transaction to be committed
+    fn wait_for_transaction_receipt(hash: &str, timeout: Option<u64>) -> Option<TransactionReceipt> =
+        "seth_waitForTransactionReceipt";
+
+    // -- web3 --
+    fn client_version() -> String = "web3_clientVersion";
+    /// Returns the Keccak-256 hash of the data
+    fn sha3(data: &str) -> String = "web3_sha3";
+
+    // -- debug --
+    /// Returns the steps the call took as `trace_transaction` does, or with the
+    /// `prestateTracer` the accounts and storage it read
+    fn trace_call(txn: &TransactionRequest, block: BlockId, options: Option<&serde_json::Value>) -> serde_json::Value =
+        "debug_traceCall";
+    /// Returns the steps the transaction took in the EVM, as geth's struct logger does
+    fn trace_transaction(hash: &str, options: Option<&serde_json::Value>) -> serde_json::Value =
+        "debug_traceTransaction";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    /// Reads the method names out of the `get_method_list` tables of seth-rpc
+    fn server_methods() -> BTreeSet<String> {
+        let calls = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("../rpc/src/calls");
+        let mut methods = BTreeSet::new();
+        for entry in fs::read_dir(calls).unwrap() {
+            let source = fs::read_to_string(entry.unwrap().path()).unwrap();
+            let pieces: Vec<&str> = source.split('"').collect();
+            for pair in pieces.windows(2) {
+                if pair[1].starts_with(".into(),") && pair[0].contains('_') {
+                    methods.insert(String::from(pair[0]));
+                }
+            }
+        }
+        methods
+    }
+
+    #[test]
+    fn every_server_method_has_a_binding() {
+        let bound: BTreeSet<String> = METHODS.iter().map(|m| String::from(*m)).collect();
+        assert_eq!(bound.len(), METHODS.len());
+        assert_eq!(server_methods(), bound);
+    }
+}
@@ -28,12 +28,20 @@ const (
 	GAS_LIMIT            = 1 << 31
 	FAMILY_NAME          = "seth"
 	FAMILY_VERSION       = "1.0"
+	FAMILY_VERSION_1_1   = "1.1"
 	ENCODING             = "application/protobuf"
 	BLOCK_INFO_PREFIX    = "00b10c"
 	BLOCK_INFO_NAMESPACE = BLOCK_INFO_PREFIX + "00"
 	CONFIG_ADDRESS       = BLOCK_INFO_PREFIX + "0100000000000000000000000000000000000000000000000000000000000000"
+	SETTINGS_PREFIX      = "000000"
+	SETTINGS_KEY_PARTS   = 4
+	SETTINGS_PART_LEN    = 16
+	STORAGE_SLOT_LEN     = STATEADDRLEN - PREFIXLEN - EVMADDRLEN
 )
 
+// Precompiles live at addresses whose only nonzero bytes are the last two
+const MAX_PRECOMPILE_ADDRESS = 0xffff
+
 func GlobalPermissionsAddress() *EvmAddr {
 	addr, _ := NewEvmAddrFromBytes([]byte{
 		0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
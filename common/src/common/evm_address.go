@@ -97,6 +97,23 @@ func (ea *EvmAddr) Derive(nonce uint64) *EvmAddr {
 	return derived
 }
 
+// Create2 derives the address CREATE2 gives a contract created by this address
+// with the given salt and init code, as Ethereum does, from the last 20 bytes of
+// keccak256(0xff ++ creator ++ salt ++ keccak256(init))
+func (ea *EvmAddr) Create2(salt []byte, initCode []byte) *EvmAddr {
+	buf := make([]byte, 0, 1+EVMADDRLEN+len(salt)+32)
+	buf = append(buf, 0xff)
+	buf = append(buf, ea.Bytes()...)
+	buf = append(buf, salt...)
+	buf = append(buf, sha3.Sha3(initCode)...)
+
+	derived, err := NewEvmAddrFromBytes(sha3.Sha3(buf)[32-EVMADDRLEN:])
+	if err != nil {
+		panic(err.Error())
+	}
+	return derived
+}
+
 func (ea *EvmAddr) ToWord256() binary.Word256 {
 	return binary.LeftPadWord256(ea.Bytes())
 }
@@ -18,9 +18,11 @@
 package common
 
 import (
+	"crypto/sha256"
 	"encoding/hex"
 	"fmt"
 	"github.com/hyperledger/burrow/binary"
+	"strings"
 )
 
 type StateAddr string
@@ -57,6 +59,31 @@ func NewBlockInfoAddr(n int64) (StateAddr, error) {
 	return StateAddr(BLOCK_INFO_NAMESPACE + hex.EncodeToString(bytes)), nil
 }
 
+// NewSettingAddr returns the address of a setting in the namespace of the
+// Sawtooth Settings transaction family
+func NewSettingAddr(key string) StateAddr {
+	parts := strings.SplitN(key, ".", SETTINGS_KEY_PARTS)
+	for len(parts) < SETTINGS_KEY_PARTS {
+		parts = append(parts, "")
+	}
+
+	addr := SETTINGS_PREFIX
+	for _, part := range parts {
+		hash := sha256.Sum256([]byte(part))
+		addr += hex.EncodeToString(hash[:])[:SETTINGS_PART_LEN]
+	}
+	return StateAddr(addr)
+}
+
+// NewStorageAddr returns the address of a storage slot of an account, which is
+// the account's address followed by the start of the SHA256 hash of the key
+// instead of zeros. Slots whose keys hash to the same address are stored
+// together.
+func NewStorageAddr(ea *EvmAddr, key binary.Word256) StateAddr {
+	hash := sha256.Sum256(key.Bytes())
+	return StateAddr(PREFIX + ea.String() + hex.EncodeToString(hash[:STORAGE_SLOT_LEN]))
+}
+
 func (sa StateAddr) String() string {
 	return string(sa)
 }
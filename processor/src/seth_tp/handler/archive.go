@@ -0,0 +1,327 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+package handler
+
+import (
+	"bytes"
+	. "common"
+	"fmt"
+	"github.com/golang/protobuf/proto"
+	"github.com/hyperledger/burrow/acm"
+	"github.com/hyperledger/burrow/binary"
+	"github.com/hyperledger/burrow/crypto"
+	"github.com/hyperledger/burrow/execution/evm/sha3"
+	"github.com/hyperledger/burrow/permission"
+	"github.com/hyperledger/sawtooth-sdk-go/processor"
+	. "protobuf/seth_pb2"
+	"sort"
+)
+
+// ArchiveContract removes a contract from global state, leaving only the hash
+// of its state in its account entry. The state is returned as a serialized
+// ArchivedContract, which is kept in the transaction's receipt, so that
+// networks can clear out contracts that are no longer used without losing the
+// ability to restore them with ResurrectContract. Storage slots the
+// transaction doesn't list are discarded, since their entries are left behind
+// under an older storage generation.
+func ArchiveContract(wrapper *SethTransaction, sender *EvmAddr, sapps *SawtoothAppState) HandlerResult {
+	txn := wrapper.GetArchiveContract()
+
+	senderAcct, err := getRootSender(sapps, sender, txn.GetNonce())
+	if err != nil {
+		return HandlerResult{Error: err}
+	}
+
+	contract, err := NewEvmAddrFromBytes(txn.GetTo())
+	if err != nil {
+		return HandlerResult{
+			Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+				"Failed to construct address of contract to archive: %v", txn.GetTo(),
+			)},
+		}
+	}
+
+	entry, err := sapps.mgr.GetEntry(contract)
+	if err != nil {
+		return HandlerResult{Error: err}
+	}
+	if entry == nil || entry.GetAccount() == nil || len(entry.GetAccount().GetCode()) == 0 {
+		return HandlerResult{
+			Error: rejected(ERR_ACCOUNT_NOT_FOUND,
+				"Only existing contracts can be archived: %v", contract,
+			),
+		}
+	}
+
+	// Collect the storage still in the account entry and the listed slots
+	storage := make(map[binary.Word256]binary.Word256)
+	for _, pair := range entry.GetStorage() {
+		storage[binary.LeftPadWord256(pair.GetKey())] = binary.LeftPadWord256(pair.GetValue())
+	}
+	address := crypto.AddressFromWord256(contract.ToWord256())
+	for _, k := range txn.GetStorageKeys() {
+		if len(k) > 32 {
+			return HandlerResult{
+				Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+					"Storage key is longer than 32 bytes: %x", k,
+				)},
+			}
+		}
+		key := binary.LeftPadWord256(k)
+		value, err := sapps.GetStorage(address, key)
+		if err != nil {
+			return HandlerResult{Error: err}
+		}
+		storage[key] = value
+
+		err = sapps.deleteStorage(contract, key)
+		if err != nil {
+			return HandlerResult{Error: err}
+		}
+	}
+
+	archive := &ArchivedContract{Account: entry.GetAccount()}
+	for key, value := range storage {
+		if value != binary.Zero256 {
+			archive.Storage = append(archive.Storage, &EvmStorage{
+				Key:   key.Bytes(),
+				Value: value.Bytes(),
+			})
+		}
+	}
+	sort.Slice(archive.Storage, func(i, j int) bool {
+		return bytes.Compare(archive.Storage[i].Key, archive.Storage[j].Key) < 0
+	})
+
+	archiveData, err := proto.Marshal(archive)
+	if err != nil {
+		return HandlerResult{
+			Error: &processor.InternalError{Msg: fmt.Sprintf(
+				"Couldn't marshal archived contract: %v", err,
+			)},
+		}
+	}
+
+	logger.Debugf("ArchiveContract(%v): %v storage slots", contract, len(archive.Storage))
+
+	sapps.mgr.MustSetEntry(contract, &EvmEntry{
+		ArchiveHash:       sha3.Sha3(archiveData),
+		StorageGeneration: entry.GetStorageGeneration() + 1,
+	})
+
+	senderAcct.IncSequence()
+	err = sapps.UpdateAccount(senderAcct)
+	if err != nil {
+		return HandlerResult{Error: err}
+	}
+
+	return HandlerResult{ReturnValue: archiveData}
+}
+
+// ResurrectContract restores a contract archived by ArchiveContract
+func ResurrectContract(wrapper *SethTransaction, sender *EvmAddr, sapps *SawtoothAppState) HandlerResult {
+	txn := wrapper.GetResurrectContract()
+
+	senderAcct, err := getRootSender(sapps, sender, txn.GetNonce())
+	if err != nil {
+		return HandlerResult{Error: err}
+	}
+
+	archive := &ArchivedContract{}
+	err = proto.Unmarshal(txn.GetArchive(), archive)
+	if err != nil || archive.GetAccount() == nil {
+		return HandlerResult{
+			Error: &processor.InvalidTransactionError{Msg: "Malformed archived contract"},
+		}
+	}
+
+	contract, err := NewEvmAddrFromBytes(archive.GetAccount().GetAddress())
+	if err != nil {
+		return HandlerResult{
+			Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+				"Failed to construct address of archived contract: %v",
+				archive.GetAccount().GetAddress(),
+			)},
+		}
+	}
+
+	entry, err := sapps.mgr.GetEntry(contract)
+	if err != nil {
+		return HandlerResult{Error: err}
+	}
+	if entry == nil || len(entry.GetArchiveHash()) == 0 {
+		return HandlerResult{
+			Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+				"Contract is not archived: %v", contract,
+			)},
+		}
+	}
+	if !bytes.Equal(sha3.Sha3(txn.GetArchive()), entry.GetArchiveHash()) {
+		return HandlerResult{
+			Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+				"Archive doesn't match the archived state of contract %v", contract,
+			)},
+		}
+	}
+
+	logger.Debugf("ResurrectContract(%v): %v storage slots", contract, len(archive.Storage))
+
+	sapps.mgr.MustSetEntry(contract, &EvmEntry{
+		Account:           archive.GetAccount(),
+		StorageGeneration: entry.GetStorageGeneration(),
+	})
+
+	address := crypto.AddressFromWord256(contract.ToWord256())
+	for _, pair := range archive.GetStorage() {
+		err = sapps.SetStorage(
+			address,
+			binary.LeftPadWord256(pair.GetKey()),
+			binary.LeftPadWord256(pair.GetValue()),
+		)
+		if err != nil {
+			return HandlerResult{Error: err}
+		}
+	}
+
+	senderAcct.IncSequence()
+	err = sapps.UpdateAccount(senderAcct)
+	if err != nil {
+		return HandlerResult{Error: err}
+	}
+
+	return HandlerResult{}
+}
+
+// ImportAccount creates an account with the given state and storage, which
+// is how a new network is seeded with the accounts exported from another
+func ImportAccount(wrapper *SethTransaction, sender *EvmAddr, sapps *SawtoothAppState) HandlerResult {
+	txn := wrapper.GetImportAccount()
+
+	senderAcct, err := getRootSender(sapps, sender, txn.GetNonce())
+	if err != nil {
+		return HandlerResult{Error: err}
+	}
+
+	imported := txn.GetAccount()
+	if imported.GetAccount() == nil {
+		return HandlerResult{
+			Error: &processor.InvalidTransactionError{Msg: "Imported account is missing"},
+		}
+	}
+
+	account, err := NewEvmAddrFromBytes(imported.GetAccount().GetAddress())
+	if err != nil {
+		return HandlerResult{
+			Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+				"Failed to construct address of imported account: %v",
+				imported.GetAccount().GetAddress(),
+			)},
+		}
+	}
+
+	entry, err := sapps.mgr.GetEntry(account)
+	if err != nil {
+		return HandlerResult{Error: err}
+	}
+	if entry != nil {
+		return HandlerResult{
+			Error: rejected(ERR_ACCOUNT_EXISTS,
+				"Account already exists at address %v", account,
+			),
+		}
+	}
+
+	for _, pair := range imported.GetStorage() {
+		if len(pair.GetKey()) > 32 || len(pair.GetValue()) > 32 {
+			return HandlerResult{
+				Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+					"Storage slot is longer than 32 bytes: %x", pair.GetKey(),
+				)},
+			}
+		}
+	}
+
+	logger.Debugf("ImportAccount(%v): %v storage slots", account, len(imported.Storage))
+
+	sapps.mgr.MustSetEntry(account, &EvmEntry{Account: imported.GetAccount()})
+
+	address := crypto.AddressFromWord256(account.ToWord256())
+	for _, pair := range imported.GetStorage() {
+		err = sapps.SetStorage(
+			address,
+			binary.LeftPadWord256(pair.GetKey()),
+			binary.LeftPadWord256(pair.GetValue()),
+		)
+		if err != nil {
+			return HandlerResult{Error: err}
+		}
+	}
+
+	senderAcct.IncSequence()
+	err = sapps.UpdateAccount(senderAcct)
+	if err != nil {
+		return HandlerResult{Error: err}
+	}
+
+	return HandlerResult{}
+}
+
+// checkNotArchived rejects creating an account at the address of an archived
+// contract, which can't be reused until the contract is resurrected
+func checkNotArchived(sapps *SawtoothAppState, address *EvmAddr) error {
+	entry, err := sapps.mgr.GetEntry(address)
+	if err != nil {
+		return err
+	}
+	if len(entry.GetArchiveHash()) > 0 {
+		return rejected(ERR_ACCOUNT_EXISTS,
+			"A contract is archived at address %v", address,
+		)
+	}
+	return nil
+}
+
+// getRootSender returns the sender of a transaction that requires the root
+// permission, after checking it has it and the nonce matches
+func getRootSender(sapps *SawtoothAppState, sender *EvmAddr, nonce uint64) (*acm.MutableAccount, error) {
+	senderAcctRef, err := sapps.GetAccount(crypto.AddressFromWord256(sender.ToWord256()))
+	if err != nil {
+		return nil, err
+	}
+	senderAcct := acm.AsMutableAccount(senderAcctRef)
+	if senderAcct == nil {
+		return nil, rejected(ERR_ACCOUNT_NOT_FOUND,
+			"Sender account must already exist: %v", sender,
+		)
+	}
+
+	if !hasPermission(sapps, senderAcct, permission.Root) {
+		return nil, rejected(ERR_PERMISSION_DENIED,
+			"Sender account does not have the root permission: %v", sender,
+		)
+	}
+
+	if nonce != senderAcct.Sequence() {
+		return nil, rejected(ERR_INVALID_NONCE,
+			"Nonces do not match: Transaction (%v), State (%v)",
+			nonce, senderAcct.Sequence(),
+		)
+	}
+
+	return senderAcct, nil
+}
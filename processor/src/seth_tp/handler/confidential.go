@@ -0,0 +1,168 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+package handler
+
+import (
+	"bytes"
+	"encoding/hex"
+	"encoding/json"
+	"fmt"
+	"github.com/hyperledger/sawtooth-sdk-go/processor"
+	"net/http"
+	. "protobuf/seth_pb2"
+	"time"
+)
+
+const DECRYPTION_SERVICE_TIMEOUT = 10 * time.Second
+
+// PayloadDecrypter decrypts the confidential payloads of transactions. It is
+// typically backed by an enclave or by a committee that holds shares of the
+// payload key, so that the key itself never reaches the transaction processor.
+type PayloadDecrypter interface {
+	// Decrypt returns the plaintext of the ciphertext, which was encrypted to
+	// the key identified by keyId. If the ciphertext can never be decrypted,
+	// for example because it was encrypted to an unknown key, Decrypt must
+	// return an *UndecryptablePayloadError so the transaction is rejected.
+	// Any other error is treated as transient.
+	Decrypt(keyId, ciphertext []byte) ([]byte, error)
+}
+
+// UndecryptablePayloadError is returned by a PayloadDecrypter when a payload
+// can't be decrypted by any processor on the network
+type UndecryptablePayloadError struct {
+	Msg string
+}
+
+func (err *UndecryptablePayloadError) Error() string {
+	return err.Msg
+}
+
+var payloadDecrypter PayloadDecrypter
+
+// SetPayloadDecrypter sets the decrypter of confidential transactions, which
+// networks enable with the sawtooth.seth.confidential_payloads setting. While
+// they are enabled and no decrypter is set, they fail with an internal error,
+// since other processors may be able to decrypt them. It must be called before
+// the processor starts handling transactions.
+func SetPayloadDecrypter(decrypter PayloadDecrypter) {
+	payloadDecrypter = decrypter
+}
+
+// decryptPayload returns the plaintext payload of a transaction, decrypting
+// it first if the transaction is confidential
+func decryptPayload(plaintext []byte, encrypted *EncryptedPayload, config *EvmConfig) ([]byte, error) {
+	if encrypted == nil {
+		return plaintext, nil
+	}
+
+	if len(plaintext) > 0 {
+		return nil, &processor.InvalidTransactionError{
+			Msg: "Transaction must not contain both a plaintext and an encrypted payload",
+		}
+	}
+
+	if !config.ConfidentialPayloads {
+		return nil, &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+			"Confidential transactions are not enabled by %v", SETTING_CONFIDENTIAL_PAYLOADS,
+		)}
+	}
+
+	if payloadDecrypter == nil {
+		return nil, &processor.InternalError{
+			Msg: "Confidential transactions are enabled but no decryption service is configured",
+		}
+	}
+
+	decrypted, err := payloadDecrypter.Decrypt(encrypted.GetKeyId(), encrypted.GetCiphertext())
+	if err != nil {
+		if _, ok := err.(*UndecryptablePayloadError); ok {
+			return nil, &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+				"Couldn't decrypt payload: %v", err,
+			)}
+		}
+		return nil, &processor.InternalError{Msg: fmt.Sprintf(
+			"Decryption service failed: %v", err,
+		)}
+	}
+
+	return decrypted, nil
+}
+
+// HttpPayloadDecrypter delegates decryption to a service over HTTP. The
+// payload is POSTed as a JSON object with hex encoded "key_id" and
+// "ciphertext" fields, and the service responds with a JSON object with a hex
+// encoded "plaintext" field. The service should respond with 422 if the
+// payload can't be decrypted.
+type HttpPayloadDecrypter struct {
+	url    string
+	client *http.Client
+}
+
+func NewHttpPayloadDecrypter(url string) *HttpPayloadDecrypter {
+	return &HttpPayloadDecrypter{
+		url:    url,
+		client: &http.Client{Timeout: DECRYPTION_SERVICE_TIMEOUT},
+	}
+}
+
+type decryptRequest struct {
+	KeyId      string `json:"key_id"`
+	Ciphertext string `json:"ciphertext"`
+}
+
+type decryptResponse struct {
+	Plaintext string `json:"plaintext"`
+}
+
+func (self *HttpPayloadDecrypter) Decrypt(keyId, ciphertext []byte) ([]byte, error) {
+	body, err := json.Marshal(decryptRequest{
+		KeyId:      hex.EncodeToString(keyId),
+		Ciphertext: hex.EncodeToString(ciphertext),
+	})
+	if err != nil {
+		return nil, err
+	}
+
+	response, err := self.client.Post(self.url, "application/json", bytes.NewReader(body))
+	if err != nil {
+		return nil, err
+	}
+	defer response.Body.Close()
+
+	if response.StatusCode == http.StatusUnprocessableEntity {
+		return nil, &UndecryptablePayloadError{Msg: fmt.Sprintf(
+			"Decryption service rejected payload for key %x", keyId,
+		)}
+	}
+	if response.StatusCode != http.StatusOK {
+		return nil, fmt.Errorf("Decryption service responded with %v", response.Status)
+	}
+
+	var decrypted decryptResponse
+	err = json.NewDecoder(response.Body).Decode(&decrypted)
+	if err != nil {
+		return nil, fmt.Errorf("Malformed response from decryption service: %v", err)
+	}
+
+	plaintext, err := hex.DecodeString(decrypted.Plaintext)
+	if err != nil {
+		return nil, fmt.Errorf("Malformed plaintext from decryption service: %v", err)
+	}
+
+	return plaintext, nil
+}
@@ -0,0 +1,77 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+package handler
+
+import (
+	"fmt"
+	"github.com/hyperledger/sawtooth-sdk-go/processor"
+	"strings"
+)
+
+// The reasons a transaction can be rejected for, sent as the extended data of
+// the rejection so that clients don't have to match on its message. They are
+// the names of the ErrorCode variants in the sawtooth-seth-types crate, which
+// seth-rpc and the CLI report, and are never renamed.
+const (
+	ERR_INVALID_TRANSACTION = "invalid_transaction"
+	ERR_PERMISSION_DENIED   = "permission_denied"
+	ERR_INVALID_NONCE       = "invalid_nonce"
+	ERR_ACCOUNT_NOT_FOUND   = "account_not_found"
+	ERR_ACCOUNT_EXISTS      = "account_exists"
+	ERR_OUT_OF_GAS          = "out_of_gas"
+	ERR_REVERTED            = "reverted"
+	ERR_UNSUPPORTED_FORK    = "unsupported_fork"
+	ERR_INVALID_SALT        = "invalid_salt"
+	ERR_CODE_TOO_LARGE      = "code_too_large"
+)
+
+// rejected returns an InvalidTransactionError that carries one of the codes
+// above
+func rejected(code string, format string, args ...interface{}) *processor.InvalidTransactionError {
+	return &processor.InvalidTransactionError{
+		Msg:          fmt.Sprintf(format, args...),
+		ExtendedData: []byte(code),
+	}
+}
+
+// vmError returns the rejection for a transaction the EVM failed to run
+func vmError(err error) *processor.InvalidTransactionError {
+	code := ERR_INVALID_TRANSACTION
+	switch message := strings.ToLower(err.Error()); {
+	case strings.Contains(message, "insufficient gas"):
+		code = ERR_OUT_OF_GAS
+	case isReverted(err):
+		code = ERR_REVERTED
+	}
+	return rejected(code, "Error while calling VM: %v", err)
+}
+
+// isReverted returns whether the EVM failed to run a transaction because it
+// executed REVERT
+func isReverted(err error) bool {
+	return strings.Contains(strings.ToLower(err.Error()), "execution reverted")
+}
+
+// withCode gives a rejection without a code the generic one, so that every
+// rejection carries a code
+func withCode(err error) error {
+	if invalid, ok := err.(*processor.InvalidTransactionError); ok && len(invalid.ExtendedData) == 0 {
+		invalid.ExtendedData = []byte(ERR_INVALID_TRANSACTION)
+	}
+	return err
+}
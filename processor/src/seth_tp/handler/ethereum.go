@@ -0,0 +1,469 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+package handler
+
+import (
+	. "common"
+	"encoding/binary"
+	"fmt"
+	ellcurv "github.com/btcsuite/btcd/btcec"
+	"github.com/hyperledger/burrow/execution/evm/sha3"
+	"github.com/hyperledger/sawtooth-sdk-go/processor"
+	"math/big"
+	. "protobuf/seth_pb2"
+)
+
+// The chain id EIP-155 signatures must commit to unless the
+// sawtooth.seth.chain_id setting gives another, which seth-rpc reports as its
+// net_version
+const DEFAULT_CHAIN_ID = 19
+
+// The type of EIP-1559 transactions, which the envelope of EIP-2718 starts with
+const DYNAMIC_FEE_TXN_TYPE = 0x02
+
+// EthereumTransaction runs a transaction signed by an Ethereum wallet as
+// the seth account of the key that signed it. Whoever relayed it only pays for
+// the seth transaction it was sent in, so the signature is checked here rather
+// than trusted from the relayer.
+func EthereumTransaction(wrapper *SethTransaction, relayer *EvmAddr, sapps *SawtoothAppState) HandlerResult {
+	raw := wrapper.GetEthereumTransaction().GetRaw()
+
+	txn, err := decodeEthereumTransaction(raw)
+	if err != nil {
+		return HandlerResult{
+			Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+				"Malformed Ethereum transaction: %v", err,
+			)},
+		}
+	}
+
+	// Without the settings, a transaction signed for the default chain id
+	// could be replayed on a network that sets another
+	if !sapps.config.SettingsRead {
+		return HandlerResult{
+			Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+				"Ethereum transactions must list %v in their inputs", SETTINGS_PREFIX,
+			)},
+		}
+	}
+
+	sender, err := txn.sender(sapps.config.ChainId)
+	if err != nil {
+		return HandlerResult{
+			Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+				"Invalid Ethereum transaction signature: %v", err,
+			)},
+		}
+	}
+
+	logger.Debugf("EthereumTransaction(%x): from %v relayed by %v", sha3.Sha3(raw), sender, relayer)
+
+	if txn.dynamicFee && txn.gasPrice < sapps.config.BaseFeePerGas {
+		return HandlerResult{
+			Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+				"Max fee per gas is less than the base fee: %v < %v",
+				txn.gasPrice, sapps.config.BaseFeePerGas,
+			)},
+		}
+	}
+
+	if len(txn.to) == 0 {
+		return CreateContractAccount(&SethTransaction{
+			TransactionType: SethTransaction_CREATE_CONTRACT_ACCOUNT,
+			CreateContractAccount: &CreateContractAccountTxn{
+				Nonce:                txn.nonce,
+				GasPrice:             txn.gasPrice,
+				GasLimit:             txn.gasLimit,
+				Value:                txn.value,
+				Init:                 txn.data,
+				MaxFeePerGas:         txn.maxFeePerGas(),
+				MaxPriorityFeePerGas: txn.maxPriorityFeePerGas,
+			},
+		}, sender, sapps)
+	}
+
+	return MessageCall(&SethTransaction{
+		TransactionType: SethTransaction_MESSAGE_CALL,
+		MessageCall: &MessageCallTxn{
+			Nonce:                txn.nonce,
+			GasPrice:             txn.gasPrice,
+			GasLimit:             txn.gasLimit,
+			To:                   txn.to,
+			Value:                txn.value,
+			Data:                 txn.data,
+			MaxFeePerGas:         txn.maxFeePerGas(),
+			MaxPriorityFeePerGas: txn.maxPriorityFeePerGas,
+		},
+	}, sender, sapps)
+}
+
+// ethereumTransaction is a decoded legacy or EIP-1559 Ethereum transaction.
+// Seth accounts hold 64 bit balances, so wider quantities are rejected when
+// decoding.
+type ethereumTransaction struct {
+	nonce    uint64
+	gasPrice uint64
+	gasLimit uint64
+	to       []byte
+	value    uint64
+	data     []byte
+
+	// Whether this is an EIP-1559 transaction, which signs its chain id and
+	// the y parity of its signature rather than a v, and whose gasPrice is its
+	// max fee per gas
+	dynamicFee           bool
+	maxPriorityFeePerGas uint64
+	signedChainId        uint64
+
+	// The fields as they were encoded, which the signature is over
+	fields [][]byte
+
+	v    *big.Int
+	r, s []byte
+}
+
+func decodeEthereumTransaction(raw []byte) (*ethereumTransaction, error) {
+	if len(raw) > 0 && raw[0] == DYNAMIC_FEE_TXN_TYPE {
+		return decodeDynamicFeeTransaction(raw[1:])
+	}
+	if len(raw) > 0 && raw[0] < 0xc0 {
+		return nil, fmt.Errorf(
+			"Only legacy and type %v transactions are supported, not type %v",
+			DYNAMIC_FEE_TXN_TYPE, raw[0],
+		)
+	}
+	items, encoded, lists, err := rlpSplitFields(raw)
+	if err != nil {
+		return nil, err
+	}
+	for _, isList := range lists {
+		if isList {
+			return nil, fmt.Errorf("Expected an RLP string")
+		}
+	}
+	if len(items) != 9 {
+		return nil, fmt.Errorf("Expected 9 fields, not %v", len(items))
+	}
+
+	txn := &ethereumTransaction{
+		to:     items[3],
+		data:   items[5],
+		fields: encoded[:6],
+		v:      new(big.Int).SetBytes(items[6]),
+		r:      items[7],
+		s:      items[8],
+	}
+	quantities := []*uint64{&txn.nonce, &txn.gasPrice, &txn.gasLimit, nil, &txn.value}
+	for i, quantity := range quantities {
+		if quantity == nil {
+			continue
+		}
+		*quantity, err = rlpUint64(items[i])
+		if err != nil {
+			return nil, err
+		}
+	}
+	if len(txn.to) != 0 && len(txn.to) != EVMADDRLEN {
+		return nil, fmt.Errorf("Recipient must be %v bytes, not %v", EVMADDRLEN, len(txn.to))
+	}
+
+	return txn, nil
+}
+
+// decodeDynamicFeeTransaction decodes an EIP-1559 transaction after its type,
+// the list of its chain id, nonce, max priority fee, max fee, gas limit,
+// recipient, value, data, access list and signature. The access list is
+// checked but not used, since state is read as the EVM goes regardless.
+func decodeDynamicFeeTransaction(raw []byte) (*ethereumTransaction, error) {
+	items, encoded, lists, err := rlpSplitFields(raw)
+	if err != nil {
+		return nil, err
+	}
+	if len(items) != 12 {
+		return nil, fmt.Errorf("Expected 12 fields, not %v", len(items))
+	}
+	for i, isList := range lists {
+		if isList != (i == 8) {
+			return nil, fmt.Errorf("Field %v has the wrong RLP type", i)
+		}
+	}
+	if err := checkAccessList(items[8]); err != nil {
+		return nil, err
+	}
+
+	txn := &ethereumTransaction{
+		to:         items[5],
+		data:       items[7],
+		dynamicFee: true,
+		fields:     encoded[:9],
+		v:          new(big.Int).SetBytes(items[9]),
+		r:          items[10],
+		s:          items[11],
+	}
+	quantities := []*uint64{
+		&txn.signedChainId, &txn.nonce, &txn.maxPriorityFeePerGas, &txn.gasPrice,
+		&txn.gasLimit, nil, &txn.value,
+	}
+	for i, quantity := range quantities {
+		if quantity == nil {
+			continue
+		}
+		*quantity, err = rlpUint64(items[i])
+		if err != nil {
+			return nil, err
+		}
+	}
+	if len(txn.to) != 0 && len(txn.to) != EVMADDRLEN {
+		return nil, fmt.Errorf("Recipient must be %v bytes, not %v", EVMADDRLEN, len(txn.to))
+	}
+	if txn.maxPriorityFeePerGas > txn.gasPrice {
+		return nil, fmt.Errorf(
+			"Max priority fee per gas is more than the max fee per gas: %v > %v",
+			txn.maxPriorityFeePerGas, txn.gasPrice,
+		)
+	}
+
+	return txn, nil
+}
+
+// checkAccessList checks that the content of an access list is a list of
+// addresses, each with a list of storage keys
+func checkAccessList(content []byte) error {
+	for len(content) > 0 {
+		entry, rest, err := rlpSplitList(content)
+		if err != nil {
+			return err
+		}
+		content = rest
+
+		address, entry, err := rlpSplitString(entry)
+		if err != nil {
+			return err
+		}
+		keys, entry, err := rlpSplitList(entry)
+		if err != nil {
+			return err
+		}
+		if len(address) != EVMADDRLEN || len(entry) != 0 {
+			return fmt.Errorf("Access list entries must be an address and storage keys")
+		}
+		for len(keys) > 0 {
+			key, next, err := rlpSplitString(keys)
+			if err != nil {
+				return err
+			}
+			if len(key) != 32 {
+				return fmt.Errorf("Access list storage keys must be 32 bytes")
+			}
+			keys = next
+		}
+	}
+	return nil
+}
+
+// maxFeePerGas is the max fee per gas of an EIP-1559 transaction, or 0 for a
+// legacy one
+func (txn *ethereumTransaction) maxFeePerGas() uint64 {
+	if txn.dynamicFee {
+		return txn.gasPrice
+	}
+	return 0
+}
+
+// sender recovers the seth address of the key that signed the transaction for
+// the given chain
+func (txn *ethereumTransaction) sender(chainId uint64) (*EvmAddr, error) {
+	var recoveryId uint64
+	fields := txn.fields
+	switch v := txn.v.Uint64(); {
+	case !txn.v.IsUint64():
+		return nil, fmt.Errorf("Invalid v: %v", txn.v)
+	case txn.dynamicFee:
+		if txn.signedChainId != chainId {
+			return nil, fmt.Errorf("Signed for chain %v, not %v", txn.signedChainId, chainId)
+		}
+		if v > 1 {
+			return nil, fmt.Errorf("Invalid y parity: %v", v)
+		}
+		recoveryId = v
+	case v == 27 || v == 28:
+		// Without a chain id, the transaction could be replayed on any chain
+		return nil, fmt.Errorf("Transactions must be signed for a chain id with EIP-155")
+	case v >= 35:
+		signedChainId := (v - 35) / 2
+		if signedChainId != chainId {
+			return nil, fmt.Errorf("Signed for chain %v, not %v", signedChainId, chainId)
+		}
+		recoveryId = (v - 35) % 2
+		fields = append(fields[:6:6], rlpEncodeUint64(chainId), rlpEncodeUint64(0), rlpEncodeUint64(0))
+	default:
+		return nil, fmt.Errorf("Invalid v: %v", v)
+	}
+
+	if len(txn.r) > 32 || len(txn.s) > 32 {
+		return nil, fmt.Errorf("r and s must be at most 32 bytes")
+	}
+	// Like Ethereum, only accept the lower of the two equivalent values of s
+	halfOrder := new(big.Int).Rsh(ellcurv.S256().N, 1)
+	if new(big.Int).SetBytes(txn.s).Cmp(halfOrder) > 0 {
+		return nil, fmt.Errorf("s is not in the lower half of the curve order")
+	}
+
+	signature := make([]byte, 65)
+	signature[0] = byte(27 + 4 + recoveryId)
+	copy(signature[33-len(txn.r):33], txn.r)
+	copy(signature[65-len(txn.s):], txn.s)
+
+	signed := rlpEncodeList(fields)
+	if txn.dynamicFee {
+		signed = append([]byte{DYNAMIC_FEE_TXN_TYPE}, signed...)
+	}
+	hash := sha3.Sha3(signed)
+	publicKey, _, err := ellcurv.RecoverCompact(ellcurv.S256(), signature, hash)
+	if err != nil {
+		return nil, err
+	}
+
+	return PubToEvmAddr(publicKey.SerializeCompressed())
+}
+
+// -- RLP --
+
+// rlpSplit returns the content of the item that data starts with, whether it
+// is a list, and the bytes after it
+func rlpSplit(data []byte) (content []byte, isList bool, rest []byte, err error) {
+	if len(data) == 0 {
+		return nil, false, nil, fmt.Errorf("Unexpected end of RLP")
+	}
+	prefix := data[0]
+	var offset, size uint64
+	switch {
+	case prefix < 0x80:
+		return data[:1], false, data[1:], nil
+	case prefix < 0xb8:
+		offset, size = 1, uint64(prefix-0x80)
+	case prefix < 0xc0:
+		offset, size, err = rlpLongSize(data, prefix-0xb7)
+	case prefix < 0xf8:
+		offset, size, isList = 1, uint64(prefix-0xc0), true
+	default:
+		offset, size, err = rlpLongSize(data, prefix-0xf7)
+		isList = true
+	}
+	if err != nil {
+		return nil, false, nil, err
+	}
+	if size > uint64(len(data))-offset {
+		return nil, false, nil, fmt.Errorf("RLP item is longer than its input")
+	}
+	return data[offset : offset+size], isList, data[offset+size:], nil
+}
+
+func rlpLongSize(data []byte, sizeLen byte) (uint64, uint64, error) {
+	offset := 1 + uint64(sizeLen)
+	if sizeLen > 8 || uint64(len(data)) < offset {
+		return 0, 0, fmt.Errorf("Malformed RLP length")
+	}
+	size := make([]byte, 8)
+	copy(size[8-sizeLen:], data[1:offset])
+	return offset, binary.BigEndian.Uint64(size), nil
+}
+
+// rlpSplitFields returns the items of the list that is all of data, each as its
+// content and as it was encoded, and whether each is itself a list
+func rlpSplitFields(data []byte) (items [][]byte, encoded [][]byte, lists []bool, err error) {
+	content, rest, err := rlpSplitList(data)
+	if err != nil {
+		return nil, nil, nil, err
+	}
+	if len(rest) != 0 {
+		return nil, nil, nil, fmt.Errorf("Trailing bytes after transaction")
+	}
+
+	for len(content) > 0 {
+		item, isList, next, err := rlpSplit(content)
+		if err != nil {
+			return nil, nil, nil, err
+		}
+		items = append(items, item)
+		encoded = append(encoded, content[:len(content)-len(next)])
+		lists = append(lists, isList)
+		content = next
+	}
+	return items, encoded, lists, nil
+}
+
+func rlpSplitList(data []byte) ([]byte, []byte, error) {
+	content, isList, rest, err := rlpSplit(data)
+	if err == nil && !isList {
+		err = fmt.Errorf("Expected an RLP list")
+	}
+	return content, rest, err
+}
+
+func rlpSplitString(data []byte) ([]byte, []byte, error) {
+	content, isList, rest, err := rlpSplit(data)
+	if err == nil && isList {
+		err = fmt.Errorf("Expected an RLP string")
+	}
+	return content, rest, err
+}
+
+func rlpUint64(b []byte) (uint64, error) {
+	if len(b) > 8 {
+		return 0, fmt.Errorf("Quantity doesn't fit in 64 bits: %x", b)
+	}
+	if len(b) > 0 && b[0] == 0 {
+		return 0, fmt.Errorf("Quantity has leading zeros: %x", b)
+	}
+	padded := make([]byte, 8)
+	copy(padded[8-len(b):], b)
+	return binary.BigEndian.Uint64(padded), nil
+}
+
+func rlpEncodeUint64(i uint64) []byte {
+	if i == 0 {
+		return []byte{0x80}
+	}
+	if i < 0x80 {
+		return []byte{byte(i)}
+	}
+	b := make([]byte, 8)
+	binary.BigEndian.PutUint64(b, i)
+	for b[0] == 0 {
+		b = b[1:]
+	}
+	return append([]byte{0x80 + byte(len(b))}, b...)
+}
+
+func rlpEncodeList(encodedItems [][]byte) []byte {
+	var content []byte
+	for _, item := range encodedItems {
+		content = append(content, item...)
+	}
+	if len(content) < 56 {
+		return append([]byte{0xc0 + byte(len(content))}, content...)
+	}
+	size := make([]byte, 8)
+	binary.BigEndian.PutUint64(size, uint64(len(content)))
+	for size[0] == 0 {
+		size = size[1:]
+	}
+	header := append([]byte{0xf7 + byte(len(size))}, size...)
+	return append(header, content...)
+}
@@ -0,0 +1,176 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+package handler
+
+import (
+	"bytes"
+	. "common"
+	ellcurv "github.com/btcsuite/btcd/btcec"
+	"github.com/hyperledger/burrow/execution/evm/sha3"
+	"testing"
+	"testing/quick"
+)
+
+// legacyFields are the fields of a legacy transaction and the key that signs
+// it, which testing/quick fills in at random
+type legacyFields struct {
+	Key      [32]byte
+	Nonce    uint64
+	GasPrice uint64
+	GasLimit uint64
+	Create   bool
+	To       [EVMADDRLEN]byte
+	Value    uint64
+	Data     []byte
+}
+
+func (f legacyFields) to() []byte {
+	if f.Create {
+		return nil
+	}
+	return f.To[:]
+}
+
+// encoded returns the fields as they are encoded before the signature
+func (f legacyFields) encoded() [][]byte {
+	return [][]byte{
+		rlpEncodeUint64(f.Nonce),
+		rlpEncodeUint64(f.GasPrice),
+		rlpEncodeUint64(f.GasLimit),
+		rlpEncodeBytes(f.to()),
+		rlpEncodeUint64(f.Value),
+		rlpEncodeBytes(f.Data),
+	}
+}
+
+// sign returns the transaction signed for the chain with EIP-155, and the
+// address of the key that signed it
+func (f legacyFields) sign(t *testing.T, chainId uint64) ([]byte, *EvmAddr) {
+	// Keep the key below the curve order and away from zero
+	key := f.Key
+	key[0] = key[0]%0x7f + 1
+	private, public := ellcurv.PrivKeyFromBytes(ellcurv.S256(), key[:])
+
+	unsigned := append(f.encoded(), rlpEncodeUint64(chainId), rlpEncodeUint64(0), rlpEncodeUint64(0))
+	signature, err := ellcurv.SignCompact(ellcurv.S256(), private, sha3.Sha3(rlpEncodeList(unsigned)), true)
+	if err != nil {
+		t.Fatal(err)
+	}
+	// SignCompact gives 27 + 4 + the recovery id for compressed keys
+	v := 35 + 2*chainId + uint64(signature[0]-31)
+	signed := append(f.encoded(), rlpEncodeUint64(v), rlpEncodeBytes(signature[1:33]), rlpEncodeBytes(signature[33:]))
+
+	sender, err := PubToEvmAddr(public.SerializeCompressed())
+	if err != nil {
+		t.Fatal(err)
+	}
+	return rlpEncodeList(signed), sender
+}
+
+// rlpListHeader returns the prefix of a list whose content is size bytes long
+func rlpListHeader(size int) []byte {
+	list := rlpEncodeList([][]byte{make([]byte, size)})
+	return list[:len(list)-size]
+}
+
+func rlpEncodeBytes(b []byte) []byte {
+	if len(b) == 1 && b[0] < 0x80 {
+		return b
+	}
+	// Strings are prefixed as lists are, 0x40 lower
+	header := rlpListHeader(len(b))
+	header[0] -= 0x40
+	return append(header, b...)
+}
+
+func TestDecodesTheLegacyTransactionsItIsSent(t *testing.T) {
+	roundTrip := func(f legacyFields) bool {
+		raw, sender := f.sign(t, DEFAULT_CHAIN_ID)
+		txn, err := decodeEthereumTransaction(raw)
+		if err != nil {
+			t.Log(err)
+			return false
+		}
+		recovered, err := txn.sender(DEFAULT_CHAIN_ID)
+		if err != nil {
+			t.Log(err)
+			return false
+		}
+		return *recovered == *sender &&
+			txn.nonce == f.Nonce &&
+			txn.gasPrice == f.GasPrice &&
+			txn.gasLimit == f.GasLimit &&
+			bytes.Equal(txn.to, f.to()) &&
+			txn.value == f.Value &&
+			bytes.Equal(txn.data, f.Data) &&
+			!txn.dynamicFee
+	}
+	if err := quick.Check(roundTrip, nil); err != nil {
+		t.Error(err)
+	}
+}
+
+func TestRejectsATrailingPayload(t *testing.T) {
+	rejected := func(f legacyFields, trailing []byte) bool {
+		if len(trailing) == 0 {
+			trailing = []byte{0}
+		}
+		raw, _ := f.sign(t, DEFAULT_CHAIN_ID)
+		_, err := decodeEthereumTransaction(append(raw, trailing...))
+		return err != nil
+	}
+	if err := quick.Check(rejected, nil); err != nil {
+		t.Error(err)
+	}
+}
+
+func TestRejectsWrongLengthPrefixes(t *testing.T) {
+	rejected := func(f legacyFields, delta int8) bool {
+		// A list 1 to 8 bytes shorter or longer than its content
+		change := int(delta%8) + 1
+		if delta < 0 {
+			change = int(delta%8) - 1
+		}
+		raw, _ := f.sign(t, DEFAULT_CHAIN_ID)
+		content, _, err := rlpSplitList(raw)
+		if err != nil {
+			t.Fatal(err)
+		}
+		_, err = decodeEthereumTransaction(append(rlpListHeader(len(content)+change), content...))
+		return err != nil
+	}
+	if err := quick.Check(rejected, nil); err != nil {
+		t.Error(err)
+	}
+}
+
+func TestRejectsQuantitiesWiderThan64Bits(t *testing.T) {
+	rejected := func(f legacyFields, field uint8, wide [24]byte, width uint8) bool {
+		// A quantity of 9 to 24 bytes, without leading zeros
+		quantity := wide[:9+width%16]
+		quantity[0] |= 1
+
+		fields := f.encoded()
+		fields[[]int{0, 1, 2, 4}[field%4]] = rlpEncodeBytes(quantity)
+		fields = append(fields, rlpEncodeUint64(35+2*DEFAULT_CHAIN_ID), rlpEncodeBytes(nil), rlpEncodeBytes(nil))
+		_, err := decodeEthereumTransaction(rlpEncodeList(fields))
+		return err != nil
+	}
+	if err := quick.Check(rejected, nil); err != nil {
+		t.Error(err)
+	}
+}
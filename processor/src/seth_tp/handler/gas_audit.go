@@ -0,0 +1,115 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+package handler
+
+import (
+	"github.com/hyperledger/burrow/logging/structure"
+	. "protobuf/seth_pb2"
+	"sort"
+	"strconv"
+	"strings"
+)
+
+var gasAudit = false
+
+// SetGasAudit enables recording the gas used by each opcode in the receipt of
+// every transaction. It slows execution down significantly, so is meant for
+// comparing gas profiles between processor versions rather than production.
+func SetGasAudit(enabled bool) {
+	gasAudit = enabled
+}
+
+// gasProfiler collects the opcodes traced by the EVM in DebugOpcodes mode,
+// attributing to each the gas remaining before it minus the gas remaining
+// before the next opcode. The gas a call passes on is attributed to the
+// opcodes of the callee instead of to the call.
+type gasProfiler struct {
+	entries map[string]*GasProfileEntry
+	lastOp  string
+	lastGas uint64
+	tracing bool
+}
+
+func newGasProfiler() *gasProfiler {
+	return &gasProfiler{entries: make(map[string]*GasProfileEntry)}
+}
+
+// Log implements the go-kit logger interface, which the Burrow EVM traces
+// opcodes to
+func (self *gasProfiler) Log(keyvals ...interface{}) error {
+	for i := 0; i+1 < len(keyvals); i += 2 {
+		if keyvals[i] != structure.MessageKey {
+			continue
+		}
+		if msg, ok := keyvals[i+1].(string); ok {
+			self.trace(msg)
+		}
+	}
+	return nil
+}
+
+// trace records a line in the format "(pc) N (op) OPCODE (st) N (gas) N"
+func (self *gasProfiler) trace(msg string) {
+	fields := strings.Fields(msg)
+	if len(fields) != 8 || fields[0] != "(pc)" || fields[2] != "(op)" || fields[6] != "(gas)" {
+		return
+	}
+	gas, err := strconv.ParseUint(fields[7], 10, 64)
+	if err != nil {
+		return
+	}
+
+	self.charge(gas)
+
+	op := fields[3]
+	entry, exists := self.entries[op]
+	if !exists {
+		entry = &GasProfileEntry{Opcode: op}
+		self.entries[op] = entry
+	}
+	entry.Count++
+
+	self.lastOp = op
+	self.lastGas = gas
+	self.tracing = true
+}
+
+// charge attributes the gas used since the last opcode to it
+func (self *gasProfiler) charge(gas uint64) {
+	if self.tracing && gas <= self.lastGas {
+		self.entries[self.lastOp].Gas += self.lastGas - gas
+	}
+}
+
+// finish is called after each EVM call with the gas left at the end of it
+func (self *gasProfiler) finish(gas uint64) {
+	self.charge(gas)
+	self.tracing = false
+}
+
+// profile returns the collected entries ordered by opcode
+func (self *gasProfiler) profile() []*GasProfileEntry {
+	profile := make([]*GasProfileEntry, 0, len(self.entries))
+	for _, entry := range self.entries {
+		profile = append(profile, entry)
+	}
+	sort.Slice(profile, func(i, j int) bool {
+		return profile[i].Opcode < profile[j].Opcode
+	})
+	return profile
+}
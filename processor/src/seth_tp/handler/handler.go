@@ -40,6 +40,10 @@ type HandlerResult struct {
 	ReturnValue []byte
 	NewAccount  acm.Account
 	Error       error
+
+	// The call reverted, and ReturnValue is what it reverted with. Nothing it
+	// did is kept but the sender's nonce.
+	Reverted bool
 }
 
 type TransactionHandler func(*SethTransaction, *common.EvmAddr, *SawtoothAppState) HandlerResult
@@ -47,10 +51,22 @@ type TransactionHandler func(*SethTransaction, *common.EvmAddr, *SawtoothAppStat
 var logger *slogging.Logger = slogging.Get()
 var vm_logger = logging.NewNoopLogger()
 
-type BurrowEVMHandler struct{}
+type BurrowEVMHandler struct {
+	versions []string
+}
 
-func NewBurrowEVMHandler() *BurrowEVMHandler {
-	return &BurrowEVMHandler{}
+// NewBurrowEVMHandler creates a handler serving the given family versions, or
+// every registered version if none are given
+func NewBurrowEVMHandler(versions []string) (*BurrowEVMHandler, error) {
+	if len(versions) == 0 {
+		versions = RegisteredFamilyVersions()
+	}
+	for _, version := range versions {
+		if getFamilyVersion(version) == nil {
+			return nil, fmt.Errorf("Unsupported family version: %v", version)
+		}
+	}
+	return &BurrowEVMHandler{versions: versions}, nil
 }
 
 func (self *BurrowEVMHandler) FamilyName() string {
@@ -58,7 +74,7 @@ func (self *BurrowEVMHandler) FamilyName() string {
 }
 
 func (self *BurrowEVMHandler) FamilyVersions() []string {
-	return []string{common.FAMILY_VERSION}
+	return self.versions
 }
 
 func (self *BurrowEVMHandler) Namespaces() []string {
@@ -66,25 +82,38 @@ func (self *BurrowEVMHandler) Namespaces() []string {
 }
 
 func (self *BurrowEVMHandler) Apply(request *processor_pb2.TpProcessRequest, context *processor.Context) error {
+	return self.Execute(request, context)
+}
+
+// Execute applies the transaction to any StateContext, so that committed
+// transactions can be replayed against a snapshot of state
+func (self *BurrowEVMHandler) Execute(request *processor_pb2.TpProcessRequest, context StateContext) error {
 
 	// Unpack and validate transaction
 	wrapper, err := unpackPayload(request.GetPayload())
 	if err != nil {
-		return err
+		return withCode(err)
 	}
 
 	// Unpack and validate header
 	header, err := unpackHeader(request)
 	if err != nil {
-		return err
+		return withCode(err)
 	}
 
-	// Retrieve the handler for this type of Burrow-EVM transaction
-	handler, exists := TxnHandlers[wrapper.GetTransactionType()]
+	// Retrieve the handler for this type of Burrow-EVM transaction in the
+	// version of the family the transaction was sent with
+	version := self.getFamilyVersion(header.GetFamilyVersion())
+	if version == nil {
+		return rejected(ERR_UNSUPPORTED_FORK,
+			"Unsupported family version: %v", header.GetFamilyVersion(),
+		)
+	}
+	handler, exists := version.Handlers[wrapper.GetTransactionType()]
 	if !exists {
-		return &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+		return rejected(ERR_INVALID_TRANSACTION,
 			"Unknown transaction type: %v", wrapper.GetTransactionType(),
-		)}
+		)
 	}
 
 	// Construct address of sender. This is the address used by the EVM to
@@ -97,18 +126,26 @@ func (self *BurrowEVMHandler) Apply(request *processor_pb2.TpProcessRequest, con
 	}
 	sender, err := common.PubToEvmAddr(public_key)
 	if err != nil {
-		return &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+		return rejected(ERR_INVALID_TRANSACTION,
 			"Couldn't determine sender from public key: %v", header.GetSignerPublicKey(),
+		)
+	}
+
+	// Read the EVM configuration from settings
+	config, err := getEvmConfig(context, version.Rules)
+	if err != nil {
+		return &processor.InternalError{Msg: fmt.Sprintf(
+			"Couldn't read EVM settings: %v", err,
 		)}
 	}
 
 	// Construct new state manager
-	sapps := NewSawtoothAppState(context)
+	sapps := NewSawtoothAppState(context, config, version.Rules)
 
 	// Call the handler
 	result := handler(wrapper, sender, sapps)
 	if result.Error != nil {
-		return result.Error
+		return withCode(result.Error)
 	}
 
 	var contractAddress []byte
@@ -119,6 +156,10 @@ func (self *BurrowEVMHandler) Apply(request *processor_pb2.TpProcessRequest, con
 		ContractAddress: contractAddress,
 		GasUsed:         result.GasUsed,
 		ReturnValue:     result.ReturnValue,
+		Reverted:        result.Reverted,
+	}
+	if sapps.gasProfiler != nil {
+		receipt.GasProfile = sapps.gasProfiler.profile()
 	}
 
 	bytes, err := proto.Marshal(receipt)
@@ -140,14 +181,28 @@ func (self *BurrowEVMHandler) Apply(request *processor_pb2.TpProcessRequest, con
 
 // -- utilities --
 
+func (self *BurrowEVMHandler) getFamilyVersion(version string) *FamilyVersion {
+	for _, served := range self.versions {
+		if served == version {
+			return getFamilyVersion(version)
+		}
+	}
+	return nil
+}
+
 func callVm(sas *SawtoothAppState, sender, receiver *acm.MutableAccount,
 	code, input []byte, gas uint64) ([]byte, uint64, error) {
 	// Create EVM
-	params, err := getParams(sas.mgr.state)
+	params, err := getParams(sas.mgr.state, sas.config.BlockGasLimit)
 	if err != nil {
 		return nil, 0, fmt.Errorf("Block Info Error: %v", err)
 	}
-	vm := evm.NewVM(*params, sender.Address(), nil, vm_logger)
+	var vm *evm.VM
+	if sas.gasProfiler != nil {
+		vm = evm.NewVM(*params, sender.Address(), nil, logging.NewLogger(sas.gasProfiler), evm.DebugOpcodes)
+	} else {
+		vm = evm.NewVM(*params, sender.Address(), nil, vm_logger)
+	}
 	evc := NewSawtoothEventFireable(sas.mgr.state)
 	vm.SetEventSink(evc)
 
@@ -161,15 +216,23 @@ func callVm(sas *SawtoothAppState, sender, receiver *acm.MutableAccount,
 
 	cache := state.NewCache(sas)
 
+	defer startRunning(sas)()
+
 	output, err := vm.Call(cache, sender, receiver, code, input, 0, &endGas)
+	if sas.gasProfiler != nil {
+		sas.gasProfiler.finish(endGas)
+	}
+	// What a reverted call reverted with, and the gas it used, are returned
+	// along with the error, but none of its changes or logs are kept
 	if err != nil {
-		return nil, 0, fmt.Errorf("EVM Error: %v", err)
+		return output, uint64(startGas - endGas), fmt.Errorf("EVM Error: %v", err)
 	}
 
 	err = cache.Sync(sas)
 	if err != nil {
 		return nil, 0, fmt.Errorf("EVM Sync Error: %v", err)
 	}
+	evc.Flush()
 
 	return output, uint64(startGas - endGas), nil
 }
@@ -202,7 +265,7 @@ func unpackHeader(request *processor_pb2.TpProcessRequest) (*transaction_pb2.Tra
 	return header, nil
 }
 
-func getParams(context *processor.Context) (*evm.Params, error) {
+func getParams(context StateContext, gasLimit uint64) (*evm.Params, error) {
 	blockInfoConfig, err := getBlockInfoConfig(context)
 	if err != nil {
 		logger.Debugf(err.Error())
@@ -212,7 +275,7 @@ func getParams(context *processor.Context) (*evm.Params, error) {
 			BlockHeight: 0,
 			BlockHash:   binary.Zero256,
 			BlockTime:   0,
-			GasLimit:    common.GAS_LIMIT,
+			GasLimit:    gasLimit,
 		}, nil
 	}
 
@@ -230,11 +293,11 @@ func getParams(context *processor.Context) (*evm.Params, error) {
 		BlockHeight: blockInfo.GetBlockNum(),
 		BlockHash:   hash,
 		BlockTime:   int64(blockInfo.GetTimestamp()),
-		GasLimit:    common.GAS_LIMIT,
+		GasLimit:    gasLimit,
 	}, nil
 }
 
-func getBlockInfoConfig(context *processor.Context) (*BlockInfoConfig, error) {
+func getBlockInfoConfig(context StateContext) (*BlockInfoConfig, error) {
 	// Retrieve block info config from global state
 	entries, err := context.GetState([]string{common.CONFIG_ADDRESS})
 	if err != nil {
@@ -255,7 +318,7 @@ func getBlockInfoConfig(context *processor.Context) (*BlockInfoConfig, error) {
 	return entry, nil
 }
 
-func getBlockInfo(context *processor.Context, blockNumber int64) (*BlockInfo, error) {
+func getBlockInfo(context StateContext, blockNumber int64) (*BlockInfo, error) {
 	// Create block info address
 	blockInfoAddr, err := common.NewBlockInfoAddr(blockNumber)
 	if err != nil {
@@ -0,0 +1,99 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+package handler
+
+import (
+	. "common"
+	"encoding/hex"
+	"errors"
+	"github.com/hyperledger/burrow/acm/state"
+	"github.com/hyperledger/burrow/binary"
+	"github.com/hyperledger/burrow/crypto"
+	"github.com/hyperledger/burrow/execution/evm/sha3"
+)
+
+const (
+	BLOCK_SEED_ADDRESS  = 0x101
+	BLOCK_SEED_GAS      = 100
+	BLOCK_SEED_WORD_GAS = 6
+)
+
+var errBlockSeedUnavailable = errors.New("Block seed is not available")
+
+func init() {
+	MustRegisterPrecompile(PrecompileAddress(BLOCK_SEED_ADDRESS), BlockSeed{})
+}
+
+// BlockSeed returns the hash of a seed derived from the signature of the
+// latest block, the caller's address and the input, which callers can use as
+// a nonce to derive several values in one block. Every validator computes the
+// same value. It is not random: the seed can be read by anyone before a
+// transaction runs, and the publisher of the latest block could sign other
+// candidate blocks until it got a seed it wanted, so it must not decide
+// anything worth biasing.
+type BlockSeed struct{}
+
+func (BlockSeed) Name() string {
+	return "blockSeed"
+}
+
+func (BlockSeed) RequiredGas(input []byte) uint64 {
+	words := uint64(len(input)+31) / 32
+	return BLOCK_SEED_GAS + words*BLOCK_SEED_WORD_GAS
+}
+
+func (BlockSeed) Run(caller *EvmAddr, input []byte) ([]byte, error) {
+	return nil, errBlockSeedUnavailable
+}
+
+func (BlockSeed) RunWithState(st state.Reader, caller *EvmAddr, input []byte) ([]byte, error) {
+	address := crypto.AddressFromWord256(PrecompileAddress(BLOCK_SEED_ADDRESS).ToWord256())
+	seed, err := st.GetStorage(address, binary.Zero256)
+	if err != nil {
+		return nil, err
+	}
+	if seed == binary.Zero256 {
+		return nil, errBlockSeedUnavailable
+	}
+
+	buf := append(seed.Bytes(), caller[:]...)
+	buf = append(buf, input...)
+
+	return sha3.Sha3(buf), nil
+}
+
+// getBlockSeed returns the hash of the latest block's signature, or zero if
+// block info isn't available
+func getBlockSeed(context StateContext) (binary.Word256, error) {
+	blockInfoConfig, err := getBlockInfoConfig(context)
+	if err != nil {
+		return binary.Zero256, nil
+	}
+
+	blockInfo, err := getBlockInfo(context, int64(blockInfoConfig.GetLatestBlock()))
+	if err != nil {
+		return binary.Zero256, err
+	}
+
+	signature, err := hex.DecodeString(blockInfo.GetHeaderSignature())
+	if err != nil {
+		return binary.Zero256, err
+	}
+
+	return binary.LeftPadWord256(sha3.Sha3(signature)), nil
+}
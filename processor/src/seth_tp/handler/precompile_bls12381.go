@@ -0,0 +1,355 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+package handler
+
+import (
+	. "common"
+	"errors"
+	"fmt"
+	"github.com/ethereum/go-ethereum/crypto/bls12381"
+	"math/big"
+)
+
+// The BLS12-381 precompiles of EIP-2537. Points and field elements use the
+// EIP's encoding: each base field element is 64 bytes, big endian, with the
+// top 16 bytes zero. Scalars are 32 bytes, big endian.
+const (
+	BLS12_G1ADD_ADDRESS         = 0x0b
+	BLS12_G1MSM_ADDRESS         = 0x0c
+	BLS12_G2ADD_ADDRESS         = 0x0d
+	BLS12_G2MSM_ADDRESS         = 0x0e
+	BLS12_PAIRING_ADDRESS       = 0x0f
+	BLS12_MAP_FP_TO_G1_ADDRESS  = 0x10
+	BLS12_MAP_FP2_TO_G2_ADDRESS = 0x11
+
+	BLS12_G1ADD_GAS            = 375
+	BLS12_G1MUL_GAS            = 12000
+	BLS12_G2ADD_GAS            = 600
+	BLS12_G2MUL_GAS            = 22500
+	BLS12_PAIRING_BASE_GAS     = 37700
+	BLS12_PAIRING_PER_PAIR_GAS = 32600
+	BLS12_MAP_FP_TO_G1_GAS     = 5500
+	BLS12_MAP_FP2_TO_G2_GAS    = 23800
+
+	BLS12_FP_LEN     = 64
+	BLS12_G1_LEN     = 2 * BLS12_FP_LEN
+	BLS12_G2_LEN     = 4 * BLS12_FP_LEN
+	BLS12_SCALAR_LEN = 32
+)
+
+var errBLS12InvalidInputLength = errors.New("Invalid input length")
+var errBLS12InvalidFieldElement = errors.New("Invalid field element top bytes")
+var errBLS12NotInSubgroup = errors.New("Point is not in the correct subgroup")
+
+func init() {
+	MustRegisterPrecompile(PrecompileAddress(BLS12_G1ADD_ADDRESS), BLS12G1Add{})
+	MustRegisterPrecompile(PrecompileAddress(BLS12_G1MSM_ADDRESS), BLS12G1MSM{})
+	MustRegisterPrecompile(PrecompileAddress(BLS12_G2ADD_ADDRESS), BLS12G2Add{})
+	MustRegisterPrecompile(PrecompileAddress(BLS12_G2MSM_ADDRESS), BLS12G2MSM{})
+	MustRegisterPrecompile(PrecompileAddress(BLS12_PAIRING_ADDRESS), BLS12Pairing{})
+	MustRegisterPrecompile(PrecompileAddress(BLS12_MAP_FP_TO_G1_ADDRESS), BLS12MapFpToG1{})
+	MustRegisterPrecompile(PrecompileAddress(BLS12_MAP_FP2_TO_G2_ADDRESS), BLS12MapFp2ToG2{})
+}
+
+// -- G1 --
+
+// BLS12G1Add adds two G1 points. The points don't need to be in the subgroup.
+type BLS12G1Add struct{}
+
+func (BLS12G1Add) Name() string {
+	return "bls12G1Add"
+}
+
+func (BLS12G1Add) RequiredGas(input []byte) uint64 {
+	return BLS12_G1ADD_GAS
+}
+
+func (BLS12G1Add) Run(caller *EvmAddr, input []byte) ([]byte, error) {
+	if len(input) != 2*BLS12_G1_LEN {
+		return nil, errBLS12InvalidInputLength
+	}
+
+	g := bls12381.NewG1()
+	p0, err := g.DecodePoint(input[:BLS12_G1_LEN])
+	if err != nil {
+		return nil, err
+	}
+	p1, err := g.DecodePoint(input[BLS12_G1_LEN:])
+	if err != nil {
+		return nil, err
+	}
+
+	r := g.New()
+	g.Add(r, p0, p1)
+
+	return g.EncodePoint(r), nil
+}
+
+// BLS12G1MSM computes the multi-scalar multiplication of G1 points. The input
+// is any number of points, each followed by its scalar. Multiplication is
+// charged per point, without the EIP's bulk discount.
+type BLS12G1MSM struct{}
+
+func (BLS12G1MSM) Name() string {
+	return "bls12G1MSM"
+}
+
+func (BLS12G1MSM) RequiredGas(input []byte) uint64 {
+	return uint64(len(input)/(BLS12_G1_LEN+BLS12_SCALAR_LEN)) * BLS12_G1MUL_GAS
+}
+
+func (BLS12G1MSM) Run(caller *EvmAddr, input []byte) ([]byte, error) {
+	pairLen := BLS12_G1_LEN + BLS12_SCALAR_LEN
+	k := len(input) / pairLen
+	if len(input) == 0 || len(input)%pairLen != 0 {
+		return nil, errBLS12InvalidInputLength
+	}
+
+	g := bls12381.NewG1()
+	points := make([]*bls12381.PointG1, k)
+	scalars := make([]*big.Int, k)
+	for i := 0; i < k; i++ {
+		offset := i * pairLen
+		p, err := g.DecodePoint(input[offset : offset+BLS12_G1_LEN])
+		if err != nil {
+			return nil, err
+		}
+		if !g.InCorrectSubgroup(p) {
+			return nil, errBLS12NotInSubgroup
+		}
+		points[i] = p
+		scalars[i] = new(big.Int).SetBytes(input[offset+BLS12_G1_LEN : offset+pairLen])
+	}
+
+	r := g.New()
+	_, err := g.MultiExp(r, points, scalars)
+	if err != nil {
+		return nil, err
+	}
+
+	return g.EncodePoint(r), nil
+}
+
+// -- G2 --
+
+// BLS12G2Add adds two G2 points. The points don't need to be in the subgroup.
+type BLS12G2Add struct{}
+
+func (BLS12G2Add) Name() string {
+	return "bls12G2Add"
+}
+
+func (BLS12G2Add) RequiredGas(input []byte) uint64 {
+	return BLS12_G2ADD_GAS
+}
+
+func (BLS12G2Add) Run(caller *EvmAddr, input []byte) ([]byte, error) {
+	if len(input) != 2*BLS12_G2_LEN {
+		return nil, errBLS12InvalidInputLength
+	}
+
+	g := bls12381.NewG2()
+	p0, err := g.DecodePoint(input[:BLS12_G2_LEN])
+	if err != nil {
+		return nil, err
+	}
+	p1, err := g.DecodePoint(input[BLS12_G2_LEN:])
+	if err != nil {
+		return nil, err
+	}
+
+	r := g.New()
+	g.Add(r, p0, p1)
+
+	return g.EncodePoint(r), nil
+}
+
+// BLS12G2MSM is the G2 counterpart of BLS12G1MSM
+type BLS12G2MSM struct{}
+
+func (BLS12G2MSM) Name() string {
+	return "bls12G2MSM"
+}
+
+func (BLS12G2MSM) RequiredGas(input []byte) uint64 {
+	return uint64(len(input)/(BLS12_G2_LEN+BLS12_SCALAR_LEN)) * BLS12_G2MUL_GAS
+}
+
+func (BLS12G2MSM) Run(caller *EvmAddr, input []byte) ([]byte, error) {
+	pairLen := BLS12_G2_LEN + BLS12_SCALAR_LEN
+	k := len(input) / pairLen
+	if len(input) == 0 || len(input)%pairLen != 0 {
+		return nil, errBLS12InvalidInputLength
+	}
+
+	g := bls12381.NewG2()
+	points := make([]*bls12381.PointG2, k)
+	scalars := make([]*big.Int, k)
+	for i := 0; i < k; i++ {
+		offset := i * pairLen
+		p, err := g.DecodePoint(input[offset : offset+BLS12_G2_LEN])
+		if err != nil {
+			return nil, err
+		}
+		if !g.InCorrectSubgroup(p) {
+			return nil, errBLS12NotInSubgroup
+		}
+		points[i] = p
+		scalars[i] = new(big.Int).SetBytes(input[offset+BLS12_G2_LEN : offset+pairLen])
+	}
+
+	r := g.New()
+	_, err := g.MultiExp(r, points, scalars)
+	if err != nil {
+		return nil, err
+	}
+
+	return g.EncodePoint(r), nil
+}
+
+// -- Pairing --
+
+// BLS12Pairing checks whether the product of the pairings of the given (G1, G2)
+// pairs is the identity, returning 1 as a 32 byte word if it is and 0 if not
+type BLS12Pairing struct{}
+
+func (BLS12Pairing) Name() string {
+	return "bls12Pairing"
+}
+
+func (BLS12Pairing) RequiredGas(input []byte) uint64 {
+	k := uint64(len(input) / (BLS12_G1_LEN + BLS12_G2_LEN))
+	return BLS12_PAIRING_BASE_GAS + k*BLS12_PAIRING_PER_PAIR_GAS
+}
+
+func (BLS12Pairing) Run(caller *EvmAddr, input []byte) ([]byte, error) {
+	pairLen := BLS12_G1_LEN + BLS12_G2_LEN
+	k := len(input) / pairLen
+	if len(input) == 0 || len(input)%pairLen != 0 {
+		return nil, errBLS12InvalidInputLength
+	}
+
+	e := bls12381.NewPairingEngine()
+	for i := 0; i < k; i++ {
+		offset := i * pairLen
+		p1, err := e.G1.DecodePoint(input[offset : offset+BLS12_G1_LEN])
+		if err != nil {
+			return nil, err
+		}
+		p2, err := e.G2.DecodePoint(input[offset+BLS12_G1_LEN : offset+pairLen])
+		if err != nil {
+			return nil, err
+		}
+		if !e.G1.InCorrectSubgroup(p1) || !e.G2.InCorrectSubgroup(p2) {
+			return nil, errBLS12NotInSubgroup
+		}
+		e.AddPair(p1, p2)
+	}
+
+	output := make([]byte, 32)
+	if e.Check() {
+		output[31] = 1
+	}
+	return output, nil
+}
+
+// -- Mapping --
+
+// BLS12MapFpToG1 maps a base field element to a G1 point
+type BLS12MapFpToG1 struct{}
+
+func (BLS12MapFpToG1) Name() string {
+	return "bls12MapFpToG1"
+}
+
+func (BLS12MapFpToG1) RequiredGas(input []byte) uint64 {
+	return BLS12_MAP_FP_TO_G1_GAS
+}
+
+func (BLS12MapFpToG1) Run(caller *EvmAddr, input []byte) ([]byte, error) {
+	if len(input) != BLS12_FP_LEN {
+		return nil, errBLS12InvalidInputLength
+	}
+
+	fe, err := decodeBLS12FieldElement(input)
+	if err != nil {
+		return nil, err
+	}
+
+	g := bls12381.NewG1()
+	r, err := g.MapToCurve(fe)
+	if err != nil {
+		return nil, err
+	}
+
+	return g.EncodePoint(r), nil
+}
+
+// BLS12MapFp2ToG2 maps an element of the quadratic extension field, encoded
+// as its c0 then c1 coefficients, to a G2 point
+type BLS12MapFp2ToG2 struct{}
+
+func (BLS12MapFp2ToG2) Name() string {
+	return "bls12MapFp2ToG2"
+}
+
+func (BLS12MapFp2ToG2) RequiredGas(input []byte) uint64 {
+	return BLS12_MAP_FP2_TO_G2_GAS
+}
+
+func (BLS12MapFp2ToG2) Run(caller *EvmAddr, input []byte) ([]byte, error) {
+	if len(input) != 2*BLS12_FP_LEN {
+		return nil, errBLS12InvalidInputLength
+	}
+
+	c0, err := decodeBLS12FieldElement(input[:BLS12_FP_LEN])
+	if err != nil {
+		return nil, err
+	}
+	c1, err := decodeBLS12FieldElement(input[BLS12_FP_LEN:])
+	if err != nil {
+		return nil, err
+	}
+
+	// The library expects the c1 coefficient first
+	fe := make([]byte, 96)
+	copy(fe[:48], c1)
+	copy(fe[48:], c0)
+
+	g := bls12381.NewG2()
+	r, err := g.MapToCurve(fe)
+	if err != nil {
+		return nil, err
+	}
+
+	return g.EncodePoint(r), nil
+}
+
+// decodeBLS12FieldElement strips the 16 zero bytes of padding from a 64 byte
+// field element
+func decodeBLS12FieldElement(in []byte) ([]byte, error) {
+	if len(in) != BLS12_FP_LEN {
+		return nil, errBLS12InvalidInputLength
+	}
+	for _, b := range in[:16] {
+		if b != 0 {
+			return nil, fmt.Errorf("%v: %x", errBLS12InvalidFieldElement, in[:16])
+		}
+	}
+	return in[16:], nil
+}
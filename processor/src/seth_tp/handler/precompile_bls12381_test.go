@@ -0,0 +1,315 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+package handler
+
+import (
+	"bytes"
+	"encoding/hex"
+	"fmt"
+	"github.com/ethereum/go-ethereum/crypto/bls12381"
+	"strings"
+	"testing"
+)
+
+// Generators and multiples of them, computed independently of the library and
+// in the EIP-2537 encoding
+const (
+	bls12G1 = "0000000000000000000000000000000017f1d3a73197d7942695638c4fa9ac0f" +
+		"c3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb" +
+		"0000000000000000000000000000000008b3f481e3aaa0f1a09e30ed741d8ae4" +
+		"fcf5e095d5d00af600db18cb2c04b3edd03cc744a2888ae40caa232946c5e7e1"
+
+	bls12G1Times2 = "000000000000000000000000000000000572cbea904d67468808c8eb50a9450c" +
+		"9721db309128012543902d0ac358a62ae28f75bb8f1c7c42c39a8c5529bf0f4e" +
+		"00000000000000000000000000000000166a9d8cabc673a322fda673779d8e38" +
+		"22ba3ecb8670e461f73bb9021d5fd76a4c56d9d4cd16bd1bba86881979749d28"
+
+	bls12G1Times3 = "0000000000000000000000000000000009ece308f9d1f0131765212deca99697" +
+		"b112d61f9be9a5f1f3780a51335b3ff981747a0b2ca2179b96d2c0c9024e5224" +
+		"00000000000000000000000000000000032b80d3a6f5b09f8a84623389c5f80c" +
+		"a69a0cddabc3097f9d9c27310fd43be6e745256c634af45ca3473b0590ae30d1"
+
+	bls12G1Times8 = "00000000000000000000000000000000085ae765588126f5e860d019c0e26235" +
+		"f567a9c0c0b2d8ff30f3e8d436b1082596e5e7462d20f5be3764fd473e57f9cf" +
+		"0000000000000000000000000000000019e7dfab8a794b6abb9f84e57739de17" +
+		"2a63415273f460d1607fa6a74f0acd97d9671b801dd1fd4f18232dd1259359a1"
+
+	bls12G1Neg = "0000000000000000000000000000000017f1d3a73197d7942695638c4fa9ac0f" +
+		"c3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb" +
+		"00000000000000000000000000000000114d1d6855d545a8aa7d76c8cf2e21f2" +
+		"67816aef1db507c96655b9d5caac42364e6f38ba0ecb751bad54dcd6b939c2ca"
+
+	bls12G1TimesNeg6 = "0000000000000000000000000000000006e82f6da4520f85c5d27d8f329eccfa" +
+		"05944fd1096b20734c894966d12a9e2a9a9744529d7212d33883113a0cadb909" +
+		"00000000000000000000000000000000022901b141a9daabba0acdf56c7a9ca7" +
+		"819db2bb9b92848d7b0885e0b57c1695d6c307cebda4d19f13259775ba9c632f"
+
+	bls12G2 = "00000000000000000000000000000000024aa2b2f08f0a91260805272dc51051" +
+		"c6e47ad4fa403b02b4510b647ae3d1770bac0326a805bbefd48056c8c121bdb8" +
+		"0000000000000000000000000000000013e02b6052719f607dacd3a088274f65" +
+		"596bd0d09920b61ab5da61bbdc7f5049334cf11213945d57e5ac7d055d042b7e" +
+		"000000000000000000000000000000000ce5d527727d6e118cc9cdc6da2e351a" +
+		"adfd9baa8cbdd3a76d429a695160d12c923ac9cc3baca289e193548608b82801" +
+		"000000000000000000000000000000000606c4a02ea734cc32acd2b02bc28b99" +
+		"cb3e287e85a763af267492ab572e99ab3f370d275cec1da1aaa9075ff05f79be"
+
+	bls12G2Times2 = "000000000000000000000000000000001638533957d540a9d2370f17cc7ed586" +
+		"3bc0b995b8825e0ee1ea1e1e4d00dbae81f14b0bf3611b78c952aacab827a053" +
+		"000000000000000000000000000000000a4edef9c1ed7f729f520e47730a124f" +
+		"d70662a904ba1074728114d1031e1572c6c886f6b57ec72a6178288c47c33577" +
+		"000000000000000000000000000000000468fb440d82b0630aeb8dca2b525678" +
+		"9a66da69bf91009cbfe6bd221e47aa8ae88dece9764bf3bd999d95d71e4c9899" +
+		"000000000000000000000000000000000f6d4552fa65dd2638b361543f887136" +
+		"a43253d9c66c411697003f7a13c308f5422e1aa0a59c8967acdefd8b6e36ccf3"
+
+	bls12G2Times3 = "00000000000000000000000000000000122915c824a0857e2ee414a3dccb23ae" +
+		"691ae54329781315a0c75df1c04d6d7a50a030fc866f09d516020ef82324afae" +
+		"0000000000000000000000000000000009380275bbc8e5dcea7dc4dd7e0550ff" +
+		"2ac480905396eda55062650f8d251c96eb480673937cc6d9d6a44aaa56ca66dc" +
+		"000000000000000000000000000000000b21da7955969e61010c7a1abc1a6f01" +
+		"36961d1e3b20b1a7326ac738fef5c721479dfd948b52fdf2455e44813ecfd892" +
+		"0000000000000000000000000000000008f239ba329b3967fe48d718a36cfe5f" +
+		"62a7e42e0bf1c1ed714150a166bfbd6bcf6b3b58b975b9edea56d53f23a0e849"
+
+	bls12G2Times8 = "0000000000000000000000000000000002142a58bae275564a6d63cb6bd6266c" +
+		"a66bef07a6ab8ca37b9d0ba2d4effbccfd89c169649f7d0e8a3eb006846579ad" +
+		"0000000000000000000000000000000012be651a5fa620340d418834526d37a8" +
+		"c932652345400b4cd9d43c8f41c080f41a6d9558118ebeab9d4268bb73e850e1" +
+		"0000000000000000000000000000000015f4b235c209d89ce833f8f296e4cfb7" +
+		"48e8abce6990ce1a5a914b9416c08e0d3a26db89625915c821a5f152b7fa592e" +
+		"0000000000000000000000000000000006fcacb3ee6650a1044852d61c9c20be" +
+		"dc8ee90aad97de8e24670a9ef57483e678db11dd95428915088d76e30cb01a37"
+
+	bls12G2Neg = "00000000000000000000000000000000024aa2b2f08f0a91260805272dc51051" +
+		"c6e47ad4fa403b02b4510b647ae3d1770bac0326a805bbefd48056c8c121bdb8" +
+		"0000000000000000000000000000000013e02b6052719f607dacd3a088274f65" +
+		"596bd0d09920b61ab5da61bbdc7f5049334cf11213945d57e5ac7d055d042b7e" +
+		"000000000000000000000000000000000d1b3cc2c7027888be51d9ef691d77bc" +
+		"b679afda66c73f17f9ee3837a55024f78c71363275a75d75d86bab79f74782aa" +
+		"0000000000000000000000000000000013fa4d4a0ad8b1ce186ed5061789213d" +
+		"993923066dddaf1040bc3ff59f825c78df74f2d75467e25e0f55f8a00fa030ed"
+
+	// (4, y) is on the G1 curve but not in its prime order subgroup
+	bls12G1NotInSubgroup = "0000000000000000000000000000000000000000000000000000000000000000" +
+		"0000000000000000000000000000000000000000000000000000000000000004" +
+		"000000000000000000000000000000000a989badd40d6212b33cffc3f3763e9b" +
+		"c760f988c9926b26da9dd85e928483446346b8ed00e1de5d5ea93e354abe706c"
+)
+
+func bls12Input(t *testing.T, parts ...string) []byte {
+	return mustDecodeHex(t, strings.Join(parts, ""))
+}
+
+func bls12Scalar(n int64) string {
+	return fmt.Sprintf("%064x", n)
+}
+
+func bls12Fp(n int64) string {
+	return fmt.Sprintf("%0128x", n)
+}
+
+// bls12Infinity is the encoding of the point at infinity of a group whose
+// points are n bytes long
+func bls12Infinity(n int) string {
+	return strings.Repeat("00", n)
+}
+
+func expectBLS12Output(t *testing.T, p Precompile, input []byte, expected string) {
+	output, err := p.Run(nil, input)
+	if err != nil {
+		t.Fatalf("%v: %v", p.Name(), err)
+	}
+	if hex.EncodeToString(output) != expected {
+		t.Errorf("%v: Expected %v, got %x", p.Name(), expected, output)
+	}
+}
+
+func TestBLS12G1Add(t *testing.T) {
+	expectBLS12Output(t, BLS12G1Add{}, bls12Input(t, bls12G1, bls12G1Times2), bls12G1Times3)
+	expectBLS12Output(t, BLS12G1Add{}, bls12Input(t, bls12G1, bls12G1), bls12G1Times2)
+	expectBLS12Output(t, BLS12G1Add{}, bls12Input(t, bls12G1, bls12G1Neg), bls12Infinity(BLS12_G1_LEN))
+	expectBLS12Output(t, BLS12G1Add{}, bls12Input(t, bls12Infinity(BLS12_G1_LEN), bls12G1), bls12G1)
+
+	// Addition doesn't check the subgroup
+	if _, err := (BLS12G1Add{}).Run(nil, bls12Input(t, bls12G1NotInSubgroup, bls12G1)); err != nil {
+		t.Errorf("Expected a point outside the subgroup to be added, got %v", err)
+	}
+}
+
+func TestBLS12G1MSM(t *testing.T) {
+	expectBLS12Output(t, BLS12G1MSM{},
+		bls12Input(t, bls12G1, bls12Scalar(2), bls12G1Times2, bls12Scalar(3)),
+		bls12G1Times8)
+	expectBLS12Output(t, BLS12G1MSM{},
+		bls12Input(t, bls12G1Times2, bls12Scalar(0)),
+		bls12Infinity(BLS12_G1_LEN))
+
+	input := bls12Input(t, bls12G1NotInSubgroup, bls12Scalar(1))
+	if _, err := (BLS12G1MSM{}).Run(nil, input); err != errBLS12NotInSubgroup {
+		t.Errorf("Expected a point outside the subgroup to fail, got %v", err)
+	}
+}
+
+func TestBLS12G2Add(t *testing.T) {
+	expectBLS12Output(t, BLS12G2Add{}, bls12Input(t, bls12G2, bls12G2Times2), bls12G2Times3)
+	expectBLS12Output(t, BLS12G2Add{}, bls12Input(t, bls12G2, bls12G2), bls12G2Times2)
+	expectBLS12Output(t, BLS12G2Add{}, bls12Input(t, bls12G2, bls12G2Neg), bls12Infinity(BLS12_G2_LEN))
+}
+
+func TestBLS12G2MSM(t *testing.T) {
+	expectBLS12Output(t, BLS12G2MSM{},
+		bls12Input(t, bls12G2, bls12Scalar(2), bls12G2Times2, bls12Scalar(3)),
+		bls12G2Times8)
+}
+
+func TestBLS12Pairing(t *testing.T) {
+	one := bls12Scalar(1)
+	zero := bls12Scalar(0)
+
+	// e(2*G1, 3*G2) * e(-6*G1, G2) is one
+	expectBLS12Output(t, BLS12Pairing{},
+		bls12Input(t, bls12G1Times2, bls12G2Times3, bls12G1TimesNeg6, bls12G2),
+		one)
+	expectBLS12Output(t, BLS12Pairing{}, bls12Input(t, bls12G1, bls12G2Neg, bls12G1, bls12G2), one)
+	expectBLS12Output(t, BLS12Pairing{}, bls12Input(t, bls12G1, bls12G2), zero)
+	expectBLS12Output(t, BLS12Pairing{}, bls12Input(t, bls12Infinity(BLS12_G1_LEN), bls12G2), one)
+
+	input := bls12Input(t, bls12G1NotInSubgroup, bls12G2)
+	if _, err := (BLS12Pairing{}).Run(nil, input); err != errBLS12NotInSubgroup {
+		t.Errorf("Expected a point outside the subgroup to fail, got %v", err)
+	}
+}
+
+func TestBLS12MapToCurve(t *testing.T) {
+	g1 := bls12381.NewG1()
+	g2 := bls12381.NewG2()
+
+	// Every field element maps to a point of the subgroup, and different
+	// elements map to different points
+	seen := make(map[string]bool)
+	for _, n := range []int64{0, 1, 2, 1 << 40} {
+		input := bls12Input(t, bls12Fp(n))
+		output, err := BLS12MapFpToG1{}.Run(nil, input)
+		if err != nil {
+			t.Fatal(err)
+		}
+		p, err := g1.DecodePoint(output)
+		if err != nil {
+			t.Fatal(err)
+		}
+		if !g1.InCorrectSubgroup(p) {
+			t.Errorf("Expected %v to map into the G1 subgroup", n)
+		}
+		seen[hex.EncodeToString(output)] = true
+
+		// Use n as the c1 coefficient, so c0 and c1 are both exercised
+		input = bls12Input(t, bls12Fp(1), bls12Fp(n))
+		output, err = BLS12MapFp2ToG2{}.Run(nil, input)
+		if err != nil {
+			t.Fatal(err)
+		}
+		q, err := g2.DecodePoint(output)
+		if err != nil {
+			t.Fatal(err)
+		}
+		if !g2.InCorrectSubgroup(q) {
+			t.Errorf("Expected (1, %v) to map into the G2 subgroup", n)
+		}
+		seen[hex.EncodeToString(output)] = true
+	}
+	if len(seen) != 8 {
+		t.Errorf("Expected 8 distinct points, got %v", len(seen))
+	}
+
+	// Swapping the coefficients changes the point
+	a, _ := BLS12MapFp2ToG2{}.Run(nil, bls12Input(t, bls12Fp(1), bls12Fp(2)))
+	b, _ := BLS12MapFp2ToG2{}.Run(nil, bls12Input(t, bls12Fp(2), bls12Fp(1)))
+	if bytes.Equal(a, b) {
+		t.Errorf("Expected the order of the coefficients to matter")
+	}
+}
+
+func TestBLS12InvalidInput(t *testing.T) {
+	// Nonzero padding in the top 16 bytes of a field element
+	padded := "01" + bls12G1[2:]
+	// The field modulus itself isn't a field element
+	modulus := "000000000000000000000000000000001a0111ea397fe69a4b1ba7b6434bacd7" +
+		"64774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffaaab"
+	// G1 with its y coordinate incremented is not on the curve
+	offCurve := bls12G1[:len(bls12G1)-1] + "2"
+
+	cases := []struct {
+		precompile Precompile
+		input      string
+	}{
+		{BLS12G1Add{}, bls12G1},
+		{BLS12G1Add{}, bls12G1 + bls12G1 + "00"},
+		{BLS12G1Add{}, padded + bls12G1},
+		{BLS12G1Add{}, offCurve + bls12G1},
+		{BLS12G1MSM{}, ""},
+		{BLS12G1MSM{}, bls12G1},
+		{BLS12G1MSM{}, offCurve + bls12Scalar(1)},
+		{BLS12G2Add{}, bls12G2},
+		{BLS12G2Add{}, bls12G2 + bls12G1},
+		{BLS12G2MSM{}, ""},
+		{BLS12G2MSM{}, bls12G2 + bls12Scalar(1) + "00"},
+		{BLS12Pairing{}, ""},
+		{BLS12Pairing{}, bls12G1},
+		{BLS12Pairing{}, offCurve + bls12G2},
+		{BLS12MapFpToG1{}, modulus[:126]},
+		{BLS12MapFpToG1{}, padded[:128]},
+		{BLS12MapFpToG1{}, modulus},
+		{BLS12MapFp2ToG2{}, modulus},
+		{BLS12MapFp2ToG2{}, padded[:128] + bls12Fp(1)},
+		{BLS12MapFp2ToG2{}, bls12Fp(1) + modulus},
+	}
+	for i, c := range cases {
+		input := mustDecodeHex(t, c.input)
+		if output, err := c.precompile.Run(nil, input); err == nil {
+			t.Errorf("Case %v: Expected %v to fail, got %x", i, c.precompile.Name(), output)
+		}
+	}
+}
+
+func TestBLS12GasCosts(t *testing.T) {
+	g1Pairs := bls12Input(t, bls12G1, bls12Scalar(1), bls12G1, bls12Scalar(2))
+	if gas := (BLS12G1MSM{}).RequiredGas(g1Pairs); gas != 2*BLS12_G1MUL_GAS {
+		t.Errorf("Expected G1 MSM to cost %v gas, got %v", 2*BLS12_G1MUL_GAS, gas)
+	}
+
+	g2Pairs := bls12Input(t, bls12G2, bls12Scalar(1), bls12G2, bls12Scalar(2), bls12G2, bls12Scalar(3))
+	if gas := (BLS12G2MSM{}).RequiredGas(g2Pairs); gas != 3*BLS12_G2MUL_GAS {
+		t.Errorf("Expected G2 MSM to cost %v gas, got %v", 3*BLS12_G2MUL_GAS, gas)
+	}
+
+	pairs := bls12Input(t, bls12G1, bls12G2, bls12G1, bls12G2)
+	expected := uint64(BLS12_PAIRING_BASE_GAS + 2*BLS12_PAIRING_PER_PAIR_GAS)
+	if gas := (BLS12Pairing{}).RequiredGas(pairs); gas != expected {
+		t.Errorf("Expected pairing to cost %v gas, got %v", expected, gas)
+	}
+}
+
+func BenchmarkBLS12Pairing2(b *testing.B) {
+	input, _ := hex.DecodeString(bls12G1Times2 + bls12G2Times3 + bls12G1TimesNeg6 + bls12G2)
+	b.ResetTimer()
+	for i := 0; i < b.N; i++ {
+		if _, err := (BLS12Pairing{}).Run(nil, input); err != nil {
+			b.Fatal(err)
+		}
+	}
+}
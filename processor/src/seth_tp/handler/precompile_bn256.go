@@ -0,0 +1,193 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+package handler
+
+import (
+	. "common"
+	"errors"
+	"github.com/ethereum/go-ethereum/crypto/bn256"
+	"math/big"
+)
+
+// The alt_bn128 (bn254) precompiles of EIP-196 and EIP-197, used by Groth16
+// verifier contracts. The bn256 package picks Cloudflare's assembly optimized
+// implementation on amd64 and arm64.
+const (
+	BN256_ADD_ADDRESS     = 0x06
+	BN256_MUL_ADDRESS     = 0x07
+	BN256_PAIRING_ADDRESS = 0x08
+
+	BN256_POINT_LEN   = 64
+	BN256_SCALAR_LEN  = 32
+	BN256_G2_LEN      = 128
+	BN256_PAIRING_LEN = BN256_POINT_LEN + BN256_G2_LEN
+)
+
+// Bn256GasCosts are the gas prices of the bn256 precompiles, which are set by
+// the sawtooth.seth.gas_schedule.bn256_* settings
+type Bn256GasCosts struct {
+	Add            uint64
+	Mul            uint64
+	PairingBase    uint64
+	PairingPerPair uint64
+}
+
+// DefaultBn256GasCosts are the prices set by EIP-1108
+var DefaultBn256GasCosts = Bn256GasCosts{
+	Add:            150,
+	Mul:            6000,
+	PairingBase:    45000,
+	PairingPerPair: 34000,
+}
+
+var errBn256BadPairingInput = errors.New("Bad elliptic curve pairing size")
+
+func init() {
+	MustRegisterPrecompile(PrecompileAddress(BN256_ADD_ADDRESS), Bn256Add{})
+	MustRegisterPrecompile(PrecompileAddress(BN256_MUL_ADDRESS), Bn256ScalarMul{})
+	MustRegisterPrecompile(PrecompileAddress(BN256_PAIRING_ADDRESS), Bn256Pairing{})
+}
+
+// Bn256Add adds two G1 points
+type Bn256Add struct{}
+
+func (Bn256Add) Name() string {
+	return "bn256Add"
+}
+
+func (p Bn256Add) RequiredGas(input []byte) uint64 {
+	return p.RequiredGasWithConfig(DefaultEvmConfig(), input)
+}
+
+func (Bn256Add) RequiredGasWithConfig(config *EvmConfig, input []byte) uint64 {
+	return config.Bn256GasCosts.Add
+}
+
+func (Bn256Add) Run(caller *EvmAddr, input []byte) ([]byte, error) {
+	input = rightPad(input, 2*BN256_POINT_LEN)
+
+	x, err := newBn256G1(input[:BN256_POINT_LEN])
+	if err != nil {
+		return nil, err
+	}
+	y, err := newBn256G1(input[BN256_POINT_LEN : 2*BN256_POINT_LEN])
+	if err != nil {
+		return nil, err
+	}
+
+	return new(bn256.G1).Add(x, y).Marshal(), nil
+}
+
+// Bn256ScalarMul multiplies a G1 point by a scalar
+type Bn256ScalarMul struct{}
+
+func (Bn256ScalarMul) Name() string {
+	return "bn256ScalarMul"
+}
+
+func (p Bn256ScalarMul) RequiredGas(input []byte) uint64 {
+	return p.RequiredGasWithConfig(DefaultEvmConfig(), input)
+}
+
+func (Bn256ScalarMul) RequiredGasWithConfig(config *EvmConfig, input []byte) uint64 {
+	return config.Bn256GasCosts.Mul
+}
+
+func (Bn256ScalarMul) Run(caller *EvmAddr, input []byte) ([]byte, error) {
+	input = rightPad(input, BN256_POINT_LEN+BN256_SCALAR_LEN)
+
+	p, err := newBn256G1(input[:BN256_POINT_LEN])
+	if err != nil {
+		return nil, err
+	}
+	k := new(big.Int).SetBytes(input[BN256_POINT_LEN : BN256_POINT_LEN+BN256_SCALAR_LEN])
+
+	return new(bn256.G1).ScalarMult(p, k).Marshal(), nil
+}
+
+// Bn256Pairing checks whether the product of the pairings of the given (G1, G2)
+// pairs is one, returning 1 as a 32 byte word if it is and 0 if not
+type Bn256Pairing struct{}
+
+func (Bn256Pairing) Name() string {
+	return "bn256Pairing"
+}
+
+func (p Bn256Pairing) RequiredGas(input []byte) uint64 {
+	return p.RequiredGasWithConfig(DefaultEvmConfig(), input)
+}
+
+func (Bn256Pairing) RequiredGasWithConfig(config *EvmConfig, input []byte) uint64 {
+	k := uint64(len(input) / BN256_PAIRING_LEN)
+	costs := config.Bn256GasCosts
+	return costs.PairingBase + k*costs.PairingPerPair
+}
+
+func (Bn256Pairing) Run(caller *EvmAddr, input []byte) ([]byte, error) {
+	if len(input)%BN256_PAIRING_LEN != 0 {
+		return nil, errBn256BadPairingInput
+	}
+
+	var g1s []*bn256.G1
+	var g2s []*bn256.G2
+	for i := 0; i < len(input); i += BN256_PAIRING_LEN {
+		g1, err := newBn256G1(input[i : i+BN256_POINT_LEN])
+		if err != nil {
+			return nil, err
+		}
+		g2, err := newBn256G2(input[i+BN256_POINT_LEN : i+BN256_PAIRING_LEN])
+		if err != nil {
+			return nil, err
+		}
+		g1s = append(g1s, g1)
+		g2s = append(g2s, g2)
+	}
+
+	output := make([]byte, 32)
+	if bn256.PairingCheck(g1s, g2s) {
+		output[31] = 1
+	}
+	return output, nil
+}
+
+func newBn256G1(blob []byte) (*bn256.G1, error) {
+	p := new(bn256.G1)
+	if _, err := p.Unmarshal(blob); err != nil {
+		return nil, err
+	}
+	return p, nil
+}
+
+func newBn256G2(blob []byte) (*bn256.G2, error) {
+	p := new(bn256.G2)
+	if _, err := p.Unmarshal(blob); err != nil {
+		return nil, err
+	}
+	return p, nil
+}
+
+// rightPad returns the input extended with zeros to at least n bytes, which is
+// how the EVM treats short precompile input
+func rightPad(input []byte, n int) []byte {
+	if len(input) >= n {
+		return input
+	}
+	padded := make([]byte, n)
+	copy(padded, input)
+	return padded
+}
@@ -0,0 +1,101 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+package handler
+
+import (
+	"github.com/ethereum/go-ethereum/crypto/bn256"
+	"math/big"
+	"testing"
+)
+
+// pairingInput builds input for n pairs whose pairing product is one, by
+// alternating e(a*G1, G2) with e(-a*G1, G2)
+func pairingInput(n int) []byte {
+	g2 := new(bn256.G2).ScalarBaseMult(big.NewInt(1)).Marshal()
+
+	var input []byte
+	for i := 0; i < n; i++ {
+		p := new(bn256.G1).ScalarBaseMult(big.NewInt(int64(i/2 + 2)))
+		if i%2 == 1 {
+			p = new(bn256.G1).Neg(p)
+		}
+		input = append(input, p.Marshal()...)
+		input = append(input, g2...)
+	}
+	return input
+}
+
+func TestBn256PairingCheck(t *testing.T) {
+	output, err := Bn256Pairing{}.Run(nil, pairingInput(4))
+	if err != nil {
+		t.Fatal(err)
+	}
+	if output[31] != 1 {
+		t.Errorf("Expected pairing check to succeed, got %x", output)
+	}
+
+	// Dropping the last pair leaves an unbalanced product
+	input := pairingInput(4)
+	output, err = Bn256Pairing{}.Run(nil, input[:3*BN256_PAIRING_LEN])
+	if err != nil {
+		t.Fatal(err)
+	}
+	if output[31] != 0 {
+		t.Errorf("Expected pairing check to fail, got %x", output)
+	}
+}
+
+func TestBn256GasCosts(t *testing.T) {
+	if gas := (Bn256Pairing{}).RequiredGas(pairingInput(2)); gas != 45000+2*34000 {
+		t.Errorf("Expected pairing to cost 113000 gas, got %v", gas)
+	}
+
+	config := DefaultEvmConfig()
+	config.Bn256GasCosts = Bn256GasCosts{Add: 1, Mul: 2, PairingBase: 3, PairingPerPair: 4}
+	if gas := (Bn256Pairing{}).RequiredGasWithConfig(config, pairingInput(2)); gas != 3+2*4 {
+		t.Errorf("Expected pairing to cost 11 gas, got %v", gas)
+	}
+}
+
+func benchmarkPairing(b *testing.B, pairs int) {
+	input := pairingInput(pairs)
+	b.ResetTimer()
+	for i := 0; i < b.N; i++ {
+		if _, err := (Bn256Pairing{}).Run(nil, input); err != nil {
+			b.Fatal(err)
+		}
+	}
+}
+
+// A Groth16 verification pairs four points
+func BenchmarkBn256Pairing2(b *testing.B) { benchmarkPairing(b, 2) }
+func BenchmarkBn256Pairing4(b *testing.B) { benchmarkPairing(b, 4) }
+func BenchmarkBn256Pairing8(b *testing.B) { benchmarkPairing(b, 8) }
+
+func BenchmarkBn256ScalarMul(b *testing.B) {
+	input := append(
+		new(bn256.G1).ScalarBaseMult(big.NewInt(3)).Marshal(),
+		new(big.Int).Lsh(big.NewInt(1), 255).Bytes()...,
+	)
+	b.ResetTimer()
+	for i := 0; i < b.N; i++ {
+		if _, err := (Bn256ScalarMul{}).Run(nil, input); err != nil {
+			b.Fatal(err)
+		}
+	}
+}
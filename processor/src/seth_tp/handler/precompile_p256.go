@@ -0,0 +1,80 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+package handler
+
+import (
+	. "common"
+	"crypto/ecdsa"
+	"crypto/elliptic"
+	"math/big"
+)
+
+// P256VERIFY_ADDRESS and P256VERIFY_GAS follow EIP-7212
+const (
+	P256VERIFY_ADDRESS   = 0x100
+	P256VERIFY_GAS       = 3450
+	P256VERIFY_INPUT_LEN = 160
+)
+
+func init() {
+	MustRegisterPrecompile(PrecompileAddress(P256VERIFY_ADDRESS), P256Verify{})
+}
+
+// P256Verify verifies a secp256r1 (NIST P-256) ECDSA signature. The input is
+// the 32 byte message hash followed by the signature's r and s values and the
+// public key's x and y coordinates, each 32 bytes. A valid signature returns 1
+// as a 32 byte word; anything else, including malformed input, returns no data.
+type P256Verify struct{}
+
+func (P256Verify) Name() string {
+	return "p256Verify"
+}
+
+func (P256Verify) RequiredGas(input []byte) uint64 {
+	return P256VERIFY_GAS
+}
+
+func (P256Verify) Run(caller *EvmAddr, input []byte) ([]byte, error) {
+	if len(input) != P256VERIFY_INPUT_LEN {
+		return nil, nil
+	}
+
+	hash := input[0:32]
+	r := new(big.Int).SetBytes(input[32:64])
+	s := new(big.Int).SetBytes(input[64:96])
+	x := new(big.Int).SetBytes(input[96:128])
+	y := new(big.Int).SetBytes(input[128:160])
+
+	curve := elliptic.P256()
+	n := curve.Params().N
+	if r.Sign() <= 0 || r.Cmp(n) >= 0 || s.Sign() <= 0 || s.Cmp(n) >= 0 {
+		return nil, nil
+	}
+	if !curve.IsOnCurve(x, y) {
+		return nil, nil
+	}
+
+	pub := &ecdsa.PublicKey{Curve: curve, X: x, Y: y}
+	if !ecdsa.Verify(pub, hash, r, s) {
+		return nil, nil
+	}
+
+	output := make([]byte, 32)
+	output[31] = 1
+	return output, nil
+}
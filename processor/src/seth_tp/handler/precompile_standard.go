@@ -0,0 +1,250 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+package handler
+
+import (
+	. "common"
+	"encoding/binary"
+	"errors"
+	ellcurv "github.com/btcsuite/btcd/btcec"
+	"github.com/ethereum/go-ethereum/crypto/blake2b"
+	"github.com/hyperledger/burrow/execution/evm/sha3"
+	"math"
+	"math/big"
+)
+
+// The precompiles Ethereum has at 0x1 to 0x9 that the Burrow EVM lacks, priced
+// as they are on mainnet since Berlin, so that contracts compiled for Ethereum
+// run with the gas they expect. Burrow's own sha256, ripemd160 and identity at
+// 0x2 to 0x4 keep Burrow's prices, since changing them would change the gas
+// used by transactions already on the chain.
+const (
+	ECRECOVER_ADDRESS = 0x01
+	MODEXP_ADDRESS    = 0x05
+	BLAKE2F_ADDRESS   = 0x09
+
+	ECRECOVER_GAS       = 3000
+	MODEXP_MIN_GAS      = 200
+	MODEXP_GAS_DIVISOR  = 3
+	BLAKE2F_ROUND_GAS   = 1
+	ECRECOVER_INPUT_LEN = 128
+	BLAKE2F_INPUT_LEN   = 213
+)
+
+var errBlake2fBadInputLength = errors.New("Blake2f input must be 213 bytes")
+var errBlake2fBadFinalFlag = errors.New("Blake2f final block flag must be 0 or 1")
+
+func init() {
+	MustRegisterPrecompile(PrecompileAddress(ECRECOVER_ADDRESS), Ecrecover{})
+	MustRegisterPrecompile(PrecompileAddress(MODEXP_ADDRESS), ModExp{})
+	MustRegisterPrecompile(PrecompileAddress(BLAKE2F_ADDRESS), Blake2f{})
+}
+
+// Ecrecover recovers the Ethereum address of the key that signed a hash, as the
+// last 20 bytes of the Keccak hash of its uncompressed public key. This is not
+// the seth address of the key, which hashes the compressed key. Input that
+// isn't a valid signature returns nothing rather than failing.
+type Ecrecover struct{}
+
+func (Ecrecover) Name() string {
+	return "ecrecover"
+}
+
+func (Ecrecover) RequiredGas(input []byte) uint64 {
+	return ECRECOVER_GAS
+}
+
+func (Ecrecover) Run(caller *EvmAddr, input []byte) ([]byte, error) {
+	input = rightPad(input, ECRECOVER_INPUT_LEN)
+
+	v := new(big.Int).SetBytes(input[32:64])
+	r := new(big.Int).SetBytes(input[64:96])
+	s := new(big.Int).SetBytes(input[96:128])
+	n := ellcurv.S256().N
+	if !v.IsUint64() || (v.Uint64() != 27 && v.Uint64() != 28) ||
+		r.Sign() == 0 || r.Cmp(n) >= 0 || s.Sign() == 0 || s.Cmp(n) >= 0 {
+		return nil, nil
+	}
+
+	signature := make([]byte, 65)
+	signature[0] = byte(v.Uint64())
+	copy(signature[1:], input[64:128])
+
+	publicKey, _, err := ellcurv.RecoverCompact(ellcurv.S256(), signature, input[:32])
+	if err != nil {
+		return nil, nil
+	}
+
+	output := make([]byte, 32)
+	copy(output[12:], sha3.Sha3(publicKey.SerializeUncompressed()[1:])[12:])
+	return output, nil
+}
+
+// ModExp computes base**exponent % modulus for numbers of any length, as in
+// EIP-198, with the gas prices of EIP-2565
+type ModExp struct{}
+
+func (ModExp) Name() string {
+	return "modexp"
+}
+
+func (ModExp) RequiredGas(input []byte) uint64 {
+	baseLen := new(big.Int).SetBytes(getData(input, 0, 32))
+	expLen := new(big.Int).SetBytes(getData(input, 32, 32))
+	modLen := new(big.Int).SetBytes(getData(input, 64, 32))
+	input = modExpOperands(input)
+
+	// Only the first word of the exponent is looked at to price it
+	expHead := new(big.Int)
+	if big.NewInt(int64(len(input))).Cmp(baseLen) > 0 {
+		headLen := uint64(32)
+		if expLen.IsUint64() && expLen.Uint64() < headLen {
+			headLen = expLen.Uint64()
+		}
+		expHead.SetBytes(getData(input, baseLen.Uint64(), headLen))
+	}
+
+	iterations := new(big.Int)
+	if expLen.Cmp(big.NewInt(32)) > 0 {
+		iterations.Sub(expLen, big.NewInt(32))
+		iterations.Mul(iterations, big.NewInt(8))
+	}
+	if bitLen := expHead.BitLen(); bitLen > 0 {
+		iterations.Add(iterations, big.NewInt(int64(bitLen-1)))
+	}
+	if iterations.Sign() == 0 {
+		iterations.SetInt64(1)
+	}
+
+	// The cost of a multiplication grows with the square of the words of the
+	// longer of the base and the modulus
+	words := modLen
+	if baseLen.Cmp(modLen) > 0 {
+		words = baseLen
+	}
+	words = new(big.Int).Add(words, big.NewInt(7))
+	words.Div(words, big.NewInt(8))
+
+	gas := new(big.Int).Mul(words, words)
+	gas.Mul(gas, iterations)
+	gas.Div(gas, big.NewInt(MODEXP_GAS_DIVISOR))
+	if !gas.IsUint64() {
+		return math.MaxUint64
+	}
+	if gas.Uint64() < MODEXP_MIN_GAS {
+		return MODEXP_MIN_GAS
+	}
+	return gas.Uint64()
+}
+
+func (ModExp) Run(caller *EvmAddr, input []byte) ([]byte, error) {
+	// The lengths are bounded by the gas charged for them
+	baseLen := new(big.Int).SetBytes(getData(input, 0, 32)).Uint64()
+	expLen := new(big.Int).SetBytes(getData(input, 32, 32)).Uint64()
+	modLen := new(big.Int).SetBytes(getData(input, 64, 32)).Uint64()
+	input = modExpOperands(input)
+
+	if baseLen == 0 && modLen == 0 {
+		return []byte{}, nil
+	}
+
+	base := new(big.Int).SetBytes(getData(input, 0, baseLen))
+	exp := new(big.Int).SetBytes(getData(input, baseLen, expLen))
+	mod := new(big.Int).SetBytes(getData(input, baseLen+expLen, modLen))
+
+	if mod.Sign() == 0 {
+		return make([]byte, modLen), nil
+	}
+	return leftPad(base.Exp(base, exp, mod).Bytes(), int(modLen)), nil
+}
+
+// Blake2f runs the compression function F of BLAKE2b, as in EIP-152, charging
+// a gas per round
+type Blake2f struct{}
+
+func (Blake2f) Name() string {
+	return "blake2f"
+}
+
+func (Blake2f) RequiredGas(input []byte) uint64 {
+	if len(input) != BLAKE2F_INPUT_LEN {
+		return 0
+	}
+	return uint64(binary.BigEndian.Uint32(input[0:4])) * BLAKE2F_ROUND_GAS
+}
+
+func (Blake2f) Run(caller *EvmAddr, input []byte) ([]byte, error) {
+	if len(input) != BLAKE2F_INPUT_LEN {
+		return nil, errBlake2fBadInputLength
+	}
+	if input[212] > 1 {
+		return nil, errBlake2fBadFinalFlag
+	}
+
+	var h [8]uint64
+	var m [16]uint64
+	var t [2]uint64
+	for i := range h {
+		h[i] = binary.LittleEndian.Uint64(input[4+i*8:])
+	}
+	for i := range m {
+		m[i] = binary.LittleEndian.Uint64(input[68+i*8:])
+	}
+	t[0] = binary.LittleEndian.Uint64(input[196:])
+	t[1] = binary.LittleEndian.Uint64(input[204:])
+
+	blake2b.F(&h, m, t, input[212] == 1, binary.BigEndian.Uint32(input[0:4]))
+
+	output := make([]byte, 64)
+	for i := range h {
+		binary.LittleEndian.PutUint64(output[i*8:], h[i])
+	}
+	return output, nil
+}
+
+// modExpOperands returns the input of ModExp after the lengths of its operands
+func modExpOperands(input []byte) []byte {
+	if len(input) <= 96 {
+		return nil
+	}
+	return input[96:]
+}
+
+// getData returns size bytes of the input from start, padded with zeros past
+// its end
+func getData(input []byte, start, size uint64) []byte {
+	length := uint64(len(input))
+	if start > length {
+		start = length
+	}
+	end := start + size
+	if end > length || end < start {
+		end = length
+	}
+	return rightPad(input[start:end], int(size))
+}
+
+// leftPad returns the input extended with leading zeros to at least n bytes
+func leftPad(input []byte, n int) []byte {
+	if len(input) >= n {
+		return input
+	}
+	padded := make([]byte, n)
+	copy(padded[n-len(input):], input)
+	return padded
+}
@@ -0,0 +1,116 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+package handler
+
+import (
+	"encoding/hex"
+	"testing"
+)
+
+func mustDecodeHex(t *testing.T, s string) []byte {
+	b, err := hex.DecodeString(s)
+	if err != nil {
+		t.Fatal(err)
+	}
+	return b
+}
+
+func TestEcrecover(t *testing.T) {
+	input := mustDecodeHex(t,
+		"38d18acb67d25c8bb9942764b62f18e17054f66a817bd4295423adf9ed98873e"+
+			"000000000000000000000000000000000000000000000000000000000000001b"+
+			"38d18acb67d25c8bb9942764b62f18e17054f66a817bd4295423adf9ed98873e"+
+			"789d1dd423d25f0772d2748d60f7e4b81bb14d086eba8e8e8efb6dcff8a4ae02")
+
+	output, err := Ecrecover{}.Run(nil, input)
+	if err != nil {
+		t.Fatal(err)
+	}
+	expected := "000000000000000000000000ceaccac640adf55b2028469bd36ba501f28b699d"
+	if hex.EncodeToString(output) != expected {
+		t.Errorf("Expected %v, got %x", expected, output)
+	}
+
+	// A v other than 27 or 28 isn't a signature
+	input[63] = 29
+	output, err = Ecrecover{}.Run(nil, input)
+	if err != nil || len(output) != 0 {
+		t.Errorf("Expected no output for an invalid signature, got %x, %v", output, err)
+	}
+}
+
+func TestModExp(t *testing.T) {
+	// 3**5 % 7
+	input := mustDecodeHex(t,
+		"0000000000000000000000000000000000000000000000000000000000000001"+
+			"0000000000000000000000000000000000000000000000000000000000000001"+
+			"0000000000000000000000000000000000000000000000000000000000000001"+
+			"030507")
+
+	output, err := ModExp{}.Run(nil, input)
+	if err != nil {
+		t.Fatal(err)
+	}
+	if hex.EncodeToString(output) != "05" {
+		t.Errorf("Expected 05, got %x", output)
+	}
+	if gas := (ModExp{}).RequiredGas(input); gas != MODEXP_MIN_GAS {
+		t.Errorf("Expected modexp to cost %v gas, got %v", MODEXP_MIN_GAS, gas)
+	}
+
+	// A modulus of 64 bytes and an exponent of 2**255 cost 8**2 * 255 / 3
+	input = mustDecodeHex(t,
+		"0000000000000000000000000000000000000000000000000000000000000001"+
+			"0000000000000000000000000000000000000000000000000000000000000020"+
+			"0000000000000000000000000000000000000000000000000000000000000040"+
+			"03"+
+			"8000000000000000000000000000000000000000000000000000000000000000")
+	if gas := (ModExp{}).RequiredGas(input); gas != 5440 {
+		t.Errorf("Expected modexp to cost 5440 gas, got %v", gas)
+	}
+}
+
+func TestBlake2f(t *testing.T) {
+	// The compression of "abc" in 12 rounds, which is its BLAKE2b-512 hash
+	input := mustDecodeHex(t,
+		"0000000c48c9bdf267e6096a3ba7ca8485ae67bb2bf894fe72f36e3cf1361d5f"+
+			"3af54fa5d182e6ad7f520e511f6c3e2b8c68059b6bbd41fbabd9831f79217e13"+
+			"19cde05b61626300000000000000000000000000000000000000000000000000"+
+			"0000000000000000000000000000000000000000000000000000000000000000"+
+			"0000000000000000000000000000000000000000000000000000000000000000"+
+			"0000000000000000000000000000000000000000000000000000000000000000"+
+			"000000000300000000000000000000000000000001")
+
+	output, err := Blake2f{}.Run(nil, input)
+	if err != nil {
+		t.Fatal(err)
+	}
+	expected := "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1" +
+		"7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923"
+	if hex.EncodeToString(output) != expected {
+		t.Errorf("Expected %v, got %x", expected, output)
+	}
+	if gas := (Blake2f{}).RequiredGas(input); gas != 12 {
+		t.Errorf("Expected blake2f to cost 12 gas, got %v", gas)
+	}
+
+	input[212] = 2
+	if _, err := (Blake2f{}).Run(nil, input); err != errBlake2fBadFinalFlag {
+		t.Errorf("Expected a bad final flag to fail, got %v", err)
+	}
+}
@@ -0,0 +1,252 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+package handler
+
+import (
+	. "common"
+	"errors"
+	"fmt"
+	"github.com/hyperledger/burrow/acm"
+	"github.com/hyperledger/burrow/acm/state"
+	"github.com/hyperledger/burrow/binary"
+	"github.com/hyperledger/burrow/crypto"
+	"github.com/hyperledger/burrow/execution/evm"
+	"github.com/hyperledger/burrow/logging"
+	"sort"
+	"sync"
+)
+
+// Precompile is a contract implemented natively by the transaction processor.
+// Operators add their own by calling RegisterPrecompile from an init function
+// in a file compiled into this package, optionally behind a build tag. Like the
+// precompiles seth adds to the Burrow EVM, they only run from family version
+// 1.1. In family version 1.0 a call to their address runs as a call to an
+// empty account.
+type Precompile interface {
+	// Name is used when logging the registered precompiles
+	Name() string
+
+	// RequiredGas returns the gas charged for running the precompile on input
+	RequiredGas(input []byte) uint64
+
+	// Run executes the precompile. Returning an error reverts the call.
+	Run(caller *EvmAddr, input []byte) ([]byte, error)
+}
+
+// StatefulPrecompile is a Precompile that reads EVM state. RunWithState is
+// called instead of Run.
+type StatefulPrecompile interface {
+	Precompile
+
+	RunWithState(st state.Reader, caller *EvmAddr, input []byte) ([]byte, error)
+}
+
+// ConfiguredPrecompile is a Precompile whose gas price is set by the EVM
+// configuration. RequiredGasWithConfig is called instead of RequiredGas.
+type ConfiguredPrecompile interface {
+	Precompile
+
+	RequiredGasWithConfig(config *EvmConfig, input []byte) uint64
+}
+
+var precompilesMutex sync.Mutex
+var precompiles = make(map[EvmAddr]Precompile)
+
+// RegisterPrecompile makes p callable at the given address. Precompiles can
+// only be registered at addresses reserved for them, and an address can't be
+// registered twice or shadow one of the EVM's built in contracts.
+func RegisterPrecompile(address *EvmAddr, p Precompile) error {
+	if !IsPrecompileAddress(address) {
+		return fmt.Errorf(
+			"Precompile %v can't be registered at %v, address must be at most %#x",
+			p.Name(), address, MAX_PRECOMPILE_ADDRESS,
+		)
+	}
+
+	if *address == *PrecompileAddress(CALL_STATE_ADDRESS) {
+		return fmt.Errorf(
+			"Precompile %v can't be registered at %v, the address is reserved",
+			p.Name(), address,
+		)
+	}
+
+	precompilesMutex.Lock()
+	defer precompilesMutex.Unlock()
+
+	if existing, exists := precompiles[*address]; exists {
+		return fmt.Errorf(
+			"Can't register precompile %v at %v, %v is already registered there",
+			p.Name(), address, existing.Name(),
+		)
+	}
+
+	addr := crypto.AddressFromWord256(address.ToWord256())
+	if !evm.RegisterNativeContract(addr, precompileContract(p)) {
+		return fmt.Errorf(
+			"Can't register precompile %v at %v, the EVM already has a contract there",
+			p.Name(), address,
+		)
+	}
+
+	precompiles[*address] = p
+
+	return nil
+}
+
+// MustRegisterPrecompile is like RegisterPrecompile, but panics on failure.
+// It is meant to be called from init functions.
+func MustRegisterPrecompile(address *EvmAddr, p Precompile) {
+	err := RegisterPrecompile(address, p)
+	if err != nil {
+		panic(err)
+	}
+}
+
+// IsPrecompileAddress returns true if the address is in the range reserved
+// for precompiles, i.e. every byte but the last two is zero.
+func IsPrecompileAddress(address *EvmAddr) bool {
+	for _, b := range address[:EVMADDRLEN-2] {
+		if b != 0 {
+			return false
+		}
+	}
+	return true
+}
+
+// PrecompileAddress builds the address of the precompile with the given number
+func PrecompileAddress(n uint16) *EvmAddr {
+	var address EvmAddr
+	address[EVMADDRLEN-2] = byte(n >> 8)
+	address[EVMADDRLEN-1] = byte(n)
+	return &address
+}
+
+// RegisteredPrecompiles returns a description of each registered precompile,
+// ordered by address
+func RegisteredPrecompiles() []string {
+	precompilesMutex.Lock()
+	defer precompilesMutex.Unlock()
+
+	descriptions := make([]string, 0, len(precompiles))
+	for address, p := range precompiles {
+		descriptions = append(descriptions, fmt.Sprintf("%v: %v", address.String(), p.Name()))
+	}
+	sort.Strings(descriptions)
+
+	return descriptions
+}
+
+// CALL_STATE_ADDRESS is reserved for the native contracts of a call to find
+// the transaction running it through the state of the call. It holds no
+// account and no precompile can be registered there.
+const CALL_STATE_ADDRESS = MAX_PRECOMPILE_ADDRESS
+
+var errNotRunning = errors.New("Precompile was called outside of a transaction")
+
+// The native contracts of the Burrow EVM are registered globally and are only
+// given the state of the call. Each transaction running its EVM is given an id
+// here, which the state answers with as the storage of CALL_STATE_ADDRESS, so
+// that its precompiles find the transaction's configuration and rules. The
+// lock is only held to add and look up transactions, so the EVMs of different
+// transactions run at the same time.
+var running = struct {
+	sync.RWMutex
+	next  uint64
+	state map[uint64]*SawtoothAppState
+}{state: make(map[uint64]*SawtoothAppState)}
+
+// startRunning registers a transaction whose EVM is about to run, returning
+// the function that removes it once it's done
+func startRunning(sapps *SawtoothAppState) func() {
+	running.Lock()
+	defer running.Unlock()
+
+	running.next++
+	id := running.next
+	running.state[id] = sapps
+	sapps.callState = binary.Uint64ToWord256(id)
+
+	return func() {
+		running.Lock()
+		defer running.Unlock()
+
+		delete(running.state, id)
+	}
+}
+
+// runningState returns the transaction running the call whose state is st
+func runningState(st state.Reader) (*SawtoothAppState, error) {
+	address := crypto.AddressFromWord256(PrecompileAddress(CALL_STATE_ADDRESS).ToWord256())
+	id, err := st.GetStorage(address, binary.Zero256)
+	if err != nil {
+		return nil, err
+	}
+
+	running.RLock()
+	defer running.RUnlock()
+
+	sapps, exists := running.state[binary.Uint64FromWord256(id)]
+	if !exists {
+		return nil, errNotRunning
+	}
+	return sapps, nil
+}
+
+// precompileContract adapts a Precompile to the native contract interface of
+// the Burrow EVM
+func precompileContract(p Precompile) evm.NativeContract {
+	return func(st state.ReaderWriter, caller acm.Account, input []byte, gas *uint64,
+		logger *logging.Logger) ([]byte, error) {
+
+		sapps, err := runningState(st)
+		if err != nil {
+			return nil, err
+		}
+
+		// Family version 1.0 was executed without these precompiles, when a call
+		// to their address ran against an empty account: it returned nothing
+		// and used no gas. Burrow has no way to leave a native contract out of a
+		// call, so the contract does the same.
+		if !sapps.rules.Precompiles {
+			return nil, nil
+		}
+
+		var required uint64
+		if cp, ok := p.(ConfiguredPrecompile); ok {
+			required = cp.RequiredGasWithConfig(sapps.config, input)
+		} else {
+			required = p.RequiredGas(input)
+		}
+		if *gas < required {
+			return nil, fmt.Errorf(
+				"Insufficient gas for precompile %v: %v < %v", p.Name(), *gas, required,
+			)
+		}
+		*gas -= required
+
+		sender, err := NewEvmAddrFromBytes(caller.Address().Bytes())
+		if err != nil {
+			return nil, err
+		}
+
+		if sp, ok := p.(StatefulPrecompile); ok {
+			return sp.RunWithState(st, sender, input)
+		}
+		return p.Run(sender, input)
+	}
+}
@@ -0,0 +1,65 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+package handler
+
+import (
+	. "common"
+	"github.com/hyperledger/burrow/acm"
+	"testing"
+)
+
+func TestPrecompilesRunAsEmptyAccountsInFamilyVersion1_0(t *testing.T) {
+	sapps := &SawtoothAppState{config: DefaultEvmConfig(), rules: getFamilyVersion(FAMILY_VERSION).Rules}
+	defer startRunning(sapps)()
+
+	gas := uint64(100000)
+	contract := precompileContract(Bn256Add{})
+	output, err := contract(sapps, acm.ConcreteAccount{}.MutableAccount(), nil, &gas, vm_logger)
+	if err != nil {
+		t.Fatal(err)
+	}
+	if len(output) != 0 || gas != 100000 {
+		t.Errorf("Expected no output and no gas used, got %x and %v gas left", output, gas)
+	}
+}
+
+func TestPrecompilesReadTheConfigurationOfTheirTransaction(t *testing.T) {
+	cheap := &SawtoothAppState{config: DefaultEvmConfig(), rules: getFamilyVersion(FAMILY_VERSION_1_1).Rules}
+	cheap.config.Bn256GasCosts.Add = 10
+	dear := &SawtoothAppState{config: DefaultEvmConfig(), rules: cheap.rules}
+	dear.config.Bn256GasCosts.Add = 1000
+
+	// Both transactions run their EVM at once
+	defer startRunning(cheap)()
+	defer startRunning(dear)()
+
+	contract := precompileContract(Bn256Add{})
+	for _, sapps := range []*SawtoothAppState{cheap, dear} {
+		gas := uint64(100000)
+		_, err := contract(sapps, acm.ConcreteAccount{}.MutableAccount(), nil, &gas, vm_logger)
+		if err != nil {
+			t.Fatal(err)
+		}
+		if used := 100000 - gas; used != sapps.config.Bn256GasCosts.Add {
+			t.Errorf("Expected %v gas used, got %v", sapps.config.Bn256GasCosts.Add, used)
+		}
+	}
+
+	if _, err := runningState(&SawtoothAppState{}); err != errNotRunning {
+		t.Errorf("Expected a transaction that isn't running not to be found, got %v", err)
+	}
+}
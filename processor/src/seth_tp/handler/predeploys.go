@@ -0,0 +1,107 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+package handler
+
+import (
+	. "common"
+	"encoding/hex"
+	"fmt"
+	. "protobuf/seth_pb2"
+	"strings"
+)
+
+// Infrastructure contracts that tooling expects at the same address on every
+// chain. Their addresses on Ethereum come from keyless deployment transactions
+// that can't be replayed on seth, so instead the runtime code of each is set in
+// a setting, normally in the genesis block, and the contract then exists at its
+// standard address.
+var wellKnownContracts = []struct {
+	Setting string
+	Address string
+}{
+	{"sawtooth.seth.predeploy.multicall3", "ca11bde05977b3631167028862be2a173976ca11"},
+	{"sawtooth.seth.predeploy.erc1820_registry", "1820a4b7618bde71dce8cdc73aab6c95905fad24"},
+	{"sawtooth.seth.predeploy.deterministic_deployment_proxy", "4e59b44847b379578588920ca78fbf26c0b4956c"},
+}
+
+func predeploySettingKeys() []string {
+	keys := make([]string, len(wellKnownContracts))
+	for i, contract := range wellKnownContracts {
+		keys[i] = contract.Setting
+	}
+	return keys
+}
+
+// parsePredeploys returns the runtime code of the well-known contracts whose
+// settings are set, by address
+func parsePredeploys(values map[string]string) map[EvmAddr][]byte {
+	predeploys := make(map[EvmAddr][]byte)
+	for _, contract := range wellKnownContracts {
+		s, exists := values[contract.Setting]
+		if !exists {
+			continue
+		}
+		code, err := hex.DecodeString(strings.TrimPrefix(s, "0x"))
+		if err != nil || len(code) == 0 {
+			logger.Warnf("Ignoring invalid %v", contract.Setting)
+			continue
+		}
+		addr, err := NewEvmAddrFromString(contract.Address)
+		if err != nil {
+			panic(err.Error())
+		}
+		predeploys[*addr] = code
+	}
+	return predeploys
+}
+
+// mustGetEntry is StateManager.MustGetEntry, except that well-known contracts
+// exist before they are written to state
+func (s *SawtoothAppState) mustGetEntry(vmAddress *EvmAddr) *EvmEntry {
+	entry, err := s.mgr.GetEntry(vmAddress)
+	if err != nil {
+		panic(fmt.Sprintf(
+			"Failed to GetEntry(%v): %v", vmAddress, err,
+		))
+	}
+	if entry == nil {
+		entry = s.getPredeployEntry(vmAddress)
+	}
+	if entry == nil {
+		panic(fmt.Sprintf(
+			"Tried to GetEntry(%v) but nothing exists there", vmAddress,
+		))
+	}
+	return entry
+}
+
+// getPredeployEntry returns the entry of a well-known contract that hasn't been
+// written to state yet, or nil if there is no well-known contract at the address
+func (s *SawtoothAppState) getPredeployEntry(vmAddress *EvmAddr) *EvmEntry {
+	code, exists := s.config.Predeploys[*vmAddress]
+	if !exists {
+		return nil
+	}
+	return &EvmEntry{
+		Account: &EvmStateAccount{
+			Address:     vmAddress.Bytes(),
+			Code:        code,
+			Permissions: &EvmPermissions{},
+		},
+	}
+}
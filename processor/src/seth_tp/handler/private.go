@@ -0,0 +1,96 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+package handler
+
+import (
+	. "common"
+	"github.com/hyperledger/burrow/acm"
+	"github.com/hyperledger/sawtooth-sdk-go/processor"
+	. "protobuf/seth_pb2"
+)
+
+// PrivateTransaction is a contract creation or message call whose payload is
+// held by the privacy managers of its participants rather than stored on chain
+type PrivateTransaction struct {
+	Sender      *EvmAddr
+	// To is nil if the transaction creates a contract
+	To          *EvmAddr
+	Nonce       uint64
+	GasLimit    uint64
+	PayloadHash []byte
+}
+
+// PrivateExecutor executes private transactions against the private state of
+// the node's privacy group. Only nodes that are participants have the payload,
+// so an executor should ignore transactions its privacy manager doesn't know.
+//
+// Private state is not part of the global state, so it is not covered by
+// consensus. Because a transaction can be applied more than once, for example
+// while the validator is choosing between forks, an executor must be able to
+// discard the results of transactions in blocks that are not committed.
+type PrivateExecutor interface {
+	Execute(txn *PrivateTransaction) error
+}
+
+var privateExecutor PrivateExecutor
+
+// SetPrivateExecutor makes this node a participant in private transactions.
+// It must be called before the processor starts handling transactions.
+func SetPrivateExecutor(executor PrivateExecutor) {
+	privateExecutor = executor
+}
+
+// checkPrivatePayload verifies that a private transaction doesn't also carry a
+// public payload
+func checkPrivatePayload(plaintext []byte, encrypted *EncryptedPayload) error {
+	if len(plaintext) > 0 || encrypted != nil {
+		return &processor.InvalidTransactionError{
+			Msg: "Private transaction must not contain a plaintext or encrypted payload",
+		}
+	}
+	return nil
+}
+
+// applyPrivateTransaction records a private transaction in global state, which
+// only increments the sender's nonce, and then hands it to the private
+// executor. A failure in private execution doesn't invalidate the transaction,
+// since it would be invisible to nodes that aren't participants.
+func applyPrivateTransaction(sapps *SawtoothAppState, senderAcct *acm.MutableAccount,
+	txn *PrivateTransaction) HandlerResult {
+
+	senderAcct.IncSequence()
+
+	err := sapps.UpdateAccount(senderAcct)
+	if err != nil {
+		return HandlerResult{
+			Error: &processor.InvalidTransactionError{Msg: err.Error()},
+		}
+	}
+
+	if privateExecutor == nil {
+		logger.Debugf("Not executing private transaction %x, no private executor", txn.PayloadHash)
+		return HandlerResult{}
+	}
+
+	err = privateExecutor.Execute(txn)
+	if err != nil {
+		logger.Warnf("Private transaction %x failed: %v", txn.PayloadHash, err)
+	}
+
+	return HandlerResult{}
+}
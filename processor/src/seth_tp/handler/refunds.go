@@ -0,0 +1,63 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+package handler
+
+// refundSchedule is the gas a hard fork refunds for clearing a storage slot
+// and for removing an account with SELFDESTRUCT, and what the gas used by a
+// transaction is divided by to get the most it may be refunded. London cut
+// these down with EIP-3529.
+type refundSchedule struct {
+	storageClear uint64
+	selfDestruct uint64
+	maxQuotient  uint64
+}
+
+var refundSchedules = map[string]refundSchedule{
+	FORK_ISTANBUL: {storageClear: 15000, selfDestruct: 24000, maxQuotient: 2},
+	FORK_BERLIN:   {storageClear: 15000, selfDestruct: 24000, maxQuotient: 2},
+	FORK_LONDON:   {storageClear: 4800, selfDestruct: 0, maxQuotient: 5},
+}
+
+// refundedGas returns the gas used by a transaction once the refunds it
+// earned are taken off, up to the cap of the fork
+func refundedGas(fork string, gasUsed, refund uint64) uint64 {
+	schedule, exists := refundSchedules[fork]
+	if !exists {
+		return gasUsed
+	}
+	if most := gasUsed / schedule.maxQuotient; refund > most {
+		refund = most
+	}
+	return gasUsed - refund
+}
+
+// refundStorageClear records the refund for setting a storage slot that held
+// a value to zero
+func (s *SawtoothAppState) refundStorageClear() {
+	s.refund += refundSchedules[s.config.Fork].storageClear
+}
+
+// refundSelfDestruct records the refund for removing an account
+func (s *SawtoothAppState) refundSelfDestruct() {
+	s.refund += refundSchedules[s.config.Fork].selfDestruct
+}
+
+// refunded returns the gas used by the transaction once its refunds are taken
+// off
+func (s *SawtoothAppState) refunded(gasUsed uint64) uint64 {
+	return refundedGas(s.config.Fork, gasUsed, s.refund)
+}
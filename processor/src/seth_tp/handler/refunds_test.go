@@ -0,0 +1,48 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+package handler
+
+import (
+	"testing"
+)
+
+func TestRefundedGas(t *testing.T) {
+	cases := []struct {
+		fork    string
+		gasUsed uint64
+		refund  uint64
+		want    uint64
+	}{
+		// Refunds below the cap are taken off in full
+		{FORK_ISTANBUL, 100000, 15000, 85000},
+		{FORK_LONDON, 100000, 4800, 95200},
+		// Before London at most half the gas used is refunded, since then a fifth
+		{FORK_ISTANBUL, 40000, 39000, 20000},
+		{FORK_BERLIN, 40000, 39000, 20000},
+		{FORK_LONDON, 40000, 39000, 32000},
+		// Chains without a fork set and unknown forks refund nothing
+		{"", 100000, 15000, 100000},
+		{"frontier", 40000, 39000, 40000},
+	}
+	for _, c := range cases {
+		got := refundedGas(c.fork, c.gasUsed, c.refund)
+		if got != c.want {
+			t.Errorf("refundedGas(%v, %v, %v) = %v, want %v",
+				c.fork, c.gasUsed, c.refund, got, c.want)
+		}
+	}
+}
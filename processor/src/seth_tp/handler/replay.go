@@ -0,0 +1,185 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+package handler
+
+import (
+	"fmt"
+	"github.com/hyperledger/sawtooth-sdk-go/processor"
+	"strings"
+)
+
+// StateContext is the part of the validator's context that transactions are
+// executed against. It is satisfied by *processor.Context, and by ReplayContext
+// when committed transactions are executed again offline.
+type StateContext interface {
+	GetState(addresses []string) (map[string][]byte, error)
+	SetState(pairs map[string][]byte) ([]string, error)
+	DeleteState(addresses []string) ([]string, error)
+	AddEvent(eventType string, attributes []processor.Attribute, data []byte) error
+	AddReceiptData(data []byte) error
+}
+
+// StateReader reads an address from the state a replay starts from, returning
+// nil if nothing is stored there.
+type StateReader func(address string) ([]byte, error)
+
+// ReplayEvent is an event a replayed transaction emitted
+type ReplayEvent struct {
+	EventType  string
+	Attributes []processor.Attribute
+	Data       []byte
+}
+
+// ReplayContext executes transactions against a snapshot of state, which is
+// read lazily and overlaid with the changes that are applied to it. What each
+// transaction does is recorded instead of being sent to a validator, and only
+// lasts until the next transaction. Like the validator, it only lets a
+// transaction read its inputs and change its outputs.
+type ReplayContext struct {
+	read    StateReader
+	entries map[string][]byte
+
+	// The address prefixes the current transaction declared
+	inputs  []string
+	outputs []string
+
+	// What the current transaction did, by address. Deleted addresses map to nil.
+	Changes     map[string][]byte
+	Events      []ReplayEvent
+	ReceiptData [][]byte
+}
+
+func NewReplayContext(read StateReader) *ReplayContext {
+	context := &ReplayContext{
+		read:    read,
+		entries: make(map[string][]byte),
+	}
+	context.Reset(nil, nil)
+	return context
+}
+
+// Reset discards what the current transaction did, and starts the next one
+// with the inputs and outputs of its header
+func (context *ReplayContext) Reset(inputs []string, outputs []string) {
+	context.inputs = inputs
+	context.outputs = outputs
+	context.Changes = make(map[string][]byte)
+	context.Events = nil
+	context.ReceiptData = nil
+}
+
+// Apply overlays changes onto the state, as when a transaction is committed.
+// Deleted addresses map to nil.
+func (context *ReplayContext) Apply(changes map[string][]byte) {
+	for address, data := range changes {
+		context.entries[address] = data
+	}
+}
+
+// GetState leaves out addresses that nothing is stored at, like the validator
+func (context *ReplayContext) GetState(addresses []string) (map[string][]byte, error) {
+	if !authorized(addresses, context.inputs) {
+		return nil, &processor.AuthorizationException{Msg: fmt.Sprint(
+			"Tried to get unauthorized address: ", addresses,
+		)}
+	}
+
+	results := make(map[string][]byte)
+	for _, address := range addresses {
+		data, cached := context.Changes[address]
+		if !cached {
+			data, cached = context.entries[address]
+		}
+		if !cached {
+			var err error
+			data, err = context.read(address)
+			if err != nil {
+				return nil, err
+			}
+			context.entries[address] = data
+		}
+		if data != nil {
+			results[address] = data
+		}
+	}
+	return results, nil
+}
+
+func (context *ReplayContext) SetState(pairs map[string][]byte) ([]string, error) {
+	addresses := make([]string, 0, len(pairs))
+	for address := range pairs {
+		addresses = append(addresses, address)
+	}
+	if !authorized(addresses, context.outputs) {
+		return nil, &processor.AuthorizationException{Msg: fmt.Sprint(
+			"Tried to set unauthorized address: ", addresses,
+		)}
+	}
+
+	for address, data := range pairs {
+		if data == nil {
+			data = []byte{}
+		}
+		context.Changes[address] = data
+	}
+	return addresses, nil
+}
+
+func (context *ReplayContext) DeleteState(addresses []string) ([]string, error) {
+	if !authorized(addresses, context.outputs) {
+		return nil, &processor.AuthorizationException{Msg: fmt.Sprint(
+			"Tried to delete unauthorized address: ", addresses,
+		)}
+	}
+
+	for _, address := range addresses {
+		context.Changes[address] = nil
+	}
+	return addresses, nil
+}
+
+func (context *ReplayContext) AddEvent(eventType string, attributes []processor.Attribute, data []byte) error {
+	context.Events = append(context.Events, ReplayEvent{
+		EventType:  eventType,
+		Attributes: attributes,
+		Data:       data,
+	})
+	return nil
+}
+
+func (context *ReplayContext) AddReceiptData(data []byte) error {
+	context.ReceiptData = append(context.ReceiptData, data)
+	return nil
+}
+
+// authorized returns whether every address starts with one of the prefixes
+func authorized(addresses []string, prefixes []string) bool {
+	for _, address := range addresses {
+		allowed := false
+		for _, prefix := range prefixes {
+			if strings.HasPrefix(address, prefix) {
+				allowed = true
+				break
+			}
+		}
+		if !allowed {
+			return false
+		}
+	}
+	return true
+}
@@ -20,11 +20,11 @@ package handler
 import (
 	. "common"
 	"fmt"
+	"github.com/golang/protobuf/proto"
 	"github.com/hyperledger/burrow/acm"
 	"github.com/hyperledger/burrow/binary"
 	"github.com/hyperledger/burrow/crypto"
 	"github.com/hyperledger/burrow/permission"
-	"github.com/hyperledger/sawtooth-sdk-go/processor"
 	. "protobuf/seth_pb2"
 )
 
@@ -33,13 +33,32 @@ import (
 // SawtoothAppState implements the interface used by the Burrow EVM to
 // access global state
 type SawtoothAppState struct {
-	mgr *StateManager
+	mgr    *StateManager
+	config *EvmConfig
+	rules  Rules
+
+	// The gas refunded for the storage cleared and the accounts removed by the
+	// transaction, before the refund is capped
+	refund uint64
+
+	// Only set in gas audit mode
+	gasProfiler *gasProfiler
+
+	// The id the transaction's precompiles find it by while its EVM runs,
+	// returned as the storage of CALL_STATE_ADDRESS
+	callState binary.Word256
 }
 
-func NewSawtoothAppState(state *processor.Context) *SawtoothAppState {
-	return &SawtoothAppState{
-		mgr: NewStateManager(state),
+func NewSawtoothAppState(state StateContext, config *EvmConfig, rules Rules) *SawtoothAppState {
+	sapps := &SawtoothAppState{
+		mgr:    NewStateManager(state),
+		config: config,
+		rules:  rules,
+	}
+	if gasAudit {
+		sapps.gasProfiler = newGasProfiler()
 	}
+	return sapps
 }
 
 // GetAccount retrieves an existing account with the given address. Returns nil
@@ -56,6 +75,9 @@ func (s *SawtoothAppState) GetAccount(addr crypto.Address) (acm.Account, error)
 	if err != nil {
 		return nil, err
 	}
+	if entry == nil {
+		entry = s.getPredeployEntry(vmAddress)
+	}
 	if entry == nil {
 		return nil, nil
 	}
@@ -85,16 +107,27 @@ func (s *SawtoothAppState) UpdateAccount(acct acm.Account) error {
 			return err
 		}
 	}
+	if len(entry.GetArchiveHash()) > 0 {
+		return fmt.Errorf("A contract is archived at %v", vmAddress)
+	}
 
-	entry.Account = toStateAccount(acct)
+	// Accounts are written back after every call even if they didn't change.
+	// Skipping those writes lets a transaction that only touches a contract's
+	// storage slots declare the contract's account entry as an input only.
+	account := toStateAccount(acct)
+	if s.rules.SlotStorage && proto.Equal(entry.Account, account) {
+		return nil
+	}
+	entry.Account = account
 
 	s.mgr.MustSetEntry(vmAddress, entry)
 
 	return nil
 }
 
-// RemoveAccount removes the account and associated storage from global state
-// and panics if it doesn't exist.
+// RemoveAccount removes the account and the storage left in its entry from
+// global state, and panics if it doesn't exist. Its other storage slots read
+// as zero from then on, even if an account is created at the address again.
 func (s *SawtoothAppState) RemoveAccount(acct crypto.Address) error {
 	addrBytes := acct.Bytes()
 	vmAddress, err := NewEvmAddrFromBytes(addrBytes)
@@ -103,6 +136,19 @@ func (s *SawtoothAppState) RemoveAccount(acct crypto.Address) error {
 	}
 	logger.Debugf("RemoveAccount(%v)", vmAddress)
 
+	entry, err := s.mgr.GetEntry(vmAddress)
+	if err == nil && entry.GetAccount() != nil {
+		s.refundSelfDestruct()
+	}
+
+	// The slots are left behind under the old storage generation
+	if s.rules.SlotStorage && err == nil && entry != nil {
+		s.mgr.MustSetEntry(vmAddress, &EvmEntry{
+			StorageGeneration: entry.GetStorageGeneration() + 1,
+		})
+		return nil
+	}
+
 	err = s.mgr.DelEntry(vmAddress)
 	if err != nil {
 		panic(fmt.Sprintf(
@@ -142,6 +188,44 @@ func (s *SawtoothAppState) CreateAccount(creator *acm.MutableAccount) acm.Accoun
 	return toVmAccount(entry.GetAccount())
 }
 
+// CreateAccountAt creates a new Contract Account at the given address, as
+// CREATE2 does, and increments the creator's nonce. The address may have held
+// an account that was removed, but panics if it holds one or an archived
+// contract.
+func (s *SawtoothAppState) CreateAccountAt(creator *acm.MutableAccount, newAddress *EvmAddr) acm.Account {
+	logger.Debugf("CreateAccountAt(%v)", newAddress)
+
+	creator.IncSequence()
+
+	entry := &EvmEntry{
+		Account: &EvmStateAccount{
+			Address:     newAddress.Bytes(),
+			Balance:     0,
+			Code:        make([]byte, 0),
+			Nonce:       0,
+			Permissions: &EvmPermissions{},
+		},
+		Storage: make([]*EvmStorage, 0),
+	}
+	existing, err := s.mgr.GetEntry(newAddress)
+	if err == nil && existing != nil && (existing.GetAccount() != nil || len(existing.GetArchiveHash()) > 0) {
+		err = fmt.Errorf("Address already in use")
+	}
+	if err == nil && existing != nil {
+		entry.StorageGeneration = existing.GetStorageGeneration()
+	}
+	if err == nil {
+		err = s.mgr.SetEntry(newAddress, entry)
+	}
+	if err != nil {
+		panic(fmt.Sprintf(
+			"Failed to create account at %v: %v", newAddress, err.Error(),
+		))
+	}
+
+	return toVmAccount(entry.GetAccount())
+}
+
 // GetStorage gets the 256 bit value stored with the given key in the given
 // account, returns zero if the key does not exist.
 func (s *SawtoothAppState) GetStorage(address crypto.Address, key binary.Word256) (binary.Word256, error) {
@@ -151,21 +235,47 @@ func (s *SawtoothAppState) GetStorage(address crypto.Address, key binary.Word256
 		return binary.Zero256, err
 	}
 
-	// Load the entry from global state
-	entry := s.mgr.MustGetEntry(vmAddress)
+	// The running transaction is found through the state of its calls in every
+	// family version
+	if *vmAddress == *PrecompileAddress(CALL_STATE_ADDRESS) {
+		return s.callState, nil
+	}
 
-	storage := entry.GetStorage()
+	if !s.rules.SlotStorage {
+		return s.getEntryStorage(vmAddress, key), nil
+	}
 
-	for _, pair := range storage {
-		k := binary.LeftPadWord256(pair.GetKey())
-		if k.Compare(key) == 0 {
-			return binary.LeftPadWord256(pair.GetValue()), nil
-		}
+	// The block seed's storage isn't stored, it is derived from the latest
+	// block
+	if *vmAddress == *PrecompileAddress(BLOCK_SEED_ADDRESS) {
+		return getBlockSeed(s.mgr.state)
+	}
+
+	// The account must exist, and storage of removed accounts is discarded
+	entry := s.mustGetEntry(vmAddress)
+	if entry.GetAccount() == nil {
+		return binary.Zero256, nil
+	}
+
+	slots, err := s.getStorageEntry(vmAddress, entry, key)
+	if err != nil {
+		return binary.Zero256, err
+	}
+	if value, exists := findStorage(slots.GetStorage(), key); exists {
+		return value, nil
 	}
 
-	return binary.Zero256, nil
+	// Contracts deployed before storage was split out may still have it in
+	// their account entry
+	value, _ := findStorage(entry.GetStorage(), key)
+
+	return value, nil
 }
 
+// SetStorage sets the value stored with the given key in the given account.
+// Each slot is stored at its own address, so that transactions touching
+// different slots of a contract don't conflict and storage-heavy contracts
+// don't outgrow the size limit of a state entry.
 func (s *SawtoothAppState) SetStorage(address crypto.Address, key, value binary.Word256) error {
 	addrBytes := address.Bytes()
 	vmAddress, err := NewEvmAddrFromBytes(addrBytes)
@@ -173,6 +283,93 @@ func (s *SawtoothAppState) SetStorage(address crypto.Address, key, value binary.
 		return err
 	}
 
+	if !s.rules.SlotStorage {
+		s.setEntryStorage(vmAddress, key, value)
+		return nil
+	}
+
+	entry := s.mustGetEntry(vmAddress)
+
+	slots, err := s.getStorageEntry(vmAddress, entry, key)
+	if err != nil {
+		return err
+	}
+	if slots == nil {
+		slots = &EvmStorageEntry{Generation: entry.GetStorageGeneration()}
+	}
+
+	// Clearing a slot that held a value earns a refund
+	current, stored := findStorage(slots.GetStorage(), key)
+	legacyValue, legacy := findStorage(entry.GetStorage(), key)
+	if !stored {
+		current = legacyValue
+	}
+	if value == binary.Zero256 && current != binary.Zero256 {
+		s.refundStorageClear()
+	}
+
+	// Zero is the value of every key that isn't stored, so it is only stored to
+	// shadow a value left in the account entry. The account entry isn't
+	// rewritten, so that it stays out of the transaction's outputs.
+	slots.Storage, _ = removeStorage(slots.GetStorage(), key)
+	if value != binary.Zero256 || legacy {
+		slots.Storage = append(slots.Storage, &EvmStorage{
+			Key:   key.Bytes(),
+			Value: value.Bytes(),
+		})
+	}
+
+	return s.mgr.SetStorageEntry(vmAddress, key, slots)
+}
+
+// getStorageEntry reads the entry holding the given storage slot of an account,
+// or nil if it doesn't exist or was left behind when the account was archived
+func (s *SawtoothAppState) getStorageEntry(vmAddress *EvmAddr, entry *EvmEntry, key binary.Word256) (*EvmStorageEntry, error) {
+	slots, err := s.mgr.GetStorageEntry(vmAddress, key)
+	if err != nil || slots == nil {
+		return nil, err
+	}
+	if slots.GetGeneration() != entry.GetStorageGeneration() {
+		return nil, nil
+	}
+	return slots, nil
+}
+
+// deleteStorage removes a storage slot from the entry it is kept in, rather
+// than storing it as zero
+func (s *SawtoothAppState) deleteStorage(vmAddress *EvmAddr, key binary.Word256) error {
+	slots, err := s.mgr.GetStorageEntry(vmAddress, key)
+	if err != nil || slots == nil {
+		return err
+	}
+	storage, removed := removeStorage(slots.GetStorage(), key)
+	if !removed {
+		return nil
+	}
+	slots.Storage = storage
+	return s.mgr.SetStorageEntry(vmAddress, key, slots)
+}
+
+// getEntryStorage reads a storage slot from the account entry, where family
+// version 1.0 keeps every slot of a contract
+func (s *SawtoothAppState) getEntryStorage(vmAddress *EvmAddr, key binary.Word256) binary.Word256 {
+	// Load the entry from global state
+	entry := s.mgr.MustGetEntry(vmAddress)
+
+	storage := entry.GetStorage()
+
+	for _, pair := range storage {
+		k := binary.LeftPadWord256(pair.GetKey())
+		if k.Compare(key) == 0 {
+			return binary.LeftPadWord256(pair.GetValue())
+		}
+	}
+
+	return binary.Zero256
+}
+
+// setEntryStorage writes a storage slot to the account entry
+func (s *SawtoothAppState) setEntryStorage(vmAddress *EvmAddr, key, value binary.Word256) {
 	entry := s.mgr.MustGetEntry(vmAddress)
 
 	storage := &(entry.Storage)
@@ -188,7 +385,7 @@ func (s *SawtoothAppState) SetStorage(address crypto.Address, key, value binary.
 		// If the key has already been set, overwrite it
 		if k.Compare(key) == 0 {
 			pair.Value = value.Bytes()
-			return nil
+			return
 		}
 	}
 
@@ -197,8 +394,6 @@ func (s *SawtoothAppState) SetStorage(address crypto.Address, key, value binary.
 		Key:   key.Bytes(),
 		Value: value.Bytes(),
 	})
-
-	return nil
 }
 
 func (s *SawtoothAppState) GetBlockHash(blockNumber int64) (binary.Word256, error) {
@@ -217,6 +412,24 @@ func (s *SawtoothAppState) GetBlockHash(blockNumber int64) (binary.Word256, erro
 
 // -- Utilities --
 
+func findStorage(storage []*EvmStorage, key binary.Word256) (binary.Word256, bool) {
+	for _, pair := range storage {
+		if binary.LeftPadWord256(pair.GetKey()) == key {
+			return binary.LeftPadWord256(pair.GetValue()), true
+		}
+	}
+	return binary.Zero256, false
+}
+
+func removeStorage(storage []*EvmStorage, key binary.Word256) ([]*EvmStorage, bool) {
+	for i, pair := range storage {
+		if binary.LeftPadWord256(pair.GetKey()) == key {
+			return append(storage[:i], storage[i+1:]...), true
+		}
+	}
+	return storage, false
+}
+
 func toStateAccount(acct acm.Account) *EvmStateAccount {
 	if acct == nil {
 		return nil
@@ -25,16 +25,27 @@ import (
 	"github.com/hyperledger/sawtooth-sdk-go/processor"
 )
 
+// SawtoothEventFireable holds the logs of a call until it succeeds, so that a
+// call that reverts emits none
 type SawtoothEventFireable struct {
-	context *processor.Context
+	context StateContext
+	events  []ReplayEvent
 }
 
-func NewSawtoothEventFireable(context *processor.Context) *SawtoothEventFireable {
+func NewSawtoothEventFireable(context StateContext) *SawtoothEventFireable {
 	return &SawtoothEventFireable{
 		context: context,
 	}
 }
 
+// Flush emits the logs held so far
+func (evc *SawtoothEventFireable) Flush() {
+	for _, event := range evc.events {
+		evc.context.AddEvent(event.EventType, event.Attributes, event.Data)
+	}
+	evc.events = nil
+}
+
 func (evc *SawtoothEventFireable) Call(call *exec.CallEvent, exception *errors.Exception) {
 	// Not used
 }
@@ -56,5 +67,9 @@ func (evc *SawtoothEventFireable) Log(log *exec.LogEvent) {
 			Value: hex.EncodeToString(topic.Bytes()),
 		})
 	}
-	evc.context.AddEvent("seth_log_event", attributes, log.Data)
+	evc.events = append(evc.events, ReplayEvent{
+		EventType:  "seth_log_event",
+		Attributes: attributes,
+		Data:       log.Data,
+	})
 }
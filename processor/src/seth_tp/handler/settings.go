@@ -0,0 +1,296 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+package handler
+
+import (
+	"bytes"
+	. "common"
+	"fmt"
+	"github.com/golang/protobuf/proto"
+	"github.com/hyperledger/burrow/acm"
+	"github.com/hyperledger/burrow/execution/evm"
+	"github.com/hyperledger/burrow/permission"
+	"github.com/hyperledger/sawtooth-sdk-go/processor"
+	"github.com/hyperledger/sawtooth-sdk-go/protobuf/setting_pb2"
+	"strconv"
+	"sync"
+)
+
+// Keys of the settings, managed by the Sawtooth Settings transaction family,
+// that configure the EVM
+const (
+	SETTING_BLOCK_GAS_LIMIT       = "sawtooth.seth.block_gas_limit"
+	SETTING_MAX_CODE_SIZE         = "sawtooth.seth.max_code_size"
+	SETTING_TRANSACTION_GAS       = "sawtooth.seth.gas_schedule.transaction"
+	SETTING_CONTRACT_CREATION_GAS = "sawtooth.seth.gas_schedule.contract_creation"
+	SETTING_PERMISSION_MODE       = "sawtooth.seth.permission_mode"
+	SETTING_COMMIT_REVERTED       = "sawtooth.seth.commit_reverted"
+	SETTING_BASE_FEE_PER_GAS      = "sawtooth.seth.base_fee_per_gas"
+	SETTING_FORK                  = "sawtooth.seth.fork"
+	SETTING_CHAIN_ID              = "sawtooth.seth.chain_id"
+	SETTING_CONFIDENTIAL_PAYLOADS = "sawtooth.seth.confidential_payloads"
+
+	SETTING_BN256_ADD_GAS              = "sawtooth.seth.gas_schedule.bn256_add"
+	SETTING_BN256_MUL_GAS              = "sawtooth.seth.gas_schedule.bn256_mul"
+	SETTING_BN256_PAIRING_BASE_GAS     = "sawtooth.seth.gas_schedule.bn256_pairing_base"
+	SETTING_BN256_PAIRING_PER_PAIR_GAS = "sawtooth.seth.gas_schedule.bn256_pairing_per_pair"
+)
+
+const (
+	// Every action is subject to account permissions
+	PERMISSION_MODE_PERMISSIONED = "permissioned"
+
+	// Any account may create accounts and contracts and make calls, only
+	// changing permissions requires the root permission
+	PERMISSION_MODE_OPEN = "open"
+)
+
+// The hard forks whose gas refunds transactions can be run with
+const (
+	FORK_ISTANBUL = "istanbul"
+	FORK_BERLIN   = "berlin"
+	FORK_LONDON   = "london"
+)
+
+var settingKeys = append([]string{
+	SETTING_BLOCK_GAS_LIMIT,
+	SETTING_MAX_CODE_SIZE,
+	SETTING_TRANSACTION_GAS,
+	SETTING_CONTRACT_CREATION_GAS,
+	SETTING_PERMISSION_MODE,
+	SETTING_COMMIT_REVERTED,
+	SETTING_BASE_FEE_PER_GAS,
+	SETTING_FORK,
+	SETTING_CHAIN_ID,
+	SETTING_CONFIDENTIAL_PAYLOADS,
+	SETTING_BN256_ADD_GAS,
+	SETTING_BN256_MUL_GAS,
+	SETTING_BN256_PAIRING_BASE_GAS,
+	SETTING_BN256_PAIRING_PER_PAIR_GAS,
+}, predeploySettingKeys()...)
+
+// EvmConfig is the runtime configuration of the EVM, read from settings
+type EvmConfig struct {
+	// The most gas a transaction may use, exposed to contracts by GASLIMIT
+	BlockGasLimit uint64
+
+	// The largest contract that can be deployed in bytes, 0 for no limit
+	MaxCodeSize uint64
+
+	// Gas charged for every transaction before the EVM runs
+	TransactionGas uint64
+
+	// Additional gas charged for contract creation before the EVM runs
+	ContractCreationGas uint64
+
+	PermissionMode string
+
+	// Whether transactions that revert are committed, keeping only the
+	// sender's nonce and a receipt of what they reverted with, instead of
+	// being rejected
+	CommitReverted bool
+
+	// The base fee per gas EIP-1559 transactions must offer at least, which
+	// stays the same from block to block since gas is free
+	BaseFeePerGas uint64
+
+	// The hard fork whose rules for gas refunds are followed, or empty for
+	// no refunds
+	Fork string
+
+	// The chain id EIP-155 signatures must commit to
+	ChainId uint64
+
+	// Whether transactions may have confidential payloads, which every
+	// processor of the network must then be able to decrypt
+	ConfidentialPayloads bool
+
+	// Whether the settings were read, rather than left at their defaults
+	// because the transaction didn't list them in its inputs
+	SettingsRead bool
+
+	// Gas prices of the bn256 precompiles
+	Bn256GasCosts Bn256GasCosts
+
+	// Runtime code of the well-known contracts enabled on this network
+	Predeploys map[EvmAddr][]byte
+}
+
+func DefaultEvmConfig() *EvmConfig {
+	return &EvmConfig{
+		BlockGasLimit:  GAS_LIMIT,
+		PermissionMode: PERMISSION_MODE_PERMISSIONED,
+		ChainId:        DEFAULT_CHAIN_ID,
+		Bn256GasCosts:  DefaultBn256GasCosts,
+		Predeploys:     make(map[EvmAddr][]byte),
+	}
+}
+
+// The Transaction Processor API doesn't expose the state root, so the parsed
+// configuration is cached by the raw setting entries it was parsed from
+var evmConfigCache struct {
+	sync.Mutex
+	key    []byte
+	config *EvmConfig
+}
+
+// getEvmConfig reads the EVM configuration from settings. Settings that aren't
+// set, or can't be parsed, keep their defaults. Versions of the family that
+// don't read settings, and transactions that didn't list them in their inputs,
+// get the defaults.
+func getEvmConfig(context StateContext, rules Rules) (*EvmConfig, error) {
+	if !rules.Settings {
+		return DefaultEvmConfig(), nil
+	}
+
+	addresses := make([]string, len(settingKeys))
+	for i, key := range settingKeys {
+		addresses[i] = NewSettingAddr(key).String()
+	}
+
+	entries, err := context.GetState(addresses)
+	if _, ok := err.(*processor.AuthorizationException); ok {
+		logger.Debugf("Settings aren't in the transaction's inputs, using defaults")
+		return DefaultEvmConfig(), nil
+	}
+	if err != nil {
+		return nil, err
+	}
+
+	var cacheKey []byte
+	for _, address := range addresses {
+		cacheKey = append(cacheKey, []byte(strconv.Itoa(len(entries[address])))...)
+		cacheKey = append(cacheKey, ':')
+		cacheKey = append(cacheKey, entries[address]...)
+	}
+
+	evmConfigCache.Lock()
+	defer evmConfigCache.Unlock()
+
+	if evmConfigCache.config != nil && bytes.Equal(evmConfigCache.key, cacheKey) {
+		return evmConfigCache.config, nil
+	}
+
+	values := make(map[string]string)
+	for _, data := range entries {
+		setting := &setting_pb2.Setting{}
+		err := proto.Unmarshal(data, setting)
+		if err != nil {
+			logger.Warnf("Ignoring malformed setting: %v", err)
+			continue
+		}
+		// Entries with colliding addresses are stored together
+		for _, entry := range setting.GetEntries() {
+			values[entry.GetKey()] = entry.GetValue()
+		}
+	}
+
+	config := DefaultEvmConfig()
+	config.SettingsRead = true
+	parseUintSetting(values, SETTING_BLOCK_GAS_LIMIT, &config.BlockGasLimit)
+	parseUintSetting(values, SETTING_MAX_CODE_SIZE, &config.MaxCodeSize)
+	parseUintSetting(values, SETTING_TRANSACTION_GAS, &config.TransactionGas)
+	parseUintSetting(values, SETTING_CONTRACT_CREATION_GAS, &config.ContractCreationGas)
+	parseUintSetting(values, SETTING_BASE_FEE_PER_GAS, &config.BaseFeePerGas)
+	parseUintSetting(values, SETTING_CHAIN_ID, &config.ChainId)
+	parseUintSetting(values, SETTING_BN256_ADD_GAS, &config.Bn256GasCosts.Add)
+	parseUintSetting(values, SETTING_BN256_MUL_GAS, &config.Bn256GasCosts.Mul)
+	parseUintSetting(values, SETTING_BN256_PAIRING_BASE_GAS, &config.Bn256GasCosts.PairingBase)
+	parseUintSetting(values, SETTING_BN256_PAIRING_PER_PAIR_GAS, &config.Bn256GasCosts.PairingPerPair)
+
+	switch mode := values[SETTING_PERMISSION_MODE]; mode {
+	case "":
+	case PERMISSION_MODE_PERMISSIONED, PERMISSION_MODE_OPEN:
+		config.PermissionMode = mode
+	default:
+		logger.Warnf("Ignoring unknown %v: %v", SETTING_PERMISSION_MODE, mode)
+	}
+
+	switch value := values[SETTING_COMMIT_REVERTED]; value {
+	case "":
+	case "true", "false":
+		config.CommitReverted = value == "true"
+	default:
+		logger.Warnf("Ignoring invalid %v: %v", SETTING_COMMIT_REVERTED, value)
+	}
+
+	switch value := values[SETTING_CONFIDENTIAL_PAYLOADS]; value {
+	case "":
+	case "true", "false":
+		config.ConfidentialPayloads = value == "true"
+	default:
+		logger.Warnf("Ignoring invalid %v: %v", SETTING_CONFIDENTIAL_PAYLOADS, value)
+	}
+
+	switch fork := values[SETTING_FORK]; fork {
+	case "":
+	case FORK_ISTANBUL, FORK_BERLIN, FORK_LONDON:
+		config.Fork = fork
+	default:
+		logger.Warnf("Ignoring unknown %v: %v", SETTING_FORK, fork)
+	}
+
+	config.Predeploys = parsePredeploys(values)
+
+	logger.Debugf("EVM configuration: %+v", config)
+
+	evmConfigCache.key = cacheKey
+	evmConfigCache.config = config
+
+	return config, nil
+}
+
+func parseUintSetting(values map[string]string, key string, value *uint64) {
+	s, exists := values[key]
+	if !exists {
+		return
+	}
+	parsed, err := strconv.ParseUint(s, 10, 64)
+	if err != nil {
+		logger.Warnf("Ignoring invalid %v: %v", key, s)
+		return
+	}
+	*value = parsed
+}
+
+// availableGas checks the gas limit of a transaction against the block gas
+// limit and the intrinsic cost of the transaction, and returns the gas left
+// for the EVM
+func (config *EvmConfig) availableGas(gasLimit, intrinsic uint64) (uint64, error) {
+	if gasLimit > config.BlockGasLimit {
+		return 0, &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+			"Gas limit exceeds block gas limit: %v > %v", gasLimit, config.BlockGasLimit,
+		)}
+	}
+	if gasLimit < intrinsic {
+		return 0, rejected(ERR_OUT_OF_GAS,
+			"Gas limit is less than the intrinsic gas of the transaction: %v < %v",
+			gasLimit, intrinsic,
+		)
+	}
+	return gasLimit - intrinsic, nil
+}
+
+// hasPermission is evm.HasPermission, except that in the open permission mode
+// every permission but root is granted
+func hasPermission(sapps *SawtoothAppState, acct acm.Account, perm permission.PermFlag) bool {
+	if sapps.config.PermissionMode == PERMISSION_MODE_OPEN && perm != permission.Root {
+		return true
+	}
+	return evm.HasPermission(sapps, acct, perm)
+}
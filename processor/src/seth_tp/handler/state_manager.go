@@ -21,6 +21,7 @@ import (
 	. "common"
 	"fmt"
 	"github.com/golang/protobuf/proto"
+	"github.com/hyperledger/burrow/binary"
 	"github.com/hyperledger/sawtooth-sdk-go/processor"
 	. "protobuf/seth_pb2"
 )
@@ -29,10 +30,10 @@ import (
 
 // StateManager simplifies accessing EVM related data stored in state
 type StateManager struct {
-	state *processor.Context
+	state StateContext
 }
 
-func NewStateManager(state *processor.Context) *StateManager {
+func NewStateManager(state StateContext) *StateManager {
 	return &StateManager{
 		state: state,
 	}
@@ -94,7 +95,7 @@ func (mgr *StateManager) GetEntry(vmAddress *EvmAddr) (*EvmEntry, error) {
 	// Retrieve the account from global state
 	entries, err := mgr.state.GetState([]string{address.String()})
 	if err != nil {
-		return nil, err
+		return nil, accessError(vmAddress, err)
 	}
 	entryData, exists := entries[address.String()]
 	if !exists {
@@ -145,7 +146,7 @@ func (mgr *StateManager) SetEntry(vmAddress *EvmAddr, entry *EvmEntry) error {
 		address.String(): entryData,
 	})
 	if err != nil {
-		return err
+		return accessError(vmAddress, err)
 	}
 
 	for _, a := range addresses {
@@ -165,3 +166,71 @@ func (mgr *StateManager) MustSetEntry(vmAddress *EvmAddr, entry *EvmEntry) {
 		))
 	}
 }
+
+// GetStorageEntry retrieves the entry holding the given storage slot of an
+// account. If the entry does not exist, nil is returned.
+func (mgr *StateManager) GetStorageEntry(vmAddress *EvmAddr, key binary.Word256) (*EvmStorageEntry, error) {
+	address := NewStorageAddr(vmAddress, key)
+
+	entries, err := mgr.state.GetState([]string{address.String()})
+	if err != nil {
+		return nil, accessError(vmAddress, err)
+	}
+	entryData, exists := entries[address.String()]
+	if !exists {
+		return nil, nil
+	}
+
+	entry := &EvmStorageEntry{}
+	err = proto.Unmarshal(entryData, entry)
+	if err != nil {
+		return nil, err
+	}
+
+	return entry, nil
+}
+
+// SetStorageEntry writes the entry holding the given storage slot of an
+// account, or deletes it if it holds no slots.
+func (mgr *StateManager) SetStorageEntry(vmAddress *EvmAddr, key binary.Word256, entry *EvmStorageEntry) error {
+	address := NewStorageAddr(vmAddress, key)
+
+	if len(entry.GetStorage()) == 0 {
+		_, err := mgr.state.DeleteState([]string{address.String()})
+		if err != nil {
+			return accessError(vmAddress, err)
+		}
+		return nil
+	}
+
+	entryData, err := proto.Marshal(entry)
+	if err != nil {
+		return err
+	}
+
+	addresses, err := mgr.state.SetState(map[string][]byte{
+		address.String(): entryData,
+	})
+	if err != nil {
+		return accessError(vmAddress, err)
+	}
+
+	for _, a := range addresses {
+		if a == address.String() {
+			return nil
+		}
+	}
+	return fmt.Errorf("Address not set: %v", address)
+}
+
+// accessError explains authorization errors, which mean the transaction
+// touched an account that isn't in the inputs or outputs it declared so the
+// validator could schedule it in parallel
+func accessError(vmAddress *EvmAddr, err error) error {
+	if _, ok := err.(*processor.AuthorizationException); ok {
+		return &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+			"Transaction accessed account %v, which isn't in its access list", vmAddress,
+		)}
+	}
+	return err
+}
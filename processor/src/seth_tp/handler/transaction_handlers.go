@@ -22,18 +22,22 @@ import (
 	"fmt"
 	"github.com/hyperledger/burrow/acm"
 	"github.com/hyperledger/burrow/crypto"
-	"github.com/hyperledger/burrow/execution/evm"
 	"github.com/hyperledger/burrow/permission"
 	"github.com/hyperledger/sawtooth-sdk-go/processor"
 	. "protobuf/seth_pb2"
 	"strings"
 )
 
+// TxnHandlers are the handlers of family version 1.1
 var TxnHandlers = map[SethTransaction_TransactionType]TransactionHandler{
 	SethTransaction_CREATE_EXTERNAL_ACCOUNT: CreateExternalAccount,
 	SethTransaction_CREATE_CONTRACT_ACCOUNT: CreateContractAccount,
 	SethTransaction_MESSAGE_CALL:            MessageCall,
 	SethTransaction_SET_PERMISSIONS:         SetPermissions,
+	SethTransaction_ARCHIVE_CONTRACT:        ArchiveContract,
+	SethTransaction_RESURRECT_CONTRACT:      ResurrectContract,
+	SethTransaction_ETHEREUM_TRANSACTION:    EthereumTransaction,
+	SethTransaction_IMPORT_ACCOUNT:          ImportAccount,
 }
 
 func CreateExternalAccount(wrapper *SethTransaction, sender *EvmAddr, sapps *SawtoothAppState) HandlerResult {
@@ -48,27 +52,27 @@ func CreateExternalAccount(wrapper *SethTransaction, sender *EvmAddr, sapps *Saw
 		senderAcct := acm.AsMutableAccount(senderAcctRef)
 		if senderAcct == nil {
 			return HandlerResult{
-				Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+				Error: rejected(ERR_ACCOUNT_NOT_FOUND,
 					"Creating account must already exist for it to be able to create other accounts: %v",
 					sender,
-				)},
+				),
 			}
 		}
-		if !evm.HasPermission(sapps, senderAcct, permission.CreateAccount) {
+		if !hasPermission(sapps, senderAcct, permission.CreateAccount) {
 			return HandlerResult{
-				Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+				Error: rejected(ERR_PERMISSION_DENIED,
 					"Sender account does not have permission to create external accounts: %v",
 					sender,
-				)},
+				),
 			}
 		}
 		// Check that the nonce in the transaction matches the nonce in state
 		if txn.GetNonce() != senderAcct.Sequence() {
 			return HandlerResult{
-				Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+				Error: rejected(ERR_INVALID_NONCE,
 					"Nonces do not match: Transaction (%v), State (%v)",
-					txn.GetNonce(), senderAcct.Sequence,
-				)},
+					txn.GetNonce(), senderAcct.Sequence(),
+				),
 			}
 		}
 
@@ -88,9 +92,9 @@ func CreateExternalAccount(wrapper *SethTransaction, sender *EvmAddr, sapps *Saw
 		existingAcct, err := sapps.GetAccount(crypto.AddressFromWord256(newAcctAddr.ToWord256()))
 		if existingAcct != nil {
 			return HandlerResult{
-				Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+				Error: rejected(ERR_ACCOUNT_EXISTS,
 					"Account already exists at address %v", newAcctAddr,
-				)},
+				),
 			}
 		}
 		if err != nil {
@@ -100,6 +104,9 @@ func CreateExternalAccount(wrapper *SethTransaction, sender *EvmAddr, sapps *Saw
 				)},
 			}
 		}
+		if err := checkNotArchived(sapps, newAcctAddr); err != nil {
+			return HandlerResult{Error: err}
+		}
 
 		// If no permissions were passed by the transaction, inherit them from
 		// sender. Otherwise, set them from transaction.
@@ -109,12 +116,12 @@ func CreateExternalAccount(wrapper *SethTransaction, sender *EvmAddr, sapps *Saw
 			newPerms.Base.Set(permission.Root, false)
 
 		} else {
-			if !evm.HasPermission(sapps, senderAcct, permission.Root) {
+			if !hasPermission(sapps, senderAcct, permission.Root) {
 				return HandlerResult{
-					Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+					Error: rejected(ERR_PERMISSION_DENIED,
 						"Creating account does not have permission to set permissions: %v",
 						sender,
-					)},
+					),
 				}
 			}
 			newPerms = toVmPermissions(txn.GetPermissions())
@@ -159,11 +166,14 @@ func CreateExternalAccount(wrapper *SethTransaction, sender *EvmAddr, sapps *Saw
 		}
 		if senderAcct != nil {
 			return HandlerResult{
-				Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+				Error: rejected(ERR_ACCOUNT_EXISTS,
 					"Account already exists at address %v", sender,
-				)},
+				),
 			}
 		}
+		if err := checkNotArchived(sapps, sender); err != nil {
+			return HandlerResult{Error: err}
+		}
 
 		// Check global permissions to decide if the account can be created
 		global, err := sapps.GetAccount(acm.GlobalPermissionsAddress)
@@ -184,12 +194,12 @@ func CreateExternalAccount(wrapper *SethTransaction, sender *EvmAddr, sapps *Saw
 
 		} else {
 			// If global permissions have been set, check the setting.
-			if !evm.HasPermission(sapps, global, permission.CreateAccount) {
+			if !hasPermission(sapps, global, permission.CreateAccount) {
 				return HandlerResult{
-					Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+					Error: rejected(ERR_PERMISSION_DENIED,
 						"New account creation is disabled, couldn't create account: %v",
 						sender,
-					)},
+					),
 				}
 			}
 
@@ -234,62 +244,168 @@ func CreateContractAccount(wrapper *SethTransaction, sender *EvmAddr, sapps *Saw
 	}
 	if senderAcct == nil {
 		return HandlerResult{
-			Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+			Error: rejected(ERR_ACCOUNT_NOT_FOUND,
 				"Creating account must already exist to create contract account: %v",
 				sender,
-			)},
+			),
 		}
 	}
 
 	// Verify this account has permission to create contract accounts
-	if !evm.HasPermission(sapps, senderAcct, permission.CreateContract) {
+	if !hasPermission(sapps, senderAcct, permission.CreateContract) {
 		return HandlerResult{
-			Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+			Error: rejected(ERR_PERMISSION_DENIED,
 				"Sender account does not have permission to create contracts: %v",
 				sender,
-			)},
+			),
 		}
 	}
 
 	// Check that the nonce in the transaction matches the nonce in state
 	if txn.GetNonce() != senderAcct.Sequence() {
 		return HandlerResult{
-			Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+			Error: rejected(ERR_INVALID_NONCE,
 				"Nonces do not match: Transaction (%v), State (%v)",
 				txn.GetNonce(), senderAcct.Sequence(),
-			)},
+			),
+		}
+	}
+
+	if len(txn.GetSalt()) != 0 && len(txn.GetSalt()) != 32 {
+		return HandlerResult{
+			Error: rejected(ERR_INVALID_SALT,
+				"Salt must be 32 bytes, not %v", len(txn.GetSalt()),
+			),
 		}
 	}
 
+	if len(txn.GetPrivatePayloadHash()) > 0 {
+		if len(txn.GetSalt()) > 0 {
+			return HandlerResult{
+				Error: &processor.InvalidTransactionError{
+					Msg: "Private contracts can't be created with a salt",
+				},
+			}
+		}
+		err = checkPrivatePayload(txn.GetInit(), txn.GetEncryptedInit())
+		if err != nil {
+			return HandlerResult{Error: err}
+		}
+		return applyPrivateTransaction(sapps, senderAcct, &PrivateTransaction{
+			Sender:      sender,
+			Nonce:       txn.GetNonce(),
+			GasLimit:    txn.GetGasLimit(),
+			PayloadHash: txn.GetPrivatePayloadHash(),
+		})
+	}
+
 	var newPerms permission.AccountPermissions
 	if txn.GetPermissions() == nil {
 		newPerms = senderAcct.Permissions()
 		newPerms.Base.Set(permission.Root, false)
 
 	} else {
-		if !evm.HasPermission(sapps, senderAcct, permission.Root) {
+		if !hasPermission(sapps, senderAcct, permission.Root) {
 			return HandlerResult{
-				Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+				Error: rejected(ERR_PERMISSION_DENIED,
 					"Creating account does not have permission to set permissions: %v",
 					sender,
-				)},
+				),
 			}
 		}
 		newPerms = toVmPermissions(txn.GetPermissions())
 	}
 
+	intrinsicGas := sapps.config.TransactionGas + sapps.config.ContractCreationGas
+	gas, err := sapps.config.availableGas(txn.GetGasLimit(), intrinsicGas)
+	if err != nil {
+		return HandlerResult{Error: err}
+	}
+
+	initCode, err := decryptPayload(txn.GetInit(), txn.GetEncryptedInit(), sapps.config)
+	if err != nil {
+		return HandlerResult{Error: err}
+	}
+
 	// Create the new account
 	// NOTE: The senderAcct's nonce will be incremented
-	newAcct := acm.AsMutableAccount(sapps.CreateAccount(senderAcct))
+	var newAcct *acm.MutableAccount
+	if len(txn.GetSalt()) > 0 {
+		newAddress := sender.Create2(txn.GetSalt(), initCode)
+		existingAcct, err := sapps.GetAccount(crypto.AddressFromWord256(newAddress.ToWord256()))
+		if err != nil {
+			return HandlerResult{
+				Error: rejected(ERR_INVALID_TRANSACTION,
+					"Couldn't get account at %v: %v", newAddress, err,
+				),
+			}
+		}
+		if existingAcct != nil {
+			return HandlerResult{
+				Error: rejected(ERR_ACCOUNT_EXISTS,
+					"Account already exists at address %v", newAddress,
+				),
+			}
+		}
+		if err := checkNotArchived(sapps, newAddress); err != nil {
+			return HandlerResult{Error: err}
+		}
+		newAcct = acm.AsMutableAccount(sapps.CreateAccountAt(senderAcct, newAddress))
+	} else {
+		newAcct = acm.AsMutableAccount(sapps.CreateAccount(senderAcct))
+	}
 
 	// Initialize the new account
-	out, gasUsed, err := callVm(sapps, newAcct, nil, txn.GetInit(), nil, txn.GetGasLimit())
+	out, gasUsed, err := callVm(sapps, newAcct, nil, initCode, nil, gas)
+	if err != nil && isReverted(err) && sapps.config.CommitReverted {
+		// Only the sender's nonce outlasts a reverted creation
+		sapps.RemoveAccount(newAcct.Address())
+		if err := sapps.UpdateAccount(senderAcct); err != nil {
+			return HandlerResult{Error: err}
+		}
+		return HandlerResult{
+			GasUsed:     gasUsed + intrinsicGas,
+			ReturnValue: out,
+			Reverted:    true,
+		}
+	}
+	if err != nil {
+		return HandlerResult{Error: vmError(err)}
+	}
+
+	// The init code may have sent the creator a balance with SELFDESTRUCT, or
+	// removed the new account the same way, so both are read back from the
+	// state it left. The creator's nonce was only incremented in memory.
+	senderAcct, err = reloadAccount(sapps, senderAcct)
+	if err != nil {
+		return HandlerResult{Error: err}
+	}
+	if senderAcct == nil {
+		return senderRemoved(sender)
+	}
+	senderAcct.IncSequence()
+	created, err := reloadAccount(sapps, newAcct)
 	if err != nil {
+		return HandlerResult{Error: err}
+	}
+	if created == nil {
+		if err := sapps.UpdateAccount(senderAcct); err != nil {
+			return HandlerResult{Error: err}
+		}
 		return HandlerResult{
-			Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
-				"Error while calling VM: %v",
-				sender,
-			)},
+			GasUsed:     sapps.refunded(gasUsed + intrinsicGas),
+			ReturnValue: out,
+			NewAccount:  newAcct,
+		}
+	}
+	newAcct = created
+
+	if sapps.config.MaxCodeSize > 0 && uint64(len(out)) > sapps.config.MaxCodeSize {
+		return HandlerResult{
+			Error: rejected(ERR_CODE_TOO_LARGE,
+				"Contract code exceeds the maximum code size: %v > %v",
+				len(out), sapps.config.MaxCodeSize,
+			),
 		}
 	}
 
@@ -336,7 +452,7 @@ func CreateContractAccount(wrapper *SethTransaction, sender *EvmAddr, sapps *Saw
 	}
 
 	return HandlerResult{
-		GasUsed:     gasUsed,
+		GasUsed:     sapps.refunded(gasUsed + intrinsicGas),
 		ReturnValue: out,
 		NewAccount:  newAcct,
 	}
@@ -350,29 +466,29 @@ func MessageCall(wrapper *SethTransaction, sender *EvmAddr, sapps *SawtoothAppSt
 	senderAcct := acm.AsMutableAccount(senderAcctRef)
 	if senderAcct == nil {
 		return HandlerResult{
-			Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+			Error: rejected(ERR_ACCOUNT_NOT_FOUND,
 				"Sender account must already exist to message call: %v", sender,
-			)},
+			),
 		}
 	}
 
 	// Verify this account has permission to make message calls
-	if !evm.HasPermission(sapps, senderAcct, permission.Call) {
+	if !hasPermission(sapps, senderAcct, permission.Call) {
 		return HandlerResult{
-			Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+			Error: rejected(ERR_PERMISSION_DENIED,
 				"Sender account does not have permission to make message calls: %v",
 				sender,
-			)},
+			),
 		}
 	}
 
 	// Check that the nonce in the transaction matches the nonce in state
 	if txn.GetNonce() != senderAcct.Sequence() {
 		return HandlerResult{
-			Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+			Error: rejected(ERR_INVALID_NONCE,
 				"Nonces do not match: Transaction (%v), State (%v)",
 				txn.GetNonce(), senderAcct.Sequence(),
-			)},
+			),
 		}
 	}
 
@@ -385,6 +501,21 @@ func MessageCall(wrapper *SethTransaction, sender *EvmAddr, sapps *SawtoothAppSt
 		}
 	}
 
+	// The receiver of a private call only exists in private state
+	if len(txn.GetPrivatePayloadHash()) > 0 {
+		err = checkPrivatePayload(txn.GetData(), txn.GetEncryptedData())
+		if err != nil {
+			return HandlerResult{Error: err}
+		}
+		return applyPrivateTransaction(sapps, senderAcct, &PrivateTransaction{
+			Sender:      sender,
+			To:          receiver,
+			Nonce:       txn.GetNonce(),
+			GasLimit:    txn.GetGasLimit(),
+			PayloadHash: txn.GetPrivatePayloadHash(),
+		})
+	}
+
 	receiverAcct, err := sapps.GetAccount(crypto.AddressFromWord256(receiver.ToWord256()))
 	if err != nil {
 		return HandlerResult{
@@ -397,38 +528,92 @@ func MessageCall(wrapper *SethTransaction, sender *EvmAddr, sapps *SawtoothAppSt
 	// Receiving account must exist to call it
 	if receiverAcct == nil {
 		return HandlerResult{
-			Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+			Error: rejected(ERR_ACCOUNT_NOT_FOUND,
 				"Receiver account must already exist to call it: %v", receiver,
-			)},
+			),
 		}
 	}
 
+	gas, err := sapps.config.availableGas(txn.GetGasLimit(), sapps.config.TransactionGas)
+	if err != nil {
+		return HandlerResult{Error: err}
+	}
+
+	data, err := decryptPayload(txn.GetData(), txn.GetEncryptedData(), sapps.config)
+	if err != nil {
+		return HandlerResult{Error: err}
+	}
+
 	// Execute the contract
 	out, gasUsed, err := callVm(
 		sapps,
 		acm.AsMutableAccount(senderAcct),
 		acm.AsMutableAccount(receiverAcct),
 		receiverAcct.Code().Bytes(),
-		txn.GetData(),
-		txn.GetGasLimit(),
+		data,
+		gas,
 	)
 
-	if err != nil {
+	if err != nil && isReverted(err) && sapps.config.CommitReverted {
+		// Only the sender's nonce outlasts a reverted call
+		senderAcct.IncSequence()
+		if err := sapps.UpdateAccount(senderAcct); err != nil {
+			return HandlerResult{Error: err}
+		}
 		return HandlerResult{
-			Error: &processor.InvalidTransactionError{Msg: err.Error()},
+			ReturnValue: out,
+			GasUsed:     gasUsed + sapps.config.TransactionGas,
+			Reverted:    true,
 		}
 	}
+	if err != nil {
+		return HandlerResult{Error: vmError(err)}
+	}
 	logger.Debug("Gas Used: ", gasUsed)
 	logger.Debug("EVM Output: ", strings.ToLower(hex.EncodeToString(out)))
 
+	// The EVM wrote back what the call changed, which may include a balance
+	// sent to the sender or the receiver's removal by SELFDESTRUCT, so only the
+	// sender's nonce is left to update
+	senderAcct, err = reloadAccount(sapps, senderAcct)
+	if err != nil {
+		return HandlerResult{Error: err}
+	}
+	if senderAcct == nil {
+		return senderRemoved(sender)
+	}
 	senderAcct.IncSequence()
 
-	sapps.UpdateAccount(senderAcct)
-	sapps.UpdateAccount(receiverAcct)
+	err = sapps.UpdateAccount(senderAcct)
+	if err != nil {
+		return HandlerResult{Error: err}
+	}
 
 	return HandlerResult{
 		ReturnValue: out,
-		GasUsed:     gasUsed,
+		GasUsed:     sapps.refunded(gasUsed + sapps.config.TransactionGas),
+	}
+}
+
+// reloadAccount reads an account back from state after the EVM ran, or returns
+// nil if the account was removed
+func reloadAccount(sapps *SawtoothAppState, acct acm.Account) (*acm.MutableAccount, error) {
+	reloaded, err := sapps.GetAccount(acct.Address())
+	if err != nil {
+		return nil, &processor.InternalError{Msg: fmt.Sprintf(
+			"Couldn't read back account %v: %v", acct.Address(), err,
+		)}
+	}
+	return acm.AsMutableAccount(reloaded), nil
+}
+
+// senderRemoved rejects a transaction whose sender was removed by the EVM,
+// which only a contract can be, such as one imported with ImportAccount
+func senderRemoved(sender *EvmAddr) HandlerResult {
+	return HandlerResult{
+		Error: rejected(ERR_INVALID_TRANSACTION,
+			"Sender account was removed by the transaction: %v", sender,
+		),
 	}
 }
 
@@ -449,29 +634,29 @@ func SetPermissions(wrapper *SethTransaction, sender *EvmAddr, sapps *SawtoothAp
 	senderAcct := acm.AsMutableAccount(senderAcctRef)
 	if senderAcct == nil {
 		return HandlerResult{
-			Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+			Error: rejected(ERR_ACCOUNT_NOT_FOUND,
 				"Sender account must already exist for updating permissions: %v", sender,
-			)},
+			),
 		}
 	}
 
 	// Verify this account has permission to update permissions
-	if !evm.HasPermission(sapps, senderAcct, permission.Root) {
+	if !hasPermission(sapps, senderAcct, permission.Root) {
 		return HandlerResult{
-			Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+			Error: rejected(ERR_PERMISSION_DENIED,
 				"Sender account does not have permission to change permissions: %v",
 				sender,
-			)},
+			),
 		}
 	}
 
 	// Check that the nonce in the transaction matches the nonce in state
 	if txn.GetNonce() != senderAcct.Sequence() {
 		return HandlerResult{
-			Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+			Error: rejected(ERR_INVALID_NONCE,
 				"Nonces do not match: Transaction (%v), State (%v)",
 				txn.GetNonce(), senderAcct.Sequence(),
-			)},
+			),
 		}
 	}
 
@@ -508,10 +693,10 @@ func SetPermissions(wrapper *SethTransaction, sender *EvmAddr, sapps *SawtoothAp
 			}.MutableAccount()
 		} else {
 			return HandlerResult{
-				Error: &processor.InvalidTransactionError{Msg: fmt.Sprintf(
+				Error: rejected(ERR_ACCOUNT_NOT_FOUND,
 					"Receiver account must already exist to change its permissions: %v",
 					receiver,
-				)},
+				),
 			}
 		}
 	}
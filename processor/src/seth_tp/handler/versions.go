@@ -0,0 +1,108 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *		 http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+package handler
+
+import (
+	. "common"
+	"fmt"
+	. "protobuf/seth_pb2"
+	"sort"
+	"sync"
+)
+
+// TxnHandlerSet maps each transaction type to its handler for one version of
+// the seth family
+type TxnHandlerSet map[SethTransaction_TransactionType]TransactionHandler
+
+// Rules are the parts of the protocol that differ between versions of the
+// family. Changing what a version does forks every node still running the
+// old processor, so a change is made behind a rule that only new versions
+// turn on.
+type Rules struct {
+	// Read the EVM configuration from the sawtooth.seth settings. Without
+	// it every transaction runs with the defaults.
+	Settings bool
+
+	// Store each storage slot of a contract at its own address. Without it
+	// every slot is kept in the contract's account entry.
+	SlotStorage bool
+
+	// Run the precompiles that seth adds to the ones of the Burrow EVM
+	Precompiles bool
+}
+
+// FamilyVersion is one version of the family: the rules it follows and the
+// handler for each transaction type
+type FamilyVersion struct {
+	Rules    Rules
+	Handlers TxnHandlerSet
+}
+
+// A processor can serve several versions of the family at once, so a protocol
+// change can ship as a new version next to the old one and be enabled once
+// every node has a processor that supports it. Transactions are routed by the
+// family version in their header.
+var familyVersionsMutex sync.Mutex
+var familyVersions = map[string]*FamilyVersion{
+	FAMILY_VERSION: {
+		Rules:    Rules{},
+		Handlers: LegacyTxnHandlers,
+	},
+	FAMILY_VERSION_1_1: {
+		Rules:    Rules{Settings: true, SlotStorage: true, Precompiles: true},
+		Handlers: TxnHandlers,
+	},
+}
+
+// RegisterFamilyVersion adds the rules and handlers for a version of the
+// family. It is meant to be called from init functions.
+func RegisterFamilyVersion(version string, rules Rules, handlers TxnHandlerSet) error {
+	familyVersionsMutex.Lock()
+	defer familyVersionsMutex.Unlock()
+
+	if _, exists := familyVersions[version]; exists {
+		return fmt.Errorf("Family version %v is already registered", version)
+	}
+	familyVersions[version] = &FamilyVersion{Rules: rules, Handlers: handlers}
+
+	return nil
+}
+
+// RegisteredFamilyVersions returns every version of the family this processor
+// can serve, in order
+func RegisteredFamilyVersions() []string {
+	familyVersionsMutex.Lock()
+	defer familyVersionsMutex.Unlock()
+
+	versions := make([]string, 0, len(familyVersions))
+	for version := range familyVersions {
+		versions = append(versions, version)
+	}
+	sort.Strings(versions)
+
+	return versions
+}
+
+// getFamilyVersion returns a version of the family, or nil if it isn't
+// registered
+func getFamilyVersion(version string) *FamilyVersion {
+	familyVersionsMutex.Lock()
+	defer familyVersionsMutex.Unlock()
+
+	return familyVersions[version]
+}
@@ -30,14 +30,31 @@ import (
 type Opts struct {
 	Verbose []bool `short:"v" long:"verbose" description:"Increase verbosity"`
 	Connect string `short:"C" long:"connect" description:"Validator component endpoint to connect to" default:"tcp://localhost:4004"`
+
+	DecryptionService string `long:"decryption-service" description:"URL of the service that decrypts confidential transaction payloads, on networks that enable them"`
+
+	FamilyVersions []string `long:"family-version" description:"Version of the seth family to serve, may be repeated (default all supported versions)"`
+
+	GasAudit bool `long:"gas-audit" description:"Record the gas used by each opcode in transaction receipts"`
 }
 
 func main() {
 	var opts Opts
+	var replayOpts ReplayOpts
 
 	logger := logging.Get()
 
 	parser := flags.NewParser(&opts, flags.Default)
+	parser.SubcommandsOptional = true
+	_, err := parser.AddCommand("replay",
+		"Replay committed blocks",
+		"Executes the seth transactions of committed blocks again, against the state they were committed on, and reports any whose state changes, events or receipt differ from the chain's",
+		&replayOpts,
+	)
+	if err != nil {
+		logger.Error(err)
+		os.Exit(2)
+	}
 	remaining, err := parser.Parse()
 	if err != nil {
 		if flagsErr, ok := err.(*flags.Error); ok && flagsErr.Type == flags.ErrHelp {
@@ -64,7 +81,31 @@ func main() {
 		logger.SetLevel(logging.WARN)
 	}
 
-	handler := seth.NewBurrowEVMHandler()
+	if opts.GasAudit {
+		logger.Warn("Gas audit mode is enabled, transactions will execute slowly")
+		seth.SetGasAudit(true)
+	}
+
+	if opts.DecryptionService != "" {
+		logger.Infof("Decrypting confidential transactions with %v", opts.DecryptionService)
+		seth.SetPayloadDecrypter(seth.NewHttpPayloadDecrypter(opts.DecryptionService))
+	}
+
+	for _, precompile := range seth.RegisteredPrecompiles() {
+		logger.Infof("Registered precompile %v", precompile)
+	}
+
+	handler, err := seth.NewBurrowEVMHandler(opts.FamilyVersions)
+	if err != nil {
+		logger.Error(err)
+		os.Exit(2)
+	}
+	logger.Infof("Serving seth family versions %v", handler.FamilyVersions())
+
+	if parser.Active != nil && parser.Active.Name == "replay" {
+		os.Exit(replay(handler, &replayOpts))
+	}
+
 	processor := processor.NewTransactionProcessor(endpoint)
 	processor.AddHandler(handler)
 	processor.ShutdownOnSignal(syscall.SIGINT, syscall.SIGTERM)
@@ -73,3 +114,4 @@ func main() {
 		logger.Error("Processor stopped: ", err)
 	}
 }
+
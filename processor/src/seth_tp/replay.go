@@ -0,0 +1,396 @@
+/**
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+package main
+
+import (
+	"bytes"
+	"common"
+	"encoding/base64"
+	"encoding/json"
+	"fmt"
+	"github.com/hyperledger/burrow/execution/evm/sha3"
+	"github.com/hyperledger/sawtooth-sdk-go/logging"
+	"github.com/hyperledger/sawtooth-sdk-go/processor"
+	"github.com/hyperledger/sawtooth-sdk-go/protobuf/processor_pb2"
+	"github.com/hyperledger/sawtooth-sdk-go/protobuf/transaction_pb2"
+	"net/http"
+	"reflect"
+	seth "seth_tp/handler"
+	"strconv"
+	"strings"
+)
+
+type ReplayOpts struct {
+	From uint64 `long:"from" description:"Number of the first block to replay" required:"true"`
+	To   uint64 `long:"to" description:"Number of the last block to replay" required:"true"`
+	Url  string `long:"url" description:"REST API to read blocks, receipts and state from" default:"http://localhost:8008"`
+}
+
+// The parts of the REST API's responses that a replay reads. Integers and
+// bytes are sent as strings and base64.
+
+type restBlock struct {
+	Header struct {
+		BlockNum        string `json:"block_num"`
+		PreviousBlockId string `json:"previous_block_id"`
+	} `json:"header"`
+	HeaderSignature string `json:"header_signature"`
+	Batches         []struct {
+		Transactions []restTransaction `json:"transactions"`
+	} `json:"batches"`
+}
+
+type restTransaction struct {
+	Header struct {
+		BatcherPublicKey string   `json:"batcher_public_key"`
+		Dependencies     []string `json:"dependencies"`
+		FamilyName       string   `json:"family_name"`
+		FamilyVersion    string   `json:"family_version"`
+		Inputs           []string `json:"inputs"`
+		Nonce            string   `json:"nonce"`
+		Outputs          []string `json:"outputs"`
+		PayloadSha512    string   `json:"payload_sha512"`
+		SignerPublicKey  string   `json:"signer_public_key"`
+	} `json:"header"`
+	HeaderSignature string `json:"header_signature"`
+	Payload         []byte `json:"payload"`
+}
+
+type restReceipt struct {
+	TransactionId string `json:"transaction_id"`
+	StateChanges  []struct {
+		Address string `json:"address"`
+		Value   []byte `json:"value"`
+		Type    string `json:"type"`
+	} `json:"state_changes"`
+	Events []struct {
+		EventType  string `json:"event_type"`
+		Attributes []struct {
+			Key   string `json:"key"`
+			Value string `json:"value"`
+		} `json:"attributes"`
+		Data []byte `json:"data"`
+	} `json:"events"`
+	Data []string `json:"data"`
+}
+
+// replay executes the seth transactions of a range of committed blocks again,
+// starting from the state before the first block, and compares what each does
+// with its receipt. Every transaction is checked against the state that was
+// committed before it, so a mismatch doesn't carry over into later
+// transactions. Returns the process's exit code.
+func replay(handler *seth.BurrowEVMHandler, opts *ReplayOpts) int {
+	logger := logging.Get()
+	if opts.From > opts.To {
+		logger.Errorf("--from %v is after --to %v", opts.From, opts.To)
+		return 2
+	}
+	url := strings.TrimRight(opts.Url, "/")
+
+	blocks, err := getBlocks(url, opts.From, opts.To)
+	if err != nil {
+		logger.Error(err)
+		return 2
+	}
+
+	head := blocks[0].Header.PreviousBlockId
+	context := seth.NewReplayContext(func(address string) ([]byte, error) {
+		return getState(url, address, head)
+	})
+
+	replayed, mismatches := 0, 0
+	for _, block := range blocks {
+		var transactions []restTransaction
+		for _, batch := range block.Batches {
+			transactions = append(transactions, batch.Transactions...)
+		}
+		receipts, err := getReceipts(url, transactions)
+		if err != nil {
+			logger.Error(err)
+			return 2
+		}
+
+		for i, transaction := range transactions {
+			receipt := receipts[i]
+			if transaction.Header.FamilyName == common.FAMILY_NAME {
+				replayed++
+				context.Reset(transaction.Header.Inputs, transaction.Header.Outputs)
+				differences := replayTransaction(handler, context, &transaction, &receipt)
+				for _, difference := range differences {
+					fmt.Printf(
+						"Block %v transaction %v: %v\n",
+						block.Header.BlockNum, transaction.HeaderSignature, difference,
+					)
+				}
+				if len(differences) > 0 {
+					mismatches++
+				}
+			}
+			context.Apply(committedChanges(&receipt))
+		}
+	}
+
+	fmt.Printf(
+		"Replayed %v seth transactions in blocks %v to %v, %v differed\n",
+		replayed, opts.From, opts.To, mismatches,
+	)
+	if mismatches > 0 {
+		return 1
+	}
+	return 0
+}
+
+// replayTransaction executes a transaction and describes how what it did
+// differs from its receipt
+func replayTransaction(handler *seth.BurrowEVMHandler, context *seth.ReplayContext,
+	transaction *restTransaction, receipt *restReceipt) []string {
+
+	header := transaction.Header
+	request := &processor_pb2.TpProcessRequest{
+		Header: &transaction_pb2.TransactionHeader{
+			BatcherPublicKey: header.BatcherPublicKey,
+			Dependencies:     header.Dependencies,
+			FamilyName:       header.FamilyName,
+			FamilyVersion:    header.FamilyVersion,
+			Inputs:           header.Inputs,
+			Nonce:            header.Nonce,
+			Outputs:          header.Outputs,
+			PayloadSha512:    header.PayloadSha512,
+			SignerPublicKey:  header.SignerPublicKey,
+		},
+		Payload:   transaction.Payload,
+		Signature: transaction.HeaderSignature,
+	}
+	err := handler.Execute(request, context)
+	if err != nil {
+		return []string{fmt.Sprintf("Committed, but failed when replayed: %v", err)}
+	}
+
+	var differences []string
+
+	committed := committedChanges(receipt)
+	for address, data := range context.Changes {
+		if expected, ok := committed[address]; !ok {
+			differences = append(differences, fmt.Sprintf("%v was not changed on chain", address))
+		} else if !bytes.Equal(data, expected) || (data == nil) != (expected == nil) {
+			differences = append(differences, fmt.Sprintf(
+				"%v was set to %v, but %v on chain",
+				address, describeEntry(data), describeEntry(expected),
+			))
+		}
+	}
+	for address := range committed {
+		if _, ok := context.Changes[address]; !ok {
+			differences = append(differences, fmt.Sprintf("%v was only changed on chain", address))
+		}
+	}
+
+	var events []seth.ReplayEvent
+	for _, event := range receipt.Events {
+		attributes := make([]processor.Attribute, 0, len(event.Attributes))
+		for _, attribute := range event.Attributes {
+			attributes = append(attributes, processor.Attribute{
+				Key:   attribute.Key,
+				Value: attribute.Value,
+			})
+		}
+		events = append(events, seth.ReplayEvent{
+			EventType:  event.EventType,
+			Attributes: attributes,
+			Data:       event.Data,
+		})
+	}
+	if !eventsEqual(context.Events, events) {
+		differences = append(differences, fmt.Sprintf(
+			"Emitted %v events that differ from the %v on chain",
+			len(context.Events), len(events),
+		))
+	}
+
+	var data []string
+	for _, receiptData := range context.ReceiptData {
+		data = append(data, base64.StdEncoding.EncodeToString(receiptData))
+	}
+	if !reflect.DeepEqual(data, receipt.Data) && (len(data) > 0 || len(receipt.Data) > 0) {
+		differences = append(differences, "Receipt data differs from the receipt on chain")
+	}
+
+	return differences
+}
+
+// committedChanges returns the state changes of a receipt by address, with
+// deleted addresses mapping to nil. Later changes to an address replace
+// earlier ones.
+func committedChanges(receipt *restReceipt) map[string][]byte {
+	changes := make(map[string][]byte)
+	for _, change := range receipt.StateChanges {
+		if change.Type == "DELETE" {
+			changes[change.Address] = nil
+		} else if change.Value == nil {
+			changes[change.Address] = []byte{}
+		} else {
+			changes[change.Address] = change.Value
+		}
+	}
+	return changes
+}
+
+func eventsEqual(replayed, committed []seth.ReplayEvent) bool {
+	if len(replayed) != len(committed) {
+		return false
+	}
+	for i := range replayed {
+		if replayed[i].EventType != committed[i].EventType ||
+			!bytes.Equal(replayed[i].Data, committed[i].Data) ||
+			len(replayed[i].Attributes) != len(committed[i].Attributes) {
+			return false
+		}
+		for j := range replayed[i].Attributes {
+			if replayed[i].Attributes[j] != committed[i].Attributes[j] {
+				return false
+			}
+		}
+	}
+	return true
+}
+
+func describeEntry(data []byte) string {
+	if data == nil {
+		return "deleted"
+	}
+	return fmt.Sprintf("%v bytes with hash %x", len(data), sha3.Sha3(data)[:8])
+}
+
+// getBlocks returns the blocks from one number to another, in the order they
+// were committed. The REST API pages blocks from a block number down.
+func getBlocks(url string, from, to uint64) ([]restBlock, error) {
+	var blocks []restBlock
+	for next := to; len(blocks) < int(to-from+1); {
+		var page struct {
+			Data []restBlock `json:"data"`
+		}
+		limit := to - from + 1 - uint64(len(blocks))
+		if limit > 100 {
+			limit = 100
+		}
+		err := getJson(fmt.Sprintf("%v/blocks?start=0x%016x&limit=%v", url, next, limit), &page)
+		if err != nil {
+			return nil, fmt.Errorf("Couldn't get blocks: %v", err)
+		}
+		if len(page.Data) == 0 {
+			return nil, fmt.Errorf("Block %v hasn't been committed", next)
+		}
+		for _, block := range page.Data {
+			num, err := strconv.ParseUint(block.Header.BlockNum, 10, 64)
+			if err != nil || num != next {
+				return nil, fmt.Errorf("Expected block %v, got %v", next, block.Header.BlockNum)
+			}
+			blocks = append(blocks, block)
+			if next == from {
+				break
+			}
+			next--
+		}
+	}
+
+	for i, j := 0, len(blocks)-1; i < j; i, j = i+1, j-1 {
+		blocks[i], blocks[j] = blocks[j], blocks[i]
+	}
+	return blocks, nil
+}
+
+// getReceipts returns the receipts of transactions, in the same order
+func getReceipts(url string, transactions []restTransaction) ([]restReceipt, error) {
+	if len(transactions) == 0 {
+		return nil, nil
+	}
+	ids := make([]string, len(transactions))
+	for i, transaction := range transactions {
+		ids[i] = transaction.HeaderSignature
+	}
+	body, err := json.Marshal(ids)
+	if err != nil {
+		return nil, err
+	}
+
+	response, err := http.Post(url+"/receipts", "application/json", bytes.NewReader(body))
+	if err != nil {
+		return nil, fmt.Errorf("Couldn't get receipts: %v", err)
+	}
+	defer response.Body.Close()
+	if response.StatusCode != http.StatusOK {
+		return nil, fmt.Errorf("Couldn't get receipts: %v", response.Status)
+	}
+	var receipts struct {
+		Data []restReceipt `json:"data"`
+	}
+	err = json.NewDecoder(response.Body).Decode(&receipts)
+	if err != nil {
+		return nil, fmt.Errorf("Couldn't decode receipts: %v", err)
+	}
+
+	if len(receipts.Data) != len(ids) {
+		return nil, fmt.Errorf("Expected %v receipts, got %v", len(ids), len(receipts.Data))
+	}
+	for i, receipt := range receipts.Data {
+		if receipt.TransactionId != ids[i] {
+			return nil, fmt.Errorf("Expected the receipt of %v, got %v", ids[i], receipt.TransactionId)
+		}
+	}
+	return receipts.Data, nil
+}
+
+// getState returns what is stored at an address as of a block, or nil if
+// nothing is
+func getState(url, address, head string) ([]byte, error) {
+	response, err := http.Get(fmt.Sprintf("%v/state/%v?head=%v", url, address, head))
+	if err != nil {
+		return nil, fmt.Errorf("Couldn't get state at %v: %v", address, err)
+	}
+	defer response.Body.Close()
+	if response.StatusCode == http.StatusNotFound {
+		return nil, nil
+	}
+	if response.StatusCode != http.StatusOK {
+		return nil, fmt.Errorf("Couldn't get state at %v: %v", address, response.Status)
+	}
+
+	var entry struct {
+		Data []byte `json:"data"`
+	}
+	err = json.NewDecoder(response.Body).Decode(&entry)
+	if err != nil {
+		return nil, fmt.Errorf("Couldn't decode state at %v: %v", address, err)
+	}
+	if entry.Data == nil {
+		return []byte{}, nil
+	}
+	return entry.Data, nil
+}
+
+func getJson(url string, value interface{}) error {
+	response, err := http.Get(url)
+	if err != nil {
+		return err
+	}
+	defer response.Body.Close()
+	if response.StatusCode != http.StatusOK {
+		return fmt.Errorf("%v", response.Status)
+	}
+	return json.NewDecoder(response.Body).Decode(value)
+}
@@ -19,7 +19,9 @@ extern crate cc;
 extern crate glob;
 extern crate protoc_rust;
 
+use std::env;
 use std::fs;
+use std::process::Command;
 
 use protoc_rust::Customize;
 
@@ -40,6 +42,17 @@ fn main() {
         customize: Customize::default(),
     })
     .expect("unable to run protoc");
+
+    // The version of the compiler, which web3_clientVersion reports
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| String::from("rustc"));
+    let rustc_version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .and_then(|version| version.split_whitespace().nth(1).map(String::from))
+        .unwrap_or_else(|| String::from("unknown"));
+    println!("cargo:rustc-env=SETH_RPC_RUSTC_VERSION={}", rustc_version);
 }
 
 fn glob_simple(pattern: &str) -> Vec<String> {
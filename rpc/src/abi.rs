@@ -0,0 +1,180 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Encoding of contract calls and decoding of their results and revert reasons, as
+//! the Solidity ABI defines them
+
+use crypto_backend;
+use serde_json::Value;
+use types::abi::{canonical_list, decode_params, encode_params};
+
+pub use types::abi::ParamType;
+
+/// A function of a contract, from its JSON ABI
+#[derive(Clone, Debug)]
+pub struct Function {
+    pub name: String,
+    pub inputs: Vec<ParamType>,
+    pub outputs: Vec<ParamType>,
+}
+
+impl Function {
+    /// Parses the functions out of a JSON ABI, skipping its events, errors and
+    /// constructor
+    pub fn from_abi(abi: &Value) -> Result<Vec<Function>, String> {
+        let params = |entry: &Value, key| -> Result<Vec<ParamType>, String> {
+            match entry.get(key) {
+                Some(Value::Array(params)) => params.iter().map(ParamType::from_abi).collect(),
+                _ => Ok(Vec::new()),
+            }
+        };
+
+        abi.as_array()
+            .ok_or("The ABI must be an array")?
+            .iter()
+            .filter(|entry| {
+                entry
+                    .get("type")
+                    .and_then(Value::as_str)
+                    .unwrap_or("function")
+                    == "function"
+            })
+            .map(|entry| {
+                Ok(Function {
+                    name: entry
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .map(String::from)
+                        .ok_or("Functions must have a name")?,
+                    inputs: params(entry, "inputs")?,
+                    outputs: params(entry, "outputs")?,
+                })
+            })
+            .collect()
+    }
+
+    pub fn signature(&self) -> String {
+        format!("{}({})", self.name, canonical_list(&self.inputs))
+    }
+
+    pub fn selector(&self) -> [u8; 4] {
+        let hash = crypto_backend::keccak256(self.signature().as_bytes());
+        [hash[0], hash[1], hash[2], hash[3]]
+    }
+
+    /// Returns the input data of a call to the function with the given arguments
+    pub fn encode_call(&self, args: &[Value]) -> Result<Vec<u8>, String> {
+        let mut data = self.selector().to_vec();
+        data.extend(encode_params(&self.inputs, args)?);
+        Ok(data)
+    }
+
+    /// Decodes the return value of a call to the function into a list of its outputs
+    pub fn decode_result(&self, data: &[u8]) -> Result<Value, String> {
+        decode_params(&self.outputs, data).map(Value::Array)
+    }
+}
+
+/// The selector of `Error(string)`, which `revert("reason")` and `require` revert with
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// The selector of `Panic(uint256)`, which failed assertions and arithmetic revert with
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Returns why a call reverted, from the data it reverted with, if it reverted with
+/// a reason string or a panic code
+pub fn revert_reason(data: &[u8]) -> Option<String> {
+    if data.len() < 4 {
+        return None;
+    }
+    let (selector, params) = data.split_at(4);
+    let (kind, panic) = if selector == ERROR_SELECTOR {
+        (ParamType::String, false)
+    } else if selector == PANIC_SELECTOR {
+        (ParamType::Uint(256), true)
+    } else {
+        return None;
+    };
+    match decode_params(&[kind], params).ok()?.pop()? {
+        Value::String(code) if panic => Some(format!("panic code {}", code)),
+        Value::String(reason) => Some(reason),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+    use transform;
+
+    fn function(abi: &str) -> Function {
+        Function::from_abi(&serde_json::from_str(abi).unwrap())
+            .unwrap()
+            .remove(0)
+    }
+
+    #[test]
+    fn encodes_the_example_of_the_solidity_docs() {
+        let sam = function(
+            r#"[{"type": "function", "name": "sam", "inputs": [
+                {"name": "a", "type": "bytes"},
+                {"name": "b", "type": "bool"},
+                {"name": "c", "type": "uint256[]"}
+            ], "outputs": []}]"#,
+        );
+        let args: Vec<Value> = serde_json::from_str(r#"["0x64617665", true, [1, 2, 3]]"#).unwrap();
+
+        assert_eq!(sam.signature(), "sam(bytes,bool,uint256[])");
+        assert_eq!(
+            transform::bytes_to_hex_str(&sam.encode_call(&args).unwrap()),
+            String::from("a5643bf2")
+                + "0000000000000000000000000000000000000000000000000000000000000060"
+                + "0000000000000000000000000000000000000000000000000000000000000001"
+                + "00000000000000000000000000000000000000000000000000000000000000a0"
+                + "0000000000000000000000000000000000000000000000000000000000000004"
+                + "6461766500000000000000000000000000000000000000000000000000000000"
+                + "0000000000000000000000000000000000000000000000000000000000000003"
+                + "0000000000000000000000000000000000000000000000000000000000000001"
+                + "0000000000000000000000000000000000000000000000000000000000000002"
+                + "0000000000000000000000000000000000000000000000000000000000000003"
+        );
+    }
+
+    #[test]
+    fn decodes_revert_reasons() {
+        let reason = transform::hex_str_to_bytes(
+            &(String::from("08c379a0")
+                + "0000000000000000000000000000000000000000000000000000000000000020"
+                + "000000000000000000000000000000000000000000000000000000000000000e"
+                + "4e6f7420656e6f75676820676173"
+                + "000000000000000000000000000000000000"),
+        )
+        .unwrap();
+        assert_eq!(revert_reason(&reason), Some(String::from("Not enough gas")));
+
+        let panic = transform::hex_str_to_bytes(
+            &(String::from("4e487b71")
+                + "0000000000000000000000000000000000000000000000000000000000000011"),
+        )
+        .unwrap();
+        assert_eq!(revert_reason(&panic), Some(String::from("panic code 17")));
+
+        assert_eq!(revert_reason(&[]), None);
+        assert_eq!(revert_reason(&reason[..40]), None);
+    }
+}
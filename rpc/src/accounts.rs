@@ -15,17 +15,30 @@
  * ------------------------------------------------------------------------------
  */
 
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use crypto_backend;
 use dirs::home_dir;
+#[cfg(feature = "personal")]
+use keystore;
 use sawtooth_sdk::signing::secp256k1::Secp256k1PrivateKey;
 use sawtooth_sdk::signing::Error as SigningError;
 use sawtooth_sdk::signing::{create_context, PrivateKey};
+use secp256k1::key::{PublicKey, SecretKey};
+use secp256k1::{Message, Secp256k1};
+#[cfg(feature = "personal")]
+use serde_json::Value;
 use std::error::Error as StdError;
 use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::fs::File;
+#[cfg(feature = "personal")]
+use std::fs::{self, OpenOptions};
 use std::io::Error as IoError;
 use std::io::Read;
-use std::path::PathBuf;
-use tiny_keccak;
+#[cfg(feature = "personal")]
+use std::io::Write;
+use std::mem;
+use std::path::{Path, PathBuf};
 use transform;
 
 #[derive(Clone, Debug)]
@@ -45,30 +58,16 @@ pub enum Error {
     SigningError,
 }
 
-impl StdError for Error {
-    fn description(&self) -> &str {
-        match *self {
-            Error::IoError(ref ie) => ie.description(),
-            Error::ParseError(ref msg) => msg,
-            Error::DirNotFound => "Couldn't find key directory",
-            Error::AliasNotFound => "Alias not found in key directory",
-            Error::SigningError => "Signing failed",
-        }
-    }
-
-    fn cause(&self) -> Option<&StdError> {
-        None
-    }
-}
+impl StdError for Error {}
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
         match *self {
             Error::IoError(ref ie) => ie.fmt(f),
-            Error::ParseError(ref msg) => write!(f, "ParseError: {}", msg),
-            Error::DirNotFound => write!(f, "DirNotFound"),
-            Error::AliasNotFound => write!(f, "AliasNotFound"),
-            Error::SigningError => write!(f, "SigningError"),
+            Error::ParseError(ref msg) => write!(f, "{}", msg),
+            Error::DirNotFound => write!(f, "Couldn't find key directory"),
+            Error::AliasNotFound => write!(f, "Alias not found in key directory"),
+            Error::SigningError => write!(f, "Signing failed"),
         }
     }
 }
@@ -88,19 +87,43 @@ impl From<SigningError> for Error {
     }
 }
 
+/// Returns the directory keys are kept in, `.sawtooth/keys` in the home directory
+/// like the other Sawtooth tools, which on Windows is in the user's profile
 pub fn get_key_dir() -> Option<PathBuf> {
-    let home = home_dir()?;
+    home_dir().map(|home| home.join(".sawtooth").join("keys"))
+}
+
+/// Returns the path of the key of an alias. The extension is appended rather than
+/// set, so that aliases with dots in them keep their whole name.
+fn key_path(alias: &str) -> Option<PathBuf> {
+    get_key_dir().map(|dir| dir.join(format!("{}.pem", alias)))
+}
+
+/// Saves a key under an alias, creating the key directory if needed. On Unix the
+/// file can only be read by its owner.
+#[cfg(feature = "personal")]
+pub fn write_key(alias: &str, pem: &str) -> Result<(), Error> {
+    let path = key_path(alias).ok_or(Error::DirNotFound)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
 
-    Some([home.to_str()?, ".sawtooth", "keys"].iter().collect())
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(pem.as_bytes())?;
+    Ok(())
 }
 
 impl Account {
     pub fn load_from_file(key_name: &str, password: &Option<String>) -> Result<Account, Error> {
-        let mut key_path = get_key_dir().ok_or(Error::DirNotFound)?;
-        key_path.push(key_name);
-        let pem = key_path.with_extension("pem");
+        let pem = key_path(key_name).ok_or(Error::DirNotFound)?;
 
-        if pem.as_path().is_file() {
+        if pem.is_file() {
             Self::load_from_str(&Self::read_file(&pem)?, password)
         } else {
             Err(Error::AliasNotFound)?
@@ -109,15 +132,15 @@ impl Account {
 
     pub fn load_from_str(key: &str, password: &Option<String>) -> Result<Account, Error> {
         let key = match (key.contains("ENCRYPTED"), password) {
-            (true, Some(pw)) => Secp256k1PrivateKey::from_pem_with_password(&key.trim(), &pw),
+            (true, Some(pw)) => Secp256k1PrivateKey::from_pem_with_password(key.trim(), pw),
             (true, None) => Err(Error::ParseError(
                 "A password is required for encrypted keys!".into(),
             ))?,
             (false, Some(_)) => {
                 warn!("Account::load_from_str got password for non-encrypted private key.");
-                Secp256k1PrivateKey::from_pem(&key.trim())
+                Secp256k1PrivateKey::from_pem(key.trim())
             }
-            (false, None) => Secp256k1PrivateKey::from_pem(&key.trim()),
+            (false, None) => Secp256k1PrivateKey::from_pem(key.trim()),
         }?;
 
         let algorithm = create_context("secp256k1").unwrap();
@@ -131,8 +154,25 @@ impl Account {
         })
     }
 
-    fn read_file(keyfile: &PathBuf) -> Result<String, Error> {
-        let mut file = File::open(keyfile.as_path().to_str().unwrap())?;
+    /// Makes an account of a private key given as hex, which isn't kept in a file
+    pub fn from_private_key(alias: &str, private_key: &str) -> Result<Account, Error> {
+        let invalid = || Error::ParseError(String::from("Invalid private key"));
+        let secp = Secp256k1::new();
+        let key = transform::hex_str_to_bytes(private_key).ok_or_else(invalid)?;
+        let secret_key = SecretKey::from_slice(&secp, &key).map_err(|_| invalid())?;
+        let public_key = PublicKey::from_secret_key(&secp, &secret_key).map_err(|_| invalid())?;
+        let public_key = compressed_public_key(&secp, &public_key);
+
+        Ok(Account {
+            alias: String::from(alias),
+            private_key: transform::bytes_to_hex_str(&key),
+            public_key: transform::bytes_to_hex_str(&public_key),
+            address: public_key_to_address(&public_key),
+        })
+    }
+
+    fn read_file(keyfile: &Path) -> Result<String, Error> {
+        let mut file = File::open(keyfile)?;
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
         Ok(contents)
@@ -147,6 +187,25 @@ impl Account {
             .map_err(|_| Error::SigningError)
     }
 
+    /// Signs a hash the way Ethereum accounts do, without hashing it again as `sign`
+    /// does, and returns the 65 byte signature `r || s || v` that ecrecover takes,
+    /// with a `v` of 27 or 28
+    pub fn sign_hash(&self, hash: &[u8; 32]) -> Result<Vec<u8>, Error> {
+        let secp = Secp256k1::new();
+        let secret = transform::hex_str_to_bytes(&self.private_key)
+            .and_then(|key| SecretKey::from_slice(&secp, &key).ok())
+            .ok_or(Error::SigningError)?;
+        let message = Message::from_slice(hash).map_err(|_| Error::SigningError)?;
+        let (recovery_id, signature) = secp
+            .sign_recoverable(&message, &secret)
+            .map_err(|_| Error::SigningError)?
+            .serialize_compact(&secp);
+
+        let mut signed = signature.to_vec();
+        signed.push(27 + recovery_id.to_i32() as u8);
+        Ok(signed)
+    }
+
     pub fn alias(&self) -> &str {
         &self.alias
     }
@@ -158,6 +217,17 @@ impl Account {
     pub fn public_key(&self) -> &str {
         &self.public_key
     }
+
+    /// Encrypts the key with a password into a JSON keystore, which geth and other
+    /// wallets can import
+    #[cfg(feature = "personal")]
+    pub fn to_keystore(&self, password: &str) -> Result<Value, Error> {
+        let private_key = transform::hex_str_to_bytes(&self.private_key)
+            .ok_or_else(|| Error::ParseError(String::from("Invalid private key")))?;
+        let public_key = transform::hex_str_to_bytes(&self.public_key)
+            .ok_or_else(|| Error::ParseError(String::from("Invalid public key")))?;
+        keystore::encrypt(&private_key, &public_key, password).map_err(Error::ParseError)
+    }
 }
 
 impl PartialEq for Account {
@@ -167,5 +237,121 @@ impl PartialEq for Account {
 }
 
 pub fn public_key_to_address(pub_key: &[u8]) -> String {
-    transform::bytes_to_hex_str(&tiny_keccak::keccak256(pub_key)[..20])
+    transform::bytes_to_hex_str(&crypto_backend::keccak256(pub_key)[..20])
+}
+
+/// Returns the hash that `eth_sign` signs for a message, as EIP-191 defines it
+pub fn personal_message_hash(message: &[u8]) -> [u8; 32] {
+    let mut data = format!("\x19Ethereum Signed Message:\n{}", message.len()).into_bytes();
+    data.extend_from_slice(message);
+    crypto_backend::keccak256(&data)
+}
+
+/// Returns the addresses of the accounts that could have made `signature` by signing
+/// `message` with `Account::sign`. A 65 byte signature ends with its recovery id and
+/// has one signer, while the 64 byte signatures `Account::sign` makes have up to two.
+pub fn recover_addresses(message: &[u8], signature: &[u8]) -> Result<Vec<String>, Error> {
+    let recovery_ids = match signature.len() {
+        64 => vec![0, 1],
+        65 => match signature[64] {
+            v @ 0..=3 => vec![i32::from(v)],
+            v @ 27..=30 => vec![i32::from(v - 27)],
+            _ => return Err(Error::ParseError(String::from("Invalid recovery id"))),
+        },
+        _ => {
+            return Err(Error::ParseError(String::from(
+                "Signature must be 64 or 65 bytes",
+            )))
+        }
+    };
+
+    // Account::sign signs the SHA-256 hash of the message
+    let mut sha = Sha256::new();
+    sha.input(message);
+    let mut hash = [0; 32];
+    sha.result(&mut hash);
+
+    Ok(recovery_ids
+        .into_iter()
+        .filter_map(|id| crypto_backend::recover(&hash, &signature[..64], id))
+        .map(|public_key| public_key_to_address(&public_key))
+        .collect())
+}
+
+/// Serializes a public key in the compressed form seth addresses are derived from.
+/// Dropping the `ArrayVec` secp256k1 serializes into reads past its end, which
+/// debug builds panic on, and it holds only bytes, so it is forgotten instead.
+pub fn compressed_public_key(secp: &Secp256k1, public_key: &PublicKey) -> Vec<u8> {
+    let serialized = public_key.serialize_vec(secp, true);
+    let bytes = serialized.to_vec();
+    mem::forget(serialized);
+    bytes
+}
+
+/// Returns the address of the contract CREATE2 creates from `creator` with the given
+/// salt and init code, which the transaction processor also gives contracts created
+/// with a salt
+pub fn create2_address(creator: &[u8], salt: &[u8], init: &[u8]) -> String {
+    let mut buf = vec![0xff];
+    buf.extend_from_slice(creator);
+    buf.extend_from_slice(salt);
+    buf.extend_from_slice(&crypto_backend::keccak256(init));
+    transform::bytes_to_hex_str(&crypto_backend::keccak256(&buf)[12..])
+}
+
+/// Returns the address of the contract created by `creator` with the given nonce, which
+/// the transaction processor derives the same way
+pub fn contract_address(creator: &[u8], nonce: u64) -> String {
+    if nonce == 0 {
+        return transform::bytes_to_hex_str(creator);
+    }
+    let mut buf = creator.to_vec();
+    buf.extend_from_slice(&nonce.to_be_bytes());
+    transform::bytes_to_hex_str(&crypto_backend::keccak256(&buf)[..20])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secp256k1::{RecoverableSignature, RecoveryId};
+
+    #[test]
+    fn derives_create2_addresses() {
+        // The first example of EIP-1014
+        assert_eq!(
+            create2_address(&[0; 20], &[0; 32], &[0]),
+            "4d1a2e2bb4f88f0250f26ffff098b0b30b26bf38"
+        );
+    }
+
+    #[test]
+    fn signs_hashes_for_ecrecover() {
+        let account = Account {
+            alias: String::new(),
+            private_key: "46".repeat(32),
+            public_key: String::new(),
+            address: String::new(),
+        };
+        let hash = crypto_backend::keccak256(b"typed data");
+        let signature = account.sign_hash(&hash).unwrap();
+        assert_eq!(signature.len(), 65);
+        assert!(signature[64] == 27 || signature[64] == 28);
+
+        // ecrecover gives the Ethereum address of the key, which is derived from the
+        // uncompressed public key
+        let secp = Secp256k1::new();
+        let recovery_id = RecoveryId::from_i32(i32::from(signature[64] - 27)).unwrap();
+        let signature =
+            RecoverableSignature::from_compact(&secp, &signature[..64], recovery_id).unwrap();
+        let public_key = secp
+            .recover(&Message::from_slice(&hash).unwrap(), &signature)
+            .unwrap();
+        let serialized = public_key.serialize_vec(&secp, false);
+        let uncompressed = serialized.to_vec();
+        mem::forget(serialized);
+        assert_eq!(
+            transform::bytes_to_hex_str(&crypto_backend::keccak256(&uncompressed[1..])[12..]),
+            "9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f"
+        );
+    }
 }
@@ -0,0 +1,357 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Authentication of clients by the `Authorization` header of their requests, with
+//! bearer tokens or JSON Web Tokens signed with a shared secret, and refusal of the
+//! methods only authenticated clients may call to the clients that aren't.
+
+use base64;
+use calls::error;
+use crypto::hmac::Hmac;
+use crypto::mac::{Mac, MacResult};
+use crypto::sha2::Sha256;
+use crypto::util::fixed_time_eq;
+use futures::future::{self, Either, Future, FutureExt};
+use jsonrpc_core::{BoxFuture, Call, Failure, Middleware, Output, Request, Response};
+use requests::{RequestMeta, HEALTH_METHOD};
+use serde_json::{self, Value};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use versions;
+
+/// The methods only authenticated clients may call unless `--auth-methods` says
+/// otherwise: those that use the unlocked accounts, send transactions, change what
+/// the server tracks, or read enough state to be costly to serve
+pub const DEFAULT_RESTRICTED_METHODS: &str = concat!(
+    "personal_*,eth_send*,eth_sign*,",
+    "seth_submitOrdered,seth_importBatches,seth_importState,seth_setPermissions,",
+    "seth_archiveContract,seth_resurrectContract,seth_registerContract,",
+    "seth_setWatchlist,seth_flushCallCache,seth_resetNonces,seth_exportState,",
+    "debug_*,eth_getProof"
+);
+
+/// Checks the credentials clients send and the methods they may call. The default
+/// authenticator lets every client call every method.
+#[derive(Clone, Default)]
+pub struct Authenticator {
+    tokens: Arc<Vec<String>>,
+    jwt_secret: Option<Arc<Vec<u8>>>,
+    restricted: Arc<Vec<String>>,
+}
+
+impl Authenticator {
+    /// Authenticates the clients that send one of `tokens` or a JWT signed with
+    /// `jwt_secret`. `restricted` are the methods only they may call, each a name or
+    /// a prefix followed by `*`.
+    pub fn new(tokens: Vec<String>, jwt_secret: Option<Vec<u8>>, restricted: Vec<String>) -> Self {
+        Authenticator {
+            tokens: Arc::new(
+                tokens
+                    .into_iter()
+                    .filter(|token| !token.is_empty())
+                    .collect(),
+            ),
+            jwt_secret: jwt_secret.map(Arc::new),
+            restricted: Arc::new(restricted),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.tokens.is_empty() || self.jwt_secret.is_some()
+    }
+
+    /// Whether the value of an `Authorization` header authenticates the client
+    pub fn check(&self, authorization: Option<&[u8]>) -> bool {
+        let token = match authorization {
+            Some(header) if header.len() > 7 && header[..7].eq_ignore_ascii_case(b"Bearer ") => {
+                &header[7..]
+            }
+            _ => return false,
+        };
+        if self
+            .tokens
+            .iter()
+            .any(|known| fixed_time_eq(known.as_bytes(), token))
+        {
+            return true;
+        }
+        match (self.jwt_secret.as_ref(), std::str::from_utf8(token)) {
+            (Some(secret), Ok(token)) => verify_jwt(secret, token, now()),
+            _ => false,
+        }
+    }
+
+    /// Whether only authenticated clients may call a method. Health checks never
+    /// need credentials, so load balancers can check servers without them, and each
+    /// version of a seth_* method is restricted like its unversioned name.
+    fn is_restricted(&self, method: &str) -> bool {
+        let method: &str = &versions::canonical(method);
+        method != HEALTH_METHOD
+            && self.restricted.iter().any(|pattern| {
+                if pattern.ends_with('*') {
+                    method.starts_with(&pattern[..pattern.len() - 1])
+                } else {
+                    method == pattern
+                }
+            })
+    }
+}
+
+impl Middleware<RequestMeta> for Authenticator {
+    type Future = BoxFuture<Option<Response>>;
+    type CallFuture = BoxFuture<Option<Output>>;
+
+    fn on_request<F, X>(
+        &self,
+        request: Request,
+        meta: RequestMeta,
+        next: F,
+    ) -> Either<Self::Future, X>
+    where
+        F: Fn(Request, RequestMeta) -> X + Send + Sync,
+        X: Future<Output = Option<Response>> + Send + 'static,
+    {
+        Either::Right(next(request, meta))
+    }
+
+    fn on_call<F, X>(&self, call: Call, meta: RequestMeta, next: F) -> Either<Self::CallFuture, X>
+    where
+        F: Fn(Call, RequestMeta) -> X + Send + Sync,
+        X: Future<Output = Option<Output>> + Send + 'static,
+    {
+        let refused = match call {
+            Call::MethodCall(ref call)
+                if self.is_enabled() && !meta.authenticated && self.is_restricted(&call.method) =>
+            {
+                warn!("Refusing {} to an unauthenticated client", call.method);
+                Output::Failure(Failure {
+                    jsonrpc: call.jsonrpc,
+                    error: error::unauthorized(),
+                    id: call.id.clone(),
+                })
+            }
+            _ => return Either::Right(next(call, meta)),
+        };
+        Either::Left(future::ready(Some(refused)).boxed())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or(0)
+}
+
+/// Whether a JWT is signed with `secret` using HS256 and is valid at `now`, by its
+/// `exp` and `nbf` claims if it has them
+fn verify_jwt(secret: &[u8], token: &str, now: u64) -> bool {
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 3 {
+        return false;
+    }
+    let decode = |part: &str| base64::decode_config(part, base64::URL_SAFE_NO_PAD).ok();
+    let parse = |part: &str| -> Option<Value> {
+        decode(part).and_then(|json| serde_json::from_slice(&json).ok())
+    };
+    let (header, claims, signature) = match (parse(parts[0]), parse(parts[1]), decode(parts[2])) {
+        (Some(header), Some(claims), Some(signature)) => (header, claims, signature),
+        _ => return false,
+    };
+    if header["alg"] != "HS256" {
+        return false;
+    }
+
+    let mut mac = Hmac::new(Sha256::new(), secret);
+    mac.input(parts[0].as_bytes());
+    mac.input(b".");
+    mac.input(parts[1].as_bytes());
+    if mac.result() != MacResult::new(&signature) {
+        return false;
+    }
+
+    let expired = claims["exp"].as_u64().is_some_and(|exp| now >= exp);
+    let early = claims["nbf"].as_u64().is_some_and(|nbf| now < nbf);
+    !expired && !early
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpc_core::{Id, MetaIoHandler, Params, Value, Version};
+
+    const SECRET: &[u8] = b"shared secret";
+
+    fn jwt(claims: &str) -> String {
+        let encode = |part: &[u8]| base64::encode_config(part, base64::URL_SAFE_NO_PAD);
+        let signed = format!(
+            "{}.{}",
+            encode(br#"{"alg":"HS256","typ":"JWT"}"#),
+            encode(claims.as_bytes())
+        );
+        let mut mac = Hmac::new(Sha256::new(), SECRET);
+        mac.input(signed.as_bytes());
+        format!("{}.{}", signed, encode(mac.result().code()))
+    }
+
+    #[test]
+    fn checks_tokens_and_jwts() {
+        let auth = Authenticator::new(
+            vec![String::from("t0ken")],
+            Some(SECRET.to_vec()),
+            Vec::new(),
+        );
+        let bearer = |token: &str| format!("Bearer {}", token).into_bytes();
+        assert!(auth.check(Some(&bearer("t0ken"))));
+        assert!(!auth.check(Some(&bearer("t0ke"))));
+        assert!(!auth.check(Some(b"t0ken")));
+        assert!(!auth.check(None));
+
+        assert!(auth.check(Some(&bearer(&jwt(r#"{"sub":"wallet"}"#)))));
+        let expiring = jwt(&format!(r#"{{"exp":{}}}"#, now() + 60));
+        assert!(verify_jwt(SECRET, &expiring, now()));
+        assert!(!verify_jwt(SECRET, &expiring, now() + 60));
+        assert!(!verify_jwt(b"another secret", &expiring, now()));
+        let mut tampered = jwt(r#"{"sub":"wallet"}"#);
+        tampered.insert(40, 'x');
+        assert!(!verify_jwt(SECRET, &tampered, now()));
+    }
+
+    #[test]
+    fn refuses_restricted_methods_to_unauthenticated_clients() {
+        let auth = Authenticator::new(
+            vec![String::from("t0ken")],
+            None,
+            vec![
+                String::from("personal_*"),
+                String::from("eth_sendRawTransaction"),
+                String::from("seth_setPermissions"),
+            ],
+        );
+        let mut io = MetaIoHandler::<RequestMeta, Authenticator>::with_middleware(auth);
+        for method in &[
+            "personal_listAccounts",
+            "eth_sendRawTransaction",
+            "eth_chainId",
+            "seth_v1_setPermissions",
+        ] {
+            io.add_method_with_meta(method, |_: Params, _: RequestMeta| {
+                future::ok(Value::Bool(true))
+            });
+        }
+        let call = |method: &str, authenticated| {
+            let request = format!(
+                r#"{{"jsonrpc": "2.0", "id": 1, "method": "{}", "params": []}}"#,
+                method
+            );
+            let meta = RequestMeta {
+                authenticated,
+                ..RequestMeta::default()
+            };
+            let response = io.handle_request_sync(&request, meta).unwrap();
+            serde_json::from_str::<Output>(&response).unwrap()
+        };
+
+        assert_eq!(
+            call("personal_listAccounts", false),
+            Output::Failure(Failure {
+                jsonrpc: Some(Version::V2),
+                error: error::unauthorized(),
+                id: Id::Num(1),
+            })
+        );
+        assert!(matches!(
+            call("eth_sendRawTransaction", false),
+            Output::Failure(_)
+        ));
+        assert!(matches!(
+            call("seth_v1_setPermissions", false),
+            Output::Failure(_)
+        ));
+        assert!(matches!(call("eth_chainId", false), Output::Success(_)));
+        assert!(matches!(
+            call("personal_listAccounts", true),
+            Output::Success(_)
+        ));
+    }
+
+    #[test]
+    fn restricts_the_root_methods_by_default() {
+        let auth = Authenticator::new(
+            vec![String::from("t0ken")],
+            None,
+            DEFAULT_RESTRICTED_METHODS
+                .split(',')
+                .map(String::from)
+                .collect(),
+        );
+        let root = [
+            "personal_unlockAccount",
+            "eth_sendTransaction",
+            "eth_signTypedData_v4",
+            "seth_importState",
+            "seth_v1_setPermissions",
+            "seth_archiveContract",
+            "seth_resurrectContract",
+            "seth_registerContract",
+            "seth_setWatchlist",
+            "seth_flushCallCache",
+            "seth_resetNonces",
+            "seth_exportState",
+            "debug_traceTransaction",
+            "debug_traceCall",
+            "eth_getProof",
+        ];
+        let open = [
+            "eth_call",
+            "eth_getBalance",
+            "eth_chainId",
+            "seth_getHealth",
+        ];
+        let mut io = MetaIoHandler::<RequestMeta, Authenticator>::with_middleware(auth);
+        for method in root.iter().chain(open.iter()) {
+            io.add_method_with_meta(method, |_: Params, _: RequestMeta| {
+                future::ok(Value::Bool(true))
+            });
+        }
+        let call = |method: &str| {
+            let request = format!(
+                r#"{{"jsonrpc": "2.0", "id": 1, "method": "{}", "params": []}}"#,
+                method
+            );
+            let response = io
+                .handle_request_sync(&request, RequestMeta::default())
+                .unwrap();
+            serde_json::from_str::<Output>(&response).unwrap()
+        };
+
+        for method in &root {
+            assert!(
+                matches!(call(method), Output::Failure(_)),
+                "{} isn't restricted",
+                method
+            );
+        }
+        for method in &open {
+            assert!(
+                matches!(call(method), Output::Success(_)),
+                "{} is restricted",
+                method
+            );
+        }
+    }
+}
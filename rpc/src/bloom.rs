@@ -0,0 +1,192 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! The bloom filters of logs that Ethereum blocks and receipts carry
+
+use crypto_backend;
+use std::iter;
+use transactions::SethLog;
+use transform;
+
+/// The size of a bloom filter, 2048 bits
+const BLOOM_BYTES: usize = 256;
+
+/// A bloom filter of the addresses and topics of logs, which lets clients skip the
+/// blocks and receipts that can't have the logs they are looking for. Each value
+/// sets the three bits given by the first three pairs of bytes of its Keccak hash,
+/// each taken as a big endian number modulo 2048, with bit 0 the lowest bit of the
+/// last byte.
+#[derive(Clone, PartialEq)]
+pub struct Bloom([u8; BLOOM_BYTES]);
+
+impl Bloom {
+    pub fn new() -> Self {
+        Bloom([0; BLOOM_BYTES])
+    }
+
+    pub fn accrue(&mut self, value: &[u8]) {
+        let hash = crypto_backend::keccak256(value);
+        for pair in hash[..6].chunks(2) {
+            let bit = (usize::from(pair[0]) << 8 | usize::from(pair[1])) % (BLOOM_BYTES * 8);
+            self.0[BLOOM_BYTES - 1 - bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    /// Adds the address and topics of a log, which seth keeps as hex without their
+    /// leading zeros on some paths, so they are padded back to 20 and 32 bytes
+    pub fn accrue_log(&mut self, log: &SethLog) {
+        let values =
+            iter::once((&log.address, 20usize)).chain(log.topics.iter().map(|topic| (topic, 32)));
+        for (value, size) in values {
+            if let Some(bytes) = padded_bytes(value, size) {
+                self.accrue(&bytes);
+            }
+        }
+    }
+
+    pub fn contains(&self, value: &[u8]) -> bool {
+        let mut single = Bloom::new();
+        single.accrue(value);
+        single
+            .0
+            .iter()
+            .zip(self.0.iter())
+            .all(|(bit, set)| bit & set == *bit)
+    }
+
+    /// Returns whether a log with the address, or a topic, padded like the ones of
+    /// `accrue_log` may have been added. Values that aren't hex can't be ruled out.
+    pub fn may_contain_hex(&self, value: &str, size: usize) -> bool {
+        match padded_bytes(value, size) {
+            Some(bytes) => self.contains(&bytes),
+            None => true,
+        }
+    }
+
+    pub fn to_hex(&self) -> String {
+        format!("0x{}", transform::bytes_to_hex_str(&self.0))
+    }
+}
+
+/// Decodes a hex value, adding back the leading zeros of a value `size` bytes long
+fn padded_bytes(value: &str, size: usize) -> Option<Vec<u8>> {
+    let padding = (size * 2).saturating_sub(value.len());
+    transform::hex_str_to_bytes(&format!("{}{}", "0".repeat(padding), value))
+}
+
+impl<'a> iter::FromIterator<&'a SethLog> for Bloom {
+    fn from_iter<I: IntoIterator<Item = &'a SethLog>>(logs: I) -> Self {
+        let mut bloom = Bloom::new();
+        for log in logs {
+            bloom.accrue_log(log);
+        }
+        bloom
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The reference vector of the ethbloom crate, the bloom of one log of the
+    // address with the topic
+    const ADDRESS: &str = "ef2d6d194084c2de36e0dabfce45d046b37d1106";
+    const TOPIC: &str = "02c69be41d0b7e40352fc85be1cd65eb03d40ef8427a0ca4596b1ead9a00e9fc";
+    const BLOOM: &str = "0x\
+        00000000000000000000000000000000\
+        00000000100000000000000000000000\
+        00000000000000000000000000000000\
+        00000000000000000000000000000000\
+        00000000000000000000000000000000\
+        00000000000000000000000000000000\
+        00000002020000000000000000000000\
+        00000000000000000000000800000000\
+        10000000000000000000000000000000\
+        00000000000000000000001000000000\
+        00000000000000000000000000000000\
+        00000000000000000000000000000000\
+        00000000000000000000000000000000\
+        00000000000000000000000000000000\
+        00000000000000000000000000000000\
+        00000000000000000000000000000000";
+
+    fn log(address: &str, topics: &[&str]) -> SethLog {
+        SethLog {
+            address: String::from(address),
+            topics: topics.iter().map(|&topic| String::from(topic)).collect(),
+            data: String::new(),
+        }
+    }
+
+    #[test]
+    fn matches_reference_vector() {
+        let address = transform::hex_str_to_bytes(ADDRESS).unwrap();
+        let topic = transform::hex_str_to_bytes(TOPIC).unwrap();
+
+        let mut bloom = Bloom::new();
+        bloom.accrue(&address);
+        assert!(bloom.contains(&address));
+        assert!(!bloom.contains(&topic));
+        bloom.accrue(&topic);
+        assert!(bloom.contains(&topic));
+        assert_eq!(BLOOM, bloom.to_hex());
+
+        let logs = [log(ADDRESS, &[TOPIC])];
+        assert_eq!(BLOOM, logs.iter().collect::<Bloom>().to_hex());
+    }
+
+    #[test]
+    fn sets_bits_for_the_hash_of_the_value() {
+        // The Keccak hash of nothing starts with c5d2 4601 86f7, which are bits 1490,
+        // 1537 and 1783, in bytes 69, 63 and 33 from the start
+        let mut bloom = Bloom::new();
+        bloom.accrue(&[]);
+        let set: Vec<(usize, u8)> = bloom
+            .0
+            .iter()
+            .enumerate()
+            .filter(|&(_, byte)| *byte != 0)
+            .map(|(index, byte)| (index, *byte))
+            .collect();
+        assert_eq!(vec![(33, 0x80), (63, 0x02), (69, 0x04)], set);
+
+        // Logs without topics only set the bits of their address, and values seth
+        // stripped of leading zeros are padded back
+        let padded = [log(&format!("00{}", &ADDRESS[2..]), &[])];
+        let stripped = [log(&ADDRESS[2..], &[])];
+        assert_eq!(
+            padded.iter().collect::<Bloom>().to_hex(),
+            stripped.iter().collect::<Bloom>().to_hex()
+        );
+        assert_eq!("0x".to_owned() + &"00".repeat(256), Bloom::new().to_hex());
+
+        // Even when that leaves an odd number of digits
+        let odd = [log(&ADDRESS[3..], &[])];
+        let bloom = odd.iter().collect::<Bloom>();
+        assert!(bloom.may_contain_hex(&format!("0{}", &ADDRESS[3..]), 20));
+    }
+
+    #[test]
+    fn may_contain_the_values_of_its_logs() {
+        let bloom = [log(ADDRESS, &[TOPIC])].iter().collect::<Bloom>();
+        assert!(bloom.may_contain_hex(ADDRESS, 20));
+        assert!(bloom.may_contain_hex(TOPIC, 32));
+        assert!(!bloom.may_contain_hex(&ADDRESS[..38], 20));
+        assert!(!Bloom::new().may_contain_hex(ADDRESS, 20));
+        assert!(Bloom::new().may_contain_hex("not hex", 20));
+    }
+}
@@ -0,0 +1,141 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How many requests in a row must fail to reach the validator before it is taken
+/// to be down
+const FAILURE_THRESHOLD: u32 = 5;
+
+/// How long requests fail fast once the validator is taken to be down, before one
+/// is let through to see if it is back
+const COOL_DOWN: Duration = Duration::from_secs(10);
+
+struct BreakerState {
+    consecutive_failures: u32,
+
+    /// Until when requests fail fast, if the breaker is open
+    open_until: Option<Instant>,
+
+    /// Whether a request is already testing the validator after a cool-down
+    probing: bool,
+}
+
+/// Stops sending requests to a validator that keeps failing to answer, so that
+/// requests fail fast during an outage rather than each holding a worker thread
+/// until it times out
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    state: Arc<Mutex<BreakerState>>,
+    threshold: u32,
+    cool_down: Duration,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        CircuitBreaker::with_limits(FAILURE_THRESHOLD, COOL_DOWN)
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new() -> Self {
+        CircuitBreaker::default()
+    }
+
+    fn with_limits(threshold: u32, cool_down: Duration) -> Self {
+        CircuitBreaker {
+            state: Arc::new(Mutex::new(BreakerState {
+                consecutive_failures: 0,
+                open_until: None,
+                probing: false,
+            })),
+            threshold,
+            cool_down,
+        }
+    }
+
+    /// Returns whether a request may be sent. Once the cool-down is over, a single
+    /// request is let through, and the others keep failing until it is answered.
+    pub fn allow(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match state.open_until {
+            None => true,
+            Some(until) if Instant::now() < until || state.probing => false,
+            Some(_) => {
+                state.probing = true;
+                true
+            }
+        }
+    }
+
+    /// Returns whether requests are failing fast
+    pub fn is_open(&self) -> bool {
+        self.state.lock().unwrap().open_until.is_some()
+    }
+
+    /// Records whether the validator answered a request that was let through
+    pub fn record(&self, answered: bool) {
+        let mut state = self.state.lock().unwrap();
+        if answered {
+            if state.open_until.is_some() {
+                info!("Validator is answering again");
+            }
+            state.consecutive_failures = 0;
+            state.open_until = None;
+            state.probing = false;
+            return;
+        }
+
+        state.consecutive_failures += 1;
+        if state.probing || state.consecutive_failures >= self.threshold {
+            if state.open_until.is_none() {
+                warn!(
+                    "Validator failed to answer {} requests in a row, failing requests for {:?}",
+                    state.consecutive_failures, self.cool_down
+                );
+            }
+            state.open_until = Some(Instant::now() + self.cool_down);
+            state.probing = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_consecutive_failures_and_closes_after_a_probe() {
+        let breaker = CircuitBreaker::with_limits(2, Duration::from_secs(0));
+        breaker.record(false);
+        breaker.record(true);
+        breaker.record(false);
+        assert!(!breaker.is_open());
+        breaker.record(false);
+        assert!(breaker.is_open());
+
+        // The cool-down is over, so one probe is let through at a time
+        assert!(breaker.allow());
+        assert!(!breaker.allow());
+        breaker.record(false);
+        assert!(breaker.allow());
+        breaker.record(true);
+        assert!(!breaker.is_open());
+        assert!(breaker.allow());
+    }
+}
@@ -0,0 +1,431 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+use client::{BlockKey, Error, ValidatorClient, SETH_NS};
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use crypto_backend;
+use jsonrpc_core::Value;
+use messages::seth::EvmEntry;
+use protobuf::{self, Message};
+use sawtooth_sdk::messages::block::{Block, BlockHeader};
+use sawtooth_sdk::messaging::stream::MessageSender;
+use serde_json;
+use serde_json::Map;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use transactions::SethReceipt;
+use transform::bytes_to_hex_str;
+
+/// How often the chain cache checks whether the chain forked
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+struct CacheEntry<V> {
+    value: V,
+    size: usize,
+    last_used: u64,
+}
+
+/// Entries of at most `max_size` bytes altogether, of which the least recently used
+/// are evicted first
+struct Lru<V> {
+    entries: HashMap<String, CacheEntry<V>>,
+    size: usize,
+    tick: u64,
+    max_size: usize,
+}
+
+impl<V: Clone> Lru<V> {
+    fn new(max_size: usize) -> Self {
+        Lru {
+            entries: HashMap::new(),
+            size: 0,
+            tick: 0,
+            max_size,
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<V> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.entries.get_mut(key).map(|entry| {
+            entry.last_used = tick;
+            entry.value.clone()
+        })
+    }
+
+    /// Adds an entry of `size` bytes, not counting its key
+    fn insert(&mut self, key: String, value: V, size: usize) {
+        let size = key.len() + size;
+        if size > self.max_size {
+            return;
+        }
+
+        self.tick += 1;
+        let tick = self.tick;
+
+        if let Some(old) = self.entries.remove(&key) {
+            self.size -= old.size;
+        }
+        while self.size + size > self.max_size {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            match oldest.and_then(|key| self.entries.remove(&key)) {
+                Some(evicted) => self.size -= evicted.size,
+                None => break,
+            }
+        }
+
+        self.size += size;
+        self.entries.insert(
+            key,
+            CacheEntry {
+                value,
+                size,
+                last_used: tick,
+            },
+        );
+    }
+
+    /// Removes every entry, returning how many there were
+    fn clear(&mut self) -> usize {
+        let count = self.entries.len();
+        self.entries.clear();
+        self.size = 0;
+        count
+    }
+}
+
+/// Caches the results of `eth_call`. Entries are keyed by the id of the block the call
+/// was executed against, so a new block never returns a stale result; results for old
+/// blocks are evicted, least recently used first, once the cache exceeds `max_size` bytes.
+#[derive(Clone)]
+pub struct CallCache {
+    state: Arc<Mutex<Lru<Value>>>,
+}
+
+impl CallCache {
+    /// Creates a cache holding at most `max_size` bytes of results. A size of 0 disables it.
+    pub fn new(max_size: usize) -> Self {
+        CallCache {
+            state: Arc::new(Mutex::new(Lru::new(max_size))),
+        }
+    }
+
+    /// Builds the key for a call made with the given parameters against the given block
+    pub fn key(block_id: &str, call: &Map<String, Value>) -> String {
+        let mut sha = Sha256::new();
+        sha.input_str(&serde_json::to_string(call).unwrap_or_default());
+        format!("{}:{}", block_id, sha.result_str())
+    }
+
+    pub fn get(&self, key: &str) -> Option<Value> {
+        self.state.lock().unwrap().get(key)
+    }
+
+    pub fn insert(&self, key: String, value: Value) {
+        let size = serde_json::to_string(&value).map(|s| s.len()).unwrap_or(0);
+        self.state.lock().unwrap().insert(key, value, size);
+    }
+
+    /// Removes every entry, returning how many there were
+    pub fn flush(&self) -> usize {
+        self.state.lock().unwrap().clear()
+    }
+}
+
+/// Caches what is read from the validator about past blocks: blocks by id, state
+/// entries by state root and address, and the receipts of transactions. Blocks and
+/// state never change once they have an id or root, but a transaction's receipt is
+/// replaced if the chain forks to another block with it, so receipts are dropped
+/// whenever the chain forks. A size of 0 disables a cache.
+///
+/// Contract code is kept once for each code hash, and the cached entries of accounts
+/// hold a reference to it rather than a copy, so that a contract read at many state
+/// roots, or many contracts with the same code, take up the room of one copy.
+#[derive(Clone)]
+pub struct ChainCache {
+    blocks: Arc<Mutex<Lru<Block>>>,
+    state: Arc<Mutex<Lru<CachedEntry>>>,
+    receipts: Arc<Mutex<Lru<SethReceipt>>>,
+    codes: Arc<Mutex<Lru<Arc<Vec<u8>>>>>,
+}
+
+/// A cached state entry. The code of an account's entry is taken out of it and shared
+/// with the other entries that have the same code.
+#[derive(Clone)]
+struct CachedEntry {
+    data: Option<Vec<u8>>,
+    code: Option<Arc<Vec<u8>>>,
+}
+
+impl ChainCache {
+    pub fn new(
+        block_size: usize,
+        state_size: usize,
+        receipt_size: usize,
+        code_size: usize,
+    ) -> Self {
+        ChainCache {
+            blocks: Arc::new(Mutex::new(Lru::new(block_size))),
+            state: Arc::new(Mutex::new(Lru::new(state_size))),
+            receipts: Arc::new(Mutex::new(Lru::new(receipt_size))),
+            codes: Arc::new(Mutex::new(Lru::new(code_size))),
+        }
+    }
+
+    pub fn get_block(&self, block_id: &str) -> Option<Block> {
+        self.blocks.lock().unwrap().get(block_id)
+    }
+
+    pub fn insert_block(&self, block: &Block) {
+        let size = block.compute_size() as usize;
+        self.blocks
+            .lock()
+            .unwrap()
+            .insert(block.header_signature.clone(), block.clone(), size);
+    }
+
+    /// Returns the entry at an address in the given state, `Some(None)` if it is
+    /// cached as not existing
+    pub fn get_state(&self, state_root: &str, address: &str) -> Option<Option<Vec<u8>>> {
+        let entry = self
+            .state
+            .lock()
+            .unwrap()
+            .get(&format!("{}:{}", state_root, address))?;
+        Some(match (entry.data, entry.code) {
+            (Some(data), Some(code)) => Some(with_code(data, &code)),
+            (data, _) => data,
+        })
+    }
+
+    pub fn insert_state(&self, state_root: &str, address: &str, value: Option<Vec<u8>>) {
+        let entry = match value {
+            Some(data) if is_account_address(address) => self.take_code(data),
+            data => CachedEntry { data, code: None },
+        };
+        let size = entry.data.as_ref().map(Vec::len).unwrap_or(0);
+        self.state
+            .lock()
+            .unwrap()
+            .insert(format!("{}:{}", state_root, address), entry, size);
+    }
+
+    /// Takes the code out of an account's entry, sharing it with the entries of other
+    /// accounts that have the same code
+    fn take_code(&self, data: Vec<u8>) -> CachedEntry {
+        let mut entry = match protobuf::parse_from_bytes::<EvmEntry>(&data) {
+            Ok(entry) if !entry.get_account().get_code().is_empty() => entry,
+            _ => {
+                return CachedEntry {
+                    data: Some(data),
+                    code: None,
+                }
+            }
+        };
+        let code = self.insert_code(entry.mut_account().take_code());
+        match entry.write_to_bytes() {
+            Ok(stripped) => CachedEntry {
+                data: Some(stripped),
+                code: Some(code),
+            },
+            Err(_) => CachedEntry {
+                data: Some(data),
+                code: None,
+            },
+        }
+    }
+
+    /// Returns the code with the given Keccak hash, if it has been read
+    pub fn get_code(&self, code_hash: &str) -> Option<Arc<Vec<u8>>> {
+        self.codes.lock().unwrap().get(code_hash)
+    }
+
+    /// Keeps code that has been read, returning the copy that is kept if it already was
+    pub fn insert_code(&self, code: Vec<u8>) -> Arc<Vec<u8>> {
+        let hash = code_hash(&code);
+        let mut codes = self.codes.lock().unwrap();
+        if let Some(kept) = codes.get(&hash) {
+            return kept;
+        }
+        let size = code.len();
+        let code = Arc::new(code);
+        codes.insert(hash, code.clone(), size);
+        code
+    }
+
+    /// Keeps the code of an account that has been read, unless it already is
+    pub fn remember_code(&self, code: &[u8]) {
+        if !code.is_empty() && self.get_code(&code_hash(code)).is_none() {
+            self.insert_code(code.to_vec());
+        }
+    }
+
+    pub fn get_receipt(&self, transaction_id: &str) -> Option<SethReceipt> {
+        self.receipts.lock().unwrap().get(transaction_id)
+    }
+
+    pub fn insert_receipt(&self, receipt: &SethReceipt) {
+        let size = receipt.contract_address.len()
+            + receipt.return_value.len()
+            + receipt
+                .logs
+                .iter()
+                .map(|log| log.address.len() + log.data.len() + 64 * log.topics.len())
+                .sum::<usize>()
+            + 16 * receipt.gas_profile.len()
+            + receipt
+                .state_changes
+                .iter()
+                .map(|change| change.address.len() + change.value.len())
+                .sum::<usize>();
+        self.receipts
+            .lock()
+            .unwrap()
+            .insert(receipt.transaction_id.clone(), receipt.clone(), size);
+    }
+
+    /// Starts watching the chain for forks in the background
+    pub fn start<S>(&self, client: ValidatorClient<S>)
+    where
+        S: MessageSender + Clone + Send + Sync + 'static,
+    {
+        let cache = self.clone();
+        thread::spawn(move || {
+            let mut head: Option<(u64, String)> = None;
+            loop {
+                let latest = head_of(&client).and_then(|latest| {
+                    if let Some((block_num, ref block_id)) = head {
+                        if forked(&client, block_num, block_id)? {
+                            let dropped = cache.receipts.lock().unwrap().clear();
+                            info!("The chain forked, dropped {} cached receipts", dropped);
+                        }
+                    }
+                    Ok(latest)
+                });
+                match latest {
+                    Ok(latest) => head = Some(latest),
+                    Err(error) => warn!("Failed to check whether the chain forked: {}", error),
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+    }
+}
+
+fn head_of<S: MessageSender + Clone>(client: &ValidatorClient<S>) -> Result<(u64, String), Error> {
+    let block = client.get_head_block()?;
+    let header: BlockHeader = protobuf::parse_from_bytes(&block.header)
+        .map_err(|error| Error::ParseError(format!("Error parsing block_header: {:?}", error)))?;
+    Ok((header.block_num, block.header_signature))
+}
+
+/// Returns whether the block that was the head is no longer in the chain
+fn forked<S: MessageSender + Clone>(
+    client: &ValidatorClient<S>,
+    block_num: u64,
+    block_id: &str,
+) -> Result<bool, Error> {
+    match client.get_block(BlockKey::Number(block_num)) {
+        Ok(block) => Ok(block.header_signature != block_id),
+        // The chain was replaced by a shorter one
+        Err(Error::NoResource) => Ok(true),
+        Err(error) => Err(error),
+    }
+}
+
+/// Returns the Keccak hash of code, the way Ethereum hashes it
+pub fn code_hash(code: &[u8]) -> String {
+    bytes_to_hex_str(&crypto_backend::keccak256(code))
+}
+
+/// Whether a state address is of an account's entry, rather than of its storage
+fn is_account_address(address: &str) -> bool {
+    address.len() == 70 && address.starts_with(SETH_NS) && address.ends_with(&"0".repeat(24))
+}
+
+/// Puts the code taken out of an account's entry back into it
+fn with_code(data: Vec<u8>, code: &[u8]) -> Vec<u8> {
+    let mut entry: EvmEntry = match protobuf::parse_from_bytes(&data) {
+        Ok(entry) => entry,
+        Err(_) => return data,
+    };
+    entry.mut_account().set_code(code.to_vec());
+    entry.write_to_bytes().unwrap_or(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::account_state_address;
+
+    #[test]
+    fn evicts_the_least_recently_used_entries() {
+        let mut lru = Lru::new(30);
+        lru.insert(String::from("a"), 1, 9);
+        lru.insert(String::from("b"), 2, 9);
+        lru.insert(String::from("c"), 3, 9);
+        assert_eq!(lru.get("a"), Some(1));
+
+        lru.insert(String::from("d"), 4, 9);
+        assert_eq!(lru.get("b"), None);
+        assert_eq!(lru.get("a"), Some(1));
+        assert_eq!(lru.size, 30);
+
+        // Entries that could never fit aren't cached
+        lru.insert(String::from("e"), 5, 30);
+        assert_eq!(lru.get("e"), None);
+        assert_eq!(lru.clear(), 3);
+    }
+
+    #[test]
+    fn keeps_one_copy_of_each_code() {
+        let cache = ChainCache::new(0, 1024 * 1024, 0, 1024 * 1024);
+        let entry = |nonce| {
+            let mut entry = EvmEntry::new();
+            entry.mut_account().set_nonce(nonce);
+            entry.mut_account().set_code(vec![0x60, 0x80, 0x60, 0x40]);
+            entry.write_to_bytes().unwrap()
+        };
+        let first = account_state_address(&"11".repeat(20));
+        let second = account_state_address(&"22".repeat(20));
+        cache.insert_state("root1", &first, Some(entry(1)));
+        cache.insert_state("root2", &first, Some(entry(2)));
+        cache.insert_state("root2", &second, Some(entry(1)));
+
+        assert_eq!(cache.get_state("root1", &first), Some(Some(entry(1))));
+        assert_eq!(cache.get_state("root2", &first), Some(Some(entry(2))));
+        assert_eq!(cache.get_state("root2", &second), Some(Some(entry(1))));
+        let codes = cache.codes.lock().unwrap();
+        assert_eq!(codes.entries.len(), 1);
+        assert_eq!(codes.size, 64 + 4);
+
+        let hash = code_hash(&[0x60, 0x80, 0x60, 0x40]);
+        drop(codes);
+        assert_eq!(
+            *cache.get_code(&hash).unwrap(),
+            vec![0x60, 0x80, 0x60, 0x40]
+        );
+        assert!(cache.get_code(&code_hash(&[])).is_none());
+    }
+}
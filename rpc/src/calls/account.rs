@@ -17,14 +17,22 @@
 
 use jsonrpc_core::{Error, Params, Value};
 
-use client::{BlockKey, BlockKeyParseError, ValidatorClient};
+use accounts::Account;
+use client::{self, BlockKey, BlockKeyParseError, SlotProof, StateRead, ValidatorClient};
+use crypto_backend;
+use state_proof;
 use transform;
+use types::{self, Quantity, Wei};
 
 use messages::seth::EvmStateAccount;
 
-use error;
+#[cfg(feature = "ledger")]
+use ledger::LedgerAccount;
+use names;
 use requests::RequestHandler;
 use sawtooth_sdk::messaging::stream::MessageSender;
+use serde_json;
+use typed_data;
 
 pub fn get_method_list<T>() -> Vec<(String, RequestHandler<T>)>
 where
@@ -34,22 +42,24 @@ where
         ("eth_getBalance".into(), get_balance),
         ("eth_getStorageAt".into(), get_storage_at),
         ("eth_getCode".into(), get_code),
+        ("eth_getCodeByHash".into(), get_code_by_hash),
         ("eth_accounts".into(), accounts),
         ("eth_getTransactionCount".into(), get_transaction_count),
+        ("eth_getProof".into(), get_proof),
+        ("eth_signTypedData_v4".into(), sign_typed_data),
     ]
 }
 
-fn validate_block_key(block: &str) -> Result<BlockKey, Error> {
+pub fn validate_block_key(block: &str) -> Result<BlockKey, Error> {
     match block.parse() {
         Ok(k) => Ok(k),
         Err(BlockKeyParseError::Invalid) => {
             Err(Error::invalid_params("Failed to parse block number"))
         }
-        Err(BlockKeyParseError::Unsupported) => Err(error::not_implemented()),
     }
 }
 
-fn validate_account_address(address: &str) -> Result<String, Error> {
+pub fn validate_account_address(address: &str) -> Result<String, Error> {
     if address.len() != 42 {
         Err(Error::invalid_params(format!(
             "Invalid address length: {} != {}",
@@ -57,7 +67,7 @@ fn validate_account_address(address: &str) -> Result<String, Error> {
             42
         )))
     } else {
-        Ok(String::from(&address[2..]))
+        transform::parse_address(address)
     }
 }
 
@@ -101,13 +111,22 @@ where
         }
     };
 
-    let key = validate_block_key(&block)?;
-    let account_address = validate_account_address(&address)?;
+    let key = pin_block(&client, validate_block_key(&block)?)?;
+    let account_address = validate_account_address(&names::resolve_address(&client, &address)?)?;
     let storage_address = validate_storage_address(&position)?;
 
-    match client.get_storage_at(&account_address, &storage_address, key) {
-        Ok(Some(value)) => Ok(transform::hex_prefix(&transform::bytes_to_hex_str(&value))),
-        Ok(None) => Ok(Value::Null),
+    match client.get_storage_at(&account_address, &storage_address, key.clone()) {
+        Ok(value) => {
+            let position = transform::hex_str_to_bytes(&storage_address).unwrap_or_default();
+            let read = StateRead::Slot(client::pad_word(&position), value);
+            verify_read(&client, &account_address, &read, key)?;
+            match read {
+                StateRead::Slot(_, Some(value)) => {
+                    Ok(transform::hex_prefix(&transform::bytes_to_hex_str(&value)))
+                }
+                _ => Ok(Value::Null),
+            }
+        }
         Err(error) => {
             error!("{}", error);
             Err(Error::internal_error())
@@ -115,6 +134,151 @@ where
     }
 }
 
+/// Names the block of a key by its id when reads are verified, so that a read and
+/// its proof are of the same state even if a block is committed in between
+fn pin_block<T>(client: &ValidatorClient<T>, block: BlockKey) -> Result<BlockKey, Error>
+where
+    T: MessageSender,
+{
+    // The pending state counts transactions that aren't in a block, so it isn't
+    // verified
+    if !client.verifies_state_reads() || matches!(block, BlockKey::Pending) {
+        return Ok(block);
+    }
+    client
+        .get_block(block)
+        .map(|block| BlockKey::Signature(block.header_signature))
+        .map_err(|err| fail!("Couldn't get block", err))
+}
+
+/// Fails a request whose read doesn't match the proof of the block's state, when
+/// reads are verified
+fn verify_read<T>(
+    client: &ValidatorClient<T>,
+    address: &str,
+    read: &StateRead,
+    block: BlockKey,
+) -> Result<(), Error>
+where
+    T: MessageSender,
+{
+    if !client.verifies_state_reads() || matches!(block, BlockKey::Pending) {
+        return Ok(());
+    }
+    match client.verify_state_read(address, read, block) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(fail!("What was read doesn't match the block's state root")),
+        Err(err) => Err(fail!("Couldn't verify state read", err)),
+    }
+}
+
+/// Proves an account and some of its storage slots with the nodes of the Merkle-Radix
+/// tree of the block's state. Given "mpt" as the format, the storage proofs are instead
+/// those of the trie Ethereum would keep the account's storage in, for accounts whose
+/// storage fits in one.
+pub fn get_proof<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("eth_getProof");
+    let usage = "Takes [address: DATA(20), storageKeys: [DATA], block: QUANTITY|TAG, \
+                 format: \"sawtooth\"|\"mpt\"]";
+
+    let (address, positions, block, format): (String, Vec<String>, String, Option<String>) = params
+        .clone()
+        .parse()
+        .or_else(|_| {
+            params
+                .parse()
+                .map(|(a, p, b): (String, Vec<String>, String)| (a, p, b, None))
+        })
+        .map_err(|_| Error::invalid_params(usage))?;
+    let mpt = match format.as_deref() {
+        None | Some("sawtooth") => false,
+        Some("mpt") => true,
+        Some(_) => return Err(Error::invalid_params(usage)),
+    };
+
+    let key = validate_block_key(&block)?;
+    let account_address = validate_account_address(&names::resolve_address(&client, &address)?)?;
+    let keys = positions
+        .iter()
+        .map(
+            |position| match transform::hex_str_to_bytes(&validate_storage_address(position)?) {
+                Some(ref key) if key.len() <= 32 => Ok(client::pad_word(key)),
+                _ => Err(Error::invalid_params(format!(
+                    "Invalid storage position: {}",
+                    position
+                ))),
+            },
+        )
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let proof = client
+        .get_proof(&account_address, &keys, key)
+        .map_err(|err| fail!("Couldn't prove account state", err))?;
+
+    let encode_nodes = |nodes: Vec<Vec<u8>>| -> Vec<String> {
+        nodes
+            .iter()
+            .map(|node| format!("0x{}", transform::bytes_to_hex_str(node)))
+            .collect()
+    };
+    let (storage_hash, storage_proofs) = if mpt {
+        if proof.slots.values().any(|value| value.len() > 32) {
+            return Err(Error::invalid_params(format!(
+                "The storage of {} doesn't fit in a storage trie",
+                address
+            )));
+        }
+        let slots: Vec<(Vec<u8>, Vec<u8>)> = proof.slots.into_iter().collect();
+        let (root, proofs) = state_proof::prove_storage(&slots, &keys);
+        let storage_proofs = proof
+            .storage_proofs
+            .into_iter()
+            .zip(proofs)
+            .map(|(slot, proof)| SlotProof { proof, ..slot })
+            .collect();
+        (transform::bytes_to_hex_str(&root), storage_proofs)
+    } else {
+        (
+            proof.storage_hash.unwrap_or_else(|| "00".repeat(32)),
+            proof.storage_proofs,
+        )
+    };
+
+    let account = proof.account.unwrap_or_default();
+    Ok(transform::to_value(&types::AccountProof {
+        address: transform::checksum_address(&account_address),
+        account_proof: encode_nodes(proof.account_proof),
+        balance: Wei::from(account.balance as u64),
+        code_hash: format!(
+            "0x{}",
+            transform::bytes_to_hex_str(&crypto_backend::keccak256(&account.code))
+        ),
+        nonce: Quantity(account.nonce),
+        storage_hash: format!("0x{}", storage_hash),
+        storage_proof: positions
+            .into_iter()
+            .zip(storage_proofs)
+            .map(|(key, slot)| types::StorageProof {
+                key,
+                value: value_to_quantity(&slot.value.unwrap_or_default()),
+                proof: encode_nodes(slot.proof),
+            })
+            .collect(),
+    }))
+}
+
+/// Formats a storage value the way quantities are, without leading zeros
+fn value_to_quantity(value: &[u8]) -> String {
+    let hex = transform::bytes_to_hex_str(value);
+    match hex.trim_start_matches('0') {
+        "" => String::from("0x0"),
+        digits => format!("0x{}", digits),
+    }
+}
+
 pub fn get_code<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
 where
     T: MessageSender,
@@ -130,6 +294,30 @@ where
     }
 }
 
+/// Returns the code with a Keccak hash, as in the `codeHash` of `eth_getProof`, if
+/// seth-rpc has read an account with the code
+pub fn get_code_by_hash<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("eth_getCodeByHash");
+    let (hash,): (String,) = params
+        .parse()
+        .map_err(|_| Error::invalid_params("Takes [codeHash: DATA(32)]"))?;
+    let hash = match hash.get(2..) {
+        Some(hash) if hash.len() == 64 && transform::hex_str_to_bytes(hash).is_some() => {
+            hash.to_lowercase()
+        }
+        _ => return Err(Error::invalid_params("Invalid code hash")),
+    };
+
+    Ok(client
+        .chain_cache
+        .get_code(&hash)
+        .map(|code| transform::hex_prefix(&transform::bytes_to_hex_str(&code)))
+        .unwrap_or(Value::Null))
+}
+
 pub fn get_transaction_count<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
 where
     T: MessageSender,
@@ -161,12 +349,18 @@ where
         }
     };
 
-    let key = validate_block_key(&block)?;
-    let address = validate_account_address(&address)?;
+    let key = pin_block(&client, validate_block_key(&block)?)?;
+    let address = validate_account_address(&names::resolve_address(&client, &address)?)?;
 
-    match client.get_account(&address, key) {
-        Ok(Some(account)) => Ok(f(account)),
-        Ok(None) => Ok(Value::Null),
+    match client.get_account(&address, key.clone()) {
+        Ok(account) => {
+            let read = StateRead::Account(account);
+            verify_read(&client, &address, &read, key)?;
+            match read {
+                StateRead::Account(Some(account)) => Ok(f(account)),
+                _ => Ok(Value::Null),
+            }
+        }
         Err(error) => {
             error!("{}", error);
             Err(Error::internal_error())
@@ -174,6 +368,48 @@ where
     }
 }
 
+/// Signs EIP-712 typed data with one of the loaded accounts, the way Ethereum accounts
+/// sign it, so that contracts taking signed permits and meta-transactions can check
+/// the signature with ecrecover
+pub fn sign_typed_data<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("eth_signTypedData_v4");
+    let usage = "Takes [address: DATA(20), typedData: OBJECT|STRING]";
+
+    let (address, typed_data): (String, Value) =
+        params.parse().map_err(|_| Error::invalid_params(usage))?;
+    // Wallets send the typed data as JSON text, and other clients as an object
+    let typed_data = match typed_data {
+        Value::String(json) => {
+            serde_json::from_str(&json).map_err(|_| Error::invalid_params(usage))?
+        }
+        typed_data @ Value::Object(_) => typed_data,
+        _ => return Err(Error::invalid_params(usage)),
+    };
+    let address = validate_account_address(&address)?.to_lowercase();
+    let hash = typed_data::hash(&typed_data).map_err(Error::invalid_params)?;
+
+    let account = client
+        .loaded_accounts()
+        .read()
+        .unwrap()
+        .iter()
+        .find(|account| account.address() == address)
+        .cloned()
+        .ok_or_else(|| {
+            Error::invalid_params(format!("Account with address `{}` not found.", address))
+        })?;
+    let signature = account
+        .sign_hash(&hash)
+        .map_err(|err| fail!("Couldn't sign typed data", err))?;
+
+    Ok(transform::hex_prefix(&transform::bytes_to_hex_str(
+        &signature,
+    )))
+}
+
 pub fn accounts<T>(_params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
 where
     T: MessageSender,
@@ -183,10 +419,17 @@ where
     let accounts = client.loaded_accounts();
     let loaded_accounts = accounts.read().unwrap();
 
-    Ok(Value::Array(
-        loaded_accounts
+    let addresses = loaded_accounts.iter().map(Account::address);
+    #[cfg(feature = "ledger")]
+    let addresses = addresses.chain(
+        client
+            .ledger
             .iter()
-            .map(|account| transform::hex_prefix(account.address()))
-            .collect(),
+            .flat_map(|ledger| ledger.accounts())
+            .map(LedgerAccount::address),
+    );
+
+    Ok(Value::Array(
+        addresses.map(transform::address_value).collect(),
     ))
 }
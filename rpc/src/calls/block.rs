@@ -15,17 +15,25 @@
  * ------------------------------------------------------------------------------
  */
 
-use client::{BlockKey, Error as ClientError, ValidatorClient};
+use bloom::Bloom;
+#[cfg(feature = "debug")]
+use calls::debug;
+use client::{self, BlockKey, Error as ClientError, ValidatorClient, SETH_NS};
 use jsonrpc_core::{Error, Params, Value};
+use messages::seth::{EvmEntry, EvmStorageEntry};
 use protobuf;
 use requests::RequestHandler;
+use response::ResponseBudget;
 use sawtooth_sdk::messages::block::BlockHeader;
+use sawtooth_sdk::messages::transaction_receipt::StateChange_Type;
 use sawtooth_sdk::messaging::stream::*;
 use serde_json::Map;
+use std::collections::BTreeMap;
 use std::str::FromStr;
-use transactions::TransactionKey;
+use transactions::{SethReceipt, Transaction};
 use transform;
-use transform::make_txn_obj_no_block;
+use transform::{make_block_txn, zerobytes};
+use types::{self, BlockTransactions, Quantity, Wei};
 
 pub fn get_method_list<T>() -> Vec<(String, RequestHandler<T>)>
 where
@@ -43,6 +51,7 @@ where
             "eth_getBlockTransactionCountByNumber".into(),
             get_block_transaction_count_by_number,
         ),
+        ("seth_getBlockArtifacts".into(), get_block_artifacts),
     ]
 }
 
@@ -188,7 +197,7 @@ where
 }
 
 /// Returns information about a block as a `json_rpc::Value` object
-fn get_block_obj<T>(
+pub fn get_block_obj<T>(
     block_key: BlockKey,
     full: bool,
     client: ValidatorClient<T>,
@@ -217,24 +226,6 @@ where
         }
     };
 
-    let mut bob = Map::new();
-    bob.insert(
-        String::from("number"),
-        transform::num_to_hex(&block_header.block_num),
-    );
-    bob.insert(
-        String::from("hash"),
-        transform::hex_prefix(&block.header_signature),
-    );
-    bob.insert(
-        String::from("parentHash"),
-        transform::hex_prefix(&block_header.previous_block_id),
-    );
-    bob.insert(
-        String::from("stateRoot"),
-        transform::hex_prefix(&block_header.state_root_hash),
-    );
-
     let receipts = match client.get_receipts_from_block(&block) {
         Ok(r) => r,
         Err(error) => {
@@ -243,42 +234,257 @@ where
         }
     };
 
-    let mut transactions = Vec::new();
+    let finalized = client
+        .is_finalized(&block.header_signature, block_header.block_num)
+        .map_err(|error| {
+            error!("Error checking finality: {:?}", error);
+            Error::internal_error()
+        })?;
+
+    let base_fee = client
+        .get_base_fee(BlockKey::Signature(block.header_signature.clone()))
+        .map_err(|error| {
+            error!("Error getting base fee: {:?}", error);
+            Error::internal_error()
+        })?;
+
+    let mut hashes = Vec::new();
+    let mut full_transactions = Vec::new();
     let mut gas: u64 = 0;
-    for (txn_id, receipt) in receipts {
+    let logs_bloom = receipts
+        .values()
+        .flat_map(|receipt| receipt.logs.iter())
+        .collect::<Bloom>();
+    client
+        .filters
+        .cache_block_bloom(&block.header_signature, logs_bloom.clone());
+    // The transactions are read from the block itself, in the order they ran
+    for (txn_idx, txn) in client::seth_transactions(&block) {
+        let receipt = match receipts.get(&txn.header_signature) {
+            Some(receipt) => receipt,
+            None => continue,
+        };
         if full {
-            let (txn, _) =
-                match client.get_transaction_and_block(&TransactionKey::Signature(txn_id)) {
-                    Ok(t) => t,
-                    Err(error) => {
-                        error!("Error getting transactions: {:?}", error);
-                        return Err(Error::internal_error());
-                    }
-                };
-            transactions.push(make_txn_obj_no_block(&txn))
+            let txn = Transaction::try_from(txn.clone()).map_err(|error| {
+                error!("Error parsing transaction: {:?}", error);
+                Error::internal_error()
+            })?;
+            full_transactions.push(make_block_txn(
+                &txn,
+                txn_idx,
+                &block.header_signature,
+                block_header.block_num,
+            ));
         } else {
-            transactions.push(transform::hex_prefix(&txn_id));
+            hashes.push(format!("0x{}", txn.header_signature));
         }
         gas += receipt.gas_used;
     }
-    bob.insert(String::from("transactions"), Value::Array(transactions));
-    bob.insert(String::from("gasUsed"), transform::num_to_hex(&gas));
-
-    // No corollaries in Sawtooth
-    bob.insert(String::from("nonce"), transform::zerobytes(8));
-    bob.insert(String::from("sha3Uncles"), transform::zerobytes(32));
-    bob.insert(String::from("logsBloom"), transform::zerobytes(256));
-    bob.insert(String::from("transactionsRoot"), transform::zerobytes(32));
-    bob.insert(String::from("receiptsRoot"), transform::zerobytes(32));
-    bob.insert(String::from("miner"), transform::zerobytes(20));
-    bob.insert(String::from("difficulty"), transform::zerobytes(0));
-    bob.insert(String::from("totalDifficulty"), transform::zerobytes(0));
-    bob.insert(String::from("extraData"), transform::zerobytes(0));
-    bob.insert(String::from("size"), transform::zerobytes(0));
-    bob.insert(String::from("gasLimit"), transform::zerobytes(0));
-    bob.insert(String::from("uncles"), Value::Array(Vec::new()));
-
-    Ok(Value::Object(bob))
+
+    Ok(transform::to_value(&types::Block {
+        number: Quantity(block_header.block_num),
+        hash: format!("0x{}", block.header_signature),
+        parent_hash: format!("0x{}", block_header.previous_block_id),
+        state_root: format!("0x{}", block_header.state_root_hash),
+        gas_used: Quantity(gas),
+        transactions: if full {
+            BlockTransactions::Full(full_transactions)
+        } else {
+            BlockTransactions::Hashes(hashes)
+        },
+        finalized,
+        logs_bloom: logs_bloom.to_hex(),
+
+        // No corollaries in Sawtooth
+        nonce: zerobytes(8),
+        sha3_uncles: zerobytes(32),
+        transactions_root: zerobytes(32),
+        receipts_root: zerobytes(32),
+        miner: zerobytes(20),
+        difficulty: Quantity(0),
+        total_difficulty: Quantity(0),
+        extra_data: zerobytes(0),
+        size: Quantity(0),
+        gas_limit: Quantity(0),
+        uncles: Vec::new(),
+        base_fee_per_gas: Wei::from(base_fee),
+    }))
+}
+
+/// Returns what the transactions of a block left behind, so that the block can be
+/// ingested with one request: the receipts of its seth transactions in block order,
+/// the bloom of their logs, the state they changed and, with the `traces` option,
+/// their traces as `debug_traceTransaction` gives them
+pub fn get_block_artifacts<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_getBlockArtifacts");
+    let usage = "Takes [blockNum: QUANTITY|TAG, options: OBJECT]";
+    let (block_num, options): (String, Option<Map<String, Value>>) = params
+        .clone()
+        .parse()
+        .or_else(|_| params.parse().map(|(b,): (String,)| (b, None)))
+        .map_err(|_| Error::invalid_params(usage))?;
+    let options = options.unwrap_or_default();
+    let block_key = BlockKey::from_str(&block_num)
+        .map_err(|_| Error::invalid_params("Invalid block number"))?;
+
+    let block = match client.get_block(block_key) {
+        Ok(block) => block,
+        Err(ClientError::NoResource) => return Ok(Value::Null),
+        Err(error) => return Err(fail!("Couldn't get block", error)),
+    };
+    let header: BlockHeader = protobuf::parse_from_bytes(&block.header)
+        .map_err(|error| fail!("Error parsing block header", error))?;
+    let receipts = client
+        .get_receipts_from_block(&block)
+        .map_err(|error| fail!("Couldn't get receipts", error))?;
+    let base_fee = client
+        .get_base_fee(BlockKey::Signature(block.header_signature.clone()))
+        .map_err(|error| fail!("Couldn't get base fee", error))?;
+
+    let mut ordered = Vec::with_capacity(receipts.len());
+    let mut receipt_objs = Vec::with_capacity(receipts.len());
+    let mut first_log_idx = 0;
+    let txns = block
+        .get_batches()
+        .iter()
+        .flat_map(|batch| batch.get_transactions().iter());
+    for (txn_idx, txn) in txns.enumerate() {
+        let receipt = match receipts.get(&txn.header_signature) {
+            Some(receipt) => receipt,
+            None => continue,
+        };
+        let txn = Transaction::try_from(txn.clone())
+            .map_err(|error| fail!("Couldn't parse transaction", error))?;
+        receipt_objs.push(transform::make_txn_receipt_obj(
+            receipt,
+            txn_idx as u64,
+            first_log_idx,
+            &block.header_signature,
+            header.block_num,
+            txn.effective_gas_price(base_fee),
+        ));
+        first_log_idx += receipt.logs.len() as u64;
+        ordered.push(receipt);
+    }
+    client.memory.charge_values(&receipt_objs)?;
+    let mut budget = ResponseBudget::new(client.max_response_size);
+    budget.charge(&receipt_objs)?;
+
+    let logs_bloom = ordered
+        .iter()
+        .flat_map(|receipt| receipt.logs.iter())
+        .collect::<Bloom>();
+    let mut artifacts = Map::new();
+    artifacts.insert(
+        String::from("blockHash"),
+        Value::String(format!("0x{}", block.header_signature)),
+    );
+    artifacts.insert(
+        String::from("blockNumber"),
+        transform::num_to_hex(&header.block_num),
+    );
+    artifacts.insert(
+        String::from("logsBloom"),
+        Value::String(logs_bloom.to_hex()),
+    );
+    artifacts.insert(String::from("receipts"), Value::Array(receipt_objs));
+    artifacts.insert(
+        String::from("stateDiff"),
+        Value::Object(state_diff(&ordered)?),
+    );
+    if options
+        .get("traces")
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+    {
+        #[cfg(feature = "debug")]
+        artifacts.insert(
+            String::from("traces"),
+            Value::Array(debug::trace_block(&client, &block, &options, &mut budget)?),
+        );
+        #[cfg(not(feature = "debug"))]
+        return Err(Error::invalid_params(
+            "Traces need seth-rpc to be built with the debug feature",
+        ));
+    }
+    Ok(Value::Object(artifacts))
+}
+
+/// The fields and storage slots of the accounts in a state diff, by address
+type AccountDiffs = BTreeMap<String, (Map<String, Value>, Map<String, Value>)>;
+
+/// Returns what the transactions of the receipts left each seth account they changed
+/// as, by address: the `balance`, `nonce` and `code` it was left with, or whether it
+/// was `deleted`, and the `storage` slots set in it. Storage entries that were
+/// deleted are left out, since which slots they held can't be told from their
+/// address.
+fn state_diff(receipts: &[&SethReceipt]) -> Result<Map<String, Value>, Error> {
+    let mut accounts: AccountDiffs = BTreeMap::new();
+    let changes = receipts
+        .iter()
+        .flat_map(|receipt| receipt.state_changes.iter());
+    for change in changes {
+        let account = match change.address.get(SETH_NS.len()..SETH_NS.len() + 40) {
+            Some(account) if change.address.starts_with(SETH_NS) => account,
+            _ => continue,
+        };
+        let deleted = change.field_type == StateChange_Type::DELETE;
+        let &mut (ref mut fields, ref mut storage) = accounts
+            .entry(transform::checksum_address(account))
+            .or_insert_with(|| (Map::new(), Map::new()));
+
+        if change.address == client::account_state_address(account) {
+            fields.clear();
+            fields.insert(String::from("deleted"), Value::Bool(deleted));
+            if deleted {
+                storage.clear();
+                continue;
+            }
+            let mut entry: EvmEntry = protobuf::parse_from_bytes(&change.value)
+                .map_err(|error| fail!("Error parsing state entry", error))?;
+            let account = entry.take_account();
+            fields.insert(
+                String::from("balance"),
+                transform::num_to_hex(&account.balance),
+            );
+            fields.insert(String::from("nonce"), transform::num_to_hex(&account.nonce));
+            fields.insert(
+                String::from("code"),
+                transform::hex_prefix(&transform::bytes_to_hex_str(&account.code)),
+            );
+            // Contracts deployed before storage was split out keep it in their entry
+            for slot in entry.get_storage() {
+                insert_slot(storage, &slot.key, &slot.value);
+            }
+        } else if !deleted {
+            let entry: EvmStorageEntry = protobuf::parse_from_bytes(&change.value)
+                .map_err(|error| fail!("Error parsing state entry", error))?;
+            for slot in entry.get_storage() {
+                insert_slot(storage, &slot.key, &slot.value);
+            }
+        }
+    }
+
+    Ok(accounts
+        .into_iter()
+        .map(|(address, (mut fields, storage))| {
+            fields.insert(String::from("storage"), Value::Object(storage));
+            (address, Value::Object(fields))
+        })
+        .collect())
+}
+
+/// Adds a storage slot to a state diff by its key padded to 32 bytes
+fn insert_slot(storage: &mut Map<String, Value>, key: &[u8], value: &[u8]) {
+    if key.len() <= 32 {
+        storage.insert(
+            format!("0x{}", transform::bytes_to_hex_str(&client::pad_word(key))),
+            transform::hex_prefix(&transform::bytes_to_hex_str(value)),
+        );
+    }
 }
 
 /// Returns the number of transactions for the given block as a hex string
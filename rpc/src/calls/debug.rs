@@ -0,0 +1,577 @@
+/*
+ * Copyright 2017 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+use calls::account::validate_block_key;
+use calls::transaction;
+use client::{self, BlockKey, Error as ClientError, ValidatorClient};
+use jsonrpc_core::{Error, Params, Value};
+use memory;
+use names;
+use primitive_types::H160;
+use protobuf;
+use requests::RequestHandler;
+use response::ResponseBudget;
+use sawtooth_sdk::messages::block::{Block, BlockHeader};
+use sawtooth_sdk::messaging::stream::MessageSender;
+use serde_json::Map;
+use tracer::TraceOptions;
+use transactions::Transaction;
+use transform;
+use vm::{self, CallError};
+use witness::{self, Witness};
+
+/// The most steps `debug_traceTransaction` records
+const MAX_STRUCT_LOGS: usize = 100_000;
+
+pub fn get_method_list<T>() -> Vec<(String, RequestHandler<T>)>
+where
+    T: MessageSender,
+{
+    vec![
+        ("seth_callWithWitness".into(), call_with_witness),
+        ("seth_replayCall".into(), replay_call),
+        ("seth_getShadowReport".into(), get_shadow_report),
+        ("debug_traceCall".into(), trace_call),
+        ("debug_traceTransaction".into(), trace_transaction),
+    ]
+}
+
+/// Runs a call as `eth_call` does, returning what it returned or failed with along
+/// with the state it read, so that it can be run again with `seth_replayCall`
+pub fn call_with_witness<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_callWithWitness");
+    let usage = "Takes [txn: OBJECT, block: QUANTITY|TAG]";
+    let (txn, block): (Map<String, Value>, Option<String>) = params
+        .clone()
+        .parse()
+        .or_else(|_| params.parse().map(|(t,): (Map<String, Value>,)| (t, None)))
+        .map_err(|_| Error::invalid_params(usage))?;
+
+    let block_key = match block {
+        Some(block) => validate_block_key(&block)?,
+        None => BlockKey::Latest,
+    };
+    let block = client.get_block(block_key).map_err(|error| {
+        error!("Failed to get block: {}", error);
+        Error::internal_error()
+    })?;
+
+    let txn = names::resolve_fields(&client, &txn, &["from", "to"])?;
+    let call = transaction::parse_call(&txn, client.gas_cap)?;
+    if call.to.is_none() {
+        return Err(Error::invalid_params("`to` not set"));
+    }
+    let (executed, witness) =
+        run_call_with_witness(call, &block, &client).map_err(transaction::call_error)?;
+    // The witness of a call that couldn't read its state isn't what it should read
+    if let Err(CallError::State(error)) = executed {
+        return Err(transaction::call_error(CallError::State(error)));
+    }
+
+    let mut outcome = call_outcome(executed);
+    outcome.insert(String::from("witness"), witness.to_value());
+    Ok(Value::Object(outcome))
+}
+
+/// Runs a call against the state recorded by `seth_callWithWitness`, without
+/// reading any from the validator
+pub fn replay_call<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_replayCall");
+    let (txn, witness): (Map<String, Value>, Value) = params
+        .parse()
+        .map_err(|_| Error::invalid_params("Takes [txn: OBJECT, witness: OBJECT]"))?;
+    let witness = Witness::from_value(&witness).map_err(Error::invalid_params)?;
+
+    let txn = names::resolve_fields(&client, &txn, &["from", "to"])?;
+    let call = transaction::parse_call(&txn, client.gas_cap)?;
+    if call.to.is_none() {
+        return Err(Error::invalid_params("`to` not set"));
+    }
+    match witness::replay(&witness, call) {
+        Err(CallError::State(error)) => Err(Error::invalid_params(error)),
+        executed => Ok(Value::Object(call_outcome(executed))),
+    }
+}
+
+/// Runs a call as `eth_call` does and traces it as `debug_traceTransaction` does, or
+/// with the `prestateTracer` returns the accounts, storage and code it read as they
+/// were before it
+pub fn trace_call<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("debug_traceCall");
+    let usage = "Takes [txn: OBJECT, block: QUANTITY|TAG, options: OBJECT]";
+    let (txn, block, options): (Map<String, Value>, String, Option<Map<String, Value>>) = params
+        .clone()
+        .parse()
+        .or_else(|_| {
+            params
+                .parse()
+                .map(|(t, b): (Map<String, Value>, String)| (t, b, None))
+        })
+        .map_err(|_| Error::invalid_params(usage))?;
+    let options = options.unwrap_or_default();
+
+    let block = client
+        .get_block(validate_block_key(&block)?)
+        .map_err(|error| {
+            error!("Failed to get block: {}", error);
+            Error::internal_error()
+        })?;
+    let txn = names::resolve_fields(&client, &txn, &["from", "to"])?;
+    let call = transaction::parse_call(&txn, client.gas_cap)?;
+
+    match options.get("tracer").and_then(Value::as_str) {
+        Some("prestateTracer") => {
+            let diff_mode = options
+                .get("tracerConfig")
+                .and_then(|config| config.get("diffMode"))
+                .and_then(Value::as_bool);
+            if diff_mode == Some(true) {
+                return Err(Error::invalid_params(
+                    "The diffMode of the prestateTracer isn't supported",
+                ));
+            }
+            let (executed, witness) =
+                run_call_with_witness(call, &block, &client).map_err(transaction::call_error)?;
+            // A call that couldn't read its state didn't read all it would have
+            if let Err(CallError::State(error)) = executed {
+                return Err(transaction::call_error(CallError::State(error)));
+            }
+            let prestate = witness.to_prestate();
+            client.memory.charge(memory::value_size(&prestate))?;
+            ResponseBudget::new(client.max_response_size).charge(&[&prestate])?;
+            Ok(prestate)
+        }
+        _ => {
+            let options = trace_options(&options)?;
+            let (state_root, block_num) =
+                transaction::block_state(&block).map_err(transaction::call_error)?;
+            let (executed, logs) = vm::trace(
+                &client,
+                &state_root,
+                block_num,
+                transaction::call_chain_id(&client, &state_root)
+                    .map_err(transaction::call_error)?,
+                Vec::new(),
+                call,
+                options,
+            )
+            .map_err(transaction::call_error)?;
+            client.memory.check()?;
+
+            let struct_logs: Vec<Value> = logs.iter().map(|log| log.to_value()).collect();
+            client.memory.charge_values(&struct_logs)?;
+            ResponseBudget::new(client.max_response_size).charge(&struct_logs)?;
+            Ok(Value::Object(trace_obj(executed, struct_logs)))
+        }
+    }
+}
+
+/// Runs a committed transaction again against the state before its block, after the
+/// transactions before it in the block, and returns each step it took in the EVM
+/// as geth's struct logger does
+pub fn trace_transaction<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("debug_traceTransaction");
+    let usage = "Takes [txnHash: DATA, options: OBJECT]";
+    let (txn_hash, options): (String, Option<Map<String, Value>>) = params
+        .clone()
+        .parse()
+        .or_else(|_| params.parse().map(|(h,): (String,)| (h, None)))
+        .map_err(|_| Error::invalid_params(usage))?;
+    let txn_hash = txn_hash
+        .get(2..)
+        .ok_or_else(|| Error::invalid_params("Invalid transaction hash, must have 0x"))?;
+    let options = trace_options(&options.unwrap_or_default())?;
+    let txn_id = client.raw_transactions.txn_id(txn_hash);
+
+    let block = match client.get_block(BlockKey::Transaction(txn_id.clone())) {
+        Ok(block) => block,
+        Err(ClientError::NoResource) => {
+            return Err(Error::invalid_params("Transaction isn't in a block"))
+        }
+        Err(error) => return Err(fail!("Couldn't get block", error)),
+    };
+    let header: BlockHeader = protobuf::parse_from_bytes(&block.header)
+        .map_err(|err| fail!("Error parsing block header", err))?;
+    if header.block_num == 0 {
+        return Err(Error::invalid_params(
+            "Transactions of the genesis block can't be traced",
+        ));
+    }
+    let parent = client
+        .get_block(BlockKey::Signature(header.previous_block_id.clone()))
+        .map_err(|err| fail!("Couldn't get block", err))?;
+    let state_root =
+        client::state_root(&parent).map_err(|err| fail!("Error parsing block header", err))?;
+    let chain_id = client
+        .get_chain_id(BlockKey::Signature(parent.header_signature.clone()))
+        .map_err(|err| fail!("Couldn't get chain id", err))?;
+
+    let mut earlier = Vec::new();
+    let mut traced = None;
+    let txns = block
+        .get_batches()
+        .iter()
+        .flat_map(|batch| batch.get_transactions().iter());
+    for txn in txns {
+        let is_traced = txn.header_signature == txn_id;
+        // Transactions of other families don't change seth's state
+        let txn = match Transaction::try_from(txn.clone()) {
+            Ok(txn) => txn,
+            Err(_) if is_traced => {
+                return Err(Error::invalid_params("Not a seth transaction"));
+            }
+            Err(_) => continue,
+        };
+        if is_traced {
+            traced = Some(
+                txn.evm_call()
+                    .map_err(Error::invalid_params)?
+                    .ok_or_else(|| Error::invalid_params("Transaction doesn't run in the EVM"))?,
+            );
+            break;
+        }
+        let from = transform::hex_str_to_bytes(&txn.from_addr())
+            .filter(|from| from.len() == 20)
+            .map(|from| H160::from_slice(&from));
+        if let Some(from) = from {
+            // The input of private transactions isn't on chain, so they only count
+            // against the sender's nonce
+            earlier.push((from, txn.evm_call().unwrap_or(None)));
+        }
+    }
+    let call = traced.ok_or_else(|| fail!("Transaction is missing from its block"))?;
+
+    let (executed, logs) = vm::trace(
+        &client,
+        &state_root,
+        header.block_num,
+        chain_id,
+        earlier,
+        call,
+        options,
+    )
+    .map_err(transaction::call_error)?;
+    client.memory.check()?;
+
+    let struct_logs: Vec<Value> = logs.iter().map(|log| log.to_value()).collect();
+    client.memory.charge_values(&struct_logs)?;
+    ResponseBudget::new(client.max_response_size).charge(&struct_logs)?;
+    Ok(Value::Object(trace_obj(executed, struct_logs)))
+}
+
+/// Traces the seth transactions of a block as `debug_traceTransaction` does, running
+/// them one after another against the state before the block, with the hash of each
+/// transaction as its `txHash`. The traces are charged to `budget`.
+pub fn trace_block<T>(
+    client: &ValidatorClient<T>,
+    block: &Block,
+    options: &Map<String, Value>,
+    budget: &mut ResponseBudget,
+) -> Result<Vec<Value>, Error>
+where
+    T: MessageSender,
+{
+    let options = trace_options(options)?;
+    let header: BlockHeader = protobuf::parse_from_bytes(&block.header)
+        .map_err(|err| fail!("Error parsing block header", err))?;
+    if header.block_num == 0 {
+        return Err(Error::invalid_params(
+            "Transactions of the genesis block can't be traced",
+        ));
+    }
+    let parent = client
+        .get_block(BlockKey::Signature(header.previous_block_id.clone()))
+        .map_err(|err| fail!("Couldn't get block", err))?;
+    let state_root =
+        client::state_root(&parent).map_err(|err| fail!("Error parsing block header", err))?;
+    let chain_id = client
+        .get_chain_id(BlockKey::Signature(parent.header_signature.clone()))
+        .map_err(|err| fail!("Couldn't get chain id", err))?;
+
+    let mut txn_ids = Vec::new();
+    let mut txns = Vec::new();
+    let all_txns = block
+        .get_batches()
+        .iter()
+        .flat_map(|batch| batch.get_transactions().iter());
+    for txn in all_txns {
+        // Transactions of other families don't change seth's state
+        let txn_id = txn.header_signature.clone();
+        let txn = match Transaction::try_from(txn.clone()) {
+            Ok(txn) => txn,
+            Err(_) => continue,
+        };
+        let from = transform::hex_str_to_bytes(&txn.from_addr())
+            .filter(|from| from.len() == 20)
+            .map(|from| H160::from_slice(&from));
+        if let Some(from) = from {
+            txn_ids.push(txn_id);
+            txns.push((from, txn.evm_call().unwrap_or(None)));
+        }
+    }
+
+    let traced = vm::trace_block(
+        client,
+        &state_root,
+        header.block_num,
+        chain_id,
+        txns,
+        options,
+    )
+    .map_err(transaction::call_error)?;
+    client.memory.check()?;
+
+    let mut traces = Vec::new();
+    for (txn_id, traced) in txn_ids.into_iter().zip(traced) {
+        // Private transactions and those that only set up accounts aren't traced
+        let ((executed, _), logs) = match traced {
+            Some(traced) => traced,
+            None => continue,
+        };
+        let struct_logs: Vec<Value> = logs.iter().map(|log| log.to_value()).collect();
+        client.memory.charge_values(&struct_logs)?;
+        budget.charge(&struct_logs)?;
+        let mut trace = trace_obj(executed, struct_logs);
+        trace.insert(
+            String::from("txHash"),
+            Value::String(format!("0x{}", txn_id)),
+        );
+        traces.push(Value::Object(trace));
+    }
+    Ok(traces)
+}
+
+/// Reports how running committed blocks again with the fork of `--shadow-fork` has
+/// gone, with the latest transactions that went another way than they were
+/// committed
+pub fn get_shadow_report<T>(_params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_getShadowReport");
+    let shadow = client
+        .shadow
+        .as_ref()
+        .ok_or_else(|| fail!("Shadow execution needs seth-rpc to be started with --shadow-fork"))?;
+    Ok(transform::to_value(&shadow.report()))
+}
+
+/// Reads the options of geth's struct logger. JavaScript and the other built-in
+/// tracers aren't supported.
+fn trace_options(options: &Map<String, Value>) -> Result<TraceOptions, Error> {
+    if options.contains_key("tracer") {
+        return Err(Error::invalid_params(
+            "Only the struct logger, and for debug_traceCall the prestateTracer, are supported",
+        ));
+    }
+    let flag = |name: &str| options.get(name).and_then(Value::as_bool);
+    let limit = match options.get("limit").and_then(Value::as_u64) {
+        Some(limit) if limit > 0 => (limit as usize).min(MAX_STRUCT_LOGS),
+        _ => MAX_STRUCT_LOGS,
+    };
+    Ok(TraceOptions {
+        disable_stack: flag("disableStack").unwrap_or(false),
+        disable_memory: flag("disableMemory")
+            .or_else(|| flag("enableMemory").map(|enable| !enable))
+            .unwrap_or(false),
+        disable_storage: flag("disableStorage").unwrap_or(false),
+        limit,
+    })
+}
+
+/// Describes a traced call as geth's struct logger does: the `gas` it used, whether
+/// it `failed`, its `returnValue` and the `structLogs` of its steps
+fn trace_obj(
+    executed: Result<vm::Executed, CallError>,
+    struct_logs: Vec<Value>,
+) -> Map<String, Value> {
+    let (gas_used, failed, output) = match executed {
+        Ok(executed) => (executed.gas_used, false, executed.output),
+        Err(CallError::Reverted(output)) => (0, true, output),
+        Err(_) => (0, true, Vec::new()),
+    };
+    let mut trace = Map::new();
+    trace.insert(String::from("gas"), Value::from(gas_used));
+    trace.insert(String::from("failed"), Value::Bool(failed));
+    trace.insert(
+        String::from("returnValue"),
+        Value::String(transform::bytes_to_hex_str(&output)),
+    );
+    trace.insert(String::from("structLogs"), Value::Array(struct_logs));
+    trace
+}
+
+/// Describes how a call went: its `output`, the `gasUsed` if it succeeded and the
+/// `error` if it didn't, with the data a reverted call reverted with as its output
+fn call_outcome(executed: Result<vm::Executed, CallError>) -> Map<String, Value> {
+    let (output, gas_used, error) = match executed {
+        Ok(executed) => (
+            executed.output,
+            transform::num_to_hex(&executed.gas_used),
+            Value::Null,
+        ),
+        Err(CallError::Reverted(output)) => {
+            (output, Value::Null, Value::from("execution reverted"))
+        }
+        Err(CallError::OutOfGas) => (Vec::new(), Value::Null, Value::from("Call ran out of gas")),
+        Err(CallError::Failed(reason)) | Err(CallError::State(reason)) => (
+            Vec::new(),
+            Value::Null,
+            Value::from(format!("Call failed: {}", reason)),
+        ),
+    };
+    let mut outcome = Map::new();
+    outcome.insert(
+        String::from("output"),
+        transform::hex_prefix(&transform::bytes_to_hex_str(&output)),
+    );
+    outcome.insert(String::from("gasUsed"), gas_used);
+    outcome.insert(String::from("error"), error);
+    outcome
+}
+
+/// Runs a call against a block as `eth_call` does, also returning the state it read
+fn run_call_with_witness<T>(
+    call: vm::Call,
+    block: &Block,
+    client: &ValidatorClient<T>,
+) -> Result<(Result<vm::Executed, CallError>, Witness), CallError>
+where
+    T: MessageSender,
+{
+    let (state_root, block_num) = transaction::block_state(block)?;
+    Ok(vm::call_with_witness(
+        client,
+        &state_root,
+        block_num,
+        transaction::call_chain_id(client, &state_root)?,
+        call,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use accounts;
+    use contracts::ContractRegistry;
+    use filters::FilterManager;
+    use mock_chain::MockChain;
+    use serde_json;
+
+    fn client() -> ValidatorClient<MockChain> {
+        ValidatorClient::new(
+            MockChain::new(),
+            Vec::new(),
+            FilterManager::new(1),
+            0,
+            String::from("1.0"),
+            ContractRegistry::new(),
+        )
+    }
+
+    fn params(json: &str) -> Params {
+        serde_json::from_str(json).unwrap()
+    }
+
+    /// The address of the counter alice deploys on the mock chain
+    fn counter() -> String {
+        let alice = MockChain::accounts()[0].address().to_string();
+        accounts::contract_address(&transform::hex_str_to_bytes(&alice).unwrap(), 1)
+    }
+
+    #[test]
+    fn reads_the_options_of_the_struct_logger() {
+        let options = |json| trace_options(&serde_json::from_str(json).unwrap());
+
+        let defaults = options("{}").unwrap();
+        assert!(!defaults.disable_stack && !defaults.disable_memory && !defaults.disable_storage);
+        assert_eq!(defaults.limit, MAX_STRUCT_LOGS);
+
+        let set = options(r#"{"disableStack": true, "enableMemory": false, "limit": 10}"#).unwrap();
+        assert!(set.disable_stack && set.disable_memory && !set.disable_storage);
+        assert_eq!(set.limit, 10);
+        assert_eq!(
+            options(r#"{"limit": 1000000000}"#).unwrap().limit,
+            MAX_STRUCT_LOGS
+        );
+        assert!(options(r#"{"tracer": "callTracer"}"#).is_err());
+    }
+
+    #[test]
+    fn replays_calls_against_the_state_they_read() {
+        let client = client();
+        let increment = format!(r#"{{"to": "0x{}"}}"#, counter());
+        let called =
+            call_with_witness(params(&format!("[{}]", increment)), client.clone()).unwrap();
+        assert_eq!(called["output"], Value::from(format!("0x{:064x}", 43)));
+        assert_eq!(called["error"], Value::Null);
+
+        let replay = |txn: &str| {
+            replay_call(
+                params(&format!("[{}, {}]", txn, called["witness"])),
+                client.clone(),
+            )
+            .unwrap()
+        };
+        let replayed = replay(&increment);
+        assert_eq!(replayed["output"], called["output"]);
+        assert_eq!(replayed["gasUsed"], called["gasUsed"]);
+        // The counter reverts when it's called with data
+        let reverted = replay(&format!(r#"{{"to": "0x{}", "data": "0x01"}}"#, counter()));
+        assert_eq!(reverted["error"], Value::from("execution reverted"));
+        assert_eq!(reverted["gasUsed"], Value::Null);
+    }
+
+    #[test]
+    fn traces_committed_transactions() {
+        let client = client();
+        let block = client.get_block(BlockKey::Number(3)).unwrap();
+        let txn_ids: Vec<String> = block
+            .get_batches()
+            .iter()
+            .flat_map(|batch| batch.get_transactions().iter())
+            .map(|txn| txn.header_signature.clone())
+            .collect();
+        let trace = |txn_id: &str| {
+            trace_transaction(params(&format!(r#"["0x{}"]"#, txn_id)), client.clone())
+        };
+
+        let increment = trace(&txn_ids[0]).unwrap();
+        assert_eq!(increment["failed"], Value::Bool(false));
+        assert_eq!(
+            increment["returnValue"],
+            Value::from(format!("{:064x}", 42))
+        );
+        assert!(!increment["structLogs"].as_array().unwrap().is_empty());
+        // The second calls the counter with data, which it reverts on
+        let reverted = trace(&txn_ids[1]).unwrap();
+        assert_eq!(reverted["failed"], Value::Bool(true));
+        assert!(trace(&"0".repeat(128)).is_err());
+    }
+}
@@ -15,12 +15,174 @@
  * ------------------------------------------------------------------------------
  */
 
-use jsonrpc_core::{Error, ErrorCode};
+use abi;
+use client::Error as ClientError;
+use jsonrpc_core::{Error, ErrorCode, Value};
+use serde_json::Map;
+use transform;
+use types;
 
-pub fn not_implemented() -> Error {
+/// The `data` of an error, which holds its code in the seth error catalogue
+fn error_data(code: types::ErrorCode) -> Map<String, Value> {
+    let mut data = Map::new();
+    data.insert(String::from("code"), Value::from(code.code()));
+    data
+}
+
+pub fn too_many_filters() -> Error {
+    Error {
+        code: ErrorCode::ServerError(-32005),
+        message: String::from("Filter limit reached, uninstall unused filters"),
+        data: Some(Value::Object(error_data(
+            types::ErrorCode::FilterLimitReached,
+        ))),
+    }
+}
+
+/// Returned when the filter store couldn't be read or written, rather than answering
+/// as if the filter didn't exist
+pub fn filter_store_failed(error: String) -> Error {
+    error!("Filter store error: {}", error);
+    Error::internal_error()
+}
+
+/// Returned instead of subscribing a WebSocket connection that already has as many
+/// subscriptions as one may have, with the limit in `data`
+#[cfg(feature = "ws")]
+pub fn too_many_subscriptions(limit: usize) -> Error {
+    let mut data = error_data(types::ErrorCode::FilterLimitReached);
+    data.insert(String::from("limit"), transform::num_to_hex(&limit));
+    Error {
+        code: ErrorCode::ServerError(-32005),
+        message: String::from("Subscription limit reached, unsubscribe unused subscriptions"),
+        data: Some(Value::Object(data)),
+    }
+}
+
+/// Returned instead of serving a request when too many requests are pending, the
+/// equivalent of HTTP 429
+pub fn overloaded() -> Error {
+    Error {
+        code: ErrorCode::ServerError(-32005),
+        message: String::from("Server is overloaded, try again later"),
+        data: Some(Value::Object(error_data(types::ErrorCode::Overloaded))),
+    }
+}
+
+/// Returned instead of a response larger than the server sends, with the limit in
+/// `data`
+pub fn response_too_large(limit: usize) -> Error {
+    let mut data = error_data(types::ErrorCode::ResponseTooLarge);
+    data.insert(String::from("limit"), transform::num_to_hex(&limit));
     Error {
-        code: ErrorCode::ServerError(-1),
-        message: String::from("Method not implemented"),
-        data: None,
+        code: ErrorCode::ServerError(-32005),
+        message: String::from("Response too large, narrow your query"),
+        data: Some(Value::Object(data)),
+    }
+}
+
+/// Returned instead of serving a batch with more requests than the server serves at
+/// once, with the limit in `data`
+pub fn batch_too_large(limit: usize) -> Error {
+    let mut data = error_data(types::ErrorCode::BatchTooLarge);
+    data.insert(String::from("limit"), transform::num_to_hex(&limit));
+    Error {
+        code: ErrorCode::ServerError(-32005),
+        message: String::from("Batch too large, split it up"),
+        data: Some(Value::Object(data)),
+    }
+}
+
+/// Returned instead of serving a method that only authenticated clients may call to
+/// a client that isn't
+pub fn unauthorized() -> Error {
+    Error {
+        code: ErrorCode::ServerError(-32001),
+        message: String::from("Unauthorized, send a valid Authorization header"),
+        data: Some(Value::Object(error_data(types::ErrorCode::Unauthorized))),
+    }
+}
+
+/// Returned instead of finishing a request that needed more memory than one request
+/// may use, with the limit in `data`
+pub fn memory_limit_exceeded(limit: usize) -> Error {
+    let mut data = error_data(types::ErrorCode::MemoryLimitExceeded);
+    data.insert(String::from("limit"), transform::num_to_hex(&limit));
+    Error {
+        code: ErrorCode::ServerError(-32005),
+        message: String::from("Request needed too much memory, narrow your query"),
+        data: Some(Value::Object(data)),
+    }
+}
+
+/// Returned instead of serving a request from a client that is over its rate limit,
+/// with the seconds until it may send another in `data`
+pub fn rate_limited(retry_after: u64) -> Error {
+    let mut data = error_data(types::ErrorCode::RateLimited);
+    data.insert(String::from("retryAfter"), Value::from(retry_after));
+    Error {
+        code: ErrorCode::ServerError(-32005),
+        message: String::from("Rate limit reached, slow down"),
+        data: Some(Value::Object(data)),
+    }
+}
+
+/// Returned while requests to the validator are failing fast because it stopped
+/// answering, so that outages can be told apart from internal errors
+pub fn validator_unavailable() -> Error {
+    Error {
+        code: ErrorCode::ServerError(-32006),
+        message: String::from("Validator is unavailable, try again later"),
+        data: Some(Value::Object(error_data(
+            types::ErrorCode::ValidatorUnavailable,
+        ))),
+    }
+}
+
+/// Returned when a call reverts, with the data it reverted with in `data`. The
+/// reason of a `revert("reason")` or a failed `require` is added to the message, as
+/// other clients do.
+pub fn execution_reverted(output: &[u8]) -> Error {
+    Error {
+        code: ErrorCode::ServerError(3),
+        message: match abi::revert_reason(output) {
+            Some(reason) => format!("execution reverted: {}", reason),
+            None => String::from("execution reverted"),
+        },
+        data: Some(transform::hex_prefix(&transform::bytes_to_hex_str(output))),
+    }
+}
+
+/// Converts an error from submitting a transaction into a JSON-RPC error. If the
+/// transaction processor rejected the transaction, its reason and code are included
+/// in `data`.
+pub fn transaction_error(error: ClientError) -> Error {
+    match error {
+        ClientError::TransactionRejected(txn_id, message, code) => {
+            let mut data = error_data(code);
+            data.insert(
+                String::from("transactionHash"),
+                transform::hex_prefix(&txn_id),
+            );
+            data.insert(String::from("reason"), Value::String(message));
+            Error {
+                code: ErrorCode::ServerError(-32003),
+                message: String::from("Transaction rejected"),
+                data: Some(Value::Object(data)),
+            }
+        }
+        ClientError::TransactionDenied(reason) => {
+            let mut data = error_data(types::ErrorCode::TransactionDenied);
+            data.insert(String::from("reason"), Value::String(reason));
+            Error {
+                code: ErrorCode::ServerError(-32007),
+                message: String::from("Transaction denied"),
+                data: Some(Value::Object(data)),
+            }
+        }
+        error => {
+            error!("{:?}", error);
+            Error::internal_error()
+        }
     }
 }
@@ -19,16 +19,20 @@ use client::{BlockKey, Error as ClientError, ValidatorClient};
 use error;
 use filters::*;
 use jsonrpc_core::{Error, ErrorCode, Params, Value};
-use protobuf;
+#[cfg(feature = "indexer")]
+use log_index::IndexedLog;
+use memory;
+use protobuf::{self, Message};
 use requests::RequestHandler;
+use response::ResponseBudget;
 use sawtooth_sdk::messages::block::{Block, BlockHeader};
 use sawtooth_sdk::messages::transaction::TransactionHeader;
 use sawtooth_sdk::messaging::stream::MessageSender;
 use serde_json::Map;
-use std::collections::HashMap;
-use transactions::SethLog;
+use std::{cmp, slice};
+use transactions::Transaction;
 use transform;
-use transform::make_log_obj;
+use transform::{make_log_obj, make_permission_change_obj};
 
 pub fn get_method_list<T>() -> Vec<(String, RequestHandler<T>)>
 where
@@ -45,9 +49,25 @@ where
         ("eth_getFilterChanges".into(), get_filter_changes),
         ("eth_getFilterLogs".into(), get_filter_logs),
         ("eth_getLogs".into(), get_logs),
+        ("seth_getLogsPage".into(), get_logs_page),
     ]
 }
 
+/// The most logs `seth_getLogsPage` returns at once
+const MAX_PAGE_LOGS: u64 = 10_000;
+
+/// How many blocks are read from the log index at a time while filling a page
+#[cfg(feature = "indexer")]
+const PAGE_INDEX_BLOCKS: u64 = 1000;
+
+/// A log that passed a filter, and where it is in the chain
+struct FoundLog {
+    block_num: u64,
+    log_idx: u64,
+    block_id: String,
+    log: Value,
+}
+
 pub fn new_filter<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
 where
     T: MessageSender,
@@ -65,7 +85,9 @@ where
 
     let filter_id = client
         .filters
-        .new_filter(Filter::Log(log_filter), current_block);
+        .new_filter(Filter::Log(log_filter), current_block)
+        .map_err(error::filter_store_failed)?
+        .ok_or_else(error::too_many_filters)?;
 
     Ok(transform::hex_prefix(&filter_id_to_hex(filter_id)))
 }
@@ -79,7 +101,11 @@ where
         error!("Failed to get current block number: {}", error);
         Error::internal_error()
     })?;
-    let filter_id = client.filters.new_filter(Filter::Block, current_block);
+    let filter_id = client
+        .filters
+        .new_filter(Filter::Block, current_block)
+        .map_err(error::filter_store_failed)?
+        .ok_or_else(error::too_many_filters)?;
     Ok(transform::hex_prefix(&filter_id_to_hex(filter_id)))
 }
 
@@ -97,7 +123,9 @@ where
     })?;
     let filter_id = client
         .filters
-        .new_filter(Filter::Transaction, current_block);
+        .new_filter(Filter::Transaction, current_block)
+        .map_err(error::filter_store_failed)?
+        .ok_or_else(error::too_many_filters)?;
     Ok(transform::hex_prefix(&filter_id_to_hex(filter_id)))
 }
 
@@ -116,7 +144,11 @@ where
         })?;
 
     Ok(Value::Bool(
-        client.filters.remove_filter(filter_id).is_some(),
+        client
+            .filters
+            .remove_filter(filter_id)
+            .map_err(error::filter_store_failed)?
+            .is_some(),
     ))
 }
 
@@ -141,13 +173,23 @@ where
     } = client
         .filters
         .get_filter(filter_id)
+        .map_err(error::filter_store_failed)?
         .ok_or_else(|| Error::invalid_params(format!("Unknown filter id: {}", filter_id)))?;
 
     let blocks = client.get_blocks_since(last_block_sent).map_err(|error| {
         error!("Failed to get blocks: {}", error);
         Error::internal_error()
     })?;
+    client.memory.charge(
+        blocks
+            .iter()
+            .map(|(_, block)| block.compute_size() as usize)
+            .sum(),
+    )?;
 
+    // Changes too large to send at once are sent over several polls, up to the last
+    // block that fits
+    let mut last_block = blocks.last().map(|&(block_num, _)| block_num);
     let response = match filter {
         Filter::Block => blocks
             .iter()
@@ -155,38 +197,41 @@ where
             .collect(),
         Filter::Transaction => blocks
             .iter()
-            .flat_map(|&(_, ref block)| {
-                block.get_batches().into_iter().flat_map(|batch| {
-                    batch
-                        .get_transactions()
-                        .into_iter()
-                        .filter(|txn| {
-                            let header: Result<TransactionHeader, _> =
-                                protobuf::parse_from_bytes(&txn.header);
-                            if let Ok(header) = header {
-                                header.family_name == "seth"
-                            } else {
-                                false
-                            }
-                        })
-                        .map(|txn| transform::hex_prefix(&txn.header_signature))
-                })
-            })
+            .flat_map(|&(_, ref block)| seth_transaction_hashes(block))
             .collect(),
         Filter::Log(log_filter) => {
+            let mut budget = ResponseBudget::new(client.max_response_size);
             let mut all_logs = Vec::new();
-            for &(_, ref block) in &blocks {
+            for (index, &(block_num, ref block)) in blocks.iter().enumerate() {
                 let logs = get_logs_from_block_and_filter(&client, block, &log_filter)?;
+                client.memory.charge_values(&logs)?;
+                if let Err(err) = budget.charge(&logs) {
+                    if index == 0 {
+                        return Err(err);
+                    }
+                    last_block = Some(block_num - 1);
+                    break;
+                }
                 all_logs.extend(logs.into_iter());
             }
             all_logs
         }
+        Filter::Permissions => {
+            let mut all_changes = Vec::new();
+            for &(block_num, ref block) in &blocks {
+                all_changes.extend(get_permission_changes_from_block(block, block_num));
+            }
+            all_changes
+        }
     };
 
     // NOTE: Updating is delayed until there are no more error sources that could cause an early
     // return after upadting the filter
-    if let Some(&(block_num, _)) = blocks.last() {
-        client.filters.update_latest_block(filter_id, block_num);
+    if let Some(block_num) = last_block {
+        client
+            .filters
+            .update_latest_block(filter_id, block_num)
+            .map_err(error::filter_store_failed)?;
     }
 
     Ok(Value::Array(response))
@@ -209,6 +254,7 @@ where
     let FilterEntry { filter, .. } = client
         .filters
         .get_filter(filter_id)
+        .map_err(error::filter_store_failed)?
         .ok_or_else(|| Error::invalid_params(format!("Unknown filter id: {}", filter_id)))?;
 
     if let Filter::Log(log_filter) = filter {
@@ -241,128 +287,629 @@ fn get_logs_from_filter<T>(
 where
     T: MessageSender,
 {
-    let interval = (log_filter.from_block, log_filter.to_block);
+    if let Some(ref block_hash) = log_filter.block_hash {
+        let block = get_filter_block(client, block_hash)?;
+        let logs = get_logs_from_block_and_filter(client, &block, log_filter)?;
+        return Ok(Value::Array(logs));
+    }
 
-    match interval {
-        // Request for logs in the latest block
-        (None, None) => {
-            let block = client.get_current_block().map_err(|e| {
-                error!("Unable to get current block: {:?}", e);
-                Error::internal_error()
-            })?;
+    let (from, to) = get_filter_range(client, log_filter)?;
 
-            let logs = get_logs_from_block_and_filter(client, &block, &log_filter)?;
-            Ok(Value::Array(logs))
+    let mut budget = ResponseBudget::new(client.max_response_size);
+    // The blocks the index has are read from it, and only later ones from the
+    // validator
+    #[cfg(feature = "indexer")]
+    let (from, mut all_logs) = get_indexed_logs(client, log_filter, from, to, &mut budget)?;
+    #[cfg(not(feature = "indexer"))]
+    let mut all_logs = Vec::new();
+
+    for block in client.get_blocks_range(from, to) {
+        match block {
+            Ok(block) => {
+                let logs = get_logs_from_block_and_filter(client, &block, log_filter)?;
+                client.memory.charge_values(&logs)?;
+                budget.charge(&logs)?;
+                all_logs.extend(logs);
+            }
+            Err(ClientError::NoResource) => {
+                // If we get a no resource, just send what was found
+                return Ok(Value::Array(all_logs));
+            }
+            Err(error) => {
+                error!("{}", error);
+                return Err(Error::internal_error());
+            }
         }
-        // Request for logs from a block to the latest block
-        (Some(from), None) => {
-            let mut block_index = from;
-            let mut all_logs = Vec::new();
-            loop {
-                match client.get_block(BlockKey::Number(block_index)) {
-                    Ok(block) => {
-                        let logs = get_logs_from_block_and_filter(client, &block, &log_filter)?;
-                        all_logs.extend(logs.into_iter());
-                    }
-                    Err(ClientError::NoResource) => {
-                        // Stop getting blocks when there are no more left
-                        return Ok(Value::Array(all_logs));
-                    }
-                    Err(error) => {
-                        error!("{}", error);
-                        return Err(Error::internal_error());
-                    }
+    }
+    Ok(Value::Array(all_logs))
+}
+
+/// Returns a page of up to `limit` of the logs that pass the filter, in the order
+/// `eth_getLogs` returns them, along with the cursor to pass for the next page, which
+/// is null once the last log of the range has been returned. A page ends early if
+/// the next log would make the response too large.
+pub fn get_logs_page<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_getLogsPage");
+
+    let usage = "Takes [filter: OBJECT, limit: QUANTITY, cursor: DATA|null]";
+
+    let (filter, limit, cursor): (Map<String, Value>, Value, Option<Value>) = params
+        .clone()
+        .parse()
+        .or_else(|_| {
+            params
+                .parse()
+                .map(|(filter, limit): (Map<String, Value>, Value)| (filter, limit, None))
+        })
+        .map_err(|_| Error::invalid_params(usage))?;
+    let log_filter = LogFilter::from_map(&filter)?;
+    let limit = transform::u64_from_hex_value(&limit).map_err(|_| Error::invalid_params(usage))?;
+    if limit == 0 {
+        return Err(Error::invalid_params("Limit must be at least 1"));
+    }
+    let limit = cmp::min(limit, MAX_PAGE_LOGS) as usize;
+    let cursor = match cursor {
+        Some(cursor) => Some(LogCursor::from_value(&cursor)?),
+        None => None,
+    };
+
+    let (from, to) = match log_filter.block_hash {
+        Some(ref block_hash) => {
+            let block = get_filter_block(&client, block_hash)?;
+            let block_num = get_block_num(&block)?;
+            (block_num, block_num)
+        }
+        None => get_filter_range(&client, &log_filter)?,
+    };
+
+    // Logs up to the cursor were on an earlier page, as long as its block is still in
+    // the chain
+    let from = match cursor {
+        Some(ref cursor) => {
+            let block_id = match client.get_block(BlockKey::Number(cursor.block_num)) {
+                Ok(block) => Some(block.header_signature),
+                Err(ClientError::NoResource) => None,
+                Err(err) => return Err(fail!("Couldn't get block", err)),
+            };
+            if block_id.as_ref() != Some(&cursor.block_id) {
+                return Err(Error::invalid_params(format!(
+                    "Block {} of the cursor is no longer in the chain",
+                    cursor.block_id
+                )));
+            }
+            cmp::max(from, cursor.block_num)
+        }
+        None => from,
+    };
+    let after = cursor.map(|cursor| (cursor.block_num, cursor.log_idx));
+
+    let mut budget = ResponseBudget::new(client.max_response_size);
+    let mut logs = Vec::new();
+    let mut last = None;
+    let mut block_num = from;
+    let mut full = false;
+    while block_num <= to && !full {
+        let (read_to, found) = read_logs(&client, &log_filter, block_num, to)?;
+        for found in found {
+            if matches!(after, Some(after) if (found.block_num, found.log_idx) <= after) {
+                continue;
+            }
+            client.memory.charge(memory::value_size(&found.log))?;
+            if let Err(err) = budget.charge(slice::from_ref(&found.log)) {
+                if logs.is_empty() {
+                    return Err(err);
                 }
-                block_index += 1;
+                full = true;
+                break;
+            }
+            logs.push(found.log);
+            last = Some(LogCursor {
+                block_num: found.block_num,
+                log_idx: found.log_idx,
+                block_id: found.block_id,
+            });
+            if logs.len() == limit {
+                full = true;
+                break;
             }
         }
-        // Request for logs from a block to a block
-        (Some(from), Some(to)) => {
-            let mut all_logs = Vec::new();
-            for block_index in from..to {
-                match client.get_block(BlockKey::Number(block_index)) {
-                    Ok(block) => {
-                        let logs = get_logs_from_block_and_filter(client, &block, &log_filter)?;
-                        all_logs.extend(logs.into_iter());
-                    }
-                    Err(ClientError::NoResource) => {
-                        // If we get a no resource, just send what was found
-                        return Ok(Value::Array(all_logs));
-                    }
-                    Err(error) => {
-                        error!("{}", error);
-                        return Err(Error::internal_error());
-                    }
+        block_num = read_to + 1;
+    }
+
+    let mut page = Map::new();
+    page.insert(String::from("logs"), Value::Array(logs));
+    page.insert(
+        String::from("nextCursor"),
+        match last {
+            Some(ref last) if full => last.to_value(),
+            _ => Value::Null,
+        },
+    );
+    Ok(Value::Object(page))
+}
+
+/// Reads the logs that pass the filter from some of the blocks from `from` to `to`,
+/// in chain order, returning the last block read along with them. Blocks the log
+/// index has are read from it a window at a time, and others from the validator one
+/// at a time.
+fn read_logs<T>(
+    client: &ValidatorClient<T>,
+    log_filter: &LogFilter,
+    from: u64,
+    to: u64,
+) -> Result<(u64, Vec<FoundLog>), Error>
+where
+    T: MessageSender,
+{
+    #[cfg(feature = "indexer")]
+    {
+        if let Some(ref log_index) = client.log_index {
+            let indexed = log_index.head().and_then(|head| match head {
+                Some((head, _)) if head >= from => {
+                    let indexed_to = to.min(head).min(from + PAGE_INDEX_BLOCKS - 1);
+                    log_index
+                        .get_logs(log_filter, from, indexed_to)
+                        .map(|logs| Some((indexed_to, logs)))
                 }
+                _ => Ok(None),
+            });
+            match indexed {
+                Ok(Some((indexed_to, logs))) => {
+                    let logs = logs
+                        .into_iter()
+                        .map(|indexed| FoundLog {
+                            log: make_indexed_log_obj(&indexed),
+                            block_num: indexed.block_num,
+                            log_idx: indexed.log_idx,
+                            block_id: indexed.block_id,
+                        })
+                        .collect();
+                    return Ok((indexed_to, logs));
+                }
+                Ok(None) => (),
+                Err(error) => error!("Failed to read the log index, reading blocks: {}", error),
             }
-            Ok(Value::Array(all_logs))
         }
-        // Getting the entire block history up to a certain block is not supported
-        (None, Some(_)) => Err(error::not_implemented()),
     }
+
+    match client.get_block(BlockKey::Number(from)) {
+        Ok(block) => Ok((from, filter_block_logs(client, &block, log_filter)?)),
+        // There are no more blocks to read
+        Err(ClientError::NoResource) => Ok((to, Vec::new())),
+        Err(error) => {
+            error!("{}", error);
+            Err(Error::internal_error())
+        }
+    }
+}
+
+/// Gets the one block a filter searches, by id
+fn get_filter_block<T>(client: &ValidatorClient<T>, block_hash: &str) -> Result<Block, Error>
+where
+    T: MessageSender,
+{
+    client
+        .get_block(BlockKey::Signature(String::from(block_hash)))
+        .map_err(|error| match error {
+            ClientError::NoResource => {
+                Error::invalid_params(format!("Unknown block: {}", block_hash))
+            }
+            error => {
+                error!("{}", error);
+                Error::internal_error()
+            }
+        })
 }
 
-fn get_logs_from_block_and_filter<T>(
+/// Returns the first and last block a filter searches, where a missing bound is the
+/// latest block, like in Ethereum
+fn get_filter_range<T>(
     client: &ValidatorClient<T>,
-    block: &Block,
     log_filter: &LogFilter,
-) -> Result<Vec<Value>, Error>
+) -> Result<(u64, u64), Error>
 where
     T: MessageSender,
 {
-    let block_header: BlockHeader = protobuf::parse_from_bytes(&block.header).map_err(|e| {
-        error!("Error parsing block header: {:?}", e);
+    let latest = client.get_current_block_number().map_err(|e| {
+        error!("Unable to get current block: {:?}", e);
         Error::internal_error()
     })?;
+    let from = log_filter.from_block.unwrap_or(latest);
+    let to = log_filter.to_block.unwrap_or(latest).min(latest);
+    Ok((from, to))
+}
 
-    // Get receipts (which have logs in them)
-    let receipts = client.get_receipts_from_block(&block).map_err(|error| {
-        error!("Unable to get receipts for current block: {}", error);
+fn get_block_num(block: &Block) -> Result<u64, Error> {
+    let block_header: BlockHeader = protobuf::parse_from_bytes(&block.header).map_err(|e| {
+        error!("Error parsing block header: {:?}", e);
         Error::internal_error()
     })?;
+    Ok(block_header.get_block_num())
+}
+
+#[cfg(feature = "indexer")]
+fn make_indexed_log_obj(indexed: &IndexedLog) -> Value {
+    make_log_obj(
+        &indexed.log.log,
+        indexed.log_idx,
+        &indexed.log.txn_id,
+        indexed.log.txn_idx,
+        &indexed.block_id,
+        indexed.block_num,
+    )
+}
+
+/// Reads the logs of the blocks from `from` that the log index has, returning the
+/// first block it doesn't have along with them
+#[cfg(feature = "indexer")]
+fn get_indexed_logs<T>(
+    client: &ValidatorClient<T>,
+    log_filter: &LogFilter,
+    from: u64,
+    to: u64,
+    budget: &mut ResponseBudget,
+) -> Result<(u64, Vec<Value>), Error>
+where
+    T: MessageSender,
+{
+    let log_index = match client.log_index {
+        Some(ref log_index) => log_index,
+        None => return Ok((from, Vec::new())),
+    };
+    let indexed = log_index.head().and_then(|head| match head {
+        Some((head, _)) if head >= from => {
+            let indexed_to = to.min(head);
+            log_index
+                .get_logs(log_filter, from, indexed_to)
+                .map(|logs| Some((indexed_to, logs)))
+        }
+        _ => Ok(None),
+    });
+    match indexed {
+        Ok(Some((indexed_to, logs))) => {
+            let logs: Vec<Value> = logs.iter().map(make_indexed_log_obj).collect();
+            client.memory.charge_values(&logs)?;
+            budget.charge(&logs)?;
+            Ok((indexed_to + 1, logs))
+        }
+        Ok(None) => Ok((from, Vec::new())),
+        Err(error) => {
+            error!("Failed to read the log index, reading blocks: {}", error);
+            Ok((from, Vec::new()))
+        }
+    }
+}
+
+/// Returns the hashes of the seth transactions in a block
+pub fn seth_transaction_hashes(block: &Block) -> Vec<Value> {
+    block
+        .get_batches()
+        .iter()
+        .flat_map(|batch| batch.get_transactions().iter())
+        .filter(|txn| {
+            let header: Result<TransactionHeader, _> = protobuf::parse_from_bytes(&txn.header);
+            if let Ok(header) = header {
+                header.family_name == "seth"
+            } else {
+                false
+            }
+        })
+        .map(|txn| transform::hex_prefix(&txn.header_signature))
+        .collect()
+}
 
-    warn!("LogFilter: {:?}", log_filter);
-    // Filter logs
-    let logs: HashMap<String, Vec<SethLog>> = receipts
+pub fn get_logs_from_block_and_filter<T>(
+    client: &ValidatorClient<T>,
+    block: &Block,
+    log_filter: &LogFilter,
+) -> Result<Vec<Value>, Error>
+where
+    T: MessageSender,
+{
+    Ok(filter_block_logs(client, block, log_filter)?
         .into_iter()
-        .map(|(txn_id, receipt)| {
-            warn!("Logs: {:?}", receipt.logs);
-            let logs: Vec<SethLog> = receipt
-                .logs
-                .into_iter()
-                .filter(|log: &SethLog| log_filter.contains(&log, None))
-                .collect();
-            (txn_id, logs)
+        .map(|found| found.log)
+        .collect())
+}
+
+/// Returns the logs of the block that pass the filter, numbered by their position
+/// among all the logs of the block
+fn filter_block_logs<T>(
+    client: &ValidatorClient<T>,
+    block: &Block,
+    log_filter: &LogFilter,
+) -> Result<Vec<FoundLog>, Error>
+where
+    T: MessageSender,
+{
+    let block_id = block.get_header_signature();
+    let block_num = get_block_num(block)?;
+
+    debug!("LogFilter: {:?}", log_filter);
+    if let Some(bloom) = client.filters.get_block_bloom(block_id) {
+        if !log_filter.may_match(&bloom) {
+            return Ok(Vec::new());
+        }
+    }
+    let found = get_block_logs(client, block)?
+        .iter()
+        .enumerate()
+        .filter(|&(_, block_log)| log_filter.contains(&block_log.log, None))
+        .map(|(log_idx, block_log)| FoundLog {
+            block_num,
+            log_idx: log_idx as u64,
+            block_id: String::from(block_id),
+            log: make_log_obj(
+                &block_log.log,
+                log_idx as u64,
+                &block_log.txn_id,
+                block_log.txn_idx,
+                block_id,
+                block_num,
+            ),
         })
-        .filter(|&(_, ref logs)| !logs.is_empty())
         .collect();
-    warn!("Filtered Logs: {:?}", logs);
+    Ok(found)
+}
 
-    // Contextual data for logs
+/// Returns every log in the block, ordered by transaction, fetching the block's receipts
+/// only if its logs aren't already cached or in the log index. The bloom of the logs is
+/// cached too.
+pub fn get_block_logs<T>(client: &ValidatorClient<T>, block: &Block) -> Result<BlockLogs, Error>
+where
+    T: MessageSender,
+{
     let block_id = block.get_header_signature();
-    let block_num = block_header.get_block_num();
-    let transactions = block
+    if let Some(logs) = client.filters.get_block_logs(block_id) {
+        return Ok(logs);
+    }
+    #[cfg(feature = "indexer")]
+    {
+        if let Some(ref log_index) = client.log_index {
+            match log_index.get_block_logs(get_block_num(block)?, block_id) {
+                Ok(Some(block_logs)) => return Ok(cache_block_logs(client, block_id, block_logs)),
+                Ok(None) => (),
+                Err(error) => error!("Failed to read the log index, reading receipts: {}", error),
+            }
+        }
+    }
+    let block_logs = read_block_logs(client, block)?;
+    Ok(cache_block_logs(client, block_id, block_logs))
+}
+
+fn cache_block_logs<T>(
+    client: &ValidatorClient<T>,
+    block_id: &str,
+    block_logs: Vec<BlockLog>,
+) -> BlockLogs
+where
+    T: MessageSender,
+{
+    let bloom = block_logs.iter().map(|block_log| &block_log.log).collect();
+    client.filters.cache_block_bloom(block_id, bloom);
+    client.filters.cache_block_logs(block_id, block_logs)
+}
+
+/// Returns every log in the block, ordered by transaction, from the block's receipts
+pub fn read_block_logs<T>(
+    client: &ValidatorClient<T>,
+    block: &Block,
+) -> Result<Vec<BlockLog>, Error>
+where
+    T: MessageSender,
+{
+    let block_id = block.get_header_signature();
+
+    // Get receipts (which have logs in them)
+    let mut receipts = client.get_receipts_from_block(&block).map_err(|error| {
+        error!("Unable to get receipts for current block: {}", error);
+        Error::internal_error()
+    })?;
+
+    let mut block_logs = Vec::new();
+    for (index, txn) in block
+        .get_batches()
+        .iter()
+        .flat_map(|batch| batch.get_transactions().iter())
+        .enumerate()
+    {
+        if let Some(receipt) = receipts.remove(&txn.header_signature) {
+            block_logs.extend(receipt.logs.into_iter().map(|log| BlockLog {
+                txn_id: txn.header_signature.clone(),
+                txn_idx: index as u64,
+                log,
+            }));
+        }
+    }
+
+    if !receipts.is_empty() {
+        error!(
+            "Found receipts for transactions missing from block `{}`",
+            block_id
+        );
+        return Err(Error::internal_error());
+    }
+
+    Ok(block_logs)
+}
+
+fn get_permission_changes_from_block(block: &Block, block_num: u64) -> Vec<Value> {
+    block
         .get_batches()
         .iter()
         .flat_map(|batch| batch.get_transactions().iter())
-        .collect::<Vec<_>>();
+        .enumerate()
+        .filter(|&(_, txn)| {
+            let header: Result<TransactionHeader, _> = protobuf::parse_from_bytes(&txn.header);
+            if let Ok(header) = header {
+                header.family_name == "seth"
+            } else {
+                false
+            }
+        })
+        .filter_map(|(idx, txn)| {
+            let txn = Transaction::try_from(txn.clone()).ok()?;
+            let (address, permissions) = txn.permissions()?;
+            Some(make_permission_change_obj(
+                &address,
+                &permissions,
+                &txn.hash(),
+                idx as u64,
+                &block.header_signature,
+                block_num,
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use accounts;
+    use contracts::ContractRegistry;
+    use crypto_backend;
+    use mock_chain::MockChain;
+    use serde_json;
+
+    fn client() -> ValidatorClient<MockChain> {
+        ValidatorClient::new(
+            MockChain::new(),
+            Vec::new(),
+            FilterManager::new(1),
+            0,
+            String::from("1.0"),
+            ContractRegistry::new(),
+        )
+    }
+
+    fn params(json: &str) -> Params {
+        serde_json::from_str(json).unwrap()
+    }
+
+    /// The address of the counter alice deploys on the mock chain, which logs its
+    /// count when bob increments it in block 3
+    fn counter() -> String {
+        let alice = MockChain::accounts()[0].address().to_string();
+        accounts::contract_address(&transform::hex_str_to_bytes(&alice).unwrap(), 1)
+    }
+
+    #[test]
+    fn gets_the_logs_that_pass_a_filter() {
+        let client = client();
+        let logs = |filter: String| {
+            get_logs(params(&format!("[{}]", filter)), client.clone())
+                .map(|logs| logs.as_array().unwrap().len())
+        };
+        let incremented =
+            transform::bytes_to_hex_str(&crypto_backend::keccak256(b"Incremented(uint256)"));
+        let head = client.get_block(BlockKey::Number(3)).unwrap();
 
-    let mut log_objects = Vec::with_capacity(logs.len());
-    for (txn_id, logs) in logs {
-        let index = transactions
+        assert_eq!(logs(String::from(r#"{"fromBlock": "0x0"}"#)), Ok(1));
+        // A filter without a range searches the latest block
+        assert_eq!(logs(String::from("{}")), Ok(1));
+        assert_eq!(logs(String::from(r#"{"toBlock": "0x2"}"#)), Ok(0));
+        assert_eq!(
+            logs(format!(
+                r#"{{"fromBlock": "0x0", "address": "0x{}"}}"#,
+                counter()
+            )),
+            Ok(1)
+        );
+        assert_eq!(
+            logs(format!(
+                r#"{{"fromBlock": "0x0", "address": "0x{}"}}"#,
+                "1".repeat(40)
+            )),
+            Ok(0)
+        );
+        assert_eq!(
+            logs(format!(r#"{{"topics": ["0x{}"]}}"#, incremented)),
+            Ok(1)
+        );
+        assert_eq!(
+            logs(format!(r#"{{"topics": ["0x{}"]}}"#, "1".repeat(64))),
+            Ok(0)
+        );
+        assert_eq!(
+            logs(format!(r#"{{"blockHash": "0x{}"}}"#, head.header_signature)),
+            Ok(1)
+        );
+        assert!(logs(format!(r#"{{"blockHash": "0x{}"}}"#, "0".repeat(128))).is_err());
+    }
+
+    #[test]
+    fn pages_logs_from_a_cursor() {
+        let client = client();
+        let page = |cursor: &str| {
+            get_logs_page(
+                params(&format!(r#"[{{"fromBlock": "0x0"}}, "0x1", {}]"#, cursor)),
+                client.clone(),
+            )
+            .unwrap()
+        };
+
+        let first = page("null");
+        assert_eq!(first["logs"].as_array().map(Vec::len), Some(1));
+        assert_eq!(first["logs"][0]["blockNumber"], Value::from("0x3"));
+        // The page is full, so the next one starts after its last log
+        let cursor = &first["nextCursor"];
+        assert!(cursor.is_string());
+        let next = page(&cursor.to_string());
+        assert_eq!(next["logs"], Value::Array(Vec::new()));
+        assert_eq!(next["nextCursor"], Value::Null);
+
+        assert!(get_logs_page(params(r#"[{}, "0x0"]"#), client.clone()).is_err());
+        let moved = LogCursor {
+            block_num: 3,
+            log_idx: 0,
+            block_id: "0".repeat(128),
+        };
+        assert!(get_logs_page(
+            params(&format!(r#"[{{}}, "0x1", {}]"#, moved.to_value())),
+            client
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn installs_filters_up_to_the_limit() {
+        let client = client();
+        let filter_id = new_filter(params(r#"[{"fromBlock": "0x0"}]"#), client.clone()).unwrap();
+        assert!(new_block_filter(Params::None, client.clone()).is_err());
+        // No blocks have been committed since the filter was installed
+        assert_eq!(
+            get_filter_changes(Params::Array(vec![filter_id.clone()]), client.clone()),
+            Ok(Value::Array(Vec::new()))
+        );
+        assert_eq!(
+            get_filter_logs(Params::Array(vec![filter_id.clone()]), client.clone())
+                .map(|logs| logs.as_array().unwrap().len()),
+            Ok(1)
+        );
+
+        let uninstall = || uninstall_filter(Params::Array(vec![filter_id.clone()]), client.clone());
+        assert_eq!(uninstall(), Ok(Value::Bool(true)));
+        assert_eq!(uninstall(), Ok(Value::Bool(false)));
+        assert!(
+            get_filter_changes(Params::Array(vec![filter_id.clone()]), client.clone()).is_err()
+        );
+        assert!(new_block_filter(Params::None, client).is_ok());
+    }
+
+    #[test]
+    fn hashes_the_seth_transactions_of_a_block() {
+        let client = client();
+        let block = client.get_block(BlockKey::Number(3)).unwrap();
+        let hashes: Vec<Value> = block
+            .get_batches()
             .iter()
-            .position(|txn| txn.header_signature == txn_id)
-            .ok_or_else(|| {
-                error!(
-                    "Failed to find index of txn `{}` in block `{}`",
-                    txn_id, block_id
-                );
-                Error::internal_error()
-            })?;
-        for log in &logs {
-            let log_obj = make_log_obj(log, &txn_id, index as u64, block_id, block_num);
-            log_objects.push(log_obj);
-        }
+            .flat_map(|batch| batch.get_transactions().iter())
+            .map(|txn| transform::hex_prefix(&txn.header_signature))
+            .collect();
+        assert_eq!(hashes.len(), 2);
+        assert_eq!(seth_transaction_hashes(&block), hashes);
     }
-    Ok(log_objects)
 }
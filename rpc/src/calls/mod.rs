@@ -18,9 +18,14 @@
 
 pub mod account;
 pub mod block;
+#[cfg(feature = "debug")]
+pub mod debug;
 pub mod error;
 pub mod logs;
 pub mod network;
+#[cfg(feature = "personal")]
 pub mod personal;
 pub mod seth;
 pub mod transaction;
+pub mod txpool;
+pub mod web3;
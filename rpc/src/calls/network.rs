@@ -17,31 +17,52 @@
 
 use jsonrpc_core::{Error, Params, Value};
 
-use client::ValidatorClient;
+use client::{BlockKey, ValidatorClient};
 use requests::RequestHandler;
+use transform;
 
 use sawtooth_sdk::messaging::stream::MessageSender;
 
-const SAWTOOTH_NET_VERSION: &str = "19";
+/// The chain id of EIP-155 signatures unless the sawtooth.seth.chain_id setting
+/// gives another, which is also the network version
+pub const DEFAULT_CHAIN_ID: u64 = 19;
 
 pub fn get_method_list<T>() -> Vec<(String, RequestHandler<T>)>
 where
     T: MessageSender,
 {
     vec![
+        ("eth_chainId".into(), chain_id),
         ("net_version".into(), version),
         ("net_peerCount".into(), peer_count),
         ("net_listening".into(), listening),
     ]
 }
 
-// Version refers to the particular network this JSON-RPC client is connected to
-pub fn version<T>(_params: Params, _client: ValidatorClient<T>) -> Result<Value, Error>
+// Version refers to the particular network this JSON-RPC client is connected to,
+// which is told apart by its chain id
+pub fn version<T>(_params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
 where
     T: MessageSender,
 {
     info!("net_version");
-    Ok(Value::String(String::from(SAWTOOTH_NET_VERSION)))
+    let chain_id = client
+        .get_chain_id(BlockKey::Latest)
+        .map_err(|error| fail!("Couldn't get chain id", error))?;
+    Ok(Value::String(chain_id.to_string()))
+}
+
+// The chain id that transactions must be signed for, which wallets ask for before
+// signing with EIP-155
+pub fn chain_id<T>(_params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("eth_chainId");
+    let chain_id = client
+        .get_chain_id(BlockKey::Latest)
+        .map_err(|error| fail!("Couldn't get chain id", error))?;
+    Ok(transform::num_to_hex(&chain_id))
 }
 
 // Return the number of actual Sawtooth peers
@@ -50,10 +71,7 @@ where
     T: MessageSender,
 {
     info!("net_peerCount");
-    let n = match client.get_peers() {
-        Err(_) => 0,
-        Ok(n) => n,
-    };
+    let n = client.get_peers().unwrap_or_default();
 
     Ok(Value::String(format!("{:#x}", n)))
 }
@@ -15,22 +15,35 @@
  * ------------------------------------------------------------------------------
  */
 
-use accounts::{get_key_dir, Account};
+use accounts;
+use accounts::Account;
+use calls::transaction::parse_transaction;
 use client::BlockKey;
 use client::ValidatorClient;
+use error;
 use jsonrpc_core::{Error, Params, Value};
+use keystore;
 use messages::seth::{
     CreateExternalAccountTxn, EvmPermissions, SethTransaction as SethTransactionPb,
     SethTransaction_TransactionType,
 };
+use mnemonic;
 use requests::RequestHandler;
 use sawtooth_sdk::messaging::stream::MessageSender;
 use sawtooth_sdk::signing::secp256k1::{Secp256k1Context, Secp256k1PrivateKey};
 use sawtooth_sdk::signing::Context;
-use std::fs;
+use serde_json::Map;
 use transactions::SethTransaction;
 use transform;
 
+/// The words of the mnemonics `personal_newMnemonic` makes by default, as many as
+/// MetaMask's have
+const DEFAULT_MNEMONIC_WORDS: usize = 12;
+
+/// The most accounts `personal_importMnemonic` imports at once, since each is a key
+/// file and a transaction
+const MAX_MNEMONIC_ACCOUNTS: u32 = 100;
+
 pub fn get_method_list<T>() -> Vec<(String, RequestHandler<T>)>
 where
     T: MessageSender,
@@ -39,7 +52,12 @@ where
         ("personal_listAccounts".into(), list_accounts),
         ("personal_newAccount".into(), new_account),
         ("personal_unlockAccount".into(), unlock_account),
+        ("personal_lockAccount".into(), lock_account),
+        ("personal_sendTransaction".into(), send_transaction),
         ("personal_importRawKey".into(), import_raw_key),
+        ("personal_exportKeystore".into(), export_keystore),
+        ("personal_newMnemonic".into(), new_mnemonic),
+        ("personal_importMnemonic".into(), import_mnemonic),
     ]
 }
 
@@ -56,7 +74,7 @@ where
     Ok(Value::Array(
         loaded_accounts
             .iter()
-            .map(|a| transform::hex_prefix(a.address()))
+            .map(|a| transform::address_value(a.address()))
             .collect::<Vec<_>>(),
     ))
 }
@@ -113,10 +131,7 @@ where
     let account = Account::load_from_str(&pem_bytes, password)
         .map_err(|err| fail!("Error generating key", err))?;
 
-    let mut filename = get_key_dir().ok_or_else(Error::internal_error)?;
-    filename.push(account.address());
-
-    fs::write(filename.with_extension("pem"), pem_bytes)
+    accounts::write_key(account.address(), &pem_bytes)
         .map_err(|err| fail!("Error generating key", err))?;
 
     // New accounts can have a moderator that is the actual account used for sending this
@@ -168,10 +183,12 @@ where
         .unlock_account(&account, Some(0))
         .map_err(|err| fail!("Couldn't unlock account", err))?;
 
-    Ok(transform::hex_prefix(&account.address()))
+    Ok(transform::address_value(account.address()))
 }
 
-/// Unlocks an account, loading it from disk if necessary
+/// Unlocks an account for a number of seconds, loading it from disk if necessary. It
+/// stays unlocked until it is locked again if the duration is 0, and for 300 seconds
+/// without one.
 pub fn unlock_account<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
 where
     T: MessageSender,
@@ -187,6 +204,7 @@ where
         }
     };
 
+    let address = transform::parse_address(&address)?;
     Ok(Value::Bool(
         match client.unlock_address(&address, &password, duration) {
             Ok(()) => true,
@@ -198,33 +216,95 @@ where
     ))
 }
 
-/// Imports a raw, hex-encoded secp256k1 key
+/// Locks an account again, returning whether it was unlocked
+pub fn lock_account<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("personal_lockAccount");
+
+    let (address,): (String,) = params
+        .parse()
+        .map_err(|_| Error::invalid_params("Takes [address: DATA]"))?;
+    let address = transform::parse_address(&address)?;
+
+    Ok(Value::Bool(client.lock_address(&address)))
+}
+
+/// Sends a transaction like `eth_sendTransaction`, signed with the key of its `from`
+/// account unlocked by the password for only this transaction
+pub fn send_transaction<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("personal_sendTransaction");
+
+    let (txn, password): (Map<String, Value>, Option<String>) = params
+        .parse()
+        .map_err(|_| Error::invalid_params("Takes [txn: OBJECT, password: STRING]"))?;
+
+    let (from, txn, access_list) = parse_transaction(&client, &txn, 0)?;
+    let account = client
+        .load_address(&from, &password)
+        .map_err(|err| Error::invalid_params(format!("Couldn't unlock account: {}", err)))?;
+    let txn_signature = client
+        .send_transaction_signed_by(&account, &from, &txn, access_list.as_deref())
+        .map_err(error::transaction_error)?;
+
+    Ok(transform::hex_prefix(&txn_signature))
+}
+
+/// Imports a raw, hex-encoded secp256k1 key, or a JSON keystore decrypted by the
+/// password. Either is saved encrypted by the password, if one is given.
 pub fn import_raw_key<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
 where
     T: MessageSender,
 {
     info!("personal_importRawKey");
 
-    let usage = "Takes [key: DATA]";
+    let usage = "Takes [key: DATA|OBJECT, password: STRING]";
 
-    let (key, password): (String, Option<String>) =
+    let (key, password): (Value, Option<String>) =
         params.parse().map_err(|_| Error::invalid_params(usage))?;
 
-    let priv_key = Secp256k1PrivateKey::from_hex(&key)
+    let key = match key {
+        Value::String(ref hex) => String::from(hex.trim().trim_start_matches("0x")),
+        Value::Object(_) => {
+            let password = password
+                .as_ref()
+                .ok_or_else(|| Error::invalid_params("Keystores need a password"))?;
+            keystore::decrypt(&key, password)
+                .map(|key| transform::bytes_to_hex_str(&key))
+                .map_err(|err| Error::invalid_params(format!("Invalid keystore: {}", err)))?
+        }
+        _ => return Err(Error::invalid_params(usage)),
+    };
+
+    import_key(&client, &key, &password).map(Value::from)
+}
+
+/// Saves a hex-encoded key, encrypted by the password if one is given, creates its
+/// account and unlocks it, returning its address
+fn import_key<T>(
+    client: &ValidatorClient<T>,
+    key: &str,
+    password: &Option<String>,
+) -> Result<String, Error>
+where
+    T: MessageSender,
+{
+    let priv_key = Secp256k1PrivateKey::from_hex(key)
         .map_err(|err| fail!("Private key must be hex-encoded", err))?;
     let pem_str = match password {
-        Some(ref pw) => priv_key.to_pem_with_password(&pw),
+        Some(ref pw) => priv_key.to_pem_with_password(pw),
         None => priv_key.to_pem(),
     }
     .map_err(|err| fail!("Couldn't load key", err))?;
 
-    let account = Account::load_from_str(&pem_str, &password)
+    let account = Account::load_from_str(&pem_str, password)
         .map_err(|err| fail!("Error loading account from key", err))?;
 
-    let mut filename = get_key_dir().ok_or_else(Error::internal_error)?;
-    filename.push(account.address());
-
-    fs::write(filename.with_extension("pem"), pem_str)
+    accounts::write_key(account.address(), &pem_str)
         .map_err(|err| fail!("Error generating key", err))?;
 
     // Create and send the transaction in for processing
@@ -248,5 +328,96 @@ where
         .unlock_account(&account, Some(0))
         .map_err(|err| fail!("Couldn't unlock account", err))?;
 
-    Ok(transform::hex_prefix(&account.address()))
+    Ok(transform::checksum_address(account.address()))
+}
+
+/// Exports the key of an account as a JSON keystore, encrypted by the same password
+/// that unlocks it
+pub fn export_keystore<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("personal_exportKeystore");
+
+    let (address, password): (String, String) = params
+        .parse()
+        .map_err(|_| Error::invalid_params("Takes [address: DATA, password: STRING]"))?;
+
+    let account = client
+        .load_address(
+            &transform::parse_address(&address)?,
+            &Some(password.clone()),
+        )
+        .map_err(|err| Error::invalid_params(format!("Couldn't unlock account: {}", err)))?;
+
+    account
+        .to_keystore(&password)
+        .map_err(|err| fail!("Couldn't encrypt key", err))
+}
+
+/// Returns a new BIP-39 mnemonic of 12 words, or of as many as are given, which
+/// isn't kept; `personal_importMnemonic` imports the accounts it derives
+pub fn new_mnemonic<T>(params: Params, _client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("personal_newMnemonic");
+
+    let usage = "Takes [words: NUMBER]";
+
+    let words = match params {
+        Params::None => DEFAULT_MNEMONIC_WORDS,
+        Params::Array(ref values) if values.is_empty() => DEFAULT_MNEMONIC_WORDS,
+        params => params
+            .parse()
+            .map(|(words,): (Option<usize>,)| words.unwrap_or(DEFAULT_MNEMONIC_WORDS))
+            .map_err(|_| Error::invalid_params(usage))?,
+    };
+
+    mnemonic::generate(words)
+        .map(Value::from)
+        .map_err(Error::invalid_params)
+}
+
+/// Imports the first `count` accounts of a BIP-39 mnemonic, one if no count is
+/// given, along the path MetaMask derives them along, each saved and created like
+/// an account of `personal_importRawKey`. Returns their addresses.
+pub fn import_mnemonic<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("personal_importMnemonic");
+
+    let usage = "Takes [mnemonic: STRING, password: STRING, count: NUMBER]";
+
+    let (phrase, password, count): (String, Option<String>, Option<u32>) = params
+        .clone()
+        .parse()
+        .or_else(|_| {
+            params
+                .clone()
+                .parse()
+                .map(|(phrase, password): (String, Option<String>)| (phrase, password, None))
+        })
+        .or_else(|_| {
+            params
+                .parse()
+                .map(|(phrase,): (String,)| (phrase, None, None))
+        })
+        .map_err(|_| Error::invalid_params(usage))?;
+    let count = count.unwrap_or(1);
+    if count == 0 || count > MAX_MNEMONIC_ACCOUNTS {
+        return Err(Error::invalid_params(format!(
+            "Between 1 and {} accounts can be imported at once",
+            MAX_MNEMONIC_ACCOUNTS
+        )));
+    }
+
+    let keys = mnemonic::derive_keys(&phrase, count).map_err(Error::invalid_params)?;
+    let addresses = keys
+        .iter()
+        .map(|key| import_key(&client, &transform::bytes_to_hex_str(key), &password))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Value::from(addresses))
 }
@@ -15,40 +15,412 @@
  * ------------------------------------------------------------------------------
  */
 
-use client::{BlockKey, ValidatorClient};
+use accounts;
+use calls::account::{validate_account_address, validate_block_key};
+use calls::transaction;
+use client::{self, BlockKey, Error as ClientError, StorageStats, ValidatorClient};
+use error;
+use filters::{filter_id_to_hex, Filter};
 use jsonrpc_core::{Error, Params, Value};
-use messages::seth::EvmPermissions;
+#[cfg(feature = "indexer")]
+use log_index::LogIndex;
 use messages::seth::SetPermissionsTxn;
 use messages::seth::SethTransaction as SethTransactionPb;
 use messages::seth::SethTransaction_TransactionType;
+use messages::seth::{ArchiveContractTxn, ImportAccountTxn, ResurrectContractTxn};
+use messages::seth::{ArchivedContract, EvmPermissions, EvmStateAccount, EvmStorage};
+use names;
+use protobuf;
+use proxies;
+#[cfg(feature = "indexer")]
+use reports;
 use requests::RequestHandler;
+use sawtooth_sdk::messages::batch::BatchList;
 use sawtooth_sdk::messaging::stream::MessageSender;
-use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json::Map;
+use std::cmp;
+use std::collections::BTreeMap;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use transactions::SethTransaction;
 use transform;
+use typed_data;
+use types::{self, Quantity};
+use versions;
 
 pub fn get_method_list<T>() -> Vec<(String, RequestHandler<T>)>
 where
     T: MessageSender,
 {
     vec![
+        ("seth_archiveContract".into(), archive_contract),
+        ("seth_decodeResult".into(), decode_result),
+        ("seth_encodeCall".into(), encode_call),
+        ("seth_exportBatches".into(), export_batches),
+        ("seth_exportState".into(), export_state),
+        ("seth_flushCallCache".into(), flush_call_cache),
+        #[cfg(feature = "indexer")]
+        ("seth_getActivityReport".into(), get_activity_report),
+        ("seth_getBlockHeaders".into(), get_block_headers),
+        (
+            "seth_getCloneImplementations".into(),
+            get_clone_implementations,
+        ),
+        ("seth_getConsensusInfo".into(), get_consensus_info),
+        ("seth_getHealth".into(), get_health),
+        ("seth_getNonceStatus".into(), get_nonce_status),
         ("seth_getPermissions".into(), get_permissions),
+        ("seth_getStorageStats".into(), get_storage_stats),
+        ("seth_getUnsupportedMethods".into(), get_unsupported_methods),
+        #[cfg(feature = "indexer")]
+        (
+            "seth_getWatchedAddressReport".into(),
+            get_watched_address_report,
+        ),
+        #[cfg(feature = "indexer")]
+        ("seth_getWatchlists".into(), get_watchlists),
+        ("seth_importBatches".into(), import_batches),
+        ("seth_importState".into(), import_state),
+        ("seth_lookupAddress".into(), lookup_address),
+        ("seth_negotiateVersion".into(), negotiate_version),
+        ("seth_newPermissionFilter".into(), new_permission_filter),
+        ("seth_recoverAddress".into(), recover_address),
+        ("seth_registerContract".into(), register_contract),
+        ("seth_resetNonces".into(), reset_nonces),
+        ("seth_resolveName".into(), resolve_name),
+        ("seth_resurrectContract".into(), resurrect_contract),
         ("seth_setPermissions".into(), set_permissions),
+        #[cfg(feature = "indexer")]
+        ("seth_setWatchlist".into(), set_watchlist),
+        ("seth_submitOrdered".into(), submit_ordered),
+        ("seth_verifySignature".into(), verify_signature),
+        (
+            "seth_waitForTransactionReceipt".into(),
+            wait_for_transaction_receipt,
+        ),
     ]
 }
 
-pub fn get_permissions<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+/// Default number of seconds `seth_waitForTransactionReceipt` waits for a receipt
+const DEFAULT_RECEIPT_TIMEOUT: u64 = 30;
+
+/// Upper bound on the wait, since each waiting request holds a worker thread
+const MAX_RECEIPT_TIMEOUT: u64 = 120;
+
+/// How often the validator is asked for the receipt while waiting
+const RECEIPT_POLL_INTERVAL_MS: u64 = 500;
+
+/// The most transactions `seth_submitOrdered` sends at once
+const MAX_ORDERED_TRANSACTIONS: usize = 64;
+
+/// The most headers `seth_getBlockHeaders` returns at once
+const MAX_BLOCK_HEADERS: u64 = 256;
+
+/// The most blocks `seth_exportBatches` returns the batches of at once
+const MAX_EXPORTED_BLOCKS: u64 = 256;
+
+/// The most batches `seth_importBatches` submits at once
+const MAX_IMPORTED_BATCHES: usize = 256;
+
+/// The most accounts `seth_importState` creates at once
+const MAX_IMPORTED_ACCOUNTS: usize = 64;
+
+/// Archives a contract, removing it and its storage from global state. Returns the
+/// transaction hash; the archived state is the `returnValue` of its receipt, and must
+/// be kept to resurrect the contract.
+pub fn archive_contract<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
 where
     T: MessageSender,
 {
-    info!("seth_getPermissions");
+    info!("seth_archiveContract");
 
     let usage = "Takes [address: ADDRESS]";
 
     let (address,): (String,) = params.parse().map_err(|_| Error::invalid_params(usage))?;
+    let to = address
+        .get(2..)
+        .and_then(transform::hex_str_to_bytes)
+        .ok_or_else(|| Error::invalid_params(usage))?;
+
+    // The storage keys are read from the latest state, so slots first written by
+    // transactions that are still pending are discarded rather than archived
+    let storage_keys = client
+        .get_storage_keys(&transform::bytes_to_hex_str(&to), BlockKey::Latest)
+        .map_err(|err| fail!("Couldn't get storage keys", err))?;
+
+    let mut txn = ArchiveContractTxn::new();
+    txn.set_to(to);
+    txn.set_storage_keys(protobuf::RepeatedField::from_vec(storage_keys));
+
+    send_root_transaction(&client, |nonce| {
+        txn.set_nonce(nonce);
+        SethTransaction::ArchiveContract(txn)
+    })
+}
+
+/// Restores a contract archived by `seth_archiveContract`, given the `returnValue` of
+/// the receipt of the transaction that archived it
+pub fn resurrect_contract<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_resurrectContract");
+
+    let usage = "Takes [archive: DATA]";
+
+    let (archive,): (String,) = params.parse().map_err(|_| Error::invalid_params(usage))?;
+    let archive = archive
+        .get(2..)
+        .and_then(transform::hex_str_to_bytes)
+        .ok_or_else(|| Error::invalid_params(usage))?;
+
+    let mut txn = ResurrectContractTxn::new();
+    txn.set_archive(archive);
+
+    send_root_transaction(&client, |nonce| {
+        txn.set_nonce(nonce);
+        SethTransaction::ResurrectContract(txn)
+    })
+}
+
+/// Sends a transaction from the unlocked account, which must have the root permission,
+/// and returns its hash
+fn send_root_transaction<T, F>(client: &ValidatorClient<T>, make_txn: F) -> Result<Value, Error>
+where
+    T: MessageSender,
+    F: FnOnce(u64) -> SethTransaction,
+{
+    let (from, nonce) = root_sender(client)?;
+
+    let txn_signature = client
+        .send_transaction(&from, &make_txn(nonce))
+        .map_err(error::transaction_error)?;
+
+    Ok(transform::hex_prefix(&txn_signature))
+}
+
+/// Returns the address and nonce of the unlocked account, which root transactions
+/// are sent from
+fn root_sender<T>(client: &ValidatorClient<T>) -> Result<(String, u64), Error>
+where
+    T: MessageSender,
+{
+    let from = client
+        .unlocked_account()
+        .ok_or_else(|| fail!("Couldn't unlock account"))?
+        .address()
+        .to_string();
+    let nonce = client
+        .get_account(&from, BlockKey::Latest)
+        .map_err(|err| fail!("Couldn't get account", err))?
+        .ok_or_else(|| Error::invalid_params("Unlocked account doesn't exist"))?
+        .nonce;
+    Ok((from, nonce))
+}
+
+/// Empties the `eth_call` result cache, returning the number of entries removed
+pub fn flush_call_cache<T>(_params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_flushCallCache");
+    Ok(transform::num_to_hex(&client.call_cache.flush()))
+}
+
+/// Returns the nonce of an account in the latest state next to the nonce its next
+/// transaction is given, which counts the transactions this server sent from it
+/// that haven't been committed. Those that have been committed or rejected since
+/// are forgotten first, so the nonces still tracked are those of transactions the
+/// validator holds as pending, or of all that were sent if it can't be asked.
+pub fn get_nonce_status<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_getNonceStatus");
+    let address = nonce_address(params, &client)?;
+
+    let nonce_at = |block| {
+        client
+            .get_account(&address, block)
+            .map(|account| account.map_or(0, |account| account.nonce))
+            .map_err(|err| fail!("Couldn't get account", err))
+    };
+    let nonce = nonce_at(BlockKey::Latest)?;
+    let pending_nonce = nonce_at(BlockKey::Pending)?;
+    let mut tracked: Vec<u64> = client
+        .txpool
+        .sent_from(&address)
+        .into_iter()
+        .map(|(_, _, nonce)| nonce)
+        .collect();
+    tracked.sort_unstable();
+
+    Ok(transform::to_value(&types::NonceStatus {
+        nonce: Quantity(nonce),
+        pending_nonce: Quantity(pending_nonce),
+        tracked: tracked.into_iter().map(Quantity).collect(),
+    }))
+}
+
+/// Forgets the transactions this server sent from an account, so that the nonce
+/// of its next transaction is its nonce in the latest state again, returning how
+/// many were forgotten. Transactions the validator still holds are committed
+/// regardless, so this is for when it has lost them, such as after a rollback.
+pub fn reset_nonces<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_resetNonces");
+    let address = nonce_address(params, &client)?;
+    let forgotten = client.txpool.remove_sent_from(&address);
+    info!("Forgot {} transactions sent from {}", forgotten, address);
+    Ok(transform::num_to_hex(&forgotten))
+}
+
+fn nonce_address<T>(params: Params, client: &ValidatorClient<T>) -> Result<String, Error>
+where
+    T: MessageSender,
+{
+    let (address,): (String,) = params
+        .parse()
+        .map_err(|_| Error::invalid_params("Takes [address: DATA(20)]"))?;
+    validate_account_address(&names::resolve_address(client, &address)?)
+}
+
+/// Reports the latest block and peers of the validator, or fails while it can't be
+/// reached, which is what `/health` answers with
+pub fn get_health<T>(_params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_getHealth");
+    if client.breaker.is_open() {
+        return Err(error::validator_unavailable());
+    }
+    let health = client
+        .get_current_block_number()
+        .and_then(|block_number| {
+            client.get_peers().map(|peers| types::Health {
+                block_number: Quantity(block_number),
+                peers: Quantity(peers as u64),
+            })
+        })
+        .map_err(|err| {
+            warn!("Validator is unhealthy: {}", err);
+            error::validator_unavailable()
+        })?;
+    Ok(transform::to_value(&health))
+}
+
+/// Picks the latest version of the seth_* methods that the client knows of the ones
+/// given, or the latest served if none are given, so that it can call that
+/// version's `seth_<version>_*` names whatever versions are added later
+pub fn negotiate_version<T>(params: Params, _client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_negotiateVersion");
+
+    let usage = "Takes [versions: [STRING]]";
+
+    let known: Option<Vec<String>> = match params {
+        Params::None => None,
+        Params::Array(ref values) if values.is_empty() => None,
+        params => params
+            .parse()
+            .map(|(known,): (Vec<String>,)| Some(known))
+            .map_err(|_| Error::invalid_params(usage))?,
+    };
+    let version = versions::VERSIONS
+        .iter()
+        .rev()
+        .find(|version| match known {
+            Some(ref known) => known.iter().any(|known| known == *version),
+            None => true,
+        })
+        .ok_or_else(|| {
+            Error::invalid_params(format!(
+                "None of the versions given are served, the versions are {}",
+                versions::VERSIONS.join(", ")
+            ))
+        })?;
+
+    Ok(transform::to_value(&types::VersionNegotiation {
+        version: String::from(*version),
+        versions: versions::VERSIONS
+            .iter()
+            .map(|version| String::from(*version))
+            .collect(),
+        unversioned: String::from(versions::UNVERSIONED),
+    }))
+}
+
+/// Returns how many times each client called each method that isn't served, most
+/// called first
+pub fn get_unsupported_methods<T>(
+    _params: Params,
+    client: ValidatorClient<T>,
+) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_getUnsupportedMethods");
+    Ok(transform::to_value(&client.unsupported_methods.report()))
+}
+
+/// Returns the permissions of an account, or of the global permissions address for
+/// `global`, or every account whose permissions are set if no address is given, so
+/// that admin consoles can manage them
+pub fn get_permissions<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_getPermissions");
+
+    let usage = "Takes [address: ADDRESS|\"global\"|null, block: QUANTITY|TAG]";
+
+    let (address, block): (Option<String>, Option<String>) = match params {
+        Params::None => (None, None),
+        Params::Array(ref values) if values.is_empty() => (None, None),
+        params => params
+            .clone()
+            .parse()
+            .or_else(|_| params.parse().map(|(a,): (Option<String>,)| (a, None)))
+            .map_err(|_| Error::invalid_params(usage))?,
+    };
+    let address = match address {
+        Some(address) => {
+            Some(permissions_address(&address).ok_or_else(|| Error::invalid_params(usage))?)
+        }
+        None => None,
+    };
+    let block = match block {
+        Some(block) => validate_block_key(&block)?,
+        None => BlockKey::Latest,
+    };
+
+    let address = match address {
+        Some(address) => address,
+        None => {
+            let entries = client
+                .get_permission_entries(block)
+                .map_err(|err| fail!("Couldn't get permissions", err))?;
+            return Ok(Value::Array(
+                entries
+                    .into_iter()
+                    .map(|(address, permissions)| {
+                        transform::to_value(&types::PermissionEntry {
+                            address: transform::checksum_address(&address),
+                            permissions: format!("{}", permissions),
+                        })
+                    })
+                    .collect(),
+            ));
+        }
+    };
 
     let account = client
-        .get_account(&address, BlockKey::Latest)
+        .get_account(&address, block)
         .map_err(|err| fail!("Couldn't get key", err))?;
 
     match account {
@@ -57,16 +429,113 @@ where
     }
 }
 
+/// Returns the address that permissions are set on, as hex without `0x`, for an
+/// address with or without `0x`, or `global` for the global permissions
+fn permissions_address(address: &str) -> Option<String> {
+    if address == "global" {
+        return Some(String::from(client::GLOBAL_PERMISSIONS_ADDRESS));
+    }
+    let address = address.strip_prefix("0x").unwrap_or(address);
+    if address.len() == 40 && transform::hex_str_to_bytes(address).is_some() {
+        Some(address.to_lowercase())
+    } else {
+        None
+    }
+}
+
+/// Returns the number of storage slots, state entries and bytes of state used by a
+/// contract, or by every contract ordered by most bytes first, so that operators can
+/// find the contracts growing global state
+pub fn get_storage_stats<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_getStorageStats");
+
+    let usage = "Takes [address: ADDRESS|null, block: QUANTITY|TAG]";
+
+    let (address, block): (Option<String>, Option<String>) = match params {
+        Params::None => (None, None),
+        params => params
+            .clone()
+            .parse()
+            .or_else(|_| params.parse().map(|(a,): (Option<String>,)| (a, None)))
+            .map_err(|_| Error::invalid_params(usage))?,
+    };
+    let address = match address {
+        Some(address) => Some(
+            address
+                .get(2..)
+                .filter(|a| a.len() == 40 && transform::hex_str_to_bytes(a).is_some())
+                .map(str::to_lowercase)
+                .ok_or_else(|| Error::invalid_params(usage))?,
+        ),
+        None => None,
+    };
+    let block = match block {
+        Some(block) => validate_block_key(&block)?,
+        None => BlockKey::Latest,
+    };
+
+    let mut stats = client
+        .get_storage_stats(address.as_deref(), block)
+        .map_err(|err| fail!("Couldn't get storage stats", err))?;
+
+    if address.is_some() {
+        return Ok(stats.pop().map_or(Value::Null, make_storage_stats_obj));
+    }
+
+    stats.retain(|stats| stats.is_contract);
+    stats.sort_by_key(|stats| cmp::Reverse(stats.bytes));
+
+    Ok(Value::Array(
+        stats.into_iter().map(make_storage_stats_obj).collect(),
+    ))
+}
+
+fn make_storage_stats_obj(stats: StorageStats) -> Value {
+    transform::to_value(&types::StorageStats {
+        address: transform::checksum_address(&stats.address),
+        slots: Quantity(stats.slots),
+        entries: Quantity(stats.entries),
+        bytes: Quantity(stats.bytes),
+    })
+}
+
+/// Creates a filter that, when polled with `eth_getFilterChanges`, returns the
+/// permission changes committed since it was last polled
+pub fn new_permission_filter<T>(_params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_newPermissionFilter");
+
+    let current_block = client
+        .get_current_block_number()
+        .map_err(|err| fail!("Failed to get current block number", err))?;
+    let filter_id = client
+        .filters
+        .new_filter(Filter::Permissions, current_block)
+        .map_err(error::filter_store_failed)?
+        .ok_or_else(error::too_many_filters)?;
+
+    Ok(transform::hex_prefix(&filter_id_to_hex(filter_id)))
+}
+
 pub fn set_permissions<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
 where
     T: MessageSender,
 {
     info!("seth_setPermissions");
 
-    let usage = "Takes [address: ADDRESS, permissions: DATA]";
+    let usage = "Takes [address: ADDRESS|\"global\", permissions: DATA]";
 
     let (address, permissions): (String, String) =
         params.parse().map_err(|_| Error::invalid_params(usage))?;
+    let address = permissions_address(&address).ok_or_else(|| Error::invalid_params(usage))?;
+    let permissions = permissions
+        .parse::<EvmPermissions>()
+        .map_err(Error::invalid_params)?;
 
     let nonce = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -82,11 +551,7 @@ where
         inner.set_to(
             transform::hex_str_to_bytes(&address).ok_or_else(|| Error::invalid_params(usage))?,
         );
-        inner.set_permissions(
-            permissions
-                .parse::<EvmPermissions>()
-                .map_err(|err| fail!("Couldn't parse permissions", err))?,
-        );
+        inner.set_permissions(permissions);
         inner
     });
 
@@ -98,7 +563,988 @@ where
                 .public_key(),
             &SethTransaction::try_from(txn).ok_or_else(|| fail!("Couldn't create transaction"))?,
         )
-        .map_err(|err| fail!("Couldn't send transaction", err))?;
+        .map_err(|err| match err {
+            ClientError::TransactionRejected(..) | ClientError::TransactionDenied(_) => {
+                error::transaction_error(err)
+            }
+            err => fail!("Couldn't send transaction", err),
+        })?;
+
+    Ok(Value::Bool(true))
+}
+
+/// Returns the hash that was signed for a message, which is either data signed with
+/// `eth_sign` or EIP-712 typed data
+fn signed_hash(message: &Value, usage: &str) -> Result<[u8; 32], Error> {
+    match *message {
+        Value::String(ref data) => hex_data(data)
+            .map(|data| accounts::personal_message_hash(&data))
+            .ok_or_else(|| Error::invalid_params(usage)),
+        Value::Object(_) => typed_data::hash(message).map_err(Error::invalid_params),
+        _ => Err(Error::invalid_params(usage)),
+    }
+}
+
+fn hex_data(data: &str) -> Option<Vec<u8>> {
+    data.get(2..)
+        .filter(|hex| hex.len() % 2 == 0)
+        .and_then(transform::hex_str_to_bytes)
+}
+
+/// Returns the addresses that could have signed the message
+fn recover_addresses(message: &Value, signature: &str, usage: &str) -> Result<Vec<String>, Error> {
+    let hash = signed_hash(message, usage)?;
+    let signature = hex_data(signature).ok_or_else(|| Error::invalid_params(usage))?;
+    accounts::recover_addresses(&hash, &signature)
+        .map_err(|err| Error::invalid_params(format!("{}", err)))
+}
+
+/// Returns the address of the account that signed a message, which is hex data
+/// signed with `eth_sign` or EIP-712 typed data. The signature must end with its
+/// recovery id, since two accounts could have made a signature without one.
+pub fn recover_address<T>(params: Params, _client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_recoverAddress");
+
+    let usage = "Takes [message: DATA|OBJECT, signature: DATA(65)]";
+
+    let (message, signature): (Value, String) =
+        params.parse().map_err(|_| Error::invalid_params(usage))?;
+    let addresses = recover_addresses(&message, &signature, usage)?;
+    if addresses.len() > 1 {
+        return Err(Error::invalid_params(
+            "Signature must end with its recovery id",
+        ));
+    }
+
+    Ok(addresses
+        .first()
+        .map(|address| transform::address_value(address))
+        .unwrap_or(Value::Null))
+}
+
+/// Returns whether a message, which is hex data signed with `eth_sign` or EIP-712
+/// typed data, was signed by the given account
+pub fn verify_signature<T>(params: Params, _client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_verifySignature");
+
+    let usage = "Takes [address: ADDRESS, message: DATA|OBJECT, signature: DATA]";
+
+    let (address, message, signature): (String, Value, String) =
+        params.parse().map_err(|_| Error::invalid_params(usage))?;
+    let address = address
+        .get(2..)
+        .map(str::to_lowercase)
+        .ok_or_else(|| Error::invalid_params(usage))?;
+
+    Ok(Value::Bool(
+        recover_addresses(&message, &signature, usage)?.contains(&address),
+    ))
+}
+
+/// Registers the ABI of a contract, so that calls to it can be encoded with
+/// `seth_encodeCall` and their results decoded with `seth_decodeResult`. A
+/// contract that was upgraded is registered again with the block its new ABI was
+/// first used in.
+pub fn register_contract<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_registerContract");
+
+    let usage = "Takes [address: ADDRESS, abi: ARRAY, fromBlock: QUANTITY]";
+
+    let (address, abi, from_block): (String, Value, Option<Value>) = params
+        .clone()
+        .parse()
+        .or_else(|_| {
+            params
+                .parse()
+                .map(|(address, abi): (String, Value)| (address, abi, None))
+        })
+        .map_err(|_| Error::invalid_params(usage))?;
+    let from_block = match from_block {
+        Some(from_block) => {
+            transform::u64_from_hex_value(&from_block).map_err(|_| Error::invalid_params(usage))?
+        }
+        None => 0,
+    };
+    client
+        .contracts
+        .register(&address, &abi, from_block)
+        .map_err(Error::invalid_params)?;
+
+    Ok(Value::Bool(true))
+}
+
+/// Returns the address a name resolves to in the latest block, or null if it isn't
+/// registered
+pub fn resolve_name<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_resolveName");
+
+    let usage = "Takes [name: STRING]";
+
+    let (name,): (String,) = params.parse().map_err(|_| Error::invalid_params(usage))?;
+    let block = names::latest_block(&client)?;
+    Ok(names::resolve(&client, &name, &block)?
+        .map(Value::String)
+        .unwrap_or(Value::Null))
+}
+
+/// Returns the name of an address in the latest block, or null if it has none that
+/// resolves back to it
+pub fn lookup_address<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_lookupAddress");
+
+    let usage = "Takes [address: ADDRESS]";
+
+    let (address,): (String,) = params.parse().map_err(|_| Error::invalid_params(usage))?;
+    let block = names::latest_block(&client)?;
+    Ok(names::lookup(&client, &address, &block)?
+        .map(Value::String)
+        .unwrap_or(Value::Null))
+}
+
+/// Returns the contract that serves the calls to an address, following the minimal
+/// proxies (clones) and the EIP-1967, EIP-1822 and ZeppelinOS proxies in the way,
+/// along with those proxies. The implementation is null if the address isn't a
+/// proxy, and the result null if the block doesn't exist.
+pub fn get_clone_implementations<T>(
+    params: Params,
+    client: ValidatorClient<T>,
+) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_getCloneImplementations");
+
+    let usage = "Takes [address: ADDRESS, block: QUANTITY|TAG]";
+
+    let (address, block): (String, Option<String>) = params
+        .clone()
+        .parse()
+        .or_else(|_| params.parse().map(|(address,): (String,)| (address, None)))
+        .map_err(|_| Error::invalid_params(usage))?;
+    let key = validate_block_key(block.as_ref().map_or("latest", String::as_str))?;
+    let address = validate_account_address(&names::resolve_address(&client, &address)?)?;
+    let block = match client.get_block(key) {
+        Ok(block) => block,
+        Err(ClientError::NoResource) => return Ok(Value::Null),
+        Err(error) => return Err(fail!("Couldn't get block", error)),
+    };
+
+    let proxies = proxies::resolve(&client, &address, &block)?;
+    let mut result = Map::new();
+    result.insert(
+        String::from("implementation"),
+        proxies
+            .last()
+            .map(|proxy| transform::address_value(&proxy.implementation))
+            .unwrap_or(Value::Null),
+    );
+    result.insert(
+        String::from("proxies"),
+        Value::Array(proxies.iter().map(|proxy| proxy.to_value()).collect()),
+    );
+    Ok(Value::Object(result))
+}
+
+/// Returns the input data of a call to a function of a registered contract, given
+/// its name, or signature if it is overloaded, and its arguments
+pub fn encode_call<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_encodeCall");
+
+    let usage = "Takes [address: ADDRESS, function: STRING, args: ARRAY]";
+
+    let (address, function, args): (String, String, Vec<Value>) =
+        params.parse().map_err(|_| Error::invalid_params(usage))?;
+    let data = client
+        .contracts
+        .function(&address, &function, None)
+        .and_then(|function| function.encode_call(&args))
+        .map_err(Error::invalid_params)?;
+
+    Ok(transform::hex_prefix(&transform::bytes_to_hex_str(&data)))
+}
+
+/// Decodes the data returned by a call to a function of a registered contract into
+/// a list of its outputs, with the ABI the contract had at the block the call was
+/// made in, or has now if no block is given. Integers are returned as decimal
+/// strings.
+pub fn decode_result<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_decodeResult");
+
+    let usage = "Takes [address: ADDRESS, function: STRING, data: DATA, block: QUANTITY|TAG]";
+
+    let (address, function, data, block): (String, String, String, Option<String>) = params
+        .clone()
+        .parse()
+        .or_else(|_| {
+            params
+                .parse()
+                .map(|(address, function, data): (String, String, String)| {
+                    (address, function, data, None)
+                })
+        })
+        .map_err(|_| Error::invalid_params(usage))?;
+    let data = hex_data(&data).ok_or_else(|| Error::invalid_params(usage))?;
+    let block = match block {
+        Some(block) => block_number(&client, validate_block_key(&block)?)?,
+        None => None,
+    };
+
+    client
+        .contracts
+        .function(&address, &function, block)
+        .and_then(|function| function.decode_result(&data))
+        .map_err(Error::invalid_params)
+}
+
+/// Returns the number of a block, or None for the latest block
+fn block_number<T>(client: &ValidatorClient<T>, block: BlockKey) -> Result<Option<u64>, Error>
+where
+    T: MessageSender,
+{
+    match block {
+        BlockKey::Latest | BlockKey::Pending => Ok(None),
+        BlockKey::Earliest => Ok(Some(0)),
+        BlockKey::Number(block_num) => Ok(Some(block_num)),
+        BlockKey::Finalized => client
+            .finalized_block_num()
+            .map(Some)
+            .map_err(|err| fail!("Couldn't get the finalized block", err)),
+        block => client
+            .get_block(block)
+            .and_then(|block| client::block_num(&block))
+            .map(Some)
+            .map_err(|err| fail!("Couldn't get block", err)),
+    }
+}
+
+/// Sends transactions from one account that the validator must run in the given
+/// order, taking the same objects as `eth_sendTransaction`. Transactions without a
+/// `nonce` are numbered on from the account's nonce. Returns their hashes.
+pub fn submit_ordered<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_submitOrdered");
+
+    let usage = "Takes [txns: ARRAY]";
+
+    let (txns,): (Vec<Map<String, Value>>,) =
+        params.parse().map_err(|_| Error::invalid_params(usage))?;
+    if txns.is_empty() || txns.len() > MAX_ORDERED_TRANSACTIONS {
+        return Err(Error::invalid_params(format!(
+            "Takes between 1 and {} transactions",
+            MAX_ORDERED_TRANSACTIONS
+        )));
+    }
+
+    let txns = txns
+        .iter()
+        .enumerate()
+        .map(|(index, txn)| transaction::parse_transaction(&client, txn, index as u64))
+        .collect::<Result<Vec<_>, Error>>()?;
+    let from = txns[0].0.clone();
+    if txns.iter().any(|(txn_from, _, _)| *txn_from != from) {
+        return Err(Error::invalid_params(
+            "All transactions must be sent from the same account",
+        ));
+    }
+
+    let txn_ids = client
+        .send_ordered_transactions(
+            &from,
+            &txns
+                .iter()
+                .map(|(_, txn, access_list)| (txn, access_list.as_deref()))
+                .collect::<Vec<_>>(),
+        )
+        .map_err(error::transaction_error)?;
+
+    Ok(Value::Array(
+        txn_ids
+            .iter()
+            .map(|txn_id| transform::hex_prefix(txn_id))
+            .collect(),
+    ))
+}
+
+/// Blocks until the receipt for the given transaction is available or the timeout
+/// (in seconds) elapses, returning the same object as `eth_getTransactionReceipt`,
+/// or null if the transaction still hasn't been committed
+pub fn wait_for_transaction_receipt<T>(
+    params: Params,
+    client: ValidatorClient<T>,
+) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_waitForTransactionReceipt");
+
+    let usage = "Takes [txnHash: DATA(64), timeout: INTEGER]";
+
+    let (txn_hash, timeout): (String, Option<u64>) = params
+        .clone()
+        .parse()
+        .or_else(|_| params.parse().map(|(t,): (String,)| (t, None)))
+        .map_err(|_| Error::invalid_params(usage))?;
+    let txn_id = txn_hash
+        .get(2..)
+        .ok_or_else(|| Error::invalid_params("Invalid transaction hash, must have 0x"))?;
+
+    let timeout = cmp::min(
+        timeout.unwrap_or(DEFAULT_RECEIPT_TIMEOUT),
+        MAX_RECEIPT_TIMEOUT,
+    );
+    let deadline = Instant::now() + Duration::from_secs(timeout);
+
+    loop {
+        let receipt = transaction::get_receipt_obj(&client, txn_id)?;
+
+        if !receipt.is_null() || Instant::now() >= deadline {
+            return Ok(receipt);
+        }
+
+        thread::sleep(Duration::from_millis(RECEIPT_POLL_INTERVAL_MS));
+    }
+}
+
+/// Returns the headers of up to `count` consecutive blocks from `fromBlock`, for light
+/// clients that follow the chain without fetching block bodies. Each header is the
+/// serialized `BlockHeader` the validator signed, which holds the state root and the
+/// signer's public key, so it can be checked without trusting this server. They are
+/// concatenated as a 4 byte big-endian length, the header and its 64 byte signature.
+/// Fewer headers are returned when the chain doesn't reach `count` blocks.
+pub fn get_block_headers<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_getBlockHeaders");
+
+    let usage = "Takes [fromBlock: QUANTITY, count: QUANTITY]";
+
+    let (from, count): (Value, Value) = params.parse().map_err(|_| Error::invalid_params(usage))?;
+    let from = transform::u64_from_hex_value(&from).map_err(|_| Error::invalid_params(usage))?;
+    let count = transform::u64_from_hex_value(&count).map_err(|_| Error::invalid_params(usage))?;
+
+    let mut headers = Vec::new();
+    let count = cmp::min(count, MAX_BLOCK_HEADERS);
+    let range = match count {
+        0 => None,
+        count => Some(client.get_blocks_range(from, from.saturating_add(count - 1))),
+    };
+    for block in range.into_iter().flatten() {
+        let block = match block {
+            Ok(block) => block,
+            Err(ClientError::NoResource) => break,
+            Err(err) => return Err(fail!("Couldn't get block", err)),
+        };
+        let signature = transform::hex_str_to_bytes(&block.header_signature)
+            .ok_or_else(|| fail!("Block has a malformed signature", block.header_signature))?;
+        headers.extend_from_slice(&(block.header.len() as u32).to_be_bytes());
+        headers.extend_from_slice(&block.header);
+        headers.extend_from_slice(&signature);
+    }
+
+    Ok(transform::hex_prefix(&transform::bytes_to_hex_str(
+        &headers,
+    )))
+}
+
+/// Returns the batches of consecutive blocks as a serialized BatchList, the format
+/// `sawtooth batch submit` reads, so that another network can be rebuilt from them.
+/// Blocks are added until the response would be too large, but the first block is
+/// always returned; `blocks` is how many were.
+pub fn export_batches<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_exportBatches");
+
+    let usage = "Takes [fromBlock: QUANTITY, count: QUANTITY]";
+
+    let (from, count): (Value, Value) = params.parse().map_err(|_| Error::invalid_params(usage))?;
+    let from = transform::u64_from_hex_value(&from).map_err(|_| Error::invalid_params(usage))?;
+    let count = transform::u64_from_hex_value(&count).map_err(|_| Error::invalid_params(usage))?;
+
+    // Each byte is returned as two hex digits
+    let budget = client.max_response_size / 2;
+
+    let mut batches = Vec::new();
+    let mut blocks: u64 = 0;
+    let count = cmp::min(count, MAX_EXPORTED_BLOCKS);
+    let range = match count {
+        0 => None,
+        count => Some(client.get_blocks_range(from, from.saturating_add(count - 1))),
+    };
+    for block in range.into_iter().flatten() {
+        let mut block = match block {
+            Ok(block) => block,
+            Err(ClientError::NoResource) => break,
+            Err(err) => return Err(fail!("Couldn't get block", err)),
+        };
+        let mut batch_list = BatchList::new();
+        batch_list.set_batches(block.take_batches());
+        let bytes = protobuf::Message::write_to_bytes(&batch_list)
+            .map_err(|err| fail!("Couldn't serialize batches", err))?;
+        if blocks > 0 && batches.len() + bytes.len() > budget {
+            break;
+        }
+        // Serialized lists append to one list holding the batches of both
+        batches.extend_from_slice(&bytes);
+        blocks += 1;
+    }
+
+    Ok(transform::to_value(&types::ExportedBatches {
+        batches: format!("0x{}", transform::bytes_to_hex_str(&batches)),
+        blocks: Quantity(blocks),
+    }))
+}
+
+/// Submits batches exported by `seth_exportBatches`, as they were signed, and waits
+/// for each to be validated in turn. Returns their ids, or the error of the first
+/// one that was rejected.
+pub fn import_batches<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_importBatches");
+
+    let usage = "Takes [batches: DATA]";
+
+    let (batches,): (Value,) = params.parse().map_err(|_| Error::invalid_params(usage))?;
+    let batches = transform::bytes_from_hex_value(&batches)?;
+    let mut batch_list: BatchList = protobuf::parse_from_bytes(&batches)
+        .map_err(|_| Error::invalid_params("Batches must be a serialized BatchList"))?;
+    let batches = batch_list.take_batches().into_vec();
+    if batches.is_empty() || batches.len() > MAX_IMPORTED_BATCHES {
+        return Err(Error::invalid_params(format!(
+            "Takes between 1 and {} batches",
+            MAX_IMPORTED_BATCHES
+        )));
+    }
+
+    let batch_ids: Vec<Value> = batches
+        .iter()
+        .map(|batch| transform::hex_prefix(&batch.header_signature))
+        .collect();
+    client
+        .submit_batches(batches)
+        .map_err(error::transaction_error)?;
+
+    Ok(Value::Array(batch_ids))
+}
+
+/// Returns every account in the state of a block with its balance, nonce, code,
+/// storage and permissions, keyed by address as the `alloc` of a geth genesis file
+/// is, so that a test network can be migrated to a new one with `seth_importState`.
+/// This reads every entry in the seth namespace, so it is slow on networks with a lot
+/// of state.
+pub fn export_state<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_exportState");
+
+    let usage = "Takes [block: QUANTITY|TAG]";
+
+    let block = match params {
+        Params::None => BlockKey::Latest,
+        Params::Array(ref values) if values.is_empty() => BlockKey::Latest,
+        params => {
+            let (block,): (String,) = params.parse().map_err(|_| Error::invalid_params(usage))?;
+            validate_block_key(&block)?
+        }
+    };
+
+    // Every entry is read from the block's state root, in case a block is committed
+    let block = client
+        .get_block(block)
+        .map_err(|err| fail!("Couldn't get block", err))?;
+    let (block_number, state_root) = client::block_num(&block)
+        .and_then(|block_number| client::state_root(&block).map(|root| (block_number, root)))
+        .map_err(|err| fail!("Couldn't parse block", err))?;
+    let accounts = client
+        .get_state_snapshot(BlockKey::StateRoot(state_root.clone()))
+        .map_err(|err| fail!("Couldn't read state", err))?;
+
+    let hex = |bytes: &[u8]| format!("0x{}", transform::bytes_to_hex_str(bytes));
+    let alloc = accounts
+        .into_iter()
+        .map(|mut snapshot| {
+            let account = snapshot.take_account();
+            let genesis_account = types::GenesisAccount {
+                balance: types::Wei::from(account.balance as u64),
+                nonce: Quantity(account.nonce),
+                code: Some(&account.code)
+                    .filter(|code| !code.is_empty())
+                    .map(|code| hex(code)),
+                storage: snapshot
+                    .take_storage()
+                    .into_iter()
+                    .map(|pair| (hex(&pair.key), hex(&client::pad_word(&pair.value))))
+                    .collect(),
+                permissions: Some(account.get_permissions())
+                    .filter(|_| account.has_permissions())
+                    .map(|permissions| format!("{}", permissions)),
+            };
+            (hex(&account.address), genesis_account)
+        })
+        .collect();
+
+    Ok(transform::to_value(&types::StateSnapshot {
+        block_number: Quantity(block_number),
+        state_root: format!("0x{}", state_root),
+        alloc,
+    }))
+}
+
+/// Creates the accounts of a state snapshot from `seth_exportState`, each with its
+/// state and storage, in IMPORT_ACCOUNT transactions sent in order from the unlocked
+/// account, which must have the root permission. None of the accounts may exist
+/// yet. Returns the transaction hashes.
+pub fn import_state<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_importState");
+
+    let usage = "Takes [alloc: OBJECT]";
+
+    let (alloc,): (BTreeMap<String, types::GenesisAccount>,) =
+        params.parse().map_err(|_| Error::invalid_params(usage))?;
+    if alloc.is_empty() || alloc.len() > MAX_IMPORTED_ACCOUNTS {
+        return Err(Error::invalid_params(format!(
+            "Takes between 1 and {} accounts",
+            MAX_IMPORTED_ACCOUNTS
+        )));
+    }
+    let accounts = alloc
+        .into_iter()
+        .map(|(address, account)| imported_account(&address, account))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let (from, nonce) = root_sender(&client)?;
+    let txns: Vec<SethTransaction> = accounts
+        .into_iter()
+        .zip(nonce..)
+        .map(|(account, nonce)| {
+            let mut txn = ImportAccountTxn::new();
+            txn.set_nonce(nonce);
+            txn.set_account(account);
+            SethTransaction::ImportAccount(txn)
+        })
+        .collect();
+
+    let txn_ids = client
+        .send_ordered_transactions(
+            &from,
+            &txns.iter().map(|txn| (txn, None)).collect::<Vec<_>>(),
+        )
+        .map_err(error::transaction_error)?;
+
+    Ok(Value::Array(
+        txn_ids
+            .iter()
+            .map(|txn_id| transform::hex_prefix(txn_id))
+            .collect(),
+    ))
+}
+
+/// Returns an account of a state snapshot as an IMPORT_ACCOUNT transaction holds it
+fn imported_account(
+    address: &str,
+    account: types::GenesisAccount,
+) -> Result<ArchivedContract, Error> {
+    let invalid =
+        |field: &str| Error::invalid_params(format!("Invalid {} of account {}", field, address));
+    let from_hex = |hex: &str| {
+        Some(hex.strip_prefix("0x").unwrap_or(hex))
+            .filter(|digits| digits.len().is_multiple_of(2))
+            .and_then(transform::hex_str_to_bytes)
+    };
+
+    let address_bytes = from_hex(address)
+        .filter(|bytes| bytes.len() == 20)
+        .ok_or_else(|| invalid("address"))?;
+    if account.balance.0 > types::U256::from(i64::MAX as u64) {
+        return Err(invalid("balance"));
+    }
+
+    let mut state = EvmStateAccount::new();
+    state.set_address(address_bytes);
+    state.set_balance(account.balance.0.low_u64() as i64);
+    state.set_nonce(account.nonce.0);
+    if let Some(code) = account.code {
+        state.set_code(from_hex(&code).ok_or_else(|| invalid("code"))?);
+    }
+    if let Some(permissions) = account.permissions {
+        state.set_permissions(
+            permissions
+                .parse::<EvmPermissions>()
+                .map_err(|_| invalid("permissions"))?,
+        );
+    }
+
+    let mut imported = ArchivedContract::new();
+    imported.set_account(state);
+    for (key, value) in account.storage {
+        let mut pair = EvmStorage::new();
+        pair.set_key(
+            from_hex(&key)
+                .filter(|key| key.len() <= 32)
+                .map(|key| client::pad_word(&key))
+                .ok_or_else(|| invalid("storage"))?,
+        );
+        pair.set_value(
+            from_hex(&value)
+                .filter(|value| value.len() <= 32)
+                .map(|value| client::pad_word(&value))
+                .ok_or_else(|| invalid("storage"))?,
+        );
+        imported.mut_storage().push(pair);
+    }
+    Ok(imported)
+}
+
+/// Returns the consensus algorithm the network is configured to run, the public keys
+/// of its members if it has a fixed membership, and the endpoints of the validators
+/// the connected validator peers with, so that operators can check on consensus
+/// without a REST API
+pub fn get_consensus_info<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_getConsensusInfo");
+
+    let usage = "Takes [block: QUANTITY|TAG]";
+
+    let block = match params {
+        Params::None => BlockKey::Latest,
+        params => {
+            let (block,): (String,) = params.parse().map_err(|_| Error::invalid_params(usage))?;
+            validate_block_key(&block)?
+        }
+    };
+
+    let consensus = client
+        .get_consensus_info(block)
+        .map_err(|err| fail!("Couldn't get consensus settings", err))?;
+    let peers = client
+        .get_peer_endpoints()
+        .map_err(|err| fail!("Couldn't get peers", err))?;
+
+    Ok(transform::to_value(&types::ConsensusInfo {
+        name: consensus.name,
+        version: consensus.version,
+        members: consensus.members,
+        peers,
+    }))
+}
+
+/// Returns the log index, which keeps the watchlists
+#[cfg(feature = "indexer")]
+fn watchlist_index<T>(client: &ValidatorClient<T>) -> Result<&LogIndex, Error>
+where
+    T: MessageSender,
+{
+    client
+        .log_index
+        .as_ref()
+        .ok_or_else(|| fail!("Watchlists need seth-rpc to be started with --log-index"))
+}
+
+/// Replaces the addresses on a watchlist, or removes it if none are given. The
+/// index records what the addresses do in the blocks it indexes from then on.
+#[cfg(feature = "indexer")]
+pub fn set_watchlist<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_setWatchlist");
+
+    let usage = "Takes [name: STRING, addresses: [ADDRESS]]";
+
+    let (name, addresses): (String, Vec<String>) =
+        params.parse().map_err(|_| Error::invalid_params(usage))?;
+    let addresses = addresses
+        .iter()
+        .map(|address| transform::parse_address(address))
+        .collect::<Result<Vec<String>, Error>>()?;
+
+    watchlist_index(&client)?
+        .set_watchlist(&name, &addresses)
+        .map_err(|err| fail!("Couldn't save watchlist", err))?;
 
     Ok(Value::Bool(true))
 }
+
+#[cfg(feature = "indexer")]
+pub fn get_watchlists<T>(_params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_getWatchlists");
+
+    let watchlists = watchlist_index(&client)?
+        .watchlists()
+        .map_err(|err| fail!("Couldn't read watchlists", err))?;
+
+    Ok(Value::Object(
+        watchlists
+            .into_iter()
+            .map(|(name, addresses)| {
+                let addresses = addresses
+                    .iter()
+                    .map(|address| Value::String(format!("0x{}", address)))
+                    .collect();
+                (name, Value::Array(addresses))
+            })
+            .collect(),
+    ))
+}
+
+/// Reports what a watched address did in the blocks the index has, from the
+/// precomputed history rather than by scanning the blocks
+#[cfg(feature = "indexer")]
+pub fn get_watched_address_report<T>(
+    params: Params,
+    client: ValidatorClient<T>,
+) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_getWatchedAddressReport");
+
+    let usage = "Takes [address: ADDRESS, fromBlock: QUANTITY, toBlock: QUANTITY]";
+
+    let args: Vec<Option<String>> = params.parse().map_err(|_| Error::invalid_params(usage))?;
+    let address = match args.first() {
+        Some(Some(address)) if args.len() <= 3 => transform::parse_address(address)?,
+        _ => return Err(Error::invalid_params(usage)),
+    };
+    let block = |position: usize, default: u64| match args.get(position) {
+        Some(Some(block)) => transform::u64_from_hex_value(&Value::String(block.clone())),
+        _ => Ok(default),
+    };
+    let from = block(1, 0)?;
+    let to = block(2, u64::MAX)?;
+
+    let index = watchlist_index(&client)?;
+    let report = reports::address_report(index, &address, from, to)
+        .map_err(|err| fail!("Couldn't read watched address", err))?;
+    Ok(transform::to_value(&report))
+}
+
+/// Reports what watched addresses did in the blocks whose transactions ran between
+/// two times, signed by one of the server's accounts so that the report can be
+/// archived and later shown not to have changed
+#[cfg(feature = "indexer")]
+pub fn get_activity_report<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_getActivityReport");
+
+    let usage = "Takes [addresses: ARRAY, fromTime: QUANTITY, toTime: QUANTITY, signer: ADDRESS]";
+
+    let (addresses, from_time, to_time, signer): (Vec<String>, Value, Value, String) =
+        params.parse().map_err(|_| Error::invalid_params(usage))?;
+    let addresses = addresses
+        .iter()
+        .map(|address| transform::parse_address(address))
+        .collect::<Result<Vec<String>, Error>>()?;
+    let from_time = transform::u64_from_hex_value(&from_time)?;
+    let to_time = transform::u64_from_hex_value(&to_time)?;
+    let signer = transform::parse_address(&signer)?;
+
+    let index = watchlist_index(&client)?;
+    let watched = index
+        .watched()
+        .map_err(|err| fail!("Couldn't read watchlists", err))?;
+    if let Some(address) = addresses.iter().find(|address| !watched.contains(*address)) {
+        return Err(Error::invalid_params(format!(
+            "Address 0x{} isn't on any watchlist",
+            address
+        )));
+    }
+    let account = client
+        .loaded_accounts()
+        .read()
+        .unwrap()
+        .iter()
+        .find(|account| account.address() == signer)
+        .cloned()
+        .ok_or_else(|| {
+            Error::invalid_params(format!("Account with address `{}` not found.", signer))
+        })?;
+
+    let report = reports::activity_report(index, &addresses, from_time, to_time)
+        .map_err(|err| fail!("Couldn't read watched addresses", err))?;
+    let signed =
+        reports::sign(&report, &account).map_err(|err| fail!("Couldn't sign report", err))?;
+    Ok(transform::to_value(&signed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use contracts::ContractRegistry;
+    use crypto::digest::Digest;
+    use crypto::sha2::Sha256;
+    use filters::FilterManager;
+    use mock_chain::MockChain;
+    use sawtooth_sdk::messages::block::BlockHeader;
+    use serde_json;
+
+    fn client() -> ValidatorClient<MockChain> {
+        ValidatorClient::new(
+            MockChain::new(),
+            Vec::new(),
+            FilterManager::new(1),
+            0,
+            String::from("1.0"),
+            ContractRegistry::new(),
+        )
+    }
+
+    fn params(json: &str) -> Params {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn reads_the_addresses_permissions_are_set_on() {
+        assert_eq!(
+            permissions_address("global"),
+            Some(String::from(client::GLOBAL_PERMISSIONS_ADDRESS))
+        );
+        let address = "0x5AAEB6053F3E94C9B9A09F33669435E7EF1BEAED";
+        assert_eq!(
+            permissions_address(address),
+            Some(address[2..].to_lowercase())
+        );
+        assert_eq!(
+            permissions_address(&address[2..]),
+            Some(address[2..].to_lowercase())
+        );
+        assert_eq!(permissions_address(&address[..40]), None);
+        assert_eq!(permissions_address(&address.replace('A', "G")), None);
+    }
+
+    #[test]
+    fn negotiates_the_latest_version_both_sides_know() {
+        let version = |json| {
+            negotiate_version(params(json), client())
+                .map(|negotiated| negotiated["version"].clone())
+        };
+        let latest = Value::from(*versions::VERSIONS.last().unwrap());
+
+        assert_eq!(version("[]"), Ok(latest.clone()));
+        assert_eq!(version(r#"[["v0", "v1"]]"#), Ok(latest));
+        assert!(version(r#"[["v0"]]"#).is_err());
+        assert!(version(r#"["v1"]"#).is_err());
+    }
+
+    #[test]
+    fn recovers_the_account_that_signed_a_message() {
+        let accounts = MockChain::accounts();
+        let (alice, bob) = (&accounts[0], &accounts[1]);
+        // Accounts sign the SHA-256 hash of what they sign
+        let mut sha = Sha256::new();
+        sha.input(&accounts::personal_message_hash(b"hello"));
+        let mut hash = [0; 32];
+        sha.result(&mut hash);
+        let signature = alice.sign_hash(&hash).unwrap();
+        let signed = |address: &str| {
+            format!(
+                r#"["0x{}", "0x68656c6c6f", "0x{}"]"#,
+                address,
+                transform::bytes_to_hex_str(&signature)
+            )
+        };
+
+        assert_eq!(
+            recover_address(
+                params(&format!(
+                    r#"["0x68656c6c6f", "0x{}"]"#,
+                    transform::bytes_to_hex_str(&signature)
+                )),
+                client()
+            ),
+            Ok(transform::address_value(alice.address()))
+        );
+        assert_eq!(
+            verify_signature(params(&signed(alice.address())), client()),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(
+            verify_signature(params(&signed(bob.address())), client()),
+            Ok(Value::Bool(false))
+        );
+        // Data must be hex with an even number of digits
+        assert!(verify_signature(
+            params(&signed(alice.address()).replace("0x68656c6c6f", "0x68656c6c6")),
+            client()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn reports_and_resets_the_nonces_of_an_account() {
+        let client = client();
+        let alice = format!(r#"["0x{}"]"#, MockChain::accounts()[0].address());
+
+        let status = get_nonce_status(params(&alice), client.clone()).unwrap();
+        assert_eq!(status["nonce"], Value::from("0x2"));
+        assert_eq!(status["pendingNonce"], Value::from("0x2"));
+        assert_eq!(status["tracked"], Value::Array(Vec::new()));
+        assert_eq!(
+            reset_nonces(params(&alice), client.clone()),
+            Ok(Value::from("0x0"))
+        );
+        assert!(get_nonce_status(params(r#"["0x1234"]"#), client).is_err());
+    }
+
+    #[test]
+    fn returns_block_headers_up_to_the_head() {
+        let headers = |json| {
+            let value = get_block_headers(params(json), client()).unwrap();
+            let mut bytes = transform::hex_str_to_bytes(&value.as_str().unwrap()[2..]).unwrap();
+            let mut numbers = Vec::new();
+            while !bytes.is_empty() {
+                let mut length = [0; 4];
+                length.copy_from_slice(&bytes[..4]);
+                let length = u32::from_be_bytes(length) as usize;
+                let header: BlockHeader =
+                    protobuf::Message::parse_from_bytes(&bytes[4..4 + length]).unwrap();
+                numbers.push(header.block_num);
+                bytes.drain(..4 + length + 64);
+            }
+            numbers
+        };
+
+        assert_eq!(headers(r#"["0x1", "0x2"]"#), vec![1, 2]);
+        // The chain ends at block 3
+        assert_eq!(headers(r#"["0x2", "0x5"]"#), vec![2, 3]);
+        assert!(headers(r#"["0x1", "0x0"]"#).is_empty());
+    }
+}
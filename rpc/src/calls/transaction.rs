@@ -15,22 +15,43 @@
  * ------------------------------------------------------------------------------
  */
 
+use accounts;
+use cache::CallCache;
+use calls::account::validate_block_key;
+use calls::logs::get_block_logs;
+use client;
 use client::{BlockKey, Error as ClientError, ValidatorClient};
 use error;
 use jsonrpc_core::{Error, ErrorCode, Params, Value};
+#[cfg(feature = "ledger")]
+use ledger::{Ledger, LedgerAccount};
 use messages::seth::{
     CreateContractAccountTxn as CreateContractAccountTxnPb, MessageCallTxn as MessageCallTxnPb,
 };
+use names;
+use primitive_types::{H160, H256, U256};
 use protobuf;
+#[cfg(feature = "ledger")]
+use raw_transaction;
+use raw_transaction::RawTransaction;
 use requests::RequestHandler;
-use sawtooth_sdk::messages::block::BlockHeader;
+use sawtooth_sdk::messages::block::{Block, BlockHeader};
 use sawtooth_sdk::messaging::stream::MessageSender;
 use serde_json::Map;
 use std::str::FromStr;
-use tiny_keccak;
-use transactions::{SethTransaction, TransactionKey};
+use transactions::{AccessListEntry, SethTransaction, Transaction, TransactionKey};
 use transform;
 use transform::{make_txn_obj, make_txn_obj_no_block, make_txn_receipt_obj};
+#[cfg(feature = "indexer")]
+use types::{Quantity, SyncProgress, SyncStatus};
+use vm;
+use vm::CallError;
+
+/// How much more gas than a call used `eth_estimateGas` returns
+const ESTIMATE_MARGIN_PERCENT: u64 = 20;
+
+/// The most blocks `eth_feeHistory` reports on at once
+const MAX_FEE_HISTORY_BLOCKS: u64 = 1024;
 
 pub fn get_method_list<T>() -> Vec<(String, RequestHandler<T>)>
 where
@@ -40,6 +61,8 @@ where
         ("eth_call".into(), call),
         ("eth_estimateGas".into(), estimate_gas),
         ("eth_gasPrice".into(), gas_price),
+        ("eth_maxPriorityFeePerGas".into(), max_priority_fee_per_gas),
+        ("eth_feeHistory".into(), fee_history),
         (
             "eth_getTransactionByBlockHashAndIndex".into(),
             get_transaction_by_block_hash_and_index,
@@ -66,11 +89,88 @@ where
         .parse()
         .map_err(|_| Error::invalid_params("Takes [txn: OBJECT]"))?;
 
+    let (from, txn, access_list) = parse_transaction(&client, &txn, 0)?;
+    #[cfg(feature = "ledger")]
+    {
+        if let Some(ledger) = client.ledger.clone() {
+            if let Some(account) = ledger.account(&from) {
+                if access_list.is_some() {
+                    return Err(Error::invalid_params(
+                        "Transactions signed by a Ledger can't have an `accessList`",
+                    ));
+                }
+                let raw = sign_with_ledger(&client, &ledger, account, &txn)?;
+                return relay_raw_transaction(&client, raw);
+            }
+        }
+    }
+    let txn_signature = client
+        .send_transaction_with_access_list(&from, &txn, access_list.as_deref())
+        .map_err(error::transaction_error)?;
+
+    Ok(transform::hex_prefix(&txn_signature))
+}
+
+/// Has a Ledger sign a transaction from one of its accounts as a legacy Ethereum
+/// transaction for the client's chain, which is then relayed like a raw transaction
+#[cfg(feature = "ledger")]
+fn sign_with_ledger<T>(
+    client: &ValidatorClient<T>,
+    ledger: &Ledger,
+    account: &LedgerAccount,
+    txn: &SethTransaction,
+) -> Result<RawTransaction, Error>
+where
+    T: MessageSender,
+{
+    let not_ethereum = || {
+        Error::invalid_params(
+            "A Ledger only signs message calls and contract creations that aren't \
+             private and have no `salt`",
+        )
+    };
+    let chain_id = client
+        .get_chain_id(BlockKey::Latest)
+        .map_err(|error| fail!("Couldn't get chain id", error))?;
+    let unsigned =
+        raw_transaction::encode_legacy(txn, chain_id, &[], &[]).ok_or_else(not_ethereum)?;
+    let (v, r, s) = ledger
+        .sign_transaction(account, &unsigned)
+        .map_err(|error| fail!(format!("Couldn't sign with the Ledger: {}", error)))?;
+
+    // The device gives back the low byte of chain_id * 2 + 35 plus the parity
+    let parity = v.wrapping_sub(chain_id.wrapping_mul(2).wrapping_add(35) as u8);
+    if parity > 1 {
+        return Err(fail!(format!("The Ledger signed with an invalid v: {}", v)));
+    }
+    let signed = raw_transaction::encode_legacy(txn, chain_id * 2 + 35 + u64::from(parity), &r, &s)
+        .ok_or_else(not_ethereum)?;
+    let raw = RawTransaction::decode(&signed)
+        .map_err(|error| fail!("The Ledger signed an invalid transaction", error))?;
+    if raw.sender() != account.address() {
+        return Err(fail!("The Ledger signed with another key"));
+    }
+    Ok(raw)
+}
+
+/// Parses a transaction object into the address it is sent from, the transaction and
+/// its access list. Without a `nonce`, the transaction is given the nonce of the
+/// account plus `nonce_offset`, for transactions that follow others not yet committed.
+pub fn parse_transaction<T>(
+    client: &ValidatorClient<T>,
+    txn: &Map<String, Value>,
+    nonce_offset: u64,
+) -> Result<(String, SethTransaction, Option<Vec<AccessListEntry>>), Error>
+where
+    T: MessageSender,
+{
+    let txn = &names::resolve_fields(client, txn, &["from", "to"])?;
+
     // Required arguments
-    let from = transform::get_string_from_map(&txn, "from")
+    let from = transform::get_string_from_map(txn, "from")
         .map_err(|_| Error::new(ErrorCode::ParseError))
         .and_then(|f| f.ok_or_else(|| Error::invalid_params("`from` not set")))?;
-    let data = transform::get_bytes_from_map(&txn, "data")
+    let data = transform::get_bytes_from_map(txn, "data")
         .map_err(|_| Error::new(ErrorCode::ParseError))
         .and_then(|f| f.ok_or_else(|| Error::invalid_params("`data` not set")))?;
     let txn_count = match client.get_account(&from, BlockKey::Latest) {
@@ -86,25 +186,76 @@ where
 
     // Optional Arguments
     let to =
-        transform::get_bytes_from_map(&txn, "to").map_err(|_| Error::new(ErrorCode::ParseError))?;
-    let gas = transform::get_u64_from_map(&txn, "gas")
+        transform::get_bytes_from_map(txn, "to").map_err(|_| Error::new(ErrorCode::ParseError))?;
+    let gas = transform::get_u64_from_map(txn, "gas")
         .map(|g| g.unwrap_or(90_000))
         .map_err(|_| Error::new(ErrorCode::ParseError))?;
-    let gas_price = transform::get_u64_from_map(&txn, "gasPrice")
-        .map(|g| g.unwrap_or(10_000_000_000_000))
-        .map_err(|_| Error::new(ErrorCode::ParseError))?;
-    let value = transform::get_u64_from_map(&txn, "value")
-        .map(|g| g.unwrap_or(0))
-        .map_err(|_| Error::new(ErrorCode::ParseError))?;
-    let nonce = transform::get_u64_from_map(&txn, "nonce")
-        .map(|g| g.unwrap_or(txn_count))
+    let gas_price = transform::get_u256_from_map(txn, "gasPrice")
+        .map(|g| g.unwrap_or_else(|| U256::from(10_000_000_000_000u64)))
+        .map_err(|_| Error::new(ErrorCode::ParseError))
+        .and_then(|g| to_seth_amount(g, "gasPrice"))?;
+    let value = transform::get_u256_from_map(txn, "value")
+        .map(Option::unwrap_or_default)
+        .map_err(|_| Error::new(ErrorCode::ParseError))
+        .and_then(|v| to_seth_amount(v, "value"))?;
+    let nonce = transform::get_u64_from_map(txn, "nonce")
+        .map(|g| g.unwrap_or(txn_count + nonce_offset))
         .map_err(|_| Error::new(ErrorCode::ParseError))?;
+    let private_for = get_private_for(txn)?;
+    let access_list = get_access_list(txn)?;
+    let salt = transform::get_bytes_from_map(txn, "salt")
+        .map_err(|_| Error::new(ErrorCode::ParseError))?
+        .unwrap_or_default();
+    if !salt.is_empty() {
+        if salt.len() != 32 {
+            return Err(Error::invalid_params("`salt` must be 32 bytes"));
+        }
+        if to.is_some() {
+            return Err(Error::invalid_params(
+                "`salt` can only be given when creating a contract",
+            ));
+        }
+        if private_for.is_some() {
+            return Err(Error::invalid_params(
+                "Private contracts can't be created with a `salt`",
+            ));
+        }
+    }
+
+    // The payload of a private transaction is sent to the privacy manager, and only its
+    // hash is put on chain
+    let (data, private_payload_hash) = match private_for {
+        #[cfg(feature = "privacy")]
+        Some(private_for) => {
+            let private_from = transform::get_string_from_map(txn, "privateFrom")
+                .map_err(|_| Error::new(ErrorCode::ParseError))?;
+            let manager = client
+                .privacy_manager
+                .as_ref()
+                .ok_or_else(|| Error::invalid_params("Private transactions are not enabled"))?;
+            let hash = manager
+                .send(&data, private_from.as_deref(), &private_for)
+                .map_err(|err| {
+                    error!("{}", err);
+                    Error::internal_error()
+                })?;
+            (Vec::new(), hash)
+        }
+        #[cfg(not(feature = "privacy"))]
+        Some(_) => {
+            return Err(Error::invalid_params(
+                "Private transactions are not enabled",
+            ))
+        }
+        None => (data, Vec::new()),
+    };
 
     let txn = if let Some(to) = to {
         // Message Call
         let mut txn = MessageCallTxnPb::new();
         txn.set_to(to);
         txn.set_data(data);
+        txn.set_private_payload_hash(private_payload_hash);
         txn.set_gas_limit(gas);
         txn.set_gas_price(gas_price);
         txn.set_value(value);
@@ -114,6 +265,8 @@ where
         // Contract Creation
         let mut txn = CreateContractAccountTxnPb::new();
         txn.set_init(data);
+        txn.set_salt(salt);
+        txn.set_private_payload_hash(private_payload_hash);
         txn.set_gas_limit(gas);
         txn.set_gas_price(gas_price);
         txn.set_value(value);
@@ -121,21 +274,158 @@ where
         SethTransaction::CreateContractAccount(txn)
     };
 
-    let txn_signature = client.send_transaction(&from, &txn).map_err(|error| {
-        error!("{:?}", error);
-        Error::internal_error()
-    })?;
+    Ok((from, txn, access_list))
+}
 
-    Ok(transform::hex_prefix(&txn_signature))
+/// Narrows an amount of wei to the 64 bits seth transactions keep it in, refusing
+/// larger amounts rather than cutting them short
+fn to_seth_amount(amount: U256, key: &str) -> Result<u64, Error> {
+    if amount > U256::from(u64::MAX) {
+        return Err(Error::invalid_params(format!(
+            "`{}` is more than seth transactions can hold, {:#x}",
+            key,
+            u64::MAX
+        )));
+    }
+    Ok(amount.as_u64())
+}
+
+/// Parses an EIP-2930 access list
+fn get_access_list(txn: &Map<String, Value>) -> Result<Option<Vec<AccessListEntry>>, Error> {
+    let entries = match txn.get("accessList") {
+        Some(Value::Array(entries)) => entries,
+        Some(_) => return Err(Error::invalid_params("`accessList` must be a list")),
+        None => return Ok(None),
+    };
+
+    entries
+        .iter()
+        .map(|entry| {
+            let address = entry
+                .get("address")
+                .and_then(Value::as_str)
+                .map(|address| address.trim_start_matches("0x").to_lowercase())
+                .filter(|address| {
+                    address.len() == 40 && transform::hex_str_to_bytes(address).is_some()
+                })
+                .ok_or_else(|| Error::invalid_params("Invalid address in `accessList`"))?;
+            let storage_keys = match entry.get("storageKeys") {
+                Some(Value::Array(keys)) => Some(
+                    keys.iter()
+                        .map(|key| key.as_str().and_then(storage_key))
+                        .collect::<Option<Vec<String>>>()
+                        .ok_or_else(|| {
+                            Error::invalid_params("Invalid storage key in `accessList`")
+                        })?,
+                ),
+                Some(_) => {
+                    return Err(Error::invalid_params(
+                        "`storageKeys` in `accessList` must be a list",
+                    ))
+                }
+                None => None,
+            };
+            Ok(AccessListEntry {
+                address,
+                storage_keys,
+            })
+        })
+        .collect::<Result<Vec<AccessListEntry>, Error>>()
+        .map(Some)
+}
+
+/// Pads a hex encoded storage key to 32 bytes
+fn storage_key(key: &str) -> Option<String> {
+    let key = key.trim_start_matches("0x").to_lowercase();
+    if key.len() > 64 || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(format!("{:0>64}", key))
 }
 
-pub fn send_raw_transaction<T>(_params: Params, _client: ValidatorClient<T>) -> Result<Value, Error>
+/// Parses the public keys of the participants of a private transaction
+fn get_private_for(txn: &Map<String, Value>) -> Result<Option<Vec<String>>, Error> {
+    match txn.get("privateFor") {
+        Some(Value::Array(keys)) => keys
+            .iter()
+            .map(|key| key.as_str().map(String::from))
+            .collect::<Option<Vec<String>>>()
+            .map(Some)
+            .ok_or_else(|| Error::invalid_params("`privateFor` must be a list of public keys")),
+        Some(_) => Err(Error::invalid_params(
+            "`privateFor` must be a list of public keys",
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Relays a transaction signed by an Ethereum wallet in a seth transaction signed by
+/// the unlocked account, and returns its Ethereum hash, which it can be looked up by
+pub fn send_raw_transaction<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
 where
     T: MessageSender,
 {
     info!("eth_sendRawTransaction");
-    // Implementing this requires substantial modification to the seth transaction family
-    Err(error::not_implemented())
+    let (data,): (String,) = params
+        .parse()
+        .map_err(|_| Error::invalid_params("Takes [data: DATA]"))?;
+    let data = data
+        .get(2..)
+        .and_then(transform::hex_str_to_bytes)
+        .ok_or_else(|| {
+            Error::invalid_params("Invalid transaction data, must be 0x prefixed hex")
+        })?;
+
+    let raw = RawTransaction::decode(&data).map_err(|error| match error {
+        ClientError::ParseError(message) => Error::invalid_params(message),
+        error => Error::invalid_params(format!("Invalid transaction: {}", error)),
+    })?;
+    relay_raw_transaction(&client, raw)
+}
+
+/// Checks that a transaction signed by an Ethereum wallet can be run, relays it in a
+/// seth transaction signed by the unlocked account, and returns its Ethereum hash
+fn relay_raw_transaction<T>(
+    client: &ValidatorClient<T>,
+    raw: RawTransaction,
+) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    let network_chain_id = client
+        .get_chain_id(BlockKey::Latest)
+        .map_err(|error| fail!("Couldn't get chain id", error))?;
+    if raw.chain_id() != network_chain_id {
+        return Err(Error::invalid_params(format!(
+            "Signed for chain {}, not {}",
+            raw.chain_id(),
+            network_chain_id
+        )));
+    }
+    if let Some((max_fee, _)) = raw.fees {
+        let base_fee = client
+            .get_base_fee(BlockKey::Latest)
+            .map_err(|error| fail!("Couldn't get base fee", error))?;
+        if max_fee < base_fee {
+            return Err(Error::invalid_params(format!(
+                "Max fee per gas is less than the base fee: {} < {}",
+                max_fee, base_fee
+            )));
+        }
+    }
+    let hash = raw.hash();
+
+    // The relayer signs the seth transaction but isn't charged or touched by it
+    let relayer = client
+        .unlocked_account()
+        .map(|account| String::from(account.address()))
+        .ok_or_else(|| Error::invalid_params("No unlocked account to relay the transaction"))?;
+    let txn_id = client
+        .send_transaction(&relayer, &SethTransaction::Ethereum(Box::new(raw)))
+        .map_err(error::transaction_error)?;
+    client.raw_transactions.insert(&hash, &txn_id);
+
+    Ok(transform::hex_prefix(&hash))
 }
 
 pub fn get_transaction_by_hash<T>(
@@ -161,7 +451,8 @@ where
         }
     };
 
-    get_transaction(client, &TransactionKey::Signature(txn_hash))
+    let txn_id = client.raw_transactions.txn_id(&txn_hash);
+    get_transaction(client, &TransactionKey::Signature(txn_id))
 }
 
 pub fn get_transaction_by_block_hash_and_index<T>(
@@ -324,6 +615,17 @@ where
                 .map(String::from)
                 .ok_or_else(|| Error::invalid_params("Invalid transaction hash, must have 0x"))
         })?;
+
+    get_receipt_obj(&client, &txn_id)
+}
+
+/// Looks up the receipt for `txn_id` and builds its JSON-RPC object, returning null
+/// if the transaction hasn't been committed yet
+pub fn get_receipt_obj<T>(client: &ValidatorClient<T>, txn_id: &str) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    let txn_id = client.raw_transactions.txn_id(txn_id);
     let receipt = match client.get_receipts(&[txn_id.clone()]) {
         Err(ClientError::NoResource) => {
             return Ok(Value::Null);
@@ -340,7 +642,7 @@ where
             return Err(Error::internal_error());
         }
     };
-    let block = client
+    let (txn, block) = client
         .get_transaction_and_block(&TransactionKey::Signature(txn_id.clone()))
         .map_err(|error| {
             error!(
@@ -349,8 +651,8 @@ where
             );
             Error::internal_error()
         })
-        .and_then(|(_, block_option)| {
-            block_option.ok_or_else(|| {
+        .and_then(|(txn, block_option)| {
+            block_option.map(|block| (txn, block)).ok_or_else(|| {
                 error!("Txn `{}` had receipt but block was missing", txn_id);
                 Error::internal_error()
             })
@@ -372,29 +674,229 @@ where
             Error::internal_error()
         })?;
 
+    let base_fee = client
+        .get_base_fee(BlockKey::Signature(block.header_signature.clone()))
+        .map_err(|error| {
+            error!("Error getting base fee: {:?}", error);
+            Error::internal_error()
+        })?;
+
+    // Logs are numbered within the block, so those of the transactions before this
+    // one are counted, unless it has none to number
+    let first_log_idx = if receipt.logs.is_empty() {
+        0
+    } else {
+        get_block_logs(client, &block)?
+            .iter()
+            .take_while(|log| log.txn_idx < index as u64)
+            .count() as u64
+    };
+
     Ok(make_txn_receipt_obj(
         &receipt,
         index as u64,
+        first_log_idx,
         &block.header_signature,
         block_header.block_num,
+        txn.effective_gas_price(base_fee),
     ))
 }
 
-pub fn gas_price<T>(_params: Params, _client: ValidatorClient<T>) -> Result<Value, Error>
+/// Returns the base fee of the latest block, which is all a transaction needs to
+/// offer since gas is free on seth
+pub fn gas_price<T>(_params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
 where
     T: MessageSender,
 {
     info!("eth_gasPrice");
+    let base_fee = client
+        .get_base_fee(BlockKey::Latest)
+        .map_err(|error| fail!("Couldn't get base fee", error))?;
+    Ok(transform::num_to_hex(&base_fee))
+}
+
+/// Returns 0, since block producers aren't paid to include transactions
+pub fn max_priority_fee_per_gas<T>(
+    _params: Params,
+    _client: ValidatorClient<T>,
+) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("eth_maxPriorityFeePerGas");
     Ok(Value::String(format!("{:#x}", 0)))
 }
 
-pub fn estimate_gas<T>(_params: Params, _client: ValidatorClient<T>) -> Result<Value, Error>
+/// Returns the base fees of the blocks up to the newest and of the block after it,
+/// and the tips the transactions of each block paid at percentiles of the gas they
+/// used. Blocks have no gas limit, so their gas used ratio is always 0.
+pub fn fee_history<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("eth_feeHistory");
+    let usage =
+        "Takes [blockCount: QUANTITY, newestBlock: QUANTITY|TAG, rewardPercentiles: [NUMBER]]";
+    let (count, newest, percentiles): (Value, String, Option<Vec<f64>>) = params
+        .clone()
+        .parse()
+        .or_else(|_| {
+            params
+                .parse()
+                .map(|(count, newest): (Value, String)| (count, newest, None))
+        })
+        .map_err(|_| Error::invalid_params(usage))?;
+    let count = match count.as_u64() {
+        Some(count) => count,
+        None => transform::u64_from_hex_value(&count).map_err(|_| Error::invalid_params(usage))?,
+    };
+    if let Some(ref percentiles) = percentiles {
+        let increasing = percentiles.windows(2).all(|pair| pair[0] <= pair[1]);
+        if !increasing || percentiles.iter().any(|p| *p < 0.0 || *p > 100.0) {
+            return Err(Error::invalid_params(
+                "Reward percentiles must increase from 0 to 100",
+            ));
+        }
+    }
+
+    let newest_block =
+        client
+            .get_block(validate_block_key(&newest)?)
+            .map_err(|error| match error {
+                ClientError::NoResource => {
+                    Error::invalid_params(format!("Unknown block: {}", newest))
+                }
+                error => fail!("Couldn't get block", error),
+            })?;
+    let newest_num = protobuf::parse_from_bytes::<BlockHeader>(&newest_block.header)
+        .map_err(|error| fail!("Couldn't parse block header", error))?
+        .block_num;
+    let count = count.min(MAX_FEE_HISTORY_BLOCKS).min(newest_num + 1);
+    let oldest = newest_num + 1 - count;
+
+    let mut base_fees = Vec::new();
+    let mut gas_used_ratios = Vec::new();
+    let mut rewards = Vec::new();
+    let mut base_fee = 0;
+    for block_num in oldest..=newest_num {
+        let block = if block_num == newest_num {
+            newest_block.clone()
+        } else {
+            client
+                .get_block(BlockKey::Number(block_num))
+                .map_err(|error| fail!("Couldn't get block", error))?
+        };
+        base_fee = client
+            .get_base_fee(BlockKey::Signature(block.header_signature.clone()))
+            .map_err(|error| fail!("Couldn't get base fee", error))?;
+        base_fees.push(transform::num_to_hex(&base_fee));
+        gas_used_ratios.push(Value::from(0.0));
+        if let Some(ref percentiles) = percentiles {
+            rewards.push(Value::Array(get_block_rewards(
+                &client,
+                &block,
+                base_fee,
+                percentiles,
+            )?));
+        }
+    }
+    // The base fee only changes with the setting, so the next block's is the one
+    // in the state of the newest block
+    if count > 0 {
+        base_fees.push(transform::num_to_hex(&base_fee));
+    }
+
+    let mut history = Map::new();
+    history.insert(String::from("oldestBlock"), transform::num_to_hex(&oldest));
+    history.insert(String::from("baseFeePerGas"), Value::Array(base_fees));
+    history.insert(String::from("gasUsedRatio"), Value::Array(gas_used_ratios));
+    if percentiles.is_some() {
+        history.insert(String::from("reward"), Value::Array(rewards));
+    }
+    Ok(Value::Object(history))
+}
+
+/// Returns the tip per gas paid at each percentile of the gas the transactions of
+/// the block used, or 0 for a block without transactions, like other clients do
+fn get_block_rewards<T>(
+    client: &ValidatorClient<T>,
+    block: &Block,
+    base_fee: u64,
+    percentiles: &[f64],
+) -> Result<Vec<Value>, Error>
+where
+    T: MessageSender,
+{
+    let receipts = client
+        .get_receipts_from_block(block)
+        .map_err(|error| fail!("Couldn't get receipts", error))?;
+    let mut tips: Vec<(u64, u64)> = block
+        .get_batches()
+        .iter()
+        .flat_map(|batch| batch.get_transactions().iter())
+        .filter_map(|txn| {
+            let gas_used = receipts.get(&txn.header_signature)?.gas_used;
+            let txn = Transaction::try_from(txn.clone()).ok()?;
+            let tip = txn.effective_gas_price(base_fee).saturating_sub(base_fee);
+            Some((tip, gas_used))
+        })
+        .collect();
+    tips.sort_unstable();
+
+    let total_gas: u64 = tips.iter().map(|&(_, gas_used)| gas_used).sum();
+    Ok(percentiles
+        .iter()
+        .map(|percentile| {
+            let threshold = (total_gas as f64 * percentile / 100.0) as u64;
+            let mut gas = 0;
+            let tip = tips
+                .iter()
+                .find(|&&(_, gas_used)| {
+                    gas += gas_used;
+                    gas >= threshold
+                })
+                .or_else(|| tips.last())
+                .map_or(0, |&(tip, _)| tip);
+            transform::num_to_hex(&tip)
+        })
+        .collect())
+}
+
+pub fn estimate_gas<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
 where
     T: MessageSender,
 {
     info!("eth_estimateGas");
-    // Implementing this requires running the EVM, which is not possible within the RPC.
-    Err(error::not_implemented())
+    let usage = "Takes [txn: OBJECT, block: QUANTITY|TAG]";
+    let (txn, block): (Map<String, Value>, Option<String>) = params
+        .clone()
+        .parse()
+        .or_else(|_| params.parse().map(|(t,): (Map<String, Value>,)| (t, None)))
+        .map_err(|_| Error::invalid_params(usage))?;
+
+    let block_key = match block {
+        Some(block) => validate_block_key(&block)?,
+        None => BlockKey::Latest,
+    };
+    let block = client.get_block(block_key).map_err(|error| {
+        error!("Failed to get block: {}", error);
+        Error::internal_error()
+    })?;
+
+    let txn = names::resolve_fields(&client, &txn, &["from", "to"])?;
+    let call = parse_call(&txn, client.gas_cap)?;
+    let gas_limit = call.gas;
+    let executed = run_call(call, &block, &client).map_err(|error| match error {
+        CallError::OutOfGas => {
+            Error::invalid_params(format!("Gas required exceeds allowance ({})", gas_limit))
+        }
+        error => call_error(error),
+    })?;
+
+    // The gas a transaction is given also has to cover what calls it makes keep
+    // back, so the estimate leaves room above what the call used
+    let estimate = executed.gas_used + executed.gas_used * ESTIMATE_MARGIN_PERCENT / 100;
+    Ok(transform::num_to_hex(&estimate.min(gas_limit)))
 }
 
 pub fn sign<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
@@ -404,31 +906,34 @@ where
     info!("eth_sign");
     let (address, payload): (String, String) = params
         .parse()
-        .map_err(|_| Error::invalid_params("Takes [txnHash: DATA(64)]"))?;
-    let address = address
-        .get(2..)
-        .map(String::from)
-        .ok_or_else(|| Error::invalid_params("Address must have 0x prefix"))?;
+        .map_err(|_| Error::invalid_params("Takes [address: DATA(20), message: DATA]"))?;
+    let address = transform::parse_address(&address)?;
 
-    let payload = payload
+    let message = payload
         .get(2..)
         .ok_or_else(|| Error::invalid_params("Payload must have 0x prefix"))
         .and_then(|p| {
             transform::hex_str_to_bytes(&p)
                 .ok_or_else(|| Error::invalid_params("Payload is invalid hex"))
-        })
-        .and_then(|payload_data| {
-            let payload_string = String::from_utf8(payload_data.clone()).map_err(|error| {
-                Error::invalid_params(format!("Payload is invalid utf8: {}", error))
-            })?;
-            let msg_string = format!(
-                "\x19Ethereum Signed Message:\n{}{}",
-                payload_data.len(),
-                payload_string
-            );
-            let msg_data = msg_string.as_bytes();
-            Ok(tiny_keccak::keccak256(&msg_data))
         })?;
+    String::from_utf8(message.clone())
+        .map_err(|error| Error::invalid_params(format!("Payload is invalid utf8: {}", error)))?;
+
+    // A Ledger signs the message itself, and returns the 65 byte signature wallets do
+    #[cfg(feature = "ledger")]
+    {
+        if let Some(ref ledger) = client.ledger {
+            if let Some(account) = ledger.account(&address) {
+                let signature = ledger
+                    .sign_personal_message(account, &message)
+                    .map_err(|error| fail!(format!("Couldn't sign with the Ledger: {}", error)))?;
+                return Ok(transform::hex_prefix(&transform::bytes_to_hex_str(
+                    &signature,
+                )));
+            }
+        }
+    }
+    let payload = accounts::personal_message_hash(&message);
 
     let accounts = client.loaded_accounts();
     let locked_accounts = accounts.read().unwrap();
@@ -449,20 +954,307 @@ where
     Ok(transform::hex_prefix(&signature))
 }
 
-pub fn call<T>(_: Params, _: ValidatorClient<T>) -> Result<Value, Error>
+pub fn call<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
 where
     T: MessageSender,
 {
     info!("eth_call");
-    // Implementing this requires running the EVM, which is not possible within the RPC.
-    Err(error::not_implemented())
+    let usage = "Takes [txn: OBJECT, block: QUANTITY|TAG]";
+    let (txn, block): (Map<String, Value>, Option<String>) = params
+        .clone()
+        .parse()
+        .or_else(|_| params.parse().map(|(t,): (Map<String, Value>,)| (t, None)))
+        .map_err(|_| Error::invalid_params(usage))?;
+
+    let block_key = match block {
+        Some(block) => validate_block_key(&block)?,
+        None => BlockKey::Latest,
+    };
+    let block = client.get_block(block_key).map_err(|error| {
+        error!("Failed to get block: {}", error);
+        Error::internal_error()
+    })?;
+
+    let txn = names::resolve_fields(&client, &txn, &["from", "to"])?;
+    cached_call(&txn, &block, &client)
+}
+
+/// Runs a call against a block, answering from the call cache if the same call was
+/// already run against it. Everything the call reads, its chain id included, is read
+/// at the state root of the block, so the block's id is all the key needs and
+/// answering a call takes no reads beyond the one that resolved its block.
+pub fn cached_call<T>(
+    txn: &Map<String, Value>,
+    block: &Block,
+    client: &ValidatorClient<T>,
+) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    let key = CallCache::key(&block.header_signature, txn);
+    if let Some(result) = client.call_cache.get(&key) {
+        return Ok(result);
+    }
+
+    let result = execute_call(txn, block, client)?;
+    client.call_cache.insert(key, result.clone());
+    Ok(result)
+}
+
+fn execute_call<T>(
+    txn: &Map<String, Value>,
+    block: &Block,
+    client: &ValidatorClient<T>,
+) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    let call = parse_call(txn, client.gas_cap)?;
+    if call.to.is_none() {
+        return Err(Error::invalid_params("`to` not set"));
+    }
+    run_call(call, block, client)
+        .map(|executed| transform::hex_prefix(&transform::bytes_to_hex_str(&executed.output)))
+        .map_err(call_error)
+}
+
+/// Parses a call object, which needs none of its fields. Calls are given the gas cap
+/// if they don't set their gas, and at most the cap if they do.
+pub fn parse_call(txn: &Map<String, Value>, gas_cap: u64) -> Result<vm::Call, Error> {
+    let address = |key| {
+        transform::get_bytes_from_map(txn, key)
+            .map_err(|_| Error::new(ErrorCode::ParseError))
+            .and_then(|address| match address {
+                Some(ref address) if address.len() != 20 => {
+                    Err(Error::invalid_params(format!("`{}` must be 20 bytes", key)))
+                }
+                address => Ok(address.map(|address| H160::from_slice(&address))),
+            })
+    };
+    let to = address("to")?;
+    let from = address("from")?.unwrap_or_default();
+    let data = transform::get_bytes_from_map(txn, "data")
+        .map(Option::unwrap_or_default)
+        .map_err(|_| Error::new(ErrorCode::ParseError))?;
+    let gas = transform::get_u64_from_map(txn, "gas")
+        .map(|g| g.map_or(gas_cap, |g| g.min(gas_cap)))
+        .map_err(|_| Error::new(ErrorCode::ParseError))?;
+    let value = transform::get_u256_from_map(txn, "value")
+        .map(Option::unwrap_or_default)
+        .map_err(|_| Error::new(ErrorCode::ParseError))?;
+    let access_list = get_access_list(txn)?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|entry| {
+            let keys = entry
+                .storage_keys
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|key| transform::hex_str_to_bytes(key))
+                .map(|key| H256::from_slice(&key))
+                .collect();
+            let address = transform::hex_str_to_bytes(&entry.address).unwrap_or_default();
+            (H160::from_slice(&address), keys)
+        })
+        .collect();
+
+    Ok(vm::Call {
+        from,
+        to,
+        value,
+        data,
+        gas,
+        access_list,
+    })
 }
 
-// Always return false
-pub fn syncing<T>(_params: Params, _client: ValidatorClient<T>) -> Result<Value, Error>
+fn run_call<T>(
+    call: vm::Call,
+    block: &Block,
+    client: &ValidatorClient<T>,
+) -> Result<vm::Executed, CallError>
+where
+    T: MessageSender,
+{
+    let (state_root, block_num) = block_state(block)?;
+    let chain_id = call_chain_id(client, &state_root)?;
+    vm::call(client, &state_root, block_num, chain_id, call)
+}
+
+/// The state root and number of a block, which calls are run against
+pub fn block_state(block: &Block) -> Result<(String, u64), CallError> {
+    client::state_root(block)
+        .and_then(|state_root| client::block_num(block).map(|num| (state_root, num)))
+        .map_err(|error| CallError::State(format!("Error parsing block header: {:?}", error)))
+}
+
+/// Returns the chain id calls run against the given state root run with, which their
+/// CHAINID opcode returns
+pub fn call_chain_id<T>(client: &ValidatorClient<T>, state_root: &str) -> Result<u64, CallError>
+where
+    T: MessageSender,
+{
+    client
+        .get_chain_id(BlockKey::StateRoot(String::from(state_root)))
+        .map_err(|error| CallError::State(format!("Error getting chain id: {:?}", error)))
+}
+
+pub fn call_error(error: CallError) -> Error {
+    match error {
+        CallError::Reverted(output) => error::execution_reverted(&output),
+        CallError::OutOfGas => Error::invalid_params("Call ran out of gas"),
+        CallError::Failed(reason) => Error::invalid_params(format!("Call failed: {}", reason)),
+        CallError::State(error) => fail!("Failed to read state of call", error),
+    }
+}
+
+/// Returns false once the log index has caught up with the validator, and how far
+/// along it is while it catches up after startup. Servers without a log index read
+/// everything from the validator, so they are never behind.
+pub fn syncing<T>(_params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
 where
     T: MessageSender,
 {
     info!("eth_syncing");
+    #[cfg(feature = "indexer")]
+    {
+        if let Some(ref log_index) = client.log_index {
+            if let Some(starting_block) = log_index.backfilling_from() {
+                let current_block = log_index
+                    .head()
+                    .map_err(|error| fail!("Failed to read the log index", error))?
+                    .map_or(0, |(block_num, _)| block_num);
+                let highest_block = client
+                    .get_current_block_number()
+                    .map_err(|error| fail!("Failed to get the latest block", error))?;
+                if current_block < highest_block {
+                    return Ok(transform::to_value(&SyncStatus::Syncing(SyncProgress {
+                        starting_block: Quantity(starting_block),
+                        current_block: Quantity(current_block),
+                        highest_block: Quantity(highest_block),
+                    })));
+                }
+            }
+        }
+    }
+    #[cfg(not(feature = "indexer"))]
+    let _ = client;
     Ok(Value::Bool(false))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use contracts::ContractRegistry;
+    use filters::FilterManager;
+    use mock_chain::MockChain;
+    use serde_json;
+
+    fn client() -> ValidatorClient<MockChain> {
+        ValidatorClient::new(
+            MockChain::new(),
+            Vec::new(),
+            FilterManager::new(1),
+            0,
+            String::from("1.0"),
+            ContractRegistry::new(),
+        )
+    }
+
+    fn object(json: &str) -> Map<String, Value> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    /// The address of the counter alice deploys on the mock chain
+    fn counter() -> String {
+        let alice = MockChain::accounts()[0].address().to_string();
+        accounts::contract_address(&transform::hex_str_to_bytes(&alice).unwrap(), 1)
+    }
+
+    #[test]
+    fn parses_access_lists() {
+        let address = "0x5AAEB6053F3E94C9B9A09F33669435E7EF1BEAED";
+        let entries = get_access_list(&object(&format!(
+            r#"{{"accessList": [{{"address": "{}", "storageKeys": ["0x1", "0xAB"]}}]}}"#,
+            address
+        )))
+        .unwrap()
+        .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].address, address[2..].to_lowercase());
+        assert_eq!(
+            entries[0].storage_keys,
+            Some(vec![format!("{:0>64}", "1"), format!("{:0>64}", "ab")])
+        );
+
+        assert!(get_access_list(&object("{}")).unwrap().is_none());
+        assert!(get_access_list(&object(r#"{"accessList": {}}"#)).is_err());
+        assert!(get_access_list(&object(r#"{"accessList": [{"address": "0x1234"}]}"#)).is_err());
+        assert!(get_access_list(&object(&format!(
+            r#"{{"accessList": [{{"address": "{}", "storageKeys": ["0x{}"]}}]}}"#,
+            address,
+            "1".repeat(65)
+        )))
+        .is_err());
+    }
+
+    #[test]
+    fn gives_calls_at_most_the_gas_cap() {
+        let gas = |json| parse_call(&object(json), 50_000).map(|call| call.gas);
+        assert_eq!(gas("{}"), Ok(50_000));
+        assert_eq!(gas(r#"{"gas": "0x5208"}"#), Ok(21_000));
+        assert_eq!(gas(r#"{"gas": "0xffffffff"}"#), Ok(50_000));
+        assert!(gas(r#"{"to": "0x1234"}"#).is_err());
+    }
+
+    #[test]
+    fn refuses_amounts_seth_transactions_cant_hold() {
+        assert_eq!(to_seth_amount(U256::from(u64::MAX), "value"), Ok(u64::MAX));
+        assert!(to_seth_amount(U256::from(u64::MAX) + 1, "value").is_err());
+    }
+
+    #[test]
+    fn runs_calls_against_the_state_of_a_block() {
+        let call = |txn: String, block: &str| {
+            call(
+                serde_json::from_str(&format!(r#"[{}, "{}"]"#, txn, block)).unwrap(),
+                client(),
+            )
+        };
+        let increment = format!(r#"{{"to": "0x{}"}}"#, counter());
+        let count = |count: u64| Ok(Value::from(format!("0x{:064x}", count)));
+
+        assert_eq!(call(increment.clone(), "latest"), count(43));
+        // The counter was deployed with a count of 41 in block 2
+        assert_eq!(call(increment, "0x2"), count(42));
+        assert!(call(
+            format!(r#"{{"to": "0x{}", "data": "0x01"}}"#, counter()),
+            "latest"
+        )
+        .is_err());
+        assert!(call(String::from("{}"), "latest").is_err());
+    }
+
+    #[test]
+    fn returns_the_receipts_of_committed_transactions() {
+        let client = client();
+        let block = client.get_block(BlockKey::Number(3)).unwrap();
+        let txn_ids: Vec<String> = block
+            .get_batches()
+            .iter()
+            .flat_map(|batch| batch.get_transactions().iter())
+            .map(|txn| txn.header_signature.clone())
+            .collect();
+        assert_eq!(txn_ids.len(), 2);
+
+        let increment = get_receipt_obj(&client, &txn_ids[0]).unwrap();
+        assert_eq!(increment["status"], Value::from("0x1"));
+        assert_eq!(increment["transactionIndex"], Value::from("0x0"));
+        assert_eq!(increment["logs"].as_array().map(Vec::len), Some(1));
+        let reverted = get_receipt_obj(&client, &txn_ids[1]).unwrap();
+        assert_eq!(reverted["status"], Value::from("0x0"));
+        assert_eq!(reverted["transactionIndex"], Value::from("0x1"));
+
+        assert_eq!(get_receipt_obj(&client, &"0".repeat(128)), Ok(Value::Null));
+    }
+}
@@ -0,0 +1,182 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+use jsonrpc_core::{Error, Params, Value};
+
+use client::{PoolTransactions, ValidatorClient};
+use requests::RequestHandler;
+use std::collections::BTreeMap;
+use transactions::Transaction;
+use transform;
+use types;
+
+use sawtooth_sdk::messaging::stream::MessageSender;
+
+pub fn get_method_list<T>() -> Vec<(String, RequestHandler<T>)>
+where
+    T: MessageSender,
+{
+    vec![
+        ("txpool_status".into(), status),
+        ("txpool_content".into(), content),
+        ("txpool_inspect".into(), inspect),
+    ]
+}
+
+// Counts the transactions this server sent that haven't been committed yet. Only
+// transactions sent through this server are known, since the validator doesn't list
+// its pending batches, but it is asked which of them are still pending.
+pub fn status<T>(_params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("txpool_status");
+    let (pending, queued) = pool_content(&client)?;
+    let count = |pool: &PoolTransactions| pool.values().map(|txns| txns.len() as u64).sum();
+    Ok(transform::to_value(&types::TxPoolStatus {
+        pending: types::Quantity(count(&pending)),
+        queued: types::Quantity(count(&queued)),
+    }))
+}
+
+pub fn content<T>(_params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("txpool_content");
+    let (pending, queued) = pool_content(&client)?;
+    Ok(transform::to_value(&types::TxPoolContent {
+        pending: by_nonce(&pending, transform::make_txn),
+        queued: by_nonce(&queued, transform::make_txn),
+    }))
+}
+
+// Summarizes each transaction the way other clients do, as who it is sent to, its
+// value, its gas limit and its gas price
+pub fn inspect<T>(_params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("txpool_inspect");
+    let (pending, queued) = pool_content(&client)?;
+    Ok(transform::to_value(&types::TxPoolContent {
+        pending: by_nonce(&pending, summary),
+        queued: by_nonce(&queued, summary),
+    }))
+}
+
+fn pool_content<T>(
+    client: &ValidatorClient<T>,
+) -> Result<(PoolTransactions, PoolTransactions), Error>
+where
+    T: MessageSender,
+{
+    client
+        .get_pool_content()
+        .map_err(|err| fail!("Couldn't get pending transactions", err))
+}
+
+fn by_nonce<F, R>(pool: &PoolTransactions, f: F) -> BTreeMap<String, BTreeMap<String, R>>
+where
+    F: Fn(&Transaction) -> R,
+{
+    pool.iter()
+        .map(|(address, txns)| {
+            let txns = txns
+                .iter()
+                .map(|(nonce, txn)| (nonce.to_string(), f(txn)))
+                .collect();
+            (transform::checksum_address(address), txns)
+        })
+        .collect()
+}
+
+fn summary(txn: &Transaction) -> String {
+    let txn = transform::make_txn(txn);
+    format!(
+        "{}: {} wei + {} gas × {} wei",
+        txn.to.unwrap_or_else(|| String::from("contract creation")),
+        txn.value.0,
+        txn.gas.0,
+        txn.gas_price.0,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::BlockKey;
+    use contracts::ContractRegistry;
+    use filters::FilterManager;
+    use mock_chain::MockChain;
+
+    fn client() -> ValidatorClient<MockChain> {
+        ValidatorClient::new(
+            MockChain::new(),
+            Vec::new(),
+            FilterManager::new(1),
+            0,
+            String::from("1.0"),
+            ContractRegistry::new(),
+        )
+    }
+
+    #[test]
+    fn groups_transactions_by_sender_and_nonce() {
+        let client = client();
+        let mut pool: PoolTransactions = BTreeMap::new();
+        for txn in client
+            .get_block(BlockKey::Number(3))
+            .unwrap()
+            .get_batches()
+            .iter()
+            .flat_map(|batch| batch.get_transactions().iter())
+        {
+            let txn = Transaction::try_from(txn.clone()).unwrap();
+            pool.entry(txn.from_addr())
+                .or_default()
+                .insert(txn.nonce(), txn);
+        }
+
+        let bob = transform::checksum_address(MockChain::accounts()[1].address());
+        let summaries = by_nonce(&pool, summary);
+        assert_eq!(summaries.keys().collect::<Vec<_>>(), vec![&bob]);
+        let nonces: Vec<&String> = summaries[&bob].keys().collect();
+        assert_eq!(nonces, vec!["1", "2"]);
+        assert!(summaries[&bob]["1"].ends_with(": 0 wei + 50000 gas × 0 wei"));
+    }
+
+    #[test]
+    fn forgets_transactions_the_validator_doesnt_hold() {
+        let client = client();
+        for txn in client
+            .get_block(BlockKey::Number(3))
+            .unwrap()
+            .get_batches()
+            .iter()
+            .flat_map(|batch| batch.get_transactions().iter())
+        {
+            client.txpool.insert(txn.clone(), "batch");
+        }
+
+        // The mock chain doesn't know of any batch
+        let status = status(Params::None, client.clone()).unwrap();
+        assert_eq!(status["pending"], Value::from("0x0"));
+        assert_eq!(status["queued"], Value::from("0x0"));
+        assert_eq!(client.txpool.count(), 0);
+    }
+}
@@ -0,0 +1,73 @@
+/*
+ * Copyright 2017 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+use jsonrpc_core::{Error, Params, Value};
+
+use client::ValidatorClient;
+use crypto_backend;
+use requests::RequestHandler;
+use transform;
+
+use sawtooth_sdk::messaging::stream::MessageSender;
+use std::env::consts::{ARCH, OS};
+
+pub fn get_method_list<T>() -> Vec<(String, RequestHandler<T>)>
+where
+    T: MessageSender,
+{
+    vec![
+        ("web3_clientVersion".into(), client_version),
+        ("web3_sha3".into(), sha3),
+    ]
+}
+
+// Names the client the way other clients do, as its name, version, platform and
+// the version of the compiler it was built with
+pub fn client_version<T>(_params: Params, _client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("web3_clientVersion");
+    Ok(Value::String(format!(
+        "seth-rpc/v{}/{}-{}/rust-{}",
+        env!("CARGO_PKG_VERSION"),
+        OS,
+        ARCH,
+        env!("SETH_RPC_RUSTC_VERSION"),
+    )))
+}
+
+// Returns the Keccak-256 hash of the data, which is what Ethereum calls sha3
+pub fn sha3<T>(params: Params, _client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("web3_sha3");
+    let (data,): (String,) = params
+        .parse()
+        .map_err(|_| Error::invalid_params("Takes [data: DATA]"))?;
+    let data = data
+        .strip_prefix("0x")
+        .ok_or_else(|| Error::invalid_params("Data must have 0x prefix"))
+        .and_then(|data| {
+            transform::hex_str_to_bytes(data)
+                .ok_or_else(|| Error::invalid_params("Data is invalid hex"))
+        })?;
+    Ok(transform::hex_prefix(&transform::bytes_to_hex_str(
+        &crypto_backend::keccak256(&data),
+    )))
+}
@@ -16,21 +16,40 @@
  */
 
 use accounts::{Account, Error as AccountError};
+use breaker::CircuitBreaker;
+use cache::{CallCache, ChainCache};
+use calls::network::DEFAULT_CHAIN_ID;
+use contracts::ContractRegistry;
 use crypto::digest::Digest;
-use crypto::sha2::Sha512;
+use crypto::sha2::{Sha256, Sha512};
 use filters::FilterManager;
-use messages::seth::{EvmEntry, EvmStateAccount, EvmStorage};
+#[cfg(feature = "ledger")]
+use ledger::Ledger;
+#[cfg(feature = "indexer")]
+use log_index::LogIndex;
+use memory::MemoryBudget;
+use messages::seth::{
+    ArchivedContract, EvmEntry, EvmPermissions, EvmStateAccount, EvmStorage, EvmStorageEntry,
+};
+use metrics::Metrics;
+use prefetch::StatePrefetcher;
+#[cfg(feature = "privacy")]
+use privacy::PrivacyManager;
 use protobuf;
+use protocol::Capabilities;
+use raw_transaction::RawTransactionIndex;
 use sawtooth_sdk::messages::batch::{Batch, BatchHeader};
 use sawtooth_sdk::messages::block::Block;
 use sawtooth_sdk::messages::block::BlockHeader;
 use sawtooth_sdk::messages::client_batch_submit::{
-    ClientBatchSubmitRequest, ClientBatchSubmitResponse, ClientBatchSubmitResponse_Status,
+    ClientBatchStatusRequest, ClientBatchStatusResponse, ClientBatchStatusResponse_Status,
+    ClientBatchStatus_Status, ClientBatchSubmitRequest, ClientBatchSubmitResponse,
+    ClientBatchSubmitResponse_Status,
 };
 use sawtooth_sdk::messages::client_block::{
     ClientBlockGetByIdRequest, ClientBlockGetByNumRequest, ClientBlockGetByTransactionIdRequest,
     ClientBlockGetResponse, ClientBlockGetResponse_Status, ClientBlockListRequest,
-    ClientBlockListResponse,
+    ClientBlockListResponse, ClientBlockListResponse_Status,
 };
 use sawtooth_sdk::messages::client_list_control::ClientPagingControls;
 use sawtooth_sdk::messages::client_peers::{
@@ -41,34 +60,61 @@ use sawtooth_sdk::messages::client_receipt::{
 };
 use sawtooth_sdk::messages::client_state::{
     ClientStateGetRequest, ClientStateGetResponse, ClientStateGetResponse_Status,
+    ClientStateListRequest, ClientStateListResponse, ClientStateListResponse_Status,
 };
 use sawtooth_sdk::messages::client_transaction::{
     ClientTransactionGetRequest, ClientTransactionGetResponse, ClientTransactionGetResponse_Status,
 };
+use sawtooth_sdk::messages::setting::Setting;
 use sawtooth_sdk::messages::transaction::{Transaction as TransactionPb, TransactionHeader};
 use sawtooth_sdk::messages::validator::Message_MessageType;
 use sawtooth_sdk::messaging::stream::*;
-use std::collections::HashMap;
+use screening::{ScreenedTransaction, Simulation, TransactionScreen};
+use serde_json;
+use sessions::SessionTracker;
+#[cfg(feature = "debug")]
+use shadow::ShadowExecutor;
+use state_proof::{self, StateHasher};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::error::Error as StdError;
 use std::fmt::{Display, Formatter, Result as FmtResult};
-use std::str::FromStr;
+use std::str::{self, FromStr};
 use std::sync::Arc;
 use std::sync::RwLock;
-use transactions::{SethReceipt, SethTransaction, Transaction, TransactionKey};
+use std::thread;
+use std::time::{Duration, Instant};
+use telemetry::UnsupportedMethods;
+use transactions::{
+    created_address, AccessListEntry, SethReceipt, SethTransaction, Transaction, TransactionKey,
+};
 use transform;
+use txpool::TxPool;
+use types::ErrorCode;
 use uuid;
+use vm;
 
 #[derive(Clone)]
 pub enum BlockKey {
     Latest,
     Earliest,
+
+    /// The latest state, with the transactions this server has sent that haven't
+    /// been committed yet. Seth has no pending block, so only the nonces of accounts
+    /// differ from the latest state.
+    Pending,
+
+    /// The most recent block that can't be reverted
+    Finalized,
     Number(u64),
     Signature(String),
     Transaction(String),
+
+    /// The state of a block already looked up, by its state root. Only state can be
+    /// read this way.
+    StateRoot(String),
 }
 
 pub enum BlockKeyParseError {
-    Unsupported,
     Invalid,
 }
 
@@ -79,7 +125,8 @@ impl FromStr for BlockKey {
         match s {
             "latest" => Ok(BlockKey::Latest),
             "earliest" => Ok(BlockKey::Earliest),
-            "pending" => Err(BlockKeyParseError::Unsupported),
+            "finalized" => Ok(BlockKey::Finalized),
+            "pending" => Ok(BlockKey::Pending),
             _ if s.len() < 3 => Err(BlockKeyParseError::Invalid),
             _ => match u64::from_str_radix(&s[2..], 16) {
                 Ok(num) => Ok(BlockKey::Number(num)),
@@ -89,8 +136,114 @@ impl FromStr for BlockKey {
     }
 }
 
-const SETH_NS: &str = "a68b06";
+pub const SETH_NS: &str = "a68b06";
+const STATE_ADDRESS_LEN: usize = 70;
 const BLOCK_INFO_NS: &str = "00b10c";
+const SETTINGS_NS: &str = "000000";
+pub const DEFAULT_FAMILY_VERSION: &str = "1.1";
+/// The family version that keeps contract storage in the account entry
+const ENTRY_STORAGE_FAMILY_VERSION: &str = "1.0";
+
+/// The setting naming the consensus algorithm the network runs
+const CONSENSUS_ALGORITHM_SETTING: &str = "sawtooth.consensus.algorithm.name";
+
+/// The setting holding the version of the consensus algorithm
+const CONSENSUS_VERSION_SETTING: &str = "sawtooth.consensus.algorithm.version";
+
+/// Consensus algorithms whose committed blocks are never reverted
+const NON_FORKING_CONSENSUS: [&str; 2] = ["pbft", "raft"];
+
+/// The settings listing the public keys of the validators taking part in consensus,
+/// for the algorithms that have a fixed membership
+const CONSENSUS_MEMBERS_SETTINGS: [(&str, &str); 2] = [
+    ("pbft", "sawtooth.consensus.pbft.members"),
+    ("raft", "sawtooth.consensus.raft.peers"),
+];
+
+/// The account whose permissions apply to accounts that don't set their own
+pub const GLOBAL_PERMISSIONS_ADDRESS: &str = "0000000000000000000000000000000000000000";
+
+/// The settings the transaction processor reads while executing a transaction
+const EVM_SETTINGS: [&str; 14] = [
+    "sawtooth.seth.block_gas_limit",
+    "sawtooth.seth.max_code_size",
+    "sawtooth.seth.gas_schedule.transaction",
+    "sawtooth.seth.gas_schedule.contract_creation",
+    "sawtooth.seth.permission_mode",
+    "sawtooth.seth.commit_reverted",
+    BASE_FEE_SETTING,
+    "sawtooth.seth.fork",
+    CHAIN_ID_SETTING,
+    "sawtooth.seth.confidential_payloads",
+    "sawtooth.seth.gas_schedule.bn256_add",
+    "sawtooth.seth.gas_schedule.bn256_mul",
+    "sawtooth.seth.gas_schedule.bn256_pairing_base",
+    "sawtooth.seth.gas_schedule.bn256_pairing_per_pair",
+];
+
+/// The setting of the base fee per gas that EIP-1559 transactions must offer
+const BASE_FEE_SETTING: &str = "sawtooth.seth.base_fee_per_gas";
+
+/// The setting of the chain id Ethereum transactions must be signed for, which the
+/// transaction processors and seth-rpc both read
+pub const CHAIN_ID_SETTING: &str = "sawtooth.seth.chain_id";
+
+/// The standard addresses of infrastructure contracts, and the settings that enable
+/// them by setting their code
+const WELL_KNOWN_CONTRACTS: [(&str, &str); 3] = [
+    (
+        "ca11bde05977b3631167028862be2a173976ca11",
+        "sawtooth.seth.predeploy.multicall3",
+    ),
+    (
+        "1820a4b7618bde71dce8cdc73aab6c95905fad24",
+        "sawtooth.seth.predeploy.erc1820_registry",
+    ),
+    (
+        "4e59b44847b379578588920ca78fbf26c0b4956c",
+        "sawtooth.seth.predeploy.deterministic_deployment_proxy",
+    ),
+];
+
+/// How long, in seconds, to wait on the validator for an imported batch to be
+/// validated before treating it as accepted
+const BATCH_STATUS_TIMEOUT: u32 = 5;
+
+/// How long to wait for the validator to answer a request before failing it, which
+/// must be longer than `BATCH_STATUS_TIMEOUT`
+const VALIDATOR_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a read of the latest block waits for the validator to reach the blocks
+/// holding the transactions of its session, before returning an older block anyway
+const SESSION_WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the latest block is read again while waiting on it
+const SESSION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How many of the latest blocks are searched for a transaction, when none of the
+/// validators can be asked which block holds it
+const MAX_TRANSACTION_SEARCH_DEPTH: usize = 1000;
+
+/// How many blocks are listed at a time while searching
+const SEARCH_PAGE_SIZE: i32 = 100;
+
+/// How many blocks of a range are requested from the validator at once
+const BLOCK_RANGE_WINDOW: u64 = 64;
+
+/// How long an account stays unlocked when no duration is given, like geth
+#[cfg(feature = "personal")]
+const DEFAULT_UNLOCK_DURATION: u64 = 300;
+
+/// An unlocked account, and when it locks again unless it stays unlocked
+type UnlockedAccount = (Account, Option<Instant>);
+
+pub const DEFAULT_MAX_RESPONSE_SIZE: usize = 16 * 1024 * 1024;
+
+/// The most memory serving one request may hold by default
+pub const DEFAULT_MAX_REQUEST_MEMORY: usize = 256 * 1024 * 1024;
+
+/// The transactions of each sender in the pool, by nonce
+pub type PoolTransactions = BTreeMap<String, BTreeMap<u64, Transaction>>;
 
 #[derive(Debug)]
 pub enum Error {
@@ -101,6 +254,12 @@ pub enum Error {
     AccountLoadError,
     SigningError,
     InvalidTransaction,
+    /// The id of the transaction, and the reason and code the transaction processor
+    /// rejected it with
+    TransactionRejected(String, String, ErrorCode),
+    ValidatorUnavailable,
+    /// A screen denied sending a transaction, for the given reason
+    TransactionDenied(String),
 }
 
 impl StdError for Error {
@@ -113,6 +272,9 @@ impl StdError for Error {
             Error::AccountLoadError => "Account loading failed",
             Error::SigningError => "Signing failed",
             Error::InvalidTransaction => "Submitted transaction was invalid",
+            Error::TransactionRejected(_, ref msg, _) => msg,
+            Error::ValidatorUnavailable => "Validator is unavailable",
+            Error::TransactionDenied(ref reason) => reason,
         }
     }
 
@@ -131,6 +293,11 @@ impl Display for Error {
             Error::AccountLoadError => write!(f, "AccountLoadError"),
             Error::SigningError => write!(f, "SigningError"),
             Error::InvalidTransaction => write!(f, "InvalidTransaction"),
+            Error::TransactionRejected(ref txn_id, ref msg, _) => {
+                write!(f, "TransactionRejected: {}: {}", txn_id, msg)
+            }
+            Error::ValidatorUnavailable => write!(f, "ValidatorUnavailable"),
+            Error::TransactionDenied(ref reason) => write!(f, "TransactionDenied: {}", reason),
         }
     }
 }
@@ -159,6 +326,34 @@ impl From<AccountError> for Error {
     }
 }
 
+/// The blocks of a range, returned by `ValidatorClient::get_blocks_range`
+pub struct BlockRange<'a, S: MessageSender + 'a> {
+    client: &'a ValidatorClient<S>,
+    /// The first block of the next window, or None once the range is fetched
+    next: Option<u64>,
+    end: u64,
+    fetched: VecDeque<Result<Block, Error>>,
+}
+
+impl<'a, S: MessageSender> Iterator for BlockRange<'a, S> {
+    type Item = Result<Block, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.fetched.is_empty() {
+            let start = self.next?;
+            let end = self.end.min(start.saturating_add(BLOCK_RANGE_WINDOW - 1));
+            let mut fetched = self.client.get_blocks_by_num(start, end);
+            self.next = end.checked_add(1).filter(|&next| next <= self.end);
+            if let Some(failed) = fetched.iter().position(Result::is_err) {
+                fetched.truncate(failed + 1);
+                self.next = None;
+            }
+            self.fetched = fetched.into();
+        }
+        self.fetched.pop_front()
+    }
+}
+
 #[derive(Clone)]
 pub struct ValidatorClient<S: MessageSender> {
     /// The ZMQ message sender
@@ -168,19 +363,278 @@ pub struct ValidatorClient<S: MessageSender> {
     loaded_accounts: Arc<RwLock<Vec<Account>>>,
 
     /// The current loaded and unlocked account that can be used for sending transactions
-    unlocked_account: Arc<RwLock<Option<Account>>>,
+    unlocked_account: Arc<RwLock<Option<UnlockedAccount>>>,
 
     /// Manages filters
     pub filters: FilterManager,
+
+    /// Results of recent `eth_call`s
+    pub call_cache: CallCache,
+
+    /// Blocks, state and receipts recently read from the validator
+    pub chain_cache: ChainCache,
+
+    /// Where the payloads of private transactions are sent, if they are enabled
+    #[cfg(feature = "privacy")]
+    pub privacy_manager: Option<PrivacyManager>,
+
+    /// The version of the seth family that transactions are sent with
+    family_version: String,
+
+    /// The transactions sent by each session
+    pub sessions: SessionTracker,
+
+    /// The session of the request being served, if it named one
+    session: Option<String>,
+
+    /// The transactions this server sent that aren't in a block yet
+    pub txpool: TxPool,
+
+    /// The seth transactions that Ethereum transactions were relayed in
+    pub raw_transactions: RawTransactionIndex,
+
+    /// How often clients called methods that aren't served
+    pub unsupported_methods: UnsupportedMethods,
+
+    /// What `/metrics` serves
+    pub metrics: Metrics,
+
+    /// The ABIs of contracts that calls can be encoded for
+    pub contracts: ContractRegistry,
+
+    /// The most transactions of `send_ordered_transactions` put in one batch
+    max_batch_size: usize,
+
+    /// Caches the latest state, if prefetching is enabled
+    pub prefetcher: Option<StatePrefetcher>,
+
+    /// Fails requests fast while the validator is down
+    pub breaker: CircuitBreaker,
+
+    /// The largest response, in bytes of JSON, that is sent
+    pub max_response_size: usize,
+
+    /// The most memory, roughly, that serving one request may hold
+    pub max_request_memory: usize,
+
+    /// What the request this client serves holds, unlimited outside of requests
+    pub memory: MemoryBudget,
+
+    /// The address of the registry contract names are resolved through, if any
+    pub name_registry: Option<String>,
+
+    /// The most gas a call or gas estimate can use
+    pub gas_cap: u64,
+
+    /// How many blocks must follow a block for it to be final, if set
+    finality_depth: Option<u64>,
+
+    /// Whether the accounts and slots that clients read are checked against proofs
+    verify_state_reads: bool,
+
+    /// The logs of committed blocks, if they are indexed
+    #[cfg(feature = "indexer")]
+    pub log_index: Option<LogIndex>,
+
+    /// Runs committed blocks again with a candidate fork, if it is being tried out
+    #[cfg(feature = "debug")]
+    pub shadow: Option<ShadowExecutor>,
+
+    /// The Ledger whose accounts sign what is sent from them, if one is used
+    #[cfg(feature = "ledger")]
+    pub ledger: Option<Arc<Ledger>>,
+
+    /// Which requests the validators answer
+    capabilities: Capabilities,
+
+    /// What each transaction is shown to before it is sent, in order
+    screens: Vec<Arc<dyn TransactionScreen>>,
+}
+
+impl<S: MessageSender + Clone> ValidatorClient<S> {
+    /// Returns a client that serves a request of the given session, with a memory
+    /// budget of its own
+    pub fn with_session(&self, session: Option<String>) -> Self {
+        ValidatorClient {
+            session,
+            memory: MemoryBudget::new(self.max_request_memory),
+            ..self.clone()
+        }
+    }
 }
 
 impl<S: MessageSender> ValidatorClient<S> {
-    pub fn new(sender: S, accounts: Vec<Account>) -> Self {
+    pub fn new(
+        sender: S,
+        accounts: Vec<Account>,
+        filters: FilterManager,
+        call_cache_size: usize,
+        family_version: String,
+        contracts: ContractRegistry,
+    ) -> Self {
         ValidatorClient {
             sender: Arc::new(RwLock::new(sender)),
+            // The first account unlocked at startup stays unlocked
+            unlocked_account: Arc::new(RwLock::new(
+                accounts.first().cloned().map(|account| (account, None)),
+            )),
             loaded_accounts: Arc::new(RwLock::new(accounts)),
-            unlocked_account: Arc::new(RwLock::new(None)),
-            filters: FilterManager::new(),
+            filters,
+            call_cache: CallCache::new(call_cache_size),
+            chain_cache: ChainCache::new(0, 0, 0, 0),
+            #[cfg(feature = "privacy")]
+            privacy_manager: None,
+            family_version,
+            sessions: SessionTracker::new(),
+            session: None,
+            txpool: TxPool::new(),
+            raw_transactions: RawTransactionIndex::new(),
+            unsupported_methods: UnsupportedMethods::new(),
+            metrics: Metrics::new(),
+            contracts,
+            max_batch_size: 1,
+            prefetcher: None,
+            breaker: CircuitBreaker::new(),
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            max_request_memory: DEFAULT_MAX_REQUEST_MEMORY,
+            memory: MemoryBudget::default(),
+            name_registry: None,
+            gas_cap: vm::DEFAULT_GAS_CAP,
+            finality_depth: None,
+            verify_state_reads: false,
+            #[cfg(feature = "indexer")]
+            log_index: None,
+            #[cfg(feature = "debug")]
+            shadow: None,
+            #[cfg(feature = "ledger")]
+            ledger: None,
+            capabilities: Capabilities::default(),
+            screens: Vec::new(),
+        }
+    }
+
+    /// Lets `send_ordered_transactions` put up to `max_batch_size` transactions in
+    /// one batch instead of one in each
+    pub fn with_max_batch_size(self, max_batch_size: usize) -> Self {
+        ValidatorClient {
+            max_batch_size: max_batch_size.max(1),
+            ..self
+        }
+    }
+
+    pub fn with_max_response_size(self, max_response_size: usize) -> Self {
+        ValidatorClient {
+            max_response_size,
+            ..self
+        }
+    }
+
+    pub fn with_max_request_memory(self, max_request_memory: usize) -> Self {
+        ValidatorClient {
+            max_request_memory,
+            ..self
+        }
+    }
+
+    pub fn with_gas_cap(self, gas_cap: u64) -> Self {
+        ValidatorClient { gas_cap, ..self }
+    }
+
+    /// Treats blocks as final once this many blocks are built on them, rather than
+    /// judging by the consensus algorithm
+    pub fn with_finality_depth(self, finality_depth: u64) -> Self {
+        ValidatorClient {
+            finality_depth: Some(finality_depth),
+            ..self
+        }
+    }
+
+    /// Has the accounts and storage slots that clients read checked against proofs of
+    /// the blocks they are read from
+    pub fn with_state_read_verification(self) -> Self {
+        ValidatorClient {
+            verify_state_reads: true,
+            ..self
+        }
+    }
+
+    pub fn verifies_state_reads(&self) -> bool {
+        self.verify_state_reads
+    }
+
+    /// Sends the payloads of private transactions to the given privacy manager
+    #[cfg(feature = "privacy")]
+    pub fn with_privacy_manager(self, privacy_manager: PrivacyManager) -> Self {
+        ValidatorClient {
+            privacy_manager: Some(privacy_manager),
+            ..self
+        }
+    }
+
+    pub fn with_name_registry(self, name_registry: String) -> Self {
+        ValidatorClient {
+            name_registry: Some(name_registry),
+            ..self
+        }
+    }
+
+    /// Serves blocks, state and receipts that were read before from the given cache
+    pub fn with_chain_cache(self, chain_cache: ChainCache) -> Self {
+        ValidatorClient {
+            chain_cache,
+            ..self
+        }
+    }
+
+    /// Serves reads of the latest state from the given prefetcher's cache
+    pub fn with_prefetcher(self, prefetcher: StatePrefetcher) -> Self {
+        ValidatorClient {
+            prefetcher: Some(prefetcher),
+            ..self
+        }
+    }
+
+    /// Works around requests that none of the validators answer, as negotiated by
+    /// their sender
+    pub fn with_capabilities(self, capabilities: Capabilities) -> Self {
+        ValidatorClient {
+            capabilities,
+            ..self
+        }
+    }
+
+    /// Shows each transaction to the given screen before it is sent, after the
+    /// screens it was already given
+    pub fn with_screen(self, screen: Arc<dyn TransactionScreen>) -> Self {
+        let mut screens = self.screens.clone();
+        screens.push(screen);
+        ValidatorClient { screens, ..self }
+    }
+
+    /// Serves log queries over the blocks the given index has from it
+    #[cfg(feature = "indexer")]
+    pub fn with_log_index(self, log_index: LogIndex) -> Self {
+        ValidatorClient {
+            log_index: Some(log_index),
+            ..self
+        }
+    }
+
+    /// Reports what the given executor finds running committed blocks again
+    #[cfg(feature = "debug")]
+    pub fn with_shadow(self, shadow: ShadowExecutor) -> Self {
+        ValidatorClient {
+            shadow: Some(shadow),
+            ..self
+        }
+    }
+
+    /// Signs what is sent from the accounts of the given Ledger with it
+    #[cfg(feature = "ledger")]
+    pub fn with_ledger(self, ledger: Ledger) -> Self {
+        ValidatorClient {
+            ledger: Some(Arc::new(ledger)),
+            ..self
         }
     }
 
@@ -188,52 +642,81 @@ impl<S: MessageSender> ValidatorClient<S> {
         self.loaded_accounts.clone()
     }
 
+    /// Returns the unlocked account, unless the time it was unlocked for is up
     pub fn unlocked_account(&self) -> Option<Account> {
-        self.unlocked_account.read().unwrap().clone()
+        match *self.unlocked_account.read().unwrap() {
+            Some((ref account, until)) if until.is_none_or(|until| Instant::now() < until) => {
+                Some(account.clone())
+            }
+            _ => None,
+        }
     }
 
-    /// Unlocks the given account, adding it to `self.loaded_accounts` if necessary
-    pub fn unlock_account(&self, account: &Account, _duration: Option<u64>) -> Result<(), Error> {
+    /// Unlocks the given account for `duration` seconds, adding it to
+    /// `self.loaded_accounts` if necessary. A duration of 0 unlocks it until it is
+    /// locked again, and none unlocks it for `DEFAULT_UNLOCK_DURATION`.
+    #[cfg(feature = "personal")]
+    pub fn unlock_account(&self, account: &Account, duration: Option<u64>) -> Result<(), Error> {
         let mut loaded_accounts = self.loaded_accounts.write().unwrap();
         let mut unlocked_account = self.unlocked_account.write().unwrap();
 
         if !loaded_accounts.contains(account) {
             loaded_accounts.push(account.clone());
         }
-        *unlocked_account = Some(account.clone());
+        let until = match duration.unwrap_or(DEFAULT_UNLOCK_DURATION) {
+            0 => None,
+            seconds => Some(Instant::now() + Duration::from_secs(seconds)),
+        };
+        *unlocked_account = Some((account.clone(), until));
 
         Ok(())
     }
 
-    /// Unlocks the given address, if it exists in `self.loaded_accounts`
+    /// Unlocks the given address for `duration` seconds, as `unlock_account` does
+    #[cfg(feature = "personal")]
     pub fn unlock_address(
         &self,
         address: &str,
         password: &Option<String>,
-        _duration: Option<u64>,
+        duration: Option<u64>,
     ) -> Result<(), Error> {
-        let mut loaded_accounts = self.loaded_accounts.write().unwrap();
-        let mut unlocked_account = self.unlocked_account.write().unwrap();
+        let account = self.load_address(address, password)?;
+        self.unlock_account(&account, duration)
+    }
 
-        // Attempt to unlock a loaded account
-        for account in loaded_accounts.iter() {
-            if account.address() == address {
-                *unlocked_account = Some(account.clone());
-                return Ok(());
-            }
+    /// Locks the given address again, returning whether it was unlocked
+    #[cfg(feature = "personal")]
+    pub fn lock_address(&self, address: &str) -> bool {
+        let mut unlocked_account = self.unlocked_account.write().unwrap();
+        let unlocked = match *unlocked_account {
+            Some((ref account, _)) => account.address() == address,
+            None => false,
+        };
+        if unlocked {
+            *unlocked_account = None;
         }
+        unlocked
+    }
 
-        // If the given address wasn't found in the list of loaded accounts, try
-        // loading it and then unlocking it. The key file must be named with the
-        // specified address
-        let account = Account::load_from_file(address, &password)?;
-        loaded_accounts.push(account.clone());
-        *unlocked_account = Some(account.clone());
-
-        Err(Error::ParseError(format!(
-            "Account with address `{}` not found!",
-            address
-        )))
+    /// Loads the key of an address from the key file named with the address, checking
+    /// the password. Accounts that were loaded by alias when seth-rpc started have no
+    /// such file, and are taken from `self.loaded_accounts` instead.
+    #[cfg(feature = "personal")]
+    pub fn load_address(&self, address: &str, password: &Option<String>) -> Result<Account, Error> {
+        match Account::load_from_file(address, password) {
+            Ok(account) => Ok(account),
+            Err(AccountError::AliasNotFound) => self
+                .loaded_accounts
+                .read()
+                .unwrap()
+                .iter()
+                .find(|account| account.address() == address)
+                .cloned()
+                .ok_or_else(|| {
+                    Error::ParseError(format!("Account with address `{}` not found!", address))
+                }),
+            Err(error) => Err(Error::from(error)),
+        }
     }
 
     pub fn request<T, U>(&self, msg_type: Message_MessageType, msg: &T) -> Result<U, String>
@@ -250,21 +733,24 @@ impl<S: MessageSender> ValidatorClient<S> {
 
         let correlation_id = uuid::Uuid::new_v4().to_string();
 
-        let sender = self.sender.write().unwrap();
-
-        let mut future = match sender.send(msg_type, &correlation_id, &msg_bytes) {
-            Ok(f) => f,
-            Err(error) => {
-                return Err(format!("Error unwrapping future: {:?}", error));
-            }
-        };
-
-        let response_msg = match future.get() {
-            Ok(m) => m,
-            Err(error) => {
-                return Err(format!("Error getting future: {:?}", error));
-            }
-        };
+        if !self.breaker.allow() {
+            return Err(String::from("Validator is unavailable"));
+        }
+        let started = Instant::now();
+        let send_result = self
+            .sender
+            .write()
+            .unwrap()
+            .send(msg_type, &correlation_id, &msg_bytes);
+        let result = send_result
+            .map_err(|error| format!("Error unwrapping future: {:?}", error))
+            .and_then(|mut future| {
+                future
+                    .get_timeout(VALIDATOR_TIMEOUT)
+                    .map_err(|error| format!("Error getting future: {:?}", error))
+            });
+        self.record_round_trip(msg_type, started, result.is_ok());
+        let response_msg = result?;
 
         let response: U = match protobuf::parse_from_bytes(&response_msg.content) {
             Ok(r) => r,
@@ -287,37 +773,331 @@ impl<S: MessageSender> ValidatorClient<S> {
 
         let correlation_id = uuid::Uuid::new_v4().to_string();
 
-        let sender = self.sender.write().unwrap();
-        let mut future = sender.send(msg_type, &correlation_id, &msg_bytes)?;
-        let response_msg = future.get()?;
+        if !self.breaker.allow() {
+            return Err(Error::ValidatorUnavailable);
+        }
+
+        // The lock is only held while sending, so that requests the validator is slow
+        // to answer, or never answers, don't hold up every other request
+        let started = Instant::now();
+        let send_result = self
+            .sender
+            .write()
+            .unwrap()
+            .send(msg_type, &correlation_id, &msg_bytes);
+        let result = send_result
+            .map_err(Error::from)
+            .and_then(|mut future| future.get_timeout(VALIDATOR_TIMEOUT).map_err(Error::from));
+        self.record_round_trip(msg_type, started, result.is_ok());
+        let response_msg = result?;
         protobuf::parse_from_bytes(&response_msg.content)
             .map_err(|error| Error::ParseError(format!("Error parsing response: {:?}", error)))
     }
 
+    /// Sends every request before waiting for any of the responses, so that they take
+    /// about as long as the slowest of them rather than all of them in turn
+    pub fn send_requests<T, U>(
+        &self,
+        msg_type: Message_MessageType,
+        msgs: &[T],
+    ) -> Vec<Result<U, Error>>
+    where
+        T: protobuf::Message,
+        U: protobuf::Message,
+    {
+        if !self.breaker.allow() {
+            return msgs
+                .iter()
+                .map(|_| Err(Error::ValidatorUnavailable))
+                .collect();
+        }
+
+        let started = Instant::now();
+        let futures: Vec<Result<MessageFuture, Error>> = {
+            let sender = self.sender.write().unwrap();
+            msgs.iter()
+                .map(|msg| {
+                    let msg_bytes = protobuf::Message::write_to_bytes(msg).map_err(|error| {
+                        Error::ParseError(format!("Error serializing request: {:?}", error))
+                    })?;
+                    let correlation_id = uuid::Uuid::new_v4().to_string();
+                    sender
+                        .send(msg_type, &correlation_id, &msg_bytes)
+                        .map_err(Error::from)
+                })
+                .collect()
+        };
+
+        futures
+            .into_iter()
+            .map(|future| {
+                let result = future.and_then(|mut future| {
+                    future.get_timeout(VALIDATOR_TIMEOUT).map_err(Error::from)
+                });
+                self.record_round_trip(msg_type, started, result.is_ok());
+                protobuf::parse_from_bytes(&result?.content).map_err(|error| {
+                    Error::ParseError(format!("Error parsing response: {:?}", error))
+                })
+            })
+            .collect()
+    }
+
+    /// Records whether the validator answered a request sent at `started`, for the
+    /// breaker and the metrics
+    fn record_round_trip(&self, msg_type: Message_MessageType, started: Instant, answered: bool) {
+        self.breaker.record(answered);
+        self.metrics.record_validator_request(
+            &format!("{:?}", msg_type).to_lowercase(),
+            started.elapsed(),
+            answered,
+        );
+    }
+
     pub fn send_transaction(&self, from: &str, txn: &SethTransaction) -> Result<String, Error> {
-        let (batch, txn_signature) = self.make_batch(from, txn)?;
+        self.send_transaction_with_access_list(from, txn, None)
+    }
+
+    /// Sends a transaction that may only touch the accounts it is sent from and to and
+    /// those in `access_list`. The validator can run transactions that declare disjoint
+    /// accounts in parallel, but rejects a transaction that touches any other account.
+    pub fn send_transaction_with_access_list(
+        &self,
+        from: &str,
+        txn: &SethTransaction,
+        access_list: Option<&[AccessListEntry]>,
+    ) -> Result<String, Error> {
+        self.send_ordered_transactions(from, &[(txn, access_list)])
+            .map(|mut txn_ids| txn_ids.remove(0))
+    }
+
+    /// Sends a transaction signed by the given account rather than the unlocked one
+    #[cfg(feature = "personal")]
+    pub fn send_transaction_signed_by(
+        &self,
+        account: &Account,
+        from: &str,
+        txn: &SethTransaction,
+        access_list: Option<&[AccessListEntry]>,
+    ) -> Result<String, Error> {
+        self.send_batches(Some(account), from, &[(txn, access_list)])
+            .map(|mut txn_ids| txn_ids.remove(0))
+    }
+
+    /// Sends transactions in batches that each depend on the one before, so that the
+    /// validator runs them in the given order even when it schedules batches in
+    /// parallel. Up to `max_batch_size` consecutive transactions share a batch, which
+    /// the validator commits or rejects as a whole. Returns their ids without waiting
+    /// for them to be validated, or the error of the first one the validator has
+    /// already rejected; the ones after it never run, since what they depend on
+    /// didn't.
+    pub fn send_ordered_transactions(
+        &self,
+        from: &str,
+        txns: &[(&SethTransaction, Option<&[AccessListEntry]>)],
+    ) -> Result<Vec<String>, Error> {
+        self.send_batches(None, from, txns)
+    }
+
+    /// Sends transactions as `send_ordered_transactions` does, signed by `signer` or
+    /// else by the account that signs transactions sent from `from`
+    fn send_batches(
+        &self,
+        signer: Option<&Account>,
+        from: &str,
+        txns: &[(&SethTransaction, Option<&[AccessListEntry]>)],
+    ) -> Result<Vec<String>, Error> {
+        for (txn, _) in txns {
+            self.screen(from, txn)?;
+        }
+
+        let mut batches = Vec::new();
+        let mut txn_ids: Vec<Vec<String>> = Vec::new();
+        for chunk in txns.chunks(self.max_batch_size) {
+            let dependencies: Vec<String> = txn_ids
+                .last()
+                .and_then(|ids| ids.last())
+                .cloned()
+                .into_iter()
+                .collect();
+            let (batch, batch_txn_ids) = self.make_batch(signer, from, chunk, &dependencies)?;
+            batches.push(batch);
+            txn_ids.push(batch_txn_ids);
+        }
+
+        self.submit(batches.clone())?;
+
+        for (batch, batch_txn_ids) in batches.iter().zip(&txn_ids) {
+            self.check_batch_status(&batch.header_signature, batch_txn_ids, None)?;
+            for (txn, txn_id) in batch.transactions.iter().zip(batch_txn_ids) {
+                self.txpool.insert(txn.clone(), &batch.header_signature);
+
+                if let Some(ref session) = self.session {
+                    self.sessions.add_transaction(session, txn_id);
+                }
+            }
+        }
+
+        Ok(txn_ids.into_iter().flatten().collect())
+    }
+
+    /// Shows a transaction to each screen in turn, failing with the reason of the
+    /// first that denies it. A screen that fails denies the transaction, so that
+    /// a policy service that is down lets nothing through.
+    fn screen(&self, from: &str, txn: &SethTransaction) -> Result<(), Error> {
+        if self.screens.is_empty() {
+            return Ok(());
+        }
+        let mut screened = ScreenedTransaction::new(from, txn);
+        if self.screens.iter().any(|screen| screen.simulates()) {
+            screened.simulation = self.simulate(&screened.from, txn).map_err(|error| {
+                error!("Failed to simulate transaction: {}", error);
+                Error::TransactionDenied(String::from("Transaction couldn't be simulated"))
+            })?;
+        }
+        for screen in &self.screens {
+            match screen.screen(&screened) {
+                Ok(None) => (),
+                Ok(Some(reason)) => return Err(Error::TransactionDenied(reason)),
+                Err(error) => {
+                    error!("Failed to screen transaction: {}", error);
+                    return Err(Error::TransactionDenied(String::from(
+                        "Transaction couldn't be screened",
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs a transaction against the state of the latest block, or returns none if
+    /// it doesn't run in the EVM. Each transaction is run on its own, so one that
+    /// depends on another sent with it is run without it.
+    fn simulate(&self, from: &str, txn: &SethTransaction) -> Result<Option<Simulation>, Error> {
+        let call = match txn.evm_call(from) {
+            Ok(Some(call)) => call,
+            Ok(None) => return Ok(None),
+            Err(error) => return Ok(Some(Simulation::failed(error))),
+        };
+        let call = vm::Call {
+            gas: call.gas.min(self.gas_cap),
+            ..call
+        };
+        let block = self.get_block(BlockKey::Latest)?;
+        vm::simulate(
+            self,
+            &state_root(&block)?,
+            block_num(&block)?,
+            self.get_chain_id(BlockKey::Latest)?,
+            call,
+        )
+        .map(|simulated| Some(Simulation::new(simulated)))
+        .map_err(|error| Error::CommunicationError(format!("{:?}", error)))
+    }
+
+    /// Sends batches that were signed elsewhere, such as those of another network's
+    /// blocks, unchanged, and waits for the validator to finish validating each in
+    /// turn. Returns the error of the first one that was rejected.
+    pub fn submit_batches(&self, batches: Vec<Batch>) -> Result<(), Error> {
+        self.submit(batches.clone())?;
+
+        for batch in &batches {
+            let txn_ids: Vec<String> = batch
+                .transactions
+                .iter()
+                .map(|txn| txn.header_signature.clone())
+                .collect();
+            self.check_batch_status(
+                &batch.header_signature,
+                &txn_ids,
+                Some(BATCH_STATUS_TIMEOUT),
+            )?;
+        }
+        Ok(())
+    }
 
+    fn submit(&self, batches: Vec<Batch>) -> Result<(), Error> {
         let mut request = ClientBatchSubmitRequest::new();
-        request.set_batches(protobuf::RepeatedField::from_vec(vec![batch]));
+        request.set_batches(protobuf::RepeatedField::from_vec(batches));
 
         let response: ClientBatchSubmitResponse =
             self.send_request(Message_MessageType::CLIENT_BATCH_SUBMIT_REQUEST, &request)?;
 
         match response.status {
             ClientBatchSubmitResponse_Status::STATUS_UNSET => Err(Error::ValidatorError),
-            ClientBatchSubmitResponse_Status::OK => Ok(txn_signature),
+            ClientBatchSubmitResponse_Status::OK => Ok(()),
             ClientBatchSubmitResponse_Status::INTERNAL_ERROR => Err(Error::ValidatorError),
             ClientBatchSubmitResponse_Status::INVALID_BATCH => Err(Error::InvalidTransaction),
             ClientBatchSubmitResponse_Status::QUEUE_FULL => Err(Error::ValidatorError),
         }
     }
 
-    pub fn make_batch(&self, from: &str, txn: &SethTransaction) -> Result<(Batch, String), Error> {
-        let payload = protobuf::Message::write_to_bytes(&txn.to_pb()).map_err(|error| {
-            Error::ParseError(format!("Error serializing payload: {:?}", error))
-        })?;
+    /// Checks whether the validator has found the given batch invalid, first waiting
+    /// up to `timeout` seconds for it to finish validating the batch if given. If the
+    /// transaction processor rejected it, the reason it gave is returned as
+    /// `TransactionRejected`. The batch was submitted either way, so one that is still
+    /// pending, or whose status can't be read, is treated as accepted.
+    fn check_batch_status(
+        &self,
+        batch_id: &str,
+        txn_ids: &[String],
+        timeout: Option<u32>,
+    ) -> Result<(), Error> {
+        let mut request = ClientBatchStatusRequest::new();
+        request.set_batch_ids(protobuf::RepeatedField::from_vec(vec![String::from(
+            batch_id,
+        )]));
+        if let Some(timeout) = timeout {
+            request.set_wait(true);
+            request.set_timeout(timeout);
+        }
+
+        let response: Result<ClientBatchStatusResponse, Error> =
+            self.send_request(Message_MessageType::CLIENT_BATCH_STATUS_REQUEST, &request);
+        let mut response = match response {
+            Ok(response) if response.status == ClientBatchStatusResponse_Status::OK => response,
+            Ok(response) => {
+                warn!(
+                    "Couldn't get the status of batch {}: {:?}",
+                    batch_id, response.status
+                );
+                return Ok(());
+            }
+            Err(error) => {
+                warn!("Couldn't get the status of batch {}: {:?}", batch_id, error);
+                return Ok(());
+            }
+        };
+
+        let statuses = response.take_batch_statuses().into_vec();
+        match statuses.first() {
+            Some(status) if status.status == ClientBatchStatus_Status::INVALID => {
+                let invalid = status
+                    .get_invalid_transactions()
+                    .iter()
+                    .find(|invalid| txn_ids.contains(&invalid.transaction_id))
+                    .or_else(|| status.get_invalid_transactions().first());
+                let txn_id = invalid
+                    .map(|invalid| invalid.transaction_id.clone())
+                    .filter(|txn_id| !txn_id.is_empty())
+                    .or_else(|| txn_ids.first().cloned())
+                    .unwrap_or_default();
+                let message = invalid
+                    .map(|invalid| invalid.message.clone())
+                    .unwrap_or_else(|| String::from("Transaction was rejected"));
+                // Transaction processors older than the error codes send none
+                let code = invalid
+                    .and_then(|invalid| str::from_utf8(&invalid.extended_data).ok())
+                    .and_then(ErrorCode::from_name)
+                    .unwrap_or(ErrorCode::InvalidTransaction);
+                Err(Error::TransactionRejected(txn_id, message, code))
+            }
+            _ => Ok(()),
+        }
+    }
 
-        let unlocked_account = self.unlocked_account.read().unwrap().clone();
+    /// Returns the account that signs transactions sent from `from`
+    fn signing_account(&self, from: &str, txn: &SethTransaction) -> Result<Account, Error> {
+        let unlocked_account = self.unlocked_account();
         let account = match (unlocked_account, txn) {
             (Some(ref acc), SethTransaction::CreateExternalAccount(ref txnpb)) => {
                 match (txnpb.to.len(), from == acc.address()) {
@@ -353,21 +1133,30 @@ impl<S: MessageSender> ValidatorClient<S> {
                 error!("Account with address `{}` not found.", from);
                 Err(Error::AccountLoadError)?
             }
-        }
-        .clone();
+        };
+        Ok(account)
+    }
+
+    fn make_transaction(
+        &self,
+        account: &Account,
+        from: &str,
+        txn: &SethTransaction,
+        access_list: Option<&[AccessListEntry]>,
+        dependencies: &[String],
+    ) -> Result<TransactionPb, Error> {
+        let payload = protobuf::Message::write_to_bytes(&txn.to_pb()).map_err(|error| {
+            Error::ParseError(format!("Error serializing payload: {:?}", error))
+        })?;
 
         let mut txn_header = TransactionHeader::new();
         txn_header.set_batcher_public_key(String::from(account.public_key()));
         txn_header.set_family_name(String::from("seth"));
-        txn_header.set_family_version(String::from("1.0"));
-        txn_header.set_inputs(protobuf::RepeatedField::from_vec(vec![
-            String::from(SETH_NS),
-            String::from(BLOCK_INFO_NS),
-        ]));
-        txn_header.set_outputs(protobuf::RepeatedField::from_vec(vec![
-            String::from(SETH_NS),
-            String::from(BLOCK_INFO_NS),
-        ]));
+        txn_header.set_family_version(self.family_version.clone());
+        let (inputs, outputs) = state_access(from, txn, access_list, &self.family_version);
+        txn_header.set_inputs(protobuf::RepeatedField::from_vec(inputs));
+        txn_header.set_outputs(protobuf::RepeatedField::from_vec(outputs));
+        txn_header.set_dependencies(protobuf::RepeatedField::from_vec(dependencies.to_vec()));
 
         let mut sha = Sha512::new();
         sha.input(&payload);
@@ -383,14 +1172,46 @@ impl<S: MessageSender> ValidatorClient<S> {
 
         let mut txn = TransactionPb::new();
         txn.set_header(txn_header_bytes);
-        txn.set_header_signature(txn_signature.clone());
+        txn.set_header_signature(txn_signature);
         txn.set_payload(payload);
 
+        Ok(txn)
+    }
+
+    /// Makes a batch of transactions from one account, which the validator runs in
+    /// order. `dependencies` are the ids of transactions that must be committed
+    /// before the first of them runs. Without a `signer`, the batch is signed by the
+    /// account that signs transactions sent from `from`.
+    pub fn make_batch(
+        &self,
+        signer: Option<&Account>,
+        from: &str,
+        txns: &[(&SethTransaction, Option<&[AccessListEntry]>)],
+        dependencies: &[String],
+    ) -> Result<(Batch, Vec<String>), Error> {
+        let account = match signer {
+            Some(account) => account.clone(),
+            None => self.signing_account(from, txns[0].0)?,
+        };
+        let mut transactions = Vec::new();
+        for (index, &(txn, access_list)) in txns.iter().enumerate() {
+            let dependencies = if index == 0 { dependencies } else { &[] };
+            transactions.push(self.make_transaction(
+                &account,
+                from,
+                txn,
+                access_list,
+                dependencies,
+            )?);
+        }
+        let txn_ids: Vec<String> = transactions
+            .iter()
+            .map(|txn| txn.header_signature.clone())
+            .collect();
+
         let mut batch_header = BatchHeader::new();
         batch_header.set_signer_public_key(String::from(account.public_key()));
-        batch_header.set_transaction_ids(protobuf::RepeatedField::from_vec(vec![
-            txn_signature.clone()
-        ]));
+        batch_header.set_transaction_ids(protobuf::RepeatedField::from_vec(txn_ids.clone()));
         let batch_header_bytes =
             protobuf::Message::write_to_bytes(&batch_header).map_err(|error| {
                 Error::ParseError(format!("Error serializing batch header: {:?}", error))
@@ -401,30 +1222,19 @@ impl<S: MessageSender> ValidatorClient<S> {
         let mut batch = Batch::new();
         batch.set_header(batch_header_bytes);
         batch.set_header_signature(batch_signature);
-        batch.set_transactions(protobuf::RepeatedField::from_vec(vec![txn]));
+        batch.set_transactions(protobuf::RepeatedField::from_vec(transactions));
 
-        Ok((batch, txn_signature))
+        Ok((batch, txn_ids))
     }
 
     pub fn get_receipts_from_block(
         &self,
         block: &Block,
     ) -> Result<HashMap<String, SethReceipt>, String> {
-        let batches = &block.batches;
-        let mut transactions = Vec::new();
-        for batch in batches.iter() {
-            for txn in batch.transactions.iter() {
-                let header: TransactionHeader = match protobuf::parse_from_bytes(&txn.header) {
-                    Ok(h) => h,
-                    Err(_) => {
-                        continue;
-                    }
-                };
-                if header.family_name == "seth" {
-                    transactions.push(txn.header_signature.clone());
-                }
-            }
-        }
+        let transactions: Vec<String> = seth_transactions(block)
+            .into_iter()
+            .map(|(_, txn)| txn.header_signature.clone())
+            .collect();
 
         let receipts = self
             .get_receipts(&transactions)
@@ -441,10 +1251,22 @@ impl<S: MessageSender> ValidatorClient<S> {
         &self,
         transaction_ids: &[String],
     ) -> Result<HashMap<String, SethReceipt>, Error> {
+        let mut seth_receipt_map = HashMap::with_capacity(transaction_ids.len());
+        let mut missing = Vec::new();
+        for transaction_id in transaction_ids {
+            match self.chain_cache.get_receipt(transaction_id) {
+                Some(receipt) => {
+                    seth_receipt_map.insert(transaction_id.clone(), receipt);
+                }
+                None => missing.push(transaction_id.clone()),
+            }
+        }
+        if missing.is_empty() {
+            return Ok(seth_receipt_map);
+        }
+
         let mut request = ClientReceiptGetRequest::new();
-        request.set_transaction_ids(protobuf::RepeatedField::from_vec(Vec::from(
-            transaction_ids,
-        )));
+        request.set_transaction_ids(protobuf::RepeatedField::from_vec(missing));
         let response: ClientReceiptGetResponse =
             self.send_request(Message_MessageType::CLIENT_RECEIPT_GET_REQUEST, &request)?;
 
@@ -467,8 +1289,8 @@ impl<S: MessageSender> ValidatorClient<S> {
             .iter()
             .map(SethReceipt::from_receipt_pb)
             .collect::<Result<Vec<SethReceipt>, Error>>()?;
-        let mut seth_receipt_map = HashMap::with_capacity(seth_receipt_list.len());
         for receipt in seth_receipt_list {
+            self.chain_cache.insert_receipt(&receipt);
             seth_receipt_map.insert(receipt.transaction_id.clone(), receipt);
         }
 
@@ -495,67 +1317,260 @@ impl<S: MessageSender> ValidatorClient<S> {
                     ClientTransactionGetResponse_Status::INTERNAL_ERROR => {
                         Err(Error::ValidatorError)
                     }
-                    ClientTransactionGetResponse_Status::NO_RESOURCE => Err(Error::NoResource),
+                    ClientTransactionGetResponse_Status::NO_RESOURCE => {
+                        self.get_pending_transaction(txn_id)
+                    }
                     ClientTransactionGetResponse_Status::INVALID_ID => Err(Error::ValidatorError),
                     ClientTransactionGetResponse_Status::OK => {
+                        self.txpool.remove(txn_id);
                         let txn = Transaction::try_from(response.take_transaction())?;
                         Ok((txn, block))
                     }
                 }
             }
-            TransactionKey::Index((ref index, ref block_key)) => {
-                let mut idx = *index;
-                let mut block = self.get_block((*block_key).clone())?;
-                for mut batch in block.take_batches().into_iter() {
-                    for txn in batch.take_transactions().into_iter() {
-                        if idx == 0 {
-                            let txn = Transaction::try_from(txn)?;
-                            return Ok((txn, Some(block)));
-                        }
-                        idx -= 1;
-                    }
-                }
-                Err(Error::NoResource)
+            TransactionKey::Index((index, ref block_key)) => {
+                let block = self.get_block((*block_key).clone())?;
+                let txn = seth_transactions(&block)
+                    .into_iter()
+                    .find(|&(txn_idx, _)| txn_idx == index)
+                    .map(|(_, txn)| txn.clone())
+                    .ok_or(Error::NoResource)?;
+                Ok((Transaction::try_from(txn)?, Some(block)))
             }
         }
     }
 
-    pub fn get_block(&self, block_key: BlockKey) -> Result<Block, Error> {
-        let response: ClientBlockGetResponse;
-        match block_key {
-            BlockKey::Signature(block_id) => {
-                let mut request = ClientBlockGetByIdRequest::new();
-                let message_type: Message_MessageType =
-                    Message_MessageType::CLIENT_BLOCK_GET_BY_ID_REQUEST;
-                request.set_block_id(block_id);
-                response = self.send_request(message_type, &request)?;
+    /// Looks up a transaction this server sent that the validator doesn't have in a
+    /// block. Returns it without a block while its batch is pending, or with its block
+    /// if it was committed in the meantime.
+    fn get_pending_transaction(&self, txn_id: &str) -> Result<(Transaction, Option<Block>), Error> {
+        let (txn, batch_id) = self.txpool.get(txn_id).ok_or(Error::NoResource)?;
+
+        let mut request = ClientBatchStatusRequest::new();
+        request.set_batch_ids(protobuf::RepeatedField::from_vec(vec![batch_id]));
+        let mut response: ClientBatchStatusResponse =
+            self.send_request(Message_MessageType::CLIENT_BATCH_STATUS_REQUEST, &request)?;
+
+        match response.status {
+            ClientBatchStatusResponse_Status::OK => (),
+            ClientBatchStatusResponse_Status::NO_RESOURCE => {
+                self.txpool.remove(txn_id);
+                return Err(Error::NoResource);
             }
-            BlockKey::Number(block_num) => {
-                let mut request = ClientBlockGetByNumRequest::new();
-                let message_type: Message_MessageType =
-                    Message_MessageType::CLIENT_BLOCK_GET_BY_NUM_REQUEST;
-                request.set_block_num(block_num);
-                response = self.send_request(message_type, &request)?;
+            _ => return Err(Error::ValidatorError),
+        }
+
+        let status = response
+            .take_batch_statuses()
+            .into_iter()
+            .next()
+            .map(|status| status.status);
+        match status {
+            Some(ClientBatchStatus_Status::PENDING) => Ok((Transaction::try_from(txn)?, None)),
+            Some(ClientBatchStatus_Status::COMMITTED) => {
+                self.txpool.remove(txn_id);
+                let block = self.get_block(BlockKey::Transaction(String::from(txn_id)))?;
+                Ok((Transaction::try_from(txn)?, Some(block)))
             }
-            BlockKey::Latest => {
-                return self.get_current_block();
+            _ => {
+                self.txpool.remove(txn_id);
+                Err(Error::NoResource)
             }
-            BlockKey::Earliest => {
-                let mut request = ClientBlockGetByIdRequest::new();
+        }
+    }
+
+    /// Returns the nonce of the next transaction an account sends, given its nonce in
+    /// the latest state, by counting the transactions this server has sent from it
+    /// since, in order. Transactions below the account's nonce have been committed,
+    /// and those the validator rejected or no longer knows of never will be, so
+    /// both are forgotten. If the validator can't be asked, every transaction that
+    /// was sent is counted.
+    fn pending_nonce(&self, address: &str, nonce: u64) -> u64 {
+        let mut sent = Vec::new();
+        for (txn_id, batch_id, txn_nonce) in self.txpool.sent_from(address) {
+            if txn_nonce < nonce {
+                self.txpool.remove(&txn_id);
+            } else {
+                sent.push((txn_id, batch_id, txn_nonce));
+            }
+        }
+        if sent.is_empty() {
+            return nonce;
+        }
+
+        let mut batch_ids: Vec<String> = sent
+            .iter()
+            .map(|(_, batch_id, _)| batch_id.clone())
+            .collect();
+        batch_ids.sort();
+        batch_ids.dedup();
+        match self.get_batch_statuses(batch_ids) {
+            Ok(statuses) => sent.retain(|(txn_id, batch_id, _)| match statuses.get(batch_id) {
+                Some(ClientBatchStatus_Status::PENDING)
+                | Some(ClientBatchStatus_Status::COMMITTED) => true,
+                _ => {
+                    self.txpool.remove(txn_id);
+                    false
+                }
+            }),
+            Err(error) => warn!("Couldn't get the status of pending batches: {:?}", error),
+        }
+
+        let nonces: HashSet<u64> = sent.iter().map(|&(_, _, txn_nonce)| txn_nonce).collect();
+        let mut next = nonce;
+        while nonces.contains(&next) {
+            next += 1;
+        }
+        next
+    }
+
+    /// Returns the transactions this server has sent that haven't been committed, by
+    /// sender and nonce, split into those that are pending, which follow on from
+    /// their sender's nonce, and those that are queued behind a nonce that hasn't
+    /// been sent. Transactions in batches the validator reports as committed,
+    /// rejected or unknown are forgotten. If the validator can't be asked, every
+    /// transaction that was sent is counted.
+    pub fn get_pool_content(&self) -> Result<(PoolTransactions, PoolTransactions), Error> {
+        let sent = self.txpool.all();
+        let mut batch_ids: Vec<String> =
+            sent.iter().map(|(_, batch_id)| batch_id.clone()).collect();
+        batch_ids.sort();
+        batch_ids.dedup();
+        let statuses = if batch_ids.is_empty() {
+            None
+        } else {
+            match self.get_batch_statuses(batch_ids) {
+                Ok(statuses) => Some(statuses),
+                Err(error) => {
+                    warn!("Couldn't get the status of pending batches: {:?}", error);
+                    None
+                }
+            }
+        };
+
+        let mut by_sender: PoolTransactions = BTreeMap::new();
+        for (txn, batch_id) in sent {
+            let txn_id = txn.header_signature.clone();
+            if let Some(ref statuses) = statuses {
+                if statuses.get(&batch_id) != Some(&ClientBatchStatus_Status::PENDING) {
+                    self.txpool.remove(&txn_id);
+                    continue;
+                }
+            }
+            match Transaction::try_from(txn) {
+                Ok(txn) => {
+                    by_sender
+                        .entry(txn.from_addr())
+                        .or_default()
+                        .insert(txn.nonce(), txn);
+                }
+                Err(error) => warn!("Couldn't parse pending transaction {}: {:?}", txn_id, error),
+            }
+        }
+
+        let mut pending: PoolTransactions = BTreeMap::new();
+        let mut queued: PoolTransactions = BTreeMap::new();
+        for (address, txns) in by_sender {
+            let mut next = self
+                .get_account(&address, BlockKey::Latest)
+                .map_err(Error::CommunicationError)?
+                .map_or(0, |account| account.nonce);
+            for (nonce, txn) in txns {
+                let pool = if nonce < next {
+                    // Committed since, or replaced by a transaction that was
+                    self.txpool.remove(&txn.hash());
+                    continue;
+                } else if nonce == next {
+                    next += 1;
+                    &mut pending
+                } else {
+                    &mut queued
+                };
+                pool.entry(address.clone()).or_default().insert(nonce, txn);
+            }
+        }
+
+        Ok((pending, queued))
+    }
+
+    /// Returns the status of each batch, without waiting for them to be committed.
+    /// Batches the validator doesn't know of are left out.
+    fn get_batch_statuses(
+        &self,
+        batch_ids: Vec<String>,
+    ) -> Result<HashMap<String, ClientBatchStatus_Status>, Error> {
+        let mut request = ClientBatchStatusRequest::new();
+        request.set_batch_ids(protobuf::RepeatedField::from_vec(batch_ids));
+        let mut response: ClientBatchStatusResponse =
+            self.send_request(Message_MessageType::CLIENT_BATCH_STATUS_REQUEST, &request)?;
+
+        match response.status {
+            ClientBatchStatusResponse_Status::OK => (),
+            ClientBatchStatusResponse_Status::NO_RESOURCE => return Ok(HashMap::new()),
+            _ => return Err(Error::ValidatorError),
+        }
+
+        Ok(response
+            .take_batch_statuses()
+            .into_iter()
+            .filter(|status| status.status != ClientBatchStatus_Status::UNKNOWN)
+            .map(|mut status| (status.take_batch_id(), status.status))
+            .collect())
+    }
+
+    pub fn get_block(&self, block_key: BlockKey) -> Result<Block, Error> {
+        let response: ClientBlockGetResponse;
+        match block_key {
+            BlockKey::Signature(block_id) => {
+                if let Some(block) = self.chain_cache.get_block(&block_id) {
+                    return Ok(block);
+                }
+                let mut request = ClientBlockGetByIdRequest::new();
+                let message_type: Message_MessageType =
+                    Message_MessageType::CLIENT_BLOCK_GET_BY_ID_REQUEST;
+                request.set_block_id(block_id);
+                response = self.send_request(message_type, &request)?;
+            }
+            BlockKey::Number(block_num) => {
+                let mut request = ClientBlockGetByNumRequest::new();
+                let message_type: Message_MessageType =
+                    Message_MessageType::CLIENT_BLOCK_GET_BY_NUM_REQUEST;
+                request.set_block_num(block_num);
+                response = self.send_request(message_type, &request)?;
+            }
+            BlockKey::Latest | BlockKey::Pending => {
+                return self.get_current_block();
+            }
+            BlockKey::Finalized => {
+                return self.get_block(BlockKey::Number(self.finalized_block_num()?));
+            }
+            BlockKey::Earliest => {
+                let mut request = ClientBlockGetByIdRequest::new();
                 let message_type: Message_MessageType =
                     Message_MessageType::CLIENT_BLOCK_GET_BY_ID_REQUEST;
                 request.set_block_id(String::from("0000000000000000"));
                 response = self.send_request(message_type, &request)?;
             }
             BlockKey::Transaction(transaction_id) => {
+                if !self
+                    .capabilities
+                    .supports(Message_MessageType::CLIENT_BLOCK_GET_BY_TRANSACTION_ID_REQUEST)
+                {
+                    return self.find_block_of_transaction(&transaction_id);
+                }
                 let mut request = ClientBlockGetByTransactionIdRequest::new();
                 let message_type: Message_MessageType =
                     Message_MessageType::CLIENT_BLOCK_GET_BY_TRANSACTION_ID_REQUEST;
                 request.set_transaction_id(transaction_id);
                 response = self.send_request(message_type, &request)?;
             }
+            BlockKey::StateRoot(_) => return Err(Error::NoResource),
         };
 
+        self.block_of_response(response)
+    }
+
+    fn block_of_response(&self, response: ClientBlockGetResponse) -> Result<Block, Error> {
         match response.status {
             ClientBlockGetResponse_Status::STATUS_UNSET => Err(Error::ValidatorError),
             ClientBlockGetResponse_Status::INTERNAL_ERROR => Err(Error::ValidatorError),
@@ -563,6 +1578,7 @@ impl<S: MessageSender> ValidatorClient<S> {
             ClientBlockGetResponse_Status::INVALID_ID => Err(Error::ValidatorError),
             ClientBlockGetResponse_Status::OK => {
                 if let Some(block) = response.block.into_option() {
+                    self.chain_cache.insert_block(&block);
                     Ok(block)
                 } else {
                     Err(Error::NoResource)
@@ -571,86 +1587,113 @@ impl<S: MessageSender> ValidatorClient<S> {
         }
     }
 
+    /// Returns the blocks from `start` to `end` in order, as they are fetched. The
+    /// requests for `BLOCK_RANGE_WINDOW` blocks at a time are sent together, so a
+    /// range takes a round trip to the validator per window rather than per block.
+    /// Fetching stops after the first error, which is `NoResource` past the latest
+    /// block.
+    pub fn get_blocks_range(&self, start: u64, end: u64) -> BlockRange<'_, S> {
+        BlockRange {
+            client: self,
+            next: if start <= end { Some(start) } else { None },
+            end,
+            fetched: VecDeque::new(),
+        }
+    }
+
+    fn get_blocks_by_num(&self, start: u64, end: u64) -> Vec<Result<Block, Error>> {
+        let requests: Vec<ClientBlockGetByNumRequest> = (start..=end)
+            .map(|block_num| {
+                let mut request = ClientBlockGetByNumRequest::new();
+                request.set_block_num(block_num);
+                request
+            })
+            .collect();
+        self.send_requests(
+            Message_MessageType::CLIENT_BLOCK_GET_BY_NUM_REQUEST,
+            &requests,
+        )
+        .into_iter()
+        .map(|response| response.and_then(|response| self.block_of_response(response)))
+        .collect()
+    }
+
     pub fn get_entry(
         &self,
         account_address: &str,
         block: BlockKey,
     ) -> Result<Option<EvmEntry>, String> {
-        let address = String::from(SETH_NS) + account_address + "000000000000000000000000";
-        let mut request = ClientStateGetRequest::new();
-        request.set_address(address);
-        match block {
-            BlockKey::Latest => {}
-            BlockKey::Earliest => {
-                match self.block_id_to_state_root(String::from("0000000000000000")) {
-                    Ok(state_root) => {
-                        request.set_state_root(state_root);
-                    }
-                    Err(error) => {
-                        return Err(format!("{:?}", error));
+        self.get_state(account_state_address(account_address), block)
+    }
+
+    fn get_state<M>(&self, address: String, block: BlockKey) -> Result<Option<M>, String>
+    where
+        M: protobuf::Message,
+    {
+        let state_root = self
+            .block_to_state_root(block)
+            .map_err(|error| format!("{:?}", error))?;
+
+        // Reads of the latest state are served from the prefetched block, if any
+        let state_data = match (state_root, self.prefetcher.as_ref()) {
+            (None, Some(prefetcher)) => match prefetcher.latest_root() {
+                Some(root) => match prefetcher.get(&root, &address) {
+                    Some(value) => value,
+                    None => {
+                        let value = self.get_state_entry(&address, Some(root.clone()))?;
+                        prefetcher.insert(&root, &address, value.clone());
+                        value
                     }
-                }
-            }
-            BlockKey::Signature(block_id) => match self.block_id_to_state_root(block_id) {
-                Ok(state_root) => {
-                    request.set_state_root(state_root);
-                }
-                Err(error) => {
-                    return Err(format!("{:?}", error));
-                }
+                },
+                None => self.get_state_entry(&address, None)?,
             },
-            BlockKey::Number(block_num) => match self.block_num_to_state_root(block_num) {
-                Ok(state_root) => {
-                    request.set_state_root(state_root);
-                }
-                Err(error) => {
-                    return Err(format!("{:?}", error));
-                }
-            },
-            BlockKey::Transaction(transaction_id) => {
-                match self.transaction_to_state_root(transaction_id) {
-                    Ok(state_root) => {
-                        request.set_state_root(state_root);
-                    }
-                    Err(error) => {
-                        return Err(format!("{:?}", error));
-                    }
-                }
+            (state_root, _) => self.get_state_entry(&address, state_root)?,
+        };
+
+        match state_data.map(|data| protobuf::parse_from_bytes(&data)) {
+            None => Ok(None),
+            Some(Ok(e)) => Ok(Some(e)),
+            Some(Err(error)) => Err(format!("Failed to deserialize state entry: {:?}", error)),
+        }
+    }
+
+    /// Returns the raw state entry at the given address and state root, or in the
+    /// latest state if no root is given
+    pub fn get_state_entry(
+        &self,
+        address: &str,
+        state_root: Option<String>,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if let Some(ref state_root) = state_root {
+            if let Some(value) = self.chain_cache.get_state(state_root, address) {
+                return Ok(value);
             }
         }
 
+        let mut request = ClientStateGetRequest::new();
+        request.set_address(String::from(address));
+        if let Some(ref state_root) = state_root {
+            request.set_state_root(state_root.clone());
+        }
+
         let response: ClientStateGetResponse =
             self.request(Message_MessageType::CLIENT_STATE_GET_REQUEST, &request)?;
 
-        let state_data = match response.status {
-            ClientStateGetResponse_Status::STATUS_UNSET => {
-                return Err(String::from("Internal error"));
-            }
-            ClientStateGetResponse_Status::OK => response.value,
-            ClientStateGetResponse_Status::NO_RESOURCE => {
-                return Ok(None);
-            }
-            ClientStateGetResponse_Status::INTERNAL_ERROR => {
-                return Err(String::from("Internal error"));
-            }
-            ClientStateGetResponse_Status::NOT_READY => {
-                return Err(String::from("Validator isn't ready"));
-            }
-            ClientStateGetResponse_Status::NO_ROOT => {
-                return Err(String::from("No root"));
-            }
-            ClientStateGetResponse_Status::INVALID_ADDRESS => {
-                return Err(String::from("Invalid address"));
-            }
-            ClientStateGetResponse_Status::INVALID_ROOT => {
-                return Err(String::from("Invalid root"));
-            }
-        };
-
-        match protobuf::parse_from_bytes(&state_data) {
-            Ok(e) => Ok(Some(e)),
-            Err(error) => Err(format!("Failed to deserialize EVM entry: {:?}", error)),
+        let value = match response.status {
+            ClientStateGetResponse_Status::STATUS_UNSET => Err(String::from("Internal error")),
+            ClientStateGetResponse_Status::OK => Ok(Some(response.value)),
+            ClientStateGetResponse_Status::NO_RESOURCE => Ok(None),
+            ClientStateGetResponse_Status::INTERNAL_ERROR => Err(String::from("Internal error")),
+            ClientStateGetResponse_Status::NOT_READY => Err(String::from("Validator isn't ready")),
+            ClientStateGetResponse_Status::NO_ROOT => Err(String::from("No root")),
+            ClientStateGetResponse_Status::INVALID_ADDRESS => Err(String::from("Invalid address")),
+            ClientStateGetResponse_Status::INVALID_ROOT => Err(String::from("Invalid root")),
+        }?;
+        if let Some(ref state_root) = state_root {
+            self.chain_cache
+                .insert_state(state_root, address, value.clone());
         }
+        Ok(value)
     }
 
     pub fn get_account(
@@ -658,17 +1701,68 @@ impl<S: MessageSender> ValidatorClient<S> {
         account_address: &str,
         block: BlockKey,
     ) -> Result<Option<EvmStateAccount>, String> {
-        self.get_entry(account_address, block)
-            .map(|option| option.map(|mut entry| entry.take_account()))
+        let pending = matches!(block, BlockKey::Pending);
+        let account = match self.get_entry(account_address, block.clone())? {
+            Some(mut entry) => {
+                self.chain_cache
+                    .remember_code(entry.get_account().get_code());
+                Some(entry.take_account())
+            }
+            None => self.get_predeploy(account_address, block)?,
+        };
+        if !pending {
+            return Ok(account);
+        }
+
+        let nonce = account.as_ref().map_or(0, |account| account.nonce);
+        let pending_nonce = self.pending_nonce(account_address, nonce);
+        if pending_nonce == nonce {
+            return Ok(account);
+        }
+        let mut account = account.unwrap_or_else(|| {
+            let mut account = EvmStateAccount::new();
+            account.address = transform::hex_str_to_bytes(account_address).unwrap_or_default();
+            account
+        });
+        account.nonce = pending_nonce;
+        Ok(Some(account))
     }
 
-    pub fn get_storage(
+    /// Returns the account of a well-known contract that is enabled by a setting but
+    /// hasn't been written to state yet
+    fn get_predeploy(
         &self,
         account_address: &str,
         block: BlockKey,
-    ) -> Result<Option<Vec<EvmStorage>>, String> {
-        self.get_entry(account_address, block)
-            .map(|option| option.map(|mut entry| entry.take_storage().into_vec()))
+    ) -> Result<Option<EvmStateAccount>, String> {
+        let key = match WELL_KNOWN_CONTRACTS
+            .iter()
+            .find(|&&(address, _)| address == account_address)
+        {
+            Some(&(_, key)) => key,
+            None => return Ok(None),
+        };
+
+        let setting: Option<Setting> = self.get_state(setting_address(key), block)?;
+        let code = setting
+            .and_then(|setting| {
+                setting
+                    .get_entries()
+                    .iter()
+                    .find(|entry| entry.key == key)
+                    .and_then(|entry| {
+                        transform::hex_str_to_bytes(entry.value.trim_start_matches("0x"))
+                    })
+            })
+            .filter(|code| !code.is_empty());
+
+        Ok(code.map(|code| {
+            let mut account = EvmStateAccount::new();
+            account.set_address(transform::hex_str_to_bytes(account_address).unwrap_or_default());
+            account.set_code(code);
+            account.set_permissions(EvmPermissions::new());
+            account
+        }))
     }
 
     pub fn get_storage_at(
@@ -677,28 +1771,214 @@ impl<S: MessageSender> ValidatorClient<S> {
         storage_address: &str,
         block: BlockKey,
     ) -> Result<Option<Vec<u8>>, String> {
-        let storage = self.get_storage(account_address, block)?;
+        let position = match transform::hex_str_to_bytes(&storage_address) {
+            Some(ref p) if p.len() <= 32 => pad_word(p),
+            _ => {
+                return Err(String::from("Failed to decode position, invalid hex."));
+            }
+        };
 
-        match storage {
-            Some(storage) => {
-                let position = match transform::hex_str_to_bytes(&storage_address) {
-                    Some(p) => p,
-                    None => {
-                        return Err(String::from("Failed to decode position, invalid hex."));
-                    }
-                };
-                for entry in storage {
-                    if entry.key == position {
-                        return Ok(Some(entry.value));
-                    }
+        let entry = self.get_entry(account_address, block.clone())?;
+        let generation = entry.as_ref().map_or(0, EvmEntry::get_storage_generation);
+        let slots: Option<EvmStorageEntry> = self.get_state(
+            storage_state_address(account_address, &transform::bytes_to_hex_str(&position)),
+            block,
+        )?;
+        if let Some(value) = slots
+            .filter(|slots| slots.generation == generation)
+            .and_then(|mut slots| find_storage(slots.take_storage(), &position))
+        {
+            return Ok(Some(value));
+        }
+
+        // Contracts deployed before storage was split out may still have it in their
+        // account entry
+        Ok(entry.and_then(|mut entry| find_storage(entry.take_storage(), &position)))
+    }
+
+    /// Reads accounts together with their storage, each in a single request that lists
+    /// the state entries of the account's namespace, with the requests sent at once.
+    /// Only the slots in the first `limit` entries are read, so accounts with more
+    /// storage than that are left incomplete.
+    pub fn get_account_states(
+        &self,
+        account_addresses: &[String],
+        block: BlockKey,
+        limit: i32,
+    ) -> Vec<Result<AccountState, String>> {
+        let state_root = match self.block_to_state_root(block.clone()) {
+            Ok(state_root) => state_root,
+            Err(error) => {
+                return account_addresses
+                    .iter()
+                    .map(|_| Err(format!("{:?}", error)))
+                    .collect()
+            }
+        };
+        let requests: Vec<ClientStateListRequest> = account_addresses
+            .iter()
+            .map(|account_address| {
+                let mut request = ClientStateListRequest::new();
+                request.set_address(account_namespace(account_address));
+                if let Some(ref state_root) = state_root {
+                    request.set_state_root(state_root.clone());
                 }
-                Ok(None)
+                let mut paging = ClientPagingControls::new();
+                paging.set_limit(limit);
+                request.set_paging(paging);
+                request
+            })
+            .collect();
+
+        self.send_requests(Message_MessageType::CLIENT_STATE_LIST_REQUEST, &requests)
+            .into_iter()
+            .zip(account_addresses)
+            .map(|(response, account_address)| {
+                let response = response.map_err(|error| format!("{:?}", error))?;
+                self.account_state_from_list(account_address, response, block.clone())
+            })
+            .collect()
+    }
+
+    fn account_state_from_list(
+        &self,
+        account_address: &str,
+        mut response: ClientStateListResponse,
+        block: BlockKey,
+    ) -> Result<AccountState, String> {
+        let entries = match response.status {
+            ClientStateListResponse_Status::OK => response.take_entries().into_vec(),
+            ClientStateListResponse_Status::NO_RESOURCE => Vec::new(),
+            status => return Err(format!("Failed to list state: {:?}", status)),
+        };
+        let complete = response.get_paging().get_next().is_empty();
+
+        let parse_error = |error| format!("Failed to deserialize state entry: {:?}", error);
+        let mut account = None;
+        let mut account_storage = HashMap::new();
+        let mut generation = 0;
+        let mut slot_entries = Vec::new();
+        for entry in entries {
+            if entry.address == account_state_address(account_address) {
+                let mut entry: EvmEntry =
+                    protobuf::parse_from_bytes(&entry.data).map_err(parse_error)?;
+                insert_storage(&mut account_storage, entry.take_storage().into_vec());
+                generation = entry.storage_generation;
+                account = Some(entry.take_account());
+            } else {
+                let entry: EvmStorageEntry =
+                    protobuf::parse_from_bytes(&entry.data).map_err(parse_error)?;
+                slot_entries.push(entry);
             }
-            None => Ok(None),
         }
+        // Entries of another storage generation were left behind when the account
+        // was archived or removed
+        let mut storage = HashMap::new();
+        for entry in slot_entries
+            .into_iter()
+            .filter(|entry| entry.generation == generation)
+        {
+            insert_storage(&mut storage, entry.storage.into_vec());
+        }
+
+        let account = match account {
+            Some(account) => Some(account),
+            None => self.get_predeploy(account_address, block)?,
+        };
+        Ok(AccountState {
+            account,
+            storage,
+            account_storage,
+            storage_generation: generation,
+            complete,
+        })
     }
 
+    /// Reads storage slots of an account from the entries they are kept in, with the
+    /// requests sent at once. Slots kept in the account entry itself aren't read,
+    /// and are none here, as are unset slots and those of entries of another storage
+    /// generation than the account's. Keys must be padded to 32 bytes.
+    pub fn get_storage_slots(
+        &self,
+        account_address: &str,
+        keys: &[Vec<u8>],
+        generation: u64,
+        state_root: &str,
+    ) -> Vec<Result<Option<Vec<u8>>, String>> {
+        let requests: Vec<ClientStateGetRequest> = keys
+            .iter()
+            .map(|key| {
+                let mut request = ClientStateGetRequest::new();
+                request.set_address(storage_state_address(
+                    account_address,
+                    &transform::bytes_to_hex_str(key),
+                ));
+                request.set_state_root(String::from(state_root));
+                request
+            })
+            .collect();
+
+        self.send_requests(Message_MessageType::CLIENT_STATE_GET_REQUEST, &requests)
+            .into_iter()
+            .zip(keys)
+            .map(|(response, key)| {
+                let response: ClientStateGetResponse =
+                    response.map_err(|error| format!("{:?}", error))?;
+                match response.status {
+                    ClientStateGetResponse_Status::OK => {}
+                    ClientStateGetResponse_Status::NO_RESOURCE => return Ok(None),
+                    status => return Err(format!("Failed to get state: {:?}", status)),
+                }
+                let mut slots: EvmStorageEntry = protobuf::parse_from_bytes(&response.value)
+                    .map_err(|error| format!("Failed to deserialize state entry: {:?}", error))?;
+                if slots.generation != generation {
+                    return Ok(None);
+                }
+                Ok(find_storage(slots.take_storage(), key))
+            })
+            .collect()
+    }
+
+    /// Returns the latest block. If the session of the request has sent transactions,
+    /// waits for the validator to reach the blocks they are in first.
     pub fn get_current_block(&self) -> Result<Block, Error> {
+        let min_block = self.session_min_block();
+        let start = Instant::now();
+        loop {
+            let block = self.get_head_block()?;
+            if min_block == 0 || block_num(&block)? >= min_block {
+                return Ok(block);
+            }
+            if start.elapsed() >= SESSION_WAIT_TIMEOUT {
+                warn!(
+                    "Latest block is still behind block {} of the session",
+                    min_block
+                );
+                return Ok(block);
+            }
+            thread::sleep(SESSION_POLL_INTERVAL);
+        }
+    }
+
+    /// Returns the lowest block that the latest block may be for the session of the
+    /// request, looking up the blocks its pending transactions have been committed in
+    fn session_min_block(&self) -> u64 {
+        let session = match self.session {
+            Some(ref session) => session,
+            None => return 0,
+        };
+        for txn_id in self.sessions.pending(session) {
+            let block_num = self
+                .get_block(BlockKey::Transaction(txn_id.clone()))
+                .and_then(|block| block_num(&block));
+            if let Ok(block_num) = block_num {
+                self.sessions.committed(session, &txn_id, block_num);
+            }
+        }
+        self.sessions.min_block(session)
+    }
+
+    pub fn get_head_block(&self) -> Result<Block, Error> {
         let mut paging = ClientPagingControls::new();
         paging.set_limit(1);
         let mut request = ClientBlockListRequest::new();
@@ -711,13 +1991,53 @@ impl<S: MessageSender> ValidatorClient<S> {
         Ok(block.clone())
     }
 
+    /// Looks for the block holding a transaction among the latest blocks, for
+    /// validators that can't be asked for it
+    fn find_block_of_transaction(&self, txn_id: &str) -> Result<Block, Error> {
+        let mut paging = ClientPagingControls::new();
+        paging.set_limit(SEARCH_PAGE_SIZE);
+        let mut request = ClientBlockListRequest::new();
+        request.set_paging(paging);
+
+        let mut searched = 0;
+        while searched < MAX_TRANSACTION_SEARCH_DEPTH {
+            let mut response: ClientBlockListResponse =
+                self.send_request(Message_MessageType::CLIENT_BLOCK_LIST_REQUEST, &request)?;
+            match response.status {
+                ClientBlockListResponse_Status::OK => {}
+                ClientBlockListResponse_Status::NO_RESOURCE => return Err(Error::NoResource),
+                _ => return Err(Error::ValidatorError),
+            }
+
+            let blocks = response.take_blocks();
+            searched += blocks.len();
+            let found = blocks.into_iter().find(|block| {
+                block
+                    .get_batches()
+                    .iter()
+                    .flat_map(|batch| batch.get_transactions().iter())
+                    .any(|txn| txn.header_signature == txn_id)
+            });
+            if let Some(block) = found {
+                return Ok(block);
+            }
+
+            // Keep listing back from the same head, in case a block is committed
+            let next = response.get_paging().get_next().to_string();
+            if next.is_empty() {
+                break;
+            }
+            let mut paging = ClientPagingControls::new();
+            paging.set_start(next);
+            paging.set_limit(SEARCH_PAGE_SIZE);
+            request.set_paging(paging);
+            request.set_head_id(response.take_head_id());
+        }
+        Err(Error::NoResource)
+    }
+
     pub fn get_current_block_number(&self) -> Result<u64, Error> {
-        let block = self.get_current_block()?;
-        let block_header: BlockHeader =
-            protobuf::parse_from_bytes(&block.header).map_err(|error| {
-                Error::ParseError(format!("Error parsing block_header: {:?}", error))
-            })?;
-        Ok(block_header.block_num)
+        block_num(&self.get_current_block()?)
     }
 
     pub fn get_blocks_since(&self, since: u64) -> Result<Vec<(u64, Block)>, Error> {
@@ -731,21 +2051,510 @@ impl<S: MessageSender> ValidatorClient<S> {
             return Ok(Vec::new());
         }
 
-        let mut blocks = Vec::with_capacity((block_num - (since + 1)) as usize);
-        for block_num in (since + 1)..block_num {
-            let block = self.get_block(BlockKey::Number(block_num))?;
+        let mut blocks = Vec::with_capacity((block_num - since) as usize);
+        for block in self.get_blocks_range(since + 1, block_num - 1) {
+            let block = block?;
             let block_header: BlockHeader =
                 protobuf::parse_from_bytes(&block.header).map_err(|error| {
                     Error::ParseError(format!("Error parsing block_header: {:?}", error))
                 })?;
-            let block_num = block_header.block_num;
-            blocks.push((block_num, block));
+            blocks.push((block_header.block_num, block));
         }
         blocks.push((block_num, block));
 
         Ok(blocks)
     }
 
+    /// Returns how much state is taken up by the given account, or by every account,
+    /// ordered by address
+    pub fn get_storage_stats(
+        &self,
+        account_address: Option<&str>,
+        block: BlockKey,
+    ) -> Result<Vec<StorageStats>, Error> {
+        let prefix = account_address.map_or_else(|| String::from(SETH_NS), account_namespace);
+        let mut stats: BTreeMap<String, StorageStats> = BTreeMap::new();
+
+        for (address, data) in self.list_state(&prefix, block)? {
+            if address.len() != STATE_ADDRESS_LEN {
+                continue;
+            }
+            let account = &address[SETH_NS.len()..SETH_NS.len() + 40];
+            let account_stats = stats
+                .entry(String::from(account))
+                .or_insert_with(|| StorageStats::new(account));
+            account_stats.bytes += data.len() as u64;
+
+            if address == account_state_address(account) {
+                let entry: EvmEntry = protobuf::parse_from_bytes(&data)
+                    .map_err(|error| Error::ParseError(format!("{:?}", error)))?;
+                account_stats.is_contract = !entry.get_account().code.is_empty();
+                account_stats.slots += entry.storage.len() as u64;
+            } else {
+                let entry: EvmStorageEntry = protobuf::parse_from_bytes(&data)
+                    .map_err(|error| Error::ParseError(format!("{:?}", error)))?;
+                account_stats.entries += 1;
+                account_stats.slots += entry.storage.len() as u64;
+            }
+        }
+
+        Ok(stats.into_values().collect())
+    }
+
+    /// Returns the accounts whose permissions are set in the state of a block, with
+    /// their permissions, including the global permissions address if they are set
+    /// there
+    pub fn get_permission_entries(
+        &self,
+        block: BlockKey,
+    ) -> Result<Vec<(String, EvmPermissions)>, Error> {
+        let mut entries = Vec::new();
+        for (address, data) in self.list_state(SETH_NS, block)? {
+            if address.len() != STATE_ADDRESS_LEN {
+                continue;
+            }
+            let account = &address[SETH_NS.len()..SETH_NS.len() + 40];
+            if address != account_state_address(account) {
+                continue;
+            }
+            let mut entry: EvmEntry = protobuf::parse_from_bytes(&data)
+                .map_err(|error| Error::ParseError(format!("{:?}", error)))?;
+            let mut account_state = entry.take_account();
+            if account_state.has_permissions() {
+                entries.push((String::from(account), account_state.take_permissions()));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Returns the keys of the storage slots of an account that are stored outside its
+    /// account entry, including those of entries left behind under an older storage
+    /// generation, so that archiving the account deletes them too
+    pub fn get_storage_keys(
+        &self,
+        account_address: &str,
+        block: BlockKey,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        let mut keys = Vec::new();
+        for (address, data) in self.list_state(&account_namespace(account_address), block)? {
+            if address == account_state_address(account_address) {
+                continue;
+            }
+            let mut entry: EvmStorageEntry = protobuf::parse_from_bytes(&data)
+                .map_err(|error| Error::ParseError(format!("{:?}", error)))?;
+            keys.extend(entry.take_storage().into_iter().map(|pair| pair.key));
+        }
+        Ok(keys)
+    }
+
+    /// Returns every account in the state of a block with its storage, ordered by
+    /// address and with the storage keys padded to 32 bytes, as the archive of a
+    /// contract holds them. Archived contracts have no account, so they are left out.
+    pub fn get_state_snapshot(&self, block: BlockKey) -> Result<Vec<ArchivedContract>, Error> {
+        let mut accounts: BTreeMap<String, ArchivedContract> = BTreeMap::new();
+        let mut generations: HashMap<String, u64> = HashMap::new();
+        let mut slot_entries = Vec::new();
+        for (address, data) in self.list_state(SETH_NS, block)? {
+            if address.len() != STATE_ADDRESS_LEN {
+                continue;
+            }
+            let account = &address[SETH_NS.len()..SETH_NS.len() + 40];
+            if address == account_state_address(account) {
+                let mut entry: EvmEntry = protobuf::parse_from_bytes(&data)
+                    .map_err(|error| Error::ParseError(format!("{:?}", error)))?;
+                let snapshot = accounts.entry(String::from(account)).or_default();
+                if entry.has_account() {
+                    snapshot.set_account(entry.take_account());
+                }
+                add_snapshot_storage(snapshot, entry.take_storage());
+                generations.insert(String::from(account), entry.storage_generation);
+            } else {
+                let entry: EvmStorageEntry = protobuf::parse_from_bytes(&data)
+                    .map_err(|error| Error::ParseError(format!("{:?}", error)))?;
+                slot_entries.push((String::from(account), entry));
+            }
+        }
+
+        // Entries of another storage generation were left behind when the account
+        // was archived or removed
+        for (account, mut entry) in slot_entries {
+            if generations.get(&account).cloned().unwrap_or_default() == entry.generation {
+                let snapshot = accounts.entry(account).or_default();
+                add_snapshot_storage(snapshot, entry.take_storage());
+            }
+        }
+
+        Ok(accounts
+            .into_values()
+            .filter(ArchivedContract::has_account)
+            .map(|mut snapshot| {
+                snapshot.mut_storage().sort_by(|a, b| a.key.cmp(&b.key));
+                snapshot
+            })
+            .collect())
+    }
+
+    /// Proves an account and some of its storage slots, given by their keys padded to
+    /// 32 bytes, in the state of a block. The validator doesn't give out the nodes of
+    /// its Merkle-Radix tree, so the tree is rebuilt from every entry of the state and
+    /// checked against the block's state root.
+    pub fn get_proof(
+        &self,
+        account_address: &str,
+        keys: &[Vec<u8>],
+        block: BlockKey,
+    ) -> Result<AccountProof, Error> {
+        let block = self.get_block(block)?;
+        let namespace = account_namespace(account_address);
+
+        // The validator doesn't give out the nodes of its tree, so the root is hashed
+        // from every entry, keeping only the nodes down to the account's namespace and
+        // the entries under it
+        let mut hasher = StateHasher::new(&namespace);
+        let mut entries = Vec::new();
+        self.for_each_state_entry(
+            "",
+            BlockKey::Signature(block.header_signature.clone()),
+            |address, data| {
+                if address.starts_with(&namespace) {
+                    entries.push((address.clone(), data.clone()));
+                }
+                hasher.add(address, data).map_err(Error::ParseError)
+            },
+        )?;
+        let (root, upper_path) = hasher.finish();
+        if root != state_root(&block)? {
+            return Err(Error::ParseError(format!(
+                "The state of block {} doesn't hash to its state root",
+                block.header_signature
+            )));
+        }
+
+        let parse_error = |error| Error::ParseError(format!("{:?}", error));
+        let mut account = None;
+        let mut account_storage = HashMap::new();
+        let mut generation = 0;
+        let mut slot_entries = Vec::new();
+        for (address, data) in &entries {
+            if *address == account_state_address(account_address) {
+                let mut entry: EvmEntry = protobuf::parse_from_bytes(data).map_err(parse_error)?;
+                insert_storage(&mut account_storage, entry.take_storage().into_vec());
+                generation = entry.storage_generation;
+                account = Some(entry.take_account());
+            } else {
+                let entry: EvmStorageEntry =
+                    protobuf::parse_from_bytes(data).map_err(parse_error)?;
+                slot_entries.push(entry);
+            }
+        }
+        let mut storage = HashMap::new();
+        for entry in slot_entries
+            .into_iter()
+            .filter(|entry| entry.generation == generation)
+        {
+            insert_storage(&mut storage, entry.storage.into_vec());
+        }
+
+        // Slots of older contracts that have no entry of their own are proven by the
+        // account entry that holds them
+        let mut addresses = vec![account_state_address(account_address)];
+        addresses.extend(keys.iter().map(|key| {
+            if !storage.contains_key(key) && account_storage.contains_key(key) {
+                account_state_address(account_address)
+            } else {
+                storage_state_address(account_address, &transform::bytes_to_hex_str(key))
+            }
+        }));
+        // The node at the account's namespace is the one every entry of the account
+        // is under, and its storage proofs start there. Rebuilt from those entries
+        // alone, the nodes below it are those of the whole tree.
+        let depth = namespace.len() / 2;
+        let has_entries = !entries.is_empty();
+        let (_, mut paths) = state_proof::prove_state(entries, &addresses);
+        let mut account_proof = upper_path;
+        if has_entries {
+            account_proof.truncate(depth);
+            account_proof.extend(paths.remove(0).into_iter().skip(depth));
+        } else {
+            paths.remove(0);
+        }
+        let storage_hash = account_proof
+            .get(depth)
+            .map(|node| state_proof::state_node_hash(node));
+        let storage_proofs = keys
+            .iter()
+            .zip(paths)
+            .map(|(key, path)| {
+                let value = storage
+                    .get(key)
+                    .or_else(|| account_storage.get(key))
+                    .cloned();
+                SlotProof {
+                    value,
+                    proof: path.into_iter().skip(depth).collect(),
+                }
+            })
+            .collect();
+
+        account_storage.extend(storage);
+        Ok(AccountProof {
+            account,
+            account_proof,
+            storage_hash,
+            storage_proofs,
+            slots: account_storage,
+        })
+    }
+
+    /// Checks an account, or a slot of its storage, read from the state of a block
+    /// against a proof of it, returning whether they match. Mismatches are counted in
+    /// the metrics. Well-known contracts that aren't in state yet have no entry to
+    /// prove, and are checked against the account they are served as.
+    pub fn verify_state_read(
+        &self,
+        account_address: &str,
+        read: &StateRead,
+        block: BlockKey,
+    ) -> Result<bool, Error> {
+        let keys = match *read {
+            StateRead::Account(_) => vec![],
+            StateRead::Slot(ref key, _) => vec![key.clone()],
+        };
+        let proof = self.get_proof(account_address, &keys, block.clone())?;
+        let matched = match *read {
+            StateRead::Account(ref account) => {
+                let proven = match proof.account {
+                    Some(account) => Some(account),
+                    None => self
+                        .get_predeploy(account_address, block)
+                        .map_err(Error::ParseError)?,
+                };
+                *account == proven
+            }
+            StateRead::Slot(_, ref value) => {
+                proof
+                    .storage_proofs
+                    .first()
+                    .and_then(|slot| slot.value.as_ref())
+                    == value.as_ref()
+            }
+        };
+        if !matched {
+            warn!(
+                "What was read of {} doesn't match the proof of its state",
+                account_address
+            );
+            self.metrics.record_state_mismatch();
+        }
+        Ok(matched)
+    }
+
+    /// Returns every state entry under the given address prefix
+    pub fn list_state(
+        &self,
+        prefix: &str,
+        block: BlockKey,
+    ) -> Result<Vec<(String, Vec<u8>)>, Error> {
+        let mut entries = Vec::new();
+        self.for_each_state_entry(prefix, block, |address, data| {
+            entries.push((address, data));
+            Ok(())
+        })?;
+        Ok(entries)
+    }
+
+    /// Passes each state entry under the given address prefix to `f` in address
+    /// order, a page at a time, so that they needn't all be held at once
+    pub fn for_each_state_entry<F>(
+        &self,
+        prefix: &str,
+        block: BlockKey,
+        mut f: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(String, Vec<u8>) -> Result<(), Error>,
+    {
+        let mut request = ClientStateListRequest::new();
+        request.set_address(String::from(prefix));
+        if let Some(state_root) = self.block_to_state_root(block)? {
+            request.set_state_root(state_root);
+        }
+
+        loop {
+            let mut response: ClientStateListResponse =
+                self.send_request(Message_MessageType::CLIENT_STATE_LIST_REQUEST, &request)?;
+
+            match response.status {
+                ClientStateListResponse_Status::OK => {}
+                ClientStateListResponse_Status::NO_RESOURCE => return Ok(()),
+                _ => return Err(Error::ValidatorError),
+            }
+
+            for mut entry in response.take_entries().into_iter() {
+                f(entry.take_address(), entry.take_data())?;
+            }
+
+            // Keep reading from the same state root, in case a block is committed
+            let next = response.get_paging().get_next().to_string();
+            if next.is_empty() {
+                return Ok(());
+            }
+            let mut paging = ClientPagingControls::new();
+            paging.set_start(next);
+            request.set_paging(paging);
+            request.set_state_root(response.take_state_root());
+        }
+    }
+
+    /// Returns the state root of the given block, or None for the latest block
+    fn block_to_state_root(&self, block: BlockKey) -> Result<Option<String>, Error> {
+        match block {
+            // Reads of a session that has sent transactions are pinned to a block that
+            // holds them, rather than whatever block the validator is at by then
+            BlockKey::Latest | BlockKey::Pending if self.session_min_block() > 0 => self
+                .get_current_block()
+                .and_then(|block| {
+                    protobuf::parse_from_bytes(&block.header).map_err(|error| {
+                        Error::ParseError(format!("Error parsing block_header: {:?}", error))
+                    })
+                })
+                .map(|block_header: BlockHeader| Some(block_header.state_root_hash)),
+            BlockKey::Latest | BlockKey::Pending => Ok(None),
+            BlockKey::Earliest => self
+                .block_id_to_state_root(String::from("0000000000000000"))
+                .map(Some),
+            BlockKey::Finalized => self
+                .finalized_block_num()
+                .and_then(|block_num| self.block_num_to_state_root(block_num))
+                .map(Some),
+            BlockKey::Signature(block_id) => self.block_id_to_state_root(block_id).map(Some),
+            BlockKey::Number(block_num) => self.block_num_to_state_root(block_num).map(Some),
+            BlockKey::Transaction(transaction_id) => {
+                self.transaction_to_state_root(transaction_id).map(Some)
+            }
+            BlockKey::StateRoot(state_root) => Ok(Some(state_root)),
+        }
+    }
+
+    /// Returns the number of the most recent block that can't be reverted. With
+    /// consensus algorithms that never fork, like PBFT and Raft, every committed block
+    /// is final. Others give no finality, so unless a finality depth is configured,
+    /// only the genesis block is final.
+    pub fn finalized_block_num(&self) -> Result<u64, Error> {
+        let depth = match self.finality_depth {
+            Some(depth) => depth,
+            None => {
+                let algorithm = self
+                    .get_setting(CONSENSUS_ALGORITHM_SETTING, BlockKey::Latest)
+                    .map_err(Error::CommunicationError)?
+                    .unwrap_or_default()
+                    .to_lowercase();
+                if NON_FORKING_CONSENSUS.contains(&algorithm.as_str()) {
+                    0
+                } else {
+                    u64::MAX
+                }
+            }
+        };
+        let latest = block_num(&self.get_current_block()?)?;
+        Ok(latest.saturating_sub(depth))
+    }
+
+    /// Returns whether a block is final, which it is if it is on the chain at or
+    /// below the finalized block
+    pub fn is_finalized(&self, block_id: &str, block_num: u64) -> Result<bool, Error> {
+        if block_num > self.finalized_block_num()? {
+            return Ok(false);
+        }
+        self.get_block(BlockKey::Number(block_num))
+            .map(|block| block.header_signature == block_id)
+    }
+
+    /// Returns the consensus algorithm configured in the state of the given block, and
+    /// its members if it has a fixed membership
+    pub fn get_consensus_info(&self, block: BlockKey) -> Result<ConsensusInfo, Error> {
+        let name = self
+            .get_setting(CONSENSUS_ALGORITHM_SETTING, block.clone())
+            .map_err(Error::CommunicationError)?;
+        let version = self
+            .get_setting(CONSENSUS_VERSION_SETTING, block.clone())
+            .map_err(Error::CommunicationError)?;
+
+        let members_setting = name.as_ref().and_then(|name| {
+            CONSENSUS_MEMBERS_SETTINGS
+                .iter()
+                .find(|&&(algorithm, _)| name.eq_ignore_ascii_case(algorithm))
+                .map(|&(_, setting)| setting)
+        });
+        let members = match members_setting {
+            Some(setting) => match self
+                .get_setting(setting, block)
+                .map_err(Error::CommunicationError)?
+            {
+                Some(members) => Some(serde_json::from_str(&members).map_err(|error| {
+                    Error::ParseError(format!("Malformed {} setting: {}", setting, error))
+                })?),
+                None => None,
+            },
+            None => None,
+        };
+
+        Ok(ConsensusInfo {
+            name,
+            version,
+            members,
+        })
+    }
+
+    /// Returns the base fee per gas in the state of the given block, which is 0
+    /// unless it is set, like the transaction processor takes it to be
+    pub fn get_base_fee(&self, block: BlockKey) -> Result<u64, Error> {
+        let base_fee = self
+            .get_setting(BASE_FEE_SETTING, block)
+            .map_err(Error::ParseError)?;
+        Ok(base_fee.and_then(|fee| fee.parse().ok()).unwrap_or(0))
+    }
+
+    /// Returns the chain id Ethereum transactions must be signed for in the state of
+    /// the given block, which is the default unless it is set, like the transaction
+    /// processor takes it to be
+    pub fn get_chain_id(&self, block: BlockKey) -> Result<u64, Error> {
+        let chain_id = self
+            .get_setting(CHAIN_ID_SETTING, block)
+            .map_err(Error::CommunicationError)?;
+        Ok(chain_id
+            .and_then(|id| id.parse().ok())
+            .unwrap_or(DEFAULT_CHAIN_ID))
+    }
+
+    /// Returns the chain id set in the state of the given block, if it is set
+    pub fn get_chain_id_setting(&self, block: BlockKey) -> Result<Option<u64>, Error> {
+        match self
+            .get_setting(CHAIN_ID_SETTING, block)
+            .map_err(Error::CommunicationError)?
+        {
+            Some(chain_id) => chain_id.trim().parse().map(Some).map_err(|_| {
+                Error::ParseError(format!(
+                    "Malformed {} setting: {}",
+                    CHAIN_ID_SETTING, chain_id
+                ))
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the value of a setting in the state of the given block
+    fn get_setting(&self, key: &str, block: BlockKey) -> Result<Option<String>, String> {
+        let setting: Option<Setting> = self.get_state(setting_address(key), block)?;
+        Ok(setting.and_then(|setting| {
+            setting
+                .get_entries()
+                .iter()
+                .find(|entry| entry.key == key)
+                .map(|entry| entry.value.clone())
+        }))
+    }
+
     fn block_num_to_state_root(&self, block_num: u64) -> Result<String, Error> {
         self.get_block(BlockKey::Number(block_num))
             .and_then(|block| {
@@ -780,6 +2589,11 @@ impl<S: MessageSender> ValidatorClient<S> {
     }
 
     pub fn get_peers(&self) -> Result<usize, Error> {
+        self.get_peer_endpoints().map(|peers| peers.len())
+    }
+
+    /// Returns the endpoints of the validators the validator is connected to
+    pub fn get_peer_endpoints(&self) -> Result<Vec<String>, Error> {
         let request = ClientPeersGetRequest::new();
         let response: ClientPeersGetResponse =
             self.send_request(Message_MessageType::CLIENT_PEERS_GET_REQUEST, &request)?;
@@ -794,7 +2608,336 @@ impl<S: MessageSender> ValidatorClient<S> {
             }
         };
 
-        let n = peers.iter().count();
-        Ok(n)
+        Ok(peers.into_vec())
+    }
+}
+
+/// The consensus algorithm a network is configured to run
+pub struct ConsensusInfo {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    /// The public keys of the validators taking part in consensus, if the algorithm
+    /// has a fixed membership
+    pub members: Option<Vec<String>>,
+}
+
+/// An account and the storage read with it
+pub struct AccountState {
+    pub account: Option<EvmStateAccount>,
+    /// The values of the storage slots read from the entries they are kept in, by key
+    /// padded to 32 bytes
+    pub storage: HashMap<Vec<u8>, Vec<u8>>,
+    /// The slots kept in the account entry itself by contracts deployed before
+    /// storage was split out, which count only for slots without an entry of their own
+    pub account_storage: HashMap<Vec<u8>, Vec<u8>>,
+    /// The storage generation of the account, which its storage entries must have
+    pub storage_generation: u64,
+    /// Whether all of the account's storage entries were read, so that slots missing
+    /// from both maps are unset
+    pub complete: bool,
+}
+
+/// An account and some of its storage slots, with the nodes of the state's
+/// Merkle-Radix tree that prove them
+pub struct AccountProof {
+    pub account: Option<EvmStateAccount>,
+    /// The nodes from the state root down to the account entry
+    pub account_proof: Vec<Vec<u8>>,
+    /// The hash of the node the account entry and its storage entries are all under,
+    /// unless the account has no entries
+    pub storage_hash: Option<String>,
+    pub storage_proofs: Vec<SlotProof>,
+    /// Every slot of the account, by key padded to 32 bytes
+    pub slots: HashMap<Vec<u8>, Vec<u8>>,
+}
+
+/// What was read of an account's state: the account, or the value of the slot with
+/// a key padded to 32 bytes
+pub enum StateRead {
+    Account(Option<EvmStateAccount>),
+    Slot(Vec<u8>, Option<Vec<u8>>),
+}
+
+/// The value of a storage slot, and the nodes from the node of its account down to
+/// the entry the slot is kept in
+pub struct SlotProof {
+    pub value: Option<Vec<u8>>,
+    pub proof: Vec<Vec<u8>>,
+}
+
+/// The amount of state taken up by an account and its storage
+pub struct StorageStats {
+    pub address: String,
+    pub is_contract: bool,
+    /// Storage slots with a nonzero value
+    pub slots: u64,
+    /// State entries holding storage slots, not counting the account entry
+    pub entries: u64,
+    /// Size of the account entry and the entries holding its storage
+    pub bytes: u64,
+}
+
+impl StorageStats {
+    fn new(address: &str) -> Self {
+        StorageStats {
+            address: String::from(address),
+            is_contract: false,
+            slots: 0,
+            entries: 0,
+            bytes: 0,
+        }
+    }
+}
+
+pub fn block_num(block: &Block) -> Result<u64, Error> {
+    protobuf::parse_from_bytes(&block.header)
+        .map(|block_header: BlockHeader| block_header.block_num)
+        .map_err(|error| Error::ParseError(format!("Error parsing block_header: {:?}", error)))
+}
+
+pub fn state_root(block: &Block) -> Result<String, Error> {
+    protobuf::parse_from_bytes(&block.header)
+        .map(|block_header: BlockHeader| block_header.state_root_hash)
+        .map_err(|error| Error::ParseError(format!("Error parsing block_header: {:?}", error)))
+}
+
+/// Returns the seth transactions of a block in the order they ran, each with its index
+/// among all of the block's transactions, which is the index receipts and logs give
+/// it. Other families' transactions, like those of the block info family, are left
+/// out.
+pub fn seth_transactions(block: &Block) -> Vec<(u64, &TransactionPb)> {
+    block
+        .get_batches()
+        .iter()
+        .flat_map(|batch| batch.get_transactions().iter())
+        .enumerate()
+        .filter(|&(_, txn)| {
+            protobuf::parse_from_bytes::<TransactionHeader>(&txn.header)
+                .map(|header| header.family_name == "seth")
+                .unwrap_or(false)
+        })
+        .map(|(index, txn)| (index as u64, txn))
+        .collect()
+}
+
+/// Returns the state address of the seth account with the given address
+pub fn account_state_address(account_address: &str) -> String {
+    String::from(SETH_NS) + account_address + "000000000000000000000000"
+}
+
+/// Returns the prefix of the state addresses of a seth account and its storage
+fn account_namespace(account_address: &str) -> String {
+    String::from(SETH_NS) + account_address
+}
+
+/// Returns the state address of a storage slot of a seth account, given the key padded
+/// to 32 bytes
+pub fn storage_state_address(account_address: &str, key: &str) -> String {
+    let mut sha = Sha256::new();
+    sha.input(&transform::hex_str_to_bytes(key).unwrap_or_default());
+    account_namespace(account_address) + &sha.result_str()[..24]
+}
+
+/// Left pads a storage key to 32 bytes
+pub fn pad_word(bytes: &[u8]) -> Vec<u8> {
+    let mut word = vec![0; 32 - bytes.len()];
+    word.extend_from_slice(bytes);
+    word
+}
+
+/// Adds storage slots to a map by key padded to 32 bytes
+fn insert_storage(map: &mut HashMap<Vec<u8>, Vec<u8>>, storage: Vec<EvmStorage>) {
+    for mut pair in storage {
+        if pair.key.len() <= 32 {
+            map.insert(pad_word(&pair.key), pair.take_value());
+        }
+    }
+}
+
+/// Adds the set storage slots to the snapshot of an account, with their keys padded
+/// to 32 bytes
+fn add_snapshot_storage(
+    snapshot: &mut ArchivedContract,
+    storage: protobuf::RepeatedField<EvmStorage>,
+) {
+    for mut pair in storage {
+        if pair.key.len() <= 32 && pair.value.iter().any(|&byte| byte != 0) {
+            pair.key = pad_word(&pair.key);
+            snapshot.mut_storage().push(pair);
+        }
+    }
+}
+
+/// Returns the value stored with a key padded to 32 bytes
+fn find_storage(storage: protobuf::RepeatedField<EvmStorage>, key: &[u8]) -> Option<Vec<u8>> {
+    storage
+        .into_iter()
+        .find(|pair| pair.key.len() <= 32 && pad_word(&pair.key) == key)
+        .map(|pair| pair.value)
+}
+
+/// Returns the state address of a setting of the Settings transaction family
+pub fn setting_address(key: &str) -> String {
+    let mut parts: Vec<&str> = key.splitn(4, '.').collect();
+    parts.resize(4, "");
+
+    let mut address = String::from(SETTINGS_NS);
+    for part in parts {
+        let mut sha = Sha256::new();
+        sha.input_str(part);
+        address.push_str(&sha.result_str()[..16]);
+    }
+    address
+}
+
+/// Returns the inputs and outputs of a transaction. When every account it touches is
+/// known, only those are declared, so that the scheduler can run it in parallel with
+/// transactions that touch other accounts. For contracts whose storage keys are known,
+/// only those slots are declared as outputs, so transactions touching different slots
+/// of a contract can also run in parallel. Otherwise it is given the whole namespace.
+/// Family version 1.0 keeps storage in the account entry, so there the entry of every
+/// contract is an output.
+fn state_access(
+    from: &str,
+    txn: &SethTransaction,
+    access_list: Option<&[AccessListEntry]>,
+    family_version: &str,
+) -> (Vec<String>, Vec<String>) {
+    // Ethereum transactions touch what they would if their signer had sent them
+    if let SethTransaction::Ethereum(ref raw) = *txn {
+        return state_access(
+            raw.sender(),
+            &raw.to_seth_transaction(),
+            access_list,
+            family_version,
+        );
+    }
+
+    let settings = EVM_SETTINGS
+        .iter()
+        .cloned()
+        .chain(WELL_KNOWN_CONTRACTS.iter().map(|&(_, key)| key))
+        .map(setting_address);
+
+    // The sender's nonce is always incremented, and a new contract is always written
+    let created = match *txn {
+        SethTransaction::CreateContractAccount(ref inner) => created_address(from, inner),
+        _ => None,
+    };
+
+    match txn.accounts(from, access_list) {
+        Some(accounts) => {
+            let mut inputs = Vec::new();
+            let mut outputs = Vec::new();
+            for account in &accounts {
+                let storage_keys = access_list.and_then(|entries| {
+                    entries
+                        .iter()
+                        .find(|entry| entry.address == *account)
+                        .and_then(|entry| entry.storage_keys.as_ref())
+                });
+                match storage_keys {
+                    Some(keys) => {
+                        let slots: Vec<String> = keys
+                            .iter()
+                            .map(|key| storage_state_address(account, key))
+                            .collect();
+                        if account == from
+                            || created.as_ref() == Some(account)
+                            || family_version == ENTRY_STORAGE_FAMILY_VERSION
+                        {
+                            outputs.push(account_state_address(account));
+                        }
+                        inputs.push(account_state_address(account));
+                        inputs.extend(slots.iter().cloned());
+                        outputs.extend(slots);
+                    }
+                    None => {
+                        inputs.push(account_namespace(account));
+                        outputs.push(account_namespace(account));
+                    }
+                }
+            }
+            outputs.sort();
+            outputs.dedup();
+            inputs.push(account_state_address(GLOBAL_PERMISSIONS_ADDRESS));
+            inputs.push(String::from(BLOCK_INFO_NS));
+            inputs.extend(settings);
+            inputs.sort();
+            inputs.dedup();
+            (inputs, outputs)
+        }
+        None => {
+            let mut inputs = vec![String::from(SETH_NS), String::from(BLOCK_INFO_NS)];
+            inputs.extend(settings);
+            (inputs, vec![String::from(SETH_NS)])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use contracts::ContractRegistry;
+    use mock_chain::MockChain;
+
+    #[test]
+    fn gets_ranges_of_blocks_in_order() {
+        let client = ValidatorClient::new(
+            MockChain::new(),
+            Vec::new(),
+            FilterManager::new(1),
+            0,
+            String::from("1.0"),
+            ContractRegistry::new(),
+        );
+        // The number of each block, or None for an error
+        let numbers = |start, end| -> Vec<Option<u64>> {
+            client
+                .get_blocks_range(start, end)
+                .map(|block| block.and_then(|block| block_num(&block)).ok())
+                .collect()
+        };
+
+        assert_eq!(vec![Some(1), Some(2)], numbers(1, 2));
+        assert!(numbers(2, 1).is_empty());
+        // The range stops at the first block that doesn't exist
+        assert_eq!(vec![Some(2), Some(3), None], numbers(2, u64::MAX));
+        assert!(matches!(
+            client.get_blocks_range(4, 5).next(),
+            Some(Err(Error::NoResource))
+        ));
+    }
+
+    #[test]
+    fn verifies_reads_against_proofs() {
+        let client = ValidatorClient::new(
+            MockChain::new(),
+            Vec::new(),
+            FilterManager::new(1),
+            0,
+            String::from("1.0"),
+            ContractRegistry::new(),
+        );
+        let alice = MockChain::accounts()[0].address().to_string();
+        let account = client.get_account(&alice, BlockKey::Latest).unwrap();
+        assert_eq!(account.as_ref().map(|account| account.nonce), Some(2));
+        let verify = |read| {
+            client
+                .verify_state_read(&alice, &read, BlockKey::Latest)
+                .unwrap()
+        };
+
+        assert!(verify(StateRead::Account(account.clone())));
+        assert!(verify(StateRead::Slot(vec![0; 32], None)));
+
+        let mut tampered = account.unwrap();
+        tampered.nonce += 1;
+        assert!(!verify(StateRead::Account(Some(tampered))));
+        assert!(!verify(StateRead::Slot(vec![0; 32], Some(vec![1]))));
+        assert!(client
+            .metrics
+            .render(&[])
+            .contains("seth_rpc_state_read_mismatches_total 2"));
     }
 }
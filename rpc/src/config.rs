@@ -0,0 +1,291 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! The TOML file of `--config`, which has a key for each flag of seth-rpc, and the
+//! settings that can change while it runs, which it reloads from the file when it
+//! gets SIGHUP: the log level, the rate limit and the CORS origins.
+
+use clap::ArgMatches;
+use front::RateLimiter;
+use jsonrpc_http_server::{cors_allow_origin, hyper, AccessControlAllowOrigin, AllowCors};
+use std::fs;
+#[cfg(unix)]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+#[cfg(unix)]
+use std::thread;
+#[cfg(unix)]
+use std::time::Duration;
+use toml::{self, value::Table, Value};
+
+/// The keys of the settings a reload changes
+const RELOADABLE: &[&str] = &["verbose", "cors-origins", "rate-limit", "rate-burst"];
+
+/// The keys of flags that take a comma separated list, which may be given as an
+/// array
+const COMMA_SEPARATED: &[&str] = &["cors-origins", "rpc-api", "auth-methods"];
+
+/// How often the reloading thread looks for SIGHUP
+#[cfg(unix)]
+const HANGUP_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[cfg(unix)]
+static HANGUP: AtomicBool = AtomicBool::new(false);
+
+/// Reads and parses a config file
+pub fn load(path: &str) -> Result<Table, String> {
+    let text =
+        fs::read_to_string(path).map_err(|error| format!("Failed to read {}: {}", path, error))?;
+    toml::from_str(&text).map_err(|error| format!("Failed to parse {}: {}", path, error))
+}
+
+/// Returns the arguments that give the settings of a config file, except those of
+/// the flags the command line gives, which `given` are the matches of
+pub fn args(config: &Table, given: &ArgMatches) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    for (key, value) in config {
+        if key == "config" {
+            return Err(String::from("A config file can't name another"));
+        }
+        if given.occurrences_of(key.replace('-', "_")) > 0 {
+            continue;
+        }
+        if key == "verbose" {
+            let count = value
+                .as_integer()
+                .filter(|count| *count >= 0)
+                .ok_or("verbose must be the number of -v to log with")?;
+            args.extend((0..count).map(|_| String::from("-v")));
+            continue;
+        }
+        let values = match *value {
+            Value::Array(ref values) if COMMA_SEPARATED.contains(&key.as_str()) => {
+                let values: Option<Vec<&str>> = values.iter().map(Value::as_str).collect();
+                let values = values.ok_or_else(|| format!("{} must be strings", key))?;
+                vec![Value::from(values.join(","))]
+            }
+            Value::Array(ref values) => values.clone(),
+            ref value => vec![value.clone()],
+        };
+        for value in values {
+            // Values are joined to their flags, so that those starting with - aren't
+            // taken for flags
+            match value {
+                Value::Boolean(true) => args.push(format!("--{}", key)),
+                Value::Boolean(false) => {}
+                Value::String(value) => args.push(format!("--{}={}", key, value)),
+                Value::Integer(value) => args.push(format!("--{}={}", key, value)),
+                Value::Float(value) => args.push(format!("--{}={}", key, value)),
+                _ => {
+                    return Err(format!(
+                        "{} must be a string, number or boolean, or an array of them",
+                        key
+                    ))
+                }
+            }
+        }
+    }
+    Ok(args)
+}
+
+/// Returns the settings of a config file that only take effect on a restart
+pub fn restart_only(config: &Table) -> Table {
+    config
+        .iter()
+        .filter(|(key, _)| !RELOADABLE.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+/// The settings a reload changes
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub log_level: log::LevelFilter,
+    pub cors_origins: Option<Vec<String>>,
+    /// The rate and burst of `--rate-limit` and `--rate-burst`
+    pub rate_limit: Option<(f64, f64)>,
+}
+
+impl Settings {
+    pub fn from_matches(arg_matches: &ArgMatches) -> Result<Self, String> {
+        let log_level = match arg_matches.occurrences_of("verbose") {
+            0 => log::LevelFilter::Warn,
+            1 => log::LevelFilter::Info,
+            2 => log::LevelFilter::Debug,
+            _ => log::LevelFilter::Trace,
+        };
+        let cors_origins = arg_matches.value_of("cors_origins").map(|origins| {
+            origins
+                .split(',')
+                .map(str::trim)
+                .filter(|origin| !origin.is_empty())
+                .map(String::from)
+                .collect()
+        });
+        let parse = |name, value: &str| {
+            value
+                .parse::<f64>()
+                .map_err(|error| format!("Invalid --{}: {}", name, error))
+        };
+        let rate_limit = match arg_matches.value_of("rate_limit") {
+            Some(rate) => {
+                let rate = parse("rate-limit", rate)?;
+                let burst = match arg_matches.value_of("rate_burst") {
+                    Some(burst) => parse("rate-burst", burst)?,
+                    None => rate,
+                };
+                if rate <= 0.0 || burst < 1.0 {
+                    return Err(String::from(
+                        "--rate-limit must be positive and --rate-burst at least 1",
+                    ));
+                }
+                Some((rate, burst))
+            }
+            None => None,
+        };
+        Ok(Settings {
+            log_level,
+            cors_origins,
+            rate_limit,
+        })
+    }
+}
+
+/// What the running server reads the settings a reload changes from. Clones share
+/// them.
+#[derive(Clone)]
+pub struct Live {
+    cors_origins: Arc<RwLock<Option<Vec<AccessControlAllowOrigin>>>>,
+    limiter: Option<RateLimiter>,
+}
+
+impl Live {
+    /// Makes what the server reads `settings` from, with the limiter it was started
+    /// with, if its rate is limited
+    pub fn new(settings: &Settings, limiter: Option<RateLimiter>) -> Self {
+        let live = Live {
+            cors_origins: Arc::default(),
+            limiter,
+        };
+        live.apply(settings);
+        live
+    }
+
+    /// Changes the settings of the running server. The front the rate limiter is in
+    /// is only started with one, so enabling or disabling it takes a restart.
+    pub fn apply(&self, settings: &Settings) {
+        log::set_max_level(settings.log_level);
+        *self.cors_origins.write().unwrap() = settings.cors_origins.as_ref().map(|origins| {
+            origins
+                .iter()
+                .map(|origin| AccessControlAllowOrigin::from(origin.as_str()))
+                .collect()
+        });
+        match (self.limiter.as_ref(), settings.rate_limit) {
+            (Some(limiter), Some((rate, burst))) => limiter.set_limits(rate, burst),
+            (None, None) => {}
+            _ => warn!("Turning the rate limit on or off takes a restart"),
+        }
+    }
+
+    /// Whether browsers may send a request from its origin
+    pub fn allows_origin(&self, request: &hyper::Request<hyper::Body>) -> bool {
+        !matches!(
+            cors_allow_origin(request, &self.cors_origins.read().unwrap()),
+            AllowCors::Invalid
+        )
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn on_hangup(_: libc::c_int) {
+    HANGUP.store(true, Ordering::SeqCst);
+}
+
+/// Applies the settings `reload` returns to `live` each time the process gets
+/// SIGHUP, logging why if it fails
+#[cfg(unix)]
+pub fn reload_on_hangup<F>(live: Live, reload: F)
+where
+    F: Fn() -> Result<Settings, String> + Send + 'static,
+{
+    // The handler only sets a flag, since there is little else a signal handler
+    // may safely do
+    unsafe {
+        libc::signal(
+            libc::SIGHUP,
+            on_hangup as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+    thread::spawn(move || loop {
+        thread::sleep(HANGUP_POLL_INTERVAL);
+        if HANGUP.swap(false, Ordering::SeqCst) {
+            match reload() {
+                Ok(settings) => {
+                    live.apply(&settings);
+                    info!("Reloaded the configuration");
+                }
+                Err(error) => error!("Failed to reload the configuration: {}", error),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns_settings_into_args() {
+        let config: Table = toml::from_str(
+            r#"
+            connect = ["tcp://validator-0:4004", "tcp://validator-1:4004"]
+            gas-cap = 50000000
+            mock-chain = false
+            screening-simulate = true
+            rpc-api = ["-personal", "-debug"]
+            verbose = 2
+            "#,
+        )
+        .unwrap();
+        let given = ::app().get_matches_from(vec!["seth-rpc", "--gas-cap", "7"]);
+        assert_eq!(
+            args(&config, &given).unwrap(),
+            vec![
+                "--connect=tcp://validator-0:4004",
+                "--connect=tcp://validator-1:4004",
+                "--rpc-api=-personal,-debug",
+                "--screening-simulate",
+                "-v",
+                "-v",
+            ]
+        );
+
+        let nested: Table = toml::from_str("[bind]\nhost = \"0.0.0.0\"").unwrap();
+        assert!(args(&nested, &given).is_err());
+        assert_eq!(
+            restart_only(&config).keys().collect::<Vec<_>>(),
+            vec![
+                "connect",
+                "gas-cap",
+                "mock-chain",
+                "rpc-api",
+                "screening-simulate"
+            ]
+        );
+    }
+}
@@ -0,0 +1,166 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+use abi::Function;
+use serde_json::{self, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+/// The functions of an ABI and the block it was first used in
+type AbiVersion = (u64, Vec<Function>);
+
+/// The ABIs of contracts, by address, that calls to them can be encoded with. A
+/// contract that was upgraded, say behind a proxy, can have an ABI for each block
+/// it was upgraded in, so that what it returned before is decoded with the ABI it
+/// had then.
+#[derive(Clone, Default)]
+pub struct ContractRegistry {
+    /// The versions of each ABI and the block each was first used in, oldest first
+    contracts: Arc<RwLock<HashMap<String, Vec<AbiVersion>>>>,
+}
+
+impl ContractRegistry {
+    pub fn new() -> Self {
+        ContractRegistry::default()
+    }
+
+    /// Loads the ABIs in a directory, each in a file named after the address of its
+    /// contract, like `<address>.json`, or `<address>@<block>.json` for the ABI it
+    /// has from a block on. A file holds either the ABI itself or an object with an
+    /// `abi` field, as compilers write them.
+    pub fn load_dir(path: &str) -> Result<Self, String> {
+        let registry = ContractRegistry::new();
+        let entries = fs::read_dir(path).map_err(|err| format!("{}: {}", path, err))?;
+        for entry in entries {
+            let path = entry.map_err(|err| err.to_string())?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            registry
+                .load_file(&path)
+                .map_err(|err| format!("{}: {}", path.display(), err))?;
+        }
+        Ok(registry)
+    }
+
+    fn load_file(&self, path: &Path) -> Result<(), String> {
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or("Invalid file name")?;
+        let (address, from_block) = match stem.find('@') {
+            Some(at) => (
+                &stem[..at],
+                stem[at + 1..]
+                    .parse()
+                    .map_err(|_| "Invalid block number in file name")?,
+            ),
+            None => (stem, 0),
+        };
+        let file = fs::File::open(path).map_err(|err| err.to_string())?;
+        let json: Value = serde_json::from_reader(file).map_err(|err| err.to_string())?;
+        self.register(address, json.get("abi").unwrap_or(&json), from_block)
+    }
+
+    /// Registers the ABI the contract at the given address has from a block on,
+    /// replacing any registered from the same block
+    pub fn register(&self, address: &str, abi: &Value, from_block: u64) -> Result<(), String> {
+        let functions = Function::from_abi(abi)?;
+        let mut contracts = self.contracts.write().unwrap();
+        let versions = contracts.entry(normalize(address)).or_default();
+        match versions.binary_search_by_key(&from_block, |&(block, _)| block) {
+            Ok(index) => versions[index].1 = functions,
+            Err(index) => versions.insert(index, (from_block, functions)),
+        }
+        Ok(())
+    }
+
+    /// Finds a function of a contract by its name or, if it is overloaded, by its
+    /// signature, like `transfer(address,uint256)`, in the ABI the contract had at
+    /// the given block, or has now if none is given
+    pub fn function(
+        &self,
+        address: &str,
+        name: &str,
+        block: Option<u64>,
+    ) -> Result<Function, String> {
+        let contracts = self.contracts.read().unwrap();
+        let versions = contracts
+            .get(&normalize(address))
+            .ok_or_else(|| format!("No ABI is registered for {}", address))?;
+        let functions = match block {
+            Some(block) => versions
+                .iter()
+                .rev()
+                .find(|&&(from_block, _)| from_block <= block)
+                .map(|(_, functions)| functions)
+                .ok_or_else(|| {
+                    format!("No ABI is registered for {} at block {}", address, block)
+                })?,
+            None => &versions[versions.len() - 1].1,
+        };
+
+        let matches: Vec<&Function> = functions
+            .iter()
+            .filter(|function| function.name == name || function.signature() == name)
+            .collect();
+        match matches.len() {
+            0 => Err(format!("Contract has no function `{}`", name)),
+            1 => Ok(matches[0].clone()),
+            _ => Err(format!(
+                "Function `{}` is overloaded, use its signature instead",
+                name
+            )),
+        }
+    }
+}
+
+fn normalize(address: &str) -> String {
+    let address = address.trim_start_matches("0x");
+    address.to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uses_the_abi_a_contract_had_at_a_block() {
+        let registry = ContractRegistry::new();
+        let abi = |name: &str| {
+            serde_json::from_str::<Value>(&format!(
+                r#"[{{"type": "function", "name": "{}", "inputs": [], "outputs": []}}]"#,
+                name
+            ))
+            .unwrap()
+        };
+        registry.register("0xAB", &abi("old"), 0).unwrap();
+        registry.register("ab", &abi("new"), 100).unwrap();
+
+        assert!(registry.function("ab", "old", Some(99)).is_ok());
+        assert!(registry.function("ab", "new", Some(99)).is_err());
+        assert!(registry.function("ab", "new", Some(100)).is_ok());
+        assert!(registry.function("ab", "new", None).is_ok());
+        assert!(registry.function("ab", "old", None).is_err());
+
+        registry.register("ab", &abi("newer"), 100).unwrap();
+        assert!(registry.function("ab", "newer", Some(100)).is_ok());
+        assert!(registry.function("cd", "old", None).is_err());
+    }
+}
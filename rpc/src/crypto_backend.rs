@@ -0,0 +1,156 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! The implementations of Keccak-256 and of recovering the keys that made secp256k1
+//! signatures, which take most of the CPU of relaying raw transactions, chosen once at
+//! startup with `--crypto-backend`.
+
+use accounts::compressed_public_key;
+use secp256k1::{ContextFlag, Message, RecoverableSignature, RecoveryId, Secp256k1};
+use sha3::{Digest, Keccak256};
+use std::sync::OnceLock;
+use tiny_keccak;
+
+/// The names `--crypto-backend` takes
+pub const BACKENDS: &[&str] = &["optimized", "portable"];
+
+/// Hashes and recovers signers
+pub trait CryptoBackend: Send + Sync {
+    fn keccak256(&self, data: &[u8]) -> [u8; 32];
+
+    /// Returns the compressed public key that made a 64 byte compact signature of a
+    /// hash, given its recovery id, if the signature is valid
+    fn recover(&self, hash: &[u8; 32], signature: &[u8], recovery_id: i32) -> Option<Vec<u8>>;
+}
+
+/// Keccak from the `keccak` crate, with its unrolled permutation or, built with the
+/// `asm-keccak` feature on aarch64, the SHA3 instructions, and recovery with one
+/// libsecp256k1 context made at startup
+pub struct Optimized {
+    secp: Secp256k1,
+}
+
+impl Optimized {
+    pub fn new() -> Self {
+        Optimized {
+            secp: Secp256k1::with_caps(ContextFlag::VerifyOnly),
+        }
+    }
+}
+
+impl CryptoBackend for Optimized {
+    fn keccak256(&self, data: &[u8]) -> [u8; 32] {
+        Keccak256::digest(data).into()
+    }
+
+    fn recover(&self, hash: &[u8; 32], signature: &[u8], recovery_id: i32) -> Option<Vec<u8>> {
+        recover_with(&self.secp, hash, signature, recovery_id)
+    }
+}
+
+/// Keccak from `tiny-keccak`, and recovery with a libsecp256k1 context made for each
+/// signature, as seth-rpc did before backends could be chosen
+pub struct Portable;
+
+impl CryptoBackend for Portable {
+    fn keccak256(&self, data: &[u8]) -> [u8; 32] {
+        tiny_keccak::keccak256(data)
+    }
+
+    fn recover(&self, hash: &[u8; 32], signature: &[u8], recovery_id: i32) -> Option<Vec<u8>> {
+        recover_with(&Secp256k1::new(), hash, signature, recovery_id)
+    }
+}
+
+fn recover_with(
+    secp: &Secp256k1,
+    hash: &[u8; 32],
+    signature: &[u8],
+    recovery_id: i32,
+) -> Option<Vec<u8>> {
+    let message = Message::from_slice(hash).ok()?;
+    let public_key = RecoveryId::from_i32(recovery_id)
+        .and_then(|id| RecoverableSignature::from_compact(secp, signature, id))
+        .and_then(|signature| secp.recover(&message, &signature))
+        .ok()?;
+    Some(compressed_public_key(secp, &public_key))
+}
+
+static BACKEND: OnceLock<Box<dyn CryptoBackend>> = OnceLock::new();
+
+/// Makes a backend by its name, one of `BACKENDS`
+pub fn by_name(name: &str) -> Option<Box<dyn CryptoBackend>> {
+    match name {
+        "optimized" => Some(Box::new(Optimized::new())),
+        "portable" => Some(Box::new(Portable)),
+        _ => None,
+    }
+}
+
+/// Sets the backend that is used from then on, unless one already is
+pub fn set(backend: Box<dyn CryptoBackend>) -> Result<(), String> {
+    BACKEND
+        .set(backend)
+        .map_err(|_| String::from("The crypto backend is already set"))
+}
+
+/// Returns the backend in use, the optimized one unless another was set first
+pub fn backend() -> &'static dyn CryptoBackend {
+    BACKEND.get_or_init(|| Box::new(Optimized::new())).as_ref()
+}
+
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    backend().keccak256(data)
+}
+
+pub fn recover(hash: &[u8; 32], signature: &[u8], recovery_id: i32) -> Option<Vec<u8>> {
+    backend().recover(hash, signature, recovery_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use accounts::Account;
+    use transform;
+
+    #[test]
+    fn backends_agree() {
+        let account = Account::from_private_key(
+            "test",
+            "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01ac7be0efd",
+        )
+        .unwrap();
+        let hash = Portable.keccak256(b"message");
+        let signed = account.sign_hash(&hash).unwrap();
+        let public_key = transform::hex_str_to_bytes(account.public_key());
+
+        let optimized = Optimized::new();
+        for data in &[&b""[..], b"message", &[0xab; 1000]] {
+            assert_eq!(optimized.keccak256(data), Portable.keccak256(data));
+        }
+        let recovery_id = i32::from(signed[64] - 27);
+        assert_eq!(
+            optimized.recover(&hash, &signed[..64], recovery_id),
+            public_key
+        );
+        assert_eq!(
+            Portable.recover(&hash, &signed[..64], recovery_id),
+            public_key
+        );
+        assert_eq!(optimized.recover(&hash, &[0; 64], recovery_id), None);
+    }
+}
@@ -0,0 +1,599 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+use mock_chain::MockChain;
+use protobuf;
+use protocol::{Capabilities, ProtocolVersion};
+use sawtooth_sdk::messages::client_peers::ClientPeersGetRequest;
+use sawtooth_sdk::messages::client_status::ClientStatusGetRequest;
+use sawtooth_sdk::messages::validator::Message_MessageType;
+use sawtooth_sdk::messaging::stream::*;
+use sawtooth_sdk::messaging::zmq_stream::{ZmqMessageConnection, ZmqMessageSender};
+use std::cmp;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use uuid;
+
+/// How often the latency of each endpoint is measured
+const PROBE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long an endpoint has to answer a probe before it is considered unhealthy
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often validators that speak an older client protocol are asked again, in
+/// case they were upgraded
+const NEGOTIATE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long a validator has to answer a request of a later release before it is
+/// taken not to know it
+const NEGOTIATE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait before connecting to a validator again after it dropped the
+/// connection, doubled each time it drops the new one too
+const MIN_RECONNECT_DELAY: Duration = Duration::from_millis(100);
+
+/// The longest wait before connecting again, after which a connection that was
+/// dropped is taken to have been fine and the wait starts over
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// How requests that may go to any validator are spread over the healthy endpoints
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Balancing {
+    /// Each request goes to the endpoint with the lowest latency
+    Latency,
+    /// The endpoints take turns
+    RoundRobin,
+}
+
+impl Balancing {
+    pub fn parse(name: &str) -> Result<Balancing, String> {
+        match name.to_lowercase().as_str() {
+            "latency" => Ok(Balancing::Latency),
+            "round-robin" => Ok(Balancing::RoundRobin),
+            _ => Err(format!(
+                "Unknown balancing {}, must be latency or round-robin",
+                name
+            )),
+        }
+    }
+}
+
+struct Endpoint<S: MessageSender> {
+    url: String,
+    sender: RwLock<S>,
+
+    /// The round-trip time of the last probe, or `None` if it failed
+    latency: RwLock<Option<Duration>>,
+}
+
+/// Sends requests to one of several validators. Batches, and the status requests
+/// that follow them, always go to the first, preferred, endpoint, as do lookups of
+/// the block that holds a transaction, which it sees first. Everything else goes
+/// to a healthy endpoint as `balancing` picks, or to the preferred endpoint if none
+/// of them are healthy, and is sent to another if it can't be sent to the one
+/// picked. Requests only go to endpoints whose validators answer them, while any
+/// do.
+pub struct EndpointSender<S: MessageSender> {
+    endpoints: Arc<Vec<Endpoint<S>>>,
+    capabilities: Capabilities,
+    balancing: Balancing,
+    /// The turn of the next request, when the endpoints take turns
+    turn: Arc<AtomicUsize>,
+}
+
+impl<S: MessageSender> Clone for EndpointSender<S> {
+    fn clone(&self) -> Self {
+        EndpointSender {
+            endpoints: self.endpoints.clone(),
+            capabilities: self.capabilities.clone(),
+            balancing: self.balancing,
+            turn: self.turn.clone(),
+        }
+    }
+}
+
+impl<S: MessageSender> EndpointSender<S> {
+    /// Creates a sender from `(url, sender)` pairs, the first of which is preferred.
+    /// Every endpoint is taken to be healthy until it is probed.
+    pub fn new(endpoints: Vec<(String, S)>) -> Self {
+        assert!(!endpoints.is_empty(), "At least one endpoint is required");
+        EndpointSender {
+            capabilities: Capabilities::new(endpoints.len()),
+            endpoints: Arc::new(
+                endpoints
+                    .into_iter()
+                    .map(|(url, sender)| Endpoint {
+                        url,
+                        sender: RwLock::new(sender),
+                        latency: RwLock::new(Some(Duration::from_secs(0))),
+                    })
+                    .collect(),
+            ),
+            balancing: Balancing::Latency,
+            turn: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn with_balancing(mut self, balancing: Balancing) -> Self {
+        self.balancing = balancing;
+        self
+    }
+
+    /// Measures the latency of every endpoint once
+    pub fn probe(&self) {
+        let request = protobuf::Message::write_to_bytes(&ClientPeersGetRequest::new())
+            .expect("Failed to serialize probe");
+        for endpoint in self.endpoints.iter() {
+            let start = Instant::now();
+            let result = endpoint
+                .sender
+                .read()
+                .unwrap()
+                .send(
+                    Message_MessageType::CLIENT_PEERS_GET_REQUEST,
+                    &uuid::Uuid::new_v4().to_string(),
+                    &request,
+                )
+                .map_err(|error| format!("{:?}", error))
+                .and_then(|mut future| {
+                    future
+                        .get_timeout(PROBE_TIMEOUT)
+                        .map_err(|error| format!("{:?}", error))
+                });
+
+            let latency = match result {
+                Ok(_) => Some(start.elapsed()),
+                Err(error) => {
+                    warn!("Validator at {} is unhealthy: {}", endpoint.url, error);
+                    None
+                }
+            };
+            debug!("Latency of {}: {:?}", endpoint.url, latency);
+            *endpoint.latency.write().unwrap() = latency;
+        }
+    }
+
+    /// The release of the client protocol that each validator speaks, as far as it
+    /// has been negotiated
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities.clone()
+    }
+
+    /// Asks each validator that isn't known to speak the latest client protocol
+    /// which release it speaks
+    pub fn negotiate(&self) {
+        for (index, endpoint) in self.endpoints.iter().enumerate() {
+            let previous = self.capabilities.version(index);
+            if previous == Some(ProtocolVersion::V1_1) {
+                continue;
+            }
+            if let Some(version) = negotiate_version(endpoint) {
+                if previous != Some(version) {
+                    info!(
+                        "Validator at {} speaks the {} client protocol",
+                        endpoint.url, version
+                    );
+                }
+                self.capabilities.set_version(index, version);
+            }
+        }
+    }
+
+    /// Picks the endpoint to send a request to, other than those it couldn't be sent
+    /// to, which must leave at least one
+    fn route(&self, destination: Message_MessageType, failed: &[usize]) -> usize {
+        // Validators that don't answer the request are only sent it if none do
+        let capable: Vec<usize> = (0..self.endpoints.len())
+            .filter(|index| !failed.contains(index))
+            .filter(|&index| self.capabilities.endpoint_supports(index, destination))
+            .collect();
+        let preferred = capable.first().cloned().unwrap_or_else(|| {
+            (0..self.endpoints.len())
+                .find(|index| !failed.contains(index))
+                .unwrap_or(0)
+        });
+        if is_pinned(destination) {
+            return preferred;
+        }
+
+        let healthy: Vec<(usize, Duration)> = capable
+            .into_iter()
+            .filter_map(|index| {
+                let latency = *self.endpoints[index].latency.read().unwrap();
+                latency.map(|latency| (index, latency))
+            })
+            .collect();
+        if healthy.is_empty() {
+            return preferred;
+        }
+        match self.balancing {
+            Balancing::Latency => healthy
+                .iter()
+                .min_by_key(|&&(_, latency)| latency)
+                .map(|&(index, _)| index)
+                .unwrap_or(preferred),
+            Balancing::RoundRobin => {
+                healthy[self.turn.fetch_add(1, Ordering::Relaxed) % healthy.len()].0
+            }
+        }
+    }
+}
+
+impl<S: MessageSender + Send + Sync + 'static> EndpointSender<S> {
+    /// Starts negotiating with the validators in the background, and probing the
+    /// endpoints if there is more than one to choose from
+    pub fn start_probing(&self) {
+        let sender = self.clone();
+        thread::spawn(move || loop {
+            sender.negotiate();
+            thread::sleep(NEGOTIATE_INTERVAL);
+        });
+
+        if self.endpoints.len() < 2 {
+            return;
+        }
+        let sender = self.clone();
+        thread::spawn(move || loop {
+            sender.probe();
+            thread::sleep(PROBE_INTERVAL);
+        });
+    }
+}
+
+/// Whether requests of a type only go to the preferred endpoint
+fn is_pinned(destination: Message_MessageType) -> bool {
+    matches!(
+        destination,
+        Message_MessageType::CLIENT_BATCH_SUBMIT_REQUEST
+            | Message_MessageType::CLIENT_BATCH_STATUS_REQUEST
+            | Message_MessageType::CLIENT_BLOCK_GET_BY_TRANSACTION_ID_REQUEST
+    )
+}
+
+/// Tells which release of the client protocol the validator speaks from the latest
+/// release whose request it answers, or returns `None` if it can't be reached
+fn negotiate_version<S: MessageSender>(endpoint: &Endpoint<S>) -> Option<ProtocolVersion> {
+    let request = protobuf::Message::write_to_bytes(&ClientStatusGetRequest::new())
+        .expect("Failed to serialize negotiation request");
+    // Status requests were added in 1.1
+    let result = endpoint
+        .sender
+        .read()
+        .unwrap()
+        .send(
+            Message_MessageType::CLIENT_STATUS_GET_REQUEST,
+            &uuid::Uuid::new_v4().to_string(),
+            &request,
+        )
+        .map(|mut future| future.get_timeout(NEGOTIATE_TIMEOUT));
+    match result {
+        Ok(Ok(_)) => Some(ProtocolVersion::V1_1),
+        Ok(Err(ReceiveError::TimeoutError)) => Some(ProtocolVersion::V1_0),
+        Ok(Err(error)) => {
+            debug!("Failed to negotiate with {}: {:?}", endpoint.url, error);
+            None
+        }
+        Err(error) => {
+            debug!("Failed to negotiate with {}: {:?}", endpoint.url, error);
+            None
+        }
+    }
+}
+
+impl<S: MessageSender> MessageSender for EndpointSender<S> {
+    fn send(
+        &self,
+        destination: Message_MessageType,
+        correlation_id: &str,
+        contents: &[u8],
+    ) -> Result<MessageFuture, SendError> {
+        let mut failed = Vec::new();
+        loop {
+            let index = self.route(destination, &failed);
+            let endpoint = &self.endpoints[index];
+            let error =
+                match endpoint
+                    .sender
+                    .read()
+                    .unwrap()
+                    .send(destination, correlation_id, contents)
+                {
+                    Ok(future) => return Ok(future),
+                    Err(error) => error,
+                };
+            if is_pinned(destination) || failed.len() + 1 >= self.endpoints.len() {
+                return Err(error);
+            }
+            // The endpoint is skipped until a probe finds it healthy again
+            warn!(
+                "Failed to send to validator at {}, trying another: {:?}",
+                endpoint.url, error
+            );
+            *endpoint.latency.write().unwrap() = None;
+            failed.push(index);
+        }
+    }
+
+    fn reply(
+        &self,
+        destination: Message_MessageType,
+        correlation_id: &str,
+        contents: &[u8],
+    ) -> Result<(), SendError> {
+        self.endpoints[0]
+            .sender
+            .read()
+            .unwrap()
+            .reply(destination, correlation_id, contents)
+    }
+
+    fn close(&mut self) {
+        for endpoint in self.endpoints.iter() {
+            endpoint.sender.write().unwrap().close();
+        }
+    }
+}
+
+/// The connection to a validator, or the mock chain served in place of one
+pub enum Connection {
+    Validator(ValidatorConnection),
+    Mock(MockChain),
+}
+
+struct Backoff {
+    /// When the connection was last opened again
+    last_attempt: Option<Instant>,
+    delay: Duration,
+}
+
+impl Backoff {
+    fn new() -> Self {
+        Backoff {
+            last_attempt: None,
+            delay: MIN_RECONNECT_DELAY,
+        }
+    }
+
+    /// Returns whether the connection may be opened again at `now`, counting it as
+    /// opened if so
+    fn attempt(&mut self, now: Instant) -> bool {
+        if let Some(last_attempt) = self.last_attempt {
+            let since = now.duration_since(last_attempt);
+            if since < self.delay {
+                return false;
+            }
+            self.delay = if since > MAX_RECONNECT_DELAY {
+                MIN_RECONNECT_DELAY
+            } else {
+                cmp::min(self.delay * 2, MAX_RECONNECT_DELAY)
+            };
+        }
+        self.last_attempt = Some(now);
+        true
+    }
+}
+
+/// A connection to a validator that is opened again when the validator drops it,
+/// like when it restarts. The sender the sdk makes stops once its connection is
+/// dropped, failing the requests waiting on it, so the next request opens a new
+/// one, waiting longer each time if the validator keeps dropping them.
+pub struct ValidatorConnection {
+    url: String,
+    sender: RwLock<ZmqMessageSender>,
+    backoff: Mutex<Backoff>,
+}
+
+impl ValidatorConnection {
+    pub fn new(url: &str) -> Self {
+        let (sender, _) = ZmqMessageConnection::new(url).create();
+        ValidatorConnection {
+            url: String::from(url),
+            sender: RwLock::new(sender),
+            backoff: Mutex::new(Backoff::new()),
+        }
+    }
+
+    /// Opens the connection again, unless it was opened again too recently, and
+    /// returns whether it was
+    fn reconnect(&self) -> bool {
+        if !self.backoff.lock().unwrap().attempt(Instant::now()) {
+            return false;
+        }
+
+        warn!("Lost the connection to {}, connecting again", self.url);
+        let (sender, _) = ZmqMessageConnection::new(&self.url).create();
+        let mut old = mem::replace(&mut *self.sender.write().unwrap(), sender);
+        old.close();
+        true
+    }
+}
+
+impl MessageSender for ValidatorConnection {
+    fn send(
+        &self,
+        destination: Message_MessageType,
+        correlation_id: &str,
+        contents: &[u8],
+    ) -> Result<MessageFuture, SendError> {
+        let result = self
+            .sender
+            .read()
+            .unwrap()
+            .send(destination, correlation_id, contents);
+        match result {
+            Err(_) if self.reconnect() => {
+                self.sender
+                    .read()
+                    .unwrap()
+                    .send(destination, correlation_id, contents)
+            }
+            result => result,
+        }
+    }
+
+    fn reply(
+        &self,
+        destination: Message_MessageType,
+        correlation_id: &str,
+        contents: &[u8],
+    ) -> Result<(), SendError> {
+        self.sender
+            .read()
+            .unwrap()
+            .reply(destination, correlation_id, contents)
+    }
+
+    fn close(&mut self) {
+        self.sender.write().unwrap().close();
+    }
+}
+
+impl MessageSender for Connection {
+    fn send(
+        &self,
+        destination: Message_MessageType,
+        correlation_id: &str,
+        contents: &[u8],
+    ) -> Result<MessageFuture, SendError> {
+        match *self {
+            Connection::Validator(ref sender) => sender.send(destination, correlation_id, contents),
+            Connection::Mock(ref chain) => chain.send(destination, correlation_id, contents),
+        }
+    }
+
+    fn reply(
+        &self,
+        destination: Message_MessageType,
+        correlation_id: &str,
+        contents: &[u8],
+    ) -> Result<(), SendError> {
+        match *self {
+            Connection::Validator(ref sender) => {
+                sender.reply(destination, correlation_id, contents)
+            }
+            Connection::Mock(ref chain) => chain.reply(destination, correlation_id, contents),
+        }
+    }
+
+    fn close(&mut self) {
+        match *self {
+            Connection::Validator(ref mut sender) => sender.close(),
+            Connection::Mock(ref mut chain) => chain.close(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A validator that answers as the mock chain does, or one that can't be sent to
+    enum TestSender {
+        Up(MockChain),
+        Down,
+    }
+
+    impl MessageSender for TestSender {
+        fn send(
+            &self,
+            destination: Message_MessageType,
+            correlation_id: &str,
+            contents: &[u8],
+        ) -> Result<MessageFuture, SendError> {
+            match *self {
+                TestSender::Up(ref chain) => chain.send(destination, correlation_id, contents),
+                TestSender::Down => Err(SendError::DisconnectedError),
+            }
+        }
+
+        fn reply(
+            &self,
+            _destination: Message_MessageType,
+            _correlation_id: &str,
+            _contents: &[u8],
+        ) -> Result<(), SendError> {
+            Ok(())
+        }
+
+        fn close(&mut self) {}
+    }
+
+    fn sender(endpoints: Vec<TestSender>) -> EndpointSender<TestSender> {
+        EndpointSender::new(
+            endpoints
+                .into_iter()
+                .enumerate()
+                .map(|(index, sender)| (format!("tcp://validator-{}:4004", index), sender))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn takes_turns_except_with_batches() {
+        let chain = MockChain::new();
+        let sender = sender(vec![
+            TestSender::Up(chain.clone()),
+            TestSender::Up(chain.clone()),
+            TestSender::Up(chain),
+        ])
+        .with_balancing(Balancing::RoundRobin);
+        let routes: Vec<usize> = (0..4)
+            .map(|_| sender.route(Message_MessageType::CLIENT_STATE_GET_REQUEST, &[]))
+            .collect();
+        assert_eq!(routes, vec![0, 1, 2, 0]);
+        assert_eq!(
+            sender.route(Message_MessageType::CLIENT_BATCH_SUBMIT_REQUEST, &[]),
+            0
+        );
+    }
+
+    #[test]
+    fn fails_over_to_endpoints_that_can_be_sent_to() {
+        let sender = sender(vec![TestSender::Down, TestSender::Up(MockChain::new())]);
+        assert!(sender
+            .send(Message_MessageType::CLIENT_STATE_GET_REQUEST, "1", &[])
+            .is_ok());
+        assert_eq!(
+            sender.route(Message_MessageType::CLIENT_STATE_GET_REQUEST, &[]),
+            1
+        );
+        // Batches stay with the preferred validator
+        assert!(sender
+            .send(Message_MessageType::CLIENT_BATCH_SUBMIT_REQUEST, "2", &[])
+            .is_err());
+    }
+
+    #[test]
+    fn backs_off_while_connections_keep_dropping() {
+        let mut backoff = Backoff::new();
+        let start = Instant::now();
+        assert!(backoff.attempt(start));
+        assert!(!backoff.attempt(start + Duration::from_millis(50)));
+        assert!(backoff.attempt(start + Duration::from_millis(100)));
+        assert_eq!(backoff.delay, Duration::from_millis(200));
+        assert!(!backoff.attempt(start + Duration::from_millis(250)));
+        assert!(backoff.attempt(start + Duration::from_millis(300)));
+        assert_eq!(backoff.delay, Duration::from_millis(400));
+
+        // A connection that lasted longer than the longest wait was fine
+        assert!(backoff.attempt(start + Duration::from_secs(60)));
+        assert_eq!(backoff.delay, MIN_RECONNECT_DELAY);
+    }
+}
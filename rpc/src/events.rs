@@ -0,0 +1,135 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! The blocks the validator commits, which it pushes a `sawtooth/block-commit`
+//! event for, as `eth_subscribe` and the log index follow them.
+
+use protobuf;
+use sawtooth_sdk::messages::client_event::{
+    ClientEventsSubscribeRequest, ClientEventsSubscribeResponse,
+    ClientEventsSubscribeResponse_Status,
+};
+use sawtooth_sdk::messages::events::{EventList, EventSubscription};
+use sawtooth_sdk::messages::validator::Message_MessageType;
+use sawtooth_sdk::messaging::stream::*;
+use sawtooth_sdk::messaging::zmq_stream::ZmqMessageConnection;
+use std::thread;
+use std::time::Duration;
+use uuid;
+
+/// The event the validator sends for each block it commits
+const BLOCK_COMMIT_EVENT: &str = "sawtooth/block-commit";
+
+/// How long to wait for the validator to accept the event subscription
+const SUBSCRIBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait before subscribing again after losing the validator
+const RESUBSCRIBE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Calls `on_block` in the background with the id of each block committed, as told by
+/// the first of the validators that accepts the subscription. If that validator goes
+/// away, the others are tried in turn, and the blocks committed in between are caught
+/// up on.
+pub fn watch_blocks<F>(urls: Vec<String>, mut on_block: F)
+where
+    F: FnMut(String) + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut last_block_id = None;
+        for url in urls.iter().cycle() {
+            let (sender, receiver) = ZmqMessageConnection::new(url).create();
+            match subscribe_to_blocks(&sender, last_block_id.as_ref()) {
+                Ok(()) => {
+                    info!("Subscribed to blocks committed by {}", url);
+                    listen(&receiver, &mut last_block_id, &mut on_block);
+                    warn!("Lost the block subscription to {}", url);
+                }
+                Err(error) => warn!("Failed to subscribe to blocks at {}: {}", url, error),
+            }
+            thread::sleep(RESUBSCRIBE_INTERVAL);
+        }
+    });
+}
+
+/// Passes on each committed block until the validator goes away
+fn listen<F: FnMut(String)>(
+    receiver: &MessageReceiver,
+    last_block_id: &mut Option<String>,
+    on_block: &mut F,
+) {
+    while let Ok(Ok(message)) = receiver.recv() {
+        if message.message_type != Message_MessageType::CLIENT_EVENTS {
+            continue;
+        }
+        let events: EventList = match protobuf::parse_from_bytes(&message.content) {
+            Ok(events) => events,
+            Err(error) => {
+                error!("Error parsing events: {:?}", error);
+                continue;
+            }
+        };
+        for event in events.get_events() {
+            let block_id = event
+                .get_attributes()
+                .iter()
+                .find(|attribute| attribute.key == "block_id")
+                .map(|attribute| attribute.value.clone());
+            if let Some(block_id) = block_id {
+                on_block(block_id.clone());
+                *last_block_id = Some(block_id);
+            }
+        }
+    }
+}
+
+/// Asks the validator to send an event for each block it commits, starting after the
+/// given block if it still knows it
+fn subscribe_to_blocks<S: MessageSender>(
+    sender: &S,
+    last_block_id: Option<&String>,
+) -> Result<(), String> {
+    let mut subscription = EventSubscription::new();
+    subscription.set_event_type(String::from(BLOCK_COMMIT_EVENT));
+    let mut request = ClientEventsSubscribeRequest::new();
+    request.set_subscriptions(protobuf::RepeatedField::from_vec(vec![subscription]));
+    if let Some(block_id) = last_block_id {
+        request.set_last_known_block_ids(protobuf::RepeatedField::from_vec(vec![block_id.clone()]));
+    }
+
+    let request_bytes = protobuf::Message::write_to_bytes(&request)
+        .map_err(|error| format!("Error serializing request: {:?}", error))?;
+    let response = sender
+        .send(
+            Message_MessageType::CLIENT_EVENTS_SUBSCRIBE_REQUEST,
+            &uuid::Uuid::new_v4().to_string(),
+            &request_bytes,
+        )
+        .map_err(|error| format!("{}", error))?
+        .get_timeout(SUBSCRIBE_TIMEOUT)
+        .map_err(|error| format!("{:?}", error))?;
+    let response: ClientEventsSubscribeResponse = protobuf::parse_from_bytes(&response.content)
+        .map_err(|error| format!("Error parsing response: {:?}", error))?;
+
+    match response.status {
+        ClientEventsSubscribeResponse_Status::OK => Ok(()),
+        // The block was forked out, so the missed blocks can't be caught up on
+        ClientEventsSubscribeResponse_Status::UNKNOWN_BLOCK if last_block_id.is_some() => {
+            subscribe_to_blocks(sender, None)
+        }
+        status => Err(format!("{:?}: {}", status, response.get_response_message())),
+    }
+}
@@ -19,10 +19,12 @@ use client::Error;
 use jsonrpc_core::{Error as RpcError, ErrorCode, Value};
 use serde_json::Map;
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+use bloom::Bloom;
 use transactions::SethLog;
 use transform;
 
@@ -57,6 +59,20 @@ impl TopicFilter {
             _ => Err(RpcError::invalid_params("Invalid topic setting")),
         }
     }
+    #[cfg(feature = "postgres")]
+    pub fn to_value(&self) -> Value {
+        match *self {
+            TopicFilter::All => Value::Null,
+            TopicFilter::Exactly(ref blob) => transform::hex_prefix(blob),
+            TopicFilter::OneOf(ref blobs) => Value::Array(
+                blobs
+                    .iter()
+                    .map(|blob| transform::hex_prefix(blob))
+                    .collect(),
+            ),
+        }
+    }
+
     /// The topic passes this filter if:
     ///   1. The filter is All (which corresponds to a null entry in the topic filter list)
     ///   2. The filter is Exactly and the topic and the filter are identitical
@@ -68,50 +84,80 @@ impl TopicFilter {
             TopicFilter::OneOf(ref blobs) => blobs.contains(&String::from(topic)),
         }
     }
+
+    /// Returns whether a topic passing this filter may be in the bloom
+    pub fn may_match(&self, bloom: &Bloom) -> bool {
+        match *self {
+            TopicFilter::All => true,
+            TopicFilter::Exactly(ref blob) => bloom.may_contain_hex(blob, 32),
+            TopicFilter::OneOf(ref blobs) => {
+                blobs.iter().any(|blob| bloom.may_contain_hex(blob, 32))
+            }
+        }
+    }
 }
 
 // -- LogFilter --
 #[derive(Debug, Clone)]
 pub struct LogFilter {
+    /// The first block to search, or the latest block if None
     pub from_block: Option<u64>,
+    /// The last block to search, or the latest block if None
     pub to_block: Option<u64>,
+    /// The one block to search instead of a range, by id
+    pub block_hash: Option<String>,
     pub addresses: Vec<String>,
     pub topics: Vec<TopicFilter>,
 }
 
+/// Parses a block bound, which is a number or a tag
+fn block_bound_from_value(value: Option<&Value>) -> Result<Option<u64>, RpcError> {
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    match value.as_str() {
+        Some("latest") | Some("pending") => Ok(None),
+        Some("earliest") => Ok(Some(0)),
+        _ => transform::u64_from_hex_value(value)
+            .map(Some)
+            .map_err(|_| RpcError::new(ErrorCode::ParseError)),
+    }
+}
+
 impl LogFilter {
     pub fn from_map(filter: &Map<String, Value>) -> Result<Self, RpcError> {
-        let from_block = match filter.get("fromBlock") {
-            Some(s) => {
-                let s = transform::u64_from_hex_value(s)
-                    .map_err(|_| RpcError::new(ErrorCode::ParseError))?;
-                Ok(Some(s))
-            }
-            None => Ok(None),
-        }?;
-        let to_block = match filter.get("toBlock") {
-            Some(s) => {
-                let s = transform::u64_from_hex_value(s)
-                    .map_err(|_| RpcError::new(ErrorCode::ParseError))?;
-                Ok(Some(s))
-            }
-            None => Ok(None),
-        }?;
+        let from_block = block_bound_from_value(filter.get("fromBlock"))?;
+        let to_block = block_bound_from_value(filter.get("toBlock"))?;
+
+        let block_hash = match filter.get("blockHash") {
+            Some(value) => Some(
+                transform::string_from_hex_value(value)
+                    .map_err(|_| RpcError::new(ErrorCode::ParseError))?,
+            ),
+            None => None,
+        };
+        if block_hash.is_some()
+            && (filter.contains_key("fromBlock") || filter.contains_key("toBlock"))
+        {
+            return Err(RpcError::invalid_params(
+                "Takes either blockHash or fromBlock and toBlock",
+            ));
+        }
 
         // Parse the address into a vec of strings
+        let parse_address = |value: &Value| {
+            value
+                .as_str()
+                .ok_or_else(|| RpcError::new(ErrorCode::ParseError))
+                .and_then(transform::parse_address)
+        };
         let addresses = match filter.get("address") {
             Some(&Value::Array(ref multiple)) => multiple
                 .iter()
-                .map(|addr_val| {
-                    transform::string_from_hex_value(addr_val)
-                        .map_err(|_| RpcError::new(ErrorCode::ParseError))
-                })
+                .map(parse_address)
                 .collect::<Result<Vec<String>, RpcError>>()?,
-            Some(value) => {
-                let address = transform::string_from_hex_value(value)
-                    .map_err(|_| RpcError::new(ErrorCode::ParseError))?;
-                vec![address]
-            }
+            Some(value) => vec![parse_address(value)?],
             None => vec![],
         };
 
@@ -127,13 +173,14 @@ impl LogFilter {
         Ok(LogFilter {
             from_block,
             to_block,
+            block_hash,
             addresses,
             topics,
         })
     }
 
     /// The log passes this filter if:
-    ///   1. the log's address is in the list of the filter's addresses
+    ///   1. the log's address is in the list of the filter's addresses, if it has any
     ///   2. the log's topic list is less than or equal to the filter's list of topic filters
     ///   3. the log topic at each index passes the topic filter at that index
     pub fn contains(&self, log: &SethLog, block_num: Option<u64>) -> bool {
@@ -147,7 +194,7 @@ impl LogFilter {
     }
 
     pub fn contains_address(&self, address: &str) -> bool {
-        self.addresses.contains(&String::from(address))
+        self.addresses.is_empty() || self.addresses.contains(&String::from(address))
     }
 
     pub fn contains_topics(&self, topics: &[String]) -> bool {
@@ -160,6 +207,17 @@ impl LogFilter {
         })
     }
 
+    /// Returns whether the bloom of a block's logs may have a log that passes this
+    /// filter, so that blocks that can't have one are skipped without their receipts
+    pub fn may_match(&self, bloom: &Bloom) -> bool {
+        let may_match_address = self.addresses.is_empty()
+            || self
+                .addresses
+                .iter()
+                .any(|address| bloom.may_contain_hex(address, 20));
+        may_match_address && self.topics.iter().all(|filter| filter.may_match(bloom))
+    }
+
     pub fn contains_block(&self, block_num: u64) -> bool {
         let lower = match self.from_block {
             Some(n) => block_num >= n,
@@ -173,6 +231,40 @@ impl LogFilter {
     }
 }
 
+/// Where a page of logs ended: the position of its last log in the chain and the
+/// id of the block it is in, so that a cursor into a block that was forked out is
+/// noticed. Cursors only depend on the chain, so they stay valid when the server
+/// restarts or its log index is rebuilt.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogCursor {
+    pub block_num: u64,
+    pub log_idx: u64,
+    pub block_id: String,
+}
+
+impl LogCursor {
+    pub fn from_value(value: &Value) -> Result<Self, RpcError> {
+        transform::from_hex_value_then(value, |s| {
+            let invalid = || RpcError::invalid_params("Invalid cursor");
+            if s.len() <= 24 || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(invalid());
+            }
+            Ok(LogCursor {
+                block_num: u64::from_str_radix(&s[..16], 16).map_err(|_| invalid())?,
+                log_idx: u64::from_str_radix(&s[16..24], 16).map_err(|_| invalid())?,
+                block_id: s[24..].to_lowercase(),
+            })
+        })
+    }
+
+    pub fn to_value(&self) -> Value {
+        transform::hex_prefix(&format!(
+            "{:016x}{:08x}{}",
+            self.block_num, self.log_idx, self.block_id
+        ))
+    }
+}
+
 pub type FilterId = usize;
 pub fn filter_id_from_hex(s: &str) -> Result<FilterId, Error> {
     usize::from_str_radix(s, 16)
@@ -188,54 +280,258 @@ pub struct FilterEntry {
     pub last_block_sent: u64,
 }
 
+/// The number of recent blocks whose logs are kept in memory
+const BLOCK_LOG_CACHE_SIZE: usize = 64;
+
+/// The number of blocks whose blooms are kept in memory, 1 MiB of them
+const BLOCK_BLOOM_CACHE_SIZE: usize = 4096;
+
+/// How long a filter that isn't polled is kept, like other Ethereum clients do
+pub const DEFAULT_FILTER_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How often idle filters are looked for
+const FILTER_SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A log emitted in a committed block, along with the transaction that emitted it
 #[derive(Debug, Clone)]
-pub struct FilterManager {
-    id_ctr: Arc<AtomicUsize>,
-    filters: Arc<Mutex<HashMap<FilterId, FilterEntry>>>,
+pub struct BlockLog {
+    pub txn_id: String,
+    pub txn_idx: u64,
+    pub log: SethLog,
 }
 
-impl FilterManager {
+pub type BlockLogs = Arc<Vec<BlockLog>>;
+
+/// Where installed filters are kept. Replicas of the server behind a load balancer
+/// must share a store, since a filter can be polled through any of them. Errors are
+/// those of the store itself, such as a database it can't reach, and are never
+/// taken to mean the filter doesn't exist.
+pub trait FilterStore: Send + Sync {
+    /// Installs a new filter, or returns `None` if `max_filters` are already installed
+    fn insert(&self, entry: FilterEntry, max_filters: usize) -> Result<Option<FilterId>, String>;
+
+    fn remove(&self, filter_id: FilterId) -> Result<Option<FilterEntry>, String>;
+
+    /// Returns a filter, counting as a poll that keeps it from expiring
+    fn get(&self, filter_id: FilterId) -> Result<Option<FilterEntry>, String>;
+
+    fn update_latest_block(&self, filter_id: FilterId, block_num: u64) -> Result<bool, String>;
+
+    /// Removes the filters that weren't polled for `idle_timeout`, returning how many
+    fn expire(&self, idle_timeout: Duration) -> Result<usize, String>;
+
+    /// Returns how many filters are installed
+    fn count(&self) -> Result<usize, String>;
+}
+
+/// Keeps filters in memory, which is enough for a single server
+#[derive(Default)]
+pub struct MemoryFilterStore {
+    id_ctr: AtomicUsize,
+    // Each filter with when it was last polled
+    filters: Mutex<HashMap<FilterId, (FilterEntry, Instant)>>,
+}
+
+impl MemoryFilterStore {
     pub fn new() -> Self {
-        FilterManager {
-            id_ctr: Arc::new(AtomicUsize::new(1)),
-            filters: Arc::new(Mutex::new(HashMap::new())),
+        MemoryFilterStore {
+            id_ctr: AtomicUsize::new(1),
+            filters: Mutex::new(HashMap::new()),
         }
     }
+}
+
+impl FilterStore for MemoryFilterStore {
+    fn insert(&self, entry: FilterEntry, max_filters: usize) -> Result<Option<FilterId>, String> {
+        let mut filters = self.filters.lock().unwrap();
+        if filters.len() >= max_filters {
+            return Ok(None);
+        }
 
-    pub fn new_filter(&self, filter: Filter, block_num: u64) -> FilterId {
         let filter_id = self.id_ctr.fetch_add(1, Ordering::SeqCst);
-        self.set_filter(filter_id, filter, block_num);
-        filter_id
+        filters.insert(filter_id, (entry, Instant::now()));
+        Ok(Some(filter_id))
     }
 
-    pub fn remove_filter(&self, filter_id: FilterId) -> Option<FilterEntry> {
-        self.filters.lock().unwrap().remove(&filter_id)
+    fn remove(&self, filter_id: FilterId) -> Result<Option<FilterEntry>, String> {
+        Ok(self
+            .filters
+            .lock()
+            .unwrap()
+            .remove(&filter_id)
+            .map(|(entry, _)| entry))
     }
 
-    pub fn get_filter(&self, filter_id: FilterId) -> Option<FilterEntry> {
-        self.filters.lock().unwrap().get(&filter_id).cloned()
+    fn get(&self, filter_id: FilterId) -> Result<Option<FilterEntry>, String> {
+        Ok(self.filters.lock().unwrap().get_mut(&filter_id).map(
+            |&mut (ref entry, ref mut polled)| {
+                *polled = Instant::now();
+                entry.clone()
+            },
+        ))
     }
 
-    pub fn update_latest_block(&self, filter_id: FilterId, block_num: u64) -> bool {
+    fn update_latest_block(&self, filter_id: FilterId, block_num: u64) -> Result<bool, String> {
         if let Entry::Occupied(mut entry) = self.filters.lock().unwrap().entry(filter_id) {
-            (*entry.get_mut()).last_block_sent = block_num;
-            true
+            (*entry.get_mut()).0.last_block_sent = block_num;
+            Ok(true)
         } else {
-            false
+            Ok(false)
         }
     }
 
-    pub fn set_filter(
-        &self,
-        filter_id: FilterId,
-        filter: Filter,
-        block_num: u64,
-    ) -> Option<FilterEntry> {
-        let filter_entry = FilterEntry {
-            filter,
-            last_block_sent: block_num,
+    fn expire(&self, idle_timeout: Duration) -> Result<usize, String> {
+        let mut filters = self.filters.lock().unwrap();
+        let count = filters.len();
+        filters.retain(|_, &mut (_, polled)| polled.elapsed() < idle_timeout);
+        Ok(count - filters.len())
+    }
+
+    fn count(&self) -> Result<usize, String> {
+        Ok(self.filters.lock().unwrap().len())
+    }
+}
+
+#[derive(Clone)]
+pub struct FilterManager {
+    store: Arc<dyn FilterStore>,
+    max_filters: usize,
+    // Filters not polled for this long are removed, unless it is None
+    idle_timeout: Option<Duration>,
+    last_sweep: Arc<Mutex<Instant>>,
+    // Logs of recently committed blocks, so that every filter polling for the same new
+    // blocks is checked against one copy of their logs instead of refetching receipts
+    block_logs: Arc<Mutex<VecDeque<(String, BlockLogs)>>>,
+    // Blooms of the logs of many more blocks, so that log searches over a range read
+    // the receipts of only the blocks that may have matching logs
+    block_blooms: Arc<Mutex<BloomCache>>,
+}
+
+/// The blooms of blocks by id, with the ids in the order they were cached
+#[derive(Default)]
+struct BloomCache {
+    blooms: HashMap<String, Bloom>,
+    order: VecDeque<String>,
+}
+
+impl FilterManager {
+    pub fn new(max_filters: usize) -> Self {
+        FilterManager::with_store(Arc::new(MemoryFilterStore::new()), max_filters)
+    }
+
+    pub fn with_store(store: Arc<dyn FilterStore>, max_filters: usize) -> Self {
+        FilterManager {
+            store,
+            max_filters,
+            idle_timeout: Some(DEFAULT_FILTER_TIMEOUT),
+            last_sweep: Arc::new(Mutex::new(Instant::now())),
+            block_logs: Arc::new(Mutex::new(VecDeque::with_capacity(BLOCK_LOG_CACHE_SIZE))),
+            block_blooms: Arc::new(Mutex::new(BloomCache::default())),
+        }
+    }
+
+    /// Keeps filters until they are uninstalled, or until they go unpolled for
+    /// `idle_timeout` if one is given
+    pub fn with_idle_timeout(self, idle_timeout: Option<Duration>) -> Self {
+        FilterManager {
+            idle_timeout,
+            ..self
+        }
+    }
+
+    /// Removes idle filters, at most once every `FILTER_SWEEP_INTERVAL`
+    fn sweep(&self) {
+        let idle_timeout = match self.idle_timeout {
+            Some(idle_timeout) => idle_timeout,
+            None => return,
         };
-        self.filters.lock().unwrap().insert(filter_id, filter_entry)
+        {
+            let mut last_sweep = self.last_sweep.lock().unwrap();
+            if last_sweep.elapsed() < FILTER_SWEEP_INTERVAL {
+                return;
+            }
+            *last_sweep = Instant::now();
+        }
+        match self.store.expire(idle_timeout) {
+            Ok(0) => (),
+            Ok(expired) => info!("Removed {} idle filters", expired),
+            Err(error) => warn!("Failed to remove idle filters: {}", error),
+        }
+    }
+
+    pub fn get_block_logs(&self, block_id: &str) -> Option<BlockLogs> {
+        self.block_logs
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(id, _)| id == block_id)
+            .map(|(_, logs)| logs.clone())
+    }
+
+    pub fn cache_block_logs(&self, block_id: &str, logs: Vec<BlockLog>) -> BlockLogs {
+        let logs = Arc::new(logs);
+        let mut block_logs = self.block_logs.lock().unwrap();
+        // Another request may have cached the same block in the meantime
+        if let Some((_, cached)) = block_logs.iter().find(|(id, _)| id == block_id) {
+            return cached.clone();
+        }
+        if block_logs.len() >= BLOCK_LOG_CACHE_SIZE {
+            block_logs.pop_front();
+        }
+        block_logs.push_back((String::from(block_id), logs.clone()));
+        logs
+    }
+
+    pub fn get_block_bloom(&self, block_id: &str) -> Option<Bloom> {
+        self.block_blooms
+            .lock()
+            .unwrap()
+            .blooms
+            .get(block_id)
+            .cloned()
+    }
+
+    pub fn cache_block_bloom(&self, block_id: &str, bloom: Bloom) {
+        let mut cache = self.block_blooms.lock().unwrap();
+        if cache.blooms.contains_key(block_id) {
+            return;
+        }
+        if cache.order.len() >= BLOCK_BLOOM_CACHE_SIZE {
+            if let Some(oldest) = cache.order.pop_front() {
+                cache.blooms.remove(&oldest);
+            }
+        }
+        cache.order.push_back(String::from(block_id));
+        cache.blooms.insert(String::from(block_id), bloom);
+    }
+
+    /// Installs a new filter, or returns `None` if `max_filters` are already installed
+    pub fn new_filter(&self, filter: Filter, block_num: u64) -> Result<Option<FilterId>, String> {
+        self.sweep();
+        self.store.insert(
+            FilterEntry {
+                filter,
+                last_block_sent: block_num,
+            },
+            self.max_filters,
+        )
+    }
+
+    pub fn remove_filter(&self, filter_id: FilterId) -> Result<Option<FilterEntry>, String> {
+        self.store.remove(filter_id)
+    }
+
+    pub fn get_filter(&self, filter_id: FilterId) -> Result<Option<FilterEntry>, String> {
+        self.sweep();
+        self.store.get(filter_id)
+    }
+
+    pub fn update_latest_block(&self, filter_id: FilterId, block_num: u64) -> Result<bool, String> {
+        self.store.update_latest_block(filter_id, block_num)
+    }
+
+    pub fn count(&self) -> Result<usize, String> {
+        self.store.count()
     }
 }
 
@@ -244,12 +540,77 @@ pub enum Filter {
     Block,
     Transaction,
     Log(LogFilter),
+    Permissions,
+}
+
+impl Filter {
+    /// Encodes the filter for stores that keep filters outside the process
+    #[cfg(feature = "postgres")]
+    pub fn to_value(&self) -> Value {
+        let mut map = Map::new();
+        let kind = match *self {
+            Filter::Block => "block",
+            Filter::Transaction => "transaction",
+            Filter::Permissions => "permissions",
+            Filter::Log(ref filter) => {
+                if let Some(from_block) = filter.from_block {
+                    map.insert(
+                        String::from("fromBlock"),
+                        transform::num_to_hex(&from_block),
+                    );
+                }
+                if let Some(to_block) = filter.to_block {
+                    map.insert(String::from("toBlock"), transform::num_to_hex(&to_block));
+                }
+                if let Some(ref block_hash) = filter.block_hash {
+                    map.insert(String::from("blockHash"), transform::hex_prefix(block_hash));
+                }
+                map.insert(
+                    String::from("address"),
+                    Value::Array(
+                        filter
+                            .addresses
+                            .iter()
+                            .map(|address| transform::address_value(address))
+                            .collect(),
+                    ),
+                );
+                map.insert(
+                    String::from("topics"),
+                    Value::Array(filter.topics.iter().map(TopicFilter::to_value).collect()),
+                );
+                "log"
+            }
+        };
+        map.insert(String::from("type"), Value::String(String::from(kind)));
+        Value::Object(map)
+    }
+
+    /// Decodes a filter encoded by `to_value`
+    #[cfg(feature = "postgres")]
+    pub fn from_value(value: &Value) -> Option<Self> {
+        let map = value.as_object()?;
+        match map.get("type")?.as_str()? {
+            "block" => Some(Filter::Block),
+            "transaction" => Some(Filter::Transaction),
+            "permissions" => Some(Filter::Permissions),
+            "log" => LogFilter::from_map(map).ok().map(Filter::Log),
+            _ => None,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::TopicFilter;
+    use super::{
+        Filter, FilterEntry, FilterStore, LogCursor, LogFilter, MemoryFilterStore, TopicFilter,
+    };
+    use bloom::Bloom;
     use jsonrpc_core::Value;
+    use serde_json;
+    use std::thread;
+    use std::time::Duration;
+    use transactions::SethLog;
 
     #[test]
     fn parse_topics() {
@@ -272,4 +633,77 @@ mod tests {
             .unwrap()
         );
     }
+
+    #[test]
+    fn parse_log_filters() {
+        let parse = |json: &str| LogFilter::from_map(&serde_json::from_str(json).unwrap());
+
+        let filter = parse(r#"{"fromBlock": "earliest", "toBlock": "0x10"}"#).unwrap();
+        assert_eq!((Some(0), Some(16)), (filter.from_block, filter.to_block));
+        assert!(filter.contains_address("12"));
+
+        let filter = parse(r#"{"blockHash": "0xab", "address": ["0x12", "0x34"]}"#).unwrap();
+        assert_eq!(Some(String::from("ab")), filter.block_hash);
+        assert_eq!((None, None), (filter.from_block, filter.to_block));
+        assert!(filter.contains_address("34"));
+        assert!(!filter.contains_address("56"));
+
+        assert!(parse(r#"{"blockHash": "0xab", "fromBlock": "latest"}"#).is_err());
+    }
+
+    #[test]
+    fn match_log_filters_against_blooms() {
+        let parse = |json: &str| LogFilter::from_map(&serde_json::from_str(json).unwrap()).unwrap();
+        let log = SethLog {
+            address: String::from("12"),
+            topics: vec![String::from("ab"), String::from("cd")],
+            data: String::new(),
+        };
+        let bloom = vec![&log].into_iter().collect::<Bloom>();
+
+        assert!(parse("{}").may_match(&bloom));
+        assert!(
+            parse(r#"{"address": ["0x34", "0x12"], "topics": [null, "0xcd"]}"#).may_match(&bloom)
+        );
+        assert!(parse(r#"{"topics": [["0xef", "0xab"]]}"#).may_match(&bloom));
+        assert!(!parse(r#"{"address": "0x34"}"#).may_match(&bloom));
+        assert!(!parse(r#"{"address": "0x12", "topics": ["0xef"]}"#).may_match(&bloom));
+        assert!(!parse(r#"{"topics": ["0xab"]}"#).may_match(&Bloom::new()));
+    }
+
+    #[test]
+    fn encode_log_cursors() {
+        let cursor = LogCursor {
+            block_num: 300,
+            log_idx: 2,
+            block_id: String::from("ab12"),
+        };
+        let value = cursor.to_value();
+        assert_eq!(
+            Value::String(String::from("0x000000000000012c00000002ab12")),
+            value
+        );
+        assert_eq!(cursor, LogCursor::from_value(&value).unwrap());
+
+        let parse = |s: &str| LogCursor::from_value(&Value::String(String::from(s)));
+        assert!(parse("0x000000000000012c00000002").is_err());
+        assert!(parse("0x000000000000012c0000000zab12").is_err());
+    }
+
+    #[test]
+    fn expire_idle_filters() {
+        let store = MemoryFilterStore::new();
+        let entry = || FilterEntry {
+            filter: Filter::Block,
+            last_block_sent: 0,
+        };
+        let idle = store.insert(entry(), 2).unwrap().unwrap();
+        let polled = store.insert(entry(), 2).unwrap().unwrap();
+
+        thread::sleep(Duration::from_millis(50));
+        assert!(store.get(polled).unwrap().is_some());
+        assert_eq!(Ok(1), store.expire(Duration::from_millis(40)));
+        assert!(store.get(idle).unwrap().is_none());
+        assert!(store.get(polled).unwrap().is_some());
+    }
 }
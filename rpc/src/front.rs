@@ -0,0 +1,495 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! A front for the JSON-RPC HTTP server, for what jsonrpc-http-server can't do
+//! itself: serving HTTPS, and limiting how fast each client IP sends requests, which
+//! it never learns. With either, seth-rpc accepts connections on the bind address
+//! itself and passes each request they carry on to the HTTP server, which then
+//! listens on localhost only, and its response back.
+
+use calls::error;
+use jsonrpc_core::{Response, Version};
+#[cfg(feature = "tls")]
+use native_tls::{Identity, TlsAcceptor};
+use serde_json;
+use std::collections::HashMap;
+#[cfg(feature = "tls")]
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+#[cfg(feature = "tls")]
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a client has to finish the TLS handshake
+#[cfg(feature = "tls")]
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a connection may go without sending anything before it is closed
+const IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How many connections are served at once, each on its own thread
+const MAX_CONNECTIONS: usize = 1024;
+
+/// The most bytes the request line and headers, or status line and headers, of a
+/// message may take
+const MAX_HEAD_SIZE: u64 = 64 * 1024;
+
+/// How many client IPs are tracked before those with full buckets are forgotten
+const MAX_BUCKETS: usize = 100_000;
+
+/// Loads the certificate chain and private key, both PEM encoded, that connections
+/// are accepted with
+#[cfg(feature = "tls")]
+pub fn load_acceptor(cert_path: &Path, key_path: &Path) -> Result<TlsAcceptor, String> {
+    let read = |path: &Path| {
+        fs::read(path).map_err(|err| format!("Failed to read {}: {}", path.display(), err))
+    };
+    let identity = Identity::from_pkcs8(&read(cert_path)?, &read(key_path)?)
+        .map_err(|err| format!("Invalid certificate or key: {}", err))?;
+    TlsAcceptor::new(identity).map_err(|err| format!("Failed to set up TLS: {}", err))
+}
+
+/// Token buckets of the requests each client IP may send, which fill at `rate`
+/// requests a second up to `burst`
+#[derive(Clone)]
+pub struct RateLimiter {
+    limits: Arc<Mutex<(f64, f64)>>,
+    buckets: Arc<Mutex<HashMap<IpAddr, (f64, Instant)>>>,
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64, burst: f64) -> Self {
+        RateLimiter {
+            limits: Arc::new(Mutex::new((rate, burst))),
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Changes the rate and burst of every clone, for the requests they take next
+    pub fn set_limits(&self, rate: f64, burst: f64) {
+        *self.limits.lock().unwrap() = (rate, burst);
+    }
+
+    /// Takes a request from the bucket of `ip`, or returns how long until it has one
+    fn take(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let (rate, burst) = *self.limits.lock().unwrap();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(&ip) {
+            buckets.retain(|_, &mut (tokens, filled)| {
+                tokens + now.duration_since(filled).as_secs_f64() * rate < burst
+            });
+        }
+        let bucket = buckets.entry(ip).or_insert((burst, now));
+        let tokens = (bucket.0 + now.duration_since(bucket.1).as_secs_f64() * rate).min(burst);
+        if tokens >= 1.0 {
+            *bucket = (tokens - 1.0, now);
+            Ok(())
+        } else {
+            *bucket = (tokens, now);
+            Err(Duration::from_secs_f64((1.0 - tokens) / rate))
+        }
+    }
+}
+
+/// What the front does with the connections it accepts
+#[derive(Clone, Default)]
+pub struct Front {
+    #[cfg(feature = "tls")]
+    pub tls: Option<TlsAcceptor>,
+    pub limiter: Option<RateLimiter>,
+}
+
+impl Front {
+    /// Whether there is anything for the front to do that the HTTP server can't
+    pub fn is_needed(&self) -> bool {
+        self.serves_tls() || self.limiter.is_some()
+    }
+
+    /// Whether connections are accepted with TLS
+    #[cfg(feature = "tls")]
+    pub fn serves_tls(&self) -> bool {
+        self.tls.is_some()
+    }
+
+    #[cfg(not(feature = "tls"))]
+    pub fn serves_tls(&self) -> bool {
+        false
+    }
+
+    /// Accepts connections on `endpoint` in the background, relaying the requests
+    /// they carry to the plain HTTP server at `upstream`
+    pub fn serve(
+        &self,
+        endpoint: &SocketAddr,
+        upstream: SocketAddr,
+    ) -> io::Result<thread::JoinHandle<()>> {
+        let listener = TcpListener::bind(endpoint)?;
+        let front = self.clone();
+        let connections = Arc::new(AtomicUsize::new(0));
+        Ok(thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        warn!("Failed to accept connection: {}", err);
+                        continue;
+                    }
+                };
+                if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                    connections.fetch_sub(1, Ordering::SeqCst);
+                    warn!("Refusing connection, {} are open", MAX_CONNECTIONS);
+                    continue;
+                }
+                let front = front.clone();
+                let connections = connections.clone();
+                thread::spawn(move || {
+                    if let Err(err) = front.accept(stream, upstream) {
+                        debug!("Closed connection: {}", err);
+                    }
+                    connections.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        }))
+    }
+
+    fn accept(&self, stream: TcpStream, upstream: SocketAddr) -> io::Result<()> {
+        let ip = stream.peer_addr()?.ip();
+        let server = TcpStream::connect(upstream)?;
+        #[cfg(feature = "tls")]
+        {
+            if let Some(ref acceptor) = self.tls {
+                stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+                let client = acceptor
+                    .accept(stream)
+                    .map_err(|err| io::Error::other(err.to_string()))?;
+                client.get_ref().set_read_timeout(Some(IDLE_TIMEOUT))?;
+                return self.relay(client, ip, server);
+            }
+        }
+        stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+        self.relay(stream, ip, server)
+    }
+
+    /// Passes the requests of a connection to the server one at a time, each
+    /// followed by the server's response, until either side closes the connection
+    fn relay<S: Read + Write>(&self, client: S, ip: IpAddr, server: TcpStream) -> io::Result<()> {
+        let mut client = BufReader::new(client);
+        let mut server = BufReader::new(server);
+
+        loop {
+            let request = match read_head(&mut client)? {
+                Some(request) => request,
+                None => return Ok(()),
+            };
+            let expects_continue = request
+                .header("expect")
+                .is_some_and(|expect| expect.eq_ignore_ascii_case("100-continue"));
+            let request_body = match request.body() {
+                Body::UntilClose => Body::Empty,
+                body => body,
+            };
+
+            let limited = self
+                .limiter
+                .as_ref()
+                .and_then(|limiter| limiter.take(ip, Instant::now()).err());
+            if let Some(retry_after) = limited {
+                // A client waiting to be told to send its body is refused without it
+                if expects_continue {
+                    return too_many_requests(client.get_mut(), retry_after, true);
+                }
+                copy_body(&mut client, &mut io::sink(), request_body)?;
+                too_many_requests(client.get_mut(), retry_after, request.closes())?;
+                if request.closes() {
+                    return Ok(());
+                }
+                continue;
+            }
+
+            // The body is read before the server sees the request, so the client is
+            // told to go on without waiting for the server's answer to `Expect`
+            if expects_continue {
+                client
+                    .get_mut()
+                    .write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+            }
+            request.write(server.get_mut(), &["expect"])?;
+            copy_body(&mut client, server.get_mut(), request_body)?;
+
+            let response = read_head(&mut server)?.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::UnexpectedEof, "Server closed connection")
+            })?;
+            response.write(client.get_mut(), &[])?;
+            let status = response.status();
+            let response_body = if request.method() == "HEAD"
+                || (100..200).contains(&status)
+                || status == 204
+                || status == 304
+            {
+                Body::Empty
+            } else {
+                response.body()
+            };
+            copy_body(&mut server, client.get_mut(), response_body)?;
+            client.get_mut().flush()?;
+
+            if response_body == Body::UntilClose || request.closes() || response.closes() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Answers a request that is over its client's rate limit with HTTP 429 and a
+/// JSON-RPC error
+fn too_many_requests<W: Write>(out: &mut W, retry_after: Duration, close: bool) -> io::Result<()> {
+    let retry_after = retry_after.as_secs() + 1;
+    let body = serde_json::to_string(&Response::from(
+        error::rate_limited(retry_after),
+        Some(Version::V2),
+    ))
+    .map_err(|err| io::Error::other(err.to_string()))?;
+    write!(
+        out,
+        "HTTP/1.1 429 Too Many Requests\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nRetry-After: {}\r\n{}\r\n{}",
+        body.len(),
+        retry_after,
+        if close { "Connection: close\r\n" } else { "" },
+        body
+    )?;
+    out.flush()
+}
+
+/// How the body of a message is delimited
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Body {
+    Empty,
+    Length(u64),
+    Chunked,
+    UntilClose,
+}
+
+/// The start line and headers of a request or response
+#[derive(Debug)]
+struct Head {
+    start: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Head {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn method(&self) -> &str {
+        self.start.split(' ').next().unwrap_or("")
+    }
+
+    fn status(&self) -> u16 {
+        self.start
+            .split(' ')
+            .nth(1)
+            .and_then(|status| status.parse().ok())
+            .unwrap_or(0)
+    }
+
+    fn body(&self) -> Body {
+        let chunked = self
+            .header("transfer-encoding")
+            .is_some_and(|encoding| encoding.to_ascii_lowercase().contains("chunked"));
+        if chunked {
+            return Body::Chunked;
+        }
+        match self.header("content-length").map(|length| length.parse()) {
+            Some(Ok(length)) => Body::Length(length),
+            _ => Body::UntilClose,
+        }
+    }
+
+    fn closes(&self) -> bool {
+        self.header("connection")
+            .is_some_and(|connection| connection.eq_ignore_ascii_case("close"))
+            || self.start.contains("HTTP/1.0")
+    }
+
+    /// Writes the head, leaving out the headers named in `skip`
+    fn write<W: Write>(&self, out: &mut W, skip: &[&str]) -> io::Result<()> {
+        let mut head = format!("{}\r\n", self.start);
+        for (name, value) in &self.headers {
+            if !skip.iter().any(|skip| name.eq_ignore_ascii_case(skip)) {
+                head.push_str(&format!("{}: {}\r\n", name, value));
+            }
+        }
+        head.push_str("\r\n");
+        out.write_all(head.as_bytes())
+    }
+}
+
+/// Reads the head of the next message, or returns `None` if the connection was
+/// closed before it started
+fn read_head<R: BufRead>(reader: &mut R) -> io::Result<Option<Head>> {
+    let mut lines = Vec::new();
+    let mut size = 0;
+    loop {
+        let mut line = String::new();
+        let read = reader
+            .by_ref()
+            .take(MAX_HEAD_SIZE - size)
+            .read_line(&mut line)?;
+        size += read as u64;
+        if read == 0 {
+            if lines.is_empty() {
+                return Ok(None);
+            }
+            return Err(invalid_data("Message head is incomplete or too large"));
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            // Empty lines before a request are ignored
+            if lines.is_empty() {
+                continue;
+            }
+            break;
+        }
+        lines.push(String::from(line));
+    }
+
+    let start = lines.remove(0);
+    let headers = lines
+        .into_iter()
+        .map(|line| {
+            let colon = line
+                .find(':')
+                .ok_or_else(|| invalid_data("Malformed header"))?;
+            Ok((
+                String::from(line[..colon].trim()),
+                String::from(line[colon + 1..].trim()),
+            ))
+        })
+        .collect::<io::Result<_>>()?;
+    Ok(Some(Head { start, headers }))
+}
+
+fn copy_body<R: BufRead, W: Write>(reader: &mut R, writer: &mut W, body: Body) -> io::Result<()> {
+    match body {
+        Body::Empty => Ok(()),
+        Body::Length(length) => copy_exactly(reader, writer, length),
+        Body::UntilClose => io::copy(reader, writer).map(|_| ()),
+        Body::Chunked => loop {
+            let mut line = String::new();
+            reader.by_ref().take(MAX_HEAD_SIZE).read_line(&mut line)?;
+            writer.write_all(line.as_bytes())?;
+            let size = line.trim().split(';').next().unwrap_or("");
+            let size =
+                u64::from_str_radix(size, 16).map_err(|_| invalid_data("Malformed chunk size"))?;
+            if size == 0 {
+                // Trailers end with an empty line, like headers
+                loop {
+                    let mut trailer = String::new();
+                    reader
+                        .by_ref()
+                        .take(MAX_HEAD_SIZE)
+                        .read_line(&mut trailer)?;
+                    writer.write_all(trailer.as_bytes())?;
+                    if trailer.trim().is_empty() {
+                        return Ok(());
+                    }
+                }
+            }
+            // Each chunk is followed by a line break
+            copy_exactly(reader, writer, size + 2)?;
+        },
+    }
+}
+
+fn copy_exactly<R: Read, W: Write>(reader: &mut R, writer: &mut W, length: u64) -> io::Result<()> {
+    let copied = io::copy(&mut reader.take(length), writer)?;
+    if copied < length {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Connection closed mid-message",
+        ));
+    }
+    Ok(())
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_each_ip_to_its_rate() {
+        let limiter = RateLimiter::new(2.0, 3.0);
+        let (first, second): (IpAddr, IpAddr) =
+            ("10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap());
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.take(first, start).is_ok());
+        }
+        assert_eq!(limiter.take(first, start), Err(Duration::from_millis(500)));
+        assert!(limiter.take(second, start).is_ok());
+
+        // Half a second later the bucket has filled with one request
+        let later = start + Duration::from_millis(500);
+        assert!(limiter.take(first, later).is_ok());
+        assert!(limiter.take(first, later).is_err());
+
+        // Clones share the limits, which a reload may change
+        limiter.clone().set_limits(4.0, 3.0);
+        assert_eq!(limiter.take(first, later), Err(Duration::from_millis(250)));
+    }
+
+    #[test]
+    fn relays_lengths_and_chunks() {
+        let mut messages: &[u8] =
+            b"\r\nPOST / HTTP/1.1\r\nContent-Length: 4\r\nExpect: 100-continue\r\n\r\n\
+            {}\r\nHTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            3\r\nabc\r\n0\r\n\r\nrest";
+
+        let request = read_head(&mut messages).unwrap().unwrap();
+        assert_eq!(request.method(), "POST");
+        assert_eq!(request.header("content-length"), Some("4"));
+        let mut out = Vec::new();
+        request.write(&mut out, &["expect"]).unwrap();
+        copy_body(&mut messages, &mut out, request.body()).unwrap();
+        assert_eq!(
+            out,
+            b"POST / HTTP/1.1\r\nContent-Length: 4\r\n\r\n{}\r\n".to_vec()
+        );
+
+        let response = read_head(&mut messages).unwrap().unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body(), Body::Chunked);
+        let mut out = Vec::new();
+        copy_body(&mut messages, &mut out, response.body()).unwrap();
+        assert_eq!(out, b"3\r\nabc\r\n0\r\n\r\n".to_vec());
+        assert_eq!(messages, b"rest");
+        assert!(!response.closes());
+    }
+}
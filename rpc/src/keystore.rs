@@ -0,0 +1,311 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Keys encrypted in the version 3 JSON keystore format of Web3 Secret Storage,
+//! which geth and MetaMask import and export accounts in. The key is encrypted
+//! with AES-128-CTR by a key derived from the password with scrypt or PBKDF2.
+
+use crypto::aes::{self, KeySize};
+use crypto::hmac::Hmac;
+use crypto::pbkdf2::pbkdf2;
+use crypto::scrypt::{scrypt, ScryptParams};
+use crypto::sha2::Sha256;
+use crypto_backend;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use secp256k1::key::PublicKey;
+use secp256k1::Secp256k1;
+use serde_json::{Map, Value};
+use std::mem;
+use transform;
+use uuid;
+
+/// The scrypt parameters geth encrypts keys with
+const SCRYPT_LOG_N: u8 = 18;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+/// The largest work factors accepted, well above what wallets use, so that a
+/// keystore can't make the server spend minutes or gigabytes decrypting it
+const MAX_SCRYPT_LOG_N: u32 = 20;
+const MAX_SCRYPT_R: u32 = 32;
+const MAX_SCRYPT_P: u32 = 16;
+const MAX_PBKDF2_ROUNDS: u32 = 1 << 22;
+
+/// Returns the private key a keystore holds, checking the password against its MAC
+pub fn decrypt(keystore: &Value, password: &str) -> Result<Vec<u8>, String> {
+    if keystore.get("version").and_then(Value::as_u64) != Some(3) {
+        return Err(String::from("Only version 3 keystores are supported"));
+    }
+    let crypto = keystore
+        .get("crypto")
+        .or_else(|| keystore.get("Crypto"))
+        .and_then(Value::as_object)
+        .ok_or_else(|| String::from("Keystore has no `crypto` object"))?;
+
+    let cipher = get_str(crypto, "cipher")?;
+    if cipher != "aes-128-ctr" {
+        return Err(format!("Unsupported cipher `{}`", cipher));
+    }
+    let ciphertext = get_hex(crypto, "ciphertext")?;
+    let iv = crypto
+        .get("cipherparams")
+        .and_then(Value::as_object)
+        .ok_or_else(|| String::from("Keystore has no `cipherparams`"))
+        .and_then(|params| get_hex(params, "iv"))?;
+    if iv.len() != 16 {
+        return Err(String::from("`iv` must be 16 bytes"));
+    }
+    let mac = get_hex(crypto, "mac")?;
+
+    let kdf_params = crypto
+        .get("kdfparams")
+        .and_then(Value::as_object)
+        .ok_or_else(|| String::from("Keystore has no `kdfparams`"))?;
+    let derived_key = derive_key(get_str(crypto, "kdf")?, kdf_params, password)?;
+
+    if keystore_mac(&derived_key, &ciphertext)[..] != mac[..] {
+        return Err(String::from("Wrong password"));
+    }
+    Ok(aes_128_ctr(&derived_key[..16], &iv, &ciphertext))
+}
+
+/// Encrypts a private key into a keystore, with the scrypt parameters geth uses
+pub fn encrypt(private_key: &[u8], public_key: &[u8], password: &str) -> Result<Value, String> {
+    encrypt_with(
+        private_key,
+        public_key,
+        password,
+        SCRYPT_LOG_N,
+        SCRYPT_R,
+        SCRYPT_P,
+    )
+}
+
+fn encrypt_with(
+    private_key: &[u8],
+    public_key: &[u8],
+    password: &str,
+    log_n: u8,
+    r: u32,
+    p: u32,
+) -> Result<Value, String> {
+    let mut salt = [0; 32];
+    let mut iv = [0; 16];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut iv);
+
+    let mut derived_key = [0; 32];
+    scrypt(
+        password.as_bytes(),
+        &salt,
+        &ScryptParams::new(log_n, r, p),
+        &mut derived_key,
+    );
+    let ciphertext = aes_128_ctr(&derived_key[..16], &iv, private_key);
+
+    let kdf_params = object(vec![
+        ("dklen", Value::from(32)),
+        ("n", Value::from(1u64 << log_n)),
+        ("r", Value::from(r)),
+        ("p", Value::from(p)),
+        ("salt", Value::from(transform::bytes_to_hex_str(&salt))),
+    ]);
+    let crypto = object(vec![
+        ("cipher", Value::from("aes-128-ctr")),
+        (
+            "cipherparams",
+            object(vec![("iv", Value::from(transform::bytes_to_hex_str(&iv)))]),
+        ),
+        (
+            "ciphertext",
+            Value::from(transform::bytes_to_hex_str(&ciphertext)),
+        ),
+        ("kdf", Value::from("scrypt")),
+        ("kdfparams", kdf_params),
+        (
+            "mac",
+            Value::from(transform::bytes_to_hex_str(&keystore_mac(
+                &derived_key,
+                &ciphertext,
+            ))),
+        ),
+    ]);
+    Ok(object(vec![
+        ("version", Value::from(3)),
+        ("id", Value::from(uuid::Uuid::new_v4().to_string())),
+        ("address", Value::from(ethereum_address(public_key)?)),
+        ("crypto", crypto),
+    ]))
+}
+
+fn derive_key(kdf: &str, params: &Map<String, Value>, password: &str) -> Result<Vec<u8>, String> {
+    if get_u32(params, "dklen")? != 32 {
+        return Err(String::from("`dklen` must be 32"));
+    }
+    let salt = get_hex(params, "salt")?;
+    let mut derived_key = vec![0; 32];
+
+    match kdf {
+        "scrypt" => {
+            let n = get_u32(params, "n")?;
+            let r = get_u32(params, "r")?;
+            let p = get_u32(params, "p")?;
+            if n < 2 || !n.is_power_of_two() || n.trailing_zeros() > MAX_SCRYPT_LOG_N {
+                return Err(format!("Unsupported scrypt `n` {}", n));
+            }
+            if r == 0 || r > MAX_SCRYPT_R || p == 0 || p > MAX_SCRYPT_P {
+                return Err(format!("Unsupported scrypt `r` {} or `p` {}", r, p));
+            }
+            let params = ScryptParams::new(n.trailing_zeros() as u8, r, p);
+            scrypt(password.as_bytes(), &salt, &params, &mut derived_key);
+        }
+        "pbkdf2" => {
+            if get_str(params, "prf")? != "hmac-sha256" {
+                return Err(String::from("Only the hmac-sha256 `prf` is supported"));
+            }
+            let rounds = get_u32(params, "c")?;
+            if rounds == 0 || rounds > MAX_PBKDF2_ROUNDS {
+                return Err(format!("Unsupported pbkdf2 `c` {}", rounds));
+            }
+            let mut mac = Hmac::new(Sha256::new(), password.as_bytes());
+            pbkdf2(&mut mac, &salt, rounds, &mut derived_key);
+        }
+        kdf => return Err(format!("Unsupported kdf `{}`", kdf)),
+    }
+    Ok(derived_key)
+}
+
+/// The MAC the password is checked with, which is the Keccak-256 hash of the second
+/// half of the derived key and the ciphertext
+fn keystore_mac(derived_key: &[u8], ciphertext: &[u8]) -> [u8; 32] {
+    let mut data = derived_key[16..32].to_vec();
+    data.extend_from_slice(ciphertext);
+    crypto_backend::keccak256(&data)
+}
+
+fn aes_128_ctr(key: &[u8], iv: &[u8], input: &[u8]) -> Vec<u8> {
+    let mut output = vec![0; input.len()];
+    aes::ctr(KeySize::KeySize128, key, iv).process(input, &mut output);
+    output
+}
+
+/// Returns the Ethereum address of a public key, which other wallets expect in the
+/// keystore, rather than its seth address. See `accounts::compressed_public_key` for
+/// why the serialized key is forgotten.
+fn ethereum_address(public_key: &[u8]) -> Result<String, String> {
+    let secp = Secp256k1::new();
+    let public_key = PublicKey::from_slice(&secp, public_key)
+        .map_err(|error| format!("Invalid public key: {:?}", error))?;
+    let serialized = public_key.serialize_vec(&secp, false);
+    let bytes = serialized.to_vec();
+    mem::forget(serialized);
+    Ok(transform::bytes_to_hex_str(
+        &crypto_backend::keccak256(&bytes[1..])[12..],
+    ))
+}
+
+fn object(fields: Vec<(&str, Value)>) -> Value {
+    Value::Object(
+        fields
+            .into_iter()
+            .map(|(key, value)| (String::from(key), value))
+            .collect(),
+    )
+}
+
+fn get_str<'a>(map: &'a Map<String, Value>, key: &str) -> Result<&'a str, String> {
+    map.get(key)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("Keystore has no `{}`", key))
+}
+
+fn get_hex(map: &Map<String, Value>, key: &str) -> Result<Vec<u8>, String> {
+    transform::hex_str_to_bytes(get_str(map, key)?.trim_start_matches("0x"))
+        .ok_or_else(|| format!("`{}` is invalid hex", key))
+}
+
+fn get_u32(map: &Map<String, Value>, key: &str) -> Result<u32, String> {
+    map.get(key)
+        .and_then(Value::as_u64)
+        .filter(|&value| value <= u64::from(u32::MAX))
+        .map(|value| value as u32)
+        .ok_or_else(|| format!("Keystore has no `{}`", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use accounts::compressed_public_key;
+    use serde_json;
+
+    #[test]
+    fn round_trips_keys() {
+        let private_key = [0x46; 32];
+        let secp = Secp256k1::new();
+        let secret = secp256k1::key::SecretKey::from_slice(&secp, &private_key).unwrap();
+        let public_key =
+            compressed_public_key(&secp, &PublicKey::from_secret_key(&secp, &secret).unwrap());
+
+        let keystore = encrypt_with(&private_key, &public_key, "password", 4, 8, 1).unwrap();
+        // The key of the EIP-155 example
+        assert_eq!(
+            keystore["address"],
+            Value::from("9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f")
+        );
+        assert_eq!(
+            decrypt(&keystore, "password").unwrap(),
+            private_key.to_vec()
+        );
+        assert_eq!(
+            decrypt(&keystore, "wrong"),
+            Err(String::from("Wrong password"))
+        );
+    }
+
+    #[test]
+    fn decrypts_pbkdf2_keystores() {
+        // The PBKDF2 test vector of Web3 Secret Storage
+        let mut keystore: Value = serde_json::from_str(
+            r#"{
+                "version": 3,
+                "id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+                "crypto": {
+                    "cipher": "aes-128-ctr",
+                    "cipherparams": { "iv": "6087dab2f9fdbbfaddc31a909735c1e6" },
+                    "ciphertext": "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+                    "kdf": "pbkdf2",
+                    "kdfparams": {
+                        "c": 262144,
+                        "dklen": 32,
+                        "prf": "hmac-sha256",
+                        "salt": "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"
+                    },
+                    "mac": "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            transform::bytes_to_hex_str(&decrypt(&keystore, "testpassword").unwrap()),
+            "7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d"
+        );
+
+        keystore["crypto"]["kdfparams"]["c"] = Value::from(u64::from(MAX_PBKDF2_ROUNDS) + 1);
+        assert!(decrypt(&keystore, "testpassword").is_err());
+    }
+}
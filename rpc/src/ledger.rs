@@ -0,0 +1,317 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Accounts whose keys stay on a Ledger device, which signs with the APDUs of its
+//! Ethereum app. The device is read and written through its hidraw node, in the
+//! 64 byte HID reports the Ledger transport frames APDUs in. The keys are those
+//! of the BIP-44 path of Ethereum accounts, m/44'/60'/0'/0/i, and the accounts'
+//! seth addresses are derived from the compressed public keys as any other's are.
+
+use accounts::{compressed_public_key, public_key_to_address};
+use mnemonic::ETHEREUM_PATH;
+use secp256k1::key::PublicKey;
+use secp256k1::Secp256k1;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::sync::Mutex;
+
+/// The size of the HID reports APDUs are framed in
+const REPORT_SIZE: usize = 64;
+
+/// The channel and tag of the frames of APDUs
+const CHANNEL: u16 = 0x0101;
+const TAG_APDU: u8 = 0x05;
+
+/// The class and instructions of the Ethereum app
+const CLA: u8 = 0xe0;
+const INS_GET_ADDRESS: u8 = 0x02;
+const INS_SIGN_TX: u8 = 0x04;
+const INS_SIGN_PERSONAL_MESSAGE: u8 = 0x08;
+
+/// The most data an APDU carries, longer data being sent in chunks
+const MAX_CHUNK: usize = 255;
+
+/// The status word of an APDU that succeeded
+const SW_OK: u16 = 0x9000;
+
+/// An account whose key the device derives along the path
+#[derive(Clone, Debug)]
+pub struct LedgerAccount {
+    path: Vec<u32>,
+    address: String,
+}
+
+impl LedgerAccount {
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+}
+
+/// A Ledger device, one APDU being exchanged with it at a time
+pub struct Ledger {
+    device: Mutex<File>,
+    accounts: Vec<LedgerAccount>,
+}
+
+impl Ledger {
+    /// Opens the hidraw node of a device whose Ethereum app is open, and reads the
+    /// addresses of its first `count` accounts
+    pub fn open(path: &str, count: u32) -> Result<Ledger, String> {
+        let device = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(|error| format!("Couldn't open Ledger at {}: {}", path, error))?;
+        let mut ledger = Ledger {
+            device: Mutex::new(device),
+            accounts: Vec::new(),
+        };
+        ledger.accounts = (0..count)
+            .map(|index| {
+                let mut path = ETHEREUM_PATH.to_vec();
+                path.push(index);
+                let address = ledger.get_address(&path)?;
+                Ok(LedgerAccount { path, address })
+            })
+            .collect::<Result<Vec<LedgerAccount>, String>>()?;
+        Ok(ledger)
+    }
+
+    pub fn accounts(&self) -> &[LedgerAccount] {
+        &self.accounts
+    }
+
+    /// Returns the account of the device with the given address, if it has one
+    pub fn account(&self, address: &str) -> Option<&LedgerAccount> {
+        self.accounts
+            .iter()
+            .find(|account| account.address.eq_ignore_ascii_case(address))
+    }
+
+    /// Has the device sign the RLP of an unsigned transaction once it is confirmed
+    /// on the device, returning its `v`, `r` and `s`. Legacy transactions get the
+    /// low byte of an EIP-155 `v` back.
+    pub fn sign_transaction(
+        &self,
+        account: &LedgerAccount,
+        unsigned: &[u8],
+    ) -> Result<(u8, Vec<u8>, Vec<u8>), String> {
+        let signature = self.sign(INS_SIGN_TX, &account.path, unsigned)?;
+        Ok((
+            signature[0],
+            signature[1..33].to_vec(),
+            signature[33..].to_vec(),
+        ))
+    }
+
+    /// Has the device sign a message the way `eth_sign` does, once it is confirmed
+    /// on the device, returning the 65 byte signature `r || s || v`
+    pub fn sign_personal_message(
+        &self,
+        account: &LedgerAccount,
+        message: &[u8],
+    ) -> Result<Vec<u8>, String> {
+        let mut data = (message.len() as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(message);
+        let signature = self.sign(INS_SIGN_PERSONAL_MESSAGE, &account.path, &data)?;
+        let mut signed = signature[1..].to_vec();
+        signed.push(signature[0]);
+        Ok(signed)
+    }
+
+    fn get_address(&self, path: &[u32]) -> Result<String, String> {
+        let response = self.exchange(&apdu(INS_GET_ADDRESS, 0x00, &path_data(path)))?;
+        let public_key = response
+            .first()
+            .and_then(|&length| response.get(1..1 + length as usize))
+            .ok_or_else(|| String::from("Malformed address from the Ledger"))?;
+        let secp = Secp256k1::new();
+        let public_key = PublicKey::from_slice(&secp, public_key)
+            .map_err(|_| String::from("Invalid public key from the Ledger"))?;
+        Ok(public_key_to_address(&compressed_public_key(
+            &secp,
+            &public_key,
+        )))
+    }
+
+    /// Sends the path and then the data in as many APDUs as it takes, and returns
+    /// the signature `v || r || s` that answers the last
+    fn sign(&self, instruction: u8, path: &[u32], data: &[u8]) -> Result<Vec<u8>, String> {
+        let mut response = Vec::new();
+        for (index, chunk) in sign_chunks(path, data).iter().enumerate() {
+            let first = if index == 0 { 0x00 } else { 0x80 };
+            response = self.exchange(&apdu(instruction, first, chunk))?;
+        }
+        if response.len() != 65 {
+            return Err(String::from("Malformed signature from the Ledger"));
+        }
+        Ok(response)
+    }
+
+    /// Writes an APDU to the device and returns the data of its answer, failing if
+    /// its status word isn't that of success
+    fn exchange(&self, apdu: &[u8]) -> Result<Vec<u8>, String> {
+        let io_error = |error| format!("Couldn't talk to the Ledger: {}", error);
+        let mut device = self.device.lock().unwrap();
+        for report in frame(apdu) {
+            // hidraw takes the report number first, which is 0 without numbered reports
+            let mut written = vec![0];
+            written.extend_from_slice(&report);
+            device.write_all(&written).map_err(io_error)?;
+        }
+
+        let mut reports = Vec::new();
+        let mut response = None;
+        while response.is_none() {
+            let mut report = [0; REPORT_SIZE];
+            device.read_exact(&mut report).map_err(io_error)?;
+            reports.push(report);
+            response = unframe(&reports)?;
+        }
+        let mut response = response.unwrap_or_default();
+        if response.len() < 2 {
+            return Err(String::from("Malformed answer from the Ledger"));
+        }
+        let status = response.split_off(response.len() - 2);
+        match u16::from_be_bytes([status[0], status[1]]) {
+            SW_OK => Ok(response),
+            status => Err(status_error(status)),
+        }
+    }
+}
+
+/// Describes a status word the Ethereum app failed an APDU with
+fn status_error(status: u16) -> String {
+    match status {
+        0x6985 => String::from("Rejected on the Ledger"),
+        0x6a80 => String::from(
+            "The Ledger refused the data, blind signing may need to be enabled in its \
+             Ethereum app",
+        ),
+        0x5515 => String::from("The Ledger is locked"),
+        0x6d00 | 0x6e00 | 0x6e01 => String::from("The Ethereum app isn't open on the Ledger"),
+        status => format!("The Ledger failed with status {:#06x}", status),
+    }
+}
+
+fn apdu(instruction: u8, p1: u8, data: &[u8]) -> Vec<u8> {
+    let mut apdu = vec![CLA, instruction, p1, 0x00, data.len() as u8];
+    apdu.extend_from_slice(data);
+    apdu
+}
+
+/// The number of steps of a path followed by each of them
+fn path_data(path: &[u32]) -> Vec<u8> {
+    let mut data = vec![path.len() as u8];
+    for step in path {
+        data.extend_from_slice(&step.to_be_bytes());
+    }
+    data
+}
+
+/// Splits what is signed into the data of APDUs, the first starting with the path
+fn sign_chunks(path: &[u32], data: &[u8]) -> Vec<Vec<u8>> {
+    let mut first = path_data(path);
+    let split = data.len().min(MAX_CHUNK - first.len());
+    first.extend_from_slice(&data[..split]);
+    let mut chunks = vec![first];
+    chunks.extend(data[split..].chunks(MAX_CHUNK).map(<[u8]>::to_vec));
+    chunks
+}
+
+/// Splits an APDU into reports of its channel, tag and sequence number, the first
+/// followed by the length of the APDU, padded with zeros
+fn frame(apdu: &[u8]) -> Vec<[u8; REPORT_SIZE]> {
+    let mut data = (apdu.len() as u16).to_be_bytes().to_vec();
+    data.extend_from_slice(apdu);
+    data.chunks(REPORT_SIZE - 5)
+        .enumerate()
+        .map(|(sequence, chunk)| {
+            let mut report = [0; REPORT_SIZE];
+            report[..2].copy_from_slice(&CHANNEL.to_be_bytes());
+            report[2] = TAG_APDU;
+            report[3..5].copy_from_slice(&(sequence as u16).to_be_bytes());
+            report[5..5 + chunk.len()].copy_from_slice(chunk);
+            report
+        })
+        .collect()
+}
+
+/// Joins the reports of an answer into the answer, or returns none if more of its
+/// reports are still to be read
+fn unframe(reports: &[[u8; REPORT_SIZE]]) -> Result<Option<Vec<u8>>, String> {
+    let mut data = Vec::new();
+    for (sequence, report) in reports.iter().enumerate() {
+        if report[..2] != CHANNEL.to_be_bytes()
+            || report[2] != TAG_APDU
+            || report[3..5] != (sequence as u16).to_be_bytes()
+        {
+            return Err(String::from("Malformed report from the Ledger"));
+        }
+        data.extend_from_slice(&report[5..]);
+    }
+    let length = u16::from_be_bytes([data[0], data[1]]) as usize;
+    if data.len() - 2 < length {
+        return Ok(None);
+    }
+    Ok(Some(data[2..2 + length].to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mnemonic::HARDENED;
+
+    #[test]
+    fn frames_apdus_in_reports() {
+        let apdu = apdu(INS_SIGN_TX, 0x00, &[0xab; 100]);
+        let reports = frame(&apdu);
+        assert_eq!(reports.len(), 2);
+        assert_eq!(&reports[0][..7], &[0x01, 0x01, 0x05, 0x00, 0x00, 0x00, 105]);
+        assert_eq!(&reports[0][7..12], &[CLA, INS_SIGN_TX, 0x00, 0x00, 100]);
+        assert_eq!(&reports[1][..5], &[0x01, 0x01, 0x05, 0x00, 0x01]);
+        assert!(reports[1][5 + 105 + 2 - 59..].iter().all(|&byte| byte == 0));
+
+        // The device answers in reports framed the same way
+        assert_eq!(unframe(&reports[..1]), Ok(None));
+        assert_eq!(unframe(&reports), Ok(Some(apdu)));
+        let mut reordered = reports.clone();
+        reordered.swap(0, 1);
+        assert!(unframe(&reordered).is_err());
+    }
+
+    #[test]
+    fn chunks_signed_data_after_the_path() {
+        let path = [44 | HARDENED, 60 | HARDENED, HARDENED, 0, 0];
+        assert_eq!(
+            path_data(&path),
+            vec![5, 0x80, 0, 0, 44, 0x80, 0, 0, 60, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+
+        let data: Vec<u8> = (0..600).map(|byte| byte as u8).collect();
+        let chunks = sign_chunks(&path, &data);
+        assert_eq!(
+            chunks.iter().map(Vec::len).collect::<Vec<usize>>(),
+            vec![255, 255, 111]
+        );
+        assert_eq!(&chunks[0][..21], &path_data(&path)[..]);
+        let signed: Vec<u8> = chunks.concat().split_off(21);
+        assert_eq!(signed, data);
+
+        assert_eq!(sign_chunks(&path, &[]), vec![path_data(&path)]);
+    }
+}
@@ -0,0 +1,1062 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! An embedded index of the logs of committed blocks, so that `eth_getLogs` over a
+//! large range doesn't fetch every block and its receipts from the validator. The
+//! index follows the chain from the block-commit events of the validators, catching
+//! up on the blocks it missed while the server was down, and removes blocks that
+//! were forked out before indexing the ones that replaced them.
+//!
+//! Operators can also put addresses on watchlists, and the index then keeps the
+//! transactions each watched address sends or is sent, and its balance after each
+//! block it has any in, from when it is watched on.
+
+use calls::logs::read_block_logs;
+use client::{BlockKey, Error as ClientError, ValidatorClient};
+use events::watch_blocks;
+use filters::{BlockLog, LogFilter, TopicFilter};
+use protobuf;
+use sawtooth_sdk::messages::block::{Block, BlockHeader};
+use sawtooth_sdk::messaging::stream::MessageSender;
+use serde_json::{self, Value};
+use sled;
+use std::cmp;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use transactions::{SethLog, Transaction};
+use transform;
+use vm;
+
+/// How many blocks are indexed between progress messages while catching up
+const PROGRESS_INTERVAL: u64 = 1000;
+
+/// How many blocks each thread of a rebuild reads before they are added to the
+/// index and the progress is saved
+const REBUILD_BLOCKS_PER_JOB: u64 = 64;
+
+/// The key in the meta tree that is set while the index is being rebuilt
+const REBUILDING_KEY: &[u8] = b"rebuilding";
+
+/// The position of a log in the chain: the block number and the index of the log
+/// within the block, both big endian so that keys sort in chain order
+type LogKey = [u8; 12];
+
+fn log_key(block_num: u64, log_idx: u32) -> LogKey {
+    let mut key = [0; 12];
+    key[..8].copy_from_slice(&block_num.to_be_bytes());
+    key[8..].copy_from_slice(&log_idx.to_be_bytes());
+    key
+}
+
+/// Reads the block number that a key of the blocks or logs tree starts with
+fn block_num_of_key(key: &[u8]) -> u64 {
+    let mut block_num = [0; 8];
+    block_num.copy_from_slice(&key[..8]);
+    u64::from_be_bytes(block_num)
+}
+
+fn split_log_key(key: &LogKey) -> (u64, u32) {
+    let mut log_idx = [0; 4];
+    log_idx.copy_from_slice(&key[8..]);
+    (block_num_of_key(key), u32::from_be_bytes(log_idx))
+}
+
+/// The key of a log in the address or topic index: what it is looked up by, then
+/// where the log is
+fn secondary_key(prefix: &[u8], key: &LogKey) -> Vec<u8> {
+    let mut secondary = prefix.to_vec();
+    secondary.extend_from_slice(key);
+    secondary
+}
+
+fn topic_prefix(position: usize, topic: &str) -> Vec<u8> {
+    let mut prefix = vec![position as u8];
+    prefix.extend_from_slice(topic.as_bytes());
+    prefix
+}
+
+fn encode_log(log: &BlockLog) -> Vec<u8> {
+    Value::Array(vec![
+        Value::String(log.txn_id.clone()),
+        Value::from(log.txn_idx),
+        Value::String(log.log.address.clone()),
+        Value::Array(log.log.topics.iter().cloned().map(Value::String).collect()),
+        Value::String(log.log.data.clone()),
+    ])
+    .to_string()
+    .into_bytes()
+}
+
+fn decode_log(bytes: &[u8]) -> Option<BlockLog> {
+    let value: Value = serde_json::from_slice(bytes).ok()?;
+    let fields = value.as_array()?;
+    Some(BlockLog {
+        txn_id: String::from(fields.first()?.as_str()?),
+        txn_idx: fields.get(1)?.as_u64()?,
+        log: SethLog {
+            address: String::from(fields.get(2)?.as_str()?),
+            topics: fields
+                .get(3)?
+                .as_array()?
+                .iter()
+                .map(|topic| topic.as_str().map(String::from))
+                .collect::<Option<Vec<String>>>()?,
+            data: String::from(fields.get(4)?.as_str()?),
+        },
+    })
+}
+
+/// The key of a balance of a watched address: the address, then the number of the
+/// block it is the balance after
+fn balance_key(address: &str, block_num: u64) -> Vec<u8> {
+    let mut key = address.as_bytes().to_vec();
+    key.extend_from_slice(&block_num.to_be_bytes());
+    key
+}
+
+/// What a watched address did in a block: the transactions it sent or was sent,
+/// by index in the block, and its balance after the block
+pub struct WatchedActivity {
+    pub address: String,
+    pub balance: i64,
+    pub transactions: Vec<(u32, Value)>,
+}
+
+/// The balances of a watched address by block number, and its transactions
+type WatchedHistory = (Vec<(u64, i64)>, Vec<Value>);
+
+/// A log found in the index
+pub struct IndexedLog {
+    pub block_num: u64,
+    pub block_id: String,
+    pub log_idx: u64,
+    pub log: BlockLog,
+}
+
+/// Keeps the logs of committed blocks on disk, by position in the chain, by the
+/// address of the contract that emitted them and by each of their topics
+#[derive(Clone)]
+pub struct LogIndex {
+    /// The id of each indexed block, by number
+    blocks: sled::Tree,
+    /// The time the transactions of each indexed block ran at, by number
+    timestamps: sled::Tree,
+    logs: sled::Tree,
+    addresses: sled::Tree,
+    topics: sled::Tree,
+
+    /// The addresses on each watchlist, by name
+    watchlists: sled::Tree,
+    /// The transactions of watched addresses, by address and position in the chain
+    watched_transactions: sled::Tree,
+    /// The balances of watched addresses, by address and block number
+    watched_balances: sled::Tree,
+    /// The watched addresses that had any transactions in each block, by number
+    watched_blocks: sled::Tree,
+
+    /// Whether a rebuild was started and hasn't finished
+    meta: sled::Tree,
+
+    /// Held while catching up, so that only one thread updates the index at a time
+    updating: Arc<Mutex<()>>,
+    /// The block the index was at when it started catching up after startup, until
+    /// it has caught up
+    backfilling_from: Arc<Mutex<Option<u64>>>,
+}
+
+impl LogIndex {
+    /// Opens the index in the given directory, creating it if it doesn't exist yet
+    pub fn open(path: &str) -> Result<Self, sled::Error> {
+        LogIndex::from_db(&sled::open(path)?)
+    }
+
+    fn from_db(db: &sled::Db) -> Result<Self, sled::Error> {
+        Ok(LogIndex {
+            blocks: db.open_tree("blocks")?,
+            timestamps: db.open_tree("timestamps")?,
+            logs: db.open_tree("logs")?,
+            addresses: db.open_tree("addresses")?,
+            topics: db.open_tree("topics")?,
+            watchlists: db.open_tree("watchlists")?,
+            watched_transactions: db.open_tree("watched_transactions")?,
+            watched_balances: db.open_tree("watched_balances")?,
+            watched_blocks: db.open_tree("watched_blocks")?,
+            meta: db.open_tree("meta")?,
+            updating: Arc::new(Mutex::new(())),
+            backfilling_from: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// Returns the number and id of the last block indexed
+    pub fn head(&self) -> Result<Option<(u64, String)>, sled::Error> {
+        Ok(self.blocks.last()?.map(|(key, block_id)| {
+            (
+                block_num_of_key(&key),
+                String::from_utf8_lossy(&block_id).into_owned(),
+            )
+        }))
+    }
+
+    /// Indexes the logs of a block and what watched addresses did in it, replacing
+    /// those of any other block at its height
+    fn insert_block(
+        &self,
+        block_num: u64,
+        block_id: &str,
+        timestamp: u64,
+        logs: &[BlockLog],
+        watched: &[WatchedActivity],
+    ) -> Result<(), sled::Error> {
+        self.remove_block(block_num)?;
+        for activity in watched {
+            for (txn_idx, txn) in &activity.transactions {
+                self.watched_transactions.insert(
+                    secondary_key(activity.address.as_bytes(), &log_key(block_num, *txn_idx)),
+                    txn.to_string().into_bytes(),
+                )?;
+            }
+            self.watched_balances.insert(
+                balance_key(&activity.address, block_num),
+                &activity.balance.to_be_bytes()[..],
+            )?;
+        }
+        if !watched.is_empty() {
+            let addresses: Vec<&str> = watched
+                .iter()
+                .map(|activity| activity.address.as_str())
+                .collect();
+            self.watched_blocks.insert(
+                &block_num.to_be_bytes()[..],
+                serde_json::to_vec(&addresses).unwrap_or_default(),
+            )?;
+        }
+        for (log_idx, log) in logs.iter().enumerate() {
+            let key = log_key(block_num, log_idx as u32);
+            self.logs.insert(&key[..], encode_log(log))?;
+            self.addresses
+                .insert(secondary_key(log.log.address.as_bytes(), &key), &[][..])?;
+            for (position, topic) in log.log.topics.iter().enumerate() {
+                self.topics
+                    .insert(secondary_key(&topic_prefix(position, topic), &key), &[][..])?;
+            }
+        }
+        self.timestamps
+            .insert(&block_num.to_be_bytes()[..], &timestamp.to_be_bytes()[..])?;
+        // The block goes in last, so that it only becomes the head once its logs are in
+        self.blocks
+            .insert(&block_num.to_be_bytes()[..], block_id.as_bytes())?;
+        Ok(())
+    }
+
+    /// Removes the block at the given height and its logs
+    fn remove_block(&self, block_num: u64) -> Result<(), sled::Error> {
+        self.blocks.remove(&block_num.to_be_bytes()[..])?;
+        self.timestamps.remove(&block_num.to_be_bytes()[..])?;
+        for entry in self.logs.scan_prefix(&block_num.to_be_bytes()[..]) {
+            let (key, value) = entry?;
+            if let Some(log) = decode_log(&value) {
+                let mut log_key = [0; 12];
+                log_key.copy_from_slice(&key);
+                self.addresses
+                    .remove(secondary_key(log.log.address.as_bytes(), &log_key))?;
+                for (position, topic) in log.log.topics.iter().enumerate() {
+                    self.topics
+                        .remove(secondary_key(&topic_prefix(position, topic), &log_key))?;
+                }
+            }
+            self.logs.remove(key)?;
+        }
+
+        if let Some(addresses) = self.watched_blocks.remove(&block_num.to_be_bytes()[..])? {
+            let addresses: Vec<String> = serde_json::from_slice(&addresses).unwrap_or_default();
+            for address in addresses {
+                self.watched_balances
+                    .remove(balance_key(&address, block_num))?;
+                let start = secondary_key(address.as_bytes(), &log_key(block_num, 0));
+                let end = secondary_key(address.as_bytes(), &log_key(block_num, u32::MAX));
+                for key in self.watched_transactions.range(start..=end).keys() {
+                    self.watched_transactions.remove(key?)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns every log of a block, ordered by position in the block, if the index
+    /// has the block with the given id
+    pub fn get_block_logs(
+        &self,
+        block_num: u64,
+        block_id: &str,
+    ) -> Result<Option<Vec<BlockLog>>, sled::Error> {
+        match self.blocks.get(&block_num.to_be_bytes()[..])? {
+            Some(ref indexed) if &indexed[..] == block_id.as_bytes() => (),
+            _ => return Ok(None),
+        }
+        let mut logs = Vec::new();
+        for value in self.logs.scan_prefix(&block_num.to_be_bytes()[..]).values() {
+            match decode_log(&value?) {
+                Some(log) => logs.push(log),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(logs))
+    }
+
+    /// Replaces the addresses on a watchlist, removing it if none are given. Only
+    /// blocks indexed from then on are recorded for addresses it adds.
+    pub fn set_watchlist(&self, name: &str, addresses: &[String]) -> Result<(), sled::Error> {
+        if addresses.is_empty() {
+            self.watchlists.remove(name.as_bytes())?;
+        } else {
+            self.watchlists.insert(
+                name.as_bytes(),
+                serde_json::to_vec(addresses).unwrap_or_default(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns the addresses on each watchlist, by name
+    pub fn watchlists(&self) -> Result<BTreeMap<String, Vec<String>>, sled::Error> {
+        let mut watchlists = BTreeMap::new();
+        for entry in self.watchlists.iter() {
+            let (name, addresses) = entry?;
+            watchlists.insert(
+                String::from_utf8_lossy(&name).into_owned(),
+                serde_json::from_slice(&addresses).unwrap_or_default(),
+            );
+        }
+        Ok(watchlists)
+    }
+
+    /// Returns the transactions a watched address sent or was sent in the blocks
+    /// from `from` to `to`, oldest first, and its balance after each block it had
+    /// any in
+    pub fn watched_activity(
+        &self,
+        address: &str,
+        from: u64,
+        to: u64,
+    ) -> Result<WatchedHistory, sled::Error> {
+        let mut balances = Vec::new();
+        let start = balance_key(address, from);
+        let end = balance_key(address, to);
+        for entry in self.watched_balances.range(start..=end) {
+            let (key, balance) = entry?;
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&balance);
+            balances.push((
+                block_num_of_key(&key[address.len()..]),
+                i64::from_be_bytes(bytes),
+            ));
+        }
+
+        let mut transactions = Vec::new();
+        let start = secondary_key(address.as_bytes(), &log_key(from, 0));
+        let end = secondary_key(address.as_bytes(), &log_key(to, u32::MAX));
+        for value in self.watched_transactions.range(start..=end).values() {
+            if let Ok(txn) = serde_json::from_slice(&value?) {
+                transactions.push(txn);
+            }
+        }
+        Ok((balances, transactions))
+    }
+
+    /// Returns the first and last indexed blocks whose transactions ran between two
+    /// times in seconds, inclusive, or None if there were none
+    pub fn blocks_between(&self, from: u64, to: u64) -> Result<Option<(u64, u64)>, sled::Error> {
+        let head = match self.head()? {
+            Some((head, _)) => head,
+            None => return Ok(None),
+        };
+        let first = self.first_block_at(from, head)?;
+        let end = match to.checked_add(1) {
+            Some(after) => self.first_block_at(after, head)?,
+            None => head + 1,
+        };
+        if first >= end {
+            return Ok(None);
+        }
+        Ok(Some((first, end - 1)))
+    }
+
+    /// Returns the first block up to `head` that ran at or after a time, or the
+    /// block after `head` if none did. Times only go forward along the chain, so
+    /// this is a binary search.
+    fn first_block_at(&self, time: u64, head: u64) -> Result<u64, sled::Error> {
+        let (mut low, mut high) = (0, head + 1);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if self.timestamp(middle)? < time {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        Ok(low)
+    }
+
+    /// Returns the time a block's transactions ran at, 0 if it was indexed before
+    /// times were kept
+    fn timestamp(&self, block_num: u64) -> Result<u64, sled::Error> {
+        Ok(self
+            .timestamps
+            .get(&block_num.to_be_bytes()[..])?
+            .map_or(0, |timestamp| {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(&timestamp);
+                u64::from_be_bytes(bytes)
+            }))
+    }
+
+    /// Returns every address on any watchlist
+    pub fn watched(&self) -> Result<HashSet<String>, sled::Error> {
+        Ok(self.watchlists()?.into_values().flatten().collect())
+    }
+
+    /// Returns the positions of the logs in the given blocks that were indexed under
+    /// the prefix
+    fn secondary_keys(
+        tree: &sled::Tree,
+        prefix: &[u8],
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<LogKey>, sled::Error> {
+        let start = secondary_key(prefix, &log_key(from, 0));
+        let end = secondary_key(prefix, &log_key(to, u32::MAX));
+        let mut keys = Vec::new();
+        for key in tree.range(start..=end).keys() {
+            let key = key?;
+            // Longer values that start with the prefix aren't this one
+            if key.len() == prefix.len() + 12 {
+                let mut log_key = [0; 12];
+                log_key.copy_from_slice(&key[prefix.len()..]);
+                keys.push(log_key);
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Returns the indexed logs of the blocks from `from` to `to` that pass the
+    /// filter. Logs are looked up by address if the filter has any, otherwise by the
+    /// first topic it filters on, and only scanned for filters with neither.
+    pub fn get_logs(
+        &self,
+        filter: &LogFilter,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<IndexedLog>, sled::Error> {
+        let first_topic = filter
+            .topics
+            .iter()
+            .enumerate()
+            .filter_map(|(position, topic)| match *topic {
+                TopicFilter::All => None,
+                TopicFilter::Exactly(ref topic) => Some((position, vec![topic.clone()])),
+                TopicFilter::OneOf(ref topics) => Some((position, topics.clone())),
+            })
+            .next();
+
+        let mut keys = Vec::new();
+        if !filter.addresses.is_empty() {
+            for address in &filter.addresses {
+                keys.extend(LogIndex::secondary_keys(
+                    &self.addresses,
+                    address.as_bytes(),
+                    from,
+                    to,
+                )?);
+            }
+        } else if let Some((position, topics)) = first_topic {
+            for topic in topics {
+                keys.extend(LogIndex::secondary_keys(
+                    &self.topics,
+                    &topic_prefix(position, &topic),
+                    from,
+                    to,
+                )?);
+            }
+        } else {
+            let start = log_key(from, 0);
+            let end = log_key(to, u32::MAX);
+            for key in self.logs.range(&start[..]..=&end[..]).keys() {
+                let key = key?;
+                let mut log_key = [0; 12];
+                log_key.copy_from_slice(&key);
+                keys.push(log_key);
+            }
+        }
+        keys.sort();
+        keys.dedup();
+
+        let mut block_ids: HashMap<u64, Option<String>> = HashMap::new();
+        let mut logs = Vec::new();
+        for key in keys {
+            let log = match self
+                .logs
+                .get(&key[..])?
+                .and_then(|value| decode_log(&value))
+            {
+                Some(log) => log,
+                None => continue,
+            };
+            if !filter.contains(&log.log, None) {
+                continue;
+            }
+            let (block_num, log_idx) = split_log_key(&key);
+            let block_id = match block_ids.entry(block_num) {
+                Entry::Occupied(entry) => entry.get().clone(),
+                Entry::Vacant(entry) => {
+                    let block_id = self
+                        .blocks
+                        .get(&block_num.to_be_bytes()[..])?
+                        .map(|block_id| String::from_utf8_lossy(&block_id).into_owned());
+                    entry.insert(block_id).clone()
+                }
+            };
+            let block_id = match block_id {
+                Some(block_id) => block_id,
+                // The block is being replaced
+                None => continue,
+            };
+            logs.push(IndexedLog {
+                block_num,
+                block_id,
+                log_idx: u64::from(log_idx),
+                log,
+            });
+        }
+        Ok(logs)
+    }
+
+    /// Indexes the blocks committed since the last one indexed, first removing the
+    /// indexed blocks that were forked out
+    pub fn catch_up<S: MessageSender>(&self, client: &ValidatorClient<S>) -> Result<(), String> {
+        let _updating = self.updating.lock().unwrap();
+        let index_error = |error: sled::Error| format!("Log index error: {}", error);
+
+        let latest = client
+            .get_current_block_number()
+            .map_err(|error| format!("{}", error))?;
+
+        let head = self.remove_forked_out(client)?;
+        let next = head.as_ref().map_or(0, |&(block_num, _)| block_num + 1);
+        let mut previous_id = head.map(|(_, block_id)| block_id);
+        // The blocks are fetched a window at a time, and their receipts one block at
+        // a time as they are indexed
+        for block in client.get_blocks_range(next, latest) {
+            let block = block.map_err(|error| format!("{}", error))?;
+            let read = read_fetched_block(client, &self.watched().map_err(index_error)?, block)?;
+            let block_num = read.block_num;
+            // The chain forked since the head was checked, which the next catch up
+            // sorts out
+            if let Some(ref previous_id) = previous_id {
+                if read.previous_id != *previous_id {
+                    return Ok(());
+                }
+            }
+            self.insert_read_block(&read).map_err(index_error)?;
+            if block_num % PROGRESS_INTERVAL == 0 && block_num < latest {
+                info!("Indexed logs up to block {} of {}", block_num, latest);
+            }
+            previous_id = Some(read.block_id);
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the index from the validator up to the latest block, reading `jobs`
+    /// blocks at once and adding them to the index in chain order, and returns the
+    /// number of the last block indexed. Watchlists are kept. The progress is saved
+    /// as blocks are added, so a rebuild that was stopped carries on from there when
+    /// it is started again.
+    pub fn rebuild<S>(&self, client: &ValidatorClient<S>, jobs: usize) -> Result<u64, String>
+    where
+        S: MessageSender + Clone + Send + Sync + 'static,
+    {
+        let _updating = self.updating.lock().unwrap();
+        let index_error = |error: sled::Error| format!("Log index error: {}", error);
+
+        if self
+            .meta
+            .get(REBUILDING_KEY)
+            .map_err(index_error)?
+            .is_none()
+        {
+            info!("Clearing the log index");
+            let trees = [
+                &self.blocks,
+                &self.timestamps,
+                &self.logs,
+                &self.addresses,
+                &self.topics,
+                &self.watched_transactions,
+                &self.watched_balances,
+                &self.watched_blocks,
+            ];
+            for tree in &trees {
+                tree.clear().map_err(index_error)?;
+            }
+            self.meta
+                .insert(REBUILDING_KEY, &[][..])
+                .map_err(index_error)?;
+            self.meta.flush().map_err(index_error)?;
+        } else {
+            info!("Resuming the rebuild of the log index");
+        }
+
+        let head = self.remove_forked_out(client)?;
+        let next = head.as_ref().map_or(0, |&(block_num, _)| block_num + 1);
+        // The logs of the block after the head may have been partly added when the
+        // rebuild was stopped
+        self.remove_block(next).map_err(index_error)?;
+        let mut previous_id = head.map(|(_, block_id)| block_id);
+
+        let latest = client
+            .get_current_block_number()
+            .map_err(|error| format!("{}", error))?;
+        let watched = self.watched().map_err(index_error)?;
+        let jobs = cmp::max(jobs, 1);
+        let mut start = next;
+        while start <= latest {
+            let end = cmp::min(start + jobs as u64 * REBUILD_BLOCKS_PER_JOB - 1, latest);
+            for read in read_blocks(client, &watched, start, end, jobs) {
+                let read = read?;
+                if let Some(ref previous_id) = previous_id {
+                    if read.previous_id != *previous_id {
+                        return Err(String::from(
+                            "The chain forked during the rebuild, run it again to carry on",
+                        ));
+                    }
+                }
+                self.insert_read_block(&read).map_err(index_error)?;
+                previous_id = Some(read.block_id);
+            }
+            self.blocks.flush().map_err(index_error)?;
+            info!("Rebuilt the log index up to block {} of {}", end, latest);
+            start = end + 1;
+        }
+
+        self.meta.remove(REBUILDING_KEY).map_err(index_error)?;
+        self.meta.flush().map_err(index_error)?;
+        Ok(latest)
+    }
+
+    /// Removes the indexed blocks that are no longer in the chain from the head
+    /// down, returning the number and id of the new head
+    fn remove_forked_out<S: MessageSender>(
+        &self,
+        client: &ValidatorClient<S>,
+    ) -> Result<Option<(u64, String)>, String> {
+        let index_error = |error: sled::Error| format!("Log index error: {}", error);
+        let mut head = self.head().map_err(index_error)?;
+        while let Some((block_num, block_id)) = head {
+            match client.get_block(BlockKey::Number(block_num)) {
+                Ok(ref block) if block.header_signature == block_id => {
+                    return Ok(Some((block_num, block_id)));
+                }
+                Ok(_) | Err(ClientError::NoResource) => {
+                    info!("Removing forked out block {} from the log index", block_id);
+                    self.remove_block(block_num).map_err(index_error)?;
+                    head = self.head().map_err(index_error)?;
+                }
+                Err(error) => return Err(format!("{}", error)),
+            }
+        }
+        Ok(None)
+    }
+
+    fn insert_read_block(&self, read: &ReadBlock) -> Result<(), sled::Error> {
+        self.insert_block(
+            read.block_num,
+            &read.block_id,
+            read.timestamp,
+            &read.logs,
+            &read.watched,
+        )
+    }
+
+    /// Reads what the watched addresses did in a block from the validator
+    fn read_watched_activity<S: MessageSender>(
+        client: &ValidatorClient<S>,
+        watched: &HashSet<String>,
+        block_num: u64,
+        block: &Block,
+    ) -> Result<Vec<WatchedActivity>, String> {
+        if watched.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut transactions: BTreeMap<String, Vec<(u32, Value)>> = BTreeMap::new();
+        let txns = block
+            .get_batches()
+            .iter()
+            .flat_map(|batch| batch.get_transactions().iter());
+        for (txn_idx, txn) in txns.enumerate() {
+            let txn = match Transaction::try_from(txn.clone()) {
+                Ok(txn) => txn,
+                Err(_) => continue,
+            };
+            let mut parties = vec![txn.from_addr()];
+            parties.extend(txn.to_addr());
+            parties.dedup();
+            for address in parties {
+                if watched.contains(&address) {
+                    let txn = transform::make_txn_obj(
+                        &txn,
+                        txn_idx as u64,
+                        &block.header_signature,
+                        block_num,
+                    );
+                    transactions
+                        .entry(address)
+                        .or_default()
+                        .push((txn_idx as u32, txn));
+                }
+            }
+        }
+
+        let mut activity = Vec::new();
+        for (address, transactions) in transactions {
+            let balance = client
+                .get_account(&address, BlockKey::Number(block_num))?
+                .map_or(0, |account| account.balance);
+            activity.push(WatchedActivity {
+                address,
+                balance,
+                transactions,
+            });
+        }
+        Ok(activity)
+    }
+
+    /// Returns the block the index started catching up from after startup, while it
+    /// is still catching up
+    pub fn backfilling_from(&self) -> Option<u64> {
+        *self.backfilling_from.lock().unwrap()
+    }
+
+    /// Catches up in the background, and again after each block is committed
+    pub fn start<S>(&self, client: ValidatorClient<S>, urls: Vec<String>)
+    where
+        S: MessageSender + Clone + Send + Sync + 'static,
+    {
+        let starting_block = match self.head() {
+            Ok(head) => head.map_or(0, |(block_num, _)| block_num),
+            Err(error) => {
+                warn!("Failed to read the log index head: {}", error);
+                0
+            }
+        };
+        *self.backfilling_from.lock().unwrap() = Some(starting_block);
+
+        let index = self.clone();
+        let startup_client = client.clone();
+        thread::spawn(move || {
+            if let Err(error) = index.catch_up(&startup_client) {
+                warn!("Failed to catch up the log index: {}", error);
+            }
+            *index.backfilling_from.lock().unwrap() = None;
+        });
+
+        let index = self.clone();
+        watch_blocks(urls, move |_| {
+            if let Err(error) = index.catch_up(&client) {
+                warn!("Failed to update the log index: {}", error);
+            }
+        });
+    }
+}
+
+/// What the index keeps of a block, as read from the validator
+struct ReadBlock {
+    block_num: u64,
+    block_id: String,
+    previous_id: String,
+    timestamp: u64,
+    logs: Vec<BlockLog>,
+    watched: Vec<WatchedActivity>,
+}
+
+fn read_block<S: MessageSender>(
+    client: &ValidatorClient<S>,
+    watched: &HashSet<String>,
+    block_num: u64,
+) -> Result<ReadBlock, String> {
+    let block = client
+        .get_block(BlockKey::Number(block_num))
+        .map_err(|error| format!("{}", error))?;
+    read_fetched_block(client, watched, block)
+}
+
+fn read_fetched_block<S: MessageSender>(
+    client: &ValidatorClient<S>,
+    watched: &HashSet<String>,
+    block: Block,
+) -> Result<ReadBlock, String> {
+    let header: BlockHeader = protobuf::parse_from_bytes(&block.header)
+        .map_err(|error| format!("Error parsing block header: {:?}", error))?;
+    let block_num = header.block_num;
+    let logs = read_block_logs(client, &block)
+        .map_err(|error| format!("Failed to read logs: {}", error.message))?;
+    let watched = LogIndex::read_watched_activity(client, watched, block_num, &block)?;
+    let timestamp = vm::read_timestamp(client, &header.state_root_hash)?;
+    Ok(ReadBlock {
+        block_num,
+        block_id: block.header_signature,
+        previous_id: header.previous_block_id,
+        timestamp,
+        logs,
+        watched,
+    })
+}
+
+/// Reads the blocks from `start` to `end` with `jobs` threads, each taking the next
+/// block that hasn't been taken, and returns them in chain order
+fn read_blocks<S>(
+    client: &ValidatorClient<S>,
+    watched: &HashSet<String>,
+    start: u64,
+    end: u64,
+    jobs: usize,
+) -> Vec<Result<ReadBlock, String>>
+where
+    S: MessageSender + Clone + Send + Sync + 'static,
+{
+    let next = AtomicU64::new(start);
+    let read = Mutex::new(BTreeMap::new());
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            let client = client.clone();
+            let (next, read) = (&next, &read);
+            scope.spawn(move || loop {
+                let block_num = next.fetch_add(1, Ordering::Relaxed);
+                if block_num > end {
+                    break;
+                }
+                let block = read_block(&client, watched, block_num);
+                read.lock().unwrap().insert(block_num, block);
+            });
+        }
+    });
+    read.into_inner().unwrap().into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use contracts::ContractRegistry;
+    use filters::FilterManager;
+    use mock_chain::MockChain;
+
+    fn block_log(txn_idx: u64, address: &str, topics: &[&str]) -> BlockLog {
+        BlockLog {
+            txn_id: format!("txn{}", txn_idx),
+            txn_idx,
+            log: SethLog {
+                address: String::from(address),
+                topics: topics.iter().map(|&topic| String::from(topic)).collect(),
+                data: String::new(),
+            },
+        }
+    }
+
+    fn filter(addresses: &[&str], topics: Vec<TopicFilter>) -> LogFilter {
+        LogFilter {
+            from_block: None,
+            to_block: None,
+            block_hash: None,
+            addresses: addresses
+                .iter()
+                .map(|&address| String::from(address))
+                .collect(),
+            topics,
+        }
+    }
+
+    fn positions(logs: &[IndexedLog]) -> Vec<(u64, u64)> {
+        logs.iter()
+            .map(|log| (log.block_num, log.log_idx))
+            .collect()
+    }
+
+    #[test]
+    fn looks_up_logs_by_address_and_topic() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let index = LogIndex::from_db(&db).unwrap();
+        index
+            .insert_block(
+                1,
+                "b1",
+                0,
+                &[block_log(0, "aa", &["t1"]), block_log(0, "bb", &["t2"])],
+                &[],
+            )
+            .unwrap();
+        index
+            .insert_block(2, "b2", 0, &[block_log(0, "aa", &["t2", "t1"])], &[])
+            .unwrap();
+        assert_eq!(Some((2, String::from("b2"))), index.head().unwrap());
+
+        let all = index.get_logs(&filter(&[], vec![]), 0, 2).unwrap();
+        assert_eq!(vec![(1, 0), (1, 1), (2, 0)], positions(&all));
+        assert_eq!("b1", all[0].block_id);
+
+        let by_address = index.get_logs(&filter(&["aa"], vec![]), 0, 2).unwrap();
+        assert_eq!(vec![(1, 0), (2, 0)], positions(&by_address));
+
+        let exactly = vec![TopicFilter::Exactly(String::from("t2"))];
+        let by_topic = index.get_logs(&filter(&[], exactly), 0, 2).unwrap();
+        assert_eq!(vec![(1, 1), (2, 0)], positions(&by_topic));
+
+        let second = vec![TopicFilter::All, TopicFilter::Exactly(String::from("t1"))];
+        let by_position = index.get_logs(&filter(&["aa"], second), 0, 2).unwrap();
+        assert_eq!(vec![(2, 0)], positions(&by_position));
+
+        let in_range = index.get_logs(&filter(&["aa"], vec![]), 2, 2).unwrap();
+        assert_eq!(vec![(2, 0)], positions(&in_range));
+    }
+
+    #[test]
+    fn replaces_forked_out_blocks() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let index = LogIndex::from_db(&db).unwrap();
+        index
+            .insert_block(1, "old", 0, &[block_log(0, "aa", &["t1"])], &[])
+            .unwrap();
+        index
+            .insert_block(1, "new", 0, &[block_log(0, "bb", &["t2"])], &[])
+            .unwrap();
+
+        assert!(index
+            .get_logs(&filter(&["aa"], vec![]), 0, 1)
+            .unwrap()
+            .is_empty());
+        let exactly = vec![TopicFilter::Exactly(String::from("t1"))];
+        assert!(index
+            .get_logs(&filter(&[], exactly), 0, 1)
+            .unwrap()
+            .is_empty());
+        let logs = index.get_logs(&filter(&["bb"], vec![]), 0, 1).unwrap();
+        assert_eq!("new", logs[0].block_id);
+        assert!(index.get_block_logs(1, "old").unwrap().is_none());
+        let block_logs = index.get_block_logs(1, "new").unwrap().unwrap();
+        assert_eq!(
+            vec!["bb"],
+            block_logs
+                .iter()
+                .map(|log| log.log.address.as_str())
+                .collect::<Vec<_>>()
+        );
+
+        index.remove_block(1).unwrap();
+        assert_eq!(None, index.head().unwrap());
+        assert!(index
+            .get_logs(&filter(&[], vec![]), 0, 1)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn forgets_watched_activity_of_forked_out_blocks() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let index = LogIndex::from_db(&db).unwrap();
+        index
+            .set_watchlist("list", &[String::from("aa"), String::from("bb")])
+            .unwrap();
+        assert_eq!(2, index.watched().unwrap().len());
+
+        let activity = |balance, txn_idx| WatchedActivity {
+            address: String::from("aa"),
+            balance,
+            transactions: vec![(txn_idx, Value::from(txn_idx))],
+        };
+        index
+            .insert_block(1, "b1", 0, &[], &[activity(5, 0)])
+            .unwrap();
+        index
+            .insert_block(2, "old", 0, &[], &[activity(3, 1)])
+            .unwrap();
+        let (balances, transactions) = index.watched_activity("aa", 0, 2).unwrap();
+        assert_eq!(vec![(1, 5), (2, 3)], balances);
+        assert_eq!(vec![Value::from(0), Value::from(1)], transactions);
+
+        index.insert_block(2, "new", 0, &[], &[]).unwrap();
+        let (balances, transactions) = index.watched_activity("aa", 0, 2).unwrap();
+        assert_eq!(vec![(1, 5)], balances);
+        assert_eq!(vec![Value::from(0)], transactions);
+
+        index.set_watchlist("list", &[]).unwrap();
+        assert!(index.watchlists().unwrap().is_empty());
+    }
+
+    #[test]
+    fn finds_the_blocks_between_two_times() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let index = LogIndex::from_db(&db).unwrap();
+        assert_eq!(None, index.blocks_between(0, 100).unwrap());
+
+        for (block_num, timestamp) in [(0, 0), (1, 10), (2, 10), (3, 20), (4, 30)] {
+            index
+                .insert_block(block_num, "b", timestamp, &[], &[])
+                .unwrap();
+        }
+        assert_eq!(Some((1, 3)), index.blocks_between(5, 25).unwrap());
+        assert_eq!(Some((1, 2)), index.blocks_between(10, 10).unwrap());
+        assert_eq!(Some((4, 4)), index.blocks_between(30, u64::MAX).unwrap());
+        assert_eq!(None, index.blocks_between(11, 19).unwrap());
+        assert_eq!(None, index.blocks_between(31, 40).unwrap());
+    }
+
+    #[test]
+    fn rebuilds_the_index_it_would_catch_up_to() {
+        let client = ValidatorClient::new(
+            MockChain::new(),
+            Vec::new(),
+            FilterManager::new(1),
+            0,
+            String::from("1.0"),
+            ContractRegistry::new(),
+        );
+        let temporary = || sled::Config::new().temporary(true).open().unwrap();
+        let caught_up = LogIndex::from_db(&temporary()).unwrap();
+        caught_up.catch_up(&client).unwrap();
+        let all_logs =
+            |index: &LogIndex| positions(&index.get_logs(&filter(&[], vec![]), 0, 3).unwrap());
+
+        let rebuilt = LogIndex::from_db(&temporary()).unwrap();
+        // Rebuilding clears what was indexed before
+        rebuilt
+            .insert_block(7, "stale", 0, &[block_log(0, "aa", &["t1"])], &[])
+            .unwrap();
+        assert_eq!(rebuilt.rebuild(&client, 2).unwrap(), 3);
+        assert_eq!(rebuilt.head().unwrap(), caught_up.head().unwrap());
+        assert_eq!(all_logs(&rebuilt), all_logs(&caught_up));
+        assert_eq!(all_logs(&rebuilt), vec![(3, 0)]);
+        assert!(rebuilt.meta.get(REBUILDING_KEY).unwrap().is_none());
+
+        // A rebuild that was stopped carries on from the last block it added
+        rebuilt.meta.insert(REBUILDING_KEY, &[][..]).unwrap();
+        rebuilt.remove_block(3).unwrap();
+        assert_eq!(rebuilt.rebuild(&client, 2).unwrap(), 3);
+        assert_eq!(rebuilt.head().unwrap(), caught_up.head().unwrap());
+        assert_eq!(all_logs(&rebuilt), vec![(3, 0)]);
+    }
+}
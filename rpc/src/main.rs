@@ -15,21 +15,56 @@
  * ------------------------------------------------------------------------------
  */
 
+extern crate base64;
 #[macro_use]
 extern crate clap;
 extern crate crypto;
 extern crate dirs;
-extern crate futures_cpupool;
+extern crate evm;
+#[cfg(feature = "debug")]
+extern crate evm_runtime;
+extern crate futures;
 extern crate jsonrpc_core;
 extern crate jsonrpc_http_server;
+extern crate jsonrpc_ipc_server;
+#[cfg(feature = "ws")]
+extern crate jsonrpc_pubsub;
+#[cfg(feature = "ws")]
+extern crate jsonrpc_ws_server;
+#[cfg(unix)]
+extern crate libc;
 #[macro_use]
 extern crate log;
+#[cfg(feature = "tls")]
+extern crate native_tls;
+#[cfg(feature = "postgres")]
+extern crate postgres;
+extern crate primitive_types;
+#[cfg(test)]
+extern crate proptest;
 extern crate protobuf;
+#[cfg(feature = "personal")]
+extern crate rand;
+#[cfg(any(feature = "privacy", feature = "screening"))]
+extern crate reqwest;
+extern crate rlp;
 extern crate sawtooth_sdk;
+extern crate sawtooth_seth_types as types;
+extern crate secp256k1;
+extern crate serde;
 extern crate serde_json;
+extern crate sha3;
 extern crate simple_logging;
+#[cfg(feature = "indexer")]
+extern crate sled;
 extern crate tiny_keccak;
+#[cfg(any(feature = "privacy", feature = "screening"))]
+extern crate tokio;
+extern crate toml;
 extern crate uuid;
+#[cfg(windows)]
+#[macro_use]
+extern crate windows_service;
 
 /// Creates a custom `jsonrpc_core::Error` object, and logs the message
 macro_rules! fail {
@@ -49,88 +84,1111 @@ macro_rules! fail {
     }};
 }
 
+mod abi;
 mod accounts;
+mod auth;
+mod bloom;
+mod breaker;
+mod cache;
 mod calls;
 mod client;
+mod config;
+mod contracts;
+mod crypto_backend;
+mod endpoints;
+#[cfg(any(feature = "indexer", feature = "ws"))]
+mod events;
 mod filters;
+mod front;
+#[cfg(feature = "personal")]
+mod keystore;
+#[cfg(feature = "ledger")]
+mod ledger;
+#[cfg(feature = "indexer")]
+mod log_index;
+mod memory;
 mod messages;
+mod metrics;
+#[cfg(feature = "personal")]
+mod mnemonic;
+mod mock_chain;
+mod names;
+mod prefetch;
+mod preflight;
+#[cfg(feature = "privacy")]
+mod privacy;
+mod protocol;
+mod proxies;
+mod raw_transaction;
+mod recording;
+#[cfg(feature = "indexer")]
+mod reports;
 mod requests;
+mod response;
+mod screening;
+#[cfg(windows)]
+mod service;
+mod sessions;
+#[cfg(feature = "debug")]
+mod shadow;
+#[cfg(feature = "postgres")]
+mod shared_index;
+mod state_proof;
+#[cfg(feature = "ws")]
+mod subscriptions;
+#[cfg(unix)]
+mod systemd;
+mod telemetry;
+#[cfg(feature = "debug")]
+mod tracer;
 mod transactions;
 mod transform;
+mod txpool;
+mod typed_data;
+mod versions;
+mod vm;
+#[cfg(feature = "debug")]
+mod witness;
 
 use accounts::Account;
+use auth::Authenticator;
+use cache::ChainCache;
 use calls::*;
-use client::ValidatorClient;
-use jsonrpc_core::{IoHandler, Params};
-use jsonrpc_http_server::ServerBuilder;
-use requests::{RequestExecutor, RequestHandler};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use client::{
+    ValidatorClient, DEFAULT_FAMILY_VERSION, DEFAULT_MAX_REQUEST_MEMORY, DEFAULT_MAX_RESPONSE_SIZE,
+};
+use config::{Live, Settings};
+use contracts::ContractRegistry;
+use endpoints::{Balancing, Connection, EndpointSender, ValidatorConnection};
+use filters::FilterManager;
+use front::{Front, RateLimiter};
+#[cfg(feature = "ws")]
+use futures::future;
+use jsonrpc_core::{MetaIoHandler, Params};
+use jsonrpc_http_server::{hyper, RequestMiddlewareAction, ServerBuilder};
+#[cfg(feature = "ws")]
+use jsonrpc_pubsub::{PubSubHandler, Session};
+#[cfg(feature = "ledger")]
+use ledger::Ledger;
+#[cfg(feature = "indexer")]
+use log_index::LogIndex;
+use metrics::{Metrics, CONTENT_TYPE, METRICS_PATH};
+use mock_chain::MockChain;
+use prefetch::StatePrefetcher;
+#[cfg(feature = "privacy")]
+use privacy::PrivacyManager;
+use recording::Recorder;
+use requests::{
+    BatchLimits, Priority, RequestExecutor, RequestHandler, RequestMeta, HEALTH_METHOD,
+};
 use sawtooth_sdk::messaging::stream::*;
-use sawtooth_sdk::messaging::zmq_stream::*;
+#[cfg(feature = "screening")]
+use screening::HttpScreen;
+use screening::RuleScreen;
+use sessions::SESSION_HEADER;
+#[cfg(feature = "debug")]
+use shadow::ShadowExecutor;
+#[cfg(feature = "postgres")]
+use shared_index::PostgresFilterStore;
+use std::env;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+#[cfg(feature = "indexer")]
+use std::path::PathBuf;
 use std::process;
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(feature = "ws")]
+use subscriptions::SubscriptionManager;
+use telemetry::UnsupportedMethods;
 
 const SERVER_THREADS: usize = 3;
+/// The namespaces of the methods seth-rpc serves, which --rpc-api picks from
+const RPC_APIS: &[&str] = &["eth", "net", "web3", "personal", "debug", "seth", "txpool"];
+const DEFAULT_MAX_FILTERS: usize = 1000;
+#[cfg(feature = "ws")]
+const DEFAULT_MAX_WS_CONNECTIONS: usize = 100;
+#[cfg(feature = "ws")]
+const DEFAULT_MAX_SUBSCRIPTIONS: usize = 100;
+/// Enough for a response of the largest size served by default to be sent in full
+#[cfg(feature = "ws")]
+const DEFAULT_MAX_WS_SEND_BUFFER: usize = 32 * 1024 * 1024;
+const DEFAULT_MAX_PENDING: usize = 64;
+const DEFAULT_MAX_BATCH_SIZE: usize = 1;
+const DEFAULT_MAX_BATCH_REQUESTS: usize = 1000;
+const DEFAULT_CALL_CACHE_SIZE: usize = 16 * 1024 * 1024;
+const DEFAULT_CHAIN_CACHE_SIZE: usize = 16 * 1024 * 1024;
+#[cfg(feature = "indexer")]
+const DEFAULT_REPORT_PERIOD: u64 = 24 * 60 * 60;
+#[cfg(feature = "indexer")]
+const DEFAULT_REBUILD_JOBS: usize = 8;
+
+/// What requests go through on their way to their methods, outermost first
+type Middleware = (
+    Recorder,
+    BatchLimits,
+    (UnsupportedMethods, Metrics),
+    Authenticator,
+);
 
 fn main() {
-    let arg_matches = clap_app!(("seth-rpc") =>
+    let given = app().get_matches();
+    if given.subcommand_matches("check-config").is_some() {
+        preflight::run(&given);
+    }
+    let arg_matches = with_config_or_exit(given);
+
+    #[cfg(windows)]
+    {
+        if arg_matches.is_present("service") {
+            return service::run();
+        }
+    }
+
+    #[cfg(unix)]
+    serve(&arg_matches, systemd::ready);
+    #[cfg(not(unix))]
+    serve(&arg_matches, || ());
+}
+
+fn app() -> App<'static, 'static> {
+    let app = clap_app!(("seth-rpc") =>
         (version: crate_version!())
         (about: "Seth RPC Server")
-        (@arg connect: --connect +takes_value
-         "Component endpoint of the validator to communicate with.")
-        (@arg bind: --bind +takes_value
-         "The host and port the RPC server should bind to.")
+        (@arg config: --config +takes_value
+         "A TOML file of settings, each keyed by the flag it stands for, which flags given on the command line override.")
+        (@arg connect: --connect... +takes_value
+         "Component endpoints of the validators to communicate with, the first preferred.")
+        (@arg balancing: --balancing +takes_value
+         "How requests other than transactions are spread over the validators of --connect, latency or round-robin.")
+        (@arg mock_chain: --("mock-chain") conflicts_with[connect]
+         "Serve a small chain that is the same on every run instead of a validator's, for testing applications.")
+        (@arg bind: --bind... +takes_value
+         "The hosts and ports the RPC server should bind to, IPv4 or IPv6 like [::1]:3030.")
+        (@arg ws_port: --("ws-port") +takes_value
+         "The port to serve JSON-RPC over WebSocket on, with eth_subscribe, at the hosts of --bind.")
+        (@arg max_ws_connections: --("max-ws-connections") +takes_value requires[ws_port]
+         "The maximum number of WebSocket connections open at once at each host, further handshakes are refused.")
+        (@arg max_subscriptions: --("max-subscriptions") +takes_value requires[ws_port]
+         "The maximum number of subscriptions each WebSocket connection can have at once.")
+        (@arg max_ws_send_buffer: --("max-ws-send-buffer") +takes_value requires[ws_port]
+         "The most bytes waiting to be sent to a WebSocket connection, one that reads too slowly to stay under it is disconnected.")
+        (@arg ipc_path: --("ipc-path") +takes_value
+         "The path of a UNIX socket, or on Windows a named pipe like \\\\.\\pipe\\seth-rpc, to also serve requests on.")
+        (@arg cors_origins: --("cors-origins") +takes_value
+         "The origins browsers may call the server from, comma separated, any by default.")
+        (@arg rpc_api: --("rpc-api") +takes_value
+         "The namespaces of methods to serve, comma separated, or the ones not to serve, each prefixed with -.")
+        (@arg rate_limit: --("rate-limit") +takes_value
+         "The number of HTTP requests a second each client IP may send, unlimited by default.")
+        (@arg rate_burst: --("rate-burst") +takes_value requires[rate_limit]
+         "The number of HTTP requests each client IP may send at once before --rate-limit applies, a second's worth by default.")
+        (@arg tls_cert: --("tls-cert") +takes_value requires[tls_key]
+         "A PEM file of the certificate chain to serve HTTPS with at the hosts of --bind instead of HTTP.")
+        (@arg tls_key: --("tls-key") +takes_value requires[tls_cert]
+         "A PEM file of the PKCS #8 private key of the certificate of --tls-cert.")
+        (@arg auth_tokens: --("auth-tokens") +takes_value
+         "A file of bearer tokens, one per line, that authenticate the clients sending them.")
+        (@arg jwt_secret: --("jwt-secret") +takes_value
+         "A file of the secret that authenticates the clients sending JWTs signed with it using HS256.")
+        (@arg auth_methods: --("auth-methods") +takes_value
+         "The methods only authenticated clients may call, comma separated, with * ending a prefix, by default those that sign or send transactions, change what the server tracks, and debug_* and the other methods that are costly to serve.")
         (@arg unlock: --unlock... +takes_value
          "The aliases of the accounts to unlock.")
+        (@arg ledger: --ledger +takes_value
+         "The hidraw device of a Ledger whose accounts sign the transactions and messages sent from them, such as /dev/hidraw0.")
+        (@arg ledger_accounts: --("ledger-accounts") +takes_value requires[ledger]
+         "The number of accounts of the Ledger of --ledger to use, derived along m/44'/60'/0'/0, 1 by default.")
+        (@arg max_filters: --("max-filters") +takes_value
+         "The maximum number of filters that can be installed at once.")
+        (@arg filter_timeout: --("filter-timeout") +takes_value
+         "The number of seconds a filter is kept without being polled, 0 to keep filters until they are uninstalled.")
+        (@arg max_pending: --("max-pending") +takes_value
+         "The maximum number of pending requests, lower priority requests are refused sooner.")
+        (@arg max_response_size: --("max-response-size") +takes_value
+         "The maximum size in bytes of a response, larger ones are refused.")
+        (@arg max_request_memory: --("max-request-memory") +takes_value
+         "The most memory in bytes, roughly, that serving one request may hold, such as for traces and logs, before it is failed.")
+        (@arg max_batch_requests: --("max-batch-requests") +takes_value
+         "The maximum number of requests in a JSON-RPC batch, larger batches are refused.")
+        (@arg max_batch_size: --("max-batch-size") +takes_value
+         "The maximum number of transactions of a seth_submitOrdered call put in one batch.")
+        (@arg index_db: --("index-db") +takes_value
+         "The PostgreSQL URL of a filter index shared between seth-rpc servers.")
+        (@arg log_index: --("log-index") +takes_value
+         "The directory to keep an index of logs in, which eth_getLogs is served from.")
+        (@arg report_dir: --("report-dir") +takes_value
+         "The directory to write a signed report of what watched addresses did in each period to, with --log-index.")
+        (@arg report_period: --("report-period") +takes_value
+         "The number of seconds each report of --report-dir covers, 86400 for a day by default.")
+        (@arg report_signer: --("report-signer") +takes_value
+         "The address of the unlocked account that signs the reports of --report-dir.")
+        (@arg call_cache_size: --("call-cache-size") +takes_value
+         "The maximum size in bytes of cached eth_call results, 0 to disable.")
+        (@arg block_cache_size: --("block-cache-size") +takes_value
+         "The maximum size in bytes of cached blocks, 0 to disable.")
+        (@arg state_cache_size: --("state-cache-size") +takes_value
+         "The maximum size in bytes of cached state entries of past blocks, 0 to disable.")
+        (@arg receipt_cache_size: --("receipt-cache-size") +takes_value
+         "The maximum size in bytes of cached transaction receipts, 0 to disable.")
+        (@arg code_cache_size: --("code-cache-size") +takes_value
+         "The maximum size in bytes of cached contract code, which eth_getCodeByHash is served from, 0 to disable.")
+        (@arg screening_url: --("screening-url") +takes_value
+         "The URL of a policy service that is asked whether each transaction may be sent.")
+        (@arg screening_simulate: --("screening-simulate")
+         "Simulate each transaction and send the policy service what it would change.")
+        (@arg screening_rules: --("screening-rules") +takes_value
+         "A JSON file of senders, targets, function selectors and values to deny transactions for.")
+        (@arg privacy_manager: --("privacy-manager") +takes_value
+         "The URL of the privacy manager used to send private transactions.")
+        (@arg family_version: --("family-version") +takes_value
+         "The version of the seth transaction family to send transactions with, 1.1 by default.")
+        (@arg prefetch: --prefetch +takes_value
+         "The number of most read state entries to load after each block, 0 to disable.")
+        (@arg abi_dir: --("abi-dir") +takes_value
+         "A directory of contract ABIs, each named after its contract's address.")
+        (@arg crypto_backend: --("crypto-backend") +takes_value
+         "The implementation of Keccak and signature recovery to use, optimized or portable, optimized by default.")
+        (@arg gas_cap: --("gas-cap") +takes_value
+         "The most gas an eth_call or eth_estimateGas can use.")
+        (@arg finality_depth: --("finality-depth") +takes_value
+         "The number of blocks after which a block is final, instead of judging by the consensus algorithm.")
+        (@arg verify_state_reads: --("verify-state-reads")
+         "Check each account and storage slot eth_getBalance, eth_getTransactionCount, eth_getCode and eth_getStorageAt return against a proof of the block's state, which reads all of it from the validator.")
+        (@arg name_registry: --("name-registry") +takes_value
+         "The address of an ENS-style registry contract to resolve names like alice.seth with.")
+        (@arg shadow_fork: --("shadow-fork") +takes_value
+         "Run each committed block again with the EVM rules of this fork, istanbul, berlin, london or shanghai, and report where transactions go another way.")
+        (@arg record: --record +takes_value
+         "A file to record each request and its response to, with passwords and keys redacted.")
+        (@arg replay: --replay +takes_value conflicts_with[bind ws_port ipc_path record]
+         "Replay the requests recorded in this file instead of serving, and print the ones answered differently.")
         (@arg verbose: -v... "Increase the logging level.")
+        (@subcommand index =>
+            (about: "Manage the log index of --log-index.")
+            (@subcommand rebuild =>
+                (about: "Rebuild the log index from the validator and exit, carrying on from where a rebuild that was stopped got to.")
+                (@arg jobs: --jobs +takes_value
+                 "The number of blocks read from the validator at once.")))
     )
-    .get_matches();
+    .subcommand(SubCommand::with_name("check-config").about(
+        "Check the configuration, that the validators answer, that the keys of --unlock can be read and which chain id the chain's sawtooth.seth.chain_id setting gives, and exit.",
+    ));
+
+    // Services are started with the same arguments, plus this one
+    if cfg!(windows) {
+        app.arg(
+            Arg::with_name("service")
+                .long("service")
+                .help("Run as a Windows service, started by the service control manager."),
+        )
+    } else {
+        app
+    }
+}
+
+/// Adds the settings of the `--config` file of a command line to its matches for the
+/// flags it doesn't give, exiting if the file is invalid
+fn with_config_or_exit(given: ArgMatches<'static>) -> ArgMatches<'static> {
+    match given.value_of("config") {
+        Some(path) => {
+            with_config(&given, path)
+                .unwrap_or_else(|error| {
+                    eprintln!("{}", error);
+                    process::exit(1);
+                })
+                .0
+        }
+        None => given,
+    }
+}
 
-    let bind = arg_matches.value_of("bind").unwrap_or("127.0.0.1:3030");
+/// Parses the command line again with the settings of a config file added, and
+/// returns the matches with the file's settings
+fn with_config(
+    given: &ArgMatches,
+    path: &str,
+) -> Result<(ArgMatches<'static>, toml::value::Table), String> {
+    let config = config::load(path)?;
+    // The file's settings go before those of the command line, which may end
+    // with a subcommand
+    let mut args: Vec<OsString> = env::args_os().take(1).collect();
+    args.extend(
+        config::args(&config, given)?
+            .into_iter()
+            .map(OsString::from),
+    );
+    args.extend(env::args_os().skip(1));
+    let matches = app()
+        .get_matches_from_safe(args)
+        .map_err(|error| format!("In {}: {}", path, error.message))?;
+    Ok((matches, config))
+}
+
+/// Runs the server until it is stopped, calling `ready` once it is serving requests
+pub fn serve<F: FnOnce()>(arg_matches: &ArgMatches, ready: F) {
+    // Set before anything is hashed, which would set the default
+    if let Some(name) = arg_matches.value_of("crypto_backend") {
+        let backend = crypto_backend::by_name(name).unwrap_or_else(|| {
+            eprintln!(
+                "Unknown crypto backend `{}`, expected one of {}",
+                name,
+                crypto_backend::BACKENDS.join(", ")
+            );
+            process::exit(1);
+        });
+        if let Err(error) = crypto_backend::set(backend) {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+    let bind: Vec<std::net::SocketAddr> = arg_matches
+        .values_of_lossy("bind")
+        .unwrap_or_else(|| vec![String::from("127.0.0.1:3030")])
+        .iter()
+        .map(|bind| abort_if_err(bind.parse()))
+        .collect();
+    let ipc_path = arg_matches.value_of("ipc_path");
+    #[cfg(feature = "tls")]
+    let tls = match (
+        arg_matches.value_of("tls_cert"),
+        arg_matches.value_of("tls_key"),
+    ) {
+        (Some(cert), Some(key)) => Some(
+            front::load_acceptor(Path::new(cert), Path::new(key)).unwrap_or_else(|error| {
+                eprintln!("{}", error);
+                process::exit(1);
+            }),
+        ),
+        _ => None,
+    };
+    #[cfg(not(feature = "tls"))]
+    {
+        require_feature(arg_matches, "tls_cert", "tls");
+        require_feature(arg_matches, "tls_key", "tls");
+    }
+    let settings = Settings::from_matches(arg_matches).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    let limiter = settings
+        .rate_limit
+        .map(|(rate, burst)| RateLimiter::new(rate, burst));
+    let front = Front {
+        #[cfg(feature = "tls")]
+        tls,
+        limiter: limiter.clone(),
+    };
+    let apis = rpc_apis(arg_matches);
+    let authenticator = authenticator(arg_matches);
+    #[cfg(feature = "ws")]
+    let ws = arg_matches.value_of("ws_port").map(|port| WsConfig {
+        port: abort_if_err(port.parse::<u16>()),
+        max_connections: arg_matches
+            .value_of("max_ws_connections")
+            .map(|max| abort_if_err(max.parse::<usize>()))
+            .unwrap_or(DEFAULT_MAX_WS_CONNECTIONS),
+        max_send_buffer: arg_matches
+            .value_of("max_ws_send_buffer")
+            .map(|max| abort_if_err(max.parse::<usize>()))
+            .unwrap_or(DEFAULT_MAX_WS_SEND_BUFFER),
+    });
+    #[cfg(feature = "ws")]
+    let max_subscriptions = arg_matches
+        .value_of("max_subscriptions")
+        .map(|max| abort_if_err(max.parse::<usize>()))
+        .unwrap_or(DEFAULT_MAX_SUBSCRIPTIONS);
+    #[cfg(not(feature = "ws"))]
+    {
+        require_feature(arg_matches, "ws_port", "ws");
+        require_feature(arg_matches, "max_ws_connections", "ws");
+        require_feature(arg_matches, "max_subscriptions", "ws");
+        require_feature(arg_matches, "max_ws_send_buffer", "ws");
+    }
     let connect = arg_matches
-        .value_of("connect")
-        .unwrap_or("tcp://127.0.0.1:4004");
-    let accounts: Vec<Account> = arg_matches
+        .values_of_lossy("connect")
+        .unwrap_or_else(|| vec![String::from("tcp://127.0.0.1:4004")]);
+    let max_filters = arg_matches
+        .value_of("max_filters")
+        .map(|max| abort_if_err(max.parse::<usize>()))
+        .unwrap_or(DEFAULT_MAX_FILTERS);
+    let max_pending = arg_matches
+        .value_of("max_pending")
+        .map(|max| abort_if_err(max.parse::<usize>()))
+        .unwrap_or(DEFAULT_MAX_PENDING);
+    let max_batch_size = arg_matches
+        .value_of("max_batch_size")
+        .map(|max| abort_if_err(max.parse::<usize>()))
+        .unwrap_or(DEFAULT_MAX_BATCH_SIZE);
+    let max_response_size = arg_matches
+        .value_of("max_response_size")
+        .map(|size| abort_if_err(size.parse::<usize>()))
+        .unwrap_or(DEFAULT_MAX_RESPONSE_SIZE);
+    let max_request_memory = arg_matches
+        .value_of("max_request_memory")
+        .map(|size| abort_if_err(size.parse::<usize>()))
+        .unwrap_or(DEFAULT_MAX_REQUEST_MEMORY);
+    let max_batch_requests = arg_matches
+        .value_of("max_batch_requests")
+        .map(|max| abort_if_err(max.parse::<usize>()))
+        .unwrap_or(DEFAULT_MAX_BATCH_REQUESTS);
+    let prefetch = arg_matches
+        .value_of("prefetch")
+        .map(|count| abort_if_err(count.parse::<usize>()))
+        .unwrap_or(0);
+    #[cfg(feature = "postgres")]
+    let filters = match arg_matches.value_of("index_db") {
+        Some(url) => {
+            let store = abort_if_err(PostgresFilterStore::connect(url));
+            FilterManager::with_store(Arc::new(store), max_filters)
+        }
+        None => FilterManager::new(max_filters),
+    };
+    #[cfg(not(feature = "postgres"))]
+    let filters = {
+        require_feature(arg_matches, "index_db", "postgres");
+        FilterManager::new(max_filters)
+    };
+    let filters = match arg_matches.value_of("filter_timeout") {
+        Some(secs) => match abort_if_err(secs.parse::<u64>()) {
+            0 => filters.with_idle_timeout(None),
+            secs => filters.with_idle_timeout(Some(Duration::from_secs(secs))),
+        },
+        None => filters,
+    };
+    #[cfg(feature = "indexer")]
+    let log_index = arg_matches
+        .value_of("log_index")
+        .map(|path| abort_if_err(LogIndex::open(path)));
+    #[cfg(not(feature = "indexer"))]
+    require_feature(arg_matches, "log_index", "indexer");
+    let rebuild = arg_matches
+        .subcommand_matches("index")
+        .and_then(|index| index.subcommand_matches("rebuild"));
+    #[cfg(feature = "indexer")]
+    let rebuild_jobs = rebuild.map(|rebuild| {
+        if log_index.is_none() {
+            eprintln!("index rebuild needs --log-index");
+            process::exit(1);
+        }
+        rebuild
+            .value_of("jobs")
+            .map_or(DEFAULT_REBUILD_JOBS, |jobs| {
+                abort_if_err(jobs.parse::<usize>())
+            })
+    });
+    #[cfg(not(feature = "indexer"))]
+    {
+        if rebuild.is_some() {
+            eprintln!("index rebuild needs seth-rpc to be built with the `indexer` feature");
+            process::exit(1);
+        }
+    }
+    #[cfg(not(feature = "indexer"))]
+    require_feature(arg_matches, "report_dir", "indexer");
+    let call_cache_size = arg_matches
+        .value_of("call_cache_size")
+        .map(|size| abort_if_err(size.parse::<usize>()))
+        .unwrap_or(DEFAULT_CALL_CACHE_SIZE);
+    let cache_size = |name| {
+        arg_matches
+            .value_of(name)
+            .map(|size| abort_if_err(size.parse::<usize>()))
+            .unwrap_or(DEFAULT_CHAIN_CACHE_SIZE)
+    };
+    let cache_sizes = (
+        cache_size("block_cache_size"),
+        cache_size("state_cache_size"),
+        cache_size("receipt_cache_size"),
+        cache_size("code_cache_size"),
+    );
+    let screening_rules = arg_matches.value_of("screening_rules").map(|path| {
+        RuleScreen::load(path).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        })
+    });
+    #[cfg(feature = "screening")]
+    let screening_url = arg_matches.value_of("screening_url").map(|url| {
+        let service = HttpScreen::new(url);
+        if arg_matches.is_present("screening_simulate") {
+            service.with_simulation()
+        } else {
+            service
+        }
+    });
+    #[cfg(not(feature = "screening"))]
+    {
+        require_feature(arg_matches, "screening_url", "screening");
+        require_feature(arg_matches, "screening_simulate", "screening");
+    }
+    #[cfg(feature = "privacy")]
+    let privacy_manager = arg_matches
+        .value_of("privacy_manager")
+        .map(PrivacyManager::new);
+    #[cfg(not(feature = "privacy"))]
+    require_feature(arg_matches, "privacy_manager", "privacy");
+    let family_version = arg_matches
+        .value_of("family_version")
+        .unwrap_or(DEFAULT_FAMILY_VERSION);
+    let contracts = arg_matches
+        .value_of("abi_dir")
+        .map(|dir| match ContractRegistry::load_dir(dir) {
+            Ok(contracts) => contracts,
+            Err(error) => {
+                eprintln!("{}", error);
+                process::exit(1);
+            }
+        })
+        .unwrap_or_default();
+    let gas_cap = arg_matches
+        .value_of("gas_cap")
+        .map(|gas| abort_if_err(gas.parse::<u64>()))
+        .unwrap_or(vm::DEFAULT_GAS_CAP);
+    let name_registry = arg_matches.value_of("name_registry").map(String::from);
+    let finality_depth = arg_matches
+        .value_of("finality_depth")
+        .map(|depth| abort_if_err(depth.parse::<u64>()));
+    #[cfg(feature = "debug")]
+    let shadow_fork = arg_matches.value_of("shadow_fork").map(|fork| {
+        vm::Fork::parse(fork).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        })
+    });
+    #[cfg(not(feature = "debug"))]
+    require_feature(arg_matches, "shadow_fork", "debug");
+    let balancing = arg_matches
+        .value_of("balancing")
+        .map(|balancing| {
+            Balancing::parse(balancing).unwrap_or_else(|error| {
+                eprintln!("{}", error);
+                process::exit(1);
+            })
+        })
+        .unwrap_or(Balancing::Latency);
+    let mock_chain = arg_matches.is_present("mock_chain");
+    let mut accounts: Vec<Account> = arg_matches
         .values_of_lossy("unlock")
-        .unwrap_or_else(Vec::new)
+        .unwrap_or_default()
         .iter()
         .map(|alias| abort_if_err(Account::load_from_file(alias, &None)))
         .collect();
+    // The accounts that sent the mock chain's transactions can send others
+    if mock_chain {
+        accounts.extend(MockChain::accounts());
+    }
 
     for account in &accounts {
         println!("{} unlocked: {}", account.alias(), account.address());
     }
+    #[cfg(feature = "ledger")]
+    let ledger = arg_matches.value_of("ledger").map(|device| {
+        let count = arg_matches
+            .value_of("ledger_accounts")
+            .map(|count| abort_if_err(count.parse::<u32>()))
+            .unwrap_or(1);
+        let ledger = Ledger::open(device, count).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        });
+        for account in ledger.accounts() {
+            println!("Ledger account: {}", account.address());
+        }
+        ledger
+    });
+    #[cfg(not(feature = "ledger"))]
+    require_feature(arg_matches, "ledger", "ledger");
+    #[cfg(feature = "indexer")]
+    let report_options = arg_matches.value_of("report_dir").map(|dir| {
+        if log_index.is_none() {
+            eprintln!("--report-dir needs --log-index");
+            process::exit(1);
+        }
+        let period = arg_matches
+            .value_of("report_period")
+            .map(|period| abort_if_err(period.parse::<u64>()))
+            .unwrap_or(DEFAULT_REPORT_PERIOD);
+        if period == 0 {
+            eprintln!("--report-period must be more than 0");
+            process::exit(1);
+        }
+        let signer = arg_matches
+            .value_of("report_signer")
+            .map(|address| address.trim_start_matches("0x").to_lowercase())
+            .and_then(|address| {
+                accounts
+                    .iter()
+                    .find(|account| account.address() == address)
+                    .cloned()
+            })
+            .unwrap_or_else(|| {
+                eprintln!("--report-dir needs --report-signer to name an unlocked account");
+                process::exit(1);
+            });
+        (PathBuf::from(dir), period, signer)
+    });
+
+    simple_logging::log_to_stderr(settings.log_level);
+    let live = Live::new(&settings, limiter);
+    #[cfg(unix)]
+    {
+        if let Some(path) = arg_matches.value_of("config").map(String::from) {
+            let started = config::load(&path)
+                .map(|config| config::restart_only(&config))
+                .unwrap_or_default();
+            config::reload_on_hangup(live.clone(), move || {
+                let given = app().get_matches_from(env::args_os());
+                let (arg_matches, config) = with_config(&given, &path)?;
+                if config::restart_only(&config) != started {
+                    warn!(
+                        "Only the log level, rate limit and CORS origins of {} are reloaded, \
+                         the other changes take a restart",
+                        path
+                    );
+                }
+                Settings::from_matches(&arg_matches)
+            });
+        }
+    }
 
-    let vs = arg_matches.occurrences_of("verbose");
-    let log_level = match vs {
-        0 => log::LevelFilter::Warn,
-        1 => log::LevelFilter::Info,
-        2 => log::LevelFilter::Debug,
-        _ => log::LevelFilter::Trace,
+    // The mock chain never commits a block, so there are none to subscribe to
+    #[cfg(any(feature = "indexer", feature = "ws"))]
+    let subscription_urls = if mock_chain {
+        Vec::new()
+    } else {
+        connect.clone()
+    };
+    let sender = if mock_chain {
+        info!("Serving the mock chain");
+        EndpointSender::new(vec![(
+            String::from("mock chain"),
+            Connection::Mock(MockChain::new()),
+        )])
+    } else {
+        EndpointSender::new(
+            connect
+                .into_iter()
+                .map(|url| {
+                    info!("Trying to connect to validator at {}", url);
+                    let connection = ValidatorConnection::new(&url);
+                    (url, Connection::Validator(connection))
+                })
+                .collect(),
+        )
+        .with_balancing(balancing)
+    };
+    sender.start_probing();
+    let capabilities = sender.capabilities();
+    let client = ValidatorClient::new(
+        sender,
+        accounts,
+        filters,
+        call_cache_size,
+        family_version.into(),
+        contracts,
+    )
+    .with_max_batch_size(max_batch_size)
+    .with_max_response_size(max_response_size)
+    .with_max_request_memory(max_request_memory)
+    .with_gas_cap(gas_cap)
+    .with_capabilities(capabilities);
+    let client = match screening_rules {
+        Some(rules) => client.with_screen(Arc::new(rules)),
+        None => client,
+    };
+    #[cfg(feature = "screening")]
+    let client = match screening_url {
+        Some(service) => client.with_screen(Arc::new(service)),
+        None => client,
+    };
+    #[cfg(feature = "privacy")]
+    let client = match privacy_manager {
+        Some(manager) => client.with_privacy_manager(manager),
+        None => client,
+    };
+    let client = match name_registry {
+        Some(registry) => client.with_name_registry(registry),
+        None => client,
+    };
+    let client = match finality_depth {
+        Some(depth) => client.with_finality_depth(depth),
+        None => client,
+    };
+    let client = if arg_matches.is_present("verify_state_reads") {
+        client.with_state_read_verification()
+    } else {
+        client
+    };
+    let client = match cache_sizes {
+        (0, 0, 0, 0) => client,
+        (blocks, state, receipts, codes) => {
+            let cache = ChainCache::new(blocks, state, receipts, codes);
+            let client = client.with_chain_cache(cache.clone());
+            cache.start(client.clone());
+            client
+        }
+    };
+    let client = if prefetch > 0 {
+        let prefetcher = StatePrefetcher::new(prefetch);
+        let client = client.with_prefetcher(prefetcher.clone());
+        prefetcher.start(client.clone());
+        client
+    } else {
+        client
     };
-    simple_logging::log_to_stderr(log_level);
+    #[cfg(feature = "debug")]
+    let client = match shadow_fork {
+        Some(fork) => {
+            let shadow = ShadowExecutor::new(fork);
+            let client = client.with_shadow(shadow.clone());
+            shadow.start(client.clone());
+            client
+        }
+        None => client,
+    };
+    #[cfg(feature = "indexer")]
+    let client = match log_index {
+        Some(log_index) => {
+            let client = client.with_log_index(log_index.clone());
+            if let Some(jobs) = rebuild_jobs {
+                rebuild_index(&log_index, &client, jobs);
+            }
+            log_index.start(client.clone(), subscription_urls.clone());
+            if let Some((dir, period, signer)) = report_options {
+                reports::start(client.clone(), log_index, dir, period, signer);
+            }
+            client
+        }
+        None => client,
+    };
+    #[cfg(feature = "ledger")]
+    let client = match ledger {
+        Some(ledger) => client.with_ledger(ledger),
+        None => client,
+    };
+    #[cfg(feature = "ws")]
+    let subscriptions = SubscriptionManager::new(max_subscriptions);
+    #[cfg(feature = "ws")]
+    {
+        if ws.is_some() {
+            subscriptions.start(client.clone(), subscription_urls);
+        }
+    }
+    let unsupported_methods = client.unsupported_methods.clone();
+    let metrics_client = client.clone();
+    let executor = RequestExecutor::new(client, max_pending);
+    let recorder = match arg_matches.value_of("record") {
+        Some(path) => abort_if_err(Recorder::open(Path::new(path))),
+        None => Recorder::default(),
+    };
+    let middleware = (
+        recorder,
+        BatchLimits::new(max_batch_requests, max_response_size),
+        (unsupported_methods, metrics_client.metrics.clone()),
+        authenticator.clone(),
+    );
+    if let Some(path) = arg_matches.value_of("replay") {
+        replay(
+            &build_io_handler(&executor, &middleware, &apis),
+            Path::new(path),
+        );
+    }
+
+    let servers: Vec<_> = bind
+        .iter()
+        .map(|endpoint| {
+            let authenticator = authenticator.clone();
+            let server = ServerBuilder::with_meta_extractor(
+                build_io_handler(&executor, &middleware, &apis),
+                move |request: &hyper::Request<hyper::Body>| RequestMeta {
+                    session: request
+                        .headers()
+                        .get(SESSION_HEADER)
+                        .and_then(|session| session.to_str().ok())
+                        .map(String::from),
+                    client: request
+                        .headers()
+                        .get(hyper::header::USER_AGENT)
+                        .and_then(|user_agent| user_agent.to_str().ok())
+                        .map(String::from),
+                    authenticated: authenticator.check(
+                        request
+                            .headers()
+                            .get(hyper::header::AUTHORIZATION)
+                            .map(|authorization| authorization.as_bytes()),
+                    ),
+                    #[cfg(feature = "ws")]
+                    pubsub: None,
+                },
+            )
+            .threads(SERVER_THREADS)
+            .health_api(("/health", HEALTH_METHOD))
+            .request_middleware({
+                let client = metrics_client.clone();
+                let live = live.clone();
+                move |request: hyper::Request<hyper::Body>| {
+                    // Origins are checked here rather than by the server, whose
+                    // are fixed once it starts, for reloads to change them
+                    if !live.allows_origin(&request) {
+                        jsonrpc_http_server::Response::invalid_allow_origin().into()
+                    } else if request.method() == hyper::Method::GET
+                        && request.uri().path() == METRICS_PATH
+                    {
+                        metrics_response(&client).into()
+                    } else {
+                        RequestMiddlewareAction::Proceed {
+                            should_continue_on_invalid_cors: false,
+                            request,
+                        }
+                    }
+                }
+            });
+            // Behind the front the server only listens on localhost, for the
+            // connections the front accepts at the endpoint to be relayed to
+            if front.is_needed() {
+                let local = std::net::SocketAddr::from(([127, 0, 0, 1], 0));
+                let server = abort_if_err(server.start_http(&local));
+                abort_if_err(front.serve(endpoint, *server.address()));
+                let scheme = if front.serves_tls() { "https" } else { "http" };
+                info!("Starting seth-rpc on {}://{}", scheme, endpoint);
+                server
+            } else {
+                let server = server.start_http(endpoint);
+                info!("Starting seth-rpc on http://{}", endpoint);
+                abort_if_err(server)
+            }
+        })
+        .collect();
 
-    info!("Trying to connect to validator at {}", connect);
+    // Requests over the socket can't name a session, since they have no headers, and
+    // are authenticated by who may open the socket
+    let ipc_server = ipc_path.map(|path| {
+        info!("Starting seth-rpc on {}", path);
+        abort_if_err(
+            jsonrpc_ipc_server::ServerBuilder::with_meta_extractor(
+                build_io_handler(&executor, &middleware, &apis),
+                |_: &jsonrpc_ipc_server::RequestContext| RequestMeta {
+                    authenticated: true,
+                    ..RequestMeta::default()
+                },
+            )
+            .start(path),
+        )
+    });
 
-    let mut io = IoHandler::new();
-    let connection = ZmqMessageConnection::new(connect);
-    let (sender, _) = connection.create();
-    let client = ValidatorClient::new(sender, accounts);
-    let executor = RequestExecutor::new(client);
+    #[cfg(feature = "ws")]
+    let ws_servers = serve_ws(
+        &bind,
+        ws,
+        &executor,
+        &middleware,
+        &apis,
+        &subscriptions,
+        &authenticator,
+    );
+
+    ready();
+
+    for server in servers {
+        server.wait();
+    }
+    #[cfg(feature = "ws")]
+    for server in ws_servers {
+        abort_if_err(server.wait());
+    }
+    if let Some(server) = ipc_server {
+        server.wait();
+    }
+}
+
+/// Renders the metrics, along with the gauges read from the client when they're
+/// scraped
+fn metrics_response<T: MessageSender>(client: &ValidatorClient<T>) -> hyper::Response<hyper::Body> {
+    let available = if client.breaker.is_open() { 0.0 } else { 1.0 };
+    let body = client.metrics.render(&[
+        (
+            "seth_rpc_filters",
+            "Installed filters",
+            client.filters.count().unwrap_or(0) as f64,
+        ),
+        (
+            "seth_rpc_txpool_transactions",
+            "Transactions sent that aren't in a block yet",
+            client.txpool.count() as f64,
+        ),
+        (
+            "seth_rpc_validator_available",
+            "1 unless requests to the validator are failing fast",
+            available,
+        ),
+    ]);
+    hyper::Response::builder()
+        .header(hyper::header::CONTENT_TYPE, CONTENT_TYPE)
+        .body(hyper::Body::from(body))
+        .unwrap_or_default()
+}
+
+/// Replays the requests recorded in a file, printing each that is answered
+/// differently, and exits, with an error if any were
+fn replay(io: &MetaIoHandler<RequestMeta, Middleware>, path: &Path) -> ! {
+    let file = abort_if_err(File::open(path));
+    let replay = abort_if_err(recording::replay(io, BufReader::new(file)));
+    for difference in &replay.differences {
+        println!(
+            "{{\"request\": {}, \"recorded\": {}, \"replayed\": {}}}",
+            difference.request, difference.recorded, difference.replayed
+        );
+    }
+    eprintln!(
+        "Replayed {} requests, {} answered differently, skipped {} with secrets",
+        replay.replayed,
+        replay.differences.len(),
+        replay.skipped
+    );
+    process::exit(if replay.differences.is_empty() { 0 } else { 1 });
+}
+
+/// Rebuilds the log index and exits, with an error if the rebuild failed
+#[cfg(feature = "indexer")]
+fn rebuild_index<T>(log_index: &LogIndex, client: &ValidatorClient<T>, jobs: usize) -> !
+where
+    T: MessageSender + Clone + Sync + Send + 'static,
+{
+    match log_index.rebuild(client, jobs) {
+        Ok(head) => {
+            eprintln!("Rebuilt the log index up to block {}", head);
+            process::exit(0);
+        }
+        Err(error) => {
+            eprintln!("Failed to rebuild the log index: {}", error);
+            process::exit(1);
+        }
+    }
+}
 
-    let methods = get_method_list();
-    for (name, method) in methods {
+fn build_io_handler<T>(
+    executor: &RequestExecutor<T>,
+    middleware: &Middleware,
+    apis: &[String],
+) -> MetaIoHandler<RequestMeta, Middleware>
+where
+    T: MessageSender + Clone + Sync + Send + 'static,
+{
+    let mut io = MetaIoHandler::with_middleware(middleware.clone());
+    // Health checks are served whatever namespaces are
+    let served = |name: &str| {
+        name == HEALTH_METHOD || apis.iter().any(|api| name.split('_').next() == Some(api))
+    };
+    for (name, method) in get_method_list()
+        .into_iter()
+        .filter(|(name, _)| served(name))
+    {
         let clone = executor.clone();
-        io.add_method(&name, move |params: Params| clone.run(params, method));
+        let priority = Priority::of(&versions::canonical(&name));
+        io.add_method_with_meta(&name, move |params: Params, meta: RequestMeta| {
+            clone.run(params, meta, method, priority)
+        });
     }
+    io
+}
 
-    let endpoint: std::net::SocketAddr = bind.parse().unwrap();
-    let server = ServerBuilder::new(io)
-        .threads(SERVER_THREADS)
-        .start_http(&endpoint)
-        .unwrap();
+/// Where and how many clients to serve over WebSocket
+#[cfg(feature = "ws")]
+struct WsConfig {
+    port: u16,
+    max_connections: usize,
+    max_send_buffer: usize,
+}
 
-    info!("Starting seth-rpc on http://{}", bind);
+/// Starts serving over WebSocket on the port at each host of `bind`
+#[cfg(feature = "ws")]
+fn serve_ws<T>(
+    bind: &[std::net::SocketAddr],
+    ws: Option<WsConfig>,
+    executor: &RequestExecutor<T>,
+    middleware: &Middleware,
+    apis: &[String],
+    subscriptions: &SubscriptionManager,
+    authenticator: &Authenticator,
+) -> Vec<jsonrpc_ws_server::Server>
+where
+    T: MessageSender + Clone + Sync + Send + 'static,
+{
+    // Connections get their own session, which their subscriptions are sent to, but
+    // can't name an `X-Seth-Session`. The requests of a connection can't carry
+    // credentials, so with authentication on, handshakes without them are refused.
+    // A connection whose messages back up past the send buffer is closed, rather than
+    // queueing notifications for it without bound.
+    match ws {
+        Some(ws) => bind
+            .iter()
+            .map(|endpoint| {
+                let endpoint = std::net::SocketAddr::new(endpoint.ip(), ws.port);
+                let authenticator = authenticator.clone();
+                let server = jsonrpc_ws_server::ServerBuilder::with_meta_extractor(
+                    build_pubsub_handler(executor, middleware, subscriptions, apis),
+                    |context: &jsonrpc_ws_server::RequestContext| RequestMeta {
+                        session: None,
+                        client: None,
+                        authenticated: true,
+                        pubsub: Some(Arc::new(Session::new(context.sender()))),
+                    },
+                )
+                .max_connections(ws.max_connections)
+                .max_out_buffer_capacity(ws.max_send_buffer)
+                .request_middleware(move |request: &jsonrpc_ws_server::ws::Request| {
+                    let authorization = request.header("authorization").map(Vec::as_slice);
+                    if !authenticator.is_enabled() || authenticator.check(authorization) {
+                        None
+                    } else {
+                        Some(jsonrpc_ws_server::ws::Response::new(
+                            401,
+                            "Unauthorized",
+                            Vec::new(),
+                        ))
+                    }
+                })
+                .start(&endpoint);
+                info!("Starting seth-rpc on ws://{}", endpoint);
+                abort_if_err(server)
+            })
+            .collect(),
+        None => Vec::new(),
+    }
+}
 
-    server.wait();
+/// Serves the same methods as `build_io_handler`, along with `eth_subscribe` if the
+/// eth namespace is served
+#[cfg(feature = "ws")]
+fn build_pubsub_handler<T>(
+    executor: &RequestExecutor<T>,
+    middleware: &Middleware,
+    subscriptions: &SubscriptionManager,
+    apis: &[String],
+) -> PubSubHandler<RequestMeta, Middleware>
+where
+    T: MessageSender + Clone + Sync + Send + 'static,
+{
+    let mut io = PubSubHandler::new(build_io_handler(executor, middleware, apis));
+    if !apis.iter().any(|api| api == "eth") {
+        return io;
+    }
+    let subscribe = subscriptions.clone();
+    let unsubscribe = subscriptions.clone();
+    io.add_subscription(
+        "eth_subscription",
+        ("eth_subscribe", move |params: Params, meta, subscriber| {
+            subscribe.subscribe(params, meta, subscriber)
+        }),
+        ("eth_unsubscribe", move |id, _: Option<RequestMeta>| {
+            future::ready(unsubscribe.unsubscribe(id))
+        }),
+    );
+    io
 }
 
 fn get_method_list<T>() -> Vec<(String, RequestHandler<T>)>
@@ -139,22 +1197,124 @@ where
 {
     let mut methods: Vec<(String, RequestHandler<T>)> = Vec::new();
 
-    methods.extend(account::get_method_list().into_iter());
-    methods.extend(block::get_method_list().into_iter());
-    methods.extend(logs::get_method_list().into_iter());
-    methods.extend(network::get_method_list().into_iter());
-    methods.extend(transaction::get_method_list().into_iter());
-    methods.extend(personal::get_method_list().into_iter());
-    methods.extend(seth::get_method_list().into_iter());
+    methods.extend(account::get_method_list());
+    methods.extend(block::get_method_list());
+    methods.extend(logs::get_method_list());
+    methods.extend(network::get_method_list());
+    methods.extend(transaction::get_method_list());
+    methods.extend(calls::txpool::get_method_list());
+    #[cfg(feature = "personal")]
+    methods.extend(personal::get_method_list());
+    methods.extend(seth::get_method_list());
+    methods.extend(web3::get_method_list());
+    #[cfg(feature = "debug")]
+    methods.extend(debug::get_method_list());
+
+    versions::with_versions(methods)
+}
 
-    methods
+/// Returns the namespaces of methods that are served, all of them unless --rpc-api
+/// names the ones to serve or the ones not to
+fn rpc_apis(arg_matches: &ArgMatches) -> Vec<String> {
+    let listed: Vec<&str> = match arg_matches.value_of("rpc_api") {
+        Some(apis) => apis
+            .split(',')
+            .map(str::trim)
+            .filter(|api| !api.is_empty())
+            .collect(),
+        None => return RPC_APIS.iter().map(|api| String::from(*api)).collect(),
+    };
+    if let Some(unknown) = listed
+        .iter()
+        .find(|api| !RPC_APIS.contains(&api.trim_start_matches('-')))
+    {
+        eprintln!(
+            "Unknown namespace {} in --rpc-api, the namespaces are {}",
+            unknown,
+            RPC_APIS.join(", ")
+        );
+        process::exit(1);
+    }
+    let left_out: Vec<&str> = listed
+        .iter()
+        .filter_map(|api| api.strip_prefix('-'))
+        .collect();
+    match left_out.len() {
+        0 => listed.iter().map(|api| String::from(*api)).collect(),
+        count if count == listed.len() => RPC_APIS
+            .iter()
+            .filter(|api| !left_out.contains(api))
+            .map(|api| String::from(*api))
+            .collect(),
+        _ => {
+            eprintln!("--rpc-api names either the namespaces to serve or the ones not to");
+            process::exit(1);
+        }
+    }
+}
+
+/// Returns what checks the credentials of --auth-tokens and --jwt-secret
+fn authenticator(arg_matches: &ArgMatches) -> Authenticator {
+    let read = |arg| {
+        arg_matches.value_of(arg).map(|path| {
+            std::fs::read_to_string(path).unwrap_or_else(|error| {
+                eprintln!("Failed to read {}: {}", path, error);
+                process::exit(1);
+            })
+        })
+    };
+    let tokens = read("auth_tokens")
+        .map(|tokens| {
+            tokens
+                .lines()
+                .map(|token| String::from(token.trim()))
+                .collect()
+        })
+        .unwrap_or_default();
+    let jwt_secret = read("jwt_secret").map(|secret| secret.trim().as_bytes().to_vec());
+    let methods = arg_matches
+        .value_of("auth_methods")
+        .unwrap_or(auth::DEFAULT_RESTRICTED_METHODS)
+        .split(',')
+        .map(str::trim)
+        .filter(|method| !method.is_empty())
+        .map(String::from)
+        .collect();
+    let authenticator = Authenticator::new(tokens, jwt_secret, methods);
+    if arg_matches.is_present("auth_methods") && !authenticator.is_enabled() {
+        eprintln!("--auth-methods needs --auth-tokens or --jwt-secret");
+        process::exit(1);
+    }
+    authenticator
+}
+
+/// Exits if an argument is given that needs a feature seth-rpc was built without
+#[cfg(not(all(
+    feature = "debug",
+    feature = "indexer",
+    feature = "ledger",
+    feature = "postgres",
+    feature = "privacy",
+    feature = "screening",
+    feature = "tls",
+    feature = "ws"
+)))]
+fn require_feature(arg_matches: &ArgMatches, arg: &str, feature: &str) {
+    if arg_matches.is_present(arg) {
+        eprintln!(
+            "--{} needs seth-rpc to be built with the `{}` feature",
+            arg.replace('_', "-"),
+            feature
+        );
+        process::exit(1);
+    }
 }
 
 fn abort_if_err<T, E: std::error::Error>(r: Result<T, E>) -> T {
     match r {
         Ok(t) => t,
         Err(error) => {
-            eprintln!("{}", error.description());
+            eprintln!("{}", error);
             process::exit(1);
         }
     }
@@ -0,0 +1,125 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Approximate accounting of the memory a request holds while it is served, in the
+//! things that grow with what it asks for: the steps of traces, the logs and
+//! values of responses, and the blocks read for them. A request that goes over its
+//! limit is failed before one pathological query runs the server out of memory.
+
+use calls::error;
+use jsonrpc_core::{Error, Value};
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// The memory a request holds, charged by what its handler builds. Clones share it.
+#[derive(Clone, Debug)]
+pub struct MemoryBudget {
+    used: Arc<AtomicUsize>,
+    limit: usize,
+}
+
+impl Default for MemoryBudget {
+    fn default() -> Self {
+        MemoryBudget::new(usize::MAX)
+    }
+}
+
+impl MemoryBudget {
+    pub fn new(limit: usize) -> Self {
+        MemoryBudget {
+            used: Arc::new(AtomicUsize::new(0)),
+            limit,
+        }
+    }
+
+    /// Charges `bytes`, failing once more than the limit is charged. What was
+    /// charged stays charged, so that the request keeps failing.
+    pub fn charge(&self, bytes: usize) -> Result<(), Error> {
+        let used = self.used.fetch_add(bytes, Ordering::Relaxed);
+        if used.saturating_add(bytes) > self.limit {
+            // Only warned of once, when the limit is crossed
+            if used <= self.limit {
+                warn!(
+                    "Failing a request that needed more than {} bytes",
+                    self.limit
+                );
+            }
+            return Err(error::memory_limit_exceeded(self.limit));
+        }
+        Ok(())
+    }
+
+    /// Charges the size of values a handler holds
+    pub fn charge_values(&self, values: &[Value]) -> Result<(), Error> {
+        self.charge(values.iter().map(value_size).sum())
+    }
+
+    /// Fails if more than the limit was charged
+    #[cfg(any(test, feature = "debug"))]
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_exceeded() {
+            Err(error::memory_limit_exceeded(self.limit))
+        } else {
+            Ok(())
+        }
+    }
+
+    #[cfg(any(test, feature = "debug"))]
+    pub fn is_exceeded(&self) -> bool {
+        self.used.load(Ordering::Relaxed) > self.limit
+    }
+}
+
+/// Returns about how many bytes a JSON value takes in memory, with what it points to
+pub fn value_size(value: &Value) -> usize {
+    mem::size_of::<Value>()
+        + match *value {
+            Value::String(ref string) => string.len(),
+            Value::Array(ref values) => values.iter().map(value_size).sum(),
+            // Each entry is a node of a map, with its key
+            Value::Object(ref entries) => entries
+                .iter()
+                .map(|(key, value)| 2 * mem::size_of::<usize>() + key.len() + value_size(value))
+                .sum(),
+            _ => 0,
+        }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fails_past_the_limit() {
+        let budget = MemoryBudget::new(100);
+        let clone = budget.clone();
+        assert!(budget.charge(60).is_ok());
+        assert!(clone.charge(40).is_ok());
+        assert!(budget.check().is_ok());
+        assert_eq!(clone.charge(1), Err(error::memory_limit_exceeded(100)));
+        assert!(budget.is_exceeded());
+        assert!(budget.check().is_err());
+
+        let string = Value::from("abc");
+        assert_eq!(value_size(&string), mem::size_of::<Value>() + 3);
+        assert_eq!(
+            value_size(&Value::from(vec![string.clone(), string])),
+            3 * mem::size_of::<Value>() + 6
+        );
+    }
+}
@@ -15,6 +15,7 @@
  * ------------------------------------------------------------------------------
  */
 
+pub mod block_info;
 pub mod seth;
 
 use messages::seth::EvmPermissions;
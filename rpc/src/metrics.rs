@@ -0,0 +1,371 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Counters and histograms of what seth-rpc does, served at `/metrics` in the text
+//! format Prometheus scrapes: the calls to each method and how long they took, how
+//! long the validator took to answer, and the calls run in the EVM.
+
+use futures::future::{Either, Future, FutureExt};
+use jsonrpc_core::{BoxFuture, Call, ErrorCode, Middleware, Output, Request, Response};
+use requests::RequestMeta;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The path metrics are served at
+pub const METRICS_PATH: &str = "/metrics";
+
+/// The content type of the text format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// The upper bounds, in seconds, of the buckets durations are counted in
+const DURATION_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// How many methods are counted at most. Only served methods are counted, so this
+/// only keeps the output bounded.
+const MAX_METHODS: usize = 1024;
+
+/// The counts of values that fell in each bucket, with their sum
+#[derive(Clone, Default)]
+struct Histogram {
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; DURATION_BUCKETS.len()];
+        }
+        for (bucket, bound) in self.buckets.iter_mut().zip(DURATION_BUCKETS) {
+            if value <= *bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    /// Writes the series of the histogram, each with `labels`
+    fn write(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        for (index, bound) in DURATION_BUCKETS.iter().enumerate() {
+            let count = self.buckets.get(index).cloned().unwrap_or(0);
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}{}le=\"{}\"}} {}",
+                name, labels, separator, bound, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{}{}le=\"+Inf\"}} {}",
+            name, labels, separator, self.count
+        );
+        let braced = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{}}}", labels)
+        };
+        let _ = writeln!(out, "{}_sum{} {}", name, braced, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, braced, self.count);
+    }
+}
+
+#[derive(Clone, Default)]
+struct MethodMetrics {
+    errors: u64,
+    duration: Histogram,
+}
+
+#[derive(Default)]
+struct Registry {
+    methods: BTreeMap<String, MethodMetrics>,
+    /// Round trips to the validator by the type of the request, with how many failed
+    validator: BTreeMap<String, (Histogram, u64)>,
+    evm_duration: Histogram,
+    evm_gas_used: u64,
+    state_mismatches: u64,
+}
+
+/// Collects the metrics. Clones share them.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    /// Records a call to a method that took `duration` and failed if `failed`
+    pub fn record_call(&self, method: &str, duration: Duration, failed: bool) {
+        let mut registry = self.registry.lock().unwrap();
+        if registry.methods.len() >= MAX_METHODS && !registry.methods.contains_key(method) {
+            return;
+        }
+        let metrics = registry.methods.entry(String::from(method)).or_default();
+        metrics.duration.observe(duration.as_secs_f64());
+        if failed {
+            metrics.errors += 1;
+        }
+    }
+
+    /// Records a request of some type the validator answered, or didn't, after
+    /// `duration`
+    pub fn record_validator_request(&self, request: &str, duration: Duration, answered: bool) {
+        let mut registry = self.registry.lock().unwrap();
+        let (duration_seconds, failures) =
+            registry.validator.entry(String::from(request)).or_default();
+        duration_seconds.observe(duration.as_secs_f64());
+        if !answered {
+            *failures += 1;
+        }
+    }
+
+    /// Records a call run in the EVM that took `duration` and used `gas_used`
+    pub fn record_execution(&self, duration: Duration, gas_used: u64) {
+        let mut registry = self.registry.lock().unwrap();
+        registry.evm_duration.observe(duration.as_secs_f64());
+        registry.evm_gas_used += gas_used;
+    }
+
+    /// Records a read of state that didn't match the proof of it
+    pub fn record_state_mismatch(&self) {
+        self.registry.lock().unwrap().state_mismatches += 1;
+    }
+
+    /// Renders the metrics in the text format, followed by `gauges`, each a name, its
+    /// help and its value, read when the metrics are scraped
+    pub fn render(&self, gauges: &[(&str, &str, f64)]) -> String {
+        let registry = self.registry.lock().unwrap();
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "seth_rpc_requests_total",
+            "Calls to each method",
+            "counter",
+        );
+        for (method, metrics) in &registry.methods {
+            let _ = writeln!(
+                out,
+                "seth_rpc_requests_total{{method=\"{}\"}} {}",
+                escape(method),
+                metrics.duration.count
+            );
+        }
+        header(
+            &mut out,
+            "seth_rpc_request_errors_total",
+            "Calls to each method that returned an error",
+            "counter",
+        );
+        for (method, metrics) in &registry.methods {
+            let _ = writeln!(
+                out,
+                "seth_rpc_request_errors_total{{method=\"{}\"}} {}",
+                escape(method),
+                metrics.errors
+            );
+        }
+        header(
+            &mut out,
+            "seth_rpc_request_duration_seconds",
+            "How long calls to each method took",
+            "histogram",
+        );
+        for (method, metrics) in &registry.methods {
+            metrics.duration.write(
+                &mut out,
+                "seth_rpc_request_duration_seconds",
+                &format!("method=\"{}\"", escape(method)),
+            );
+        }
+
+        header(
+            &mut out,
+            "seth_rpc_validator_request_duration_seconds",
+            "How long the validator took to answer each type of request",
+            "histogram",
+        );
+        for (request, (duration, _)) in &registry.validator {
+            duration.write(
+                &mut out,
+                "seth_rpc_validator_request_duration_seconds",
+                &format!("type=\"{}\"", escape(request)),
+            );
+        }
+        header(
+            &mut out,
+            "seth_rpc_validator_request_failures_total",
+            "Requests of each type the validator didn't answer",
+            "counter",
+        );
+        for (request, &(_, failures)) in &registry.validator {
+            let _ = writeln!(
+                out,
+                "seth_rpc_validator_request_failures_total{{type=\"{}\"}} {}",
+                escape(request),
+                failures
+            );
+        }
+
+        header(
+            &mut out,
+            "seth_rpc_evm_execution_duration_seconds",
+            "How long calls run in the EVM of seth-rpc took",
+            "histogram",
+        );
+        registry
+            .evm_duration
+            .write(&mut out, "seth_rpc_evm_execution_duration_seconds", "");
+        header(
+            &mut out,
+            "seth_rpc_evm_gas_used_total",
+            "Gas used by calls run in the EVM of seth-rpc",
+            "counter",
+        );
+        let _ = writeln!(out, "seth_rpc_evm_gas_used_total {}", registry.evm_gas_used);
+
+        header(
+            &mut out,
+            "seth_rpc_state_read_mismatches_total",
+            "Reads of accounts and storage that didn't match the proofs of their state",
+            "counter",
+        );
+        let _ = writeln!(
+            out,
+            "seth_rpc_state_read_mismatches_total {}",
+            registry.state_mismatches
+        );
+
+        for &(name, help, value) in gauges {
+            header(&mut out, name, help, "gauge");
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+        out
+    }
+}
+
+impl Middleware<RequestMeta> for Metrics {
+    type Future = BoxFuture<Option<Response>>;
+    type CallFuture = BoxFuture<Option<Output>>;
+
+    fn on_request<F, X>(
+        &self,
+        request: Request,
+        meta: RequestMeta,
+        next: F,
+    ) -> Either<Self::Future, X>
+    where
+        F: Fn(Request, RequestMeta) -> X + Send + Sync,
+        X: Future<Output = Option<Response>> + Send + 'static,
+    {
+        Either::Right(next(request, meta))
+    }
+
+    fn on_call<F, X>(&self, call: Call, meta: RequestMeta, next: F) -> Either<Self::CallFuture, X>
+    where
+        F: Fn(Call, RequestMeta) -> X + Send + Sync,
+        X: Future<Output = Option<Output>> + Send + 'static,
+    {
+        let method = match call {
+            Call::MethodCall(ref call) => call.method.clone(),
+            _ => return Either::Right(next(call, meta)),
+        };
+        let metrics = self.clone();
+        let started = Instant::now();
+        Either::Left(
+            next(call, meta)
+                .map(move |output| {
+                    // Calls to methods that aren't served are counted by
+                    // `UnsupportedMethods`, and their names are up to clients
+                    let failed = match output {
+                        Some(Output::Failure(ref failure))
+                            if failure.error.code == ErrorCode::MethodNotFound =>
+                        {
+                            return output;
+                        }
+                        Some(Output::Failure(_)) => true,
+                        _ => false,
+                    };
+                    metrics.record_call(&method, started.elapsed(), failed);
+                    output
+                })
+                .boxed(),
+        )
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Escapes a label value
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_counters_and_histograms() {
+        let metrics = Metrics::new();
+        metrics.record_call("eth_call", Duration::from_millis(3), false);
+        metrics.record_call("eth_call", Duration::from_millis(200), true);
+        metrics.record_validator_request(
+            "client_state_get_request",
+            Duration::from_millis(2),
+            true,
+        );
+        metrics.record_execution(Duration::from_millis(1), 21_000);
+        metrics.record_state_mismatch();
+
+        let rendered = metrics.render(&[("seth_rpc_filters", "Installed filters", 2.0)]);
+        let lines: Vec<&str> = rendered.lines().collect();
+        for expected in &[
+            "# TYPE seth_rpc_requests_total counter",
+            "seth_rpc_requests_total{method=\"eth_call\"} 2",
+            "seth_rpc_request_errors_total{method=\"eth_call\"} 1",
+            "seth_rpc_request_duration_seconds_bucket{method=\"eth_call\",le=\"0.005\"} 1",
+            "seth_rpc_request_duration_seconds_bucket{method=\"eth_call\",le=\"0.25\"} 2",
+            "seth_rpc_request_duration_seconds_bucket{method=\"eth_call\",le=\"+Inf\"} 2",
+            "seth_rpc_request_duration_seconds_count{method=\"eth_call\"} 2",
+            "seth_rpc_validator_request_duration_seconds_count{type=\"client_state_get_request\"} 1",
+            "seth_rpc_validator_request_failures_total{type=\"client_state_get_request\"} 0",
+            "seth_rpc_evm_execution_duration_seconds_count 1",
+            "seth_rpc_evm_gas_used_total 21000",
+            "seth_rpc_state_read_mismatches_total 1",
+            "# TYPE seth_rpc_filters gauge",
+            "seth_rpc_filters 2",
+        ] {
+            assert!(lines.contains(expected), "{} is missing", expected);
+        }
+    }
+}
@@ -0,0 +1,248 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Keys derived from BIP-39 mnemonics as BIP-32 describes, along the BIP-44 path of
+//! Ethereum accounts, m/44'/60'/0'/0/i, which MetaMask and other wallets derive
+//! theirs along. A mnemonic made by either gives the other the same keys.
+
+use accounts::compressed_public_key;
+use crypto::digest::Digest;
+use crypto::hmac::Hmac;
+use crypto::mac::Mac;
+use crypto::pbkdf2::pbkdf2;
+use crypto::sha2::{Sha256, Sha512};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use secp256k1::key::{PublicKey, SecretKey};
+use secp256k1::Secp256k1;
+
+/// The English wordlist of BIP-39, which wallets make mnemonics from by default
+const WORDLIST: &str = include_str!("english.txt");
+
+/// The numbers of words a mnemonic can have, each word giving 11 bits of which one
+/// in 33 is checksum
+pub const WORD_COUNTS: &[usize] = &[12, 15, 18, 21, 24];
+
+/// The bit that marks a BIP-32 index as hardened
+pub const HARDENED: u32 = 1 << 31;
+
+/// The path down to the keys of Ethereum accounts, m/44'/60'/0'/0
+pub const ETHEREUM_PATH: &[u32] = &[44 | HARDENED, 60 | HARDENED, HARDENED, 0];
+
+/// The rounds of PBKDF2 a mnemonic is stretched into a seed with
+const SEED_ROUNDS: u32 = 2048;
+
+/// Returns a new mnemonic of `word_count` words from the system's randomness
+pub fn generate(word_count: usize) -> Result<String, String> {
+    if !WORD_COUNTS.contains(&word_count) {
+        return Err(String::from("Mnemonics have 12, 15, 18, 21 or 24 words"));
+    }
+    let mut entropy = vec![0; word_count * 4 / 3];
+    OsRng.fill_bytes(&mut entropy);
+    Ok(from_entropy(&entropy))
+}
+
+/// Returns the mnemonic of some entropy, which is its bits and then those of the
+/// start of its SHA-256 hash, in words of 11 bits each
+fn from_entropy(entropy: &[u8]) -> String {
+    let words: Vec<&str> = WORDLIST.lines().collect();
+    let bits = with_checksum(entropy);
+    bits.chunks(11)
+        .map(|word| words[word.iter().fold(0, |index, &bit| index << 1 | bit as usize)])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn with_checksum(entropy: &[u8]) -> Vec<bool> {
+    let mut hash = [0; 32];
+    let mut sha = Sha256::new();
+    sha.input(entropy);
+    sha.result(&mut hash);
+    let bit = |bytes: &[u8], i: usize| bytes[i / 8] & (0x80 >> (i % 8)) != 0;
+    (0..entropy.len() * 8)
+        .map(|i| bit(entropy, i))
+        .chain((0..entropy.len() / 4).map(|i| bit(&hash, i)))
+        .collect()
+}
+
+/// Checks that a mnemonic's words are all in the wordlist and that its checksum
+/// matches, and returns it with its words lowercase and separated by single spaces
+/// as its seed is derived from
+pub fn normalize(mnemonic: &str) -> Result<String, String> {
+    let words: Vec<&str> = WORDLIST.lines().collect();
+    let given: Vec<String> = mnemonic.split_whitespace().map(str::to_lowercase).collect();
+    if !WORD_COUNTS.contains(&given.len()) {
+        return Err(format!(
+            "Mnemonics have 12, 15, 18, 21 or 24 words, not {}",
+            given.len()
+        ));
+    }
+    let mut bits = Vec::with_capacity(given.len() * 11);
+    for word in &given {
+        let index = words
+            .binary_search(&word.as_str())
+            .map_err(|_| format!("`{}` isn't a word of the BIP-39 English wordlist", word))?;
+        bits.extend((0..11).rev().map(|i| index & (1 << i) != 0));
+    }
+    let entropy: Vec<u8> = bits[..given.len() * 32 / 3]
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0, |byte, &bit| byte << 1 | bit as u8))
+        .collect();
+    if with_checksum(&entropy) != bits {
+        return Err(String::from("The mnemonic's checksum doesn't match"));
+    }
+    Ok(given.join(" "))
+}
+
+/// Returns the seed of a normalized mnemonic and passphrase
+fn seed(mnemonic: &str, passphrase: &str) -> [u8; 64] {
+    let mut mac = Hmac::new(Sha512::new(), mnemonic.as_bytes());
+    let mut seed = [0; 64];
+    pbkdf2(
+        &mut mac,
+        format!("mnemonic{}", passphrase).as_bytes(),
+        SEED_ROUNDS,
+        &mut seed,
+    );
+    seed
+}
+
+/// Returns the private keys of the first `count` Ethereum accounts of a mnemonic
+pub fn derive_keys(mnemonic: &str, count: u32) -> Result<Vec<Vec<u8>>, String> {
+    let secp = Secp256k1::new();
+    let seed = seed(&normalize(mnemonic)?, "");
+    let (master_key, master_chain_code) = split(&hmac_sha512(b"Bitcoin seed", &seed));
+    let mut parent = (
+        SecretKey::from_slice(&secp, &master_key).map_err(|_| invalid_key())?,
+        master_chain_code,
+    );
+    for &index in ETHEREUM_PATH {
+        parent = child(&secp, &parent, index)?;
+    }
+    (0..count)
+        .map(|index| child(&secp, &parent, index).map(|(key, _)| key[..].to_vec()))
+        .collect()
+}
+
+/// Returns the child key and chain code at an index of an extended private key
+fn child(
+    secp: &Secp256k1,
+    (key, chain_code): &(SecretKey, Vec<u8>),
+    index: u32,
+) -> Result<(SecretKey, Vec<u8>), String> {
+    let mut data = if index & HARDENED != 0 {
+        let mut data = vec![0];
+        data.extend_from_slice(&key[..]);
+        data
+    } else {
+        let public_key = PublicKey::from_secret_key(secp, key).map_err(|_| invalid_key())?;
+        compressed_public_key(secp, &public_key)
+    };
+    data.extend_from_slice(&[
+        (index >> 24) as u8,
+        (index >> 16) as u8,
+        (index >> 8) as u8,
+        index as u8,
+    ]);
+    let (tweak, chain_code) = split(&hmac_sha512(chain_code, &data));
+    let mut child = *key;
+    SecretKey::from_slice(secp, &tweak)
+        .and_then(|tweak| child.add_assign(secp, &tweak))
+        .map_err(|_| invalid_key())?;
+    Ok((child, chain_code))
+}
+
+fn hmac_sha512(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::new(Sha512::new(), key);
+    mac.input(data);
+    mac.result().code().to_vec()
+}
+
+fn split(output: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    (output[..32].to_vec(), output[32..].to_vec())
+}
+
+/// BIP-32 skips the keys whose derivation gives an invalid key, but there is about a
+/// one in 2^127 chance of one along a path, so they are refused instead
+fn invalid_key() -> String {
+    String::from("The mnemonic derives an invalid key")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transform;
+
+    #[test]
+    fn derives_the_keys_of_other_wallets() {
+        // Test vectors of BIP-39, whose seeds are derived with the passphrase TREZOR
+        let vectors = [
+            (
+                "00000000000000000000000000000000",
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+                "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+            ),
+            (
+                "9e885d952ad362caeb4efe34a8e91bd2",
+                "ozone drill grab fiber curtain grace pudding thank cruise elder eight picnic",
+                "274ddc525802f7c828d8ef7ddbcdc5304e87ac3535913611fbbfa986d0c9e5476c91689f9c8a54fd55bd38606aa6a8595ad213d4c9c9f9aca3fb217069a41028",
+            ),
+            (
+                "f585c11aec520db57dd353c69554b21a89b20fb0650966fa0a9d6f74fd989d8f",
+                "void come effort suffer camp survey warrior heavy shoot primary clutch crush open amazing screen patrol group space point ten exist slush involve unfold",
+                "01f5bced59dec48e362f2c45b5de68b9fd6c92c6634f44d6d40aab69056506f0e35524a518034ddc1192e1dacd32c1ed3eaa3c3b131c88ed8e7e54c49a5d0998",
+            ),
+        ];
+        for &(entropy, mnemonic, seed_hex) in &vectors {
+            assert_eq!(
+                from_entropy(&transform::hex_str_to_bytes(entropy).unwrap()),
+                mnemonic
+            );
+            assert_eq!(normalize(&mnemonic.to_uppercase()).unwrap(), mnemonic);
+            assert_eq!(
+                transform::bytes_to_hex_str(&seed(mnemonic, "TREZOR")),
+                seed_hex
+            );
+        }
+        assert!(normalize("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon").is_err());
+        assert!(normalize("abandon about").is_err());
+        assert_eq!(
+            generate(24)
+                .and_then(|mnemonic| normalize(&mnemonic))
+                .unwrap()
+                .split(' ')
+                .count(),
+            24
+        );
+
+        // The accounts of the mnemonic Hardhat and Foundry test with
+        let keys = derive_keys(
+            "test test test test test test test test test test test junk",
+            2,
+        )
+        .unwrap();
+        assert_eq!(
+            keys.iter()
+                .map(|key| transform::bytes_to_hex_str(key))
+                .collect::<Vec<_>>(),
+            vec![
+                "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80",
+                "59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d",
+            ]
+        );
+    }
+}
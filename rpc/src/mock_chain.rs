@@ -0,0 +1,965 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! A small chain that is the same on every run, which `--mock-chain` serves in place
+//! of a validator so that applications can be tested against seth-rpc without a
+//! network. After the genesis block, which configures PBFT, `alice` and `bob` create
+//! their accounts, `alice` deploys a counter, and `bob` increments it and then makes
+//! a call of it that reverts. Batches submitted to it are rejected.
+
+use accounts::{contract_address, Account};
+use client::{
+    account_state_address, setting_address, storage_state_address, DEFAULT_FAMILY_VERSION, SETH_NS,
+};
+use crypto::digest::Digest;
+use crypto::sha2::Sha512;
+use crypto_backend;
+use messages::block_info::{BlockInfo, BlockInfoConfig};
+use messages::seth::{
+    CreateContractAccountTxn, CreateExternalAccountTxn, EvmEntry, EvmStateAccount, EvmStorage,
+    EvmStorageEntry, MessageCallTxn, SethTransactionReceipt,
+};
+use protobuf;
+use sawtooth_sdk::messages::batch::{Batch, BatchHeader};
+use sawtooth_sdk::messages::block::{Block, BlockHeader};
+use sawtooth_sdk::messages::client_batch_submit::{
+    ClientBatchStatus, ClientBatchStatusRequest, ClientBatchStatusResponse,
+    ClientBatchStatusResponse_Status, ClientBatchStatus_Status, ClientBatchSubmitResponse,
+    ClientBatchSubmitResponse_Status,
+};
+use sawtooth_sdk::messages::client_block::{
+    ClientBlockGetByBatchIdRequest, ClientBlockGetByIdRequest, ClientBlockGetByNumRequest,
+    ClientBlockGetByTransactionIdRequest, ClientBlockGetResponse, ClientBlockGetResponse_Status,
+    ClientBlockListRequest, ClientBlockListResponse, ClientBlockListResponse_Status,
+};
+use sawtooth_sdk::messages::client_list_control::{ClientPagingControls, ClientPagingResponse};
+use sawtooth_sdk::messages::client_peers::{ClientPeersGetResponse, ClientPeersGetResponse_Status};
+use sawtooth_sdk::messages::client_receipt::{
+    ClientReceiptGetRequest, ClientReceiptGetResponse, ClientReceiptGetResponse_Status,
+};
+use sawtooth_sdk::messages::client_state::{
+    ClientStateGetRequest, ClientStateGetResponse, ClientStateGetResponse_Status,
+    ClientStateListRequest, ClientStateListResponse, ClientStateListResponse_Entry,
+    ClientStateListResponse_Status,
+};
+use sawtooth_sdk::messages::client_status::{
+    ClientStatusGetResponse, ClientStatusGetResponse_Status,
+};
+use sawtooth_sdk::messages::client_transaction::{
+    ClientTransactionGetRequest, ClientTransactionGetResponse, ClientTransactionGetResponse_Status,
+};
+use sawtooth_sdk::messages::events::{Event, Event_Attribute};
+use sawtooth_sdk::messages::setting::{Setting, Setting_Entry};
+use sawtooth_sdk::messages::transaction::{Transaction as TransactionPb, TransactionHeader};
+use sawtooth_sdk::messages::transaction_receipt::{
+    StateChange, StateChange_Type, TransactionReceipt,
+};
+use sawtooth_sdk::messages::validator::{Message, Message_MessageType};
+use sawtooth_sdk::messaging::stream::*;
+use state_proof;
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
+use std::sync::mpsc;
+use std::sync::Arc;
+use transactions::SethTransaction;
+use transform;
+use vm::{BLOCK_INFO_CONFIG_ADDRESS, BLOCK_INFO_NAMESPACE};
+
+/// The key the mock chain's blocks are signed with
+const VALIDATOR_KEY: &str = "0303030303030303030303030303030303030303030303030303030303030303";
+
+/// The aliases and keys of the accounts that send the mock chain's transactions,
+/// which are unlocked while it is served
+const ACCOUNT_KEYS: [(&str, &str); 2] = [
+    (
+        "alice",
+        "0101010101010101010101010101010101010101010101010101010101010101",
+    ),
+    (
+        "bob",
+        "0202020202020202020202020202020202020202020202020202020202020202",
+    ),
+];
+
+/// The time of the genesis block, with a block every `BLOCK_INTERVAL` seconds after it
+const GENESIS_TIMESTAMP: u64 = 1_600_000_000;
+const BLOCK_INTERVAL: u64 = 15;
+
+/// How many blocks or state entries are listed at once, unless fewer are asked for,
+/// and the most that can be asked for, as with the validator
+const DEFAULT_PAGE_SIZE: i32 = 100;
+const MAX_PAGE_SIZE: i32 = 1000;
+
+/// The event the counter logs with its new count
+const INCREMENTED_EVENT: &str = "Incremented(uint256)";
+
+struct MockBlock {
+    block: Block,
+    state_root: String,
+    receipts: Vec<TransactionReceipt>,
+}
+
+/// Answers requests for the mock chain as a validator would, except that it never
+/// commits another block
+#[derive(Clone)]
+pub struct MockChain {
+    blocks: Arc<Vec<MockBlock>>,
+    /// The state after each block, by its root
+    states: Arc<HashMap<String, BTreeMap<String, Vec<u8>>>>,
+}
+
+impl MockChain {
+    pub fn new() -> Self {
+        let accounts = MockChain::accounts();
+        let (alice, bob) = (&accounts[0], &accounts[1]);
+        let validator = Account::from_private_key("validator", VALIDATOR_KEY)
+            .expect("Invalid mock validator key");
+        let mut chain = ChainBuilder::new(validator.clone());
+
+        chain.set_setting("sawtooth.consensus.algorithm.name", "pbft");
+        chain.set_setting("sawtooth.consensus.algorithm.version", "1.0");
+        chain.set_setting(
+            "sawtooth.consensus.pbft.members",
+            &format!("[\"{}\"]", validator.public_key()),
+        );
+        chain.commit();
+
+        for account in &accounts {
+            chain.set_account(account.address(), 1, &[]);
+            chain.add_transaction(
+                account,
+                &SethTransaction::CreateExternalAccount(CreateExternalAccountTxn::new()),
+                &SethTransactionReceipt::new(),
+                Vec::new(),
+            );
+        }
+        chain.commit();
+
+        let counter = contract_address(&address_bytes(alice.address()), 1);
+        let mut create = CreateContractAccountTxn::new();
+        create.set_nonce(1);
+        create.set_gas_limit(100_000);
+        create.set_init(counter_init_code());
+        let mut receipt = SethTransactionReceipt::new();
+        receipt.set_contract_address(address_bytes(&counter));
+        receipt.set_gas_used(64_926);
+        receipt.set_return_value(counter_code());
+        chain.set_account(alice.address(), 2, &[]);
+        chain.set_account(&counter, 1, &counter_code());
+        chain.set_storage(&counter, 0, 41);
+        chain.add_transaction(
+            alice,
+            &SethTransaction::CreateContractAccount(create),
+            &receipt,
+            Vec::new(),
+        );
+        chain.commit();
+
+        let mut increment = MessageCallTxn::new();
+        increment.set_nonce(1);
+        increment.set_gas_limit(50_000);
+        increment.set_to(address_bytes(&counter));
+        let mut receipt = SethTransactionReceipt::new();
+        receipt.set_gas_used(27_503);
+        receipt.set_return_value(word(42));
+        let incremented = log_event(
+            &counter,
+            &[crypto_backend::keccak256(INCREMENTED_EVENT.as_bytes()).to_vec()],
+            word(42),
+        );
+        chain.set_storage(&counter, 0, 42);
+        chain.add_transaction(
+            bob,
+            &SethTransaction::MessageCall(increment),
+            &receipt,
+            vec![incremented],
+        );
+        // The counter reverts when it is called with any data
+        let mut revert = MessageCallTxn::new();
+        revert.set_nonce(2);
+        revert.set_gas_limit(50_000);
+        revert.set_to(address_bytes(&counter));
+        revert.set_data(vec![1]);
+        let mut receipt = SethTransactionReceipt::new();
+        receipt.set_gas_used(21_022);
+        receipt.set_reverted(true);
+        chain.set_account(bob.address(), 3, &[]);
+        chain.add_transaction(
+            bob,
+            &SethTransaction::MessageCall(revert),
+            &receipt,
+            Vec::new(),
+        );
+        chain.commit();
+
+        MockChain {
+            blocks: Arc::new(chain.blocks),
+            states: Arc::new(chain.states),
+        }
+    }
+
+    /// The accounts that send the mock chain's transactions
+    pub fn accounts() -> Vec<Account> {
+        ACCOUNT_KEYS
+            .iter()
+            .map(|&(alias, key)| {
+                Account::from_private_key(alias, key).expect("Invalid mock account key")
+            })
+            .collect()
+    }
+
+    /// Returns the type and content of the response to a request, or none for
+    /// requests the validator wouldn't answer
+    fn answer(
+        &self,
+        request_type: Message_MessageType,
+        contents: &[u8],
+    ) -> Option<(Message_MessageType, Vec<u8>)> {
+        match request_type {
+            Message_MessageType::CLIENT_BLOCK_LIST_REQUEST => Some((
+                Message_MessageType::CLIENT_BLOCK_LIST_RESPONSE,
+                encode(&self.list_blocks(&decode(contents)?)),
+            )),
+            Message_MessageType::CLIENT_BLOCK_GET_BY_ID_REQUEST => {
+                let request: ClientBlockGetByIdRequest = decode(contents)?;
+                let block = self.find_block(|block| block.header_signature == request.block_id);
+                Some(block_response(block))
+            }
+            Message_MessageType::CLIENT_BLOCK_GET_BY_NUM_REQUEST => {
+                let request: ClientBlockGetByNumRequest = decode(contents)?;
+                let block = self.blocks.get(request.block_num as usize);
+                Some(block_response(block.map(|block| &block.block)))
+            }
+            Message_MessageType::CLIENT_BLOCK_GET_BY_TRANSACTION_ID_REQUEST => {
+                let request: ClientBlockGetByTransactionIdRequest = decode(contents)?;
+                let block = self.find_block(|block| {
+                    transactions(block).any(|txn| txn.header_signature == request.transaction_id)
+                });
+                Some(block_response(block))
+            }
+            Message_MessageType::CLIENT_BLOCK_GET_BY_BATCH_ID_REQUEST => {
+                let request: ClientBlockGetByBatchIdRequest = decode(contents)?;
+                let block = self.find_block(|block| {
+                    block
+                        .get_batches()
+                        .iter()
+                        .any(|batch| batch.header_signature == request.batch_id)
+                });
+                Some(block_response(block))
+            }
+            Message_MessageType::CLIENT_STATE_GET_REQUEST => Some((
+                Message_MessageType::CLIENT_STATE_GET_RESPONSE,
+                encode(&self.get_state(&decode(contents)?)),
+            )),
+            Message_MessageType::CLIENT_STATE_LIST_REQUEST => Some((
+                Message_MessageType::CLIENT_STATE_LIST_RESPONSE,
+                encode(&self.list_state(&decode(contents)?)),
+            )),
+            Message_MessageType::CLIENT_RECEIPT_GET_REQUEST => Some((
+                Message_MessageType::CLIENT_RECEIPT_GET_RESPONSE,
+                encode(&self.get_receipts(&decode(contents)?)),
+            )),
+            Message_MessageType::CLIENT_TRANSACTION_GET_REQUEST => Some((
+                Message_MessageType::CLIENT_TRANSACTION_GET_RESPONSE,
+                encode(&self.get_transaction(&decode(contents)?)),
+            )),
+            Message_MessageType::CLIENT_BATCH_SUBMIT_REQUEST => {
+                let mut response = ClientBatchSubmitResponse::new();
+                response.set_status(ClientBatchSubmitResponse_Status::INVALID_BATCH);
+                Some((
+                    Message_MessageType::CLIENT_BATCH_SUBMIT_RESPONSE,
+                    encode(&response),
+                ))
+            }
+            Message_MessageType::CLIENT_BATCH_STATUS_REQUEST => {
+                let request: ClientBatchStatusRequest = decode(contents)?;
+                Some((
+                    Message_MessageType::CLIENT_BATCH_STATUS_RESPONSE,
+                    encode(&batch_statuses(&request)),
+                ))
+            }
+            Message_MessageType::CLIENT_PEERS_GET_REQUEST => {
+                let mut response = ClientPeersGetResponse::new();
+                response.set_status(ClientPeersGetResponse_Status::OK);
+                Some((
+                    Message_MessageType::CLIENT_PEERS_GET_RESPONSE,
+                    encode(&response),
+                ))
+            }
+            Message_MessageType::CLIENT_STATUS_GET_REQUEST => {
+                let mut response = ClientStatusGetResponse::new();
+                response.set_status(ClientStatusGetResponse_Status::OK);
+                Some((
+                    Message_MessageType::CLIENT_STATUS_GET_RESPONSE,
+                    encode(&response),
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    fn find_block<F: Fn(&Block) -> bool>(&self, matches: F) -> Option<&Block> {
+        self.blocks
+            .iter()
+            .map(|block| &block.block)
+            .find(|block| matches(block))
+    }
+
+    /// Lists blocks from the head back to the genesis block
+    fn list_blocks(&self, request: &ClientBlockListRequest) -> ClientBlockListResponse {
+        let mut response = ClientBlockListResponse::new();
+        let head = if request.head_id.is_empty() {
+            self.blocks.len() - 1
+        } else {
+            match self
+                .blocks
+                .iter()
+                .position(|block| block.block.header_signature == request.head_id)
+            {
+                Some(head) => head,
+                None => {
+                    response.set_status(ClientBlockListResponse_Status::NO_RESOURCE);
+                    return response;
+                }
+            }
+        };
+
+        let blocks: Vec<Block> = self.blocks[..=head]
+            .iter()
+            .rev()
+            .map(|block| &block.block)
+            .filter(|block| {
+                request.block_ids.is_empty() || request.block_ids.contains(&block.header_signature)
+            })
+            .cloned()
+            .collect();
+        match page(blocks, request.get_paging(), |block| {
+            &block.header_signature
+        }) {
+            Some((blocks, paging)) => {
+                response.set_status(ClientBlockListResponse_Status::OK);
+                response.set_blocks(protobuf::RepeatedField::from_vec(blocks));
+                response.set_head_id(self.blocks[head].block.header_signature.clone());
+                response.set_paging(paging);
+            }
+            None => response.set_status(ClientBlockListResponse_Status::INVALID_PAGING),
+        }
+        response
+    }
+
+    /// Returns the state with the given root, or the latest state if none is given
+    fn state(&self, state_root: &str) -> Option<&BTreeMap<String, Vec<u8>>> {
+        if state_root.is_empty() {
+            self.blocks
+                .last()
+                .and_then(|block| self.states.get(&block.state_root))
+        } else {
+            self.states.get(state_root)
+        }
+    }
+
+    fn get_state(&self, request: &ClientStateGetRequest) -> ClientStateGetResponse {
+        let mut response = ClientStateGetResponse::new();
+        let state = match self.state(&request.state_root) {
+            Some(state) => state,
+            None => {
+                response.set_status(ClientStateGetResponse_Status::NO_ROOT);
+                return response;
+            }
+        };
+        if request.address.len() != 70 || !is_hex(&request.address) {
+            response.set_status(ClientStateGetResponse_Status::INVALID_ADDRESS);
+            return response;
+        }
+
+        match state.get(&request.address) {
+            Some(value) => {
+                response.set_status(ClientStateGetResponse_Status::OK);
+                response.set_value(value.clone());
+            }
+            None => response.set_status(ClientStateGetResponse_Status::NO_RESOURCE),
+        }
+        response
+    }
+
+    /// Lists the state entries under an address prefix, in order of address
+    fn list_state(&self, request: &ClientStateListRequest) -> ClientStateListResponse {
+        let mut response = ClientStateListResponse::new();
+        let state = match self.state(&request.state_root) {
+            Some(state) => state,
+            None => {
+                response.set_status(ClientStateListResponse_Status::NO_ROOT);
+                return response;
+            }
+        };
+        if request.address.len() > 70 || !is_hex(&request.address) {
+            response.set_status(ClientStateListResponse_Status::INVALID_ADDRESS);
+            return response;
+        }
+
+        let entries: Vec<ClientStateListResponse_Entry> = state
+            .range(request.address.clone()..)
+            .take_while(|&(address, _)| address.starts_with(&request.address))
+            .map(|(address, data)| {
+                let mut entry = ClientStateListResponse_Entry::new();
+                entry.set_address(address.clone());
+                entry.set_data(data.clone());
+                entry
+            })
+            .collect();
+        match page(entries, request.get_paging(), |entry| &entry.address) {
+            Some((entries, paging)) => {
+                response.set_status(ClientStateListResponse_Status::OK);
+                response.set_entries(protobuf::RepeatedField::from_vec(entries));
+                response.set_paging(paging);
+                response.set_state_root(match request.state_root.as_str() {
+                    "" => self.blocks[self.blocks.len() - 1].state_root.clone(),
+                    state_root => String::from(state_root),
+                });
+            }
+            None => response.set_status(ClientStateListResponse_Status::INVALID_PAGING),
+        }
+        response
+    }
+
+    /// Returns the receipts of the transactions, if all of them are committed
+    fn get_receipts(&self, request: &ClientReceiptGetRequest) -> ClientReceiptGetResponse {
+        let mut response = ClientReceiptGetResponse::new();
+        let receipts: Option<Vec<TransactionReceipt>> = request
+            .transaction_ids
+            .iter()
+            .map(|txn_id| {
+                self.blocks
+                    .iter()
+                    .flat_map(|block| block.receipts.iter())
+                    .find(|receipt| receipt.transaction_id == *txn_id)
+                    .cloned()
+            })
+            .collect();
+        match receipts {
+            Some(receipts) => {
+                response.set_status(ClientReceiptGetResponse_Status::OK);
+                response.set_receipts(protobuf::RepeatedField::from_vec(receipts));
+            }
+            None => response.set_status(ClientReceiptGetResponse_Status::NO_RESOURCE),
+        }
+        response
+    }
+
+    fn get_transaction(
+        &self,
+        request: &ClientTransactionGetRequest,
+    ) -> ClientTransactionGetResponse {
+        let mut response = ClientTransactionGetResponse::new();
+        let txn = self
+            .blocks
+            .iter()
+            .flat_map(|block| transactions(&block.block))
+            .find(|txn| txn.header_signature == request.transaction_id);
+        match txn {
+            Some(txn) => {
+                response.set_status(ClientTransactionGetResponse_Status::OK);
+                response.set_transaction(txn.clone());
+            }
+            None => response.set_status(ClientTransactionGetResponse_Status::NO_RESOURCE),
+        }
+        response
+    }
+}
+
+impl Default for MockChain {
+    fn default() -> Self {
+        MockChain::new()
+    }
+}
+
+impl MessageSender for MockChain {
+    fn send(
+        &self,
+        destination: Message_MessageType,
+        correlation_id: &str,
+        contents: &[u8],
+    ) -> Result<MessageFuture, SendError> {
+        let (sender, receiver) = mpsc::channel();
+        // Requests that aren't answered fail at once, as if they had timed out
+        if let Some((message_type, content)) = self.answer(destination, contents) {
+            let mut message = Message::new();
+            message.set_message_type(message_type);
+            message.set_correlation_id(String::from(correlation_id));
+            message.set_content(content);
+            sender
+                .send(Ok(message))
+                .map_err(|_| SendError::UnknownError)?;
+        }
+        Ok(MessageFuture::new(receiver))
+    }
+
+    fn reply(
+        &self,
+        _destination: Message_MessageType,
+        _correlation_id: &str,
+        _contents: &[u8],
+    ) -> Result<(), SendError> {
+        Ok(())
+    }
+
+    fn close(&mut self) {}
+}
+
+/// Builds the blocks of the mock chain one at a time, along with their state
+struct ChainBuilder {
+    validator: Account,
+    state: BTreeMap<String, Vec<u8>>,
+    states: HashMap<String, BTreeMap<String, Vec<u8>>>,
+    blocks: Vec<MockBlock>,
+    batches: Vec<Batch>,
+    receipts: Vec<TransactionReceipt>,
+    /// The seth state set since the last transaction was added, which its receipt
+    /// records
+    changes: Vec<StateChange>,
+}
+
+impl ChainBuilder {
+    fn new(validator: Account) -> Self {
+        ChainBuilder {
+            validator,
+            state: BTreeMap::new(),
+            states: HashMap::new(),
+            blocks: Vec::new(),
+            batches: Vec::new(),
+            receipts: Vec::new(),
+            changes: Vec::new(),
+        }
+    }
+
+    fn set_setting(&mut self, key: &str, value: &str) {
+        let mut entry = Setting_Entry::new();
+        entry.set_key(String::from(key));
+        entry.set_value(String::from(value));
+        let mut setting = Setting::new();
+        setting.set_entries(protobuf::RepeatedField::from_vec(vec![entry]));
+        self.state.insert(setting_address(key), encode(&setting));
+    }
+
+    fn set_account(&mut self, address: &str, nonce: u64, code: &[u8]) {
+        let mut account = EvmStateAccount::new();
+        account.set_address(address_bytes(address));
+        account.set_nonce(nonce);
+        account.set_code(code.to_vec());
+        let mut entry = EvmEntry::new();
+        entry.set_account(account);
+        self.set_seth_state(account_state_address(address), encode(&entry));
+    }
+
+    fn set_storage(&mut self, address: &str, key: u64, value: u64) {
+        let mut slot = EvmStorage::new();
+        slot.set_key(word(key));
+        slot.set_value(word(value));
+        let mut entry = EvmStorageEntry::new();
+        entry.set_storage(protobuf::RepeatedField::from_vec(vec![slot]));
+        self.set_seth_state(
+            storage_state_address(address, &transform::bytes_to_hex_str(&word(key))),
+            encode(&entry),
+        );
+    }
+
+    fn set_seth_state(&mut self, address: String, data: Vec<u8>) {
+        let mut change = StateChange::new();
+        change.set_address(address.clone());
+        change.set_value(data.clone());
+        change.set_field_type(StateChange_Type::SET);
+        self.changes.push(change);
+        self.state.insert(address, data);
+    }
+
+    /// Adds a transaction to the next block, in a batch of its own, with the receipt
+    /// and logs the transaction processor gave it
+    fn add_transaction(
+        &mut self,
+        signer: &Account,
+        txn: &SethTransaction,
+        receipt: &SethTransactionReceipt,
+        events: Vec<Event>,
+    ) {
+        let payload = encode(&txn.to_pb());
+        let mut sha = Sha512::new();
+        sha.input(&payload);
+        let mut header = TransactionHeader::new();
+        header.set_batcher_public_key(String::from(signer.public_key()));
+        header.set_family_name(String::from("seth"));
+        header.set_family_version(String::from(DEFAULT_FAMILY_VERSION));
+        header.set_inputs(protobuf::RepeatedField::from_vec(vec![String::from(
+            SETH_NS,
+        )]));
+        header.set_outputs(protobuf::RepeatedField::from_vec(vec![String::from(
+            SETH_NS,
+        )]));
+        header.set_payload_sha512(sha.result_str());
+        header.set_signer_public_key(String::from(signer.public_key()));
+        let header = encode(&header);
+        let mut transaction = TransactionPb::new();
+        transaction.set_header_signature(sign(signer, &header));
+        transaction.set_header(header);
+        transaction.set_payload(payload);
+        let txn_id = transaction.header_signature.clone();
+
+        let mut batch_header = BatchHeader::new();
+        batch_header.set_signer_public_key(String::from(signer.public_key()));
+        batch_header.set_transaction_ids(protobuf::RepeatedField::from_vec(vec![txn_id.clone()]));
+        let batch_header = encode(&batch_header);
+        let mut batch = Batch::new();
+        batch.set_header_signature(sign(signer, &batch_header));
+        batch.set_header(batch_header);
+        batch.set_transactions(protobuf::RepeatedField::from_vec(vec![transaction]));
+        self.batches.push(batch);
+
+        let mut txn_receipt = TransactionReceipt::new();
+        txn_receipt.set_transaction_id(txn_id);
+        txn_receipt.set_data(protobuf::RepeatedField::from_vec(vec![encode(receipt)]));
+        txn_receipt.set_events(protobuf::RepeatedField::from_vec(events));
+        txn_receipt.set_state_changes(protobuf::RepeatedField::from_vec(mem::take(
+            &mut self.changes,
+        )));
+        self.receipts.push(txn_receipt);
+    }
+
+    /// Commits the transactions added since the last block in a new block
+    fn commit(&mut self) {
+        let block_num = self.blocks.len() as u64;
+        let previous_block_id = match self.blocks.last() {
+            Some(previous) => {
+                let previous_block_id = previous.block.header_signature.clone();
+                // Like the block info injector, each block records the one before it
+                self.set_block_info(block_num - 1, &previous_block_id);
+                previous_block_id
+            }
+            None => String::from("0000000000000000"),
+        };
+        let entries = self
+            .state
+            .iter()
+            .map(|(address, data)| (address.clone(), data.clone()))
+            .collect();
+        let (state_root, _) = state_proof::prove_state(entries, &[]);
+
+        let batches = mem::take(&mut self.batches);
+        let mut header = BlockHeader::new();
+        header.set_block_num(block_num);
+        header.set_previous_block_id(previous_block_id);
+        header.set_signer_public_key(String::from(self.validator.public_key()));
+        header.set_batch_ids(
+            batches
+                .iter()
+                .map(|batch| batch.header_signature.clone())
+                .collect(),
+        );
+        header.set_state_root_hash(state_root.clone());
+        let header = encode(&header);
+        let mut block = Block::new();
+        block.set_header_signature(sign(&self.validator, &header));
+        block.set_header(header);
+        block.set_batches(protobuf::RepeatedField::from_vec(batches));
+
+        self.states.insert(state_root.clone(), self.state.clone());
+        self.blocks.push(MockBlock {
+            block,
+            state_root,
+            receipts: mem::take(&mut self.receipts),
+        });
+    }
+
+    fn set_block_info(&mut self, block_num: u64, block_id: &str) {
+        let previous_block_id = match block_num {
+            0 => String::from("0000000000000000"),
+            _ => self.blocks[block_num as usize - 1]
+                .block
+                .header_signature
+                .clone(),
+        };
+        let mut info = BlockInfo::new();
+        info.set_block_num(block_num);
+        info.set_previous_block_id(previous_block_id);
+        info.set_signer_public_key(String::from(self.validator.public_key()));
+        info.set_header_signature(String::from(block_id));
+        info.set_timestamp(GENESIS_TIMESTAMP + BLOCK_INTERVAL * block_num);
+        self.state.insert(
+            format!("{}{:062x}", BLOCK_INFO_NAMESPACE, block_num),
+            encode(&info),
+        );
+
+        let mut config = BlockInfoConfig::new();
+        config.set_latest_block(block_num);
+        config.set_target_count(256);
+        config.set_sync_tolerance(300);
+        self.state
+            .insert(String::from(BLOCK_INFO_CONFIG_ADDRESS), encode(&config));
+    }
+}
+
+/// The code of the counter, which increments the count in slot 0, logs it with
+/// `Incremented(uint256)` and returns it, or reverts when it is called with data
+fn counter_code() -> Vec<u8> {
+    let mut code = vec![
+        0x36, // CALLDATASIZE
+        0x60, 0x3c, // PUSH1 60
+        0x57, // JUMPI
+        0x60, 0x00, 0x54, // SLOAD(0)
+        0x60, 0x01, 0x01, // ADD 1
+        0x80, // DUP1
+        0x60, 0x00, 0x55, // SSTORE(0)
+        0x60, 0x00, 0x52, // MSTORE(0)
+        0x7f, // PUSH32
+    ];
+    code.extend_from_slice(&crypto_backend::keccak256(INCREMENTED_EVENT.as_bytes()));
+    code.extend_from_slice(&[
+        0x60, 0x20, 0x60, 0x00, 0xa1, // LOG1(0, 32)
+        0x60, 0x20, 0x60, 0x00, 0xf3, // RETURN(0, 32)
+        0x5b, // JUMPDEST
+        0x60, 0x00, 0x80, 0xfd, // REVERT(0, 0)
+    ]);
+    code
+}
+
+/// The code that deploys the counter with a count of 41
+fn counter_init_code() -> Vec<u8> {
+    let code = counter_code();
+    let length = code.len() as u8;
+    let mut init = vec![
+        0x60, 0x29, 0x60, 0x00, 0x55, // SSTORE(0, 41)
+        0x60, length, 0x60, 0x11, 0x60, 0x00, 0x39, // CODECOPY(0, 17, length)
+        0x60, length, 0x60, 0x00, 0xf3, // RETURN(0, length)
+    ];
+    init.extend(code);
+    init
+}
+
+/// Returns the event the transaction processor sends for a log
+fn log_event(address: &str, topics: &[Vec<u8>], data: Vec<u8>) -> Event {
+    let mut attributes = vec![attribute("address", address)];
+    for (index, topic) in topics.iter().enumerate() {
+        attributes.push(attribute(
+            &format!("topic{}", index + 1),
+            &transform::bytes_to_hex_str(topic),
+        ));
+    }
+    let mut event = Event::new();
+    event.set_event_type(String::from("seth_log_event"));
+    event.set_attributes(protobuf::RepeatedField::from_vec(attributes));
+    event.set_data(data);
+    event
+}
+
+fn attribute(key: &str, value: &str) -> Event_Attribute {
+    let mut attribute = Event_Attribute::new();
+    attribute.set_key(String::from(key));
+    attribute.set_value(String::from(value));
+    attribute
+}
+
+/// Returns the page of items that the paging controls ask for, along with the paging
+/// of the response, or none if they start at an item that isn't listed or ask for
+/// too many
+fn page<T, F>(
+    items: Vec<T>,
+    paging: &ClientPagingControls,
+    id: F,
+) -> Option<(Vec<T>, ClientPagingResponse)>
+where
+    F: Fn(&T) -> &String,
+{
+    let limit = match paging.limit {
+        0 => DEFAULT_PAGE_SIZE,
+        limit if !(0..=MAX_PAGE_SIZE).contains(&limit) => return None,
+        limit => limit,
+    };
+    let start = if paging.start.is_empty() {
+        0
+    } else {
+        items.iter().position(|item| *id(item) == paging.start)?
+    };
+
+    let mut response = ClientPagingResponse::new();
+    response.set_start(paging.start.clone());
+    response.set_limit(limit);
+    let mut items: Vec<T> = items.into_iter().skip(start).collect();
+    if items.len() > limit as usize {
+        response.set_next(id(&items[limit as usize]).clone());
+        items.truncate(limit as usize);
+    }
+    Some((items, response))
+}
+
+/// Tells that every batch is unknown, since none are ever accepted
+fn batch_statuses(request: &ClientBatchStatusRequest) -> ClientBatchStatusResponse {
+    let statuses = request
+        .batch_ids
+        .iter()
+        .map(|batch_id| {
+            let mut status = ClientBatchStatus::new();
+            status.set_batch_id(batch_id.clone());
+            status.set_status(ClientBatchStatus_Status::UNKNOWN);
+            status
+        })
+        .collect();
+    let mut response = ClientBatchStatusResponse::new();
+    response.set_status(ClientBatchStatusResponse_Status::OK);
+    response.set_batch_statuses(protobuf::RepeatedField::from_vec(statuses));
+    response
+}
+
+fn block_response(block: Option<&Block>) -> (Message_MessageType, Vec<u8>) {
+    let mut response = ClientBlockGetResponse::new();
+    match block {
+        Some(block) => {
+            response.set_status(ClientBlockGetResponse_Status::OK);
+            response.set_block(block.clone());
+        }
+        None => response.set_status(ClientBlockGetResponse_Status::NO_RESOURCE),
+    }
+    (
+        Message_MessageType::CLIENT_BLOCK_GET_RESPONSE,
+        encode(&response),
+    )
+}
+
+fn transactions(block: &Block) -> impl Iterator<Item = &TransactionPb> {
+    block
+        .get_batches()
+        .iter()
+        .flat_map(|batch| batch.get_transactions().iter())
+}
+
+fn sign(account: &Account, message: &[u8]) -> String {
+    account
+        .sign(message)
+        .expect("Failed to sign mock chain message")
+}
+
+fn encode<M: protobuf::Message>(message: &M) -> Vec<u8> {
+    protobuf::Message::write_to_bytes(message).expect("Failed to serialize mock chain message")
+}
+
+fn decode<M: protobuf::Message>(contents: &[u8]) -> Option<M> {
+    protobuf::parse_from_bytes(contents).ok()
+}
+
+fn address_bytes(address: &str) -> Vec<u8> {
+    transform::hex_str_to_bytes(address).expect("Invalid mock chain address")
+}
+
+/// Returns a number as a 32 byte word
+fn word(value: u64) -> Vec<u8> {
+    let mut word = vec![0; 24];
+    word.extend_from_slice(&value.to_be_bytes());
+    word
+}
+
+fn is_hex(value: &str) -> bool {
+    value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sawtooth_sdk::messages::client_batch_submit::ClientBatchSubmitRequest;
+
+    fn request<M: protobuf::Message, R: protobuf::Message>(
+        chain: &MockChain,
+        request_type: Message_MessageType,
+        request: &M,
+    ) -> R {
+        let message = chain
+            .send(request_type, "1", &encode(request))
+            .unwrap()
+            .get()
+            .unwrap();
+        protobuf::parse_from_bytes(&message.content).unwrap()
+    }
+
+    #[test]
+    fn is_the_same_on_every_run() {
+        let chain = MockChain::new();
+        let again = MockChain::new();
+        let ids = |chain: &MockChain| -> Vec<String> {
+            chain
+                .blocks
+                .iter()
+                .map(|block| block.block.header_signature.clone())
+                .collect()
+        };
+        assert_eq!(ids(&chain), ids(&again));
+        assert_eq!(chain.blocks.len(), 4);
+    }
+
+    #[test]
+    fn pages_blocks_from_the_head() {
+        let chain = MockChain::new();
+        let mut paging = ClientPagingControls::new();
+        paging.set_limit(3);
+        let mut list = ClientBlockListRequest::new();
+        list.set_paging(paging);
+        let response: ClientBlockListResponse = request(
+            &chain,
+            Message_MessageType::CLIENT_BLOCK_LIST_REQUEST,
+            &list,
+        );
+        assert_eq!(response.status, ClientBlockListResponse_Status::OK);
+        let numbers: Vec<u64> = response
+            .get_blocks()
+            .iter()
+            .map(|block| {
+                protobuf::parse_from_bytes::<BlockHeader>(&block.header)
+                    .unwrap()
+                    .block_num
+            })
+            .collect();
+        assert_eq!(numbers, vec![3, 2, 1]);
+        assert_eq!(
+            response.get_paging().get_next(),
+            chain.blocks[0].block.header_signature
+        );
+    }
+
+    #[test]
+    fn serves_state_and_rejects_batches() {
+        let chain = MockChain::new();
+        let alice = &MockChain::accounts()[0];
+        let mut get = ClientStateGetRequest::new();
+        get.set_address(account_state_address(alice.address()));
+        let response: ClientStateGetResponse =
+            request(&chain, Message_MessageType::CLIENT_STATE_GET_REQUEST, &get);
+        assert_eq!(response.status, ClientStateGetResponse_Status::OK);
+        let entry: EvmEntry = protobuf::parse_from_bytes(&response.value).unwrap();
+        assert_eq!(entry.get_account().nonce, 2);
+
+        // The genesis block has no accounts
+        get.set_state_root(chain.blocks[0].state_root.clone());
+        let response: ClientStateGetResponse =
+            request(&chain, Message_MessageType::CLIENT_STATE_GET_REQUEST, &get);
+        assert_eq!(response.status, ClientStateGetResponse_Status::NO_RESOURCE);
+
+        let response: ClientBatchSubmitResponse = request(
+            &chain,
+            Message_MessageType::CLIENT_BATCH_SUBMIT_REQUEST,
+            &ClientBatchSubmitRequest::new(),
+        );
+        assert_eq!(
+            response.status,
+            ClientBatchSubmitResponse_Status::INVALID_BATCH
+        );
+    }
+}
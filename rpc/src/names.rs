@@ -0,0 +1,249 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Resolution of names like `alice.seth` to addresses, through a registry contract
+//! with the interface of the ENS registry. A name's node is its namehash, the
+//! registry returns the resolver of a node, and the resolver returns its address.
+//! Addresses are named by the reverse record of `{address}.addr.reverse`.
+
+use abi::{Function, ParamType};
+use calls::transaction;
+use client::{BlockKey, ValidatorClient};
+use crypto_backend;
+use jsonrpc_core::{Error, Value};
+use sawtooth_sdk::messages::block::Block;
+use sawtooth_sdk::messaging::stream::MessageSender;
+use serde_json::Map;
+use transform;
+
+const ZERO_ADDRESS: &str = "0x0000000000000000000000000000000000000000";
+
+/// Returns whether an address parameter is a name rather than hex
+pub fn is_name(address: &str) -> bool {
+    !address.starts_with("0x") && address.contains('.')
+}
+
+/// Returns the node of a name, as EIP-137 defines it
+pub fn namehash(name: &str) -> [u8; 32] {
+    let mut node = [0; 32];
+    if name.is_empty() {
+        return node;
+    }
+    for label in name.rsplit('.') {
+        let mut data = node.to_vec();
+        data.extend_from_slice(&crypto_backend::keccak256(label.as_bytes()));
+        node = crypto_backend::keccak256(&data);
+    }
+    node
+}
+
+/// Returns an address parameter as hex, resolving it first if it is a name
+pub fn resolve_address<T>(client: &ValidatorClient<T>, address: &str) -> Result<String, Error>
+where
+    T: MessageSender,
+{
+    if !is_name(address) {
+        return Ok(String::from(address));
+    }
+    let block = latest_block(client)?;
+    resolve(client, address, &block)?
+        .ok_or_else(|| Error::invalid_params(format!("Name `{}` is not registered", address)))
+}
+
+/// Resolves the names given for the addresses of a call or transaction object, and
+/// puts those given as hex in lower case once their checksums are checked
+pub fn resolve_fields<T>(
+    client: &ValidatorClient<T>,
+    txn: &Map<String, Value>,
+    fields: &[&str],
+) -> Result<Map<String, Value>, Error>
+where
+    T: MessageSender,
+{
+    let mut txn = txn.clone();
+    for field in fields {
+        let resolved = match txn.get(*field).and_then(Value::as_str) {
+            Some(address) if is_name(address) => resolve_address(client, address)?,
+            Some(address) => format!("0x{}", transform::parse_address(address)?),
+            None => continue,
+        };
+        txn.insert(String::from(*field), Value::String(resolved));
+    }
+    Ok(txn)
+}
+
+/// Returns the address a name resolves to in a block, if any
+pub fn resolve<T>(
+    client: &ValidatorClient<T>,
+    name: &str,
+    block: &Block,
+) -> Result<Option<String>, Error>
+where
+    T: MessageSender,
+{
+    let node = namehash(name);
+    let resolver = match node_resolver(client, &node, block)? {
+        Some(resolver) => resolver,
+        None => return Ok(None),
+    };
+    let address = call_function(
+        client,
+        &resolver,
+        function("addr", ParamType::Address),
+        &node,
+        block,
+    )?;
+    Ok(address
+        .as_str()
+        .filter(|address| *address != ZERO_ADDRESS)
+        .map(String::from))
+}
+
+/// Returns the name of an address in a block, if it has one that resolves back to it
+pub fn lookup<T>(
+    client: &ValidatorClient<T>,
+    address: &str,
+    block: &Block,
+) -> Result<Option<String>, Error>
+where
+    T: MessageSender,
+{
+    let address = address.trim_start_matches("0x").to_lowercase();
+    let node = namehash(&format!("{}.addr.reverse", address));
+    let resolver = match node_resolver(client, &node, block)? {
+        Some(resolver) => resolver,
+        None => return Ok(None),
+    };
+    let name = call_function(
+        client,
+        &resolver,
+        function("name", ParamType::String),
+        &node,
+        block,
+    )?;
+    let name = match name.as_str() {
+        Some(name) if !name.is_empty() => String::from(name),
+        _ => return Ok(None),
+    };
+
+    // Anyone can claim any name in their reverse record, so it is only returned if
+    // the name is also registered to the address
+    match resolve(client, &name, block)? {
+        Some(ref resolved) if resolved.trim_start_matches("0x") == address => Ok(Some(name)),
+        _ => Ok(None),
+    }
+}
+
+pub fn latest_block<T>(client: &ValidatorClient<T>) -> Result<Block, Error>
+where
+    T: MessageSender,
+{
+    client
+        .get_block(BlockKey::Latest)
+        .map_err(|error| fail!("Failed to get block", error))
+}
+
+fn node_resolver<T>(
+    client: &ValidatorClient<T>,
+    node: &[u8; 32],
+    block: &Block,
+) -> Result<Option<String>, Error>
+where
+    T: MessageSender,
+{
+    let registry = client.name_registry.as_ref().ok_or_else(|| {
+        Error::invalid_params("Names can't be resolved without a --name-registry")
+    })?;
+    let resolver = call_function(
+        client,
+        registry,
+        function("resolver", ParamType::Address),
+        node,
+        block,
+    )?;
+    Ok(resolver
+        .as_str()
+        .filter(|resolver| *resolver != ZERO_ADDRESS)
+        .map(String::from))
+}
+
+fn function(name: &str, output: ParamType) -> Function {
+    Function {
+        name: String::from(name),
+        inputs: vec![ParamType::FixedBytes(32)],
+        outputs: vec![output],
+    }
+}
+
+/// Calls a function of the registry or a resolver with a node, and returns its
+/// output. Calls are answered from the call cache while the block is the same.
+fn call_function<T>(
+    client: &ValidatorClient<T>,
+    contract: &str,
+    function: Function,
+    node: &[u8; 32],
+    block: &Block,
+) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    let data = function
+        .encode_call(&[transform::hex_prefix(&transform::bytes_to_hex_str(node))])
+        .map_err(Error::invalid_params)?;
+    let mut call = Map::new();
+    call.insert(String::from("to"), Value::String(String::from(contract)));
+    call.insert(
+        String::from("data"),
+        transform::hex_prefix(&transform::bytes_to_hex_str(&data)),
+    );
+
+    let output = transaction::cached_call(&call, block, client)?;
+    let output = output
+        .as_str()
+        .and_then(|output| transform::hex_str_to_bytes(output.trim_start_matches("0x")))
+        .unwrap_or_default();
+
+    // Contracts that don't implement the function return nothing, which is taken
+    // to mean they have no record
+    if output.is_empty() {
+        return Ok(Value::Null);
+    }
+    function
+        .decode_result(&output)
+        .map(|mut outputs| outputs[0].take())
+        .map_err(|error| fail!("Failed to decode name record", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_names_as_eip_137_does() {
+        assert_eq!(namehash(""), [0; 32]);
+        assert_eq!(
+            transform::bytes_to_hex_str(&namehash("eth")),
+            "93cdeb708b7545dc668eb9280176169d1c33cfd8ed6f04690a0bcc88a93fc4ae"
+        );
+        assert_eq!(
+            transform::bytes_to_hex_str(&namehash("foo.eth")),
+            "de9b09fd7c5f901e23a3f19fecc54828e9c848539801e86591bd9801b019f84f"
+        );
+        assert!(is_name("alice.seth"));
+        assert!(!is_name("0x0000000000000000000000000000000000000000"));
+    }
+}
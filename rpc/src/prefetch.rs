@@ -0,0 +1,188 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+use client::{state_root, ValidatorClient};
+use sawtooth_sdk::messaging::stream::MessageSender;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How often the prefetcher checks for a new block
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How many state entries are cached for the latest state at most
+const MAX_ENTRIES: usize = 16 * 1024;
+
+/// How many state addresses have their reads counted at most
+const MAX_TRACKED: usize = 16 * 1024;
+
+struct PrefetchState {
+    /// The state root that reads of the latest state are served from
+    latest_root: Option<String>,
+
+    /// The entries read at `latest_root`, `None` for those that don't exist
+    entries: HashMap<String, Option<Vec<u8>>>,
+
+    /// How often each state address was read, halved after each block so that
+    /// addresses that are no longer read cool down
+    reads: HashMap<String, u64>,
+}
+
+/// Caches the state entries of the latest block. After each new block, the most
+/// read entries are loaded at its state root before reads move on to it, so that
+/// reads of the contracts that dominate traffic don't wait on the validator.
+#[derive(Clone)]
+pub struct StatePrefetcher {
+    state: Arc<Mutex<PrefetchState>>,
+
+    /// How many of the most read entries are loaded after each block
+    hot_entries: usize,
+}
+
+impl StatePrefetcher {
+    pub fn new(hot_entries: usize) -> Self {
+        StatePrefetcher {
+            state: Arc::new(Mutex::new(PrefetchState {
+                latest_root: None,
+                entries: HashMap::new(),
+                reads: HashMap::new(),
+            })),
+            hot_entries,
+        }
+    }
+
+    /// Returns the state root reads of the latest state are served from, once the
+    /// first block has been prefetched
+    pub fn latest_root(&self) -> Option<String> {
+        self.state.lock().unwrap().latest_root.clone()
+    }
+
+    /// Counts a read of the address at the given state root, returning the entry if
+    /// it is cached
+    pub fn get(&self, state_root: &str, address: &str) -> Option<Option<Vec<u8>>> {
+        let mut state = self.state.lock().unwrap();
+        if state.reads.len() < MAX_TRACKED || state.reads.contains_key(address) {
+            *state.reads.entry(String::from(address)).or_insert(0) += 1;
+        }
+        if state.latest_root.as_deref() != Some(state_root) {
+            return None;
+        }
+        state.entries.get(address).cloned()
+    }
+
+    /// Caches an entry that was read at the given state root
+    pub fn insert(&self, state_root: &str, address: &str, value: Option<Vec<u8>>) {
+        let mut state = self.state.lock().unwrap();
+        if state.latest_root.as_deref() == Some(state_root) && state.entries.len() < MAX_ENTRIES {
+            state.entries.insert(String::from(address), value);
+        }
+    }
+
+    /// Returns the most read addresses, halving every count
+    fn hot_addresses(&self) -> Vec<String> {
+        let mut state = self.state.lock().unwrap();
+        let mut reads: Vec<(String, u64)> = state
+            .reads
+            .iter()
+            .map(|(address, count)| (address.clone(), *count))
+            .collect();
+        reads.sort_by_key(|&(_, count)| Reverse(count));
+        reads.truncate(self.hot_entries);
+
+        for count in state.reads.values_mut() {
+            *count /= 2;
+        }
+        state.reads.retain(|_, count| *count > 0);
+
+        reads.into_iter().map(|(address, _)| address).collect()
+    }
+
+    /// Loads the hot entries at the given state root, then serves reads of the latest
+    /// state from it
+    fn prefetch<S: MessageSender>(&self, client: &ValidatorClient<S>, root: String) {
+        let hot = self.hot_addresses();
+        let mut entries = HashMap::with_capacity(hot.len());
+        for address in hot {
+            match client.get_state_entry(&address, Some(root.clone())) {
+                Ok(value) => {
+                    entries.insert(address, value);
+                }
+                Err(error) => warn!("Failed to prefetch {}: {}", address, error),
+            }
+        }
+        debug!("Prefetched {} entries at {}", entries.len(), root);
+
+        let mut state = self.state.lock().unwrap();
+        state.latest_root = Some(root);
+        state.entries = entries;
+    }
+
+    /// Starts prefetching each new block in the background
+    pub fn start<S>(&self, client: ValidatorClient<S>)
+    where
+        S: MessageSender + Send + Sync + 'static,
+    {
+        let prefetcher = self.clone();
+        thread::spawn(move || loop {
+            let root = client
+                .get_head_block()
+                .and_then(|block| state_root(&block))
+                .map_err(|error| format!("{:?}", error));
+            match root {
+                Ok(root) => {
+                    if prefetcher.latest_root().as_ref() != Some(&root) {
+                        prefetcher.prefetch(&client, root);
+                    }
+                }
+                Err(error) => warn!("Failed to get latest block to prefetch: {}", error),
+            }
+            thread::sleep(POLL_INTERVAL);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caches_only_the_latest_root() {
+        let prefetcher = StatePrefetcher::new(1);
+        prefetcher.state.lock().unwrap().latest_root = Some(String::from("root"));
+
+        prefetcher.insert("root", "a", Some(vec![1]));
+        prefetcher.insert("old", "b", Some(vec![2]));
+        assert_eq!(prefetcher.get("root", "a"), Some(Some(vec![1])));
+        assert_eq!(prefetcher.get("root", "b"), None);
+        assert_eq!(prefetcher.get("old", "a"), None);
+    }
+
+    #[test]
+    fn prefetches_the_most_read_addresses() {
+        let prefetcher = StatePrefetcher::new(1);
+        for _ in 0..3 {
+            prefetcher.get("root", "hot");
+        }
+        prefetcher.get("root", "cold");
+
+        assert_eq!(prefetcher.hot_addresses(), vec![String::from("hot")]);
+        // "cold" was halved to zero and forgotten, "hot" cooled down to 1
+        assert_eq!(prefetcher.state.lock().unwrap().reads.len(), 1);
+    }
+}
@@ -0,0 +1,495 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! The `check-config` command, which checks a configuration at deploy time rather
+//! than seth-rpc exiting once it runs: that the config file and flags are valid,
+//! that the validators answer and that the keys to unlock and the Ledger can be
+//! read. It also reports the chain id the chain's settings give.
+
+use accounts::{self, Account, Error as AccountError};
+use calls::network::DEFAULT_CHAIN_ID;
+use clap::ArgMatches;
+use client::{self, BlockKey, ValidatorClient};
+use config::Settings;
+use contracts::ContractRegistry;
+use crypto_backend;
+use endpoints::{Balancing, Connection, EndpointSender, ValidatorConnection};
+use filters::FilterManager;
+#[cfg(feature = "tls")]
+use front;
+#[cfg(feature = "ledger")]
+use ledger::Ledger;
+use sawtooth_sdk::messaging::stream::MessageSender;
+use screening::RuleScreen;
+use std::fs;
+use std::net::SocketAddr;
+#[cfg(feature = "tls")]
+use std::path::Path;
+use std::process;
+#[cfg(feature = "debug")]
+use vm;
+
+/// The flags that take a number
+const NUMERIC_FLAGS: &[&str] = &[
+    "ws_port",
+    "max_ws_connections",
+    "max_subscriptions",
+    "max_ws_send_buffer",
+    "max_filters",
+    "filter_timeout",
+    "max_pending",
+    "max_response_size",
+    "max_request_memory",
+    "max_batch_requests",
+    "max_batch_size",
+    "report_period",
+    "call_cache_size",
+    "block_cache_size",
+    "state_cache_size",
+    "receipt_cache_size",
+    "code_cache_size",
+    "prefetch",
+    "gas_cap",
+    "finality_depth",
+    "ledger_accounts",
+];
+
+/// The flags that need a feature seth-rpc may be built without
+const FEATURE_FLAGS: &[(&str, &str)] = &[
+    ("ws_port", "ws"),
+    ("index_db", "postgres"),
+    ("max_ws_connections", "ws"),
+    ("max_subscriptions", "ws"),
+    ("max_ws_send_buffer", "ws"),
+    ("log_index", "indexer"),
+    ("report_dir", "indexer"),
+    ("shadow_fork", "debug"),
+    ("ledger", "ledger"),
+    ("privacy_manager", "privacy"),
+    ("screening_url", "screening"),
+    ("screening_simulate", "screening"),
+    ("tls_cert", "tls"),
+    ("tls_key", "tls"),
+];
+
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Passed,
+    Warning,
+    Failed,
+}
+
+/// What the checks found, in the order they were made
+#[derive(Default)]
+struct Report {
+    results: Vec<(Outcome, String)>,
+}
+
+impl Report {
+    fn pass<S: Into<String>>(&mut self, message: S) {
+        self.results.push((Outcome::Passed, message.into()));
+    }
+
+    fn warn<S: Into<String>>(&mut self, message: S) {
+        self.results.push((Outcome::Warning, message.into()));
+    }
+
+    fn fail<S: Into<String>>(&mut self, message: S) {
+        self.results.push((Outcome::Failed, message.into()));
+    }
+
+    fn failures(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|(outcome, _)| *outcome == Outcome::Failed)
+            .count()
+    }
+
+    fn print(&self) {
+        for (outcome, message) in &self.results {
+            let label = match *outcome {
+                Outcome::Passed => "ok",
+                Outcome::Warning => "warn",
+                Outcome::Failed => "FAIL",
+            };
+            println!("{:<5} {}", label, message);
+        }
+        match self.failures() {
+            0 => println!("The configuration is fine"),
+            1 => println!("Found 1 problem"),
+            count => println!("Found {} problems", count),
+        }
+    }
+}
+
+/// Checks the configuration of a command line, with the settings of its `--config`
+/// file, prints a report and exits, with an error if any check failed
+pub fn run(given: &ArgMatches<'static>) -> ! {
+    let mut report = Report::default();
+    let arg_matches = match given.value_of("config") {
+        Some(path) => match ::with_config(given, path) {
+            Ok((arg_matches, config)) => {
+                report.pass(format!("Read {} settings from {}", config.len(), path));
+                arg_matches
+            }
+            Err(error) => {
+                report.fail(format!("{}; the rest is checked without the file", error));
+                given.clone()
+            }
+        },
+        None => given.clone(),
+    };
+
+    check_flags(&arg_matches, &mut report);
+    let accounts = check_keys(&arg_matches, &mut report);
+    #[cfg(feature = "ledger")]
+    check_ledger(&arg_matches, &mut report);
+    if let Some(signer) = arg_matches.value_of("report_signer") {
+        let signer = signer.trim_start_matches("0x").to_lowercase();
+        if !accounts.iter().any(|account| account.address() == signer) {
+            report.fail(format!(
+                "--report-signer {} isn't an account of --unlock; unlock the key of the \
+                 account that signs the reports",
+                signer
+            ));
+        }
+    }
+    check_validators(&arg_matches, &mut report);
+
+    report.print();
+    process::exit(if report.failures() == 0 { 0 } else { 1 });
+}
+
+/// Checks that the value of each flag is one seth-rpc accepts, and that the files
+/// they name can be read
+fn check_flags(arg_matches: &ArgMatches, report: &mut Report) {
+    let failures = report.failures();
+
+    for bind in arg_matches.values_of("bind").into_iter().flatten() {
+        if bind.parse::<SocketAddr>().is_err() {
+            report.fail(format!(
+                "--bind {} isn't a host and port, like 127.0.0.1:3030 or [::1]:3030",
+                bind
+            ));
+        }
+    }
+    for flag in NUMERIC_FLAGS {
+        if let Some(value) = arg_matches.value_of(flag) {
+            let valid = match *flag {
+                "ws_port" => value.parse::<u16>().is_ok(),
+                "ledger_accounts" => value.parse::<u32>().is_ok(),
+                _ => value.parse::<u64>().is_ok(),
+            };
+            if !valid {
+                report.fail(format!("--{} {} isn't a number", flag_name(flag), value));
+            }
+        }
+    }
+    for &(flag, feature) in FEATURE_FLAGS {
+        let built_with = match feature {
+            "ws" => cfg!(feature = "ws"),
+            "indexer" => cfg!(feature = "indexer"),
+            "postgres" => cfg!(feature = "postgres"),
+            "ledger" => cfg!(feature = "ledger"),
+            "privacy" => cfg!(feature = "privacy"),
+            "screening" => cfg!(feature = "screening"),
+            "tls" => cfg!(feature = "tls"),
+            _ => cfg!(feature = "debug"),
+        };
+        if arg_matches.is_present(flag) && !built_with {
+            report.fail(format!(
+                "--{} needs seth-rpc to be built with the `{}` feature",
+                flag_name(flag),
+                feature
+            ));
+        }
+    }
+    if let Err(error) = Settings::from_matches(arg_matches) {
+        report.fail(error);
+    }
+
+    if let Some(name) = arg_matches.value_of("crypto_backend") {
+        if crypto_backend::by_name(name).is_none() {
+            report.fail(format!(
+                "Unknown --crypto-backend {}, expected one of {}",
+                name,
+                crypto_backend::BACKENDS.join(", ")
+            ));
+        }
+    }
+    if let Some(Err(error)) = arg_matches.value_of("balancing").map(Balancing::parse) {
+        report.fail(error);
+    }
+    #[cfg(feature = "debug")]
+    {
+        if let Some(Err(error)) = arg_matches.value_of("shadow_fork").map(vm::Fork::parse) {
+            report.fail(error);
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    if let (Some(cert), Some(key)) = (
+        arg_matches.value_of("tls_cert"),
+        arg_matches.value_of("tls_key"),
+    ) {
+        if let Err(error) = front::load_acceptor(Path::new(cert), Path::new(key)) {
+            report.fail(error);
+        }
+    }
+    for flag in &["auth_tokens", "jwt_secret"] {
+        if let Some(path) = arg_matches.value_of(flag) {
+            match fs::read_to_string(path) {
+                Ok(ref contents) if contents.trim().is_empty() => {
+                    report.fail(format!("--{} {} is empty", flag_name(flag), path))
+                }
+                Ok(_) => {}
+                Err(error) => report.fail(format!("Failed to read {}: {}", path, error)),
+            }
+        }
+    }
+    if arg_matches.is_present("auth_methods")
+        && !arg_matches.is_present("auth_tokens")
+        && !arg_matches.is_present("jwt_secret")
+    {
+        report.fail("--auth-methods needs --auth-tokens or --jwt-secret");
+    }
+    if let Some(Err(error)) = arg_matches
+        .value_of("screening_rules")
+        .map(RuleScreen::load)
+    {
+        report.fail(error);
+    }
+    if let Some(Err(error)) = arg_matches
+        .value_of("abi_dir")
+        .map(ContractRegistry::load_dir)
+    {
+        report.fail(error);
+    }
+    if arg_matches.is_present("report_dir") && !arg_matches.is_present("log_index") {
+        report.fail("--report-dir needs --log-index");
+    }
+
+    if report.failures() == failures {
+        report.pass("The flags are valid");
+    }
+}
+
+/// Checks that the Ledger of `--ledger` answers with the addresses of its accounts
+#[cfg(feature = "ledger")]
+fn check_ledger(arg_matches: &ArgMatches, report: &mut Report) {
+    let device = match arg_matches.value_of("ledger") {
+        Some(device) => device,
+        None => return,
+    };
+    let count = match arg_matches
+        .value_of("ledger_accounts")
+        .map(str::parse::<u32>)
+    {
+        Some(Ok(count)) => count,
+        Some(Err(_)) => return,
+        None => 1,
+    };
+    match Ledger::open(device, count) {
+        Ok(ledger) => {
+            for account in ledger.accounts() {
+                report.pass(format!("The Ledger has account 0x{}", account.address()));
+            }
+        }
+        Err(error) => report.fail(format!(
+            "{}; the Ledger must be plugged in and unlocked with its Ethereum app open",
+            error
+        )),
+    }
+}
+
+/// Checks that the keys of the accounts to unlock can be read, returning the
+/// accounts they are the keys of
+fn check_keys(arg_matches: &ArgMatches, report: &mut Report) -> Vec<Account> {
+    let aliases = arg_matches.values_of_lossy("unlock").unwrap_or_default();
+    if aliases.is_empty() {
+        return Vec::new();
+    }
+    let dir = match accounts::get_key_dir() {
+        Some(dir) => dir,
+        None => {
+            report.fail("There is no home directory to find the keys of --unlock in");
+            return Vec::new();
+        }
+    };
+    if let Err(error) = fs::read_dir(&dir) {
+        report.fail(format!(
+            "Failed to read the key directory {}: {}; create it and give the user \
+             seth-rpc runs as access to it",
+            dir.display(),
+            error
+        ));
+        return Vec::new();
+    }
+
+    let mut accounts = Vec::new();
+    for alias in aliases {
+        match Account::load_from_file(&alias, &None) {
+            Ok(account) => {
+                report.pass(format!(
+                    "Unlocked {} as 0x{}",
+                    account.alias(),
+                    account.address()
+                ));
+                accounts.push(account);
+            }
+            Err(AccountError::AliasNotFound) => report.fail(format!(
+                "There is no key {}.pem in {}; create it with `sawtooth keygen {}` or \
+                 import it with `seth account import`",
+                alias,
+                dir.display(),
+                alias
+            )),
+            Err(AccountError::IoError(error)) => report.fail(format!(
+                "Failed to read the key {}.pem in {}: {}",
+                alias,
+                dir.display(),
+                error
+            )),
+            Err(error) => report.fail(format!(
+                "The key {}.pem in {} can't be unlocked: {}; keys of --unlock must be \
+                 unencrypted PEM files",
+                alias,
+                dir.display(),
+                error
+            )),
+        }
+    }
+    accounts
+}
+
+/// Checks that each validator answers, and reports the chain id the settings of the
+/// first to answer give
+fn check_validators(arg_matches: &ArgMatches, report: &mut Report) {
+    if arg_matches.is_present("mock_chain") {
+        report.pass("Serving the mock chain, so there are no validators to check");
+        return;
+    }
+    let family_version = arg_matches
+        .value_of("family_version")
+        .unwrap_or(::DEFAULT_FAMILY_VERSION);
+
+    let mut chain_checked = false;
+    let connect = arg_matches
+        .values_of_lossy("connect")
+        .unwrap_or_else(|| vec![String::from("tcp://127.0.0.1:4004")]);
+    for url in connect {
+        if !url.starts_with("tcp://") && !url.starts_with("ipc://") {
+            report.fail(format!(
+                "--connect {} isn't a ZMQ endpoint, like tcp://validator:4004",
+                url
+            ));
+            continue;
+        }
+        let connection = Connection::Validator(ValidatorConnection::new(&url));
+        let client = ValidatorClient::new(
+            EndpointSender::new(vec![(url.clone(), connection)]),
+            Vec::new(),
+            FilterManager::new(1),
+            0,
+            String::from(family_version),
+            ContractRegistry::new(),
+        );
+        match client.get_current_block_number() {
+            Ok(number) => report.pass(format!("The validator at {} is at block {}", url, number)),
+            Err(error) => {
+                report.fail(format!(
+                    "The validator at {} didn't answer: {}; check that it runs with \
+                     --bind component:{}",
+                    url,
+                    error,
+                    url.trim_start_matches("tcp://")
+                ));
+                continue;
+            }
+        }
+
+        if !chain_checked {
+            chain_checked = true;
+            check_chain_id(&client, report);
+        }
+    }
+}
+
+fn check_chain_id<T>(client: &ValidatorClient<T>, report: &mut Report)
+where
+    T: MessageSender,
+{
+    match client.get_chain_id_setting(BlockKey::Latest) {
+        Ok(Some(chain_id)) => report.pass(format!(
+            "The chain id is {}, as the chain's {} setting gives it",
+            chain_id,
+            client::CHAIN_ID_SETTING
+        )),
+        Ok(None) => report.pass(format!(
+            "The chain doesn't set {}, so the chain id is {}",
+            client::CHAIN_ID_SETTING,
+            DEFAULT_CHAIN_ID
+        )),
+        Err(client::Error::ParseError(message)) => report.warn(format!(
+            "{}; the transaction processors ignore it and take the chain id to be {}",
+            message, DEFAULT_CHAIN_ID
+        )),
+        Err(error) => report.fail(format!("Failed to read the chain's settings: {}", error)),
+    }
+}
+
+/// Returns the name of a flag as it is given on the command line
+fn flag_name(flag: &str) -> String {
+    flag.replace('_', "-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_invalid_flags() {
+        let mut report = Report::default();
+        let arg_matches = ::app().get_matches_from(vec![
+            "seth-rpc",
+            "--bind",
+            "127.0.0.1:3030",
+            "--bind",
+            "localhost",
+            "--max-filters",
+            "many",
+            "--balancing",
+            "random",
+            "--rate-limit",
+            "0",
+        ]);
+        check_flags(&arg_matches, &mut report);
+        assert_eq!(report.failures(), 4);
+        assert!(report.results[0].1.contains("--bind localhost"));
+        assert!(report.results[1].1.contains("--max-filters many"));
+
+        let mut report = Report::default();
+        check_flags(
+            &::app().get_matches_from(vec!["seth-rpc", "--gas-cap", "7"]),
+            &mut report,
+        );
+        assert_eq!(
+            report.results,
+            vec![(Outcome::Passed, String::from("The flags are valid"))]
+        );
+    }
+}
@@ -0,0 +1,83 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+use base64;
+use client::Error;
+use reqwest;
+use serde_json::{Map, Value};
+use tokio;
+
+/// A client for a Tessera-style privacy manager, which stores the payloads of private
+/// transactions and distributes them to the privacy managers of their participants.
+/// Only the hash the privacy manager returns is submitted to the validator.
+#[derive(Clone)]
+pub struct PrivacyManager {
+    url: String,
+    client: reqwest::blocking::Client,
+}
+
+impl PrivacyManager {
+    pub fn new(url: &str) -> Self {
+        PrivacyManager {
+            url: url.trim_end_matches('/').into(),
+            client: reqwest::blocking::Client::new(),
+        }
+    }
+
+    /// Stores the payload with the privacy manager, shares it with the participants
+    /// identified by the public keys in `private_for`, and returns its hash
+    pub fn send(
+        &self,
+        payload: &[u8],
+        private_from: Option<&str>,
+        private_for: &[String],
+    ) -> Result<Vec<u8>, Error> {
+        let mut request = Map::new();
+        request.insert("payload".into(), Value::String(base64::encode(payload)));
+        if let Some(from) = private_from {
+            request.insert("from".into(), Value::String(from.into()));
+        }
+        request.insert(
+            "to".into(),
+            Value::Array(private_for.iter().cloned().map(Value::String).collect()),
+        );
+
+        // See HttpScreen::screen for why the requests are made in place
+        let response = tokio::task::block_in_place(|| {
+            self.client
+                .post(format!("{}/send", self.url))
+                .json(&request)
+                .send()
+                .and_then(|response| response.error_for_status())
+        })
+        .map_err(|err| {
+            Error::CommunicationError(format!("Privacy manager request failed: {}", err))
+        })?;
+
+        let body: Map<String, Value> =
+            tokio::task::block_in_place(|| response.json()).map_err(|err| {
+                Error::ParseError(format!("Malformed privacy manager response: {}", err))
+            })?;
+
+        body.get("key")
+            .and_then(Value::as_str)
+            .and_then(|key| base64::decode(key).ok())
+            .ok_or_else(|| {
+                Error::ParseError(String::from("Privacy manager response is missing a key"))
+            })
+    }
+}
@@ -0,0 +1,122 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Which client requests each validator answers. Validators don't report their
+//! version, and those that don't know a request drop it rather than answering, so
+//! the version of each is told from whether it answers the requests that were
+//! added in later releases.
+
+use sawtooth_sdk::messages::validator::Message_MessageType;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+/// The releases of the validator's client protocol that seth-rpc knows the
+/// differences between. Later releases speak the 1.1 client protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ProtocolVersion {
+    V1_0,
+    V1_1,
+}
+
+impl ProtocolVersion {
+    /// The release that validators answer a request from
+    pub fn required_for(message_type: Message_MessageType) -> Self {
+        match message_type {
+            Message_MessageType::CLIENT_BLOCK_GET_BY_TRANSACTION_ID_REQUEST
+            | Message_MessageType::CLIENT_BLOCK_GET_BY_BATCH_ID_REQUEST
+            | Message_MessageType::CLIENT_STATUS_GET_REQUEST => ProtocolVersion::V1_1,
+            _ => ProtocolVersion::V1_0,
+        }
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProtocolVersion::V1_0 => write!(f, "1.0"),
+            ProtocolVersion::V1_1 => write!(f, "1.1+"),
+        }
+    }
+}
+
+/// The negotiated version of each validator seth-rpc talks to, by the index of its
+/// endpoint. Validators that haven't been negotiated with yet are assumed to answer
+/// everything, as are all validators of a client without any endpoints.
+#[derive(Clone, Default)]
+pub struct Capabilities {
+    versions: Arc<RwLock<Vec<Option<ProtocolVersion>>>>,
+}
+
+impl Capabilities {
+    pub fn new(endpoints: usize) -> Self {
+        Capabilities {
+            versions: Arc::new(RwLock::new(vec![None; endpoints])),
+        }
+    }
+
+    pub fn version(&self, endpoint: usize) -> Option<ProtocolVersion> {
+        self.versions
+            .read()
+            .unwrap()
+            .get(endpoint)
+            .cloned()
+            .flatten()
+    }
+
+    pub fn set_version(&self, endpoint: usize, version: ProtocolVersion) {
+        if let Some(slot) = self.versions.write().unwrap().get_mut(endpoint) {
+            *slot = Some(version);
+        }
+    }
+
+    /// Whether the validator at the endpoint answers the request
+    pub fn endpoint_supports(&self, endpoint: usize, message_type: Message_MessageType) -> bool {
+        self.version(endpoint)
+            .is_none_or(|version| version >= ProtocolVersion::required_for(message_type))
+    }
+
+    /// Whether any of the validators answers the request
+    pub fn supports(&self, message_type: Message_MessageType) -> bool {
+        let endpoints = self.versions.read().unwrap().len();
+        endpoints == 0
+            || (0..endpoints).any(|endpoint| self.endpoint_supports(endpoint, message_type))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn supports_what_any_validator_answers() {
+        let by_txn = Message_MessageType::CLIENT_BLOCK_GET_BY_TRANSACTION_ID_REQUEST;
+        assert!(Capabilities::default().supports(by_txn));
+
+        let capabilities = Capabilities::new(2);
+        assert!(capabilities.supports(by_txn));
+        capabilities.set_version(0, ProtocolVersion::V1_0);
+        assert!(capabilities.supports(by_txn));
+        assert!(!capabilities.endpoint_supports(0, by_txn));
+
+        capabilities.set_version(1, ProtocolVersion::V1_0);
+        assert!(!capabilities.supports(by_txn));
+        assert!(capabilities.supports(Message_MessageType::CLIENT_BLOCK_GET_BY_NUM_REQUEST));
+
+        capabilities.set_version(1, ProtocolVersion::V1_1);
+        assert!(capabilities.supports(by_txn));
+    }
+}
@@ -0,0 +1,366 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Recognition of the contracts that delegate every call to another: clones, whose
+//! code is an EIP-1167 minimal proxy with the implementation's address in it, and
+//! proxies that keep the address of their implementation, or of a beacon that
+//! returns it, in one of the storage slots EIP-1967 and its predecessors set aside.
+
+use calls::transaction;
+use client::{BlockKey, ValidatorClient};
+use crypto_backend;
+use jsonrpc_core::{Error, Value};
+use primitive_types::U256;
+use sawtooth_sdk::messages::block::Block;
+use sawtooth_sdk::messaging::stream::MessageSender;
+use serde_json::Map;
+use transform;
+
+/// How many proxies in a row are followed, since an implementation may itself be a
+/// proxy, before giving up on a loop
+const MAX_HOPS: usize = 8;
+
+/// The selector of `implementation()`, which beacons return the implementation with
+const IMPLEMENTATION_SELECTOR: &str = "0x5c60da1b";
+
+/// The kind of a minimal proxy, and its code before and after the push of the
+/// implementation's address. `None` stands for the byte that differs with the
+/// length of the push.
+type MinimalProxy = (&'static str, &'static [u8], &'static [Option<u8>]);
+
+const MINIMAL_PROXIES: &[MinimalProxy] = &[
+    (
+        "eip1167",
+        &[0x36, 0x3d, 0x3d, 0x37, 0x3d, 0x3d, 0x3d, 0x36, 0x3d],
+        &[
+            Some(0x5a),
+            Some(0xf4),
+            Some(0x3d),
+            Some(0x82),
+            Some(0x80),
+            Some(0x3e),
+            Some(0x90),
+            Some(0x3d),
+            Some(0x91),
+            Some(0x60),
+            None,
+            Some(0x57),
+            Some(0xfd),
+            Some(0x5b),
+            Some(0xf3),
+        ],
+    ),
+    // The shorter clone that saves gas by reordering the stack
+    (
+        "eip1167",
+        &[0x3d, 0x3d, 0x3d, 0x3d, 0x36, 0x3d, 0x3d, 0x37, 0x36, 0x3d],
+        &[
+            Some(0x5a),
+            Some(0xf4),
+            Some(0x3d),
+            Some(0x3d),
+            Some(0x93),
+            Some(0x80),
+            Some(0x3e),
+            Some(0x60),
+            None,
+            Some(0x57),
+            Some(0xfd),
+            Some(0x5b),
+            Some(0xf3),
+        ],
+    ),
+    // The clone of EIP-7511, which pushes zeroes with PUSH0
+    (
+        "eip7511",
+        &[0x36, 0x5f, 0x5f, 0x37, 0x5f, 0x5f, 0x36, 0x5f],
+        &[
+            Some(0x5a),
+            Some(0xf4),
+            Some(0x3d),
+            Some(0x5f),
+            Some(0x5f),
+            Some(0x3e),
+            Some(0x5f),
+            Some(0x3d),
+            Some(0x91),
+            Some(0x60),
+            None,
+            Some(0x57),
+            Some(0xfd),
+            Some(0x5b),
+            Some(0xf3),
+        ],
+    ),
+];
+
+/// A contract that delegates calls to `implementation`
+#[derive(Debug, PartialEq)]
+pub struct Proxy {
+    pub address: String,
+    pub kind: &'static str,
+    pub implementation: String,
+    /// The beacon the implementation was read from, for beacon proxies
+    pub beacon: Option<String>,
+}
+
+impl Proxy {
+    pub fn to_value(&self) -> Value {
+        let mut proxy = Map::new();
+        proxy.insert(String::from("address"), hex_address(&self.address));
+        proxy.insert(String::from("kind"), Value::from(self.kind));
+        proxy.insert(
+            String::from("implementation"),
+            hex_address(&self.implementation),
+        );
+        if let Some(ref beacon) = self.beacon {
+            proxy.insert(String::from("beacon"), hex_address(beacon));
+        }
+        Value::Object(proxy)
+    }
+}
+
+/// Returns the proxies that calls to an address pass through in a block, each
+/// delegating to the next and the last to the contract that serves them. Addresses
+/// are hex without `0x`.
+pub fn resolve<T>(
+    client: &ValidatorClient<T>,
+    address: &str,
+    block: &Block,
+) -> Result<Vec<Proxy>, Error>
+where
+    T: MessageSender,
+{
+    let mut proxies: Vec<Proxy> = Vec::new();
+    let mut address = address.to_lowercase();
+    while let Some(proxy) = resolve_one(client, &address, block)? {
+        address = proxy.implementation.clone();
+        let looped = proxies.iter().any(|seen| seen.address == address);
+        proxies.push(proxy);
+        if looped || proxies.len() == MAX_HOPS {
+            break;
+        }
+    }
+    Ok(proxies)
+}
+
+fn resolve_one<T>(
+    client: &ValidatorClient<T>,
+    address: &str,
+    block: &Block,
+) -> Result<Option<Proxy>, Error>
+where
+    T: MessageSender,
+{
+    let key = BlockKey::Signature(block.header_signature.clone());
+    let code = client
+        .get_account(address, key.clone())
+        .map_err(|error| fail!("Failed to get account", error))?
+        .map(|account| account.code)
+        .unwrap_or_default();
+    if code.is_empty() {
+        return Ok(None);
+    }
+    let proxy = |kind, implementation, beacon| {
+        Some(Proxy {
+            address: String::from(address),
+            kind,
+            implementation,
+            beacon,
+        })
+    };
+    if let Some((kind, implementation)) = minimal_proxy(&code) {
+        return Ok(proxy(kind, implementation, None));
+    }
+
+    let slot = |kind, name: &str| -> Result<Option<(&'static str, String)>, Error> {
+        let value = client
+            .get_storage_at(
+                address,
+                &transform::bytes_to_hex_str(&storage_slot(name)),
+                key.clone(),
+            )
+            .map_err(|error| fail!("Failed to get storage", error))?;
+        Ok(value
+            .and_then(|value| word_address(&value))
+            .map(|at| (kind, at)))
+    };
+    let found = match slot("eip1967", "eip1967.proxy.implementation")? {
+        Some(found) => Some(found),
+        None => match slot("eip1822", "PROXIABLE")? {
+            Some(found) => Some(found),
+            None => slot("zeppelinos", "org.zeppelinos.proxy.implementation")?,
+        },
+    };
+    if let Some((kind, implementation)) = found {
+        return Ok(proxy(kind, implementation, None));
+    }
+
+    match slot("eip1967Beacon", "eip1967.proxy.beacon")? {
+        Some((kind, beacon)) => Ok(beacon_implementation(client, &beacon, block)?
+            .and_then(|implementation| proxy(kind, implementation, Some(beacon)))),
+        None => Ok(None),
+    }
+}
+
+/// Returns the address a beacon's `implementation()` returns, if it returns one
+fn beacon_implementation<T>(
+    client: &ValidatorClient<T>,
+    beacon: &str,
+    block: &Block,
+) -> Result<Option<String>, Error>
+where
+    T: MessageSender,
+{
+    let mut call = Map::new();
+    call.insert(String::from("to"), hex_address(beacon));
+    call.insert(String::from("data"), Value::from(IMPLEMENTATION_SELECTOR));
+    let output = transaction::cached_call(&call, block, client)?;
+    Ok(output
+        .as_str()
+        .and_then(|output| transform::hex_str_to_bytes(output.trim_start_matches("0x")))
+        .filter(|output| output.len() == 32)
+        .and_then(|output| word_address(&output)))
+}
+
+/// Returns the kind of minimal proxy some code is and the address it delegates to,
+/// if it is one
+fn minimal_proxy(code: &[u8]) -> Option<(&'static str, String)> {
+    MINIMAL_PROXIES.iter().find_map(|&(kind, prefix, suffix)| {
+        let rest = code.strip_prefix(prefix)?;
+        // The address is pushed with PUSH1 to PUSH20, shorter for addresses
+        // with leading zeroes
+        let length = match rest.first() {
+            Some(&push @ 0x60..=0x73) => usize::from(push - 0x5f),
+            _ => return None,
+        };
+        let pushed = rest.get(1..=length)?;
+        let rest = &rest[length + 1..];
+        let matches = rest.len() == suffix.len()
+            && rest
+                .iter()
+                .zip(suffix)
+                .all(|(byte, expected)| expected.is_none_or(|expected| *byte == expected));
+        if !matches {
+            return None;
+        }
+        let mut address = vec![0; 20 - length];
+        address.extend_from_slice(pushed);
+        Some((kind, transform::bytes_to_hex_str(&address)))
+    })
+}
+
+/// Returns the slot named by `name` the way EIP-1967 names them, the Keccak hash
+/// of the name less one, or EIP-1822 and ZeppelinOS without the one
+fn storage_slot(name: &str) -> [u8; 32] {
+    let hash = crypto_backend::keccak256(name.as_bytes());
+    if !name.starts_with("eip1967.") {
+        return hash;
+    }
+    let mut slot = [0; 32];
+    (U256::from_big_endian(&hash) - U256::one()).to_big_endian(&mut slot);
+    slot
+}
+
+/// Returns the address in the low 20 bytes of a storage word, unless it is zero
+fn word_address(word: &[u8]) -> Option<String> {
+    let address = &word[word.len().saturating_sub(20)..];
+    if address.iter().all(|byte| *byte == 0) {
+        return None;
+    }
+    let mut padded = vec![0; 20 - address.len()];
+    padded.extend_from_slice(address);
+    Some(transform::bytes_to_hex_str(&padded))
+}
+
+fn hex_address(address: &str) -> Value {
+    transform::address_value(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(hex: &str) -> Vec<u8> {
+        transform::hex_str_to_bytes(hex).unwrap()
+    }
+
+    #[test]
+    fn recognizes_minimal_proxies() {
+        let implementation = "bebebebebebebebebebebebebebebebebebebebe";
+        assert_eq!(
+            minimal_proxy(&code(&format!(
+                "363d3d373d3d3d363d73{}5af43d82803e903d91602b57fd5bf3",
+                implementation
+            ))),
+            Some(("eip1167", String::from(implementation)))
+        );
+        assert_eq!(
+            minimal_proxy(&code(&format!(
+                "3d3d3d3d363d3d37363d73{}5af43d3d93803e602a57fd5bf3",
+                implementation
+            ))),
+            Some(("eip1167", String::from(implementation)))
+        );
+        assert_eq!(
+            minimal_proxy(&code(&format!(
+                "365f5f375f5f365f73{}5af43d5f5f3e5f3d91602a57fd5bf3",
+                implementation
+            ))),
+            Some(("eip7511", String::from(implementation)))
+        );
+
+        // Vanity addresses with leading zeroes are pushed with fewer bytes
+        assert_eq!(
+            minimal_proxy(&code(
+                "363d3d373d3d3d363d6f0102030405060708090a0b0c0d0e0f10\
+                 5af43d82803e903d91602757fd5bf3"
+            )),
+            Some((
+                "eip1167",
+                String::from("000000000102030405060708090a0b0c0d0e0f10")
+            ))
+        );
+
+        // Code that merely starts the same way isn't a clone
+        assert_eq!(
+            minimal_proxy(&code(&format!(
+                "363d3d373d3d3d363d73{}5af43d82803e903d91602b57fd5bf300",
+                implementation
+            ))),
+            None
+        );
+        assert_eq!(minimal_proxy(&code("6080604052")), None);
+    }
+
+    #[test]
+    fn computes_proxy_slots() {
+        assert_eq!(
+            transform::bytes_to_hex_str(&storage_slot("eip1967.proxy.implementation")),
+            "360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc"
+        );
+        assert_eq!(
+            transform::bytes_to_hex_str(&storage_slot("eip1967.proxy.beacon")),
+            "a3f0ad74e5423aebfd80d3ef4346578335a9a72aeaee59ff6cb3582b35133d50"
+        );
+        assert_eq!(
+            transform::bytes_to_hex_str(&storage_slot("PROXIABLE")),
+            "c5f16f0fcc639fa48a6947836d9850f504798523bf8c9a3a87d5876cf622bcf7"
+        );
+        assert_eq!(word_address(&[0; 32]), None);
+    }
+}
@@ -0,0 +1,730 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Transactions signed by Ethereum wallets, which `eth_sendRawTransaction` relays
+//! to the transaction processor in an `EthereumTxn`. The processor checks the
+//! signature itself; it is only recovered here to know which accounts the
+//! transaction touches.
+
+use accounts::public_key_to_address;
+use client::Error;
+use crypto_backend;
+use messages::seth::{
+    CreateContractAccountTxn as CreateContractAccountTxnPb, MessageCallTxn as MessageCallTxnPb,
+};
+use rlp::{Rlp, RlpStream};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use transactions::SethTransaction;
+use transform;
+
+/// How many Ethereum transaction hashes are remembered at most
+const MAX_HASHES: usize = 65536;
+
+/// The type of EIP-1559 transactions, which the envelope of EIP-2718 starts with
+pub const DYNAMIC_FEE_TXN_TYPE: u8 = 2;
+
+/// A legacy or EIP-1559 Ethereum transaction, decoded the same way the transaction
+/// processor decodes it. Seth balances are 64 bits, so wider quantities are
+/// rejected.
+#[derive(Clone, Debug)]
+pub struct RawTransaction {
+    pub raw: Vec<u8>,
+    pub nonce: u64,
+    /// The gas price of a legacy transaction, or the `max_fee_per_gas` of an
+    /// EIP-1559 one
+    pub gas_price: u64,
+    pub gas_limit: u64,
+    pub to: Option<Vec<u8>>,
+    pub value: u64,
+    pub data: Vec<u8>,
+    /// The `max_fee_per_gas` and `max_priority_fee_per_gas` of an EIP-1559
+    /// transaction, or none for a legacy one
+    pub fees: Option<(u64, u64)>,
+    sender: String,
+    chain_id: u64,
+}
+
+impl RawTransaction {
+    pub fn decode(raw: &[u8]) -> Result<Self, Error> {
+        match raw.first() {
+            Some(&DYNAMIC_FEE_TXN_TYPE) => return RawTransaction::decode_dynamic_fee(raw),
+            Some(&first) if first < 0xc0 => {
+                return Err(Error::ParseError(format!(
+                    "Only legacy and type {} transactions are supported, not type {}",
+                    DYNAMIC_FEE_TXN_TYPE, first
+                )));
+            }
+            _ => (),
+        }
+        let parse_error = |error| Error::ParseError(format!("Malformed transaction: {}", error));
+        let rlp = Rlp::new(raw);
+        if rlp.item_count().map_err(parse_error)? != 9 || encoded_len(&rlp)? != raw.len() {
+            return Err(Error::ParseError(String::from(
+                "Transaction must be a list of 9 fields",
+            )));
+        }
+
+        let to = rlp.at(3).and_then(|to| to.data()).map_err(parse_error)?;
+        if !to.is_empty() && to.len() != 20 {
+            return Err(Error::ParseError(String::from(
+                "Recipient must be 20 bytes",
+            )));
+        }
+
+        let v: u64 = rlp.val_at(6).map_err(parse_error)?;
+        let r = rlp.at(7).and_then(|r| r.data()).map_err(parse_error)?;
+        let s = rlp.at(8).and_then(|s| s.data()).map_err(parse_error)?;
+
+        // Without a chain id, the transaction could be replayed on any chain
+        let chain_id = match v {
+            27 | 28 => {
+                return Err(Error::ParseError(String::from(
+                    "Transactions must be signed for a chain id with EIP-155",
+                )))
+            }
+            v if v >= 35 => (v - 35) / 2,
+            _ => return Err(Error::ParseError(format!("Invalid v: {}", v))),
+        };
+        let mut signed = RlpStream::new_list(9);
+        for field in rlp.iter().take(6) {
+            signed.append_raw(field.as_raw(), 1);
+        }
+        signed.append(&chain_id).append(&0u8).append(&0u8);
+        let sender = recover_sender(&signed.out(), r, s, ((v - 35) % 2) as i32)?;
+
+        Ok(RawTransaction {
+            raw: raw.to_vec(),
+            nonce: rlp.val_at(0).map_err(parse_error)?,
+            gas_price: rlp.val_at(1).map_err(parse_error)?,
+            gas_limit: rlp.val_at(2).map_err(parse_error)?,
+            to: if to.is_empty() {
+                None
+            } else {
+                Some(to.to_vec())
+            },
+            value: rlp.val_at(4).map_err(parse_error)?,
+            data: rlp.val_at(5).map_err(parse_error)?,
+            fees: None,
+            sender,
+            chain_id,
+        })
+    }
+
+    /// Decodes an EIP-1559 transaction, the type byte followed by the list of its
+    /// chain id, nonce, max priority fee, max fee, gas limit, recipient, value,
+    /// data, access list and signature. The access list is checked but not used,
+    /// since the transaction processor reads state as it goes regardless.
+    fn decode_dynamic_fee(raw: &[u8]) -> Result<Self, Error> {
+        let parse_error = |error| Error::ParseError(format!("Malformed transaction: {}", error));
+        let rlp = Rlp::new(&raw[1..]);
+        if rlp.item_count().map_err(parse_error)? != 12 || encoded_len(&rlp)? != raw.len() - 1 {
+            return Err(Error::ParseError(String::from(
+                "Transaction must be a list of 12 fields",
+            )));
+        }
+
+        let to = rlp.at(5).and_then(|to| to.data()).map_err(parse_error)?;
+        if !to.is_empty() && to.len() != 20 {
+            return Err(Error::ParseError(String::from(
+                "Recipient must be 20 bytes",
+            )));
+        }
+        let access_list = rlp.at(8).map_err(parse_error)?;
+        let valid_access_list = access_list.is_list()
+            && access_list.iter().all(|entry| {
+                entry.item_count() == Ok(2)
+                    && entry
+                        .at(0)
+                        .and_then(|address| address.data())
+                        .map(<[u8]>::len)
+                        == Ok(20)
+                    && entry.at(1).map(|keys| {
+                        keys.is_list()
+                            && keys.iter().all(|key| key.data().map(<[u8]>::len) == Ok(32))
+                    }) == Ok(true)
+            });
+        if !valid_access_list {
+            return Err(Error::ParseError(String::from(
+                "Access list must be a list of addresses and storage keys",
+            )));
+        }
+
+        let max_priority_fee_per_gas: u64 = rlp.val_at(2).map_err(parse_error)?;
+        let max_fee_per_gas: u64 = rlp.val_at(3).map_err(parse_error)?;
+        if max_priority_fee_per_gas > max_fee_per_gas {
+            return Err(Error::ParseError(String::from(
+                "maxPriorityFeePerGas must be at most maxFeePerGas",
+            )));
+        }
+
+        let y_parity: u64 = rlp.val_at(9).map_err(parse_error)?;
+        if y_parity > 1 {
+            return Err(Error::ParseError(format!("Invalid y parity: {}", y_parity)));
+        }
+        let r = rlp.at(10).and_then(|r| r.data()).map_err(parse_error)?;
+        let s = rlp.at(11).and_then(|s| s.data()).map_err(parse_error)?;
+        let mut signed = RlpStream::new_list(9);
+        for field in rlp.iter().take(9) {
+            signed.append_raw(field.as_raw(), 1);
+        }
+        let mut payload = vec![DYNAMIC_FEE_TXN_TYPE];
+        payload.extend_from_slice(&signed.out());
+        let sender = recover_sender(&payload, r, s, y_parity as i32)?;
+
+        Ok(RawTransaction {
+            raw: raw.to_vec(),
+            nonce: rlp.val_at(1).map_err(parse_error)?,
+            gas_price: max_fee_per_gas,
+            gas_limit: rlp.val_at(4).map_err(parse_error)?,
+            to: if to.is_empty() {
+                None
+            } else {
+                Some(to.to_vec())
+            },
+            value: rlp.val_at(6).map_err(parse_error)?,
+            data: rlp.val_at(7).map_err(parse_error)?,
+            fees: Some((max_fee_per_gas, max_priority_fee_per_gas)),
+            sender,
+            chain_id: rlp.val_at(0).map_err(parse_error)?,
+        })
+    }
+
+    /// The chain the transaction was signed for with EIP-155
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    /// The seth address of the key that signed the transaction
+    pub fn sender(&self) -> &str {
+        &self.sender
+    }
+
+    /// The Ethereum hash of the transaction, which wallets look it up by
+    pub fn hash(&self) -> String {
+        transform::bytes_to_hex_str(&crypto_backend::keccak256(&self.raw))
+    }
+
+    /// The seth transaction the processor runs this as, on behalf of `sender`
+    pub fn to_seth_transaction(&self) -> SethTransaction {
+        match self.to {
+            None => {
+                let mut txn = CreateContractAccountTxnPb::new();
+                txn.set_nonce(self.nonce);
+                txn.set_gas_price(self.gas_price);
+                txn.set_gas_limit(self.gas_limit);
+                txn.set_value(self.value);
+                txn.set_init(self.data.clone());
+                if let Some((max_fee, max_priority_fee)) = self.fees {
+                    txn.set_max_fee_per_gas(max_fee);
+                    txn.set_max_priority_fee_per_gas(max_priority_fee);
+                }
+                SethTransaction::CreateContractAccount(txn)
+            }
+            Some(ref to) => {
+                let mut txn = MessageCallTxnPb::new();
+                txn.set_nonce(self.nonce);
+                txn.set_gas_price(self.gas_price);
+                txn.set_gas_limit(self.gas_limit);
+                txn.set_to(to.clone());
+                txn.set_value(self.value);
+                txn.set_data(self.data.clone());
+                if let Some((max_fee, max_priority_fee)) = self.fees {
+                    txn.set_max_fee_per_gas(max_fee);
+                    txn.set_max_priority_fee_per_gas(max_priority_fee);
+                }
+                SethTransaction::MessageCall(txn)
+            }
+        }
+    }
+}
+
+/// The length of the item the data starts with, which is less than the length of
+/// the data if anything follows the item
+fn encoded_len(rlp: &Rlp) -> Result<usize, Error> {
+    rlp.payload_info()
+        .map(|info| info.header_len + info.value_len)
+        .map_err(|error| Error::ParseError(format!("Malformed transaction: {}", error)))
+}
+
+fn recover_sender(signed: &[u8], r: &[u8], s: &[u8], recovery_id: i32) -> Result<String, Error> {
+    if r.len() > 32 || s.len() > 32 {
+        return Err(Error::ParseError(String::from(
+            "r and s must be at most 32 bytes",
+        )));
+    }
+    let mut signature = [0; 64];
+    signature[32 - r.len()..32].copy_from_slice(r);
+    signature[64 - s.len()..].copy_from_slice(s);
+
+    let hash = crypto_backend::keccak256(signed);
+    let public_key = crypto_backend::recover(&hash, &signature, recovery_id)
+        .ok_or_else(|| Error::ParseError(String::from("Invalid signature")))?;
+    Ok(public_key_to_address(&public_key))
+}
+
+/// Encodes a contract creation or message call as a legacy transaction with the
+/// given `v`, `r` and `s`, or with the chain id and two empty values when it is
+/// encoded to be signed with EIP-155. Private transactions and those with a salt
+/// have no Ethereum form.
+#[cfg(feature = "ledger")]
+pub fn encode_legacy(txn: &SethTransaction, v: u64, r: &[u8], s: &[u8]) -> Option<Vec<u8>> {
+    let mut stream = RlpStream::new_list(9);
+    match *txn {
+        SethTransaction::MessageCall(ref txn) if txn.get_private_payload_hash().is_empty() => {
+            stream
+                .append(&txn.get_nonce())
+                .append(&txn.get_gas_price())
+                .append(&txn.get_gas_limit())
+                .append(&txn.get_to())
+                .append(&txn.get_value())
+                .append(&txn.get_data());
+        }
+        SethTransaction::CreateContractAccount(ref txn)
+            if txn.get_private_payload_hash().is_empty() && txn.get_salt().is_empty() =>
+        {
+            stream
+                .append(&txn.get_nonce())
+                .append(&txn.get_gas_price())
+                .append(&txn.get_gas_limit())
+                .append_empty_data()
+                .append(&txn.get_value())
+                .append(&txn.get_init());
+        }
+        _ => return None,
+    }
+    // r and s are numbers, so they are encoded without leading zeros
+    let trimmed = |bytes: &[u8]| bytes.iter().skip_while(|&&byte| byte == 0).count();
+    stream
+        .append(&v)
+        .append(&&r[r.len() - trimmed(r)..])
+        .append(&&s[s.len() - trimmed(s)..]);
+    Some(stream.out().to_vec())
+}
+
+/// The ids of the seth transactions that Ethereum transactions were relayed in, by
+/// Ethereum hash, so that they can be looked up by the hash the wallet knows. Only
+/// transactions relayed by this server are known, and the oldest are forgotten once
+/// `MAX_HASHES` are.
+#[derive(Clone, Default)]
+pub struct RawTransactionIndex {
+    index: Arc<Mutex<Hashes>>,
+}
+
+#[derive(Default)]
+struct Hashes {
+    txn_ids: HashMap<String, String>,
+
+    /// The hashes in the order they were inserted
+    order: VecDeque<String>,
+}
+
+impl RawTransactionIndex {
+    pub fn new() -> Self {
+        RawTransactionIndex::default()
+    }
+
+    pub fn insert(&self, hash: &str, txn_id: &str) {
+        let mut hashes = self.index.lock().unwrap();
+        if hashes.order.len() >= MAX_HASHES {
+            if let Some(oldest) = hashes.order.pop_front() {
+                hashes.txn_ids.remove(&oldest);
+            }
+        }
+        let previous = hashes
+            .txn_ids
+            .insert(String::from(hash), String::from(txn_id));
+        if previous.is_none() {
+            hashes.order.push_back(String::from(hash));
+        }
+    }
+
+    /// Returns the id of the seth transaction a hash refers to, which is the hash
+    /// itself unless it is the Ethereum hash of a relayed transaction
+    pub fn txn_id(&self, hash: &str) -> String {
+        self.index
+            .lock()
+            .unwrap()
+            .txn_ids
+            .get(hash)
+            .cloned()
+            .unwrap_or_else(|| String::from(hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use accounts::compressed_public_key;
+    use calls::network::DEFAULT_CHAIN_ID;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use secp256k1::key::{PublicKey, SecretKey};
+    use secp256k1::{Message, Secp256k1};
+    use std::sync::OnceLock;
+
+    #[test]
+    fn decodes_eip155_transaction() {
+        // The transaction of the EIP-155 example, signed for seth's chain by the
+        // same key
+        let secp = Secp256k1::new();
+        let secret = SecretKey::from_slice(&secp, &[0x46; 32]).unwrap();
+        let public_key = PublicKey::from_secret_key(&secp, &secret).unwrap();
+
+        let mut unsigned = RlpStream::new_list(9);
+        unsigned
+            .append(&9u64)
+            .append(&20_000_000_000u64)
+            .append(&21000u64)
+            .append(&vec![0x35u8; 20])
+            .append(&1_000_000_000_000_000_000u64)
+            .append_empty_data()
+            .append(&DEFAULT_CHAIN_ID)
+            .append(&0u8)
+            .append(&0u8);
+        let hash = crypto_backend::keccak256(&unsigned.out());
+        let message = Message::from_slice(&hash).unwrap();
+        let (recovery_id, signature) = secp
+            .sign_recoverable(&message, &secret)
+            .unwrap()
+            .serialize_compact(&secp);
+
+        let mut signed = RlpStream::new_list(9);
+        signed
+            .append(&9u64)
+            .append(&20_000_000_000u64)
+            .append(&21000u64)
+            .append(&vec![0x35u8; 20])
+            .append(&1_000_000_000_000_000_000u64)
+            .append_empty_data()
+            .append(&(35 + 2 * DEFAULT_CHAIN_ID + recovery_id.to_i32() as u64))
+            .append(&signature[..32].to_vec())
+            .append(&signature[32..].to_vec());
+        let raw = signed.out().to_vec();
+
+        let txn = RawTransaction::decode(&raw).unwrap();
+        assert_eq!(txn.nonce, 9);
+        assert_eq!(txn.gas_limit, 21000);
+        assert_eq!(txn.to, Some(vec![0x35; 20]));
+        assert_eq!(txn.value, 1_000_000_000_000_000_000);
+        assert_eq!(
+            txn.sender(),
+            public_key_to_address(&compressed_public_key(&secp, &public_key))
+        );
+        assert_eq!(txn.chain_id(), DEFAULT_CHAIN_ID);
+
+        // Signed for another chain, which recovers some other sender
+        let chain_1 = RawTransaction::decode(&decode_with_v(&raw, 37)).unwrap();
+        assert_eq!(chain_1.chain_id(), 1);
+        assert_ne!(chain_1.sender(), txn.sender());
+
+        // Signed without a chain id, which any chain would accept
+        assert!(RawTransaction::decode(&decode_with_v(&raw, 27)).is_err());
+        assert!(RawTransaction::decode(&decode_with_v(&raw, 28)).is_err());
+    }
+
+    #[test]
+    fn decodes_eip1559_transaction() {
+        let secp = Secp256k1::new();
+        let secret = SecretKey::from_slice(&secp, &[0x46; 32]).unwrap();
+        let public_key = PublicKey::from_secret_key(&secp, &secret).unwrap();
+
+        let mut access_list = RlpStream::new_list(1);
+        access_list
+            .begin_list(2)
+            .append(&vec![0x35u8; 20])
+            .begin_list(1)
+            .append(&vec![0u8; 32]);
+        let access_list = access_list.out();
+        let fields = |stream: &mut RlpStream| {
+            stream
+                .append(&DEFAULT_CHAIN_ID)
+                .append(&9u64)
+                .append(&2_000_000_000u64)
+                .append(&30_000_000_000u64)
+                .append(&21000u64)
+                .append(&vec![0x35u8; 20])
+                .append(&1_000_000u64)
+                .append_empty_data()
+                .append_raw(&access_list, 1);
+        };
+        let mut unsigned = RlpStream::new_list(9);
+        fields(&mut unsigned);
+        let mut payload = vec![DYNAMIC_FEE_TXN_TYPE];
+        payload.extend_from_slice(&unsigned.out());
+        let hash = crypto_backend::keccak256(&payload);
+        let message = Message::from_slice(&hash).unwrap();
+        let (recovery_id, signature) = secp
+            .sign_recoverable(&message, &secret)
+            .unwrap()
+            .serialize_compact(&secp);
+
+        let mut signed = RlpStream::new_list(12);
+        fields(&mut signed);
+        signed
+            .append(&(recovery_id.to_i32() as u64))
+            .append(&signature[..32].to_vec())
+            .append(&signature[32..].to_vec());
+        let mut raw = vec![DYNAMIC_FEE_TXN_TYPE];
+        raw.extend_from_slice(&signed.out());
+
+        let txn = RawTransaction::decode(&raw).unwrap();
+        assert_eq!(txn.nonce, 9);
+        assert_eq!(txn.gas_price, 30_000_000_000);
+        assert_eq!(txn.fees, Some((30_000_000_000, 2_000_000_000)));
+        assert_eq!(txn.to, Some(vec![0x35; 20]));
+        assert_eq!(txn.value, 1_000_000);
+        assert_eq!(txn.chain_id(), DEFAULT_CHAIN_ID);
+        assert_eq!(
+            txn.sender(),
+            public_key_to_address(&compressed_public_key(&secp, &public_key))
+        );
+        // The hash covers the type
+        assert_eq!(
+            txn.hash(),
+            transform::bytes_to_hex_str(&crypto_backend::keccak256(&raw))
+        );
+
+        // Other typed transactions are still rejected
+        raw[0] = 1;
+        assert!(RawTransaction::decode(&raw).is_err());
+    }
+
+    #[cfg(feature = "ledger")]
+    #[test]
+    fn encodes_seth_transactions_as_legacy() {
+        let secp = Secp256k1::new();
+        let secret = SecretKey::from_slice(&secp, &[0x46; 32]).unwrap();
+        let public_key = PublicKey::from_secret_key(&secp, &secret).unwrap();
+
+        let mut call = MessageCallTxnPb::new();
+        call.set_nonce(9);
+        call.set_gas_price(20_000_000_000);
+        call.set_gas_limit(21000);
+        call.set_to(vec![0x35; 20]);
+        call.set_value(1_000_000);
+        call.set_data(vec![0xab, 0xcd]);
+        let call = SethTransaction::MessageCall(call);
+
+        let unsigned = encode_legacy(&call, DEFAULT_CHAIN_ID, &[], &[]).unwrap();
+        let hash = crypto_backend::keccak256(&unsigned);
+        let message = Message::from_slice(&hash).unwrap();
+        let (recovery_id, signature) = secp
+            .sign_recoverable(&message, &secret)
+            .unwrap()
+            .serialize_compact(&secp);
+        let v = 35 + 2 * DEFAULT_CHAIN_ID + recovery_id.to_i32() as u64;
+        let raw = encode_legacy(&call, v, &signature[..32], &signature[32..]).unwrap();
+
+        let txn = RawTransaction::decode(&raw).unwrap();
+        assert_eq!(txn.nonce, 9);
+        assert_eq!(txn.gas_price, 20_000_000_000);
+        assert_eq!(txn.to, Some(vec![0x35; 20]));
+        assert_eq!(txn.data, vec![0xab, 0xcd]);
+        assert_eq!(txn.chain_id(), DEFAULT_CHAIN_ID);
+        assert_eq!(
+            txn.sender(),
+            public_key_to_address(&compressed_public_key(&secp, &public_key))
+        );
+
+        // Leading zeros of r and s aren't encoded
+        let padded = encode_legacy(&call, 37, &[0, 0, 1], &[0, 2]).unwrap();
+        let rlp = Rlp::new(&padded);
+        assert_eq!(rlp.at(7).and_then(|r| r.data()), Ok(&[1][..]));
+        assert_eq!(rlp.at(8).and_then(|s| s.data()), Ok(&[2][..]));
+
+        let mut create = CreateContractAccountTxnPb::new();
+        create.set_init(vec![0x60, 0x00]);
+        let create = encode_legacy(&SethTransaction::CreateContractAccount(create), 1, &[], &[]);
+        assert_eq!(
+            Rlp::new(&create.unwrap()).at(3).and_then(|to| to.data()),
+            Ok(&[][..])
+        );
+        let mut salted = CreateContractAccountTxnPb::new();
+        salted.set_salt(vec![1; 32]);
+        let salted = SethTransaction::CreateContractAccount(salted);
+        assert!(encode_legacy(&salted, 1, &[], &[]).is_none());
+    }
+
+    fn decode_with_v(raw: &[u8], v: u64) -> Vec<u8> {
+        let rlp = Rlp::new(raw);
+        let mut stream = RlpStream::new_list(9);
+        for (index, field) in rlp.iter().enumerate() {
+            if index == 6 {
+                stream.append(&v);
+            } else {
+                stream.append_raw(field.as_raw(), 1);
+            }
+        }
+        stream.out().to_vec()
+    }
+
+    /// The fields of a legacy transaction that a wallet chooses
+    #[derive(Debug)]
+    struct Legacy {
+        nonce: u64,
+        gas_price: u64,
+        gas_limit: u64,
+        to: Option<Vec<u8>>,
+        value: u64,
+        data: Vec<u8>,
+    }
+
+    fn legacy() -> impl Strategy<Value = Legacy> {
+        (
+            any::<u64>(),
+            any::<u64>(),
+            any::<u64>(),
+            proptest::option::of(vec(any::<u8>(), 20)),
+            any::<u64>(),
+            vec(any::<u8>(), 0..300),
+        )
+            .prop_map(|(nonce, gas_price, gas_limit, to, value, data)| Legacy {
+                nonce,
+                gas_price,
+                gas_limit,
+                to,
+                value,
+                data,
+            })
+    }
+
+    /// Keys below the curve order, none of them zero
+    fn secret_key() -> impl Strategy<Value = [u8; 32]> {
+        proptest::array::uniform32(1u8..0x80)
+    }
+
+    /// Signs the transaction for the chain with EIP-155, returning it and the seth
+    /// address of the key
+    fn sign_legacy(txn: &Legacy, secret: &[u8; 32], chain_id: u64) -> (Vec<u8>, String) {
+        let fields = |stream: &mut RlpStream| {
+            stream
+                .append(&txn.nonce)
+                .append(&txn.gas_price)
+                .append(&txn.gas_limit)
+                .append(&txn.to.clone().unwrap_or_default())
+                .append(&txn.value)
+                .append(&txn.data);
+        };
+        let mut unsigned = RlpStream::new_list(9);
+        fields(&mut unsigned);
+        unsigned.append(&chain_id).append(&0u8).append(&0u8);
+        // Making a context is slow, so every case shares one
+        static SECP: OnceLock<Secp256k1> = OnceLock::new();
+        let secp = SECP.get_or_init(Secp256k1::new);
+        let secret = SecretKey::from_slice(secp, secret).unwrap();
+        let message = Message::from_slice(&crypto_backend::keccak256(&unsigned.out())).unwrap();
+        let (recovery_id, signature) = secp
+            .sign_recoverable(&message, &secret)
+            .unwrap()
+            .serialize_compact(secp);
+
+        let mut signed = RlpStream::new_list(9);
+        fields(&mut signed);
+        signed
+            .append(&(35 + 2 * chain_id + recovery_id.to_i32() as u64))
+            .append(&signature[..32].to_vec())
+            .append(&signature[32..].to_vec());
+        let public_key = PublicKey::from_secret_key(secp, &secret).unwrap();
+        (
+            signed.out().to_vec(),
+            public_key_to_address(&compressed_public_key(secp, &public_key)),
+        )
+    }
+
+    /// Re-encodes a list with a length prefix that is off by `delta`
+    fn with_list_length(raw: &[u8], delta: i64) -> Vec<u8> {
+        let info = Rlp::new(raw).payload_info().unwrap();
+        let length = (info.value_len as i64 + delta) as usize;
+        let mut encoded = if length < 56 {
+            vec![0xc0 + length as u8]
+        } else {
+            let bytes: Vec<u8> = length
+                .to_be_bytes()
+                .iter()
+                .cloned()
+                .skip_while(|&byte| byte == 0)
+                .collect();
+            let mut header = vec![0xf7 + bytes.len() as u8];
+            header.extend(bytes);
+            header
+        };
+        encoded.extend_from_slice(&raw[info.header_len..]);
+        encoded
+    }
+
+    proptest! {
+        #[test]
+        fn decodes_the_legacy_transactions_it_is_sent(txn in legacy(), secret in secret_key()) {
+            let (raw, sender) = sign_legacy(&txn, &secret, DEFAULT_CHAIN_ID);
+            let decoded = RawTransaction::decode(&raw).unwrap();
+            prop_assert_eq!(decoded.nonce, txn.nonce);
+            prop_assert_eq!(decoded.gas_price, txn.gas_price);
+            prop_assert_eq!(decoded.gas_limit, txn.gas_limit);
+            prop_assert_eq!(&decoded.to, &txn.to);
+            prop_assert_eq!(decoded.value, txn.value);
+            prop_assert_eq!(&decoded.data, &txn.data);
+            prop_assert_eq!(decoded.fees, None);
+            prop_assert_eq!(decoded.chain_id(), DEFAULT_CHAIN_ID);
+            prop_assert_eq!(decoded.sender(), sender.as_str());
+            prop_assert_eq!(decoded.raw, raw);
+        }
+
+        #[test]
+        fn rejects_a_trailing_payload(
+            txn in legacy(),
+            secret in secret_key(),
+            trailing in vec(any::<u8>(), 1..40),
+        ) {
+            let (mut raw, _) = sign_legacy(&txn, &secret, DEFAULT_CHAIN_ID);
+            raw.extend(trailing);
+            prop_assert!(RawTransaction::decode(&raw).is_err());
+        }
+
+        #[test]
+        fn rejects_wrong_length_prefixes(
+            txn in legacy(),
+            secret in secret_key(),
+            delta in prop_oneof![-8i64..0, 1i64..9],
+        ) {
+            let (raw, _) = sign_legacy(&txn, &secret, DEFAULT_CHAIN_ID);
+            prop_assert!(RawTransaction::decode(&with_list_length(&raw, delta)).is_err());
+        }
+
+        #[test]
+        fn rejects_quantities_wider_than_64_bits(
+            txn in legacy(),
+            secret in secret_key(),
+            field in prop_oneof![Just(0usize), Just(1), Just(2), Just(4)],
+            wide in (1u8.., vec(any::<u8>(), 8..24)),
+        ) {
+            let (raw, _) = sign_legacy(&txn, &secret, DEFAULT_CHAIN_ID);
+            let (first, rest) = wide;
+            let mut quantity = vec![first];
+            quantity.extend(rest);
+            let mut widened = RlpStream::new_list(9);
+            for (index, item) in Rlp::new(&raw).iter().enumerate() {
+                if index == field {
+                    widened.append(&quantity);
+                } else {
+                    widened.append_raw(item.as_raw(), 1);
+                }
+            }
+            prop_assert!(RawTransaction::decode(&widened.out()).is_err());
+        }
+    }
+}
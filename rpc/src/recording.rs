@@ -0,0 +1,286 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Recording of the requests a server is sent and its responses to them, and
+//! replaying of the recorded requests against another build, so that the exchanges
+//! behind a reported integration bug can be reproduced exactly. A recording has a
+//! line of JSON for each request or batch, with the request and the response sent
+//! back. Passwords, private keys and keystores are redacted before they are
+//! written, and the requests they were redacted from aren't replayed.
+
+use futures::future::{Either, Future, FutureExt};
+use jsonrpc_core::{BoxFuture, MetaIoHandler, Middleware, Request, Response};
+use requests::RequestMeta;
+use serde_json::{self, Map, Value};
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, LineWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// What secrets are replaced with
+const REDACTED: &str = "<redacted>";
+
+/// The positions of the params of each method that are secrets
+const SECRET_PARAMS: &[(&str, &[usize])] = &[
+    ("personal_newAccount", &[0]),
+    ("personal_unlockAccount", &[1]),
+    ("personal_sendTransaction", &[1]),
+    ("personal_importRawKey", &[0, 1]),
+    ("personal_exportKeystore", &[1]),
+];
+
+/// The methods whose results are secrets
+const SECRET_RESULTS: &[&str] = &["personal_exportKeystore"];
+
+/// Writes each request the server is sent to a recording along with its response.
+/// The default recorder records nothing.
+#[derive(Clone, Default)]
+pub struct Recorder {
+    file: Option<Arc<Mutex<LineWriter<File>>>>,
+}
+
+impl Recorder {
+    /// Records to the end of the file at `path`, creating it if needed
+    pub fn open(path: &Path) -> Result<Self, io::Error> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        Ok(Recorder {
+            file: Some(Arc::new(Mutex::new(LineWriter::new(file)))),
+        })
+    }
+
+    fn record(&self, request: Value, response: Value) {
+        let file = match self.file {
+            Some(ref file) => file,
+            None => return,
+        };
+        let mut record = record(request, response);
+        redact(&mut record);
+        let mut file = file.lock().unwrap();
+        if let Err(err) = writeln!(file, "{}", record) {
+            error!("Failed to record request: {}", err);
+        }
+    }
+}
+
+impl Middleware<RequestMeta> for Recorder {
+    type Future = BoxFuture<Option<Response>>;
+    type CallFuture = BoxFuture<Option<jsonrpc_core::Output>>;
+
+    fn on_request<F, X>(
+        &self,
+        request: Request,
+        meta: RequestMeta,
+        next: F,
+    ) -> Either<Self::Future, X>
+    where
+        F: Fn(Request, RequestMeta) -> X + Send + Sync,
+        X: Future<Output = Option<Response>> + Send + 'static,
+    {
+        if self.file.is_none() {
+            return Either::Right(next(request, meta));
+        }
+        let recorded = serde_json::to_value(&request).unwrap_or(Value::Null);
+        let recorder = self.clone();
+        Either::Left(
+            next(request, meta)
+                .map(move |response| {
+                    recorder.record(
+                        recorded,
+                        serde_json::to_value(&response).unwrap_or(Value::Null),
+                    );
+                    response
+                })
+                .boxed(),
+        )
+    }
+}
+
+fn record(request: Value, response: Value) -> Value {
+    let mut record = Map::new();
+    record.insert(String::from("request"), request);
+    record.insert(String::from("response"), response);
+    Value::Object(record)
+}
+
+/// Replaces the secrets in a record of a request and its response
+fn redact(record: &mut Value) {
+    let mut secret_ids = HashSet::new();
+    for call in calls(&mut record["request"]) {
+        let method = call["method"].as_str().map(String::from);
+        let method = match method {
+            Some(method) => method,
+            None => continue,
+        };
+        if let Some(&(_, positions)) = SECRET_PARAMS.iter().find(|&&(name, _)| name == method) {
+            if let Some(params) = call["params"].as_array_mut() {
+                for &position in positions {
+                    if let Some(param) = params.get_mut(position) {
+                        *param = Value::from(REDACTED);
+                    }
+                }
+            }
+        }
+        if SECRET_RESULTS.contains(&method.as_str()) {
+            secret_ids.insert(call["id"].to_string());
+        }
+    }
+    for output in calls(&mut record["response"]) {
+        if output.get("result").is_some() && secret_ids.contains(&output["id"].to_string()) {
+            output["result"] = Value::from(REDACTED);
+        }
+    }
+}
+
+/// Returns the calls of a request, or the outputs of a response, whether or not it
+/// is a batch
+fn calls(message: &mut Value) -> Vec<&mut Value> {
+    match *message {
+        Value::Array(ref mut calls) => calls.iter_mut().collect(),
+        Value::Object(_) => vec![message],
+        _ => Vec::new(),
+    }
+}
+
+/// A request whose response when it was replayed wasn't the one recorded
+#[derive(Debug)]
+pub struct Difference {
+    pub request: Value,
+    pub recorded: Value,
+    pub replayed: Value,
+}
+
+#[derive(Debug, Default)]
+pub struct Replay {
+    pub replayed: usize,
+    /// The requests that weren't replayed since secrets were redacted from them
+    pub skipped: usize,
+    pub differences: Vec<Difference>,
+}
+
+/// Sends each request of a recording to `io` in turn, comparing its response with
+/// the one recorded
+pub fn replay<M, R>(io: &MetaIoHandler<RequestMeta, M>, recording: R) -> Result<Replay, io::Error>
+where
+    M: Middleware<RequestMeta>,
+    R: BufRead,
+{
+    let mut replay = Replay::default();
+    for line in recording.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut recorded: Value = serde_json::from_str(&line)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let request = recorded["request"].take();
+        if request.to_string().contains(REDACTED) {
+            replay.skipped += 1;
+            continue;
+        }
+
+        let response = io
+            .handle_request_sync(&request.to_string(), RequestMeta::default())
+            .and_then(|response| serde_json::from_str(&response).ok())
+            .unwrap_or(Value::Null);
+        let mut replayed = record(request, response);
+        redact(&mut replayed);
+        replay.replayed += 1;
+        if replayed["response"] != recorded["response"] {
+            replay.differences.push(Difference {
+                request: replayed["request"].take(),
+                recorded: recorded["response"].take(),
+                replayed: replayed["response"].take(),
+            });
+        }
+    }
+    Ok(replay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use jsonrpc_core::Params;
+
+    fn parse(json: &str) -> Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn redacts_passwords_and_keystores() {
+        let mut record = parse(
+            r#"{
+                "request": [
+                    {"jsonrpc": "2.0", "id": 1, "method": "personal_unlockAccount",
+                     "params": ["0x0123", "hunter2", 300]},
+                    {"jsonrpc": "2.0", "id": 2, "method": "personal_exportKeystore",
+                     "params": ["0x0123", "hunter2"]},
+                    {"jsonrpc": "2.0", "id": 3, "method": "eth_chainId", "params": []}
+                ],
+                "response": [
+                    {"jsonrpc": "2.0", "id": 1, "result": true},
+                    {"jsonrpc": "2.0", "id": 2, "result": {"crypto": {}}},
+                    {"jsonrpc": "2.0", "id": 3, "result": "0x1"}
+                ]
+            }"#,
+        );
+        redact(&mut record);
+        assert_eq!(
+            record["request"][0]["params"],
+            parse(r#"["0x0123", "<redacted>", 300]"#)
+        );
+        assert_eq!(
+            record["request"][1]["params"],
+            parse(r#"["0x0123", "<redacted>"]"#)
+        );
+        assert_eq!(record["response"][0]["result"], Value::Bool(true));
+        assert_eq!(record["response"][1]["result"], Value::from(REDACTED));
+        assert_eq!(record["response"][2]["result"], Value::from("0x1"));
+    }
+
+    #[test]
+    fn replays_recorded_requests() {
+        let mut io = MetaIoHandler::<RequestMeta>::default();
+        io.add_method_with_meta("eth_chainId", |_: Params, _: RequestMeta| {
+            future::ok(Value::from("0x2"))
+        });
+        io.add_method_with_meta("net_version", |_: Params, _: RequestMeta| {
+            future::ok(Value::from("19"))
+        });
+        let recording = concat!(
+            r#"{"request": {"jsonrpc": "2.0", "id": 1, "method": "eth_chainId", "params": []},"#,
+            r#" "response": {"jsonrpc": "2.0", "id": 1, "result": "0x1"}}"#,
+            "\n",
+            r#"{"request": {"jsonrpc": "2.0", "id": 2, "method": "net_version", "params": []},"#,
+            r#" "response": {"jsonrpc": "2.0", "id": 2, "result": "19"}}"#,
+            "\n",
+            r#"{"request": {"jsonrpc": "2.0", "id": 3, "method": "personal_unlockAccount","#,
+            r#" "params": ["0x0123", "<redacted>"]},"#,
+            r#" "response": {"jsonrpc": "2.0", "id": 3, "result": true}}"#,
+            "\n",
+        );
+
+        let replay = replay(&io, recording.as_bytes()).unwrap();
+        assert_eq!(replay.replayed, 2);
+        assert_eq!(replay.skipped, 1);
+        assert_eq!(replay.differences.len(), 1);
+        assert_eq!(replay.differences[0].request["method"], "eth_chainId");
+        assert_eq!(replay.differences[0].recorded["result"], "0x1");
+        assert_eq!(replay.differences[0].replayed["result"], "0x2");
+    }
+}
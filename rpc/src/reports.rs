@@ -0,0 +1,241 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Signed reports of what watched addresses did between two times, built from the
+//! history the log index keeps of them. A report is signed by an account of the
+//! server the way `eth_sign` signs its JSON text, so that an archived copy can be
+//! shown to come from the server and not to have changed since. Reports can also
+//! be written to a directory once a period, for each period as it ends.
+
+use accounts::{self, Account};
+use client::ValidatorClient;
+use log_index::LogIndex;
+use sawtooth_sdk::messaging::stream::MessageSender;
+use serde_json;
+use sled;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use types::{self, Quantity, Wei};
+
+/// How long after a period ends its report is written, so that the blocks of its
+/// last seconds have been committed and indexed
+const REPORT_DELAY: Duration = Duration::from_secs(60);
+
+/// Reports what a watched address did in the blocks from `from` to `to`
+pub fn address_report(
+    index: &LogIndex,
+    address: &str,
+    from: u64,
+    to: u64,
+) -> Result<types::WatchedAddressReport, sled::Error> {
+    let watchlists = index
+        .watchlists()?
+        .into_iter()
+        .filter(|(_, addresses)| addresses.iter().any(|watched| watched == address))
+        .map(|(name, _)| name)
+        .collect();
+    let (balances, transactions) = index.watched_activity(address, from, to)?;
+
+    Ok(types::WatchedAddressReport {
+        address: format!("0x{}", address),
+        watchlists,
+        balances: balances
+            .into_iter()
+            .map(|(block_num, balance)| types::WatchedBalance {
+                block_number: Quantity(block_num),
+                balance: Wei::from(balance as u64),
+            })
+            .collect(),
+        transactions: transactions
+            .into_iter()
+            .filter_map(|txn| serde_json::from_value(txn).ok())
+            .collect(),
+    })
+}
+
+/// Reports what watched addresses did in the blocks whose transactions ran
+/// between two times in seconds, inclusive
+pub fn activity_report(
+    index: &LogIndex,
+    addresses: &[String],
+    from_time: u64,
+    to_time: u64,
+) -> Result<types::ActivityReport, sled::Error> {
+    let blocks = index.blocks_between(from_time, to_time)?;
+    let addresses = match blocks {
+        Some((from, to)) => addresses
+            .iter()
+            .map(|address| address_report(index, address, from, to))
+            .collect::<Result<_, _>>()?,
+        None => Vec::new(),
+    };
+
+    Ok(types::ActivityReport {
+        from_time: Quantity(from_time),
+        to_time: Quantity(to_time),
+        from_block: blocks.map(|(from, _)| Quantity(from)),
+        to_block: blocks.map(|(_, to)| Quantity(to)),
+        generated_at: Quantity(now()),
+        addresses,
+    })
+}
+
+/// Signs the JSON text of a report as `eth_sign` signs a message
+pub fn sign(
+    report: &types::ActivityReport,
+    signer: &Account,
+) -> Result<types::SignedReport, String> {
+    let report = serde_json::to_string(report)
+        .map_err(|error| format!("Failed to serialize report: {}", error))?;
+    let signature = signer
+        .sign(&accounts::personal_message_hash(report.as_bytes()))
+        .map_err(|error| format!("Failed to sign report: {}", error))?;
+
+    Ok(types::SignedReport {
+        report,
+        signer: format!("0x{}", signer.address()),
+        signature: format!("0x{}", signature),
+    })
+}
+
+/// Writes a signed report of every watched address to `dir` after each period of
+/// `period` seconds ends, counting periods from the Unix epoch, so that daily
+/// reports cover days in UTC
+pub fn start<S>(
+    client: ValidatorClient<S>,
+    index: LogIndex,
+    dir: PathBuf,
+    period: u64,
+    signer: Account,
+) where
+    S: MessageSender + Clone + Send + Sync + 'static,
+{
+    thread::spawn(move || loop {
+        let end = (now() / period + 1) * period;
+        thread::sleep(Duration::from_secs(end - now()) + REPORT_DELAY);
+
+        if let Err(error) = index.catch_up(&client) {
+            warn!("Failed to catch up the log index for reports: {}", error);
+        }
+        let from_time = end - period;
+        let path = dir.join(format!("activity-{}.json", from_time));
+        match write_report(&index, &path, from_time, end - 1, &signer) {
+            Ok(()) => info!("Wrote activity report {}", path.display()),
+            Err(error) => warn!("Failed to write activity report: {}", error),
+        }
+    });
+}
+
+fn write_report(
+    index: &LogIndex,
+    path: &Path,
+    from_time: u64,
+    to_time: u64,
+    signer: &Account,
+) -> Result<(), String> {
+    let mut addresses: Vec<String> = index
+        .watched()
+        .map_err(|error| format!("Log index error: {}", error))?
+        .into_iter()
+        .collect();
+    addresses.sort();
+    let report = activity_report(index, &addresses, from_time, to_time)
+        .map_err(|error| format!("Log index error: {}", error))?;
+    let signed = sign(&report, signer)?;
+    let json = serde_json::to_string_pretty(&signed)
+        .map_err(|error| format!("Failed to serialize report: {}", error))?;
+    fs::write(path, json).map_err(|error| format!("{}", error))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use contracts::ContractRegistry;
+    use filters::FilterManager;
+    use mock_chain::MockChain;
+    use std::env;
+    use std::process;
+    use transform;
+
+    #[test]
+    fn writes_signed_reports_of_watched_addresses() {
+        let client = ValidatorClient::new(
+            MockChain::new(),
+            Vec::new(),
+            FilterManager::new(1),
+            0,
+            String::from("1.0"),
+            ContractRegistry::new(),
+        );
+        let dir = env::temp_dir().join(format!("seth-reports-{}", process::id()));
+        let accounts = MockChain::accounts();
+        let (alice, bob) = (&accounts[0], &accounts[1]);
+        let index = LogIndex::open(dir.join("index").to_str().unwrap()).unwrap();
+        index
+            .set_watchlist("users", &[String::from(bob.address())])
+            .unwrap();
+        index.catch_up(&client).unwrap();
+
+        let report = activity_report(&index, &[String::from(bob.address())], 0, u64::MAX).unwrap();
+        assert_eq!(report.from_block, Some(Quantity(0)));
+        assert_eq!(report.to_block, Some(Quantity(3)));
+        let watched = &report.addresses[0];
+        assert_eq!(watched.address, format!("0x{}", bob.address()));
+        assert_eq!(watched.watchlists, vec![String::from("users")]);
+        // Bob creates his account in block 1 and calls the counter twice in block 3
+        assert_eq!(watched.transactions.len(), 3);
+        // The mock chain's blocks after the first run 15 seconds apart from the
+        // Unix time 1600000000
+        let later = activity_report(
+            &index,
+            &[String::from(bob.address())],
+            1_600_000_015,
+            u64::MAX,
+        )
+        .unwrap();
+        assert_eq!(later.from_block, Some(Quantity(2)));
+        assert_eq!(later.addresses[0].transactions.len(), 2);
+        let before =
+            activity_report(&index, &[String::from(bob.address())], 1, 1_599_999_999).unwrap();
+        assert_eq!(before.from_block, None);
+        assert!(before.addresses.is_empty());
+
+        let path = dir.join("report.json");
+        write_report(&index, &path, 0, u64::MAX, alice).unwrap();
+        let signed: types::SignedReport =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(signed.signer, format!("0x{}", alice.address()));
+        let signers = accounts::recover_addresses(
+            &accounts::personal_message_hash(signed.report.as_bytes()),
+            &transform::hex_str_to_bytes(&signed.signature[2..]).unwrap(),
+        )
+        .unwrap();
+        assert!(signers.iter().any(|signer| signer == alice.address()));
+
+        drop(index);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -16,28 +16,360 @@
  */
 
 use super::client::ValidatorClient;
-use futures_cpupool::CpuPool;
-use jsonrpc_core::{BoxFuture, Error, Params, Value};
+use calls::error;
+use futures::executor::ThreadPool;
+use futures::future::{self, Either, Future, FutureExt};
+use futures::task::SpawnExt;
+use jsonrpc_core::{
+    BoxFuture, Error, Failure, Id, Metadata, Middleware, Output, Params, Request, Response,
+    Result as RpcResult, Value, Version,
+};
+#[cfg(feature = "ws")]
+use jsonrpc_pubsub::{PubSubMetadata, Session};
+use response::{json_size, ResponseBudget};
 use sawtooth_sdk::messaging::stream::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// The method load balancers check health with, which `/health` calls
+pub const HEALTH_METHOD: &str = "seth_getHealth";
+
+/// The most threads requests are run on, however many may be pending
+const MAX_REQUEST_THREADS: usize = 64;
 
 pub type RequestHandler<T> = fn(Params, ValidatorClient<T>) -> Result<Value, Error>;
 
+/// What the server knows about a request besides its params
+#[derive(Clone, Default)]
+pub struct RequestMeta {
+    /// The session the request belongs to, from the `X-Seth-Session` header
+    pub session: Option<String>,
+    /// The `User-Agent` of the client that sent the request, if it sent one
+    pub client: Option<String>,
+    /// Whether the client sent credentials the server accepts
+    pub authenticated: bool,
+    /// The WebSocket connection the request came over, which subscriptions are sent to
+    #[cfg(feature = "ws")]
+    pub pubsub: Option<Arc<Session>>,
+}
+
+impl Metadata for RequestMeta {}
+
+#[cfg(feature = "ws")]
+impl PubSubMetadata for RequestMeta {
+    fn session(&self) -> Option<Arc<Session>> {
+        self.pubsub.clone()
+    }
+}
+
+/// How much a method matters when the server is overloaded. Requests are refused
+/// from the lowest priority up, so that health checks and reads keep being served
+/// while expensive requests pile up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Methods that scan many blocks or all of state, or block for a long time
+    Heavy,
+    Write,
+    Read,
+    Health,
+}
+
+impl Priority {
+    pub fn of(method: &str) -> Self {
+        match method {
+            "net_listening" | "net_peerCount" | "net_version" | "eth_blockNumber"
+            | "eth_chainId" | "eth_syncing" | "seth_getHealth" => Priority::Health,
+            "eth_getLogs"
+            | "eth_getFilterLogs"
+            | "eth_getProof"
+            | "seth_getBlockArtifacts"
+            | "seth_getBlockHeaders"
+            | "seth_exportState"
+            | "seth_getStorageStats"
+            | "seth_waitForTransactionReceipt" => Priority::Heavy,
+            "eth_sendRawTransaction"
+            | "eth_sendTransaction"
+            | "eth_sign"
+            | "eth_signTypedData_v4"
+            | "seth_archiveContract"
+            | "seth_importState"
+            | "seth_resurrectContract"
+            | "seth_setPermissions"
+            | "seth_submitOrdered" => Priority::Write,
+            method if method.starts_with("personal_") => Priority::Write,
+            _ => Priority::Read,
+        }
+    }
+
+    /// The percentage of `max_pending` that requests of this priority may fill
+    fn share(self) -> usize {
+        match self {
+            Priority::Heavy => 25,
+            Priority::Write => 50,
+            Priority::Read => 75,
+            Priority::Health => 100,
+        }
+    }
+}
+
+/// Counts a request as pending until it is dropped, even if its handler panics
+struct PendingGuard(Arc<AtomicUsize>);
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 #[derive(Clone)]
 pub struct RequestExecutor<T: MessageSender + Clone + Sync + Send + 'static> {
-    pool: CpuPool,
+    pool: ThreadPool,
     client: ValidatorClient<T>,
+    pending: Arc<AtomicUsize>,
+    max_pending: usize,
 }
 
 impl<T: MessageSender + Clone + Sync + Send + 'static> RequestExecutor<T> {
-    pub fn new(client: ValidatorClient<T>) -> Self {
+    /// Handlers block while they wait for validators, so requests are run on a
+    /// thread for each request that may be pending, rather than one per CPU, up to
+    /// `MAX_REQUEST_THREADS`. Requests pending beyond that wait for a thread. The
+    /// connections to validators route responses by correlation id, so as many
+    /// requests can wait on them at once.
+    pub fn new(client: ValidatorClient<T>, max_pending: usize) -> Self {
         RequestExecutor {
-            pool: CpuPool::new_num_cpus(),
+            pool: ThreadPool::builder()
+                .pool_size(max_pending.clamp(1, MAX_REQUEST_THREADS))
+                .name_prefix("request-")
+                .create()
+                .expect("Failed to start the request thread pool"),
             client,
+            pending: Arc::new(AtomicUsize::new(0)),
+            max_pending,
+        }
+    }
+
+    pub fn run(
+        &self,
+        params: Params,
+        meta: RequestMeta,
+        handler: RequestHandler<T>,
+        priority: Priority,
+    ) -> BoxFuture<RpcResult<Value>> {
+        let guard = PendingGuard(self.pending.clone());
+        let pending = self.pending.fetch_add(1, Ordering::SeqCst);
+        if pending >= self.max_pending * priority.share() / 100 {
+            warn!(
+                "Refusing {:?} request, {} requests pending",
+                priority, pending
+            );
+            return future::err(error::overloaded()).boxed();
+        }
+
+        let client = self.client.with_session(meta.session);
+        let handle = self.pool.spawn_with_handle(future::lazy(move |_| {
+            let _guard = guard;
+            // Whatever error the handler made of it, a request that failed because
+            // the validator is down says so
+            let result = handler(params, client.clone()).map_err(|err| {
+                if client.breaker.is_open() {
+                    error::validator_unavailable()
+                } else {
+                    err
+                }
+            })?;
+            match json_size(&result, client.max_response_size) {
+                Some(_) => Ok(result),
+                None => Err(error::response_too_large(client.max_response_size)),
+            }
+        }));
+        match handle {
+            Ok(handle) => handle.boxed(),
+            Err(spawn_error) => {
+                error!("Failed to run request: {}", spawn_error);
+                future::err(Error::internal_error()).boxed()
+            }
+        }
+    }
+}
+
+/// Serves batches of requests within limits on how many requests a batch has and
+/// how large its response is altogether. The requests of a batch are run at once,
+/// like requests that arrive together, and answered in order.
+#[derive(Clone)]
+pub struct BatchLimits {
+    max_requests: usize,
+    max_response_size: usize,
+}
+
+impl BatchLimits {
+    pub fn new(max_requests: usize, max_response_size: usize) -> Self {
+        BatchLimits {
+            max_requests,
+            max_response_size,
+        }
+    }
+
+    /// Returns the response to a batch without running it, if it is refused
+    fn refuse(&self, calls: usize) -> Option<Response> {
+        if calls == 0 {
+            // An empty batch is answered with an error rather than nothing
+            Some(Response::Single(Output::invalid_request(
+                Id::Null,
+                Some(Version::V2),
+            )))
+        } else if calls > self.max_requests {
+            warn!("Refusing batch of {} requests", calls);
+            Some(Response::from(
+                error::batch_too_large(self.max_requests),
+                Some(Version::V2),
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Replaces the outputs that don't fit in the response with errors, keeping
+    /// their ids so that the client can tell which requests to send again
+    fn fit(&self, outputs: Vec<Output>) -> Vec<Output> {
+        let mut budget = ResponseBudget::new(self.max_response_size);
+        outputs
+            .into_iter()
+            .map(|output| match budget.charge(&[&output]) {
+                Ok(()) => output,
+                Err(error) => Output::Failure(Failure {
+                    jsonrpc: output.version(),
+                    error,
+                    id: output.id().clone(),
+                }),
+            })
+            .collect()
+    }
+}
+
+impl Middleware<RequestMeta> for BatchLimits {
+    type Future = BoxFuture<Option<Response>>;
+    type CallFuture = BoxFuture<Option<Output>>;
+
+    fn on_request<F, X>(
+        &self,
+        request: Request,
+        meta: RequestMeta,
+        next: F,
+    ) -> Either<Self::Future, X>
+    where
+        F: Fn(Request, RequestMeta) -> X + Send + Sync,
+        X: Future<Output = Option<Response>> + Send + 'static,
+    {
+        let calls = match request {
+            Request::Batch(ref calls) => calls.len(),
+            Request::Single(_) => return Either::Right(next(request, meta)),
+        };
+        if let Some(response) = self.refuse(calls) {
+            return Either::Left(future::ready(Some(response)).boxed());
+        }
+        let limits = self.clone();
+        Either::Left(
+            next(request, meta)
+                .map(move |response| match response {
+                    Some(Response::Batch(outputs)) => Some(Response::Batch(limits.fit(outputs))),
+                    response => response,
+                })
+                .boxed(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use contracts::ContractRegistry;
+    use filters::FilterManager;
+    use futures::executor::block_on;
+    use mock_chain::MockChain;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    const CONCURRENT_REQUESTS: usize = 16;
+
+    static WAITING: AtomicUsize = AtomicUsize::new(0);
+
+    /// Waits for all of the requests to be waiting at once, as requests do for
+    /// validators, and answers whether they were
+    fn wait_for_the_others(_: Params, _: ValidatorClient<MockChain>) -> Result<Value, Error> {
+        WAITING.fetch_add(1, Ordering::SeqCst);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while WAITING.load(Ordering::SeqCst) < CONCURRENT_REQUESTS {
+            if Instant::now() > deadline {
+                return Ok(Value::Bool(false));
+            }
+            thread::sleep(Duration::from_millis(10));
         }
+        Ok(Value::Bool(true))
+    }
+
+    fn output(id: u64, result: &str) -> Output {
+        Output::from(Ok(Value::from(result)), Id::Num(id), Some(Version::V2))
     }
 
-    pub fn run(&self, params: Params, handler: RequestHandler<T>) -> BoxFuture<Value> {
-        let client = self.client.clone();
-        Box::new(self.pool.spawn_fn(move || handler(params, client)))
+    #[test]
+    fn refuses_empty_and_oversized_batches() {
+        let limits = BatchLimits::new(2, 1024);
+        assert!(limits.refuse(1).is_none());
+        assert!(limits.refuse(2).is_none());
+        match limits.refuse(0) {
+            Some(Response::Single(Output::Failure(failure))) => {
+                assert_eq!(failure.error.code, jsonrpc_core::ErrorCode::InvalidRequest);
+                assert_eq!(failure.id, Id::Null);
+            }
+            response => panic!("Unexpected response {:?}", response),
+        }
+        match limits.refuse(3) {
+            Some(Response::Single(Output::Failure(failure))) => {
+                assert_eq!(failure.error, error::batch_too_large(2));
+            }
+            response => panic!("Unexpected response {:?}", response),
+        }
+    }
+
+    #[test]
+    fn fails_the_outputs_past_the_response_size() {
+        let limits = BatchLimits::new(10, 120);
+        let outputs = limits.fit(vec![
+            output(1, &"a".repeat(40)),
+            output(2, &"b".repeat(40)),
+            output(3, "c"),
+        ]);
+        let ids: Vec<_> = outputs.iter().map(|output| output.id().clone()).collect();
+        assert_eq!(ids, vec![Id::Num(1), Id::Num(2), Id::Num(3)]);
+        match outputs.as_slice() {
+            [Output::Success(_), Output::Failure(failure), Output::Success(_)] => {
+                assert_eq!(failure.error, error::response_too_large(120));
+            }
+            outputs => panic!("Unexpected outputs {:?}", outputs),
+        }
+    }
+
+    #[test]
+    fn runs_every_pending_request_at_once() {
+        let client = ValidatorClient::new(
+            MockChain::new(),
+            Vec::new(),
+            FilterManager::new(1),
+            0,
+            String::from("1.0"),
+            ContractRegistry::new(),
+        );
+        let executor = RequestExecutor::new(client, CONCURRENT_REQUESTS);
+        let requests = (0..CONCURRENT_REQUESTS).map(|_| {
+            executor.run(
+                Params::None,
+                RequestMeta::default(),
+                wait_for_the_others,
+                Priority::Health,
+            )
+        });
+        for result in block_on(future::join_all(requests)) {
+            assert_eq!(result, Ok(Value::Bool(true)));
+        }
     }
 }
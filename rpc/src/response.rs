@@ -0,0 +1,103 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Bounds on the size of responses
+
+use calls::error;
+use jsonrpc_core::Error;
+use serde::Serialize;
+use serde_json;
+use std::io;
+
+/// Counts the bytes written to it, failing once there are more than `limit`
+struct SizeLimit {
+    written: usize,
+    limit: usize,
+}
+
+impl io::Write for SizeLimit {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written += buf.len();
+        if self.written > self.limit {
+            return Err(io::Error::other("Size limit reached"));
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns the size of the value serialized as JSON, or `None` if it is larger than
+/// `limit`. The serialization stops at the limit, and nothing is buffered.
+pub fn json_size<S: Serialize>(value: &S, limit: usize) -> Option<usize> {
+    let mut counter = SizeLimit { written: 0, limit };
+    serde_json::to_writer(&mut counter, value)
+        .ok()
+        .map(|_| counter.written)
+}
+
+/// Tracks the size of a response while a handler builds it, so that it can stop
+/// building one that ends up too large before it runs out of memory
+pub struct ResponseBudget {
+    remaining: usize,
+    limit: usize,
+}
+
+impl ResponseBudget {
+    pub fn new(limit: usize) -> Self {
+        ResponseBudget {
+            remaining: limit,
+            limit,
+        }
+    }
+
+    /// Takes the size of items added to the response out of the budget, failing
+    /// once it is used up
+    pub fn charge<S: Serialize>(&mut self, items: &[S]) -> Result<(), Error> {
+        for item in items {
+            // Each item is followed by a comma
+            let size = json_size(item, self.remaining)
+                .map(|size| size + 1)
+                .filter(|size| *size <= self.remaining)
+                .ok_or_else(|| error::response_too_large(self.limit))?;
+            self.remaining -= size;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpc_core::Value;
+
+    #[test]
+    fn stops_counting_at_the_limit() {
+        let value = Value::from(vec!["abc"; 3]);
+        assert_eq!(json_size(&value, 100), Some(19));
+        assert_eq!(json_size(&value, 19), Some(19));
+        assert_eq!(json_size(&value, 18), None);
+
+        let mut budget = ResponseBudget::new(12);
+        assert!(budget
+            .charge(&[Value::from("abc"), Value::from("abc")])
+            .is_ok());
+        assert!(budget.charge(&[Value::from("abc")]).is_err());
+    }
+}
@@ -0,0 +1,613 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Screening of the transactions seth-rpc sends, before they are forwarded to the
+//! validator, so that operators can enforce policies on what goes through their
+//! server. A screen is shown who sends each transaction, what it is sent to, the
+//! selector of the function it calls and the value it carries, and may deny it.
+//! Screens run in the server, or ask a policy service over HTTP. Screens can also
+//! have transactions simulated first, to check what they would change.
+
+use abi;
+use client::Error;
+use crypto_backend;
+use primitive_types::{H160, U256};
+#[cfg(feature = "screening")]
+use reqwest;
+use serde_json::{self, Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+#[cfg(feature = "screening")]
+use tokio;
+use transactions::SethTransaction;
+use transform;
+use vm::{CallError, Simulated};
+
+/// What a screen is shown of a transaction. Addresses are lowercase hex without a
+/// prefix.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScreenedTransaction {
+    /// The type of the transaction, as the seth transaction family names it
+    pub kind: &'static str,
+    pub from: String,
+    /// The account the transaction is sent to, or None for contract creations
+    pub to: Option<String>,
+    /// The first four bytes of the input of a call, which select the function it
+    /// calls. Private and confidential calls have none, since their input isn't
+    /// seen by seth-rpc in the clear.
+    pub selector: Option<String>,
+    pub value: u64,
+    /// What the transaction would do, if a screen needs it simulated and it runs
+    /// in the EVM
+    pub simulation: Option<Simulation>,
+}
+
+/// What a transaction did when it was run against the state of the latest block.
+/// Changes are signed amounts by the account they change, and are saturated at the
+/// bounds of an i128.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Simulation {
+    /// Why the transaction failed, or None if it succeeded
+    pub error: Option<String>,
+    pub gas_used: u64,
+    /// The changes to the balances of accounts
+    pub balance_changes: BTreeMap<String, i128>,
+    /// The changes to the balances of ERC-20 tokens by the token, as their
+    /// `Transfer` events tell them
+    pub token_changes: BTreeMap<String, BTreeMap<String, i128>>,
+}
+
+impl Simulation {
+    pub fn new(simulated: Simulated) -> Self {
+        let (error, gas_used) = match simulated.result {
+            Ok(executed) => (None, executed.gas_used),
+            Err(error) => (Some(call_error(error)), 0),
+        };
+        let balance_changes = simulated
+            .balances
+            .into_iter()
+            .map(|(address, (before, after))| (address_hex(&address), delta(before, after)))
+            .collect();
+
+        let transfer_topic = crypto_backend::keccak256(b"Transfer(address,address,uint256)");
+        let mut token_changes: BTreeMap<String, BTreeMap<String, i128>> = BTreeMap::new();
+        // ERC-721 transfers index their third argument, so have four topics
+        for log in simulated.logs.iter().filter(|log| {
+            log.topics.len() == 3
+                && log.topics[0].as_bytes() == transfer_topic
+                && log.data.len() == 32
+        }) {
+            let amount = U256::from_big_endian(&log.data);
+            let holders = token_changes.entry(address_hex(&log.address)).or_default();
+            let from = address_hex(&H160::from_slice(&log.topics[1].as_bytes()[12..]));
+            let to = address_hex(&H160::from_slice(&log.topics[2].as_bytes()[12..]));
+            let from_change = holders.entry(from).or_insert(0);
+            *from_change = from_change.saturating_add(delta(amount, U256::zero()));
+            let to_change = holders.entry(to).or_insert(0);
+            *to_change = to_change.saturating_add(delta(U256::zero(), amount));
+        }
+
+        Simulation {
+            error,
+            gas_used,
+            balance_changes,
+            token_changes,
+        }
+    }
+
+    /// A simulation of a transaction that can't be run, with why it can't
+    pub fn failed(error: String) -> Self {
+        Simulation {
+            error: Some(error),
+            ..Simulation::default()
+        }
+    }
+
+    #[cfg(feature = "screening")]
+    fn to_value(&self) -> Value {
+        let changes = |changes: &BTreeMap<String, i128>| {
+            Value::Object(
+                changes
+                    .iter()
+                    .map(|(address, change)| {
+                        (format!("0x{}", address), Value::from(change.to_string()))
+                    })
+                    .collect(),
+            )
+        };
+        let mut simulation = Map::new();
+        simulation.insert(String::from("success"), Value::Bool(self.error.is_none()));
+        simulation.insert(
+            String::from("error"),
+            self.error.clone().map_or(Value::Null, Value::String),
+        );
+        simulation.insert(
+            String::from("gasUsed"),
+            transform::num_to_hex(&self.gas_used),
+        );
+        simulation.insert(
+            String::from("balanceChanges"),
+            changes(&self.balance_changes),
+        );
+        simulation.insert(
+            String::from("tokenChanges"),
+            Value::Object(
+                self.token_changes
+                    .iter()
+                    .map(|(token, holders)| (format!("0x{}", token), changes(holders)))
+                    .collect(),
+            ),
+        );
+        Value::Object(simulation)
+    }
+}
+
+impl ScreenedTransaction {
+    /// Describes a transaction sent from `from`. Ethereum transactions are
+    /// described as sent by the account that signed them, not the one relaying them.
+    pub fn new(from: &str, txn: &SethTransaction) -> Self {
+        let selector = |data: &[u8]| data.get(..4).map(transform::bytes_to_hex_str);
+        let (kind, from, to, selector, value) = match *txn {
+            SethTransaction::CreateExternalAccount(ref txn) => (
+                "CREATE_EXTERNAL_ACCOUNT",
+                String::from(from),
+                Some(transform::bytes_to_hex_str(&txn.to)).filter(|to| !to.is_empty()),
+                None,
+                0,
+            ),
+            SethTransaction::CreateContractAccount(ref txn) => (
+                "CREATE_CONTRACT_ACCOUNT",
+                String::from(from),
+                None,
+                None,
+                txn.value,
+            ),
+            SethTransaction::MessageCall(ref txn) => (
+                "MESSAGE_CALL",
+                String::from(from),
+                Some(transform::bytes_to_hex_str(&txn.to)),
+                selector(&txn.data),
+                txn.value,
+            ),
+            SethTransaction::SetPermissions(ref txn) => (
+                "SET_PERMISSIONS",
+                String::from(from),
+                Some(transform::bytes_to_hex_str(&txn.to)),
+                None,
+                0,
+            ),
+            SethTransaction::ArchiveContract(ref txn) => (
+                "ARCHIVE_CONTRACT",
+                String::from(from),
+                Some(transform::bytes_to_hex_str(&txn.to)),
+                None,
+                0,
+            ),
+            SethTransaction::ResurrectContract(_) => {
+                ("RESURRECT_CONTRACT", String::from(from), None, None, 0)
+            }
+            SethTransaction::ImportAccount(ref txn) => (
+                "IMPORT_ACCOUNT",
+                String::from(from),
+                Some(transform::bytes_to_hex_str(
+                    &txn.get_account().get_account().address,
+                )),
+                None,
+                0,
+            ),
+            SethTransaction::Ethereum(ref raw) => (
+                "ETHEREUM_TRANSACTION",
+                String::from(raw.sender()),
+                raw.to.as_ref().map(|to| transform::bytes_to_hex_str(to)),
+                raw.to.as_ref().and_then(|_| selector(&raw.data)),
+                raw.value,
+            ),
+        };
+        ScreenedTransaction {
+            kind,
+            from,
+            to,
+            selector,
+            value,
+            simulation: None,
+        }
+    }
+
+    /// The transaction as a policy service is sent it, with 0x prefixed hex
+    #[cfg(feature = "screening")]
+    pub fn to_value(&self) -> Value {
+        let mut txn = Map::new();
+        txn.insert(String::from("type"), Value::from(self.kind));
+        txn.insert(String::from("from"), transform::hex_prefix(&self.from));
+        txn.insert(
+            String::from("to"),
+            self.to
+                .as_ref()
+                .map_or(Value::Null, |to| transform::hex_prefix(to)),
+        );
+        txn.insert(
+            String::from("selector"),
+            self.selector
+                .as_ref()
+                .map_or(Value::Null, |selector| transform::hex_prefix(selector)),
+        );
+        txn.insert(String::from("value"), transform::num_to_hex(&self.value));
+        if let Some(ref simulation) = self.simulation {
+            txn.insert(String::from("simulation"), simulation.to_value());
+        }
+        Value::Object(txn)
+    }
+}
+
+/// Decides whether transactions may be sent. Screens are called from the
+/// threads serving requests, so they must be safe to share between them.
+pub trait TransactionScreen: Send + Sync {
+    /// Returns the reason a transaction is denied, or None if it may be sent. An
+    /// error, such as a policy service that can't be reached, denies it as well.
+    fn screen(&self, txn: &ScreenedTransaction) -> Result<Option<String>, Error>;
+
+    /// Whether the screen is shown what transactions would do, by simulating them
+    /// before it is called
+    fn simulates(&self) -> bool {
+        false
+    }
+}
+
+/// Asks a policy service whether each transaction may be sent, by POSTing it as
+/// JSON to the service's URL. The service answers with an object whose `allow`
+/// is true to let the transaction through, with a `reason` when it denies it.
+#[cfg(feature = "screening")]
+pub struct HttpScreen {
+    url: String,
+    client: reqwest::blocking::Client,
+    simulate: bool,
+}
+
+#[cfg(feature = "screening")]
+impl HttpScreen {
+    pub fn new(url: &str) -> Self {
+        HttpScreen {
+            url: String::from(url),
+            client: reqwest::blocking::Client::new(),
+            simulate: false,
+        }
+    }
+
+    /// Sends the service the simulation of each transaction along with it
+    pub fn with_simulation(self) -> Self {
+        HttpScreen {
+            simulate: true,
+            ..self
+        }
+    }
+}
+
+#[cfg(feature = "screening")]
+impl TransactionScreen for HttpScreen {
+    fn screen(&self, txn: &ScreenedTransaction) -> Result<Option<String>, Error> {
+        // The blocking client panics when it's waited on from a worker of the
+        // HTTP server's runtime, so the worker's other tasks are handed off first
+        let response = tokio::task::block_in_place(|| {
+            self.client
+                .post(&self.url)
+                .json(&txn.to_value())
+                .send()
+                .and_then(|response| response.error_for_status())
+        })
+        .map_err(|err| {
+            Error::CommunicationError(format!("Screening service request failed: {}", err))
+        })?;
+
+        let body: Map<String, Value> =
+            tokio::task::block_in_place(|| response.json()).map_err(|err| {
+                Error::ParseError(format!("Malformed screening service response: {}", err))
+            })?;
+
+        match body.get("allow").and_then(Value::as_bool) {
+            Some(true) => Ok(None),
+            Some(false) => Ok(Some(
+                body.get("reason")
+                    .and_then(Value::as_str)
+                    .map_or_else(|| String::from("Denied by policy"), String::from),
+            )),
+            None => Err(Error::ParseError(String::from(
+                "Screening service response is missing `allow`",
+            ))),
+        }
+    }
+
+    fn simulates(&self) -> bool {
+        self.simulate
+    }
+}
+
+/// Denies transactions by fixed rules, read from a JSON file like:
+///
+/// ```json
+/// {
+///   "denySenders": ["0x..."],
+///   "denyTargets": ["0x..."],
+///   "denySelectors": ["0xa9059cbb"],
+///   "maxValue": 1000000,
+///   "requireSuccess": true,
+///   "maxTokenOutflow": {"0x...": "1000000000000000000000"}
+/// }
+/// ```
+///
+/// Every field is optional. The last two have transactions simulated, to deny
+/// those that would fail, and those that would take more than the given amount of
+/// a token from any account.
+#[derive(Debug, Default, PartialEq)]
+pub struct RuleScreen {
+    deny_senders: Vec<String>,
+    deny_targets: Vec<String>,
+    deny_selectors: Vec<String>,
+    max_value: Option<u64>,
+    require_success: bool,
+    max_token_outflow: BTreeMap<String, i128>,
+}
+
+impl RuleScreen {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let rules = fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read screening rules {}: {}", path, err))?;
+        RuleScreen::parse(&rules)
+            .map_err(|err| format!("Invalid screening rules {}: {}", path, err))
+    }
+
+    fn parse(rules: &str) -> Result<Self, String> {
+        let rules: Map<String, Value> =
+            serde_json::from_str(rules).map_err(|err| format!("{}", err))?;
+
+        let hex_list = |field: &str| -> Result<Vec<String>, String> {
+            match rules.get(field) {
+                None => Ok(Vec::new()),
+                Some(Value::Array(values)) => values
+                    .iter()
+                    .map(|value| {
+                        value
+                            .as_str()
+                            .map(|hex| hex.trim_start_matches("0x").to_lowercase())
+                            .ok_or_else(|| format!("`{}` must hold hex strings", field))
+                    })
+                    .collect(),
+                Some(_) => Err(format!("`{}` must be a list", field)),
+            }
+        };
+        let max_value = match rules.get("maxValue") {
+            None => None,
+            Some(value) => Some(
+                value
+                    .as_u64()
+                    .ok_or_else(|| String::from("`maxValue` must be a number"))?,
+            ),
+        };
+        let require_success = match rules.get("requireSuccess") {
+            None => false,
+            Some(value) => value
+                .as_bool()
+                .ok_or_else(|| String::from("`requireSuccess` must be a boolean"))?,
+        };
+        // Token amounts can be too large for JSON numbers, so can also be strings
+        let max_token_outflow = match rules.get("maxTokenOutflow") {
+            None => BTreeMap::new(),
+            Some(Value::Object(limits)) => limits
+                .iter()
+                .map(|(token, limit)| {
+                    let limit = match *limit {
+                        Value::Number(ref limit) => limit.as_u64().map(i128::from),
+                        Value::String(ref limit) => limit.parse::<i128>().ok(),
+                        _ => None,
+                    };
+                    match limit {
+                        Some(limit) if limit >= 0 => {
+                            Ok((token.trim_start_matches("0x").to_lowercase(), limit))
+                        }
+                        _ => Err(format!(
+                            "The `maxTokenOutflow` of {} must be a positive amount",
+                            token
+                        )),
+                    }
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err(String::from("`maxTokenOutflow` must be an object")),
+        };
+
+        Ok(RuleScreen {
+            deny_senders: hex_list("denySenders")?,
+            deny_targets: hex_list("denyTargets")?,
+            deny_selectors: hex_list("denySelectors")?,
+            max_value,
+            require_success,
+            max_token_outflow,
+        })
+    }
+}
+
+impl TransactionScreen for RuleScreen {
+    fn screen(&self, txn: &ScreenedTransaction) -> Result<Option<String>, Error> {
+        let denied = |list: &[String], value: &Option<String>| {
+            value.as_ref().is_some_and(|value| list.contains(value))
+        };
+
+        Ok(if self.deny_senders.contains(&txn.from) {
+            Some(format!("Sender 0x{} is denied", txn.from))
+        } else if denied(&self.deny_targets, &txn.to) {
+            Some(format!(
+                "Target 0x{} is denied",
+                txn.to.clone().unwrap_or_default()
+            ))
+        } else if denied(&self.deny_selectors, &txn.selector) {
+            Some(format!(
+                "Function 0x{} is denied",
+                txn.selector.clone().unwrap_or_default()
+            ))
+        } else if self.max_value.is_some_and(|max| txn.value > max) {
+            Some(format!("Value {} is more than is allowed", txn.value))
+        } else {
+            txn.simulation
+                .as_ref()
+                .and_then(|simulation| self.check_simulation(simulation))
+        })
+    }
+
+    fn simulates(&self) -> bool {
+        self.require_success || !self.max_token_outflow.is_empty()
+    }
+}
+
+impl RuleScreen {
+    fn check_simulation(&self, simulation: &Simulation) -> Option<String> {
+        if self.require_success {
+            if let Some(ref error) = simulation.error {
+                return Some(format!("Transaction would fail: {}", error));
+            }
+        }
+        for (token, max) in &self.max_token_outflow {
+            let outflow = simulation
+                .token_changes
+                .get(token)
+                .into_iter()
+                .flatten()
+                .find(|(_, change)| **change < -*max);
+            if let Some((holder, change)) = outflow {
+                return Some(format!(
+                    "0x{} would lose {} of token 0x{}, more than {}",
+                    holder,
+                    change.unsigned_abs(),
+                    token,
+                    max
+                ));
+            }
+        }
+        None
+    }
+}
+
+/// Describes why a simulated call failed the way `eth_call` does
+fn call_error(error: CallError) -> String {
+    match error {
+        CallError::Reverted(output) => match abi::revert_reason(&output) {
+            Some(reason) => format!("execution reverted: {}", reason),
+            None => String::from("execution reverted"),
+        },
+        CallError::OutOfGas => String::from("out of gas"),
+        CallError::Failed(reason) | CallError::State(reason) => reason,
+    }
+}
+
+fn address_hex(address: &H160) -> String {
+    transform::bytes_to_hex_str(address.as_bytes())
+}
+
+/// The change from one amount to another, saturated to an i128
+fn delta(before: U256, after: U256) -> i128 {
+    let saturate = |amount: U256| {
+        if amount > U256::from(i128::MAX as u128) {
+            i128::MAX
+        } else {
+            amount.as_u128() as i128
+        }
+    };
+    if after >= before {
+        saturate(after - before)
+    } else {
+        -saturate(before - after)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(from: &str, to: &str, selector: &str, value: u64) -> ScreenedTransaction {
+        ScreenedTransaction {
+            kind: "MESSAGE_CALL",
+            from: String::from(from),
+            to: Some(String::from(to)),
+            selector: Some(String::from(selector)),
+            value,
+            simulation: None,
+        }
+    }
+
+    #[test]
+    fn denies_by_rules() {
+        let screen = RuleScreen::parse(
+            r#"{"denySenders": ["0xAA"], "denyTargets": ["bb"],
+                "denySelectors": ["0xa9059cbb"], "maxValue": 10}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            None,
+            screen.screen(&call("cc", "dd", "00000000", 10)).unwrap()
+        );
+        assert!(screen
+            .screen(&call("aa", "dd", "00000000", 0))
+            .unwrap()
+            .is_some());
+        assert!(screen
+            .screen(&call("cc", "bb", "00000000", 0))
+            .unwrap()
+            .is_some());
+        assert!(screen
+            .screen(&call("cc", "dd", "a9059cbb", 0))
+            .unwrap()
+            .is_some());
+        assert!(screen
+            .screen(&call("cc", "dd", "00000000", 11))
+            .unwrap()
+            .is_some());
+
+        assert_eq!(Ok(RuleScreen::default()), RuleScreen::parse("{}"));
+        assert!(RuleScreen::parse(r#"{"denySenders": "aa"}"#).is_err());
+    }
+
+    #[test]
+    fn checks_simulations() {
+        let screen =
+            RuleScreen::parse(r#"{"requireSuccess": true, "maxTokenOutflow": {"0xEE": "100"}}"#)
+                .unwrap();
+        assert!(screen.simulates());
+        assert!(!RuleScreen::default().simulates());
+
+        let mut transfer = Simulation::default();
+        transfer
+            .token_changes
+            .entry(String::from("ee"))
+            .or_default()
+            .insert(String::from("cc"), -100);
+        let mut txn = call("cc", "ee", "a9059cbb", 0);
+        txn.simulation = Some(transfer.clone());
+        assert_eq!(None, screen.screen(&txn).unwrap());
+
+        transfer
+            .token_changes
+            .get_mut("ee")
+            .unwrap()
+            .insert(String::from("cc"), -101);
+        txn.simulation = Some(transfer);
+        assert!(screen.screen(&txn).unwrap().is_some());
+
+        txn.simulation = Some(Simulation::failed(String::from("execution reverted")));
+        assert!(screen.screen(&txn).unwrap().is_some());
+
+        assert_eq!(delta(U256::from(5), U256::from(2)), -3);
+        assert_eq!(delta(U256::zero(), U256::max_value()), i128::MAX);
+    }
+}
@@ -0,0 +1,96 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Running as a Windows service, for hosts without systemd. The service is started
+//! with the same arguments as `seth-rpc`, plus `--service`.
+
+use std::ffi::OsString;
+use std::process;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use windows_service::service::{
+    ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType,
+};
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+use windows_service::service_dispatcher;
+
+const SERVICE_NAME: &str = "seth-rpc";
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// Hands the process over to the service control manager, which calls back into
+/// the server on another thread
+pub fn run() {
+    if let Err(error) = service_dispatcher::start(SERVICE_NAME, ffi_service_main) {
+        eprintln!("Failed to start service: {}", error);
+        process::exit(1);
+    }
+}
+
+fn status(state: ServiceState) -> ServiceStatus {
+    ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: state,
+        controls_accepted: match state {
+            ServiceState::Running => ServiceControlAccept::STOP,
+            _ => ServiceControlAccept::empty(),
+        },
+        exit_code: ServiceExitCode::Win32(0),
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    }
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    let (stop_sender, stop_receiver) = mpsc::channel();
+    let handler = move |control| match control {
+        ServiceControl::Stop => {
+            stop_sender.send(()).ok();
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    };
+    let handle = match service_control_handler::register(SERVICE_NAME, handler) {
+        Ok(handle) => handle,
+        Err(error) => {
+            error!("Failed to register service control handler: {}", error);
+            process::exit(1);
+        }
+    };
+    handle
+        .set_service_status(status(ServiceState::StartPending))
+        .ok();
+
+    // Nothing needs to be cleaned up on the way out, since requests are only ever
+    // answered, so stopping exits without waiting for the servers
+    thread::spawn(move || {
+        stop_receiver.recv().ok();
+        handle
+            .set_service_status(status(ServiceState::Stopped))
+            .ok();
+        process::exit(0);
+    });
+
+    ::serve(&::with_config_or_exit(::app().get_matches()), || {
+        handle
+            .set_service_status(status(ServiceState::Running))
+            .ok();
+    });
+}
@@ -0,0 +1,100 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The HTTP header that requests of the same session share
+pub const SESSION_HEADER: &str = "X-Seth-Session";
+
+/// How long a session is remembered after it last sent a transaction
+const SESSION_TTL: Duration = Duration::from_secs(600);
+
+/// How many transactions of a session may be waiting to be seen in a block
+const MAX_PENDING_TRANSACTIONS: usize = 16;
+
+struct Session {
+    /// The highest block known to hold a transaction of the session
+    min_block: u64,
+
+    /// Transactions of the session that haven't been seen in a block yet
+    pending: Vec<String>,
+
+    last_used: Instant,
+}
+
+/// Remembers the transactions each session has sent, so that later reads of the
+/// latest block from the same session can be made to reflect them
+#[derive(Clone, Default)]
+pub struct SessionTracker {
+    sessions: Arc<Mutex<HashMap<String, Session>>>,
+}
+
+impl SessionTracker {
+    pub fn new() -> Self {
+        SessionTracker::default()
+    }
+
+    /// Records a transaction the session has sent, forgetting sessions that have
+    /// been idle for too long
+    pub fn add_transaction(&self, session: &str, txn_id: &str) {
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, session| session.last_used.elapsed() < SESSION_TTL);
+
+        let session = sessions
+            .entry(String::from(session))
+            .or_insert_with(|| Session {
+                min_block: 0,
+                pending: Vec::new(),
+                last_used: Instant::now(),
+            });
+        if session.pending.len() >= MAX_PENDING_TRANSACTIONS {
+            session.pending.remove(0);
+        }
+        session.pending.push(String::from(txn_id));
+        session.last_used = Instant::now();
+    }
+
+    /// Returns the transactions of the session that haven't been seen in a block yet
+    pub fn pending(&self, session: &str) -> Vec<String> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(session)
+            .map(|session| session.pending.clone())
+            .unwrap_or_default()
+    }
+
+    /// Records that a transaction of the session was seen in the given block
+    pub fn committed(&self, session: &str, txn_id: &str, block_num: u64) {
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(session) {
+            session.pending.retain(|pending| pending != txn_id);
+            session.min_block = session.min_block.max(block_num);
+        }
+    }
+
+    /// Returns the lowest block that reads of the session's latest block may return
+    pub fn min_block(&self, session: &str) -> u64 {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(session)
+            .map(|session| session.min_block)
+            .unwrap_or(0)
+    }
+}
@@ -0,0 +1,399 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Shadow execution of committed blocks with a candidate fork of the EVM. Each
+//! block is run again against the state before it, one transaction after another,
+//! and how each transaction went is compared with its receipt, so that a fork can
+//! be tried against the transactions a chain really sees before the transaction
+//! processors are upgraded to it. Only whether a transaction succeeded, what it
+//! returned and the topics and data of its logs are compared. Gas is left out,
+//! since the transaction processor counts it in its own EVM, as are the addresses
+//! of logs, since contracts are created at other addresses in this one.
+
+use client::{self, BlockKey, ValidatorClient};
+use evm::backend::Log;
+use primitive_types::H160;
+use protobuf;
+use sawtooth_sdk::messages::block::{Block, BlockHeader};
+use sawtooth_sdk::messaging::stream::MessageSender;
+use serde_json::{self, Map, Value};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use transactions::{SethLog, SethReceipt, Transaction};
+use transform;
+use types::{self, Quantity};
+use vm::{self, CallError, Fork, Outcome};
+
+/// How often the executor checks for a new block
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How many of the latest divergences are kept to be reported
+const MAX_DIVERGENCES: usize = 1000;
+
+#[derive(Default)]
+struct ShadowState {
+    /// The last block run again
+    last_block: Option<u64>,
+    blocks: u64,
+    transactions: u64,
+    divergences: u64,
+    /// How many blocks couldn't be run again, like when state couldn't be read
+    failures: u64,
+    recent: VecDeque<types::ShadowDivergence>,
+}
+
+/// Runs each block committed after it starts again with the rules of a candidate
+/// fork, counting the transactions that go another way than they were committed
+#[derive(Clone)]
+pub struct ShadowExecutor {
+    state: Arc<Mutex<ShadowState>>,
+    fork: Fork,
+}
+
+impl ShadowExecutor {
+    pub fn new(fork: Fork) -> Self {
+        ShadowExecutor {
+            state: Arc::new(Mutex::new(ShadowState::default())),
+            fork,
+        }
+    }
+
+    pub fn report(&self) -> types::ShadowReport {
+        let state = self.state.lock().unwrap();
+        types::ShadowReport {
+            fork: String::from(self.fork.name()),
+            last_block: state.last_block.map(Quantity),
+            blocks: Quantity(state.blocks),
+            transactions: Quantity(state.transactions),
+            divergences: Quantity(state.divergences),
+            failures: Quantity(state.failures),
+            recent: state.recent.iter().cloned().collect(),
+        }
+    }
+
+    /// Starts running each new block again in the background, from the latest
+    /// block on
+    pub fn start<S>(&self, client: ValidatorClient<S>)
+    where
+        S: MessageSender + Send + Sync + 'static,
+    {
+        let shadow = self.clone();
+        thread::spawn(move || loop {
+            match client.get_current_block_number() {
+                Ok(head) => {
+                    let next = match shadow.state.lock().unwrap().last_block {
+                        Some(last) => last + 1,
+                        None => head,
+                    };
+                    for block_num in next..=head {
+                        shadow.run_block(&client, block_num);
+                    }
+                }
+                Err(error) => warn!("Failed to get latest block to run again: {}", error),
+            }
+            thread::sleep(POLL_INTERVAL);
+        });
+    }
+
+    fn run_block<S: MessageSender>(&self, client: &ValidatorClient<S>, block_num: u64) {
+        let checked = client
+            .get_block(BlockKey::Number(block_num))
+            .map_err(|error| format!("Couldn't get block: {}", error))
+            .and_then(|block| self.check(client, &block));
+        let mut state = self.state.lock().unwrap();
+        state.last_block = Some(block_num);
+        state.blocks += 1;
+        match checked {
+            Ok((transactions, divergences)) => {
+                state.transactions += transactions;
+                for divergence in divergences {
+                    warn!(
+                        "Transaction {} of block {} has another {} with {}: {} instead of {}",
+                        divergence.transaction_hash,
+                        block_num,
+                        divergence.field,
+                        self.fork.name(),
+                        divergence.candidate,
+                        divergence.committed
+                    );
+                    state.divergences += 1;
+                    if state.recent.len() >= MAX_DIVERGENCES {
+                        state.recent.pop_front();
+                    }
+                    state.recent.push_back(divergence);
+                }
+            }
+            Err(error) => {
+                warn!("Failed to run block {} again: {}", block_num, error);
+                state.failures += 1;
+            }
+        }
+    }
+
+    /// Runs a block again, returning how many of its transactions ran in the EVM
+    /// and how those that went another way differed
+    fn check<S: MessageSender>(
+        &self,
+        client: &ValidatorClient<S>,
+        block: &Block,
+    ) -> Result<(u64, Vec<types::ShadowDivergence>), String> {
+        let header: BlockHeader = protobuf::parse_from_bytes(&block.header)
+            .map_err(|error| format!("Error parsing block header: {:?}", error))?;
+        if header.block_num == 0 {
+            return Ok((0, Vec::new()));
+        }
+        let parent = client
+            .get_block(BlockKey::Signature(header.previous_block_id.clone()))
+            .map_err(|error| format!("Couldn't get parent block: {}", error))?;
+        let state_root = client::state_root(&parent)
+            .map_err(|error| format!("Error parsing parent block header: {}", error))?;
+        let chain_id = client
+            .get_chain_id(BlockKey::Signature(parent.header_signature.clone()))
+            .map_err(|error| format!("Couldn't get chain id: {}", error))?;
+
+        let mut txn_ids = Vec::new();
+        let mut txns = Vec::new();
+        let all_txns = block
+            .get_batches()
+            .iter()
+            .flat_map(|batch| batch.get_transactions().iter());
+        for txn in all_txns {
+            // Transactions of other families don't change seth's state
+            let txn_id = txn.header_signature.clone();
+            let txn = match Transaction::try_from(txn.clone()) {
+                Ok(txn) => txn,
+                Err(_) => continue,
+            };
+            let from = transform::hex_str_to_bytes(&txn.from_addr())
+                .filter(|from| from.len() == 20)
+                .map(|from| H160::from_slice(&from));
+            if let Some(from) = from {
+                // Private transactions only count against the sender's nonce,
+                // since their input isn't on chain
+                let call = txn.evm_call().unwrap_or(None);
+                txn_ids.push(txn_id);
+                txns.push((from, call));
+            }
+        }
+        let creates: Vec<bool> = txns
+            .iter()
+            .map(|(_, call)| matches!(*call, Some(ref call) if call.to.is_none()))
+            .collect();
+
+        let outcomes = vm::replay(
+            client,
+            &state_root,
+            header.block_num,
+            chain_id,
+            txns,
+            self.fork,
+        )
+        .map_err(|error| format!("{:?}", error))?;
+        let receipts = client
+            .get_receipts(&txn_ids)
+            .map_err(|error| format!("Couldn't get receipts: {}", error))?;
+
+        let mut transactions = 0;
+        let mut divergences = Vec::new();
+        for ((txn_id, outcome), creates) in txn_ids.iter().zip(outcomes).zip(creates) {
+            let outcome = match outcome {
+                Some(outcome) => outcome,
+                None => continue,
+            };
+            let receipt = receipts
+                .get(txn_id)
+                .ok_or_else(|| format!("Missing receipt of {}", txn_id))?;
+            transactions += 1;
+            let differences = compare(receipt, &outcome, creates);
+            divergences.extend(
+                differences
+                    .into_iter()
+                    .map(|(field, committed, candidate)| types::ShadowDivergence {
+                        block_number: Quantity(header.block_num),
+                        block_hash: format!("0x{}", block.header_signature),
+                        transaction_hash: format!("0x{}", txn_id),
+                        field: String::from(field),
+                        committed,
+                        candidate,
+                    }),
+            );
+        }
+        Ok((transactions, divergences))
+    }
+}
+
+/// Compares how a transaction went when run again with its receipt, returning each
+/// field that differs as committed and as run again. What a creation returned
+/// isn't compared, since it is the code of a contract at another address.
+fn compare(
+    receipt: &SethReceipt,
+    outcome: &Outcome,
+    creates: bool,
+) -> Vec<(&'static str, String, String)> {
+    let (ref result, ref logs) = *outcome;
+    let committed_status = if receipt.reverted {
+        String::from("reverted")
+    } else {
+        String::from("succeeded")
+    };
+    let (status, output) = match *result {
+        Ok(ref executed) => (String::from("succeeded"), &executed.output[..]),
+        Err(CallError::Reverted(ref output)) => (String::from("reverted"), &output[..]),
+        Err(CallError::OutOfGas) => (String::from("ran out of gas"), &[][..]),
+        Err(CallError::Failed(ref reason)) | Err(CallError::State(ref reason)) => {
+            (format!("failed: {}", reason), &[][..])
+        }
+    };
+    if status != committed_status {
+        return vec![("status", committed_status, status)];
+    }
+
+    let mut differences = Vec::new();
+    let output = transform::bytes_to_hex_str(output);
+    if !creates && output != receipt.return_value {
+        differences.push((
+            "returnValue",
+            format!("0x{}", receipt.return_value),
+            format!("0x{}", output),
+        ));
+    }
+    let committed_logs = committed_logs(&receipt.logs);
+    let logs = candidate_logs(logs);
+    if logs != committed_logs {
+        differences.push(("logs", committed_logs, logs));
+    }
+    differences
+}
+
+fn committed_logs(logs: &[SethLog]) -> String {
+    logs_json(
+        logs.iter()
+            .map(|log| (log.topics.clone(), log.data.clone()))
+            .collect(),
+    )
+}
+
+fn candidate_logs(logs: &[Log]) -> String {
+    logs_json(
+        logs.iter()
+            .map(|log| {
+                let topics = log
+                    .topics
+                    .iter()
+                    .map(|topic| transform::bytes_to_hex_str(topic.as_bytes()))
+                    .collect();
+                (topics, transform::bytes_to_hex_str(&log.data))
+            })
+            .collect(),
+    )
+}
+
+/// Writes logs as the JSON text of their topics and data
+fn logs_json(logs: Vec<(Vec<String>, String)>) -> String {
+    let logs: Vec<Value> = logs
+        .into_iter()
+        .map(|(topics, data)| {
+            let mut log = Map::new();
+            log.insert(
+                String::from("topics"),
+                Value::Array(
+                    topics
+                        .iter()
+                        .map(|topic| Value::String(format!("0x{}", topic)))
+                        .collect(),
+                ),
+            );
+            log.insert(String::from("data"), Value::String(format!("0x{}", data)));
+            Value::Object(log)
+        })
+        .collect();
+    serde_json::to_string(&logs).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use primitive_types::H256;
+    use vm::Executed;
+
+    fn receipt(reverted: bool, return_value: &str, logs: Vec<SethLog>) -> SethReceipt {
+        SethReceipt {
+            transaction_id: String::from("txn"),
+            contract_address: String::new(),
+            gas_used: 21000,
+            return_value: String::from(return_value),
+            logs,
+            gas_profile: Vec::new(),
+            reverted,
+            state_changes: Vec::new(),
+        }
+    }
+
+    fn executed(output: Vec<u8>) -> Result<Executed, CallError> {
+        Ok(Executed {
+            output,
+            gas_used: 0,
+        })
+    }
+
+    #[test]
+    fn compares_outcomes_with_receipts() {
+        let topic = H256::repeat_byte(0xdd);
+        let log = Log {
+            address: H160::repeat_byte(0xaa),
+            topics: vec![topic],
+            data: vec![1],
+        };
+        let committed_log = SethLog {
+            address: String::from("bb"),
+            topics: vec![transform::bytes_to_hex_str(topic.as_bytes())],
+            data: String::from("01"),
+        };
+
+        // Logs are compared without their addresses, so these match
+        let committed = receipt(false, "2a", vec![committed_log.clone()]);
+        assert!(compare(
+            &committed,
+            &(executed(vec![0x2a]), vec![log.clone()]),
+            false
+        )
+        .is_empty());
+
+        let differences = compare(&committed, &(executed(vec![0x2b]), Vec::new()), false);
+        let fields: Vec<_> = differences.iter().map(|(field, _, _)| *field).collect();
+        assert_eq!(fields, vec!["returnValue", "logs"]);
+        assert_eq!(differences[0].1, "0x2a");
+        assert_eq!(differences[0].2, "0x2b");
+        assert_eq!(differences[1].2, "[]");
+
+        // Only the status is reported once it differs, and a creation's code isn't
+        // compared
+        let reverted = (Err(CallError::Reverted(Vec::new())), Vec::new());
+        assert_eq!(
+            compare(&committed, &reverted, false),
+            vec![(
+                "status",
+                String::from("succeeded"),
+                String::from("reverted")
+            )]
+        );
+        let created = (executed(vec![0x60]), vec![log]);
+        assert!(compare(&committed, &created, true).is_empty());
+    }
+}
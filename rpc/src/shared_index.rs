@@ -0,0 +1,224 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+use filters::{Filter, FilterEntry, FilterId, FilterStore};
+use postgres::{self, NoTls};
+use serde_json;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Keeps filters in a PostgreSQL database, so that several seth-rpc servers behind a
+/// load balancer can serve the same filters. Errors talking to the database fail the
+/// request, and a connection that was lost is reopened by the next request.
+pub struct PostgresFilterStore {
+    url: String,
+    // None once the connection was lost, until it is reopened
+    client: Mutex<Option<postgres::Client>>,
+}
+
+impl PostgresFilterStore {
+    /// Connects to the database and creates the filter table if it doesn't exist yet
+    pub fn connect(url: &str) -> Result<Self, postgres::Error> {
+        Ok(PostgresFilterStore {
+            url: String::from(url),
+            client: Mutex::new(Some(open(url)?)),
+        })
+    }
+
+    /// Runs `f` with the connection, reopening it first if it was lost
+    fn with_client<T, F>(&self, f: F) -> Result<T, String>
+    where
+        F: FnOnce(&mut postgres::Client) -> Result<T, postgres::Error>,
+    {
+        let mut kept = self.client.lock().unwrap();
+        let mut client = match kept.take() {
+            Some(client) if !client.is_closed() => client,
+            _ => {
+                let client = open(&self.url)
+                    .map_err(|error| format!("Couldn't reconnect to the database: {}", error))?;
+                info!("Reconnected to the filter database");
+                client
+            }
+        };
+        let result = f(&mut client);
+        if !client.is_closed() {
+            *kept = Some(client);
+        }
+        result.map_err(|error| error.to_string())
+    }
+}
+
+fn insert_filter(
+    client: &mut postgres::Client,
+    entry: &FilterEntry,
+    max_filters: usize,
+) -> Result<Option<FilterId>, postgres::Error> {
+    // The table is locked so that replicas installing filters at the same time
+    // can't go over the limit together
+    let mut txn = client.transaction()?;
+    txn.batch_execute("LOCK TABLE seth_filters IN EXCLUSIVE MODE")?;
+    let count: i64 = txn
+        .query_one("SELECT COUNT(*) FROM seth_filters", &[])?
+        .get(0);
+    if count as usize >= max_filters {
+        return Ok(None);
+    }
+
+    let filter = entry.filter.to_value().to_string();
+    let id: i64 = txn
+        .query_one(
+            "INSERT INTO seth_filters (filter, last_block_sent) VALUES ($1, $2) RETURNING id",
+            &[&filter, &(entry.last_block_sent as i64)],
+        )?
+        .get(0);
+    txn.commit()?;
+
+    Ok(Some(id as FilterId))
+}
+
+/// Opens a connection to the database, creating the filter table if it doesn't exist
+/// yet
+fn open(url: &str) -> Result<postgres::Client, postgres::Error> {
+    let mut client = postgres::Client::connect(url, NoTls)?;
+    client.batch_execute(
+        "CREATE TABLE IF NOT EXISTS seth_filters (
+            id BIGSERIAL PRIMARY KEY,
+            filter TEXT NOT NULL,
+            last_block_sent BIGINT NOT NULL
+        );
+        ALTER TABLE seth_filters
+            ADD COLUMN IF NOT EXISTS last_polled TIMESTAMPTZ NOT NULL DEFAULT now()",
+    )?;
+    Ok(client)
+}
+
+fn parse_entry(filter: &str, last_block_sent: i64) -> Option<FilterEntry> {
+    let filter = serde_json::from_str(filter)
+        .ok()
+        .and_then(|value| Filter::from_value(&value));
+    if filter.is_none() {
+        error!("Malformed filter in index database");
+    }
+    filter.map(|filter| FilterEntry {
+        filter,
+        last_block_sent: last_block_sent as u64,
+    })
+}
+
+impl FilterStore for PostgresFilterStore {
+    fn insert(&self, entry: FilterEntry, max_filters: usize) -> Result<Option<FilterId>, String> {
+        self.with_client(|client| insert_filter(client, &entry, max_filters))
+    }
+
+    fn remove(&self, filter_id: FilterId) -> Result<Option<FilterEntry>, String> {
+        let row = self.with_client(|client| {
+            client.query_opt(
+                "DELETE FROM seth_filters WHERE id = $1 RETURNING filter, last_block_sent",
+                &[&(filter_id as i64)],
+            )
+        })?;
+        Ok(row.and_then(|row| parse_entry(row.get(0), row.get(1))))
+    }
+
+    fn get(&self, filter_id: FilterId) -> Result<Option<FilterEntry>, String> {
+        let row = self.with_client(|client| {
+            client.query_opt(
+                "UPDATE seth_filters SET last_polled = now() WHERE id = $1
+                 RETURNING filter, last_block_sent",
+                &[&(filter_id as i64)],
+            )
+        })?;
+        Ok(row.and_then(|row| parse_entry(row.get(0), row.get(1))))
+    }
+
+    fn update_latest_block(&self, filter_id: FilterId, block_num: u64) -> Result<bool, String> {
+        let updated = self.with_client(|client| {
+            client.execute(
+                "UPDATE seth_filters SET last_block_sent = $2 WHERE id = $1",
+                &[&(filter_id as i64), &(block_num as i64)],
+            )
+        })?;
+        Ok(updated > 0)
+    }
+
+    fn expire(&self, idle_timeout: Duration) -> Result<usize, String> {
+        let expired = self.with_client(|client| {
+            client.execute(
+                "DELETE FROM seth_filters WHERE last_polled < now() - make_interval(secs => $1)",
+                &[&idle_timeout.as_secs_f64()],
+            )
+        })?;
+        Ok(expired as usize)
+    }
+
+    fn count(&self) -> Result<usize, String> {
+        let count: i64 = self.with_client(|client| {
+            client
+                .query_one("SELECT COUNT(*) FROM seth_filters", &[])
+                .map(|row| row.get(0))
+        })?;
+        Ok(count as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use filters::{LogFilter, TopicFilter};
+
+    #[test]
+    fn reads_back_the_filters_it_writes() {
+        let read_back = |filter: &Filter| parse_entry(&filter.to_value().to_string(), 7).unwrap();
+
+        for filter in &[Filter::Block, Filter::Transaction, Filter::Permissions] {
+            let entry = read_back(filter);
+            assert_eq!(entry.last_block_sent, 7);
+            assert_eq!(
+                entry.filter.to_value().to_string(),
+                filter.to_value().to_string()
+            );
+        }
+
+        let address = "5aaeb6053f3e94c9b9a09f33669435e7ef1beaed";
+        let log = read_back(&Filter::Log(LogFilter {
+            from_block: Some(1),
+            to_block: None,
+            block_hash: None,
+            addresses: vec![String::from(address)],
+            topics: vec![TopicFilter::All, TopicFilter::Exactly("ab".repeat(32))],
+        }));
+        match log.filter {
+            Filter::Log(filter) => {
+                assert_eq!(filter.from_block, Some(1));
+                assert_eq!(filter.to_block, None);
+                assert_eq!(filter.addresses, vec![String::from(address)]);
+                assert_eq!(
+                    filter.topics,
+                    vec![TopicFilter::All, TopicFilter::Exactly("ab".repeat(32))]
+                );
+            }
+            filter => panic!("Read back {:?}", filter),
+        }
+    }
+
+    #[test]
+    fn skips_malformed_filters() {
+        assert!(parse_entry("not json", 0).is_none());
+        assert!(parse_entry(r#"{"type": "pending"}"#, 0).is_none());
+        assert!(parse_entry(r#"{"type": "log", "fromBlock": "latest!"}"#, 0).is_none());
+    }
+}
@@ -0,0 +1,567 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Proofs of state entries in Sawtooth's Merkle-Radix tree, and of storage slots in
+//! the Merkle Patricia tries that Ethereum keeps storage in
+
+use crypto::digest::Digest;
+use crypto::sha2::Sha512;
+use crypto_backend;
+use rlp::RlpStream;
+
+/// The length of a state address in hex. Each of its 35 bytes is a level of the tree.
+const ADDRESS_LEN: usize = 70;
+
+/// The nodes from the root of a tree down towards a key. When the key isn't in the
+/// tree, they end at the last node on its path.
+pub struct ProofPath {
+    key: Vec<u8>,
+    nodes: Vec<Vec<u8>>,
+}
+
+impl ProofPath {
+    fn new(key: Vec<u8>) -> Self {
+        ProofPath {
+            key,
+            nodes: Vec::new(),
+        }
+    }
+
+    /// Nodes are added while walking back up from the leaves, so they are turned
+    /// around once the root is reached
+    fn into_nodes(self) -> Vec<Vec<u8>> {
+        let mut nodes = self.nodes;
+        nodes.reverse();
+        nodes
+    }
+}
+
+/// Rebuilds the Merkle-Radix tree of a state from all of its entries, and returns its
+/// root together with the encoded nodes on the path to each of the addresses.
+///
+/// Each node is the CBOR map `{"c": children, "v": value}`, where the children map the
+/// next byte of the address, as two hex digits, to the hash of the child node, and the
+/// value is the entry's data at the leaves and null elsewhere. The hash of a node is
+/// the first 32 bytes of the SHA-512 hash of its encoding, as hex.
+pub fn prove_state(
+    mut entries: Vec<(String, Vec<u8>)>,
+    addresses: &[String],
+) -> (String, Vec<Vec<Vec<u8>>>) {
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut paths: Vec<ProofPath> = addresses
+        .iter()
+        .map(|address| ProofPath::new(address.clone().into_bytes()))
+        .collect();
+
+    let root = if entries.is_empty() {
+        let node = encode_state_node(&[], None);
+        for path in &mut paths {
+            path.nodes.push(node.clone());
+        }
+        state_node_hash(&node)
+    } else {
+        hash_state_node(&entries, 0, &mut paths)
+    };
+    (root, paths.into_iter().map(ProofPath::into_nodes).collect())
+}
+
+/// Hashes the Merkle-Radix tree of a state from its entries as they are listed, in
+/// address order, holding only the nodes that are still being built rather than every
+/// entry. It keeps the nodes on the path to one address prefix, for proving the
+/// entries under it.
+pub struct StateHasher {
+    prefix: String,
+    // The entry last added, whose leaf isn't hashed until the next one is added
+    last: Option<(String, Vec<u8>)>,
+    // The children of the nodes on the path to the last entry, one per level
+    open: Vec<Vec<(String, String)>>,
+    path: Vec<Option<Vec<u8>>>,
+}
+
+impl StateHasher {
+    pub fn new(prefix: &str) -> Self {
+        StateHasher {
+            prefix: String::from(prefix),
+            last: None,
+            open: vec![Vec::new(); ADDRESS_LEN / 2],
+            path: vec![None; ADDRESS_LEN / 2 + 1],
+        }
+    }
+
+    /// Adds the next entry, failing if it doesn't come after the last one
+    pub fn add(&mut self, address: String, data: Vec<u8>) -> Result<(), String> {
+        if address.len() != ADDRESS_LEN {
+            return Err(format!("{} isn't a state address", address));
+        }
+        let shared = match self.last {
+            Some((ref last, _)) if *last >= address => {
+                return Err(format!("{} was listed after {}", address, last));
+            }
+            Some((ref last, _)) => last
+                .as_bytes()
+                .chunks(2)
+                .zip(address.as_bytes().chunks(2))
+                .take_while(|(a, b)| a == b)
+                .count(),
+            None => 0,
+        };
+        self.close(shared);
+        self.last = Some((address, data));
+        Ok(())
+    }
+
+    /// Returns the root of the tree and the nodes from it down towards the prefix,
+    /// ending at its node, or at the last node on the way when nothing is under it
+    pub fn finish(mut self) -> (String, Vec<Vec<u8>>) {
+        let root = if self.last.is_some() {
+            self.close(0);
+            encode_state_node(&self.children(0), None)
+        } else {
+            encode_state_node(&[], None)
+        };
+        self.keep("", &root);
+        let root = state_node_hash(&root);
+        (root, self.path.into_iter().flatten().collect())
+    }
+
+    /// Hashes the nodes of the last entry below the first `shared` levels, which it
+    /// has in common with the next one
+    fn close(&mut self, shared: usize) {
+        let (address, data) = match self.last.take() {
+            Some(last) => last,
+            None => return,
+        };
+        let leaf = encode_state_node(&[], Some(&data));
+        self.keep(&address, &leaf);
+        let mut hash = state_node_hash(&leaf);
+        for level in (shared + 1..ADDRESS_LEN / 2).rev() {
+            self.open[level].push((address[level * 2..level * 2 + 2].to_string(), hash));
+            let node = encode_state_node(&self.children(level), None);
+            self.open[level].clear();
+            self.keep(&address[..level * 2], &node);
+            hash = state_node_hash(&node);
+        }
+        self.open[shared].push((address[shared * 2..shared * 2 + 2].to_string(), hash));
+    }
+
+    fn children(&self, level: usize) -> Vec<(&str, String)> {
+        self.open[level]
+            .iter()
+            .map(|(token, hash)| (token.as_str(), hash.clone()))
+            .collect()
+    }
+
+    /// Keeps the node at an address prefix if it's on the path to the prefix proven
+    fn keep(&mut self, node_prefix: &str, node: &[u8]) {
+        if self.prefix.starts_with(node_prefix) {
+            self.path[node_prefix.len() / 2] = Some(node.to_vec());
+        }
+    }
+}
+
+/// Returns the hash of a node of the Merkle-Radix tree
+pub fn state_node_hash(node: &[u8]) -> String {
+    let mut sha = Sha512::new();
+    sha.input(node);
+    String::from(&sha.result_str()[..64])
+}
+
+/// Hashes the node of the entries, which all share the address prefix of the given
+/// length, and adds it to the paths of the addresses under it
+fn hash_state_node(
+    entries: &[(String, Vec<u8>)],
+    prefix_len: usize,
+    paths: &mut [ProofPath],
+) -> String {
+    let prefix = &entries[0].0.as_bytes()[..prefix_len];
+    let node = if prefix_len >= ADDRESS_LEN {
+        encode_state_node(&[], Some(&entries[0].1))
+    } else {
+        let mut children = Vec::new();
+        let mut start = 0;
+        while start < entries.len() {
+            let token = &entries[start].0[prefix_len..prefix_len + 2];
+            let end = entries[start..]
+                .iter()
+                .position(|(address, _)| &address[prefix_len..prefix_len + 2] != token)
+                .map_or(entries.len(), |len| start + len);
+            let hash = hash_state_node(&entries[start..end], prefix_len + 2, paths);
+            children.push((token, hash));
+            start = end;
+        }
+        encode_state_node(&children, None)
+    };
+
+    for path in paths.iter_mut().filter(|path| path.key.starts_with(prefix)) {
+        path.nodes.push(node.clone());
+    }
+    state_node_hash(&node)
+}
+
+/// Encodes a node the way Sawtooth does, as canonical CBOR with the keys in order
+fn encode_state_node(children: &[(&str, String)], value: Option<&[u8]>) -> Vec<u8> {
+    let mut node = Vec::new();
+    cbor_header(&mut node, 5, 2);
+    cbor_text(&mut node, "c");
+    cbor_header(&mut node, 5, children.len());
+    for (token, hash) in children {
+        cbor_text(&mut node, token);
+        cbor_text(&mut node, hash);
+    }
+    cbor_text(&mut node, "v");
+    match value {
+        Some(value) => {
+            cbor_header(&mut node, 2, value.len());
+            node.extend_from_slice(value);
+        }
+        None => node.push(0xf6),
+    }
+    node
+}
+
+fn cbor_text(out: &mut Vec<u8>, text: &str) {
+    cbor_header(out, 3, text.len());
+    out.extend_from_slice(text.as_bytes());
+}
+
+/// Writes the major type and length of a CBOR item in as few bytes as it fits in
+fn cbor_header(out: &mut Vec<u8>, major: u8, len: usize) {
+    let major = major << 5;
+    let len = len as u64;
+    if len < 24 {
+        out.push(major | len as u8);
+    } else if len <= 0xff {
+        out.extend_from_slice(&[major | 24, len as u8]);
+    } else if len <= 0xffff {
+        out.push(major | 25);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else if len <= 0xffff_ffff {
+        out.push(major | 26);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&len.to_be_bytes());
+    }
+}
+
+/// Builds the secure trie Ethereum keeps an account's storage in, with each slot under
+/// the Keccak hash of its key padded to 32 bytes and holding the RLP of its value
+/// without leading zeros, and returns its root together with the proofs of the keys.
+/// Slots that are zero aren't kept in the trie.
+pub fn prove_storage(
+    slots: &[(Vec<u8>, Vec<u8>)],
+    keys: &[Vec<u8>],
+) -> ([u8; 32], Vec<Vec<Vec<u8>>>) {
+    let items = slots
+        .iter()
+        .filter_map(|(key, value)| {
+            let start = value.iter().position(|&byte| byte != 0)?;
+            Some((
+                crypto_backend::keccak256(key).to_vec(),
+                rlp::encode(&&value[start..]).to_vec(),
+            ))
+        })
+        .collect();
+    let keys: Vec<Vec<u8>> = keys
+        .iter()
+        .map(|key| crypto_backend::keccak256(key).to_vec())
+        .collect();
+    prove_trie(items, &keys)
+}
+
+/// Builds a Merkle Patricia trie of the items, and returns its root together with
+/// the nodes on the path to each of the keys. As in Ethereum's proofs, nodes that are
+/// small enough to be embedded in their parent aren't given separately.
+pub fn prove_trie(
+    items: Vec<(Vec<u8>, Vec<u8>)>,
+    keys: &[Vec<u8>],
+) -> ([u8; 32], Vec<Vec<Vec<u8>>>) {
+    let mut items: Vec<(Vec<u8>, Vec<u8>)> = items
+        .into_iter()
+        .map(|(key, value)| (nibbles(&key), value))
+        .collect();
+    items.sort();
+    let mut paths: Vec<ProofPath> = keys
+        .iter()
+        .map(|key| ProofPath::new(nibbles(key)))
+        .collect();
+
+    let root = if items.is_empty() {
+        rlp::NULL_RLP.to_vec()
+    } else {
+        encode_trie_node(&items, 0, &mut paths)
+    };
+    for path in &mut paths {
+        // The root is given even when it's small
+        if path.nodes.last() != Some(&root) {
+            path.nodes.push(root.clone());
+        }
+    }
+    (
+        crypto_backend::keccak256(&root),
+        paths.into_iter().map(ProofPath::into_nodes).collect(),
+    )
+}
+
+/// Encodes the node of the items, whose keys all share the nibbles before the given
+/// depth, and adds it to the paths of the keys under it
+fn encode_trie_node(
+    items: &[(Vec<u8>, Vec<u8>)],
+    depth: usize,
+    paths: &mut [ProofPath],
+) -> Vec<u8> {
+    let first = &items[0].0;
+    let last = &items[items.len() - 1].0;
+    let shared = first[depth..]
+        .iter()
+        .zip(&last[depth..])
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut stream;
+    if items.len() == 1 {
+        stream = RlpStream::new_list(2);
+        stream.append(&hex_prefix(&first[depth..], true));
+        stream.append(&items[0].1);
+    } else if shared > 0 {
+        let branch = encode_trie_node(items, depth + shared, paths);
+        stream = RlpStream::new_list(2);
+        stream.append(&hex_prefix(&first[depth..depth + shared], false));
+        append_trie_ref(&mut stream, &branch);
+    } else {
+        stream = RlpStream::new_list(17);
+        // Only the shortest key can end at the branch, and it sorts first
+        let (value, rest) = if first.len() == depth {
+            (Some(&items[0].1), &items[1..])
+        } else {
+            (None, items)
+        };
+        for nibble in 0..16 {
+            let start = rest.iter().position(|(key, _)| key[depth] == nibble);
+            match start {
+                Some(start) => {
+                    let len = rest[start..]
+                        .iter()
+                        .take_while(|(key, _)| key[depth] == nibble)
+                        .count();
+                    let child = encode_trie_node(&rest[start..start + len], depth + 1, paths);
+                    append_trie_ref(&mut stream, &child);
+                }
+                None => {
+                    stream.append_empty_data();
+                }
+            }
+        }
+        match value {
+            Some(value) => stream.append(value),
+            None => stream.append_empty_data(),
+        };
+    }
+
+    let node = stream.out().to_vec();
+    if node.len() >= 32 {
+        let prefix = &first[..depth];
+        for path in paths.iter_mut().filter(|path| path.key.starts_with(prefix)) {
+            path.nodes.push(node.clone());
+        }
+    }
+    node
+}
+
+/// Adds a child to a node, by its hash unless it's small enough to embed
+fn append_trie_ref(stream: &mut RlpStream, node: &[u8]) {
+    if node.len() < 32 {
+        stream.append_raw(node, 1);
+    } else {
+        stream.append(&crypto_backend::keccak256(node).as_ref());
+    }
+}
+
+/// Packs the nibbles of a path into bytes, with a first nibble that tells whether the
+/// path ends in a leaf and whether it has an odd length
+fn hex_prefix(path: &[u8], leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 2 } else { 0 } + (path.len() % 2) as u8;
+    let mut packed = Vec::with_capacity(path.len() / 2 + 1);
+    let rest = if path.len() % 2 == 1 {
+        packed.push(flag << 4 | path[0]);
+        &path[1..]
+    } else {
+        packed.push(flag << 4);
+        path
+    };
+    packed.extend(rest.chunks(2).map(|pair| pair[0] << 4 | pair[1]));
+    packed
+}
+
+fn nibbles(key: &[u8]) -> Vec<u8> {
+    key.iter()
+        .flat_map(|byte| vec![byte >> 4, byte & 0x0f])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transform;
+
+    fn trie_root(items: &[(&str, &str)]) -> String {
+        let items = items
+            .iter()
+            .map(|(key, value)| (key.as_bytes().to_vec(), value.as_bytes().to_vec()))
+            .collect();
+        transform::bytes_to_hex_str(&prove_trie(items, &[]).0)
+    }
+
+    fn pad(bytes: &[u8]) -> Vec<u8> {
+        let mut word = vec![0; 32 - bytes.len()];
+        word.extend_from_slice(bytes);
+        word
+    }
+
+    #[test]
+    fn computes_trie_roots() {
+        assert_eq!(
+            trie_root(&[]),
+            "56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421"
+        );
+        assert_eq!(
+            trie_root(&[
+                ("do", "verb"),
+                ("dog", "puppy"),
+                ("doge", "coin"),
+                ("horse", "stallion"),
+            ]),
+            "5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84"
+        );
+        assert_eq!(
+            trie_root(&[
+                ("doe", "reindeer"),
+                ("dog", "puppy"),
+                ("dogglesworth", "cat")
+            ]),
+            "8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3"
+        );
+    }
+
+    #[test]
+    fn proves_storage_slots() {
+        let slots: Vec<(Vec<u8>, Vec<u8>)> = (1..40u8)
+            .map(|n| (pad(&[n]), pad(&[n, 0])))
+            .chain(vec![(pad(&[99]), vec![0; 32])])
+            .collect();
+        let (root, proofs) = prove_storage(&slots, &[pad(&[7]), pad(&[99])]);
+
+        // The first node of each proof is the root, and each node after it is
+        // referred to by the hash of the one before
+        for proof in &proofs {
+            assert_eq!(crypto_backend::keccak256(&proof[0]), root);
+            for pair in proof.windows(2) {
+                let hash = crypto_backend::keccak256(&pair[1]);
+                assert!(pair[0].windows(32).any(|window| window == hash));
+            }
+        }
+
+        // The slot that is set ends in a leaf holding its value, while the zero one
+        // isn't in the trie
+        let leaf = rlp::Rlp::new(proofs[0].last().unwrap());
+        assert_eq!(leaf.item_count().unwrap(), 2);
+        assert_eq!(leaf.at(1).unwrap().data().unwrap(), &[0x82, 7, 0][..]);
+        let (without_zero, _) = prove_storage(&slots[..slots.len() - 1], &[]);
+        assert_eq!(root, without_zero);
+    }
+
+    #[test]
+    fn encodes_state_nodes() {
+        assert_eq!(
+            encode_state_node(&[], Some(&[1, 2])),
+            vec![0xa2, 0x61, b'c', 0xa0, 0x61, b'v', 0x42, 1, 2]
+        );
+
+        let hash = "00".repeat(32);
+        let children: Vec<(&str, String)> = vec![("0a", hash.clone()), ("ff", hash.clone())];
+        let node = encode_state_node(&children, None);
+        assert_eq!(&node[..4], &[0xa2, 0x61, b'c', 0xa2]);
+        assert_eq!(&node[4..7], &[0x62, b'0', b'a']);
+        assert_eq!(&node[7..9], &[0x78, 64]);
+        assert_eq!(&node[node.len() - 3..], &[0x61, b'v', 0xf6]);
+    }
+
+    #[test]
+    fn proves_state_entries() {
+        let address = |last: &str| format!("{}{}", "a6".repeat(34), last);
+        let entries = vec![
+            (address("01"), vec![1]),
+            (address("02"), vec![2]),
+            ("b7".repeat(35), vec![3]),
+        ];
+        let (root, proofs) = prove_state(entries, &[address("02"), address("03")]);
+
+        // The entry's path runs through every level down to a leaf holding its data
+        assert_eq!(proofs[0].len(), 36);
+        assert_eq!(state_node_hash(&proofs[0][0]), root);
+        assert_eq!(proofs[0][35], encode_state_node(&[], Some(&[2])));
+        for pair in proofs[0].windows(2) {
+            let hash = state_node_hash(&pair[1]);
+            assert!(pair[0].windows(64).any(|window| window == hash.as_bytes()));
+        }
+
+        // An address that isn't set ends at its parent, which has no child for it
+        assert_eq!(proofs[1].len(), 35);
+        assert_eq!(proofs[1][..35], proofs[0][..35]);
+    }
+
+    #[test]
+    fn hashes_listed_state() {
+        let address = |prefix: &str, last: &str| {
+            format!("{}{}{}", prefix, "00".repeat(34 - prefix.len() / 2), last)
+        };
+        let entries = vec![
+            (address("a68b06", "01"), vec![1]),
+            (address("a68b06", "02"), vec![2]),
+            (address("a68b0611", "01"), vec![3]),
+            (address("b7", "01"), vec![4]),
+        ];
+        let namespace = &entries[0].0[..46];
+
+        let hasher = |entries: &[(String, Vec<u8>)], prefix: &str| {
+            let mut hasher = StateHasher::new(prefix);
+            for (address, data) in entries {
+                hasher.add(address.clone(), data.clone()).unwrap();
+            }
+            hasher.finish()
+        };
+
+        // The root and the nodes down to the prefix are the same as when the whole
+        // tree is rebuilt
+        let (root, path) = hasher(&entries, namespace);
+        let (expected_root, proofs) = prove_state(entries.clone(), &[entries[0].0.clone()]);
+        assert_eq!(root, expected_root);
+        assert_eq!(path.len(), 24);
+        assert_eq!(path[..], proofs[0][..24]);
+
+        // A prefix nothing is under ends at the last node on its way
+        let (_, path) = hasher(&entries, &"a68b07".repeat(7));
+        assert_eq!(path.len(), 3);
+        assert_eq!(path[..], proofs[0][..3]);
+
+        assert_eq!(hasher(&[], namespace).0, prove_state(Vec::new(), &[]).0);
+
+        let mut hasher = StateHasher::new(namespace);
+        hasher.add(entries[1].0.clone(), Vec::new()).unwrap();
+        assert!(hasher.add(entries[0].0.clone(), Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,264 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! `eth_subscribe` over WebSocket. The validator pushes a `sawtooth/block-commit`
+//! event for each block it commits, and each block is turned into notifications for
+//! the subscriptions it matches, the same way filters see it when they are polled.
+
+use calls::block::get_block_obj;
+use calls::error;
+use calls::logs::{get_logs_from_block_and_filter, seth_transaction_hashes};
+use client::{BlockKey, ValidatorClient};
+use events::watch_blocks;
+use filters::LogFilter;
+use jsonrpc_core::{Error, Params, Value};
+use jsonrpc_pubsub::{Session, Sink, Subscriber, SubscriptionId};
+use requests::RequestMeta;
+use sawtooth_sdk::messages::block::Block;
+use sawtooth_sdk::messaging::stream::*;
+use serde_json::Map;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+pub enum SubscriptionKind {
+    NewHeads,
+    Logs(LogFilter),
+    /// Seth only learns of transactions once they are committed, so these are the
+    /// transactions of each new block, like `eth_newPendingTransactionFilter` returns
+    NewPendingTransactions,
+}
+
+impl SubscriptionKind {
+    fn from_params(params: Params) -> Result<Self, Error> {
+        let usage = "Takes [\"newHeads\"|\"newPendingTransactions\"] or [\"logs\", filter: OBJECT]";
+
+        let params: Vec<Value> = params.parse().map_err(|_| Error::invalid_params(usage))?;
+        match (params.first().and_then(Value::as_str), params.get(1)) {
+            (Some("newHeads"), None) => Ok(SubscriptionKind::NewHeads),
+            (Some("newPendingTransactions"), None) => Ok(SubscriptionKind::NewPendingTransactions),
+            (Some("logs"), Some(Value::Object(filter))) => {
+                Ok(SubscriptionKind::Logs(LogFilter::from_map(filter)?))
+            }
+            _ => Err(Error::invalid_params(usage)),
+        }
+    }
+}
+
+struct Subscription {
+    kind: SubscriptionKind,
+    sink: Sink,
+    // The connection that subscribed, which doesn't keep it open
+    connection: Weak<Session>,
+}
+
+/// The subscriptions of every connected WebSocket client
+#[derive(Clone)]
+pub struct SubscriptionManager {
+    id_ctr: Arc<AtomicUsize>,
+    subscriptions: Arc<Mutex<HashMap<SubscriptionId, Subscription>>>,
+    max_per_connection: usize,
+}
+
+impl SubscriptionManager {
+    /// Creates a manager that lets each connection have at most `max_per_connection`
+    /// subscriptions at once
+    pub fn new(max_per_connection: usize) -> Self {
+        SubscriptionManager {
+            id_ctr: Arc::default(),
+            subscriptions: Arc::default(),
+            max_per_connection,
+        }
+    }
+
+    pub fn subscribe(&self, params: Params, meta: RequestMeta, subscriber: Subscriber) {
+        info!("eth_subscribe");
+
+        let kind = match SubscriptionKind::from_params(params) {
+            Ok(kind) => kind,
+            Err(error) => {
+                subscriber.reject(error).ok();
+                return;
+            }
+        };
+
+        let connection = meta.pubsub.as_ref().map(Arc::downgrade).unwrap_or_default();
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let subscribed = subscriptions
+            .values()
+            .filter(|subscription| subscription.connection.ptr_eq(&connection))
+            .count();
+        if meta.pubsub.is_some() && subscribed >= self.max_per_connection {
+            subscriber
+                .reject(error::too_many_subscriptions(self.max_per_connection))
+                .ok();
+            return;
+        }
+
+        let id = SubscriptionId::String(format!(
+            "0x{:x}",
+            self.id_ctr.fetch_add(1, Ordering::SeqCst)
+        ));
+        if let Ok(sink) = subscriber.assign_id(id.clone()) {
+            subscriptions.insert(
+                id,
+                Subscription {
+                    kind,
+                    sink,
+                    connection,
+                },
+            );
+        }
+    }
+
+    /// Removes a subscription, which happens for every subscription of a client when
+    /// it disconnects
+    pub fn unsubscribe(&self, id: SubscriptionId) -> Result<Value, Error> {
+        info!("eth_unsubscribe");
+        Ok(Value::Bool(
+            self.subscriptions.lock().unwrap().remove(&id).is_some(),
+        ))
+    }
+
+    /// Sends the notifications for a newly committed block
+    fn notify_block<S: MessageSender + Clone>(&self, client: &ValidatorClient<S>, block: &Block) {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        if subscriptions.is_empty() {
+            return;
+        }
+
+        let mut head = None;
+        let mut transactions = None;
+        let mut closed = Vec::new();
+        for (id, subscription) in subscriptions.iter() {
+            let results = match subscription.kind {
+                SubscriptionKind::NewHeads => {
+                    let head = head.get_or_insert_with(|| {
+                        get_block_obj(
+                            BlockKey::Signature(block.header_signature.clone()),
+                            false,
+                            client.clone(),
+                        )
+                    });
+                    match *head {
+                        Ok(ref head) => vec![head.clone()],
+                        Err(_) => continue,
+                    }
+                }
+                SubscriptionKind::Logs(ref filter) => {
+                    match get_logs_from_block_and_filter(client, block, filter) {
+                        Ok(logs) => logs,
+                        Err(_) => continue,
+                    }
+                }
+                SubscriptionKind::NewPendingTransactions => transactions
+                    .get_or_insert_with(|| seth_transaction_hashes(block))
+                    .clone(),
+            };
+
+            for result in results {
+                if !notify(&subscription.sink, id, result) {
+                    closed.push(id.clone());
+                    break;
+                }
+            }
+        }
+
+        for id in closed {
+            subscriptions.remove(&id);
+        }
+    }
+
+    /// Starts notifying subscriptions of committed blocks in the background
+    pub fn start<S>(&self, client: ValidatorClient<S>, urls: Vec<String>)
+    where
+        S: MessageSender + Clone + Send + Sync + 'static,
+    {
+        let manager = self.clone();
+        watch_blocks(urls, move |block_id| {
+            match client.get_block(BlockKey::Signature(block_id.clone())) {
+                Ok(block) => manager.notify_block(&client, &block),
+                Err(error) => error!("Failed to get committed block {}: {}", block_id, error),
+            }
+        });
+    }
+}
+
+/// Sends one notification, returning false if the client has gone away, which is also
+/// the case once a client that reads too slowly has been disconnected for falling
+/// too far behind
+fn notify(sink: &Sink, id: &SubscriptionId, result: Value) -> bool {
+    let id = match *id {
+        SubscriptionId::String(ref id) => Value::String(id.clone()),
+        SubscriptionId::Number(id) => Value::from(id),
+    };
+    let mut params = Map::new();
+    params.insert(String::from("subscription"), id);
+    params.insert(String::from("result"), result);
+    sink.notify(Params::Map(params)).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use serde_json;
+
+    fn params(json: &str) -> Params {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn parse_subscription_kinds() {
+        match SubscriptionKind::from_params(params(r#"["newHeads"]"#)) {
+            Ok(SubscriptionKind::NewHeads) => (),
+            _ => panic!("Expected newHeads"),
+        }
+        match SubscriptionKind::from_params(params(r#"["logs", {"address": "0x12"}]"#)) {
+            Ok(SubscriptionKind::Logs(filter)) => assert!(filter.contains_address("12")),
+            _ => panic!("Expected logs"),
+        }
+        assert!(SubscriptionKind::from_params(params(r#"["logs"]"#)).is_err());
+        assert!(SubscriptionKind::from_params(params(r#"["newHeads", {}]"#)).is_err());
+        assert!(SubscriptionKind::from_params(params(r#"["syncing"]"#)).is_err());
+    }
+
+    #[test]
+    fn limit_subscriptions_per_connection() {
+        let manager = SubscriptionManager::new(1);
+        let connection = || RequestMeta {
+            pubsub: Some(Arc::new(Session::new(
+                futures::channel::mpsc::unbounded().0,
+            ))),
+            ..RequestMeta::default()
+        };
+        let first = connection();
+        let second = connection();
+        let subscribe = |meta: &RequestMeta| {
+            let (subscriber, id, _) = Subscriber::new_test("eth_subscription");
+            manager.subscribe(params(r#"["newHeads"]"#), meta.clone(), subscriber);
+            block_on(id).unwrap()
+        };
+
+        assert!(subscribe(&first).is_ok());
+        assert_eq!(
+            subscribe(&first).unwrap_err().message,
+            "Subscription limit reached, unsubscribe unused subscriptions"
+        );
+        assert!(subscribe(&second).is_ok());
+    }
+}
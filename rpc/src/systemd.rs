@@ -0,0 +1,80 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Notifications to systemd, as `sd_notify` sends them, for services run with
+//! `Type=notify` and `WatchdogSec=`
+
+use std::env;
+use std::os::unix::net::UnixDatagram;
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+/// Sends a state like `READY=1` to the service manager, if it asked for
+/// notifications by setting `NOTIFY_SOCKET`
+pub fn notify(state: &str) {
+    let path = match env::var("NOTIFY_SOCKET") {
+        Ok(path) => path,
+        Err(_) => return,
+    };
+    if let Err(error) = send(&path, state) {
+        warn!("Failed to notify systemd at {}: {}", path, error);
+    }
+}
+
+fn send(path: &str, state: &str) -> std::io::Result<()> {
+    let socket = UnixDatagram::unbound()?;
+    match path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            let address = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(state.as_bytes(), &address)?;
+        }
+        _ => {
+            socket.send_to(state.as_bytes(), path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns how often the service manager expects a watchdog notification, if it
+/// enabled the watchdog for this process
+fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok() != Some(process::id()) {
+            return None;
+        }
+    }
+    env::var("WATCHDOG_USEC")
+        .ok()
+        .and_then(|usec| usec.parse::<u64>().ok())
+        .filter(|usec| *usec > 0)
+        .map(Duration::from_micros)
+}
+
+/// Tells the service manager the server is ready, and keeps notifying its
+/// watchdog, at half the interval it expects, for as long as the process runs
+pub fn ready() {
+    notify("READY=1");
+    if let Some(interval) = watchdog_interval() {
+        thread::spawn(move || loop {
+            notify("WATCHDOG=1");
+            thread::sleep(interval / 2);
+        });
+    }
+}
@@ -0,0 +1,160 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Counts of the calls clients make to methods seth-rpc doesn't serve, by method and
+//! client, so that the methods real applications need can be added first.
+
+use futures::future::{Either, Future, FutureExt};
+use jsonrpc_core::{BoxFuture, Call, ErrorCode, Middleware, Output, Request, Response};
+use requests::RequestMeta;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use types::{self, Quantity};
+
+/// How many pairs of a method and a client are counted at most, so that clients
+/// calling made up methods can't use up memory
+const MAX_TRACKED: usize = 10_000;
+
+/// How many characters of a method or client are kept
+const MAX_NAME_LENGTH: usize = 128;
+
+/// What clients that don't send a `User-Agent` are counted as
+const UNKNOWN_CLIENT: &str = "unknown";
+
+/// Counts the calls that fail because their method isn't served
+#[derive(Clone, Default)]
+pub struct UnsupportedMethods {
+    calls: Arc<Mutex<HashMap<(String, String), u64>>>,
+}
+
+impl UnsupportedMethods {
+    pub fn new() -> Self {
+        UnsupportedMethods::default()
+    }
+
+    fn record(&self, method: &str, client: Option<&str>) {
+        let client = client.unwrap_or(UNKNOWN_CLIENT);
+        info!("{} called unsupported method {}", client, method);
+        let mut calls = self.calls.lock().unwrap();
+        let key = (truncate(method), truncate(client));
+        if calls.len() < MAX_TRACKED || calls.contains_key(&key) {
+            *calls.entry(key).or_insert(0) += 1;
+        }
+    }
+
+    /// Returns the counts, most called first
+    pub fn report(&self) -> Vec<types::UnsupportedMethodCalls> {
+        let mut report: Vec<_> = self
+            .calls
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|((method, client), &calls)| types::UnsupportedMethodCalls {
+                method: method.clone(),
+                client: client.clone(),
+                calls: Quantity(calls),
+            })
+            .collect();
+        report.sort_by(|a, b| {
+            b.calls
+                .0
+                .cmp(&a.calls.0)
+                .then_with(|| a.method.cmp(&b.method))
+                .then_with(|| a.client.cmp(&b.client))
+        });
+        report
+    }
+}
+
+impl Middleware<RequestMeta> for UnsupportedMethods {
+    type Future = BoxFuture<Option<Response>>;
+    type CallFuture = BoxFuture<Option<Output>>;
+
+    fn on_request<F, X>(
+        &self,
+        request: Request,
+        meta: RequestMeta,
+        next: F,
+    ) -> Either<Self::Future, X>
+    where
+        F: Fn(Request, RequestMeta) -> X + Send + Sync,
+        X: Future<Output = Option<Response>> + Send + 'static,
+    {
+        Either::Right(next(request, meta))
+    }
+
+    fn on_call<F, X>(&self, call: Call, meta: RequestMeta, next: F) -> Either<Self::CallFuture, X>
+    where
+        F: Fn(Call, RequestMeta) -> X + Send + Sync,
+        X: Future<Output = Option<Output>> + Send + 'static,
+    {
+        let method = match call {
+            Call::MethodCall(ref call) => call.method.clone(),
+            _ => return Either::Right(next(call, meta)),
+        };
+        let client = meta.client.clone();
+        let unsupported = self.clone();
+        Either::Left(
+            next(call, meta)
+                .map(move |output| {
+                    if let Some(Output::Failure(ref failure)) = output {
+                        if failure.error.code == ErrorCode::MethodNotFound {
+                            unsupported.record(&method, client.as_deref());
+                        }
+                    }
+                    output
+                })
+                .boxed(),
+        )
+    }
+}
+
+fn truncate(name: &str) -> String {
+    name.chars().take(MAX_NAME_LENGTH).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_most_called_first() {
+        let unsupported = UnsupportedMethods::new();
+        unsupported.record("eth_getWork", Some("miner/1.0"));
+        unsupported.record("eth_getUncleCountByBlockNumber", None);
+        unsupported.record("eth_getUncleCountByBlockNumber", None);
+        unsupported.record("eth_getWork", Some("wallet/2.1"));
+
+        let report: Vec<_> = unsupported
+            .report()
+            .into_iter()
+            .map(|calls| (calls.method, calls.client, calls.calls.0))
+            .collect();
+        assert_eq!(
+            report,
+            vec![
+                (
+                    String::from("eth_getUncleCountByBlockNumber"),
+                    String::from(UNKNOWN_CLIENT),
+                    2
+                ),
+                (String::from("eth_getWork"), String::from("miner/1.0"), 1),
+                (String::from("eth_getWork"), String::from("wallet/2.1"), 1),
+            ]
+        );
+    }
+}
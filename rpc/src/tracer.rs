@@ -0,0 +1,428 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Records each step a call takes in the EVM as the struct logs geth returns from
+//! `debug_traceTransaction`: the opcode and where it is, the gas left before it and
+//! what it cost, the call depth, and the stack, memory and storage it saw.
+
+use evm::gasometer::tracing as gas_tracing;
+use evm::tracing as executor_tracing;
+use evm_runtime::tracing as runtime_tracing;
+use memory::MemoryBudget;
+use primitive_types::{H160, H256, U256};
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
+use std::rc::Rc;
+use transform;
+
+/// What a trace leaves out, and how many steps it records at most
+#[derive(Clone, Copy)]
+pub struct TraceOptions {
+    pub disable_stack: bool,
+    pub disable_memory: bool,
+    pub disable_storage: bool,
+    pub limit: usize,
+}
+
+/// A step of a call
+#[derive(Debug, PartialEq)]
+pub struct StructLog {
+    pub pc: usize,
+    pub op: u8,
+    /// The gas left before the step, and what the step cost
+    pub gas: u64,
+    pub gas_cost: u64,
+    /// 1 for the steps of the call itself, and one more for each call it made
+    pub depth: usize,
+    /// The stack from the bottom up
+    pub stack: Option<Vec<H256>>,
+    pub memory: Option<Vec<u8>>,
+    /// The slots of the running contract read or written so far, for steps that
+    /// read or write one
+    pub storage: Option<BTreeMap<H256, H256>>,
+}
+
+impl StructLog {
+    pub fn to_value(&self) -> Value {
+        let mut log = Map::new();
+        log.insert(String::from("pc"), Value::from(self.pc));
+        log.insert(String::from("op"), Value::from(opcode_name(self.op)));
+        log.insert(String::from("gas"), Value::from(self.gas));
+        log.insert(String::from("gasCost"), Value::from(self.gas_cost));
+        log.insert(String::from("depth"), Value::from(self.depth));
+        if let Some(ref stack) = self.stack {
+            let stack = stack
+                .iter()
+                .map(|word| transform::num_to_hex(&U256::from_big_endian(word.as_bytes())))
+                .collect();
+            log.insert(String::from("stack"), Value::Array(stack));
+        }
+        if let Some(ref memory) = self.memory {
+            let memory = memory
+                .chunks(32)
+                .map(|word| Value::String(transform::bytes_to_hex_str(word)))
+                .collect();
+            log.insert(String::from("memory"), Value::Array(memory));
+        }
+        if let Some(ref storage) = self.storage {
+            let storage = storage
+                .iter()
+                .map(|(key, value)| {
+                    (
+                        transform::bytes_to_hex_str(key.as_bytes()),
+                        Value::String(transform::bytes_to_hex_str(value.as_bytes())),
+                    )
+                })
+                .collect();
+            log.insert(String::from("storage"), Value::Object(storage));
+        }
+        Value::Object(log)
+    }
+}
+
+/// Runs `f`, recording the steps of the EVM calls it makes and charging them to
+/// `memory`. Steps stop being recorded once `memory` is exceeded.
+pub fn trace<R, F: FnOnce() -> R>(
+    options: TraceOptions,
+    memory: &MemoryBudget,
+    f: F,
+) -> (R, Vec<StructLog>) {
+    let tracer = Rc::new(RefCell::new(Tracer {
+        options,
+        memory: memory.clone(),
+        logs: Vec::new(),
+        depth: 0,
+        storage: HashMap::new(),
+        costing: false,
+    }));
+    let mut executor = Listener(tracer.clone());
+    let mut gas = Listener(tracer.clone());
+    let mut runtime = Listener(tracer.clone());
+    let result = executor_tracing::using(&mut executor, || {
+        gas_tracing::using(&mut gas, || runtime_tracing::using(&mut runtime, f))
+    });
+    let logs = mem::take(&mut tracer.borrow_mut().logs);
+    (result, logs)
+}
+
+struct Tracer {
+    options: TraceOptions,
+    memory: MemoryBudget,
+    logs: Vec<StructLog>,
+    /// How many calls are running
+    depth: usize,
+    /// The slots each contract read or wrote so far
+    storage: HashMap<H160, BTreeMap<H256, H256>>,
+    /// Whether the gas recorded goes to the last step, which is only until it makes
+    /// a call or the next step starts
+    costing: bool,
+}
+
+impl Tracer {
+    fn step(&mut self, pc: usize, op: u8, stack: &[H256], memory: &[u8]) {
+        self.costing = false;
+        if self.logs.len() >= self.options.limit || self.memory.is_exceeded() {
+            return;
+        }
+        let mut size = mem::size_of::<StructLog>();
+        if !self.options.disable_stack {
+            size += mem::size_of_val(stack);
+        }
+        if !self.options.disable_memory {
+            size += memory.len();
+        }
+        // Going over is noticed by the next step, and by the handler
+        let _ = self.memory.charge(size);
+        self.logs.push(StructLog {
+            pc,
+            op,
+            gas: 0,
+            gas_cost: 0,
+            depth: self.depth.max(1),
+            stack: if self.options.disable_stack {
+                None
+            } else {
+                Some(stack.to_vec())
+            },
+            memory: if self.options.disable_memory {
+                None
+            } else {
+                Some(memory.to_vec())
+            },
+            storage: None,
+        });
+        self.costing = true;
+    }
+
+    /// Records the cost of the last step, given the gas left before it
+    fn cost(&mut self, gas: u64, cost: u64) {
+        if !self.costing {
+            return;
+        }
+        if let Some(log) = self.logs.last_mut() {
+            if log.gas_cost == 0 {
+                log.gas = gas;
+            }
+            log.gas_cost += cost;
+        }
+    }
+
+    fn storage(&mut self, address: H160, index: H256, value: H256) {
+        if self.options.disable_storage {
+            return;
+        }
+        let storage = self.storage.entry(address).or_default();
+        storage.insert(index, value);
+        if let Some(log) = self.logs.last_mut() {
+            let _ = self
+                .memory
+                .charge(storage.len() * 2 * mem::size_of::<H256>());
+            log.storage = Some(storage.clone());
+        }
+    }
+}
+
+/// Passes the events of one part of the EVM to the tracer they share
+struct Listener(Rc<RefCell<Tracer>>);
+
+impl runtime_tracing::EventListener for Listener {
+    fn event(&mut self, event: runtime_tracing::Event) {
+        let mut tracer = self.0.borrow_mut();
+        match event {
+            runtime_tracing::Event::Step {
+                opcode,
+                position: Ok(pc),
+                stack,
+                memory,
+                ..
+            } => tracer.step(*pc, opcode.as_u8(), stack.data(), memory.data()),
+            runtime_tracing::Event::SLoad {
+                address,
+                index,
+                value,
+            }
+            | runtime_tracing::Event::SStore {
+                address,
+                index,
+                value,
+            } => tracer.storage(address, index, value),
+            _ => (),
+        }
+    }
+}
+
+impl gas_tracing::EventListener for Listener {
+    fn event(&mut self, event: gas_tracing::Event) {
+        let mut tracer = self.0.borrow_mut();
+        match event {
+            gas_tracing::Event::RecordCost {
+                cost,
+                snapshot: Some(snapshot),
+            } => tracer.cost(snapshot.gas(), cost),
+            gas_tracing::Event::RecordDynamicCost {
+                gas_cost,
+                memory_gas,
+                snapshot: Some(snapshot),
+                ..
+            } => tracer.cost(
+                snapshot.gas(),
+                gas_cost + memory_gas.saturating_sub(snapshot.memory_gas),
+            ),
+            _ => (),
+        }
+    }
+}
+
+impl executor_tracing::EventListener for Listener {
+    fn event(&mut self, event: executor_tracing::Event) {
+        let mut tracer = self.0.borrow_mut();
+        match event {
+            executor_tracing::Event::Call { .. } | executor_tracing::Event::Create { .. } => {
+                tracer.depth += 1;
+                tracer.costing = false;
+            }
+            executor_tracing::Event::Exit { .. } => {
+                tracer.depth = tracer.depth.saturating_sub(1);
+                tracer.costing = false;
+            }
+            _ => (),
+        }
+    }
+}
+
+/// The name geth gives an opcode
+pub fn opcode_name(op: u8) -> String {
+    let name = match op {
+        0x00 => "STOP",
+        0x01 => "ADD",
+        0x02 => "MUL",
+        0x03 => "SUB",
+        0x04 => "DIV",
+        0x05 => "SDIV",
+        0x06 => "MOD",
+        0x07 => "SMOD",
+        0x08 => "ADDMOD",
+        0x09 => "MULMOD",
+        0x0a => "EXP",
+        0x0b => "SIGNEXTEND",
+        0x10 => "LT",
+        0x11 => "GT",
+        0x12 => "SLT",
+        0x13 => "SGT",
+        0x14 => "EQ",
+        0x15 => "ISZERO",
+        0x16 => "AND",
+        0x17 => "OR",
+        0x18 => "XOR",
+        0x19 => "NOT",
+        0x1a => "BYTE",
+        0x1b => "SHL",
+        0x1c => "SHR",
+        0x1d => "SAR",
+        0x20 => "SHA3",
+        0x30 => "ADDRESS",
+        0x31 => "BALANCE",
+        0x32 => "ORIGIN",
+        0x33 => "CALLER",
+        0x34 => "CALLVALUE",
+        0x35 => "CALLDATALOAD",
+        0x36 => "CALLDATASIZE",
+        0x37 => "CALLDATACOPY",
+        0x38 => "CODESIZE",
+        0x39 => "CODECOPY",
+        0x3a => "GASPRICE",
+        0x3b => "EXTCODESIZE",
+        0x3c => "EXTCODECOPY",
+        0x3d => "RETURNDATASIZE",
+        0x3e => "RETURNDATACOPY",
+        0x3f => "EXTCODEHASH",
+        0x40 => "BLOCKHASH",
+        0x41 => "COINBASE",
+        0x42 => "TIMESTAMP",
+        0x43 => "NUMBER",
+        0x44 => "DIFFICULTY",
+        0x45 => "GASLIMIT",
+        0x46 => "CHAINID",
+        0x47 => "SELFBALANCE",
+        0x48 => "BASEFEE",
+        0x50 => "POP",
+        0x51 => "MLOAD",
+        0x52 => "MSTORE",
+        0x53 => "MSTORE8",
+        0x54 => "SLOAD",
+        0x55 => "SSTORE",
+        0x56 => "JUMP",
+        0x57 => "JUMPI",
+        0x58 => "PC",
+        0x59 => "MSIZE",
+        0x5a => "GAS",
+        0x5b => "JUMPDEST",
+        0x5f => "PUSH0",
+        0x60..=0x7f => return format!("PUSH{}", op - 0x5f),
+        0x80..=0x8f => return format!("DUP{}", op - 0x7f),
+        0x90..=0x9f => return format!("SWAP{}", op - 0x8f),
+        0xa0..=0xa4 => return format!("LOG{}", op - 0xa0),
+        0xf0 => "CREATE",
+        0xf1 => "CALL",
+        0xf2 => "CALLCODE",
+        0xf3 => "RETURN",
+        0xf4 => "DELEGATECALL",
+        0xf5 => "CREATE2",
+        0xfa => "STATICCALL",
+        0xfd => "REVERT",
+        0xfe => "INVALID",
+        0xff => "SELFDESTRUCT",
+        _ => return format!("opcode {:#04x} not defined", op),
+    };
+    String::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vm::Call;
+    use witness::{self, Witness, WitnessAccount};
+
+    #[test]
+    fn records_each_step_of_a_call() {
+        // PUSH1 0x2a PUSH1 0x07 SSTORE PUSH1 0x07 SLOAD STOP
+        let contract = H160::from_low_u64_be(0xc0de);
+        let mut witness = Witness::default();
+        witness.accounts.insert(H160::zero(), None);
+        witness.accounts.insert(
+            contract,
+            Some(WitnessAccount {
+                balance: U256::zero(),
+                nonce: U256::one(),
+                code: vec![0x60, 0x2a, 0x60, 0x07, 0x55, 0x60, 0x07, 0x54, 0x00],
+            }),
+        );
+        witness
+            .storage
+            .insert((contract, H256::from_low_u64_be(7)), H256::zero());
+        let call = || Call {
+            from: H160::zero(),
+            to: Some(contract),
+            value: U256::zero(),
+            data: Vec::new(),
+            gas: 100_000,
+            access_list: Vec::new(),
+        };
+        let options = TraceOptions {
+            disable_stack: false,
+            disable_memory: true,
+            disable_storage: false,
+            limit: 100,
+        };
+
+        let (executed, logs) = trace(options, &MemoryBudget::default(), || {
+            witness::replay(&witness, call())
+        });
+        assert!(executed.is_ok());
+        let ops: Vec<String> = logs.iter().map(|log| opcode_name(log.op)).collect();
+        assert_eq!(
+            vec!["PUSH1", "PUSH1", "SSTORE", "PUSH1", "SLOAD", "STOP"],
+            ops
+        );
+        assert_eq!(
+            vec![0, 2, 4, 5, 7, 8],
+            logs.iter().map(|log| log.pc).collect::<Vec<_>>()
+        );
+        assert!(logs.iter().all(|log| log.depth == 1));
+        assert_eq!(3, logs[0].gas_cost);
+        assert_eq!(logs[0].gas - 3, logs[1].gas);
+        assert_eq!(
+            Some(vec![H256::from_low_u64_be(0x2a), H256::from_low_u64_be(7)]),
+            logs[2].stack
+        );
+        let stored = logs[2].storage.as_ref().unwrap();
+        assert_eq!(
+            Some(&H256::from_low_u64_be(0x2a)),
+            stored.get(&H256::from_low_u64_be(7))
+        );
+        assert_eq!(None, logs[0].memory);
+
+        // Steps stop being recorded once the memory of the request is used up
+        let memory = MemoryBudget::new(2 * mem::size_of::<StructLog>());
+        let (executed, logs) = trace(options, &memory, || witness::replay(&witness, call()));
+        assert!(executed.is_ok());
+        assert!(logs.len() < 6);
+        assert!(memory.check().is_err());
+    }
+}
@@ -19,25 +19,42 @@
 use protobuf;
 
 use messages::seth::{
+    ArchiveContractTxn as ArchiveContractTxnPb, ArchivedContract,
     CreateContractAccountTxn as CreateContractAccountTxnPb,
-    CreateExternalAccountTxn as CreateExternalAccountTxnPb, MessageCallTxn as MessageCallTxnPb,
+    CreateExternalAccountTxn as CreateExternalAccountTxnPb, EthereumTxn as EthereumTxnPb,
+    EvmPermissions, GasProfileEntry, ImportAccountTxn as ImportAccountTxnPb,
+    MessageCallTxn as MessageCallTxnPb, ResurrectContractTxn as ResurrectContractTxnPb,
     SetPermissionsTxn as SetPermissionsTxnPb, SethTransaction as SethTrasactionPb,
     SethTransactionReceipt, SethTransaction_TransactionType,
 };
 
 use sawtooth_sdk::messages::events::{Event, Event_Attribute};
 use sawtooth_sdk::messages::transaction::{Transaction as TransactionPb, TransactionHeader};
-use sawtooth_sdk::messages::transaction_receipt::TransactionReceipt;
+use sawtooth_sdk::messages::transaction_receipt::{StateChange, TransactionReceipt};
 
-use accounts::public_key_to_address;
+use accounts::{contract_address, create2_address, public_key_to_address};
 use client::{BlockKey, Error};
+use primitive_types::{H160, U256};
+use raw_transaction::RawTransaction;
 use transform;
+use vm;
+
+/// An entry of an EIP-2930 access list. If `storage_keys` is None every storage slot of
+/// the account may be accessed, otherwise only those listed, as 32 byte hex strings.
+pub struct AccessListEntry {
+    pub address: String,
+    pub storage_keys: Option<Vec<String>>,
+}
 
 pub enum SethTransaction {
     CreateExternalAccount(CreateExternalAccountTxnPb),
     CreateContractAccount(CreateContractAccountTxnPb),
     MessageCall(MessageCallTxnPb),
     SetPermissions(SetPermissionsTxnPb),
+    ArchiveContract(ArchiveContractTxnPb),
+    ResurrectContract(ResurrectContractTxnPb),
+    Ethereum(Box<RawTransaction>),
+    ImportAccount(ImportAccountTxnPb),
 }
 
 impl SethTransaction {
@@ -55,6 +72,20 @@ impl SethTransaction {
             SethTransaction_TransactionType::SET_PERMISSIONS => {
                 Some(SethTransaction::SetPermissions(txn.take_set_permissions()))
             }
+            SethTransaction_TransactionType::ARCHIVE_CONTRACT => Some(
+                SethTransaction::ArchiveContract(txn.take_archive_contract()),
+            ),
+            SethTransaction_TransactionType::RESURRECT_CONTRACT => Some(
+                SethTransaction::ResurrectContract(txn.take_resurrect_contract()),
+            ),
+            SethTransaction_TransactionType::ETHEREUM_TRANSACTION => {
+                RawTransaction::decode(txn.get_ethereum_transaction().get_raw())
+                    .ok()
+                    .map(|raw| SethTransaction::Ethereum(Box::new(raw)))
+            }
+            SethTransaction_TransactionType::IMPORT_ACCOUNT => {
+                Some(SethTransaction::ImportAccount(txn.take_import_account()))
+            }
             _ => None,
         }
     }
@@ -78,9 +109,149 @@ impl SethTransaction {
                 txn.set_transaction_type(SethTransaction_TransactionType::SET_PERMISSIONS);
                 txn.set_set_permissions(inner.clone());
             }
+            SethTransaction::ArchiveContract(ref inner) => {
+                txn.set_transaction_type(SethTransaction_TransactionType::ARCHIVE_CONTRACT);
+                txn.set_archive_contract(inner.clone());
+            }
+            SethTransaction::ResurrectContract(ref inner) => {
+                txn.set_transaction_type(SethTransaction_TransactionType::RESURRECT_CONTRACT);
+                txn.set_resurrect_contract(inner.clone());
+            }
+            SethTransaction::Ethereum(ref inner) => {
+                let mut ethereum_txn = EthereumTxnPb::new();
+                ethereum_txn.set_raw(inner.raw.clone());
+                txn.set_transaction_type(SethTransaction_TransactionType::ETHEREUM_TRANSACTION);
+                txn.set_ethereum_transaction(ethereum_txn);
+            }
+            SethTransaction::ImportAccount(ref inner) => {
+                txn.set_transaction_type(SethTransaction_TransactionType::IMPORT_ACCOUNT);
+                txn.set_import_account(inner.clone());
+            }
         }
         txn
     }
+
+    /// The addresses of every account this transaction, sent from `from`, can read or
+    /// write. Which accounts a contract touches can't be known without running it, so
+    /// for contract creations and calls they must be listed in an access list; without
+    /// one None is returned.
+    pub fn accounts(
+        &self,
+        from: &str,
+        access_list: Option<&[AccessListEntry]>,
+    ) -> Option<Vec<String>> {
+        // The relayer of an Ethereum transaction isn't touched, only its signer
+        if let SethTransaction::Ethereum(ref raw) = *self {
+            return raw
+                .to_seth_transaction()
+                .accounts(raw.sender(), access_list);
+        }
+        let mut accounts = vec![String::from(from)];
+        match *self {
+            SethTransaction::CreateExternalAccount(ref txn) => {
+                if !txn.to.is_empty() {
+                    accounts.push(transform::bytes_to_hex_str(&txn.to));
+                }
+            }
+            SethTransaction::SetPermissions(ref txn) => {
+                accounts.push(transform::bytes_to_hex_str(&txn.to));
+            }
+            SethTransaction::ArchiveContract(ref txn) => {
+                accounts.push(transform::bytes_to_hex_str(&txn.to));
+            }
+            SethTransaction::ResurrectContract(ref txn) => {
+                let archive: ArchivedContract = protobuf::parse_from_bytes(&txn.archive).ok()?;
+                accounts.push(transform::bytes_to_hex_str(&archive.get_account().address));
+            }
+            SethTransaction::ImportAccount(ref txn) => {
+                accounts.push(transform::bytes_to_hex_str(
+                    &txn.get_account().get_account().address,
+                ));
+            }
+            // Private transactions only increment the sender's nonce
+            SethTransaction::CreateContractAccount(ref txn)
+                if !txn.private_payload_hash.is_empty() => {}
+            SethTransaction::MessageCall(ref txn) if !txn.private_payload_hash.is_empty() => {}
+            SethTransaction::CreateContractAccount(ref txn) => {
+                accounts.push(created_address(from, txn)?);
+                accounts.extend(access_list?.iter().map(|entry| entry.address.clone()));
+            }
+            SethTransaction::MessageCall(ref txn) => {
+                accounts.push(transform::bytes_to_hex_str(&txn.to));
+                accounts.extend(access_list?.iter().map(|entry| entry.address.clone()));
+            }
+            SethTransaction::Ethereum(_) => unreachable!(),
+        }
+        accounts.sort();
+        accounts.dedup();
+        Some(accounts)
+    }
+
+    /// The call this transaction runs in the EVM when sent from `from`, or none if it
+    /// doesn't run in it. Private and confidential transactions can't be run, since
+    /// the code or input they run isn't on chain.
+    pub fn evm_call(&self, from: &str) -> Result<Option<vm::Call>, String> {
+        let private = || String::from("The input of private transactions isn't on chain");
+        let (to, value, data, gas) = match *self {
+            SethTransaction::CreateContractAccount(ref txn) => {
+                if !txn.private_payload_hash.is_empty() || txn.has_encrypted_init() {
+                    return Err(private());
+                }
+                (None, txn.value, txn.init.clone(), txn.gas_limit)
+            }
+            SethTransaction::MessageCall(ref txn) => {
+                if !txn.private_payload_hash.is_empty() || txn.has_encrypted_data() {
+                    return Err(private());
+                }
+                (
+                    Some(txn.to.clone()),
+                    txn.value,
+                    txn.data.clone(),
+                    txn.gas_limit,
+                )
+            }
+            SethTransaction::Ethereum(ref raw) => {
+                (raw.to.clone(), raw.value, raw.data.clone(), raw.gas_limit)
+            }
+            _ => return Ok(None),
+        };
+        let address = |bytes: &[u8]| {
+            if bytes.len() == 20 {
+                Ok(H160::from_slice(bytes))
+            } else {
+                Err(format!(
+                    "Invalid address: {}",
+                    transform::bytes_to_hex_str(bytes)
+                ))
+            }
+        };
+        let from = transform::hex_str_to_bytes(from).unwrap_or_default();
+        Ok(Some(vm::Call {
+            from: address(&from)?,
+            to: match to {
+                Some(to) => Some(address(&to)?),
+                None => None,
+            },
+            value: U256::from(value),
+            data,
+            gas,
+            access_list: Vec::new(),
+        }))
+    }
+}
+
+/// The address of the contract a creation sent from `from` creates, or none if it
+/// can't be known before the transaction runs, since it is created with a salt and
+/// encrypted init code
+pub fn created_address(from: &str, txn: &CreateContractAccountTxnPb) -> Option<String> {
+    let creator = transform::hex_str_to_bytes(from)?;
+    if txn.salt.is_empty() {
+        Some(contract_address(&creator, txn.nonce))
+    } else if txn.has_encrypted_init() {
+        None
+    } else {
+        Some(create2_address(&creator, &txn.salt, &txn.init))
+    }
 }
 
 pub enum TransactionKey {
@@ -124,6 +295,10 @@ impl Transaction {
             SethTransaction::CreateContractAccount(ref txn) => txn.nonce,
             SethTransaction::MessageCall(ref txn) => txn.nonce,
             SethTransaction::SetPermissions(ref txn) => txn.nonce,
+            SethTransaction::ArchiveContract(ref txn) => txn.nonce,
+            SethTransaction::ResurrectContract(ref txn) => txn.nonce,
+            SethTransaction::ImportAccount(ref txn) => txn.nonce,
+            SethTransaction::Ethereum(ref raw) => raw.nonce,
         }
     }
 
@@ -132,13 +307,54 @@ impl Transaction {
             SethTransaction::CreateExternalAccount(_) => None,
             SethTransaction::CreateContractAccount(ref txn) => Some(txn.gas_limit),
             SethTransaction::MessageCall(ref txn) => Some(txn.gas_limit),
-            SethTransaction::SetPermissions(_) => None,
+            SethTransaction::Ethereum(ref raw) => Some(raw.gas_limit),
+            _ => None,
+        }
+    }
+
+    pub fn gas_price(&self) -> u64 {
+        match self.inner {
+            SethTransaction::CreateContractAccount(ref txn) => txn.gas_price,
+            SethTransaction::MessageCall(ref txn) => txn.gas_price,
+            SethTransaction::Ethereum(ref raw) => raw.gas_price,
+            _ => 0,
+        }
+    }
+
+    /// The max fee and max priority fee per gas of an EIP-1559 transaction, or
+    /// none for any other
+    pub fn fees(&self) -> Option<(u64, u64)> {
+        match self.inner {
+            SethTransaction::Ethereum(ref raw) => raw.fees,
+            SethTransaction::CreateContractAccount(ref txn) if txn.max_fee_per_gas > 0 => {
+                Some((txn.max_fee_per_gas, txn.max_priority_fee_per_gas))
+            }
+            SethTransaction::MessageCall(ref txn) if txn.max_fee_per_gas > 0 => {
+                Some((txn.max_fee_per_gas, txn.max_priority_fee_per_gas))
+            }
+            _ => None,
+        }
+    }
+
+    /// What the transaction pays per gas in a block with the given base fee, as
+    /// EIP-1559 prices it
+    pub fn effective_gas_price(&self, base_fee: u64) -> u64 {
+        match self.fees() {
+            Some((max_fee, max_priority_fee)) => {
+                max_fee.min(base_fee.saturating_add(max_priority_fee))
+            }
+            None => self.gas_price(),
         }
     }
 
     #[allow(clippy::wrong_self_convention)]
     pub fn from_addr(&self) -> String {
-        public_key_to_address(&transform::hex_str_to_bytes(&self.signer_public_key).unwrap())
+        match self.inner {
+            SethTransaction::Ethereum(ref raw) => String::from(raw.sender()),
+            _ => public_key_to_address(
+                &transform::hex_str_to_bytes(&self.signer_public_key).unwrap(),
+            ),
+        }
     }
 
     pub fn to_addr(&self) -> Option<String> {
@@ -149,6 +365,14 @@ impl Transaction {
             SethTransaction::CreateContractAccount(_) => None,
             SethTransaction::MessageCall(ref txn) => Some(transform::bytes_to_hex_str(&txn.to)),
             SethTransaction::SetPermissions(ref txn) => Some(transform::bytes_to_hex_str(&txn.to)),
+            SethTransaction::ArchiveContract(ref txn) => Some(transform::bytes_to_hex_str(&txn.to)),
+            SethTransaction::ResurrectContract(_) => None,
+            SethTransaction::Ethereum(ref raw) => {
+                raw.to.as_ref().map(|to| transform::bytes_to_hex_str(to))
+            }
+            SethTransaction::ImportAccount(ref txn) => Some(transform::bytes_to_hex_str(
+                &txn.get_account().get_account().address,
+            )),
         }
     }
 
@@ -156,8 +380,54 @@ impl Transaction {
         match self.inner {
             SethTransaction::CreateExternalAccount(_) => None,
             SethTransaction::CreateContractAccount(_) => None,
+            // The plaintext of private and confidential calls is never on chain, so
+            // report the payload hash or ciphertext instead
+            SethTransaction::MessageCall(ref txn) if !txn.private_payload_hash.is_empty() => {
+                Some(transform::bytes_to_hex_str(&txn.private_payload_hash))
+            }
+            SethTransaction::MessageCall(ref txn) if txn.has_encrypted_data() => Some(
+                transform::bytes_to_hex_str(txn.get_encrypted_data().get_ciphertext()),
+            ),
             SethTransaction::MessageCall(ref txn) => Some(transform::bytes_to_hex_str(&txn.data)),
-            SethTransaction::SetPermissions(_) => None,
+            SethTransaction::ResurrectContract(ref txn) => {
+                Some(transform::bytes_to_hex_str(&txn.archive))
+            }
+            SethTransaction::Ethereum(ref raw) => Some(transform::bytes_to_hex_str(&raw.data)),
+            _ => None,
+        }
+    }
+
+    /// The call this transaction runs in the EVM, or none if it doesn't run in it
+    #[cfg(feature = "debug")]
+    pub fn evm_call(&self) -> Result<Option<vm::Call>, String> {
+        self.inner.evm_call(&self.from_addr())
+    }
+
+    /// The address and permissions assigned by this transaction, if it changes any
+    pub fn permissions(&self) -> Option<(String, EvmPermissions)> {
+        match self.inner {
+            SethTransaction::CreateExternalAccount(ref txn) if txn.has_permissions() => {
+                let address = if txn.to.is_empty() {
+                    self.from_addr()
+                } else {
+                    transform::bytes_to_hex_str(&txn.to)
+                };
+                Some((address, txn.get_permissions().clone()))
+            }
+            SethTransaction::SetPermissions(ref txn) => Some((
+                transform::bytes_to_hex_str(&txn.to),
+                txn.get_permissions().clone(),
+            )),
+            SethTransaction::ImportAccount(ref txn)
+                if txn.get_account().get_account().has_permissions() =>
+            {
+                let account = txn.get_account().get_account();
+                Some((
+                    transform::bytes_to_hex_str(&account.address),
+                    account.get_permissions().clone(),
+                ))
+            }
+            _ => None,
         }
     }
 }
@@ -199,12 +469,18 @@ impl SethLog {
     }
 }
 
+#[derive(Clone)]
 pub struct SethReceipt {
     pub transaction_id: String,
     pub contract_address: String,
     pub gas_used: u64,
     pub return_value: String,
     pub logs: Vec<SethLog>,
+    pub gas_profile: Vec<GasProfileEntry>,
+    /// Whether the call reverted, with `return_value` holding what it reverted with
+    pub reverted: bool,
+    /// The state entries the transaction set or deleted
+    pub state_changes: Vec<StateChange>,
 }
 
 impl SethReceipt {
@@ -248,6 +524,7 @@ impl SethReceipt {
         };
         let gas_used = seth_receipt_pb.get_gas_used();
         let return_value = transform::bytes_to_hex_str(seth_receipt_pb.get_return_value());
+        let gas_profile = seth_receipt_pb.get_gas_profile().to_vec();
 
         Ok(SethReceipt {
             transaction_id: String::from(receipt.get_transaction_id()),
@@ -255,6 +532,9 @@ impl SethReceipt {
             gas_used,
             return_value,
             logs,
+            gas_profile,
+            reverted: seth_receipt_pb.get_reverted(),
+            state_changes: receipt.get_state_changes().to_vec(),
         })
     }
 }
@@ -15,10 +15,18 @@
  * ------------------------------------------------------------------------------
  */
 
+use abi;
+use bloom::Bloom;
+use crypto_backend;
 use jsonrpc_core::{Error, Value};
-use serde_json::Map;
+use messages::seth::{EvmPermissions, GasProfileEntry};
+use primitive_types::U256;
+use raw_transaction::DYNAMIC_FEE_TXN_TYPE;
+use serde::Serialize;
+use serde_json::{self, Map};
 use std::fmt::LowerHex;
 use transactions::{SethLog, SethReceipt, Transaction};
+use types::{self, PermissionChange, Quantity, TransactionLog, TransactionReceipt, Wei};
 
 // -- Hex --
 
@@ -48,6 +56,54 @@ pub fn bytes_to_hex_str(b: &[u8]) -> String {
         .join("")
 }
 
+// -- Address --
+
+/// Returns an address, 0x prefixed, with the letters of its hex in the case of its
+/// EIP-55 checksum: upper case where the nibble of the Keccak-256 hash of the lower
+/// case hex is 8 or more
+pub fn checksum_address(address: &str) -> String {
+    let address = address.trim_start_matches("0x").to_lowercase();
+    let hash = bytes_to_hex_str(&crypto_backend::keccak256(address.as_bytes()));
+    let checksummed: String = address
+        .chars()
+        .zip(hash.chars())
+        .map(|(c, nibble)| {
+            if nibble >= '8' {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect();
+    format!("0x{}", checksummed)
+}
+
+/// Parses a 0x prefixed address into its hex in lower case. The case of an address
+/// in only one case is ignored, while one in both cases must be in the case of its
+/// EIP-55 checksum, so that a mistyped address isn't taken for another.
+pub fn parse_address(address: &str) -> Result<String, Error> {
+    let hex = address
+        .get(2..)
+        .filter(|_| address.starts_with("0x"))
+        .ok_or_else(|| Error::invalid_params(format!("Address {} must have 0x", address)))?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::invalid_params(format!(
+            "Address {} isn't hex",
+            address
+        )));
+    }
+    let mixed_case =
+        hex.chars().any(|c| c.is_ascii_lowercase()) && hex.chars().any(|c| c.is_ascii_uppercase());
+    if mixed_case && checksum_address(hex) != address {
+        return Err(Error::invalid_params(format!(
+            "Address {} has an invalid EIP-55 checksum; check it for typos, or send it \
+             in one case",
+            address
+        )));
+    }
+    Ok(hex.to_lowercase())
+}
+
 // -- To/From Value
 
 // -- String --
@@ -62,17 +118,16 @@ pub fn hex_prefix(s: &str) -> Value {
     Value::String(format!("0x{}", s))
 }
 
-pub fn zerobytes(mut nbytes: usize) -> Value {
+/// Returns an address the way responses give addresses, with its EIP-55 checksum
+pub fn address_value(address: &str) -> Value {
+    Value::String(checksum_address(address))
+}
+
+pub fn zerobytes(nbytes: usize) -> String {
     if nbytes == 0 {
-        return Value::String(String::from("0x0"));
-    }
-    let mut s = String::with_capacity(2 + nbytes * 2);
-    s.push_str("0x");
-    while nbytes > 0 {
-        s.push_str("00");
-        nbytes -= 1;
+        return String::from("0x0");
     }
-    Value::String(s)
+    format!("0x{}", "00".repeat(nbytes))
 }
 
 pub fn from_hex_value_then<T, F>(value: &Value, then: F) -> Result<T, Error>
@@ -96,6 +151,14 @@ pub fn u64_from_hex_value(value: &Value) -> Result<u64, Error> {
     })
 }
 
+/// Parses a quantity that can take up to 256 bits, like an amount of wei
+pub fn u256_from_hex_value(value: &Value) -> Result<U256, Error> {
+    from_hex_value_then(value, |s| {
+        U256::from_str_radix(s, 16)
+            .map_err(|error| Error::invalid_params(format!("Not a number: {:?}", error)))
+    })
+}
+
 pub fn bytes_from_hex_value(value: &Value) -> Result<Vec<u8>, Error> {
     from_hex_value_then(value, |s| {
         hex_str_to_bytes(s).ok_or_else(|| Error::invalid_params(String::from("Not valid hex")))
@@ -126,6 +189,10 @@ pub fn get_u64_from_map(map: &Map<String, Value>, key: &str) -> Result<Option<u6
     get_hex_value_from_map_then(map, key, u64_from_hex_value)
 }
 
+pub fn get_u256_from_map(map: &Map<String, Value>, key: &str) -> Result<Option<U256>, Error> {
+    get_hex_value_from_map_then(map, key, u256_from_hex_value)
+}
+
 pub fn get_bytes_from_map(map: &Map<String, Value>, key: &str) -> Result<Option<Vec<u8>>, Error> {
     get_hex_value_from_map_then(map, key, bytes_from_hex_value)
 }
@@ -146,114 +213,196 @@ pub fn get_array_from_map(map: &Map<String, Value>, key: &str) -> Result<Vec<Val
     }
 }
 
+/// Encodes one of the types of the API the way clients decode it
+pub fn to_value<T: Serialize>(t: &T) -> Value {
+    serde_json::to_value(t).expect("API types are always serializable")
+}
+
 // -- Receipt --
+/// Makes the receipt of the transaction at `txn_idx` in its block, whose first log
+/// is log `first_log_idx` of the block
 pub fn make_txn_receipt_obj(
     receipt: &SethReceipt,
     txn_idx: u64,
+    first_log_idx: u64,
     block_id: &str,
     block_num: u64,
+    effective_gas_price: u64,
 ) -> Value {
-    let mut map = Map::new();
-    map.insert(
-        String::from("transactionHash"),
-        hex_prefix(&receipt.transaction_id),
-    );
-    map.insert(String::from("transactionIndex"), num_to_hex(&txn_idx));
-    map.insert(String::from("blockHash"), hex_prefix(block_id));
-    map.insert(String::from("blockNumber"), num_to_hex(&block_num));
-    map.insert(
-        String::from("cumulativeGasUsed"),
-        num_to_hex(&receipt.gas_used),
-    ); // Calculating this is expensive
-    map.insert(String::from("gasUsed"), num_to_hex(&receipt.gas_used));
-    map.insert(
-        String::from("contractAddress"),
-        match receipt.contract_address.len() {
-            0 => Value::Null,
-            _ => hex_prefix(&receipt.contract_address),
+    to_value(&TransactionReceipt {
+        transaction_hash: format!("0x{}", receipt.transaction_id),
+        transaction_index: Quantity(txn_idx),
+        block_hash: format!("0x{}", block_id),
+        block_number: Quantity(block_num),
+        cumulative_gas_used: Quantity(receipt.gas_used), // Calculating this is expensive
+        gas_used: Quantity(receipt.gas_used),
+        contract_address: match receipt.contract_address.len() {
+            0 => None,
+            _ => Some(checksum_address(&receipt.contract_address)),
         },
-    );
-    map.insert(
-        String::from("returnValue"),
-        hex_prefix(&receipt.return_value),
-    );
-    map.insert(
-        String::from("logs"),
-        Value::Array(
-            receipt
-                .logs
-                .iter()
-                .map(|log| make_log_obj(log, &receipt.transaction_id, txn_idx, block_id, block_num))
-                .collect(),
-        ),
-    );
-    Value::Object(map)
+        return_value: format!("0x{}", receipt.return_value),
+        status: Quantity(if receipt.reverted { 0 } else { 1 }),
+        revert_reason: if receipt.reverted {
+            hex_str_to_bytes(&receipt.return_value).and_then(|data| abi::revert_reason(&data))
+        } else {
+            None
+        },
+        logs_bloom: receipt.logs.iter().collect::<Bloom>().to_hex(),
+        logs: receipt
+            .logs
+            .iter()
+            .enumerate()
+            .map(|(log_idx, log)| {
+                make_log(
+                    log,
+                    first_log_idx + log_idx as u64,
+                    &receipt.transaction_id,
+                    txn_idx,
+                    block_id,
+                    block_num,
+                )
+            })
+            .collect(),
+        // Only recorded by transaction processors in gas audit mode
+        gas_profile: receipt.gas_profile.iter().map(make_gas_profile).collect(),
+        effective_gas_price: Wei::from(effective_gas_price),
+    })
+}
+
+fn make_gas_profile(entry: &GasProfileEntry) -> types::GasProfileEntry {
+    types::GasProfileEntry {
+        opcode: entry.opcode.clone(),
+        count: Quantity(entry.count),
+        gas: Quantity(entry.gas),
+    }
 }
 
 // -- Log --
+fn make_log(
+    log: &SethLog,
+    log_idx: u64,
+    txn_id: &str,
+    txn_idx: u64,
+    block_id: &str,
+    block_num: u64,
+) -> TransactionLog {
+    TransactionLog {
+        removed: false,
+        log_index: Quantity(log_idx),
+        transaction_index: Quantity(txn_idx),
+        transaction_hash: format!("0x{}", txn_id),
+        block_hash: format!("0x{}", block_id),
+        block_number: Quantity(block_num),
+        address: checksum_address(&log.address),
+        data: format!("0x{}", log.data),
+        topics: log.topics.iter().map(|t| format!("0x{}", t)).collect(),
+    }
+}
+
 pub fn make_log_obj(
     log: &SethLog,
+    log_idx: u64,
     txn_id: &str,
     txn_idx: u64,
     block_id: &str,
     block_num: u64,
 ) -> Value {
-    let mut map = Map::new();
-    map.insert(String::from("removed"), Value::Bool(false));
-    map.insert(String::from("logIndex"), num_to_hex(&0)); // Calculating this is expensive
-    map.insert(String::from("transactionIndex"), num_to_hex(&txn_idx));
-    map.insert(String::from("transactionHash"), hex_prefix(txn_id));
-    map.insert(String::from("blockHash"), hex_prefix(block_id));
-    map.insert(String::from("blockNumber"), num_to_hex(&block_num));
-    map.insert(String::from("address"), hex_prefix(&log.address));
-    map.insert(String::from("data"), hex_prefix(&log.data));
-    map.insert(
-        String::from("topics"),
-        Value::Array(log.topics.iter().map(|t| hex_prefix(t)).collect()),
-    );
-    Value::Object(map)
+    to_value(&make_log(
+        log, log_idx, txn_id, txn_idx, block_id, block_num,
+    ))
+}
+
+// -- Permissions --
+pub fn make_permission_change_obj(
+    address: &str,
+    permissions: &EvmPermissions,
+    txn_id: &str,
+    txn_idx: u64,
+    block_id: &str,
+    block_num: u64,
+) -> Value {
+    to_value(&PermissionChange {
+        address: checksum_address(address),
+        permissions: format!("{}", permissions),
+        transaction_index: Quantity(txn_idx),
+        transaction_hash: format!("0x{}", txn_id),
+        block_hash: format!("0x{}", block_id),
+        block_number: Quantity(block_num),
+    })
 }
 
 // -- Transaction --
 pub fn make_txn_obj(txn: &Transaction, txn_idx: u64, block_id: &str, block_num: u64) -> Value {
-    let obj = make_txn_obj_no_block(txn);
-    if let Value::Object(mut map) = obj {
-        map.insert(String::from("blockHash"), hex_prefix(block_id));
-        map.insert(String::from("blockNumber"), num_to_hex(&block_num));
-        map.insert(String::from("transactionIndex"), num_to_hex(&txn_idx));
-        Value::Object(map)
-    } else {
-        obj
-    }
+    to_value(&make_block_txn(txn, txn_idx, block_id, block_num))
+}
+
+/// Makes a transaction committed at `txn_idx` in a block
+pub fn make_block_txn(
+    txn: &Transaction,
+    txn_idx: u64,
+    block_id: &str,
+    block_num: u64,
+) -> types::Transaction {
+    let mut txn = make_txn(txn);
+    txn.block_hash = Some(format!("0x{}", block_id));
+    txn.block_number = Some(Quantity(block_num));
+    txn.transaction_index = Some(Quantity(txn_idx));
+    txn
 }
 
 pub fn make_txn_obj_no_block(txn: &Transaction) -> Value {
-    let mut map = Map::with_capacity(11);
-    map.insert(String::from("hash"), hex_prefix(&txn.hash()));
-    map.insert(String::from("nonce"), num_to_hex(&txn.nonce()));
-    map.insert(String::from("blockHash"), Value::Null);
-    map.insert(String::from("blockNumber"), Value::Null);
-    map.insert(String::from("transactionIndex"), Value::Null);
-    map.insert(String::from("from"), hex_prefix(&txn.from_addr()));
-    let to = match txn.to_addr() {
-        Some(addr) => hex_prefix(&addr),
-        None => Value::Null,
-    };
-    map.insert(String::from("to"), to);
-
-    map.insert(String::from("value"), zerobytes(0));
-    map.insert(String::from("gasPrice"), zerobytes(0));
-
-    let gas = match txn.gas_limit() {
-        Some(g) => num_to_hex(&g),
-        None => zerobytes(0),
-    };
-    map.insert(String::from("gas"), gas);
-
-    let input = match txn.data() {
-        Some(data) => hex_prefix(&data),
-        None => zerobytes(0),
-    };
-    map.insert(String::from("input"), input);
-    Value::Object(map)
+    to_value(&make_txn(txn))
+}
+
+pub fn make_txn(txn: &Transaction) -> types::Transaction {
+    types::Transaction {
+        hash: format!("0x{}", txn.hash()),
+        nonce: Quantity(txn.nonce()),
+        block_hash: None,
+        block_number: None,
+        transaction_index: None,
+        from: checksum_address(&txn.from_addr()),
+        to: txn.to_addr().map(|addr| checksum_address(&addr)),
+        value: Wei::default(),
+        gas_price: Wei::default(),
+        gas: Quantity(txn.gas_limit().unwrap_or(0)),
+        input: match txn.data() {
+            Some(data) => format!("0x{}", data),
+            None => zerobytes(0),
+        },
+        txn_type: Quantity(txn.fees().map_or(0, |_| u64::from(DYNAMIC_FEE_TXN_TYPE))),
+        max_fee_per_gas: txn.fees().map(|(max_fee, _)| Wei::from(max_fee)),
+        max_priority_fee_per_gas: txn
+            .fees()
+            .map(|(_, max_priority_fee)| Wei::from(max_priority_fee)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_addresses() {
+        // The examples of EIP-55
+        let checksummed = [
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+            "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+        ];
+        for address in &checksummed {
+            assert_eq!(checksum_address(&address.to_lowercase()), *address);
+            assert_eq!(parse_address(address), Ok(address[2..].to_lowercase()));
+            assert_eq!(
+                parse_address(&address.to_uppercase().replace("0X", "0x")),
+                Ok(address[2..].to_lowercase())
+            );
+        }
+
+        // A mistyped address in both cases is refused
+        assert!(parse_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD").is_err());
+        assert!(parse_address("5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").is_err());
+        assert!(parse_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeg").is_err());
+    }
 }
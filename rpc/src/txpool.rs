@@ -0,0 +1,196 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+use sawtooth_sdk::messages::transaction::Transaction;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use transactions;
+
+/// How long a transaction is remembered after it was sent
+const PENDING_TTL: Duration = Duration::from_secs(600);
+
+/// How many transactions are remembered at most
+const MAX_PENDING: usize = 4096;
+
+struct PendingTransaction {
+    txn: Transaction,
+    batch_id: String,
+    sent: Instant,
+}
+
+/// The transactions this server has sent to the validator, until they are found in a
+/// block, so that they can be looked up while they are still pending
+#[derive(Clone, Default)]
+pub struct TxPool {
+    pending: Arc<Mutex<HashMap<String, PendingTransaction>>>,
+}
+
+impl TxPool {
+    pub fn new() -> Self {
+        TxPool::default()
+    }
+
+    /// Records a transaction that was sent in the given batch. Transactions sent too
+    /// long ago are forgotten, and nothing is recorded once the pool is full.
+    pub fn insert(&self, txn: Transaction, batch_id: &str) {
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, pending| pending.sent.elapsed() < PENDING_TTL);
+        if pending.len() >= MAX_PENDING {
+            warn!(
+                "Transaction pool is full, not recording {}",
+                txn.header_signature
+            );
+            return;
+        }
+        pending.insert(
+            txn.header_signature.clone(),
+            PendingTransaction {
+                txn,
+                batch_id: String::from(batch_id),
+                sent: Instant::now(),
+            },
+        );
+    }
+
+    /// Returns a recorded transaction and the id of the batch it was sent in
+    pub fn get(&self, txn_id: &str) -> Option<(Transaction, String)> {
+        self.pending
+            .lock()
+            .unwrap()
+            .get(txn_id)
+            .map(|pending| (pending.txn.clone(), pending.batch_id.clone()))
+    }
+
+    /// Returns each recorded transaction and the id of the batch it was sent in
+    pub fn all(&self) -> Vec<(Transaction, String)> {
+        self.pending
+            .lock()
+            .unwrap()
+            .values()
+            .map(|pending| (pending.txn.clone(), pending.batch_id.clone()))
+            .collect()
+    }
+
+    /// Returns the id, batch id and nonce of each recorded transaction sent from an
+    /// account
+    pub fn sent_from(&self, address: &str) -> Vec<(String, String, u64)> {
+        self.pending
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(txn_id, pending)| {
+                transactions::Transaction::try_from(pending.txn.clone())
+                    .ok()
+                    .filter(|txn| txn.from_addr().eq_ignore_ascii_case(address))
+                    .map(|txn| (txn_id.clone(), pending.batch_id.clone(), txn.nonce()))
+            })
+            .collect()
+    }
+
+    /// Forgets every recorded transaction sent from an account, returning how many
+    /// there were
+    pub fn remove_sent_from(&self, address: &str) -> usize {
+        let sent = self.sent_from(address);
+        let mut pending = self.pending.lock().unwrap();
+        sent.iter()
+            .filter(|(txn_id, _, _)| pending.remove(txn_id).is_some())
+            .count()
+    }
+
+    /// Forgets a transaction once it is in a block or won't ever be
+    pub fn remove(&self, txn_id: &str) {
+        self.pending.lock().unwrap().remove(txn_id);
+    }
+
+    /// Returns how many transactions are pending
+    pub fn count(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::{BlockKey, ValidatorClient};
+    use contracts::ContractRegistry;
+    use filters::FilterManager;
+    use mock_chain::MockChain;
+
+    /// The transactions bob sends in the last block of the mock chain, with the
+    /// nonces 1 and 2
+    fn bobs_transactions() -> Vec<Transaction> {
+        ValidatorClient::new(
+            MockChain::new(),
+            Vec::new(),
+            FilterManager::new(1),
+            0,
+            String::from("1.0"),
+            ContractRegistry::new(),
+        )
+        .get_block(BlockKey::Number(3))
+        .unwrap()
+        .get_batches()
+        .iter()
+        .flat_map(|batch| batch.get_transactions().iter().cloned())
+        .collect()
+    }
+
+    #[test]
+    fn remembers_transactions_until_they_are_forgotten() {
+        let txns = bobs_transactions();
+        let pool = TxPool::new();
+        pool.insert(txns[0].clone(), "first");
+        pool.insert(txns[1].clone(), "second");
+        assert_eq!(pool.count(), 2);
+        assert_eq!(
+            pool.get(&txns[1].header_signature)
+                .map(|(_, batch_id)| batch_id),
+            Some(String::from("second"))
+        );
+        assert!(pool.get("unknown").is_none());
+
+        pool.remove(&txns[0].header_signature);
+        assert_eq!(pool.count(), 1);
+        assert_eq!(pool.all().len(), 1);
+        assert!(pool.get(&txns[0].header_signature).is_none());
+    }
+
+    #[test]
+    fn finds_the_transactions_sent_from_an_account() {
+        let txns = bobs_transactions();
+        let accounts = MockChain::accounts();
+        let (alice, bob) = (accounts[0].address(), accounts[1].address());
+        let pool = TxPool::new();
+        for txn in &txns {
+            pool.insert(txn.clone(), "batch");
+        }
+
+        let mut nonces: Vec<u64> = pool
+            .sent_from(&bob.to_uppercase())
+            .into_iter()
+            .map(|(_, _, nonce)| nonce)
+            .collect();
+        nonces.sort_unstable();
+        assert_eq!(nonces, vec![1, 2]);
+        assert!(pool.sent_from(alice).is_empty());
+
+        assert_eq!(pool.remove_sent_from(alice), 0);
+        assert_eq!(pool.remove_sent_from(bob), 2);
+        assert_eq!(pool.count(), 0);
+    }
+}
@@ -0,0 +1,295 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Hashing of EIP-712 typed data
+
+use crypto_backend;
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+use transform;
+
+/// Returns the hash that is signed for the given typed data, an object with the
+/// `types`, `primaryType`, `domain` and `message` of EIP-712
+pub fn hash(typed_data: &Value) -> Result<[u8; 32], String> {
+    let types = typed_data
+        .get("types")
+        .and_then(Value::as_object)
+        .ok_or("`types` must be an object")?;
+    let primary_type = typed_data
+        .get("primaryType")
+        .and_then(Value::as_str)
+        .ok_or("`primaryType` must be a string")?;
+    let domain = typed_data.get("domain").ok_or("`domain` is missing")?;
+    let message = typed_data.get("message").ok_or("`message` is missing")?;
+
+    let mut data = vec![0x19, 0x01];
+    data.extend_from_slice(&hash_struct(types, "EIP712Domain", domain)?);
+    data.extend_from_slice(&hash_struct(types, primary_type, message)?);
+    Ok(crypto_backend::keccak256(&data))
+}
+
+/// Returns the fields of a struct type as `(name, type)` pairs
+fn fields<'a>(
+    types: &'a Map<String, Value>,
+    name: &str,
+) -> Result<Vec<(&'a str, &'a str)>, String> {
+    types
+        .get(name)
+        .and_then(Value::as_array)
+        .ok_or_else(|| format!("Type `{}` is not defined", name))?
+        .iter()
+        .map(|field| {
+            match (
+                field.get("name").and_then(Value::as_str),
+                field.get("type").and_then(Value::as_str),
+            ) {
+                (Some(name), Some(kind)) => Ok((name, kind)),
+                _ => Err(format!("Fields of `{}` must have a name and type", name)),
+            }
+        })
+        .collect()
+}
+
+/// Strips any array suffixes from a type
+fn base_type(kind: &str) -> &str {
+    kind.split('[').next().unwrap_or(kind)
+}
+
+/// Adds the struct types that `name` refers to, including itself, to `found`
+fn dependencies(
+    types: &Map<String, Value>,
+    name: &str,
+    found: &mut BTreeSet<String>,
+) -> Result<(), String> {
+    if found.contains(name) || !types.contains_key(name) {
+        return Ok(());
+    }
+    found.insert(String::from(name));
+    for (_, kind) in fields(types, name)? {
+        dependencies(types, base_type(kind), found)?;
+    }
+    Ok(())
+}
+
+fn encode_type(types: &Map<String, Value>, name: &str) -> Result<String, String> {
+    let mut found = BTreeSet::new();
+    dependencies(types, name, &mut found)?;
+    found.remove(name);
+
+    let mut encoded = String::new();
+    for name in Some(String::from(name)).iter().chain(found.iter()) {
+        let fields = fields(types, name)?
+            .iter()
+            .map(|&(field, kind)| format!("{} {}", kind, field))
+            .collect::<Vec<String>>()
+            .join(",");
+        encoded += &format!("{}({})", name, fields);
+    }
+    Ok(encoded)
+}
+
+fn hash_struct(types: &Map<String, Value>, name: &str, value: &Value) -> Result<[u8; 32], String> {
+    let object = value
+        .as_object()
+        .ok_or_else(|| format!("Value of `{}` must be an object", name))?;
+
+    let mut data = crypto_backend::keccak256(encode_type(types, name)?.as_bytes()).to_vec();
+    for (field, kind) in fields(types, name)? {
+        let value = object.get(field).unwrap_or(&Value::Null);
+        data.extend_from_slice(&encode_value(types, kind, value)?);
+    }
+    Ok(crypto_backend::keccak256(&data))
+}
+
+fn encode_value(types: &Map<String, Value>, kind: &str, value: &Value) -> Result<[u8; 32], String> {
+    if kind.ends_with(']') {
+        let item_kind = &kind[..kind.rfind('[').unwrap_or(0)];
+        let items = value
+            .as_array()
+            .ok_or_else(|| format!("Value of `{}` must be an array", kind))?;
+        let mut data = Vec::new();
+        for item in items {
+            data.extend_from_slice(&encode_value(types, item_kind, item)?);
+        }
+        return Ok(crypto_backend::keccak256(&data));
+    }
+    if types.contains_key(kind) {
+        return hash_struct(types, kind, value);
+    }
+
+    let invalid = || format!("Invalid value for `{}`: {}", kind, value);
+    let mut word = [0; 32];
+    match kind {
+        "string" => {
+            let string = value.as_str().ok_or_else(invalid)?;
+            word = crypto_backend::keccak256(string.as_bytes());
+        }
+        "bytes" => word = crypto_backend::keccak256(&hex_value(value).ok_or_else(invalid)?),
+        "bool" => word[31] = value.as_bool().ok_or_else(invalid)? as u8,
+        "address" => {
+            let address = hex_value(value)
+                .filter(|address| address.len() == 20)
+                .ok_or_else(invalid)?;
+            word[12..].copy_from_slice(&address);
+        }
+        _ if kind.starts_with("bytes") => {
+            let bytes = hex_value(value)
+                .filter(|bytes| bytes.len() <= 32)
+                .ok_or_else(invalid)?;
+            word[..bytes.len()].copy_from_slice(&bytes);
+        }
+        _ if kind.starts_with("uint") || kind.starts_with("int") => {
+            word = int_value(value).ok_or_else(invalid)?;
+        }
+        _ => return Err(format!("Type `{}` is not supported", kind)),
+    }
+    Ok(word)
+}
+
+/// Parses a `0x` prefixed hex string with an even number of digits
+pub fn hex_value(value: &Value) -> Option<Vec<u8>> {
+    value
+        .as_str()
+        .and_then(|hex| hex.get(2..))
+        .filter(|hex| hex.len() % 2 == 0)
+        .and_then(transform::hex_str_to_bytes)
+}
+
+/// Parses an integer given as a JSON number or a decimal or hex string into a 256 bit
+/// two's complement word
+pub fn int_value(value: &Value) -> Option<[u8; 32]> {
+    let mut word = [0; 32];
+    if let Some(number) = value.as_i64() {
+        word = [if number < 0 { 0xff } else { 0 }; 32];
+        word[24..].copy_from_slice(&number.to_be_bytes());
+        return Some(word);
+    }
+    if let Some(number) = value.as_u64() {
+        word[24..].copy_from_slice(&number.to_be_bytes());
+        return Some(word);
+    }
+
+    let string = value.as_str()?;
+    if let Some(hex) = string.strip_prefix("0x") {
+        let hex = if hex.len() % 2 == 1 {
+            format!("0{}", hex)
+        } else {
+            String::from(hex)
+        };
+        let bytes = transform::hex_str_to_bytes(&hex).filter(|bytes| bytes.len() <= 32)?;
+        word[32 - bytes.len()..].copy_from_slice(&bytes);
+        return Some(word);
+    }
+
+    let (negative, digits) = match string.get(..1) {
+        Some("-") => (true, &string[1..]),
+        _ => (false, string),
+    };
+    if digits.is_empty() {
+        return None;
+    }
+    for digit in digits.chars() {
+        let mut carry = digit.to_digit(10)?;
+        for byte in word.iter_mut().rev() {
+            let product = u32::from(*byte) * 10 + carry;
+            *byte = product as u8;
+            carry = product >> 8;
+        }
+        if carry != 0 {
+            return None;
+        }
+    }
+    if negative {
+        let mut carry = 1;
+        for byte in word.iter_mut().rev() {
+            let sum = u32::from(!*byte) + carry;
+            *byte = sum as u8;
+            carry = sum >> 8;
+        }
+    }
+    Some(word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn hashes_the_example_of_the_eip() {
+        let typed_data: Value = serde_json::from_str(
+            r#"{
+                "types": {
+                    "EIP712Domain": [
+                        {"name": "name", "type": "string"},
+                        {"name": "version", "type": "string"},
+                        {"name": "chainId", "type": "uint256"},
+                        {"name": "verifyingContract", "type": "address"}
+                    ],
+                    "Person": [
+                        {"name": "name", "type": "string"},
+                        {"name": "wallet", "type": "address"}
+                    ],
+                    "Mail": [
+                        {"name": "from", "type": "Person"},
+                        {"name": "to", "type": "Person"},
+                        {"name": "contents", "type": "string"}
+                    ]
+                },
+                "primaryType": "Mail",
+                "domain": {
+                    "name": "Ether Mail",
+                    "version": "1",
+                    "chainId": 1,
+                    "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+                },
+                "message": {
+                    "from": {
+                        "name": "Cow",
+                        "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826"
+                    },
+                    "to": {
+                        "name": "Bob",
+                        "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB"
+                    },
+                    "contents": "Hello, Bob!"
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            transform::bytes_to_hex_str(&hash(&typed_data).unwrap()),
+            "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+        );
+    }
+
+    #[test]
+    fn parses_integers() {
+        assert_eq!(int_value(&Value::from(-1)), Some([0xff; 32]));
+        assert_eq!(int_value(&Value::from("-1")), Some([0xff; 32]));
+        assert_eq!(
+            int_value(&Value::from("0x3e8")).map(|w| w[30..].to_vec()),
+            Some(vec![0x03, 0xe8])
+        );
+        assert_eq!(
+            int_value(&Value::from("1000")).map(|w| w[30..].to_vec()),
+            Some(vec![0x03, 0xe8])
+        );
+        assert_eq!(int_value(&Value::from("12a")), None);
+    }
+}
@@ -0,0 +1,141 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Versions of the seth_* extension methods. Each version is served under its own
+//! names, like `seth_v1_getPermissions`, and the unversioned names stay those of
+//! v1, so that a method can change in a later version without breaking the
+//! integrations that call an earlier one. The methods of an earlier version whose
+//! parameters or results differ from the latest's are served by shims that adapt
+//! them, and methods that are added are served in every version.
+
+use requests::RequestHandler;
+use sawtooth_sdk::messaging::stream::MessageSender;
+use std::borrow::Cow;
+
+/// The versions served, oldest first
+pub const VERSIONS: &[&str] = &["v1"];
+
+/// The version the unversioned names of the seth_* methods are
+pub const UNVERSIONED: &str = "v1";
+
+/// The method clients negotiate a version with, which isn't versioned itself
+pub const NEGOTIATE_METHOD: &str = "seth_negotiateVersion";
+
+const PREFIX: &str = "seth_";
+
+/// The methods of earlier versions that are served by a shim instead of the latest
+/// method, by version and name without the `seth_` prefix. There are none until a
+/// method changes in a later version.
+fn shims<T>() -> Vec<(&'static str, &'static str, RequestHandler<T>)>
+where
+    T: MessageSender,
+{
+    Vec::new()
+}
+
+/// Adds the versioned names of the seth_* methods to the latest methods, and serves
+/// the unversioned names and those of each version by their shims where they have
+/// one
+pub fn with_versions<T>(
+    methods: Vec<(String, RequestHandler<T>)>,
+) -> Vec<(String, RequestHandler<T>)>
+where
+    T: MessageSender,
+{
+    let shims = shims::<T>();
+    let shim = |version: &str, method: &str| {
+        shims
+            .iter()
+            .find(|&&(shim_version, name, _)| shim_version == version && name == method)
+            .map(|&(_, _, handler)| handler)
+    };
+
+    let mut versioned = Vec::new();
+    for (name, handler) in methods {
+        let method = match name.strip_prefix(PREFIX) {
+            Some(method) if name != NEGOTIATE_METHOD => String::from(method),
+            _ => {
+                versioned.push((name, handler));
+                continue;
+            }
+        };
+        for version in VERSIONS {
+            versioned.push((
+                format!("{}{}_{}", PREFIX, version, method),
+                shim(version, &method).unwrap_or(handler),
+            ));
+        }
+        versioned.push((name, shim(UNVERSIONED, &method).unwrap_or(handler)));
+    }
+    versioned
+}
+
+/// Returns the unversioned name of a method, so that what is decided by the name of
+/// a method, like who may call it, is decided alike for each of its versions
+pub fn canonical(method: &str) -> Cow<'_, str> {
+    let versioned = method.strip_prefix(PREFIX).and_then(|rest| {
+        let (version, method) = rest.split_at(rest.find('_')?);
+        if VERSIONS.contains(&version) {
+            Some(&method[1..])
+        } else {
+            None
+        }
+    });
+    match versioned {
+        Some(method) => Cow::Owned(format!("{}{}", PREFIX, method)),
+        None => Cow::Borrowed(method),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::ValidatorClient;
+    use jsonrpc_core::{Error, Params, Value};
+    use mock_chain::MockChain;
+
+    fn handler(_: Params, _: ValidatorClient<MockChain>) -> Result<Value, Error> {
+        Ok(Value::Null)
+    }
+
+    #[test]
+    fn serves_each_version() {
+        let methods: Vec<(String, RequestHandler<MockChain>)> = vec![
+            (String::from("eth_chainId"), handler),
+            (String::from("seth_getPermissions"), handler),
+            (String::from(NEGOTIATE_METHOD), handler),
+        ];
+        let names: Vec<String> = with_versions(methods)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "eth_chainId",
+                "seth_v1_getPermissions",
+                "seth_getPermissions",
+                NEGOTIATE_METHOD,
+            ]
+        );
+
+        assert_eq!(canonical("seth_v1_submitOrdered"), "seth_submitOrdered");
+        assert_eq!(canonical("seth_submitOrdered"), "seth_submitOrdered");
+        assert_eq!(canonical("seth_v9_submitOrdered"), "seth_v9_submitOrdered");
+        assert_eq!(canonical("eth_signTypedData_v4"), "eth_signTypedData_v4");
+    }
+}
@@ -0,0 +1,1119 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Read-only execution of calls against the state of a block, for `eth_call`. The
+//! state a call changes is thrown away once it returns.
+
+use client::{BlockKey, ValidatorClient};
+use crypto::digest::Digest;
+use crypto::ripemd160::Ripemd160;
+use crypto::sha2::Sha256;
+use evm::backend::{Apply, Backend, Basic, Log};
+use evm::executor::stack::{
+    MemoryStackState, PrecompileFailure, PrecompileFn, PrecompileOutput, StackExecutor, StackState,
+    StackSubstateMetadata,
+};
+use evm::{Config, Context, ExitError, ExitReason, ExitSucceed};
+use messages::block_info::{BlockInfo, BlockInfoConfig};
+use messages::seth::EvmStateAccount;
+use primitive_types::{H160, H256, U256};
+use protobuf;
+use sawtooth_sdk::messaging::stream::MessageSender;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Instant;
+#[cfg(feature = "debug")]
+use tracer::{self, StructLog, TraceOptions};
+use transform;
+#[cfg(feature = "debug")]
+use witness::{Witness, WitnessAccount};
+
+/// The most gas a call can use, unless configured otherwise. Gas is free on seth,
+/// so this only bounds how long a call can run.
+pub const DEFAULT_GAS_CAP: u64 = 50_000_000;
+
+pub const BLOCK_INFO_CONFIG_ADDRESS: &str =
+    "00b10c0100000000000000000000000000000000000000000000000000000000000000";
+pub const BLOCK_INFO_NAMESPACE: &str = "00b10c00";
+
+/// How many state entries of an account are read along with it. The storage of
+/// accounts with more entries than this is read a slot at a time past the first.
+const ACCOUNT_STATE_LIMIT: i32 = 100;
+
+/// The most accounts and storage slots that are read ahead of a call needing them,
+/// for each contract whose code is loaded
+const MAX_PREDICTED_ACCOUNTS: usize = 16;
+const MAX_PREDICTED_SLOTS: usize = 64;
+
+const PUSH1: u8 = 0x60;
+const PUSH20: u8 = 0x73;
+const PUSH32: u8 = 0x7f;
+const SLOAD: u8 = 0x54;
+
+pub struct Call {
+    pub from: H160,
+    /// The contract called, or none to run `data` as the code creating a contract
+    pub to: Option<H160>,
+    pub value: U256,
+    pub data: Vec<u8>,
+    pub gas: u64,
+    /// The accounts and storage slots the call says it reads, which are read
+    /// before it runs
+    pub access_list: Vec<(H160, Vec<H256>)>,
+}
+
+pub struct Executed {
+    pub output: Vec<u8>,
+    /// The gas the call used before refunds, which is what it needs to be given
+    pub gas_used: u64,
+}
+
+/// What a call would do if it were sent in a transaction
+pub struct Simulated {
+    pub result: Result<Executed, CallError>,
+    /// The balances of the accounts the call changes, before and after it
+    pub balances: BTreeMap<H160, (U256, U256)>,
+    /// The logs the call emits, none if it fails
+    pub logs: Vec<Log>,
+}
+
+/// The rules of an Ethereum hard fork that calls are run with. Calls are run as
+/// Istanbul does unless a candidate fork is being tried out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "debug"), allow(dead_code))]
+pub enum Fork {
+    Istanbul,
+    Berlin,
+    London,
+    Shanghai,
+}
+
+impl Fork {
+    #[cfg(feature = "debug")]
+    pub fn parse(name: &str) -> Result<Fork, String> {
+        match name.to_lowercase().as_str() {
+            "istanbul" => Ok(Fork::Istanbul),
+            "berlin" => Ok(Fork::Berlin),
+            "london" => Ok(Fork::London),
+            "shanghai" => Ok(Fork::Shanghai),
+            _ => Err(format!(
+                "Unknown fork {}, must be istanbul, berlin, london or shanghai",
+                name
+            )),
+        }
+    }
+
+    #[cfg(feature = "debug")]
+    pub fn name(self) -> &'static str {
+        match self {
+            Fork::Istanbul => "istanbul",
+            Fork::Berlin => "berlin",
+            Fork::London => "london",
+            Fork::Shanghai => "shanghai",
+        }
+    }
+
+    fn config(self) -> Config {
+        match self {
+            Fork::Istanbul => Config::istanbul(),
+            Fork::Berlin => Config::berlin(),
+            Fork::London => Config::london(),
+            Fork::Shanghai => Config::shanghai(),
+        }
+    }
+}
+
+/// How a call run in a transaction went, with the logs it emitted
+#[cfg(feature = "debug")]
+pub type Outcome = (Result<Executed, CallError>, Vec<Log>);
+
+#[derive(Debug)]
+pub enum CallError {
+    /// The call reverted, with the data it reverted with
+    Reverted(Vec<u8>),
+    /// The call ran out of the gas it was given
+    OutOfGas,
+    /// The call failed without reverting for another reason, like an invalid opcode
+    Failed(String),
+    /// The state the call read could not be loaded
+    State(String),
+}
+
+/// Runs a call against the state of a block, the way a transaction in the block
+/// after it would run
+pub fn call<T>(
+    client: &ValidatorClient<T>,
+    state_root: &str,
+    block_num: u64,
+    chain_id: u64,
+    call: Call,
+) -> Result<Executed, CallError>
+where
+    T: MessageSender,
+{
+    let started = Instant::now();
+    let mut backend = StateBackend::new(client, state_root, block_num, chain_id, &call);
+    let (reason, output, gas_used) = execute(&mut backend, call);
+    client.metrics.record_execution(started.elapsed(), gas_used);
+    if let Some(error) = backend.error.into_inner() {
+        return Err(CallError::State(error));
+    }
+    result(reason, output, gas_used)
+}
+
+/// Runs a call as `call` does, also returning the balances it changes and the logs
+/// it emits. Only failing to read state fails here, a call that fails doesn't.
+pub fn simulate<T>(
+    client: &ValidatorClient<T>,
+    state_root: &str,
+    block_num: u64,
+    chain_id: u64,
+    call: Call,
+) -> Result<Simulated, CallError>
+where
+    T: MessageSender,
+{
+    let started = Instant::now();
+    let mut backend = StateBackend::new(client, state_root, block_num, chain_id, &call);
+    let (reason, output, gas_used, (changed, logs)) =
+        run(&mut backend, Fork::Istanbul, call, |state| {
+            let (changes, logs) = state.deconstruct();
+            let changed = changes
+                .into_iter()
+                .map(|change| match change {
+                    Apply::Modify { address, basic, .. } => (address, basic.balance),
+                    Apply::Delete { address } => (address, U256::zero()),
+                })
+                .collect::<Vec<_>>();
+            (changed, logs.into_iter().collect::<Vec<_>>())
+        });
+    let balances = changed
+        .into_iter()
+        .map(|(address, after)| (address, (backend.basic(address).balance, after)))
+        .filter(|(_, (before, after))| before != after)
+        .collect();
+    client.metrics.record_execution(started.elapsed(), gas_used);
+    if let Some(error) = backend.error.into_inner() {
+        return Err(CallError::State(error));
+    }
+
+    Ok(Simulated {
+        result: result(reason, output, gas_used),
+        balances,
+        logs,
+    })
+}
+
+/// Runs a call as `call` does, also returning the state it read
+#[cfg(feature = "debug")]
+pub fn call_with_witness<T>(
+    client: &ValidatorClient<T>,
+    state_root: &str,
+    block_num: u64,
+    chain_id: u64,
+    call: Call,
+) -> (Result<Executed, CallError>, Witness)
+where
+    T: MessageSender,
+{
+    let witness = Witness {
+        block_num,
+        chain_id,
+        ..Witness::default()
+    };
+    let mut backend = StateBackend::new(client, state_root, block_num, chain_id, &call);
+    backend.witness = Some(RefCell::new(witness));
+    let (reason, output, gas_used) = execute(&mut backend, call);
+    let witness = backend.witness.take().unwrap_or_default().into_inner();
+    match backend.error.into_inner() {
+        Some(error) => (Err(CallError::State(error)), witness),
+        None => (result(reason, output, gas_used), witness),
+    }
+}
+
+pub fn execute<B: Backend>(backend: &mut B, call: Call) -> (ExitReason, Vec<u8>, u64) {
+    let (reason, output, gas_used, _) = run(backend, Fork::Istanbul, call, |_| ());
+    (reason, output, gas_used)
+}
+
+/// Runs a call as `execute` does with the rules of `fork`, handing the state it left
+/// to `finish`
+fn run<'b, B, R, F>(
+    backend: &'b mut B,
+    fork: Fork,
+    call: Call,
+    finish: F,
+) -> (ExitReason, Vec<u8>, u64, R)
+where
+    B: Backend,
+    F: for<'c> FnOnce(MemoryStackState<'b, 'c, B>) -> R,
+{
+    let config = fork.config();
+    let precompiles = precompiles();
+    let metadata = StackSubstateMetadata::new(call.gas, &config);
+    let state = MemoryStackState::new(metadata, backend);
+    let mut executor = StackExecutor::new_with_precompiles(state, &config, &precompiles);
+    let (reason, output) = match call.to {
+        Some(to) => {
+            executor.transact_call(call.from, to, call.value, call.data, call.gas, Vec::new())
+        }
+        None => executor.transact_create(call.from, call.value, call.data, call.gas, Vec::new()),
+    };
+    let gas_used = executor.state().metadata().gasometer().total_used_gas();
+    (reason, output, gas_used, finish(executor.into_state()))
+}
+
+/// Runs the transactions a block has before one against the state before the
+/// block, and then traces that transaction's call. Each earlier transaction is
+/// given by its sender and its call, or no call if it doesn't run in the EVM, in
+/// which case only the sender's nonce changes.
+#[cfg(feature = "debug")]
+pub fn trace<T>(
+    client: &ValidatorClient<T>,
+    state_root: &str,
+    block_num: u64,
+    chain_id: u64,
+    earlier: Vec<(H160, Option<Call>)>,
+    call: Call,
+    options: TraceOptions,
+) -> Result<(Result<Executed, CallError>, Vec<StructLog>), CallError>
+where
+    T: MessageSender,
+{
+    let backend = StateBackend::new(client, state_root, block_num, chain_id, &call);
+    let mut overlay = Overlay::new(&backend, Fork::Istanbul);
+    for (from, earlier) in earlier {
+        match earlier {
+            // Whether earlier transactions succeeded only matters to what they changed
+            Some(earlier) => {
+                let _ = overlay.apply_call(earlier);
+            }
+            None => overlay.increment_nonce(from),
+        }
+    }
+    overlay.origin = call.from;
+    let ((reason, output, gas_used), logs) =
+        tracer::trace(options, &client.memory, || execute(&mut overlay, call));
+    if let Some(error) = backend.error.into_inner() {
+        return Err(CallError::State(error));
+    }
+    Ok((result(reason, output, gas_used), logs))
+}
+
+/// Runs the transactions of a block one after another against the state before it
+/// with the rules of `fork`, as `trace` runs those before the one it traces, and
+/// returns how each call went along with the logs it emitted, or none for those
+/// that don't run in the EVM
+#[cfg(feature = "debug")]
+pub fn replay<T>(
+    client: &ValidatorClient<T>,
+    state_root: &str,
+    block_num: u64,
+    chain_id: u64,
+    txns: Vec<(H160, Option<Call>)>,
+    fork: Fork,
+) -> Result<Vec<Option<Outcome>>, CallError>
+where
+    T: MessageSender,
+{
+    let first = match txns.iter().find_map(|(_, call)| call.as_ref()) {
+        Some(first) => first,
+        None => return Ok(txns.iter().map(|_| None).collect()),
+    };
+    let backend = StateBackend::new(client, state_root, block_num, chain_id, first);
+    let mut overlay = Overlay::new(&backend, fork);
+    let mut outcomes = Vec::with_capacity(txns.len());
+    for (from, call) in txns {
+        match call {
+            Some(call) => outcomes.push(Some(overlay.apply_call(call))),
+            None => {
+                overlay.increment_nonce(from);
+                outcomes.push(None);
+            }
+        }
+    }
+    if let Some(error) = backend.error.into_inner() {
+        return Err(CallError::State(error));
+    }
+    Ok(outcomes)
+}
+
+/// How a traced call went, along with the steps it took
+#[cfg(feature = "debug")]
+type TracedCall = (Outcome, Vec<StructLog>);
+
+/// Runs the transactions of a block one after another against the state before it,
+/// as `replay` does with Istanbul's rules, and returns how each call went along with
+/// the steps it took, or none for those that don't run in the EVM
+#[cfg(feature = "debug")]
+pub fn trace_block<T>(
+    client: &ValidatorClient<T>,
+    state_root: &str,
+    block_num: u64,
+    chain_id: u64,
+    txns: Vec<(H160, Option<Call>)>,
+    options: TraceOptions,
+) -> Result<Vec<Option<TracedCall>>, CallError>
+where
+    T: MessageSender,
+{
+    let first = match txns.iter().find_map(|(_, call)| call.as_ref()) {
+        Some(first) => first,
+        None => return Ok(txns.iter().map(|_| None).collect()),
+    };
+    let backend = StateBackend::new(client, state_root, block_num, chain_id, first);
+    let mut overlay = Overlay::new(&backend, Fork::Istanbul);
+    let mut traces = Vec::with_capacity(txns.len());
+    for (from, call) in txns {
+        match call {
+            Some(call) => traces.push(Some(tracer::trace(options, &client.memory, || {
+                overlay.apply_call(call)
+            }))),
+            None => {
+                overlay.increment_nonce(from);
+                traces.push(None);
+            }
+        }
+    }
+    if let Some(error) = backend.error.into_inner() {
+        return Err(CallError::State(error));
+    }
+    Ok(traces)
+}
+
+// A call that failed to read state may have gone another way than it should have,
+// so callers check for that before using the result
+pub fn result(reason: ExitReason, output: Vec<u8>, gas_used: u64) -> Result<Executed, CallError> {
+    match reason {
+        ExitReason::Succeed(_) => Ok(Executed { output, gas_used }),
+        ExitReason::Revert(_) => Err(CallError::Reverted(output)),
+        ExitReason::Error(ExitError::OutOfGas) => Err(CallError::OutOfGas),
+        ExitReason::Error(error) => Err(CallError::Failed(format!("{:?}", error))),
+        ExitReason::Fatal(error) => Err(CallError::Failed(format!("{:?}", error))),
+    }
+}
+
+/// Reads the accounts and storage a call touches from the state of a block. Each
+/// account is read with its storage in one request. When a contract has more storage
+/// than that reads, the slots its code reads at constant keys are read ahead, along
+/// with the accounts at addresses in its code, all at once rather than each as the
+/// call gets to it. Reads can't fail here, so the first error is kept to fail the
+/// call with once it returns.
+struct StateBackend<'a, T: MessageSender + 'a> {
+    client: &'a ValidatorClient<T>,
+    state_root: String,
+    block_num: u64,
+    chain_id: u64,
+    gas_limit: u64,
+    origin: H160,
+    timestamp: RefCell<Option<u64>>,
+    accounts: RefCell<HashMap<H160, Option<EvmStateAccount>>>,
+    storage: RefCell<HashMap<(H160, H256), H256>>,
+    /// The accounts whose storage was all read along with them
+    complete_storage: RefCell<HashSet<H160>>,
+    /// The slots kept in the entries of accounts whose storage wasn't all read, by
+    /// contracts deployed before storage was split out
+    account_storage: RefCell<HashMap<H160, HashMap<H256, H256>>>,
+    /// The storage generations of the accounts whose storage wasn't all read, which
+    /// the entries of their slots must have
+    storage_generations: RefCell<HashMap<H160, u64>>,
+    /// What the call has read, if it is being recorded
+    #[cfg(feature = "debug")]
+    witness: Option<RefCell<Witness>>,
+    error: RefCell<Option<String>>,
+}
+
+impl<'a, T> StateBackend<'a, T>
+where
+    T: MessageSender,
+{
+    /// Creates the backend of a call, reading the accounts and slots it is expected
+    /// to touch
+    fn new(
+        client: &'a ValidatorClient<T>,
+        state_root: &str,
+        block_num: u64,
+        chain_id: u64,
+        call: &Call,
+    ) -> Self {
+        let backend = StateBackend {
+            client,
+            state_root: String::from(state_root),
+            block_num,
+            chain_id,
+            gas_limit: call.gas,
+            origin: call.from,
+            timestamp: RefCell::new(None),
+            accounts: RefCell::new(HashMap::new()),
+            storage: RefCell::new(HashMap::new()),
+            complete_storage: RefCell::new(HashSet::new()),
+            account_storage: RefCell::new(HashMap::new()),
+            storage_generations: RefCell::new(HashMap::new()),
+            #[cfg(feature = "debug")]
+            witness: None,
+            error: RefCell::new(None),
+        };
+
+        let mut accounts = vec![call.from];
+        accounts.extend(call.to);
+        accounts.extend(call.access_list.iter().map(|&(address, _)| address));
+        backend.load_accounts(&accounts, true);
+        for &(address, ref keys) in &call.access_list {
+            backend.read_storage(address, keys);
+        }
+        backend
+    }
+
+    fn block_key(&self) -> BlockKey {
+        BlockKey::StateRoot(self.state_root.clone())
+    }
+
+    fn fail(&self, error: String) {
+        let mut first = self.error.borrow_mut();
+        if first.is_none() {
+            *first = Some(error);
+        }
+    }
+
+    fn account(&self, address: H160) -> Option<EvmStateAccount> {
+        let cached = self.accounts.borrow().get(&address).cloned();
+        let account = cached.unwrap_or_else(|| {
+            self.load_accounts(&[address], true);
+            self.accounts.borrow().get(&address).cloned().flatten()
+        });
+        #[cfg(feature = "debug")]
+        self.record(|witness| {
+            witness.accounts.entry(address).or_insert_with(|| {
+                account.as_ref().map(|account| WitnessAccount {
+                    balance: balance(account),
+                    nonce: U256::from(account.nonce),
+                    code: account.code.clone(),
+                })
+            });
+        });
+        account
+    }
+
+    /// Adds to the witness of the call, if it is being recorded
+    #[cfg(feature = "debug")]
+    fn record<F: FnOnce(&mut Witness)>(&self, add: F) {
+        if let Some(ref witness) = self.witness {
+            add(&mut witness.borrow_mut());
+        }
+    }
+
+    /// Reads the accounts that haven't been yet, and if `predict` is set, reads ahead
+    /// what their code is expected to read
+    fn load_accounts(&self, addresses: &[H160], predict: bool) {
+        let mut addresses: Vec<H160> = addresses
+            .iter()
+            .filter(|address| !self.accounts.borrow().contains_key(address))
+            .cloned()
+            .collect();
+        addresses.sort();
+        addresses.dedup();
+        if addresses.is_empty() {
+            return;
+        }
+
+        let hex_addresses: Vec<String> = addresses
+            .iter()
+            .map(|address| transform::bytes_to_hex_str(address.as_bytes()))
+            .collect();
+        let states =
+            self.client
+                .get_account_states(&hex_addresses, self.block_key(), ACCOUNT_STATE_LIMIT);
+
+        let mut predicted_accounts = Vec::new();
+        let mut predicted_slots = Vec::new();
+        for (address, state) in addresses.into_iter().zip(states) {
+            let state = match state {
+                Ok(state) => state,
+                Err(error) => {
+                    self.fail(error);
+                    self.accounts.borrow_mut().insert(address, None);
+                    continue;
+                }
+            };
+
+            let mut storage = self.storage.borrow_mut();
+            for (key, value) in state.storage {
+                storage.insert((address, H256::from_slice(&key)), word(&value));
+            }
+            let account_storage = state
+                .account_storage
+                .into_iter()
+                .map(|(key, value)| (H256::from_slice(&key), word(&value)));
+            if state.complete {
+                for (key, value) in account_storage {
+                    storage.entry((address, key)).or_insert(value);
+                }
+                self.complete_storage.borrow_mut().insert(address);
+            } else {
+                self.account_storage
+                    .borrow_mut()
+                    .insert(address, account_storage.collect());
+                self.storage_generations
+                    .borrow_mut()
+                    .insert(address, state.storage_generation);
+            }
+
+            if let (true, Some(account)) = (predict, state.account.as_ref()) {
+                let (accounts, slots) = predict_reads(&account.code);
+                predicted_accounts.extend(accounts);
+                if !state.complete {
+                    predicted_slots.push((address, slots));
+                }
+            }
+            self.accounts.borrow_mut().insert(address, state.account);
+        }
+
+        self.load_accounts(&predicted_accounts, false);
+        for (address, slots) in predicted_slots {
+            self.read_storage(address, &slots);
+        }
+    }
+
+    /// Reads the storage slots of an account that haven't been yet, all at once
+    fn read_storage(&self, address: H160, keys: &[H256]) {
+        self.load_accounts(&[address], false);
+        if self.complete_storage.borrow().contains(&address) {
+            return;
+        }
+        let mut keys: Vec<H256> = keys
+            .iter()
+            .filter(|key| !self.storage.borrow().contains_key(&(address, **key)))
+            .cloned()
+            .collect();
+        keys.sort();
+        keys.dedup();
+        if keys.is_empty() {
+            return;
+        }
+
+        let generation = self
+            .storage_generations
+            .borrow()
+            .get(&address)
+            .cloned()
+            .unwrap_or_default();
+        let values = self.client.get_storage_slots(
+            &transform::bytes_to_hex_str(address.as_bytes()),
+            &keys
+                .iter()
+                .map(|key| key.as_bytes().to_vec())
+                .collect::<Vec<_>>(),
+            generation,
+            &self.state_root,
+        );
+        let account_storage = self.account_storage.borrow();
+        let mut storage = self.storage.borrow_mut();
+        for (key, value) in keys.into_iter().zip(values) {
+            let value = match value {
+                Ok(Some(value)) => word(&value),
+                Ok(None) => account_storage
+                    .get(&address)
+                    .and_then(|slots| slots.get(&key))
+                    .cloned()
+                    .unwrap_or_default(),
+                Err(error) => {
+                    self.fail(error);
+                    H256::zero()
+                }
+            };
+            storage.insert((address, key), value);
+        }
+    }
+
+    fn read_timestamp(&self) -> Result<u64, String> {
+        read_timestamp(self.client, &self.state_root)
+    }
+}
+
+/// Reads the time of the latest block whose info is in a state, which is what the
+/// transaction processor uses for `TIMESTAMP`
+pub fn read_timestamp<T>(client: &ValidatorClient<T>, state_root: &str) -> Result<u64, String>
+where
+    T: MessageSender,
+{
+    let config: Option<BlockInfoConfig> =
+        read_block_info(client, state_root, BLOCK_INFO_CONFIG_ADDRESS)?;
+    let config = match config {
+        Some(config) => config,
+        None => return Ok(0),
+    };
+    let address = format!("{}{:062x}", BLOCK_INFO_NAMESPACE, config.latest_block);
+    let info: Option<BlockInfo> = read_block_info(client, state_root, &address)?;
+    Ok(info.map(|info| info.timestamp).unwrap_or(0))
+}
+
+fn read_block_info<T, M>(
+    client: &ValidatorClient<T>,
+    state_root: &str,
+    address: &str,
+) -> Result<Option<M>, String>
+where
+    T: MessageSender,
+    M: protobuf::Message,
+{
+    match client.get_state_entry(address, Some(String::from(state_root)))? {
+        Some(data) => protobuf::parse_from_bytes(&data)
+            .map(Some)
+            .map_err(|error| format!("Failed to deserialize block info: {:?}", error)),
+        None => Ok(None),
+    }
+}
+
+impl<'a, T> Backend for StateBackend<'a, T>
+where
+    T: MessageSender,
+{
+    fn gas_price(&self) -> U256 {
+        U256::zero()
+    }
+
+    fn origin(&self) -> H160 {
+        self.origin
+    }
+
+    // Block hashes aren't kept in state, so `BLOCKHASH` is always zero
+    fn block_hash(&self, _number: U256) -> H256 {
+        H256::zero()
+    }
+
+    fn block_number(&self) -> U256 {
+        U256::from(self.block_num)
+    }
+
+    fn block_coinbase(&self) -> H160 {
+        H160::zero()
+    }
+
+    fn block_timestamp(&self) -> U256 {
+        if let Some(timestamp) = *self.timestamp.borrow() {
+            return U256::from(timestamp);
+        }
+        let timestamp = self.read_timestamp().unwrap_or_else(|error| {
+            self.fail(error);
+            0
+        });
+        *self.timestamp.borrow_mut() = Some(timestamp);
+        #[cfg(feature = "debug")]
+        self.record(|witness| witness.timestamp = Some(timestamp));
+        U256::from(timestamp)
+    }
+
+    fn block_difficulty(&self) -> U256 {
+        U256::zero()
+    }
+
+    fn block_randomness(&self) -> Option<H256> {
+        None
+    }
+
+    fn block_gas_limit(&self) -> U256 {
+        U256::from(self.gas_limit)
+    }
+
+    fn block_base_fee_per_gas(&self) -> U256 {
+        U256::zero()
+    }
+
+    fn chain_id(&self) -> U256 {
+        U256::from(self.chain_id)
+    }
+
+    fn exists(&self, address: H160) -> bool {
+        self.account(address).is_some()
+    }
+
+    fn basic(&self, address: H160) -> Basic {
+        match self.account(address) {
+            Some(account) => Basic {
+                balance: balance(&account),
+                nonce: U256::from(account.nonce),
+            },
+            None => Basic::default(),
+        }
+    }
+
+    fn code(&self, address: H160) -> Vec<u8> {
+        self.account(address)
+            .map(|mut account| account.take_code())
+            .unwrap_or_default()
+    }
+
+    fn storage(&self, address: H160, index: H256) -> H256 {
+        let cached = self.storage.borrow().get(&(address, index)).cloned();
+        let value = cached.unwrap_or_else(|| {
+            self.read_storage(address, &[index]);
+            self.storage
+                .borrow()
+                .get(&(address, index))
+                .cloned()
+                .unwrap_or_default()
+        });
+        #[cfg(feature = "debug")]
+        self.record(|witness| {
+            witness.storage.insert((address, index), value);
+        });
+        value
+    }
+
+    fn original_storage(&self, address: H160, index: H256) -> Option<H256> {
+        Some(self.storage(address, index))
+    }
+}
+
+/// The state of another backend with the changes of calls run on it applied over
+/// it, so that calls can be run one after another like the transactions of a block
+#[cfg(feature = "debug")]
+struct Overlay<'a, B: Backend + 'a> {
+    backend: &'a B,
+    fork: Fork,
+    origin: H160,
+    /// The accounts changed, or none for those deleted
+    accounts: HashMap<H160, Option<(Basic, Vec<u8>)>>,
+    storage: HashMap<(H160, H256), H256>,
+    /// The accounts whose storage was wiped, which isn't read from `backend` again
+    cleared: HashSet<H160>,
+}
+
+#[cfg(feature = "debug")]
+impl<'a, B: Backend> Overlay<'a, B> {
+    fn new(backend: &'a B, fork: Fork) -> Self {
+        Overlay {
+            backend,
+            fork,
+            origin: backend.origin(),
+            accounts: HashMap::new(),
+            storage: HashMap::new(),
+            cleared: HashSet::new(),
+        }
+    }
+
+    /// Runs a call, keeping what it changed whether it succeeded or not, as a
+    /// committed transaction does, and returns how it went with the logs it emitted
+    fn apply_call(&mut self, call: Call) -> Outcome {
+        self.origin = call.from;
+        let fork = self.fork;
+        let (reason, output, gas_used, (changes, logs)) = run(self, fork, call, |state| {
+            let (changes, logs) = state.deconstruct();
+            let changes = changes
+                .into_iter()
+                .map(|change| match change {
+                    Apply::Modify {
+                        address,
+                        basic,
+                        code,
+                        storage,
+                        reset_storage,
+                    } => Apply::Modify {
+                        address,
+                        basic,
+                        code,
+                        storage: storage.into_iter().collect::<Vec<(H256, H256)>>(),
+                        reset_storage,
+                    },
+                    Apply::Delete { address } => Apply::Delete { address },
+                })
+                .collect::<Vec<_>>();
+            (changes, logs.into_iter().collect::<Vec<_>>())
+        });
+        for change in changes {
+            match change {
+                Apply::Modify {
+                    address,
+                    basic,
+                    code,
+                    storage,
+                    reset_storage,
+                } => {
+                    let code = code.unwrap_or_else(|| self.code(address));
+                    if reset_storage {
+                        self.clear_storage(address);
+                    }
+                    self.accounts.insert(address, Some((basic, code)));
+                    for (index, value) in storage {
+                        self.storage.insert((address, index), value);
+                    }
+                }
+                Apply::Delete { address } => {
+                    self.clear_storage(address);
+                    self.accounts.insert(address, None);
+                }
+            }
+        }
+        (result(reason, output, gas_used), logs)
+    }
+
+    /// Counts a transaction that doesn't run in the EVM against its sender
+    fn increment_nonce(&mut self, address: H160) {
+        let mut basic = self.basic(address);
+        basic.nonce += U256::one();
+        let code = self.code(address);
+        self.accounts.insert(address, Some((basic, code)));
+    }
+
+    fn clear_storage(&mut self, address: H160) {
+        self.storage
+            .retain(|&(slot_address, _), _| slot_address != address);
+        self.cleared.insert(address);
+    }
+}
+
+#[cfg(feature = "debug")]
+impl<'a, B: Backend> Backend for Overlay<'a, B> {
+    fn gas_price(&self) -> U256 {
+        self.backend.gas_price()
+    }
+
+    fn origin(&self) -> H160 {
+        self.origin
+    }
+
+    fn block_hash(&self, number: U256) -> H256 {
+        self.backend.block_hash(number)
+    }
+
+    fn block_number(&self) -> U256 {
+        self.backend.block_number()
+    }
+
+    fn block_coinbase(&self) -> H160 {
+        self.backend.block_coinbase()
+    }
+
+    fn block_timestamp(&self) -> U256 {
+        self.backend.block_timestamp()
+    }
+
+    fn block_difficulty(&self) -> U256 {
+        self.backend.block_difficulty()
+    }
+
+    fn block_randomness(&self) -> Option<H256> {
+        self.backend.block_randomness()
+    }
+
+    fn block_gas_limit(&self) -> U256 {
+        self.backend.block_gas_limit()
+    }
+
+    fn block_base_fee_per_gas(&self) -> U256 {
+        self.backend.block_base_fee_per_gas()
+    }
+
+    fn chain_id(&self) -> U256 {
+        self.backend.chain_id()
+    }
+
+    fn exists(&self, address: H160) -> bool {
+        match self.accounts.get(&address) {
+            Some(account) => account.is_some(),
+            None => self.backend.exists(address),
+        }
+    }
+
+    fn basic(&self, address: H160) -> Basic {
+        match self.accounts.get(&address) {
+            Some(Some((basic, _))) => basic.clone(),
+            Some(None) => Basic::default(),
+            None => self.backend.basic(address),
+        }
+    }
+
+    fn code(&self, address: H160) -> Vec<u8> {
+        match self.accounts.get(&address) {
+            Some(Some((_, code))) => code.clone(),
+            Some(None) => Vec::new(),
+            None => self.backend.code(address),
+        }
+    }
+
+    fn storage(&self, address: H160, index: H256) -> H256 {
+        match self.storage.get(&(address, index)) {
+            Some(value) => *value,
+            None if self.cleared.contains(&address) => H256::zero(),
+            None => self.backend.storage(address, index),
+        }
+    }
+
+    fn original_storage(&self, address: H160, index: H256) -> Option<H256> {
+        Some(self.storage(address, index))
+    }
+}
+
+/// Seth balances are signed, and accounts can't hold less than nothing
+pub fn balance(account: &EvmStateAccount) -> U256 {
+    U256::from(account.balance.max(0) as u64)
+}
+
+/// Guesses which accounts and storage slots code reads from the constants it pushes:
+/// addresses are pushed whole, and the slots of fixed-size state variables are
+/// pushed right before they are loaded. Slots of mappings and arrays are hashes
+/// computed while running, so they can't be told.
+fn predict_reads(code: &[u8]) -> (Vec<H160>, Vec<H256>) {
+    let mut accounts = Vec::new();
+    let mut slots = Vec::new();
+    let mut pc = 0;
+    while pc < code.len() {
+        let opcode = code[pc];
+        if !(PUSH1..=PUSH32).contains(&opcode) {
+            pc += 1;
+            continue;
+        }
+        let size = (opcode - PUSH1 + 1) as usize;
+        let data = &code[(pc + 1).min(code.len())..(pc + 1 + size).min(code.len())];
+        pc += 1 + size;
+
+        if opcode == PUSH20 && data.len() == 20 && accounts.len() < MAX_PREDICTED_ACCOUNTS {
+            accounts.push(H160::from_slice(data));
+        }
+        if code.get(pc) == Some(&SLOAD) && slots.len() < MAX_PREDICTED_SLOTS {
+            slots.push(word(data));
+        }
+    }
+    (accounts, slots)
+}
+
+/// Left pads a storage value to a word, treating values longer than one as unset
+fn word(value: &[u8]) -> H256 {
+    if value.len() > 32 {
+        return H256::zero();
+    }
+    let mut word = [0; 32];
+    word[32 - value.len()..].copy_from_slice(value);
+    H256::from(word)
+}
+
+/// The precompiled contracts of the transaction processor that can be run here.
+/// `ecrecover` isn't one of them, as seth accounts don't have Ethereum addresses.
+fn precompiles() -> BTreeMap<H160, PrecompileFn> {
+    let mut precompiles: BTreeMap<H160, PrecompileFn> = BTreeMap::new();
+    precompiles.insert(H160::from_low_u64_be(2), sha256);
+    precompiles.insert(H160::from_low_u64_be(3), ripemd160);
+    precompiles.insert(H160::from_low_u64_be(4), identity);
+    precompiles
+}
+
+/// Charges the gas of a precompile, a base cost plus a cost per word of input
+fn charge(
+    input: &[u8],
+    gas_limit: Option<u64>,
+    base: u64,
+    per_word: u64,
+) -> Result<u64, PrecompileFailure> {
+    let cost = base + per_word * (input.len() as u64).div_ceil(32);
+    match gas_limit {
+        Some(limit) if cost > limit => Err(ExitError::OutOfGas.into()),
+        _ => Ok(cost),
+    }
+}
+
+fn returned(output: Vec<u8>, cost: u64) -> Result<(PrecompileOutput, u64), PrecompileFailure> {
+    Ok((
+        PrecompileOutput {
+            exit_status: ExitSucceed::Returned,
+            output,
+        },
+        cost,
+    ))
+}
+
+fn sha256(
+    input: &[u8],
+    gas_limit: Option<u64>,
+    _context: &Context,
+    _is_static: bool,
+) -> Result<(PrecompileOutput, u64), PrecompileFailure> {
+    let cost = charge(input, gas_limit, 60, 12)?;
+    let mut sha = Sha256::new();
+    sha.input(input);
+    let mut hash = vec![0; 32];
+    sha.result(&mut hash);
+    returned(hash, cost)
+}
+
+fn ripemd160(
+    input: &[u8],
+    gas_limit: Option<u64>,
+    _context: &Context,
+    _is_static: bool,
+) -> Result<(PrecompileOutput, u64), PrecompileFailure> {
+    let cost = charge(input, gas_limit, 600, 120)?;
+    let mut ripemd = Ripemd160::new();
+    ripemd.input(input);
+    // The hash is left padded to a word
+    let mut hash = vec![0; 32];
+    ripemd.result(&mut hash[12..]);
+    returned(hash, cost)
+}
+
+fn identity(
+    input: &[u8],
+    gas_limit: Option<u64>,
+    _context: &Context,
+    _is_static: bool,
+) -> Result<(PrecompileOutput, u64), PrecompileFailure> {
+    let cost = charge(input, gas_limit, 15, 3)?;
+    returned(input.to_vec(), cost)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn precompiles_pad_hashes_and_charge_gas() {
+        let context = Context {
+            address: H160::zero(),
+            caller: H160::zero(),
+            apparent_value: U256::zero(),
+        };
+        let (output, cost) = ripemd160(b"", None, &context, true).unwrap();
+        assert_eq!(
+            transform::bytes_to_hex_str(&output.output),
+            "0000000000000000000000009c1185a5c5e9fc54612808977ee8f548b2258d31"
+        );
+        assert_eq!(cost, 600);
+
+        // 33 bytes are two words
+        assert!(identity(&[0; 33], Some(21), &context, true).is_ok());
+        assert!(identity(&[0; 33], Some(20), &context, true).is_err());
+    }
+
+    #[test]
+    fn predicts_constant_reads() {
+        // PUSH1 0x05 SLOAD PUSH20 <address> EXTCODESIZE PUSH1 0x01 PUSH1 0x02 SSTORE,
+        // then a truncated PUSH32
+        let mut code = vec![PUSH1, 0x05, SLOAD, PUSH20];
+        code.extend_from_slice(&[0xab; 20]);
+        code.extend_from_slice(&[0x3b, PUSH1, 0x01, PUSH1, 0x02, 0x55, PUSH32, 0x54]);
+
+        let (accounts, slots) = predict_reads(&code);
+        assert_eq!(accounts, vec![H160::from_slice(&[0xab; 20])]);
+        assert_eq!(slots, vec![H256::from_low_u64_be(5)]);
+    }
+
+    #[test]
+    fn storage_values_are_padded_to_words() {
+        assert_eq!(word(&[1, 2]), H256::from_low_u64_be(0x0102));
+        assert_eq!(word(&[]), H256::zero());
+        assert_eq!(word(&[1; 33]), H256::zero());
+    }
+}
@@ -0,0 +1,422 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! The state a call read, recorded by `vm::call_with_witness`, which the call can be
+//! run against again later without the validator.
+
+use evm::backend::{Backend, Basic};
+use primitive_types::{H160, H256, U256};
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use transform;
+use vm::{self, Call, CallError, Executed};
+
+/// The state a call read: each account and storage slot, and the block's time if
+/// the call used it
+#[derive(Debug, Default, PartialEq)]
+pub struct Witness {
+    pub block_num: u64,
+    pub chain_id: u64,
+    pub timestamp: Option<u64>,
+    /// The accounts read, or none for those that don't exist
+    pub accounts: BTreeMap<H160, Option<WitnessAccount>>,
+    pub storage: BTreeMap<(H160, H256), H256>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WitnessAccount {
+    pub balance: U256,
+    pub nonce: U256,
+    pub code: Vec<u8>,
+}
+
+impl Witness {
+    /// Encodes the witness as an object holding `blockNumber`, `chainId`, an optional
+    /// `timestamp`, `accounts` by address, and `storage` by address and then key
+    pub fn to_value(&self) -> Value {
+        let mut accounts = Map::new();
+        for (address, account) in &self.accounts {
+            let account = match *account {
+                Some(ref account) => {
+                    let mut fields = Map::new();
+                    fields.insert(
+                        String::from("balance"),
+                        transform::num_to_hex(&account.balance),
+                    );
+                    fields.insert(String::from("nonce"), transform::num_to_hex(&account.nonce));
+                    fields.insert(
+                        String::from("code"),
+                        transform::hex_prefix(&transform::bytes_to_hex_str(&account.code)),
+                    );
+                    Value::Object(fields)
+                }
+                None => Value::Null,
+            };
+            accounts.insert(format!("{:?}", address), account);
+        }
+
+        let mut storage = Map::new();
+        for (&(address, key), value) in &self.storage {
+            let slots = storage
+                .entry(format!("{:?}", address))
+                .or_insert_with(|| Value::Object(Map::new()));
+            if let Value::Object(ref mut slots) = *slots {
+                slots.insert(format!("{:?}", key), Value::String(format!("{:?}", value)));
+            }
+        }
+
+        let mut witness = Map::new();
+        witness.insert(
+            String::from("blockNumber"),
+            transform::num_to_hex(&self.block_num),
+        );
+        witness.insert(
+            String::from("chainId"),
+            transform::num_to_hex(&self.chain_id),
+        );
+        if let Some(timestamp) = self.timestamp {
+            witness.insert(String::from("timestamp"), transform::num_to_hex(&timestamp));
+        }
+        witness.insert(String::from("accounts"), Value::Object(accounts));
+        witness.insert(String::from("storage"), Value::Object(storage));
+        Value::Object(witness)
+    }
+
+    /// Encodes what the call read as geth's prestate tracer does: an object of the
+    /// accounts by address, each with its `balance` and, unless they're zero or empty,
+    /// its `nonce`, `code` and the `storage` slots read. Accounts that don't exist
+    /// have a zero balance.
+    pub fn to_prestate(&self) -> Value {
+        let mut prestate = Map::new();
+        for (address, account) in &self.accounts {
+            let mut fields = Map::new();
+            let account = account.clone().unwrap_or(WitnessAccount {
+                balance: U256::zero(),
+                nonce: U256::zero(),
+                code: Vec::new(),
+            });
+            fields.insert(
+                String::from("balance"),
+                transform::num_to_hex(&account.balance),
+            );
+            if !account.nonce.is_zero() {
+                fields.insert(String::from("nonce"), Value::from(account.nonce.low_u64()));
+            }
+            if !account.code.is_empty() {
+                fields.insert(
+                    String::from("code"),
+                    transform::hex_prefix(&transform::bytes_to_hex_str(&account.code)),
+                );
+            }
+            prestate.insert(format!("{:?}", address), Value::Object(fields));
+        }
+
+        for (&(address, key), value) in &self.storage {
+            let fields = prestate
+                .entry(format!("{:?}", address))
+                .or_insert_with(|| Value::Object(Map::new()));
+            if let Value::Object(ref mut fields) = *fields {
+                let slots = fields
+                    .entry(String::from("storage"))
+                    .or_insert_with(|| Value::Object(Map::new()));
+                if let Value::Object(ref mut slots) = *slots {
+                    slots.insert(format!("{:?}", key), Value::String(format!("{:?}", value)));
+                }
+            }
+        }
+        Value::Object(prestate)
+    }
+
+    pub fn from_value(value: &Value) -> Result<Self, String> {
+        let witness = value
+            .as_object()
+            .ok_or_else(|| String::from("Witness must be an object"))?;
+        let mut accounts = BTreeMap::new();
+        for (address, account) in get_object(witness, "accounts")? {
+            let account = match *account {
+                Value::Null => None,
+                Value::Object(ref account) => Some(WitnessAccount {
+                    balance: get_quantity(account, "balance")?,
+                    nonce: get_quantity(account, "nonce")?,
+                    code: get_data(account, "code")?,
+                }),
+                _ => return Err(format!("Account `{}` must be an object or null", address)),
+            };
+            accounts.insert(parse_hex(address)?, account);
+        }
+
+        let mut storage = BTreeMap::new();
+        for (address, slots) in get_object(witness, "storage")? {
+            let address = parse_hex(address)?;
+            let slots = slots
+                .as_object()
+                .ok_or_else(|| format!("Storage of `{:?}` must be an object", address))?;
+            for (key, value) in slots {
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| format!("Slot `{}` must be a string", key))?;
+                storage.insert((address, parse_hex(key)?), parse_hex(value)?);
+            }
+        }
+
+        Ok(Witness {
+            block_num: get_quantity(witness, "blockNumber")?.low_u64(),
+            chain_id: get_quantity(witness, "chainId")?.low_u64(),
+            timestamp: match witness.get("timestamp") {
+                Some(_) => Some(get_quantity(witness, "timestamp")?.low_u64()),
+                None => None,
+            },
+            accounts,
+            storage,
+        })
+    }
+}
+
+fn get_object<'a>(
+    map: &'a Map<String, Value>,
+    key: &str,
+) -> Result<&'a Map<String, Value>, String> {
+    map.get(key)
+        .and_then(Value::as_object)
+        .ok_or_else(|| format!("Witness has no `{}` object", key))
+}
+
+fn get_quantity(map: &Map<String, Value>, key: &str) -> Result<U256, String> {
+    map.get(key)
+        .and_then(Value::as_str)
+        .and_then(|value| value.get(2..))
+        .and_then(|value| U256::from_str_radix(value, 16).ok())
+        .ok_or_else(|| format!("`{}` must be a quantity", key))
+}
+
+fn get_data(map: &Map<String, Value>, key: &str) -> Result<Vec<u8>, String> {
+    map.get(key)
+        .and_then(Value::as_str)
+        .and_then(|value| transform::hex_str_to_bytes(value.trim_start_matches("0x")))
+        .ok_or_else(|| format!("`{}` must be data", key))
+}
+
+/// Parses an address or word, which has to be its full size
+fn parse_hex<H: FromStr>(value: &str) -> Result<H, String> {
+    value
+        .get(2..)
+        .and_then(|hex| H::from_str(hex).ok())
+        .ok_or_else(|| format!("`{}` is invalid", value))
+}
+
+/// Runs a call against the state a witness holds. The call fails with
+/// `CallError::State` if it reads what isn't in the witness, which happens if the
+/// call isn't the one the witness was recorded for.
+pub fn replay(witness: &Witness, call: Call) -> Result<Executed, CallError> {
+    let mut backend = WitnessBackend {
+        witness,
+        gas_limit: call.gas,
+        origin: call.from,
+        error: RefCell::new(None),
+    };
+    let (reason, output, gas_used) = vm::execute(&mut backend, call);
+    if let Some(error) = backend.error.into_inner() {
+        return Err(CallError::State(error));
+    }
+    vm::result(reason, output, gas_used)
+}
+
+/// Reads the state of a call from a witness, keeping the first read of what isn't in
+/// it to fail the call with
+struct WitnessBackend<'a> {
+    witness: &'a Witness,
+    gas_limit: u64,
+    origin: H160,
+    error: RefCell<Option<String>>,
+}
+
+impl<'a> WitnessBackend<'a> {
+    fn missing(&self, what: String) {
+        let mut first = self.error.borrow_mut();
+        if first.is_none() {
+            *first = Some(format!("{} is not in the witness", what));
+        }
+    }
+
+    fn account(&self, address: H160) -> Option<&'a WitnessAccount> {
+        match self.witness.accounts.get(&address) {
+            Some(account) => account.as_ref(),
+            None => {
+                self.missing(format!("Account {:?}", address));
+                None
+            }
+        }
+    }
+}
+
+impl<'a> Backend for WitnessBackend<'a> {
+    fn gas_price(&self) -> U256 {
+        U256::zero()
+    }
+
+    fn origin(&self) -> H160 {
+        self.origin
+    }
+
+    fn block_hash(&self, _number: U256) -> H256 {
+        H256::zero()
+    }
+
+    fn block_number(&self) -> U256 {
+        U256::from(self.witness.block_num)
+    }
+
+    fn block_coinbase(&self) -> H160 {
+        H160::zero()
+    }
+
+    fn block_timestamp(&self) -> U256 {
+        U256::from(self.witness.timestamp.unwrap_or_else(|| {
+            self.missing(String::from("The block's timestamp"));
+            0
+        }))
+    }
+
+    fn block_difficulty(&self) -> U256 {
+        U256::zero()
+    }
+
+    fn block_randomness(&self) -> Option<H256> {
+        None
+    }
+
+    fn block_gas_limit(&self) -> U256 {
+        U256::from(self.gas_limit)
+    }
+
+    fn block_base_fee_per_gas(&self) -> U256 {
+        U256::zero()
+    }
+
+    fn chain_id(&self) -> U256 {
+        U256::from(self.witness.chain_id)
+    }
+
+    fn exists(&self, address: H160) -> bool {
+        self.account(address).is_some()
+    }
+
+    fn basic(&self, address: H160) -> Basic {
+        match self.account(address) {
+            Some(account) => Basic {
+                balance: account.balance,
+                nonce: account.nonce,
+            },
+            None => Basic::default(),
+        }
+    }
+
+    fn code(&self, address: H160) -> Vec<u8> {
+        self.account(address)
+            .map(|account| account.code.clone())
+            .unwrap_or_default()
+    }
+
+    fn storage(&self, address: H160, index: H256) -> H256 {
+        match self.witness.storage.get(&(address, index)) {
+            Some(value) => *value,
+            None => {
+                self.missing(format!("Slot {:?} of {:?}", index, address));
+                H256::zero()
+            }
+        }
+    }
+
+    fn original_storage(&self, address: H160, index: H256) -> Option<H256> {
+        Some(self.storage(address, index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn replays_calls_against_witnesses() {
+        // PUSH1 0x07 SLOAD PUSH1 0x00 MSTORE PUSH1 0x20 PUSH1 0x00 RETURN
+        let contract = H160::from_low_u64_be(0xc0de);
+        let mut witness = Witness {
+            block_num: 5,
+            chain_id: 19,
+            ..Witness::default()
+        };
+        witness.accounts.insert(H160::zero(), None);
+        witness.accounts.insert(
+            contract,
+            Some(WitnessAccount {
+                balance: U256::zero(),
+                nonce: U256::one(),
+                code: vec![
+                    0x60, 0x07, 0x54, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3,
+                ],
+            }),
+        );
+        witness.storage.insert(
+            (contract, H256::from_low_u64_be(7)),
+            H256::from_low_u64_be(42),
+        );
+
+        let witness = Witness::from_value(&witness.to_value()).unwrap();
+        assert_eq!(
+            witness.to_prestate(),
+            serde_json::from_str::<Value>(
+                r#"{
+                    "0x0000000000000000000000000000000000000000": {"balance": "0x0"},
+                    "0x000000000000000000000000000000000000c0de": {
+                        "balance": "0x0",
+                        "nonce": 1,
+                        "code": "0x60075460005260206000f3",
+                        "storage": {
+                            "0x0000000000000000000000000000000000000000000000000000000000000007":
+                                "0x000000000000000000000000000000000000000000000000000000000000002a"
+                        }
+                    }
+                }"#
+            )
+            .unwrap()
+        );
+        let call = |to| Call {
+            from: H160::zero(),
+            to: Some(to),
+            value: U256::zero(),
+            data: Vec::new(),
+            gas: 100_000,
+            access_list: Vec::new(),
+        };
+        let executed = replay(&witness, call(contract)).unwrap();
+        assert_eq!(
+            executed.output,
+            H256::from_low_u64_be(42).as_bytes().to_vec()
+        );
+
+        match replay(&witness, call(H160::from_low_u64_be(1))) {
+            Err(CallError::State(_)) => (),
+            result => panic!(
+                "Expected a missing account, got {:?}",
+                result.map(|e| e.output)
+            ),
+        }
+    }
+}
@@ -0,0 +1,374 @@
+# Copyright 2018 Intel Corporation
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+# ------------------------------------------------------------------------------
+
+"""
+Soak test for seth-rpc. Replays a recorded workload against the server from
+several threads while a flaky validator delays and drops its responses and
+restarts now and then. Every request must finish within a deadline, and must
+either return the recorded result or an error; a different result is a wrong
+answer. Once the faults stop, every request of the workload must succeed
+again.
+
+Run with soak_seth_rpc.yaml, which starts seth-rpc next to this script.
+"""
+
+import argparse
+import asyncio
+import json
+import logging
+import os
+import random
+import sys
+import threading
+import time
+
+import requests
+import zmq
+import zmq.asyncio
+
+from rpc_client import RpcClient
+
+from sawtooth_sdk.protobuf.validator_pb2 import Message
+from sawtooth_sdk.protobuf.client_block_pb2 import ClientBlockListResponse
+from sawtooth_sdk.protobuf.client_block_pb2 import ClientBlockGetResponse
+from sawtooth_sdk.protobuf.client_peers_pb2 import ClientPeersGetResponse
+from sawtooth_sdk.protobuf.client_state_pb2 import ClientStateGetRequest
+from sawtooth_sdk.protobuf.client_state_pb2 import ClientStateGetResponse
+from sawtooth_sdk.protobuf.client_receipt_pb2 import ClientReceiptGetResponse
+from sawtooth_sdk.protobuf.transaction_receipt_pb2 import TransactionReceipt
+from sawtooth_sdk.protobuf.block_pb2 import Block
+from sawtooth_sdk.protobuf.block_pb2 import BlockHeader
+from sawtooth_sdk.protobuf.batch_pb2 import Batch
+from sawtooth_sdk.protobuf.transaction_pb2 import Transaction
+from sawtooth_sdk.protobuf.transaction_pb2 import TransactionHeader
+from protobuf.seth_pb2 import SethTransactionReceipt
+from protobuf.seth_pb2 import EvmEntry
+from protobuf.seth_pb2 import EvmStateAccount
+from protobuf.seth_pb2 import EvmStorage
+
+LOGGER = logging.getLogger(__name__)
+
+WORKLOAD = os.path.join(os.path.dirname(__file__), "data", "soak_workload.json")
+
+# The chain the flaky validator serves, which the recorded workload was
+# recorded against
+BLOCK_ID = "f" * 128
+BLOCK_NUM = 123
+PREV_BLOCK_ID = "e" * 128
+STATE_ROOT = "d" * 64
+TXN_ID = "c" * 64
+GAS = 456
+ACCOUNT_ADDRESS = "434d46456b6973a678b77382fca0252629f4389f"
+ACCOUNT_ENTRY = "a68b06" + ACCOUNT_ADDRESS + "0" * 24
+
+# seth-rpc gives up on the validator after 10 seconds, so a request still
+# running after this long is stuck
+REQUEST_DEADLINE = 30
+
+
+class FlakyValidator(object):
+    """
+    Answers the requests seth-rpc sends while serving the workload, delaying
+    each response, dropping some of them and rebinding its socket every so
+    often, as a validator that is overloaded or restarting would.
+    """
+
+    def __init__(self, url, delay, drop_rate, restart_interval, seed):
+        self._url = url
+        self._delay = delay
+        self._drop_rate = drop_rate
+        self._restart_interval = restart_interval
+        self._random = random.Random(seed)
+        self._socket = None
+        self._stopped = threading.Event()
+        self._thread = None
+
+        self.faults = True
+        self.stats = {"answered": 0, "dropped": 0, "restarts": 0}
+
+    def start(self):
+        self._thread = threading.Thread(target=self._run)
+        self._thread.start()
+
+    def stop(self):
+        self._stopped.set()
+        self._thread.join()
+
+    def _run(self):
+        loop = zmq.asyncio.ZMQEventLoop()
+        asyncio.set_event_loop(loop)
+        self._context = zmq.asyncio.Context()
+        self._bind()
+        loop.run_until_complete(self._serve())
+        self._socket.close()
+        self._context.term()
+        loop.close()
+
+    def _bind(self):
+        self._socket = self._context.socket(zmq.ROUTER)
+        self._socket.set(zmq.LINGER, 0)
+        self._socket.bind(self._url)
+
+    async def _serve(self):
+        next_restart = time.time() + self._restart_interval
+        while not self._stopped.is_set():
+            if self.faults and time.time() >= next_restart:
+                LOGGER.info("Restarting validator")
+                self._socket.close()
+                await asyncio.sleep(self._random.uniform(0.5, 2.0))
+                self._bind()
+                self.stats["restarts"] += 1
+                next_restart = time.time() + self._restart_interval
+
+            try:
+                ident, data = await asyncio.wait_for(
+                    self._socket.recv_multipart(), 0.5)
+            except asyncio.TimeoutError:
+                continue
+
+            message = Message()
+            message.ParseFromString(data)
+
+            if self.faults and self._random.random() < self._drop_rate:
+                self.stats["dropped"] += 1
+                continue
+
+            delay = self._random.uniform(0, self._delay) if self.faults else 0
+            asyncio.ensure_future(
+                self._respond_later(self._socket, ident, message, delay))
+
+    async def _respond_later(self, socket, ident, message, delay):
+        await asyncio.sleep(delay)
+        response = self._response(message)
+        if response is None:
+            LOGGER.warning("Unexpected request: %s", message.message_type)
+            return
+
+        message_type, content = response
+        try:
+            await socket.send_multipart([ident, Message(
+                message_type=message_type,
+                content=content.SerializeToString(),
+                correlation_id=message.correlation_id,
+            ).SerializeToString()])
+            self.stats["answered"] += 1
+        except zmq.ZMQError:
+            # The socket was closed by a restart in the meantime
+            pass
+
+    def _response(self, message):
+        if message.message_type == Message.CLIENT_BLOCK_LIST_REQUEST:
+            return Message.CLIENT_BLOCK_LIST_RESPONSE, ClientBlockListResponse(
+                status=ClientBlockListResponse.OK,
+                blocks=[make_block()])
+
+        if message.message_type in (
+                Message.CLIENT_BLOCK_GET_BY_NUM_REQUEST,
+                Message.CLIENT_BLOCK_GET_BY_ID_REQUEST):
+            return Message.CLIENT_BLOCK_GET_RESPONSE, ClientBlockGetResponse(
+                status=ClientBlockGetResponse.OK,
+                block=make_block())
+
+        if message.message_type == Message.CLIENT_RECEIPT_GET_REQUEST:
+            return (
+                Message.CLIENT_RECEIPT_GET_RESPONSE,
+                ClientReceiptGetResponse(
+                    status=ClientReceiptGetResponse.OK,
+                    receipts=[TransactionReceipt(
+                        data=[SethTransactionReceipt(
+                            gas_used=GAS).SerializeToString()],
+                        transaction_id=TXN_ID,
+                    )]))
+
+        if message.message_type == Message.CLIENT_STATE_GET_REQUEST:
+            request = ClientStateGetRequest()
+            request.ParseFromString(message.content)
+            if request.address != ACCOUNT_ENTRY:
+                return (
+                    Message.CLIENT_STATE_GET_RESPONSE,
+                    ClientStateGetResponse(
+                        status=ClientStateGetResponse.NO_RESOURCE))
+            return Message.CLIENT_STATE_GET_RESPONSE, ClientStateGetResponse(
+                status=ClientStateGetResponse.OK,
+                value=EvmEntry(
+                    account=EvmStateAccount(
+                        balance=123,
+                        nonce=456,
+                        code=bytes([0xab, 0xcd, 0xef])),
+                    storage=[EvmStorage(
+                        key=bytes([0x01, 0x23, 0x45]),
+                        value=bytes([0x67, 0x89]))],
+                ).SerializeToString())
+
+        if message.message_type == Message.CLIENT_PEERS_GET_REQUEST:
+            return Message.CLIENT_PEERS_GET_RESPONSE, ClientPeersGetResponse(
+                status=ClientPeersGetResponse.OK,
+                peers=["test_peer"])
+
+        return None
+
+
+def make_block():
+    return Block(
+        header=BlockHeader(
+            block_num=BLOCK_NUM,
+            previous_block_id=PREV_BLOCK_ID,
+            state_root_hash=STATE_ROOT,
+        ).SerializeToString(),
+        header_signature=BLOCK_ID,
+        batches=[Batch(transactions=[Transaction(
+            header=TransactionHeader(
+                family_name="seth",
+            ).SerializeToString(),
+            header_signature=TXN_ID,
+        )])],
+    )
+
+
+def matches(expected, result):
+    """Recorded objects only hold the fields worth checking"""
+    if isinstance(expected, dict) and isinstance(result, dict):
+        return all(
+            key in result and matches(value, result[key])
+            for key, value in expected.items())
+    return expected == result
+
+
+class Replayer(object):
+    """Replays the workload from several threads and tallies the outcomes"""
+
+    def __init__(self, url, workload, threads):
+        self._url = url
+        self._workload = workload
+        self._threads = threads
+        self._lock = threading.Lock()
+        self._running = False
+        self.stats = {"ok": 0, "errors": 0}
+        self.failures = []
+
+    def start(self):
+        self._running = True
+        self._workers = [
+            threading.Thread(target=self._run, args=(seed,))
+            for seed in range(self._threads)]
+        for worker in self._workers:
+            worker.start()
+
+    def stop(self):
+        self._running = False
+        for worker in self._workers:
+            worker.join(REQUEST_DEADLINE * 2)
+            if worker.is_alive():
+                self.failures.append("A worker is still blocked on a request")
+
+    def _run(self, seed):
+        rand = random.Random(seed)
+        request_id = 0
+        while self._running:
+            call = rand.choice(self._workload)
+            request_id += 1
+            self.call(call, request_id)
+
+    def call(self, call, request_id):
+        """Sends one request of the workload, returning whether it succeeded"""
+        start = time.time()
+        try:
+            response = requests.post(self._url, timeout=REQUEST_DEADLINE, json={
+                "jsonrpc": "2.0",
+                "method": call["method"],
+                "params": call["params"],
+                "id": request_id,
+            }).json()
+        except requests.Timeout:
+            self._fail("{} didn't finish within {}s".format(
+                call["method"], REQUEST_DEADLINE))
+            return False
+        except (requests.RequestException, ValueError) as err:
+            self._fail("{} failed: {}".format(call["method"], err))
+            return False
+
+        LOGGER.debug("%s took %.2fs", call["method"], time.time() - start)
+
+        if "error" in response:
+            with self._lock:
+                self.stats["errors"] += 1
+            return False
+
+        if not matches(call["result"], response.get("result")):
+            self._fail("Wrong answer to {}: {}".format(
+                call["method"], response.get("result")))
+            return False
+
+        with self._lock:
+            self.stats["ok"] += 1
+        return True
+
+    def _fail(self, failure):
+        LOGGER.error(failure)
+        with self._lock:
+            self.failures.append(failure)
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.strip())
+    parser.add_argument("--url", default="http://seth-rpc:3030/")
+    parser.add_argument("--bind", default="tcp://eth0:4004")
+    parser.add_argument("--duration", type=int, default=300,
+                        help="seconds to inject faults for")
+    parser.add_argument("--threads", type=int, default=8)
+    parser.add_argument("--delay", type=float, default=2.0,
+                        help="maximum seconds to delay each response by")
+    parser.add_argument("--drop-rate", type=float, default=0.05)
+    parser.add_argument("--restart-interval", type=int, default=60)
+    parser.add_argument("--seed", type=int, default=0)
+    args = parser.parse_args()
+
+    logging.basicConfig(level=logging.INFO)
+
+    with open(WORKLOAD) as workload_file:
+        workload = json.load(workload_file)
+
+    validator = FlakyValidator(
+        args.bind, args.delay, args.drop_rate, args.restart_interval,
+        args.seed)
+    validator.start()
+    RpcClient(args.url).wait_for_service()
+
+    replayer = Replayer(args.url, workload, args.threads)
+    replayer.start()
+    time.sleep(args.duration)
+
+    LOGGER.info("Stopping faults")
+    validator.faults = False
+    replayer.stop()
+
+    # Without faults, the server must have recovered and answer everything
+    for request_id, call in enumerate(workload):
+        if not replayer.call(call, request_id):
+            replayer.failures.append(
+                "{} failed after faults stopped".format(call["method"]))
+    validator.stop()
+
+    LOGGER.info("Requests: %s", replayer.stats)
+    LOGGER.info("Validator: %s", validator.stats)
+    for failure in replayer.failures:
+        LOGGER.error(failure)
+
+    if replayer.failures or replayer.stats["ok"] == 0:
+        sys.exit(1)
+
+
+if __name__ == "__main__":
+    main()
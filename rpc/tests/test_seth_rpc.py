@@ -13,6 +13,7 @@
 # limitations under the License.
 # ------------------------------------------------------------------------------
 
+import hashlib
 import unittest
 
 from rpc_client import RpcClient
@@ -37,6 +38,11 @@ from sawtooth_sdk.protobuf.client_batch_submit_pb2 import \
     ClientBatchSubmitRequest
 from sawtooth_sdk.protobuf.client_batch_submit_pb2 import \
     ClientBatchSubmitResponse
+from sawtooth_sdk.protobuf.client_batch_submit_pb2 import \
+    ClientBatchStatusRequest
+from sawtooth_sdk.protobuf.client_batch_submit_pb2 import \
+    ClientBatchStatusResponse
+from sawtooth_sdk.protobuf.client_batch_submit_pb2 import ClientBatchStatus
 from sawtooth_sdk.protobuf.block_pb2 import Block
 from sawtooth_sdk.protobuf.block_pb2 import BlockHeader
 from sawtooth_sdk.protobuf.batch_pb2 import Batch
@@ -51,6 +57,7 @@ from protobuf.seth_pb2 import SethTransactionReceipt
 from protobuf.seth_pb2 import EvmEntry
 from protobuf.seth_pb2 import EvmStateAccount
 from protobuf.seth_pb2 import EvmStorage
+from protobuf.seth_pb2 import EvmStorageEntry
 from protobuf.seth_pb2 import SethTransaction
 from protobuf.seth_pb2 import CreateExternalAccountTxn
 from protobuf.seth_pb2 import CreateContractAccountTxn
@@ -93,6 +100,8 @@ class SethRpcTest(unittest.TestCase):
         cls.code_s = "abcdef"
         cls.position_b = bytes([0x01, 0x23, 0x45])
         cls.position_s = "012345"
+        cls.slot_s = hashlib.sha256(
+            bytes(29) + cls.position_b).hexdigest()[:24]
         cls.stored_b = bytes([0x67, 0x89])
         cls.stored_s = "6789"
         cls.topic1_s = "ff" * 32
@@ -384,11 +393,39 @@ class SethRpcTest(unittest.TestCase):
 
     def test_get_storage_at(self):
         """Test that an account's storage is retrieved correctly."""
-        # self._test_get_account("balance")
         self.rpc.acall(
             "eth_getStorageAt",
             ["0x" + self.account_address, "0x" + self.position_s, "latest"])
 
+        msg, request = self._receive_state_request()
+        self.assertEqual(request.address,
+            "a68b06" + self.account_address + self.slot_s)
+
+        self.validator.respond(
+            Message.CLIENT_STATE_GET_RESPONSE,
+            ClientStateGetResponse(
+                status=ClientStateGetResponse.OK,
+                value=EvmStorageEntry(
+                    storage=[EvmStorage(key=self.position_b,
+                                        value=self.stored_b)],
+                ).SerializeToString()),
+            msg)
+        result = self.rpc.get_result()
+        self.assertEqual("0x" + self.stored_s, result)
+
+    def test_get_storage_at_account_entry(self):
+        """Test that storage kept in the account entry, by contracts deployed
+           before storage was split out, is retrieved correctly.
+        """
+        self.rpc.acall(
+            "eth_getStorageAt",
+            ["0x" + self.account_address, "0x" + self.position_s, "latest"])
+
+        msg, request = self._receive_state_request()
+        self.assertEqual(request.address,
+            "a68b06" + self.account_address + self.slot_s)
+        self._send_state_no_resource(msg)
+
         msg, request = self._receive_state_request()
         self.assertEqual(request.address,
             "a68b06" + self.account_address + "0" * 24)
@@ -417,6 +454,11 @@ class SethRpcTest(unittest.TestCase):
             "eth_getStorageAt",
             ["0x" + bad_account_address, "0x" + self.position_s, "latest"])
 
+        msg, request = self._receive_state_request()
+        self.assertEqual(request.address,
+            "a68b06" + bad_account_address + self.slot_s)
+        self._send_state_no_resource(msg)
+
         msg, request = self._receive_state_request()
         self.assertEqual(request.address,
             "a68b06" + bad_account_address + "0" * 24)
@@ -535,6 +577,47 @@ class SethRpcTest(unittest.TestCase):
         self.assertEqual(result["gas"], hex(self.gas))
         self.assertEqual(result["input"], "0x" + self.contract_call_s)
 
+    def test_get_pending_transaction_by_hash(self):
+        """Tests that a transaction this server sent is retrieved without a
+           block while its batch is pending."""
+        self.rpc.acall(
+            "eth_sendTransaction", [{
+                "from": "0x" + self.account_address,
+                "data": "0x" + self.contract_call_s,
+                "to": "0x" + self.contract_address,
+        }])
+        msg, _ = self._receive_state_request()
+        self._send_state_response(msg)
+        msg, _ = self._receive_submit_request()
+        self._send_submit_response(msg)
+        msg, _ = self._receive_batch_status_request()
+        self._send_batch_status_response(msg, ClientBatchStatus.PENDING)
+        self.assertEqual(
+            "0x" + self.contract_call_txn_id, self.rpc.get_result())
+
+        self.rpc.acall(
+            "eth_getTransactionByHash", ["0x" + self.contract_call_txn_id])
+
+        msg, request = self._receive_transaction_request()
+        self.assertEqual(request.transaction_id, self.contract_call_txn_id)
+        self.validator.respond(
+            Message.CLIENT_TRANSACTION_GET_RESPONSE,
+            ClientTransactionGetResponse(
+                status=ClientTransactionGetResponse.NO_RESOURCE),
+            msg)
+        msg, _ = self._receive_block_request_transaction()
+        self._send_block_no_resource(msg)
+        msg, _ = self._receive_batch_status_request()
+        self._send_batch_status_response(msg, ClientBatchStatus.PENDING)
+
+        result = self.rpc.get_result()
+        self.assertEqual(result["hash"], "0x" + self.contract_call_txn_id)
+        self.assertIsNone(result["blockHash"])
+        self.assertIsNone(result["blockNumber"])
+        self.assertIsNone(result["transactionIndex"])
+        self.assertEqual(result["from"], "0x" + self.account_address)
+        self.assertEqual(result["to"], "0x" + self.contract_address)
+
     def test_get_transaction_by_hash_bad_input(self):
         """Test that the correct error message is returned if no input is given
            to eth_getTransactionByHash.
@@ -717,6 +800,8 @@ class SethRpcTest(unittest.TestCase):
         self.assertEqual(create.value, 0)
 
         self._send_submit_response(msg)
+        msg, _ = self._receive_batch_status_request()
+        self._send_batch_status_response(msg, ClientBatchStatus.PENDING)
         self.assertEqual(
             "0x" + self.contract_init_txn_id, self.rpc.get_result())
 
@@ -745,6 +830,8 @@ class SethRpcTest(unittest.TestCase):
         self.assertEqual(call.value, 0)
 
         self._send_submit_response(msg)
+        msg, _ = self._receive_batch_status_request()
+        self._send_batch_status_response(msg, ClientBatchStatus.PENDING)
         self.assertEqual(
             "0x" + self.contract_call_txn_id, self.rpc.get_result())
 
@@ -1262,6 +1349,25 @@ class SethRpcTest(unittest.TestCase):
             ClientBatchSubmitResponse(status=ClientBatchSubmitResponse.OK),
             msg)
 
+    def _receive_batch_status_request(self):
+        msg = self.validator.receive()
+        self.assertEqual(msg.message_type, Message.CLIENT_BATCH_STATUS_REQUEST)
+        request = ClientBatchStatusRequest()
+        request.ParseFromString(msg.content)
+        return msg, request
+
+    def _send_batch_status_response(self, msg, status):
+        request = ClientBatchStatusRequest()
+        request.ParseFromString(msg.content)
+        self.validator.respond(
+            Message.CLIENT_BATCH_STATUS_RESPONSE,
+            ClientBatchStatusResponse(
+                status=ClientBatchStatusResponse.OK,
+                batch_statuses=[ClientBatchStatus(
+                    batch_id=request.batch_ids[0],
+                    status=status)]),
+            msg)
+
     def _receive_receipt_request(self):
         # Verify receipt get request
         msg = self.validator.receive()
@@ -1332,7 +1438,7 @@ class SethRpcTest(unittest.TestCase):
         txn_header.ParseFromString(txn.header)
         self.assertEqual(txn_header.signer_public_key, self.public_key)
         self.assertEqual(txn_header.family_name, "seth")
-        self.assertEqual(txn_header.family_version, "1.0")
+        self.assertEqual(txn_header.family_version, "1.1")
 
         return msg, txn
 
@@ -0,0 +1,549 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Encoding and decoding of values as the Solidity ABI defines them, from the types
+//! of a contract's JSON ABI. Values are JSON: integers are decimal strings when
+//! decoded, and numbers or decimal or hex strings when encoded, while addresses and
+//! bytes are 0x-prefixed hex strings and arrays and tuples are arrays.
+
+use serde_json::Value;
+use U256;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParamType {
+    Uint(usize),
+    Int(usize),
+    Address,
+    Bool,
+    FixedBytes(usize),
+    Bytes,
+    String,
+    Array(Box<ParamType>),
+    FixedArray(Box<ParamType>, usize),
+    Tuple(Vec<ParamType>),
+}
+
+impl ParamType {
+    /// Parses a parameter of a JSON ABI, which has a `type` and, for tuples,
+    /// `components`
+    pub fn from_abi(param: &Value) -> Result<ParamType, String> {
+        let kind = param
+            .get("type")
+            .and_then(Value::as_str)
+            .ok_or("Parameters must have a type")?;
+        let invalid = || format!("Invalid type `{}`", kind);
+        let (base, suffixes) = match kind.find('[') {
+            Some(index) => kind.split_at(index),
+            None => (kind, ""),
+        };
+        let bits = |bits: &str| match bits.parse() {
+            Ok(bits) if bits > 0 && bits <= 256 && bits % 8 == 0 => Ok(bits),
+            _ => Err(invalid()),
+        };
+
+        let mut param_type = match base {
+            "tuple" => ParamType::Tuple(
+                param
+                    .get("components")
+                    .and_then(Value::as_array)
+                    .ok_or("Tuples must have components")?
+                    .iter()
+                    .map(ParamType::from_abi)
+                    .collect::<Result<_, _>>()?,
+            ),
+            "address" => ParamType::Address,
+            "bool" => ParamType::Bool,
+            "bytes" => ParamType::Bytes,
+            "string" => ParamType::String,
+            "uint" => ParamType::Uint(256),
+            "int" => ParamType::Int(256),
+            _ if base.starts_with("uint") => ParamType::Uint(bits(&base[4..])?),
+            _ if base.starts_with("int") => ParamType::Int(bits(&base[3..])?),
+            _ if base.starts_with("bytes") => match base[5..].parse() {
+                Ok(size) if size > 0 && size <= 32 => ParamType::FixedBytes(size),
+                _ => return Err(invalid()),
+            },
+            _ => return Err(format!("Unsupported type `{}`", kind)),
+        };
+
+        for suffix in suffixes.split_terminator(']') {
+            param_type = match suffix {
+                "[" => ParamType::Array(Box::new(param_type)),
+                _ => match suffix.get(1..).map(str::parse) {
+                    Some(Ok(size)) => ParamType::FixedArray(Box::new(param_type), size),
+                    _ => return Err(invalid()),
+                },
+            };
+        }
+        Ok(param_type)
+    }
+
+    /// Returns the type as it appears in function signatures
+    pub fn canonical(&self) -> String {
+        match *self {
+            ParamType::Uint(bits) => format!("uint{}", bits),
+            ParamType::Int(bits) => format!("int{}", bits),
+            ParamType::Address => String::from("address"),
+            ParamType::Bool => String::from("bool"),
+            ParamType::FixedBytes(size) => format!("bytes{}", size),
+            ParamType::Bytes => String::from("bytes"),
+            ParamType::String => String::from("string"),
+            ParamType::Array(ref inner) => format!("{}[]", inner.canonical()),
+            ParamType::FixedArray(ref inner, size) => format!("{}[{}]", inner.canonical(), size),
+            ParamType::Tuple(ref types) => format!("({})", canonical_list(types)),
+        }
+    }
+
+    /// Whether values of the type are encoded after the heads, at an offset
+    pub fn is_dynamic(&self) -> bool {
+        match *self {
+            ParamType::Bytes | ParamType::String | ParamType::Array(_) => true,
+            ParamType::FixedArray(ref inner, _) => inner.is_dynamic(),
+            ParamType::Tuple(ref types) => types.iter().any(ParamType::is_dynamic),
+            _ => false,
+        }
+    }
+
+    /// The size of the head of a value of this type
+    pub fn head_size(&self) -> usize {
+        match *self {
+            _ if self.is_dynamic() => 32,
+            ParamType::FixedArray(ref inner, size) => inner.head_size() * size,
+            ParamType::Tuple(ref types) => types.iter().map(ParamType::head_size).sum(),
+            _ => 32,
+        }
+    }
+}
+
+/// Returns the types as they appear in a signature, separated by commas
+pub fn canonical_list(types: &[ParamType]) -> String {
+    types
+        .iter()
+        .map(ParamType::canonical)
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// Formats bytes as 0x-prefixed hex
+pub fn to_hex(bytes: &[u8]) -> String {
+    let digits: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("0x{}", digits)
+}
+
+/// Parses hex, with or without `0x`
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    let hex = hex.trim_start_matches("0x");
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Encodes values of the types one after the other, as the arguments of a call are
+pub fn encode_params(types: &[ParamType], values: &[Value]) -> Result<Vec<u8>, String> {
+    if types.len() != values.len() {
+        return Err(format!(
+            "Expected {} values, got {}",
+            types.len(),
+            values.len()
+        ));
+    }
+
+    let head_size: usize = types.iter().map(ParamType::head_size).sum();
+    let mut head = Vec::new();
+    let mut tail = Vec::new();
+    for (kind, value) in types.iter().zip(values) {
+        let encoded = encode(kind, value)?;
+        if kind.is_dynamic() {
+            head.extend_from_slice(&uint_word(U256::from(head_size + tail.len())));
+            tail.extend(encoded);
+        } else {
+            head.extend(encoded);
+        }
+    }
+    head.extend(tail);
+    Ok(head)
+}
+
+fn encode(kind: &ParamType, value: &Value) -> Result<Vec<u8>, String> {
+    let invalid = || format!("Invalid value for `{}`: {}", kind.canonical(), value);
+    let items = || value.as_array().ok_or_else(invalid);
+    let hex = || value.as_str().and_then(from_hex).ok_or_else(invalid);
+
+    match *kind {
+        ParamType::Uint(bits) => match int_value(value).ok_or_else(invalid)? {
+            (false, number) if number.bits() <= bits => Ok(uint_word(number).to_vec()),
+            _ => Err(invalid()),
+        },
+        ParamType::Int(bits) => {
+            let (negative, number) = int_value(value).ok_or_else(invalid)?;
+            let limit = U256::one() << (bits - 1);
+            if negative && number <= limit {
+                Ok(uint_word((!number).overflowing_add(U256::one()).0).to_vec())
+            } else if !negative && number < limit {
+                Ok(uint_word(number).to_vec())
+            } else {
+                Err(invalid())
+            }
+        }
+        ParamType::Address => {
+            let address = hex()?;
+            if address.len() != 20 {
+                return Err(invalid());
+            }
+            let mut word = vec![0; 12];
+            word.extend(address);
+            Ok(word)
+        }
+        ParamType::Bool => {
+            let mut word = vec![0; 32];
+            word[31] = value.as_bool().ok_or_else(invalid)? as u8;
+            Ok(word)
+        }
+        ParamType::FixedBytes(size) => {
+            let mut bytes = hex()?;
+            if bytes.len() != size {
+                return Err(invalid());
+            }
+            bytes.resize(32, 0);
+            Ok(bytes)
+        }
+        ParamType::Bytes => Ok(encode_bytes(&hex()?)),
+        ParamType::String => Ok(encode_bytes(value.as_str().ok_or_else(invalid)?.as_bytes())),
+        ParamType::Array(ref inner) => {
+            let items = items()?;
+            let mut data = uint_word(U256::from(items.len())).to_vec();
+            data.extend(encode_params(&vec![(**inner).clone(); items.len()], items)?);
+            Ok(data)
+        }
+        ParamType::FixedArray(ref inner, size) => {
+            encode_params(&vec![(**inner).clone(); size], items()?)
+        }
+        ParamType::Tuple(ref types) => encode_params(types, items()?),
+    }
+}
+
+/// Parses an integer given as a JSON number or a decimal or 0x-prefixed hex string
+/// into whether it is negative and its magnitude
+fn int_value(value: &Value) -> Option<(bool, U256)> {
+    if let Some(number) = value.as_i64() {
+        return Some((number < 0, U256::from(number.unsigned_abs())));
+    }
+    if let Some(number) = value.as_u64() {
+        return Some((false, U256::from(number)));
+    }
+
+    let string = value.as_str()?;
+    let (negative, digits) = match string.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, string),
+    };
+    let number = match digits.strip_prefix("0x") {
+        Some(hex) => U256::from_str_radix(hex, 16).ok()?,
+        None if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) => {
+            U256::from_dec_str(digits).ok()?
+        }
+        None => return None,
+    };
+    Some((negative && !number.is_zero(), number))
+}
+
+fn encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    let mut data = uint_word(U256::from(bytes.len())).to_vec();
+    data.extend_from_slice(bytes);
+    let padded = data.len().div_ceil(32) * 32;
+    data.resize(padded, 0);
+    data
+}
+
+fn uint_word(value: U256) -> [u8; 32] {
+    let mut word = [0; 32];
+    value.to_big_endian(&mut word);
+    word
+}
+
+/// Decodes values of the types encoded one after the other, as the outputs of a call
+/// are
+pub fn decode_params(types: &[ParamType], data: &[u8]) -> Result<Vec<Value>, String> {
+    let mut position = 0;
+    types
+        .iter()
+        .map(|kind| {
+            let value = if kind.is_dynamic() {
+                let offset = read_usize(data, position)?;
+                decode(kind, data.get(offset..).ok_or_else(too_short)?)
+            } else {
+                decode(kind, &data[position.min(data.len())..])
+            };
+            position += kind.head_size();
+            value
+        })
+        .collect()
+}
+
+/// Decodes one value of the type from the start of the data
+pub fn decode(kind: &ParamType, data: &[u8]) -> Result<Value, String> {
+    let word = || data.get(..32).ok_or_else(too_short);
+
+    match *kind {
+        ParamType::Uint(_) => Ok(Value::String(U256::from_big_endian(word()?).to_string())),
+        ParamType::Int(_) => {
+            let number = U256::from_big_endian(word()?);
+            Ok(Value::String(if number.bit(255) {
+                format!("-{}", (!number).overflowing_add(U256::one()).0)
+            } else {
+                number.to_string()
+            }))
+        }
+        ParamType::Address => Ok(Value::String(to_hex(&word()?[12..]))),
+        ParamType::Bool => Ok(Value::Bool(word()?[31] != 0)),
+        ParamType::FixedBytes(size) => Ok(Value::String(to_hex(&word()?[..size]))),
+        ParamType::Bytes => Ok(Value::String(to_hex(decode_bytes(data)?))),
+        ParamType::String => String::from_utf8(decode_bytes(data)?.to_vec())
+            .map(Value::String)
+            .map_err(|_| String::from("Invalid UTF-8 in string")),
+        ParamType::Array(ref inner) => {
+            let length = read_usize(data, 0)?;
+            // Every item takes at least a word, which bounds the allocation below
+            if length > data.len() / 32 {
+                return Err(too_short());
+            }
+            decode_params(&vec![(**inner).clone(); length], &data[32..]).map(Value::Array)
+        }
+        ParamType::FixedArray(ref inner, size) => {
+            decode_params(&vec![(**inner).clone(); size], data).map(Value::Array)
+        }
+        ParamType::Tuple(ref types) => decode_params(types, data).map(Value::Array),
+    }
+}
+
+fn decode_bytes(data: &[u8]) -> Result<&[u8], String> {
+    let length = read_usize(data, 0)?;
+    data.get(32..)
+        .and_then(|data| data.get(..length))
+        .ok_or_else(too_short)
+}
+
+fn read_usize(data: &[u8], position: usize) -> Result<usize, String> {
+    let word = data
+        .get(position..)
+        .and_then(|data| data.get(..32))
+        .ok_or_else(too_short)?;
+    if word[..24].iter().any(|b| *b != 0) {
+        return Err(String::from("Offset or length is too large"));
+    }
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&word[24..]);
+    Ok(u64::from_be_bytes(bytes) as usize)
+}
+
+/// Returned when the data ends before what it encodes
+pub fn too_short() -> String {
+    String::from("Data is too short")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use serde_json;
+
+    fn types(params: &str) -> Vec<ParamType> {
+        serde_json::from_str::<Vec<Value>>(params)
+            .unwrap()
+            .iter()
+            .map(ParamType::from_abi)
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn encodes_the_example_of_the_solidity_docs() {
+        let inputs = types(
+            r#"[
+                {"name": "a", "type": "bytes"},
+                {"name": "b", "type": "bool"},
+                {"name": "c", "type": "uint256[]"}
+            ]"#,
+        );
+        let args: Vec<Value> = serde_json::from_str(r#"["0x64617665", true, [1, 2, 3]]"#).unwrap();
+
+        assert_eq!(canonical_list(&inputs), "bytes,bool,uint256[]");
+        assert_eq!(
+            to_hex(&encode_params(&inputs, &args).unwrap()),
+            String::from("0x")
+                + "0000000000000000000000000000000000000000000000000000000000000060"
+                + "0000000000000000000000000000000000000000000000000000000000000001"
+                + "00000000000000000000000000000000000000000000000000000000000000a0"
+                + "0000000000000000000000000000000000000000000000000000000000000004"
+                + "6461766500000000000000000000000000000000000000000000000000000000"
+                + "0000000000000000000000000000000000000000000000000000000000000003"
+                + "0000000000000000000000000000000000000000000000000000000000000001"
+                + "0000000000000000000000000000000000000000000000000000000000000002"
+                + "0000000000000000000000000000000000000000000000000000000000000003"
+        );
+    }
+
+    #[test]
+    fn decodes_what_it_encodes() {
+        let outputs = types(
+            r#"[
+                {"name": "a", "type": "int8"},
+                {"name": "b", "type": "string"},
+                {"name": "c", "type": "tuple[2]", "components": [
+                    {"name": "d", "type": "address"},
+                    {"name": "e", "type": "bytes2[]"}
+                ]}
+            ]"#,
+        );
+        let values: Vec<Value> = serde_json::from_str(
+            r#"["-5", "seth", [
+                ["0x434d46456b6973a678b77382fca0252629f4389f", ["0xabcd"]],
+                ["0x0000000000000000000000000000000000000001", []]
+            ]]"#,
+        )
+        .unwrap();
+
+        let data = encode_params(&outputs, &values).unwrap();
+        assert_eq!(decode_params(&outputs, &data).unwrap(), values);
+        assert!(decode_params(&outputs, &data[..data.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn checks_integer_ranges() {
+        let kind = ParamType::Uint(8);
+        assert!(encode(&kind, &Value::from(255)).is_ok());
+        assert!(encode(&kind, &Value::from(256)).is_err());
+        assert!(encode(&kind, &Value::from(-1)).is_err());
+
+        let kind = ParamType::Int(8);
+        assert!(encode(&kind, &Value::from(-128)).is_ok());
+        assert!(encode(&kind, &Value::from(128)).is_err());
+    }
+
+    fn param_type() -> impl Strategy<Value = ParamType> {
+        let bits = || (1usize..=32).prop_map(|bytes| bytes * 8);
+        let leaf = prop_oneof![
+            bits().prop_map(ParamType::Uint),
+            bits().prop_map(ParamType::Int),
+            Just(ParamType::Address),
+            Just(ParamType::Bool),
+            (1usize..=32).prop_map(ParamType::FixedBytes),
+            Just(ParamType::Bytes),
+            Just(ParamType::String),
+        ];
+        leaf.prop_recursive(3, 16, 3, |inner| {
+            prop_oneof![
+                inner
+                    .clone()
+                    .prop_map(|inner| ParamType::Array(Box::new(inner))),
+                (inner.clone(), 1usize..4)
+                    .prop_map(|(inner, size)| ParamType::FixedArray(Box::new(inner), size)),
+                vec(inner, 1..4).prop_map(ParamType::Tuple),
+            ]
+        })
+    }
+
+    /// Values of the type as they are decoded, which is how encoding takes them too
+    fn value_of(kind: &ParamType) -> BoxedStrategy<Value> {
+        let word = || any::<[u8; 32]>().prop_map(|word| U256::from_big_endian(&word));
+        match *kind {
+            ParamType::Uint(bits) => word()
+                .prop_map(move |number| Value::String((number >> (256 - bits)).to_string()))
+                .boxed(),
+            ParamType::Int(bits) => (word(), any::<bool>())
+                .prop_map(move |(number, negative)| {
+                    let magnitude = number >> (257 - bits);
+                    Value::String(if negative {
+                        format!("-{}", magnitude + 1)
+                    } else {
+                        magnitude.to_string()
+                    })
+                })
+                .boxed(),
+            ParamType::Address => any::<[u8; 20]>()
+                .prop_map(|address| Value::String(to_hex(&address)))
+                .boxed(),
+            ParamType::Bool => any::<bool>().prop_map(Value::Bool).boxed(),
+            ParamType::FixedBytes(size) => vec(any::<u8>(), size)
+                .prop_map(|bytes| Value::String(to_hex(&bytes)))
+                .boxed(),
+            ParamType::Bytes => vec(any::<u8>(), 0..70)
+                .prop_map(|bytes| Value::String(to_hex(&bytes)))
+                .boxed(),
+            ParamType::String => any::<String>().prop_map(Value::String).boxed(),
+            ParamType::Array(ref inner) => {
+                vec(value_of(inner), 0..4).prop_map(Value::Array).boxed()
+            }
+            ParamType::FixedArray(ref inner, size) => {
+                vec(value_of(inner), size).prop_map(Value::Array).boxed()
+            }
+            ParamType::Tuple(ref types) => types
+                .iter()
+                .map(value_of)
+                .collect::<Vec<_>>()
+                .prop_map(Value::Array)
+                .boxed(),
+        }
+    }
+
+    fn params() -> impl Strategy<Value = (Vec<ParamType>, Vec<Value>)> {
+        vec(param_type(), 1..4).prop_flat_map(|types| {
+            let values: Vec<_> = types.iter().map(value_of).collect();
+            (Just(types), values)
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn decodes_the_values_it_encodes((types, values) in params()) {
+            let data = encode_params(&types, &values).unwrap();
+            prop_assert_eq!(decode_params(&types, &data).unwrap(), values);
+        }
+
+        #[test]
+        fn rejects_lengths_past_the_end(bytes in vec(any::<u8>(), 0..70), extra in 1usize..64) {
+            let mut data = encode_params(&[ParamType::Bytes], &[Value::String(to_hex(&bytes))])
+                .unwrap();
+            let length = data.len() - 64 + extra;
+            data[32..64].copy_from_slice(&uint_word(U256::from(length)));
+            prop_assert_eq!(decode_params(&[ParamType::Bytes], &data), Err(too_short()));
+        }
+
+        #[test]
+        fn rejects_offsets_and_lengths_wider_than_64_bits(
+            items in vec(any::<u64>(), 0..4),
+            word in 0usize..2,
+            index in 0usize..24,
+            byte in 1u8..,
+        ) {
+            let kind = [ParamType::Array(Box::new(ParamType::Uint(64)))];
+            let items: Vec<Value> = items.into_iter().map(Value::from).collect();
+            let mut data = encode_params(&kind, &[Value::Array(items)]).unwrap();
+            // The offset of the array, or the number of its items
+            data[word * 32 + index] = byte;
+            prop_assert_eq!(
+                decode_params(&kind, &data),
+                Err(String::from("Offset or length is too large"))
+            );
+        }
+    }
+}
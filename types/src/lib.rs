@@ -0,0 +1,817 @@
+/*
+ * Copyright 2018 Intel Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! The accounts, transactions, receipts, logs and blocks of the seth-rpc JSON-RPC API,
+//! shared by the server and its clients so that both sides agree on their encoding.
+//! Addresses, hashes and other data are 0x-prefixed hex strings, as they are on the
+//! wire. The Solidity ABI codec in `abi` is shared the same way, for the calls both
+//! sides encode and decode.
+
+extern crate primitive_types;
+#[cfg(test)]
+extern crate proptest;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+pub mod abi;
+
+pub use primitive_types::U256;
+
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::collections::BTreeMap;
+
+/// A number, which is sent as a 0x-prefixed hex string
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Quantity(pub u64);
+
+impl Serialize for Quantity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:#x}", self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Quantity {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let digits = s
+            .get(2..)
+            .filter(|_| s.starts_with("0x"))
+            .ok_or_else(|| de::Error::custom(format!("Quantity must start with 0x: {}", s)))?;
+        let digits = if digits.is_empty() { "0" } else { digits };
+        u64::from_str_radix(digits, 16)
+            .map(Quantity)
+            .map_err(|_| de::Error::custom(format!("Invalid quantity: {}", s)))
+    }
+}
+
+/// An amount of wei, such as a balance, a value or a price per gas, which is a
+/// quantity that can take up to 256 bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Wei(pub U256);
+
+impl From<u64> for Wei {
+    fn from(wei: u64) -> Self {
+        Wei(U256::from(wei))
+    }
+}
+
+impl Serialize for Wei {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:#x}", self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Wei {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let digits = s
+            .get(2..)
+            .filter(|_| s.starts_with("0x"))
+            .ok_or_else(|| de::Error::custom(format!("Quantity must start with 0x: {}", s)))?;
+        let digits = if digits.is_empty() { "0" } else { digits };
+        U256::from_str_radix(digits, 16)
+            .map(Wei)
+            .map_err(|_| de::Error::custom(format!("Invalid quantity: {}", s)))
+    }
+}
+
+/// The block whose state a method reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockId {
+    Number(u64),
+    Earliest,
+    Latest,
+    Pending,
+}
+
+impl Serialize for BlockId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            BlockId::Number(n) => Quantity(n).serialize(serializer),
+            BlockId::Earliest => serializer.serialize_str("earliest"),
+            BlockId::Latest => serializer.serialize_str("latest"),
+            BlockId::Pending => serializer.serialize_str("pending"),
+        }
+    }
+}
+
+/// A transaction to send with `eth_sendTransaction`, or to run with `eth_call`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gas: Option<Quantity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gas_price: Option<Wei>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Wei>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<Quantity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_for: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_list: Option<Vec<AccessListEntry>>,
+}
+
+/// An account a transaction touches, and optionally the storage slots of it that it
+/// touches
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessListEntry {
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage_keys: Option<Vec<String>>,
+}
+
+/// The logs that `eth_newFilter` and `eth_getLogs` match. Each topic is matched by
+/// any of the given values, or by any value if it is `None`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_block: Option<Quantity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_block: Option<Quantity>,
+    #[serde(default)]
+    pub address: Vec<String>,
+    #[serde(default)]
+    pub topics: Vec<Option<Vec<String>>>,
+}
+
+/// A block. The fields that have no corollary in Sawtooth always have the zero value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Block {
+    pub number: Quantity,
+    pub hash: String,
+    pub parent_hash: String,
+    pub state_root: String,
+    pub gas_used: Quantity,
+    pub transactions: BlockTransactions,
+    /// Whether the block can no longer be reverted, which Ethereum doesn't report
+    #[serde(default)]
+    pub finalized: bool,
+    pub nonce: String,
+    pub sha3_uncles: String,
+    pub logs_bloom: String,
+    pub transactions_root: String,
+    pub receipts_root: String,
+    pub miner: String,
+    pub difficulty: Quantity,
+    pub total_difficulty: Quantity,
+    pub extra_data: String,
+    pub size: Quantity,
+    pub gas_limit: Quantity,
+    pub uncles: Vec<String>,
+    /// The `sawtooth.seth.base_fee_per_gas` setting in the state of the block,
+    /// which EIP-1559 transactions must offer at least
+    #[serde(default)]
+    pub base_fee_per_gas: Wei,
+}
+
+/// The transactions of a block, which are only hashes unless the full block was
+/// requested
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BlockTransactions {
+    Hashes(Vec<String>),
+    Full(Vec<Transaction>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transaction {
+    pub hash: String,
+    pub nonce: Quantity,
+    pub block_hash: Option<String>,
+    pub block_number: Option<Quantity>,
+    pub transaction_index: Option<Quantity>,
+    pub from: String,
+    pub to: Option<String>,
+    pub value: Wei,
+    pub gas_price: Wei,
+    pub gas: Quantity,
+    pub input: String,
+    /// 0x2 for an EIP-1559 transaction, or 0x0
+    #[serde(rename = "type", default)]
+    pub txn_type: Quantity,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_fee_per_gas: Option<Wei>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_priority_fee_per_gas: Option<Wei>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionReceipt {
+    pub transaction_hash: String,
+    pub transaction_index: Quantity,
+    pub block_hash: String,
+    pub block_number: Quantity,
+    pub cumulative_gas_used: Quantity,
+    pub gas_used: Quantity,
+    pub contract_address: Option<String>,
+    pub logs: Vec<TransactionLog>,
+    /// The bloom filter of the addresses and topics of the logs
+    #[serde(default)]
+    pub logs_bloom: String,
+    pub return_value: String,
+    /// 0x0 if the transaction reverted, which is only committed on networks that
+    /// keep reverted transactions, and 0x1 otherwise. Servers from before it was
+    /// reported only returned receipts of transactions that succeeded.
+    #[serde(default = "succeeded")]
+    pub status: Quantity,
+    /// Why the transaction reverted, if it reverted with a reason
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revert_reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gas_profile: Vec<GasProfileEntry>,
+    /// The price per gas the transaction was signed with, or for an EIP-1559
+    /// transaction its priority fee on top of the base fee of its block, as far
+    /// as its max fee allows
+    #[serde(default)]
+    pub effective_gas_price: Wei,
+}
+
+fn succeeded() -> Quantity {
+    Quantity(1)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionLog {
+    pub removed: bool,
+    pub log_index: Quantity,
+    pub transaction_index: Quantity,
+    pub transaction_hash: String,
+    pub block_hash: String,
+    pub block_number: Quantity,
+    pub address: String,
+    pub data: String,
+    pub topics: Vec<String>,
+}
+
+/// The base fees of a range of blocks and of the block after them, and the tips
+/// paid in each at the percentiles asked for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeHistory {
+    pub oldest_block: Quantity,
+    pub base_fee_per_gas: Vec<Wei>,
+    pub gas_used_ratio: Vec<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reward: Option<Vec<Vec<Wei>>>,
+}
+
+/// Some of the logs that passed a filter, and the cursor to get the ones after them
+/// with, which is null after the last page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogPage {
+    pub logs: Vec<TransactionLog>,
+    pub next_cursor: Option<String>,
+}
+
+/// Gas used by one opcode in a transaction, recorded by processors in gas audit mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GasProfileEntry {
+    pub opcode: String,
+    pub count: Quantity,
+    pub gas: Quantity,
+}
+
+/// A change to the permissions of an account, returned by permission filters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionChange {
+    pub address: String,
+    pub permissions: String,
+    pub transaction_index: Quantity,
+    pub transaction_hash: String,
+    pub block_hash: String,
+    pub block_number: Quantity,
+}
+
+/// What `eth_getFilterChanges` returns, which depends on the kind of filter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FilterChanges {
+    Hashes(Vec<String>),
+    Logs(Vec<TransactionLog>),
+    Permissions(Vec<PermissionChange>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageStats {
+    pub address: String,
+    pub slots: Quantity,
+    pub entries: Quantity,
+    pub bytes: Quantity,
+}
+
+/// What `seth_getStorageStats` returns, which is one contract's stats if it was
+/// given an address
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum StorageStatsResult {
+    All(Vec<StorageStats>),
+    One(Option<StorageStats>),
+}
+
+/// The permissions set for an account, as `seth_getPermissions` lists them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionEntry {
+    pub address: String,
+    pub permissions: String,
+}
+
+/// What `seth_getPermissions` returns, which is one account's permissions if it was
+/// given an address
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PermissionsResult {
+    All(Vec<PermissionEntry>),
+    One(Option<String>),
+}
+
+/// What `seth_negotiateVersion` returns, which is the latest version of the seth_*
+/// methods both sides know, and the versions served
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionNegotiation {
+    pub version: String,
+    pub versions: Vec<String>,
+    pub unversioned: String,
+}
+
+/// What `eth_getProof` returns. The proofs are encoded nodes from the root down, of
+/// the state's Merkle-Radix tree, or of the account's storage trie for storage proofs
+/// translated to the form Ethereum gives them in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountProof {
+    pub address: String,
+    pub account_proof: Vec<String>,
+    pub balance: Wei,
+    pub code_hash: String,
+    pub nonce: Quantity,
+    pub storage_hash: String,
+    pub storage_proof: Vec<StorageProof>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageProof {
+    pub key: String,
+    pub value: String,
+    pub proof: Vec<String>,
+}
+
+/// What `seth_getConsensusInfo` returns. `members` is only set for consensus
+/// algorithms with a fixed membership, like PBFT and Raft.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsensusInfo {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub members: Option<Vec<String>>,
+    pub peers: Vec<String>,
+}
+
+/// What `seth_exportBatches` returns: the batches of `blocks` consecutive blocks,
+/// as hex of a serialized BatchList
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedBatches {
+    pub batches: String,
+    pub blocks: Quantity,
+}
+
+/// What `seth_exportState` returns: every account in the state of a block, by
+/// address, which `seth_importState` takes to seed another network with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StateSnapshot {
+    pub block_number: Quantity,
+    pub state_root: String,
+    pub alloc: BTreeMap<String, GenesisAccount>,
+}
+
+/// An account of a state snapshot, as the `alloc` of a geth genesis file holds it,
+/// with the seth permissions of the account added
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GenesisAccount {
+    #[serde(default)]
+    pub balance: Wei,
+    #[serde(default)]
+    pub nonce: Quantity,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub storage: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permissions: Option<String>,
+}
+
+/// What `seth_getWatchedAddressReport` returns: the watchlists an address is on,
+/// and the transactions it sent or was sent since it was first watched, with its
+/// balance after each block it had any in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchedAddressReport {
+    pub address: String,
+    pub watchlists: Vec<String>,
+    pub balances: Vec<WatchedBalance>,
+    pub transactions: Vec<Transaction>,
+}
+
+/// The balance of a watched address after a block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchedBalance {
+    pub block_number: Quantity,
+    pub balance: Wei,
+}
+
+/// What watched addresses did between two times, in seconds, which
+/// `seth_getActivityReport` and the periodic reports of `--report-dir` sign
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityReport {
+    pub from_time: Quantity,
+    pub to_time: Quantity,
+    pub from_block: Option<Quantity>,
+    pub to_block: Option<Quantity>,
+    pub generated_at: Quantity,
+    pub addresses: Vec<WatchedAddressReport>,
+}
+
+/// A report as the JSON text that was signed, the address of the account that
+/// signed it and the signature, which `seth_verifySignature` checks given the
+/// text as hex data
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedReport {
+    pub report: String,
+    pub signer: String,
+    pub signature: String,
+}
+
+/// What `seth_getShadowReport` returns: the fork committed blocks are run again
+/// with, how many blocks and transactions have been, how many of those went
+/// another way than they were committed and how many couldn't be run, and the
+/// latest of the ones that went another way
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShadowReport {
+    pub fork: String,
+    pub last_block: Option<Quantity>,
+    pub blocks: Quantity,
+    pub transactions: Quantity,
+    pub divergences: Quantity,
+    pub failures: Quantity,
+    pub recent: Vec<ShadowDivergence>,
+}
+
+/// A committed transaction that went another way when run again: which of its
+/// `status`, `returnValue` or `logs` differed, as committed and as run again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShadowDivergence {
+    pub block_number: Quantity,
+    pub block_hash: String,
+    pub transaction_hash: String,
+    pub field: String,
+    pub committed: String,
+    pub candidate: String,
+}
+
+/// What `seth_getHealth` returns while the validator can be reached: the number
+/// of its latest block and how many peers it has
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Health {
+    pub block_number: Quantity,
+    pub peers: Quantity,
+}
+
+/// How far along seth-rpc is in catching up with the validator: the block it
+/// started from, the last one it has and the validator's latest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncProgress {
+    pub starting_block: Quantity,
+    pub current_block: Quantity,
+    pub highest_block: Quantity,
+}
+
+/// What `eth_syncing` returns, which is false once seth-rpc has caught up
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SyncStatus {
+    Syncing(SyncProgress),
+    Synced(bool),
+}
+
+/// What `seth_getUnsupportedMethods` returns for each method a client has called
+/// that seth-rpc doesn't serve: the method, the `User-Agent` of the client, or
+/// `unknown` for clients that didn't send one, and how many times it was called
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnsupportedMethodCalls {
+    pub method: String,
+    pub client: String,
+    pub calls: Quantity,
+}
+
+/// What `seth_getNonceStatus` returns for an account: its nonce in the latest
+/// state, the nonce seth-rpc gives its next transaction, and the nonces of the
+/// transactions seth-rpc sent from it that are still counted towards that
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NonceStatus {
+    pub nonce: Quantity,
+    pub pending_nonce: Quantity,
+    pub tracked: Vec<Quantity>,
+}
+
+/// What `txpool_status` returns: how many of the transactions seth-rpc sent can
+/// be committed next, and how many wait for a nonce that hasn't been sent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxPoolStatus {
+    pub pending: Quantity,
+    pub queued: Quantity,
+}
+
+/// What `txpool_content` and `txpool_inspect` return, by sender and then by nonce
+/// in decimal, like other clients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxPoolContent<T> {
+    pub pending: BTreeMap<String, BTreeMap<String, T>>,
+    pub queued: BTreeMap<String, BTreeMap<String, T>>,
+}
+
+/// Why seth failed to do something, with a code that is never reused or renumbered,
+/// so that integrators can decide what to retry or alert on. The transaction
+/// processor sends the name as the extended data of a rejection, seth-rpc sends the
+/// code as `data.code` of JSON-RPC errors, and the CLI exits with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// The transaction was rejected for a reason without a more specific code
+    InvalidTransaction = 10,
+    /// The sender lacks the permission the transaction needs
+    PermissionDenied = 11,
+    /// The nonce isn't the sender's next one
+    InvalidNonce = 12,
+    /// The sender, or the account the transaction acts on, doesn't exist
+    AccountNotFound = 13,
+    /// The account to create already exists
+    AccountExists = 14,
+    /// The gas limit doesn't cover the transaction
+    OutOfGas = 15,
+    /// The contract reverted
+    Reverted = 16,
+    /// The transaction needs a family version or EVM feature that isn't supported
+    UnsupportedFork = 17,
+    /// The salt of a contract creation isn't 32 bytes
+    InvalidSalt = 18,
+    /// The code a contract creation returned is larger than the network allows
+    CodeTooLarge = 19,
+    /// seth-rpc stopped getting answers from the validator
+    ValidatorUnavailable = 20,
+    /// seth-rpc has too many pending requests
+    Overloaded = 21,
+    /// The response would be larger than seth-rpc sends
+    ResponseTooLarge = 22,
+    /// seth-rpc has as many filters installed, or the WebSocket connection as many
+    /// subscriptions, as it allows
+    FilterLimitReached = 23,
+    /// seth-rpc's screening policy doesn't allow the transaction to be sent
+    TransactionDenied = 24,
+    /// The batch has more requests than seth-rpc serves at once
+    BatchTooLarge = 25,
+    /// The method may only be called by authenticated clients
+    Unauthorized = 26,
+    /// The client sent more requests than seth-rpc serves it in the time
+    RateLimited = 27,
+    /// The request needed more memory than seth-rpc gives one request
+    MemoryLimitExceeded = 28,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 19] = [
+        ErrorCode::InvalidTransaction,
+        ErrorCode::PermissionDenied,
+        ErrorCode::InvalidNonce,
+        ErrorCode::AccountNotFound,
+        ErrorCode::AccountExists,
+        ErrorCode::OutOfGas,
+        ErrorCode::Reverted,
+        ErrorCode::UnsupportedFork,
+        ErrorCode::InvalidSalt,
+        ErrorCode::CodeTooLarge,
+        ErrorCode::ValidatorUnavailable,
+        ErrorCode::Overloaded,
+        ErrorCode::ResponseTooLarge,
+        ErrorCode::FilterLimitReached,
+        ErrorCode::TransactionDenied,
+        ErrorCode::BatchTooLarge,
+        ErrorCode::Unauthorized,
+        ErrorCode::RateLimited,
+        ErrorCode::MemoryLimitExceeded,
+    ];
+
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_code(code: u64) -> Option<Self> {
+        ErrorCode::ALL
+            .iter()
+            .cloned()
+            .find(|error| u64::from(error.code()) == code)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorCode::InvalidTransaction => "invalid_transaction",
+            ErrorCode::PermissionDenied => "permission_denied",
+            ErrorCode::InvalidNonce => "invalid_nonce",
+            ErrorCode::AccountNotFound => "account_not_found",
+            ErrorCode::AccountExists => "account_exists",
+            ErrorCode::OutOfGas => "out_of_gas",
+            ErrorCode::Reverted => "reverted",
+            ErrorCode::UnsupportedFork => "unsupported_fork",
+            ErrorCode::InvalidSalt => "invalid_salt",
+            ErrorCode::CodeTooLarge => "code_too_large",
+            ErrorCode::ValidatorUnavailable => "validator_unavailable",
+            ErrorCode::Overloaded => "overloaded",
+            ErrorCode::ResponseTooLarge => "response_too_large",
+            ErrorCode::FilterLimitReached => "filter_limit_reached",
+            ErrorCode::TransactionDenied => "transaction_denied",
+            ErrorCode::BatchTooLarge => "batch_too_large",
+            ErrorCode::Unauthorized => "unauthorized",
+            ErrorCode::RateLimited => "rate_limited",
+            ErrorCode::MemoryLimitExceeded => "memory_limit_exceeded",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        ErrorCode::ALL
+            .iter()
+            .cloned()
+            .find(|error| error.name() == name)
+    }
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(self.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quantity(json: &str) -> Result<Quantity, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    fn wei(json: &str) -> Result<Wei, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    #[test]
+    fn reads_and_writes_quantities_as_hex() {
+        assert_eq!(serde_json::to_string(&Quantity(0)).unwrap(), r#""0x0""#);
+        assert_eq!(serde_json::to_string(&Quantity(255)).unwrap(), r#""0xff""#);
+        assert_eq!(quantity(r#""0x1F""#).unwrap(), Quantity(31));
+        assert_eq!(quantity(r#""0x""#).unwrap(), Quantity(0));
+        assert_eq!(
+            quantity(r#""0xffffffffffffffff""#).unwrap(),
+            Quantity(u64::MAX)
+        );
+
+        assert!(quantity(r#""0x10000000000000000""#).is_err());
+        assert!(quantity(r#""1f""#).is_err());
+        assert!(quantity(r#""0xg""#).is_err());
+        assert!(quantity("31").is_err());
+    }
+
+    #[test]
+    fn reads_and_writes_amounts_of_wei_up_to_256_bits() {
+        let max = format!(r#""0x{}""#, "f".repeat(64));
+        assert_eq!(wei(&max).unwrap(), Wei(U256::MAX));
+        assert_eq!(serde_json::to_string(&Wei(U256::MAX)).unwrap(), max);
+        assert_eq!(wei(r#""0x3e8""#).unwrap(), Wei::from(1000));
+        assert_eq!(serde_json::to_string(&Wei::from(0)).unwrap(), r#""0x0""#);
+
+        assert!(wei(&format!(r#""0x1{}""#, "0".repeat(64))).is_err());
+        assert!(wei(r#""1000""#).is_err());
+    }
+
+    #[test]
+    fn writes_block_ids_as_numbers_or_tags() {
+        let written = |block| serde_json::to_string(&block).unwrap();
+        assert_eq!(written(BlockId::Number(16)), r#""0x10""#);
+        assert_eq!(written(BlockId::Earliest), r#""earliest""#);
+        assert_eq!(written(BlockId::Latest), r#""latest""#);
+        assert_eq!(written(BlockId::Pending), r#""pending""#);
+    }
+
+    #[test]
+    fn reads_receipts_from_servers_that_report_less() {
+        // A receipt from before the status and effective gas price were reported
+        let receipt: TransactionReceipt = serde_json::from_str(
+            r#"{
+                "transactionHash": "0x01",
+                "transactionIndex": "0x0",
+                "blockHash": "0x02",
+                "blockNumber": "0x3",
+                "cumulativeGasUsed": "0x5208",
+                "gasUsed": "0x5208",
+                "contractAddress": null,
+                "logs": [],
+                "returnValue": ""
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(receipt.status, Quantity(1));
+        assert_eq!(receipt.effective_gas_price, Wei::from(0));
+        assert!(receipt.revert_reason.is_none());
+
+        let written = serde_json::to_value(&receipt).unwrap();
+        assert!(written.get("revertReason").is_none());
+        assert!(written.get("gasProfile").is_none());
+    }
+
+    #[test]
+    fn tells_block_hashes_from_full_transactions() {
+        let hashes: BlockTransactions = serde_json::from_str(r#"["0x01", "0x02"]"#).unwrap();
+        assert!(matches!(hashes, BlockTransactions::Hashes(ref hashes) if hashes.len() == 2));
+        let full: BlockTransactions = serde_json::from_str(
+            r#"[{
+                "hash": "0x01",
+                "nonce": "0x0",
+                "blockHash": null,
+                "blockNumber": null,
+                "transactionIndex": null,
+                "from": "0x03",
+                "to": null,
+                "value": "0x0",
+                "gasPrice": "0x0",
+                "gas": "0x5208",
+                "input": "0x"
+            }]"#,
+        )
+        .unwrap();
+        match full {
+            BlockTransactions::Full(txns) => assert_eq!(txns[0].txn_type, Quantity(0)),
+            hashes => panic!("Read {:?}", hashes),
+        }
+    }
+
+    #[test]
+    fn looks_up_error_codes_by_code_and_name() {
+        for &error in ErrorCode::ALL.iter() {
+            assert_eq!(ErrorCode::from_code(u64::from(error.code())), Some(error));
+            assert_eq!(ErrorCode::from_name(error.name()), Some(error));
+            assert_eq!(
+                serde_json::to_string(&error).unwrap(),
+                error.code().to_string()
+            );
+        }
+        let mut codes: Vec<u8> = ErrorCode::ALL.iter().map(|error| error.code()).collect();
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), ErrorCode::ALL.len());
+
+        assert_eq!(ErrorCode::from_code(0), None);
+        assert_eq!(ErrorCode::from_name("InvalidTransaction"), None);
+    }
+}